@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::AiProviderConfig;
+
+const SYSTEM_PROMPT: &str =
+    "You are a SQL Server performance expert helping a developer who is new \
+to execution plans. You get a summary of a plan with literal values replaced by '?'. Explain the \
+main cost drivers in plain language and give concrete, prioritized tuning suggestions (indexes, \
+query rewrites, statistics). Be concise and do not invent objects that are not in the plan.";
+
+/// Send `plan_summary` to the chat completions endpoint and return the reply
+pub async fn request_suggestions(
+    config: &AiProviderConfig,
+    api_key: Option<&str>,
+    plan_summary: &str,
+) -> Result<String, String> {
+    let url = format!("{}/chat/completions", config.endpoint.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs.clamp(5, 600)))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+    let mut request = client.post(&url).json(&json!({
+        "model": config.model,
+        "temperature": 0.2,
+        "messages": [
+            { "role": "system", "content": SYSTEM_PROMPT },
+            { "role": "user", "content": plan_summary },
+        ],
+    }));
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!(
+                "The AI endpoint did not answer within {} seconds",
+                config.timeout_secs
+            )
+        } else {
+            format!("Failed to reach the AI endpoint: {}", e)
+        }
+    })?;
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response from the AI endpoint: {}", e))?;
+    if !status.is_success() {
+        let message = body["error"]["message"].as_str().unwrap_or("no details");
+        return Err(format!("AI endpoint returned {}: {}", status, message));
+    }
+    body["choices"][0]["message"]["content"]
+        .as_str()
+        .map(|s| s.trim().to_string())
+        .ok_or_else(|| "The AI endpoint returned no answer".to_string())
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use super::{client, redact, AiProviderInfo};
+use crate::plan::parser;
+use crate::settings;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiExplanation {
+    pub model: String,
+    pub suggestions: String,
+    /// Exactly what was sent, so users can see nothing sensitive left the machine
+    pub sent_summary: String,
+}
+
+#[tauri::command]
+pub async fn get_ai_provider(app: tauri::AppHandle) -> Result<AiProviderInfo, String> {
+    Ok(super::load(&app)?.info())
+}
+
+/// `api_key`: None keeps the stored key, an empty string removes it
+#[tauri::command]
+pub async fn update_ai_provider(
+    endpoint: String,
+    model: String,
+    timeout_secs: Option<u64>,
+    api_key: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<AiProviderInfo, String> {
+    let endpoint = endpoint.trim();
+    if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+        return Err("The endpoint must be an http(s) URL".to_string());
+    }
+    if model.trim().is_empty() {
+        return Err("Model name is required".to_string());
+    }
+
+    let mut config = super::load(&app)?;
+    config.endpoint = endpoint.to_string();
+    config.model = model.trim().to_string();
+    if let Some(secs) = timeout_secs {
+        config.timeout_secs = secs;
+    }
+    config.set_api_key(api_key.as_deref())?;
+    super::save(&app, &config)?;
+    Ok(config.info())
+}
+
+/// Tuning suggestions for a plan from the configured endpoint. Only runs when
+/// AI explanations are switched on in the settings.
+#[tauri::command]
+pub async fn explain_plan_ai(
+    plan_xml: String,
+    app: tauri::AppHandle,
+) -> Result<AiExplanation, String> {
+    if !settings::load(&app)?.ai_explanations_enabled {
+        return Err("AI explanations are turned off. Enable them in Settings to send plan summaries to the configured endpoint.".to_string());
+    }
+    let config = super::load(&app)?;
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    let sent_summary = redact::plan_summary(&plan);
+
+    let suggestions =
+        client::request_suggestions(&config, config.api_key()?.as_deref(), &sent_summary).await?;
+    Ok(AiExplanation {
+        model: config.model,
+        suggestions,
+        sent_summary,
+    })
+}
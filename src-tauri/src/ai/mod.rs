@@ -0,0 +1,94 @@
+pub mod client;
+pub mod commands;
+pub mod redact;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::db::encryption;
+
+const AI_STORE: &str = "settings.json";
+
+const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// OpenAI-compatible chat completions endpoint used by explain_plan_ai. The
+/// API key is kept encrypted with the machine key, like saved passwords.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AiProviderConfig {
+    /// Base URL; `/chat/completions` is appended
+    pub endpoint: String,
+    pub model: String,
+    pub timeout_secs: u64,
+    pub encrypted_api_key: Option<String>,
+}
+
+impl Default for AiProviderConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            encrypted_api_key: None,
+        }
+    }
+}
+
+/// What the frontend sees: never the key itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiProviderInfo {
+    pub endpoint: String,
+    pub model: String,
+    pub timeout_secs: u64,
+    pub has_api_key: bool,
+}
+
+impl AiProviderConfig {
+    pub fn info(&self) -> AiProviderInfo {
+        AiProviderInfo {
+            endpoint: self.endpoint.clone(),
+            model: self.model.clone(),
+            timeout_secs: self.timeout_secs,
+            has_api_key: self.encrypted_api_key.is_some(),
+        }
+    }
+
+    pub fn api_key(&self) -> Result<Option<String>, String> {
+        self.encrypted_api_key
+            .as_deref()
+            .map(encryption::decrypt_password)
+            .transpose()
+    }
+
+    /// None keeps the current key, an empty string removes it
+    pub fn set_api_key(&mut self, api_key: Option<&str>) -> Result<(), String> {
+        match api_key.map(str::trim) {
+            None => {}
+            Some("") => self.encrypted_api_key = None,
+            Some(key) => self.encrypted_api_key = Some(encryption::encrypt_password(key)?),
+        }
+        Ok(())
+    }
+}
+
+pub fn load(app: &AppHandle) -> Result<AiProviderConfig, String> {
+    let store = app.store(AI_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get("aiProvider")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default())
+}
+
+pub fn save(app: &AppHandle, config: &AiProviderConfig) -> Result<(), String> {
+    let store = app.store(AI_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "aiProvider",
+        serde_json::to_value(config).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
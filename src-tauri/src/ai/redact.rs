@@ -0,0 +1,124 @@
+use crate::db::redact::Redactor;
+use crate::db::types::RedactOptions;
+use crate::plan::explain;
+use crate::plan::summary::object_display_name;
+use crate::plan::types::{ParsedPlan, PlanNode};
+
+/// Operators listed per statement; deeper plans are cut off
+const MAX_OPERATORS: usize = 60;
+
+/// Text summary of a plan safe to send to a third party: literal values in
+/// statement text and predicates are replaced so they never leave the
+/// machine. Object and column names are kept, since tuning advice needs them.
+pub fn plan_summary(plan: &ParsedPlan) -> String {
+    let explanation = explain::explain(plan);
+    let mut redactor = Redactor::new(RedactOptions::default());
+    let mut out = String::new();
+    for (stmt, explained) in plan.statements.iter().zip(&explanation.statements) {
+        out.push_str(&format!(
+            "Statement {} (estimated cost {:.4}{}):\n{}\n",
+            stmt.statement_id,
+            stmt.statement_sub_tree_cost,
+            stmt.degree_of_parallelism
+                .map(|dop| format!(", DOP {}", dop))
+                .unwrap_or_default(),
+            redactor.sql(&stmt.statement_text)
+        ));
+        if let Some(root) = &stmt.root {
+            out.push_str("Operators:\n");
+            let mut count = 0;
+            operator_lines(root, 1, &mut count, &mut redactor, &mut out);
+            if count > MAX_OPERATORS {
+                out.push_str(&format!("  ... {} more operators\n", count - MAX_OPERATORS));
+            }
+        }
+        if !explained.notes.is_empty() {
+            out.push_str("Notes:\n");
+            for note in &explained.notes {
+                out.push_str(&format!("  - {}\n", note));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn operator_lines(
+    node: &PlanNode,
+    depth: usize,
+    count: &mut usize,
+    redactor: &mut Redactor,
+    out: &mut String,
+) {
+    *count += 1;
+    if *count <= MAX_OPERATORS {
+        let mut line = format!(
+            "{}{} [{}]",
+            "  ".repeat(depth),
+            node.physical_op,
+            node.logical_op
+        );
+        if let Some(object) = object_display_name(node) {
+            line.push_str(&format!(" on {}", object));
+        }
+        line.push_str(&format!(" est. rows {:.0}", node.estimate_rows));
+        if let Some(rt) = &node.runtime {
+            line.push_str(&format!(", actual rows {:.0}", rt.actual_rows));
+        }
+        line.push_str(&format!(
+            ", subtree cost {:.4}",
+            node.estimated_total_subtree_cost
+        ));
+        if !node.seek_predicates.is_empty() {
+            let seeks: Vec<String> = node
+                .seek_predicates
+                .iter()
+                .map(|p| redactor.sql(p))
+                .collect();
+            line.push_str(&format!(", seek {}", seeks.join(" and ")));
+        }
+        if let Some(predicate) = &node.predicate {
+            line.push_str(&format!(", predicate {}", redactor.sql(predicate)));
+        }
+        if !node.warnings.is_empty() {
+            line.push_str(&format!(", warnings: {}", node.warnings.join(", ")));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    for child in &node.children {
+        operator_lines(child, depth + 1, count, redactor, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    #[test]
+    fn test_summary_has_no_literals() {
+        let plan = parse_plan(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="SELECT * FROM dbo.Customers WHERE Email = 'jane@example.com' AND Id = 42" StatementSubTreeCost="0.5">
+                <QueryPlan>
+                  <RelOp NodeId="0" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="1" EstimatedTotalSubtreeCost="0.5">
+                    <IndexScan>
+                      <Object Schema="[dbo]" Table="[Customers]" Index="[PK_Customers]" />
+                      <Predicate><ScalarOperator ScalarString="[Email]=N'jane@example.com'" /></Predicate>
+                    </IndexScan>
+                  </RelOp>
+                </QueryPlan>
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#,
+        )
+        .unwrap();
+        let summary = plan_summary(&plan);
+        assert!(!summary.contains("jane"));
+        assert!(!summary.contains("42"));
+        assert!(summary.contains("SELECT * FROM dbo.Customers WHERE Email = ?1 AND Id = ?2"));
+        assert!(summary
+            .contains("Clustered Index Scan [Clustered Index Scan] on dbo.Customers.PK_Customers"));
+        assert!(summary.contains("predicate [Email]=?1"));
+    }
+}
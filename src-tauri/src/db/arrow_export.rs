@@ -0,0 +1,525 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Array, Float32Builder,
+    Float64Builder, Int16Builder, Int32Builder, Int64Builder, StringArray, StringBuilder,
+    Time64MicrosecondBuilder, TimestampMicrosecondBuilder, UInt8Builder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use chrono::{NaiveDate, Timelike};
+use futures_util::{Stream, TryStreamExt};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use tiberius::numeric::Numeric;
+use tiberius::{Column, ColumnType, FromSql, QueryItem, Row};
+
+use super::types::ExportFormat;
+
+// Query results as Parquet or Arrow IPC files for notebooks. Rows are read
+// from the server and written in batches, so a large result is never held in
+// memory. Columns keep their SQL Server types where Arrow has one; the wire
+// metadata carries no decimal scale, so decimal columns take it from their
+// first value.
+
+/// Rows per record batch (and progress event)
+pub const BATCH_ROWS: usize = 8192;
+
+/// Precision of every decimal column; SQL Server's maximum
+const DECIMAL_PRECISION: u8 = 38;
+
+/// Arrow timezone of datetimeoffset columns, which are stored as UTC
+const UTC: &str = "+00:00";
+
+/// What was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSummary {
+    pub rows: u64,
+    pub columns: usize,
+    /// Result sets after the first, which are not exported
+    pub skipped_result_sets: usize,
+}
+
+/// Decimal values until the column's scale is known
+#[derive(Default)]
+struct DecimalColumn {
+    values: Vec<Option<Numeric>>,
+    /// Fixed by the first batch; Some(None) writes the column as text
+    scale: Option<Option<u8>>,
+}
+
+impl DecimalColumn {
+    /// Fix the scale from the first value; a column with no value in the
+    /// first batch is written as text, which loses nothing
+    fn settle(&mut self) {
+        if self.scale.is_none() {
+            self.scale = Some(self.values.iter().flatten().map(|v| v.scale()).next());
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self.scale {
+            Some(Some(scale)) => DataType::Decimal128(DECIMAL_PRECISION, scale as i8),
+            _ => DataType::Utf8,
+        }
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef, String> {
+        let values = std::mem::take(&mut self.values);
+        Ok(match self.scale {
+            Some(Some(scale)) => Arc::new(
+                values
+                    .iter()
+                    .map(|v| v.map(|v| rescale(v.value(), v.scale(), scale)))
+                    .collect::<Decimal128Array>()
+                    .with_precision_and_scale(DECIMAL_PRECISION, scale as i8)
+                    .map_err(|e| e.to_string())?,
+            ),
+            _ => Arc::new(
+                values
+                    .iter()
+                    .map(|v| v.map(|v| v.to_string()))
+                    .collect::<StringArray>(),
+            ),
+        })
+    }
+}
+
+/// Unscaled decimal value at another scale
+fn rescale(value: i128, from: u8, to: u8) -> i128 {
+    if to >= from {
+        value * 10i128.pow((to - from) as u32)
+    } else {
+        value / 10i128.pow((from - to) as u32)
+    }
+}
+
+/// One column of the batch being filled
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    UInt8(UInt8Builder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Decimal(DecimalColumn),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+    Date(Date32Builder),
+    Time(Time64MicrosecondBuilder),
+    Timestamp(TimestampMicrosecondBuilder),
+    TimestampUtc(TimestampMicrosecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(column_type: ColumnType) -> Self {
+        match column_type {
+            ColumnType::Bit | ColumnType::Bitn => Self::Boolean(BooleanBuilder::new()),
+            ColumnType::Int1 => Self::UInt8(UInt8Builder::new()),
+            ColumnType::Int2 => Self::Int16(Int16Builder::new()),
+            ColumnType::Int4 => Self::Int32(Int32Builder::new()),
+            // intn carries no width; bigint holds all of them
+            ColumnType::Int8 | ColumnType::Intn => Self::Int64(Int64Builder::new()),
+            ColumnType::Float4 => Self::Float32(Float32Builder::new()),
+            ColumnType::Float8 | ColumnType::Floatn | ColumnType::Money | ColumnType::Money4 => {
+                Self::Float64(Float64Builder::new())
+            }
+            ColumnType::Decimaln | ColumnType::Numericn => Self::Decimal(DecimalColumn::default()),
+            ColumnType::BigVarBin | ColumnType::BigBinary | ColumnType::Image => {
+                Self::Binary(BinaryBuilder::new())
+            }
+            ColumnType::Daten => Self::Date(Date32Builder::new()),
+            ColumnType::Timen => Self::Time(Time64MicrosecondBuilder::new()),
+            ColumnType::Datetime4
+            | ColumnType::Datetime
+            | ColumnType::Datetimen
+            | ColumnType::Datetime2 => Self::Timestamp(TimestampMicrosecondBuilder::new()),
+            ColumnType::DatetimeOffsetn => {
+                Self::TimestampUtc(TimestampMicrosecondBuilder::new().with_timezone(UTC))
+            }
+            // Strings, GUIDs and XML; types the client cannot read are left NULL
+            _ => Self::Utf8(StringBuilder::new()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Boolean(_) => DataType::Boolean,
+            Self::UInt8(_) => DataType::UInt8,
+            Self::Int16(_) => DataType::Int16,
+            Self::Int32(_) => DataType::Int32,
+            Self::Int64(_) => DataType::Int64,
+            Self::Float32(_) => DataType::Float32,
+            Self::Float64(_) => DataType::Float64,
+            Self::Decimal(column) => column.data_type(),
+            Self::Utf8(_) => DataType::Utf8,
+            Self::Binary(_) => DataType::Binary,
+            Self::Date(_) => DataType::Date32,
+            Self::Time(_) => DataType::Time64(TimeUnit::Microsecond),
+            Self::Timestamp(_) => DataType::Timestamp(TimeUnit::Microsecond, None),
+            Self::TimestampUtc(_) => DataType::Timestamp(TimeUnit::Microsecond, Some(UTC.into())),
+        }
+    }
+
+    /// Append column `i` of the row; NULL and unreadable values are appended as null
+    fn append(&mut self, row: &Row, i: usize) {
+        match self {
+            Self::Boolean(b) => b.append_option(get::<bool>(row, i)),
+            Self::UInt8(b) => b.append_option(get::<u8>(row, i)),
+            Self::Int16(b) => b.append_option(get::<i16>(row, i)),
+            Self::Int32(b) => b.append_option(get::<i32>(row, i)),
+            Self::Int64(b) => b.append_option(
+                get::<i64>(row, i)
+                    .or_else(|| get::<i32>(row, i).map(i64::from))
+                    .or_else(|| get::<i16>(row, i).map(i64::from))
+                    .or_else(|| get::<u8>(row, i).map(i64::from)),
+            ),
+            Self::Float32(b) => b.append_option(get::<f32>(row, i)),
+            Self::Float64(b) => {
+                b.append_option(get::<f64>(row, i).or_else(|| get::<f32>(row, i).map(f64::from)))
+            }
+            Self::Decimal(column) => column.values.push(get::<Numeric>(row, i)),
+            Self::Utf8(b) => b.append_option(
+                get::<&str>(row, i)
+                    .map(str::to_string)
+                    .or_else(|| get::<uuid::Uuid>(row, i).map(|v| v.to_string()))
+                    .or_else(|| get::<&tiberius::xml::XmlData>(row, i).map(|v| v.to_string())),
+            ),
+            Self::Binary(b) => b.append_option(get::<&[u8]>(row, i)),
+            Self::Date(b) => b.append_option(get::<NaiveDate>(row, i).map(date_days)),
+            Self::Time(b) => b.append_option(get::<chrono::NaiveTime>(row, i).map(|v| {
+                v.num_seconds_from_midnight() as i64 * 1_000_000 + v.nanosecond() as i64 / 1_000
+            })),
+            Self::Timestamp(b) => b.append_option(
+                get::<chrono::NaiveDateTime>(row, i).map(|v| v.and_utc().timestamp_micros()),
+            ),
+            Self::TimestampUtc(b) => b.append_option(
+                get::<chrono::DateTime<chrono::FixedOffset>>(row, i).map(|v| v.timestamp_micros()),
+            ),
+        }
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef, String> {
+        Ok(match self {
+            Self::Boolean(b) => Arc::new(b.finish()),
+            Self::UInt8(b) => Arc::new(b.finish()),
+            Self::Int16(b) => Arc::new(b.finish()),
+            Self::Int32(b) => Arc::new(b.finish()),
+            Self::Int64(b) => Arc::new(b.finish()),
+            Self::Float32(b) => Arc::new(b.finish()),
+            Self::Float64(b) => Arc::new(b.finish()),
+            Self::Decimal(column) => column.finish()?,
+            Self::Utf8(b) => Arc::new(b.finish()),
+            Self::Binary(b) => Arc::new(b.finish()),
+            Self::Date(b) => Arc::new(b.finish()),
+            Self::Time(b) => Arc::new(b.finish()),
+            Self::Timestamp(b) => Arc::new(b.finish()),
+            Self::TimestampUtc(b) => Arc::new(b.finish()),
+        })
+    }
+}
+
+/// Column `i` as `T`; None for NULL and for a value of another type
+fn get<'a, T: FromSql<'a>>(row: &'a Row, i: usize) -> Option<T> {
+    row.try_get::<T, _>(i).ok().flatten()
+}
+
+/// Days since 1970-01-01, Arrow's Date32
+fn date_days(date: NaiveDate) -> i32 {
+    (date - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() as i32
+}
+
+enum BatchWriter {
+    Parquet(ArrowWriter<File>),
+    Ipc(FileWriter<File>),
+}
+
+impl BatchWriter {
+    fn create(path: &Path, format: ExportFormat, schema: SchemaRef) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+        Ok(match format {
+            ExportFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                Self::Parquet(
+                    ArrowWriter::try_new(file, schema, Some(properties))
+                        .map_err(|e| e.to_string())?,
+                )
+            }
+            ExportFormat::ArrowIpc => {
+                Self::Ipc(FileWriter::try_new(file, &schema).map_err(|e| e.to_string())?)
+            }
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), String> {
+        match self {
+            Self::Parquet(writer) => writer.write(batch).map_err(|e| e.to_string()),
+            Self::Ipc(writer) => writer.write(batch).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn close(self) -> Result<(), String> {
+        match self {
+            Self::Parquet(writer) => writer.close().map(|_| ()).map_err(|e| e.to_string()),
+            Self::Ipc(mut writer) => writer.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// The file being written: columns of the first result set and the batch
+/// being filled. The file is created with the first batch, once decimal
+/// scales are known.
+struct Export<'a> {
+    path: &'a Path,
+    format: ExportFormat,
+    names: Vec<String>,
+    builders: Vec<ColumnBuilder>,
+    pending_rows: usize,
+    writer: Option<(BatchWriter, SchemaRef)>,
+}
+
+impl<'a> Export<'a> {
+    fn new(path: &'a Path, format: ExportFormat, columns: &[Column]) -> Self {
+        Self {
+            path,
+            format,
+            names: column_names(columns),
+            builders: columns
+                .iter()
+                .map(|c| ColumnBuilder::new(c.column_type()))
+                .collect(),
+            pending_rows: 0,
+            writer: None,
+        }
+    }
+
+    fn append(&mut self, row: &Row) {
+        for (i, builder) in self.builders.iter_mut().enumerate() {
+            builder.append(row, i);
+        }
+        self.pending_rows += 1;
+    }
+
+    /// Write the filled batch; the first one also fixes the schema and
+    /// creates the file
+    fn flush(&mut self) -> Result<(), String> {
+        if self.writer.is_none() {
+            for builder in &mut self.builders {
+                if let ColumnBuilder::Decimal(column) = builder {
+                    column.settle();
+                }
+            }
+            let schema = Arc::new(Schema::new(
+                self.names
+                    .iter()
+                    .zip(&self.builders)
+                    .map(|(name, builder)| Field::new(name, builder.data_type(), true))
+                    .collect::<Vec<_>>(),
+            ));
+            let writer = BatchWriter::create(self.path, self.format, schema.clone())?;
+            self.writer = Some((writer, schema));
+        } else if self.pending_rows == 0 {
+            return Ok(());
+        }
+        let Some((writer, schema)) = &mut self.writer else {
+            unreachable!("writer is created above");
+        };
+        let arrays = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(|e| e.to_string())?;
+        self.pending_rows = 0;
+        writer.write(&batch)
+    }
+
+    fn close(mut self) -> Result<(), String> {
+        self.flush()?;
+        match self.writer {
+            Some((writer, _)) => writer.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Column names for the schema, which needs them unique and non-empty:
+/// unnamed columns become "column<n>" and repeats get a numeric suffix
+fn column_names(columns: &[Column]) -> Vec<String> {
+    unique_names(columns.iter().map(|c| c.name()))
+}
+
+fn unique_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for (i, name) in names.enumerate() {
+        let base = if name.is_empty() {
+            format!("column{}", i + 1)
+        } else {
+            name.to_string()
+        };
+        let mut candidate = base.clone();
+        let mut suffix = 2;
+        while unique.iter().any(|n| n.eq_ignore_ascii_case(&candidate)) {
+            candidate = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        unique.push(candidate);
+    }
+    unique
+}
+
+/// Write the first result set of the stream to `path`; `on_progress` gets
+/// the rows written so far after each batch. A partly written file is
+/// removed when the query or the write fails.
+pub async fn write<S>(
+    stream: S,
+    path: &Path,
+    format: ExportFormat,
+    on_progress: impl Fn(u64),
+) -> Result<ExportSummary, String>
+where
+    S: Stream<Item = tiberius::Result<QueryItem>> + Unpin,
+{
+    let outcome = write_stream(stream, path, format, on_progress).await;
+    if outcome.is_err() && path.exists() {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!(error = %e, path = %path.display(), "Failed to remove a partial export");
+        }
+    }
+    outcome
+}
+
+async fn write_stream<S>(
+    mut stream: S,
+    path: &Path,
+    format: ExportFormat,
+    on_progress: impl Fn(u64),
+) -> Result<ExportSummary, String>
+where
+    S: Stream<Item = tiberius::Result<QueryItem>> + Unpin,
+{
+    let mut export: Option<Export> = None;
+    let mut result_sets: usize = 0;
+    let mut rows: u64 = 0;
+    // Later result sets are read to the end so the session stays usable
+    while let Some(item) = stream
+        .try_next()
+        .await
+        .map_err(|e| format!("Query failed: {}", e))?
+    {
+        match item {
+            QueryItem::Metadata(meta) => {
+                result_sets += 1;
+                if export.is_none() {
+                    export = Some(Export::new(path, format, meta.columns()));
+                }
+            }
+            QueryItem::Row(row) if result_sets == 1 => {
+                let Some(export) = export.as_mut() else {
+                    continue;
+                };
+                export.append(&row);
+                rows += 1;
+                if export.pending_rows == BATCH_ROWS {
+                    export.flush()?;
+                    on_progress(rows);
+                }
+            }
+            QueryItem::Row(_) => {}
+        }
+    }
+
+    let export = export.ok_or("The query returned no result set to export")?;
+    let columns = export.names.len();
+    export.close()?;
+    on_progress(rows);
+    Ok(ExportSummary {
+        rows,
+        columns,
+        skipped_result_sets: result_sets.saturating_sub(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_types() {
+        let types = [
+            ColumnType::Bit,
+            ColumnType::Int1,
+            ColumnType::Intn,
+            ColumnType::Money,
+            ColumnType::Decimaln,
+            ColumnType::NVarchar,
+            ColumnType::Guid,
+            ColumnType::BigVarBin,
+            ColumnType::Daten,
+            ColumnType::Datetime2,
+            ColumnType::DatetimeOffsetn,
+        ]
+        .map(|t| ColumnBuilder::new(t).data_type());
+        assert_eq!(
+            types,
+            [
+                DataType::Boolean,
+                DataType::UInt8,
+                DataType::Int64,
+                DataType::Float64,
+                // No value seen yet
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Utf8,
+                DataType::Binary,
+                DataType::Date32,
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                DataType::Timestamp(TimeUnit::Microsecond, Some(UTC.into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decimal_scale_from_first_value() {
+        let mut column = DecimalColumn {
+            values: vec![None, Some(Numeric::new_with_scale(12345, 2))],
+            ..Default::default()
+        };
+        column.settle();
+        assert_eq!(column.data_type(), DataType::Decimal128(38, 2));
+        column.finish().unwrap();
+
+        // Later batches keep the scale
+        column.values = vec![Some(Numeric::new_with_scale(5, 0))];
+        column.settle();
+        let array = column.finish().unwrap();
+        let array = array.as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(array.value(0), 500);
+
+        let mut empty = DecimalColumn {
+            values: vec![None],
+            ..Default::default()
+        };
+        empty.settle();
+        assert_eq!(empty.data_type(), DataType::Utf8);
+    }
+
+    #[test]
+    fn test_unique_names() {
+        assert_eq!(
+            unique_names(["id", "", "Id", "id"].into_iter()),
+            vec!["id", "column2", "Id_2", "id_3"]
+        );
+        assert_eq!(date_days(NaiveDate::from_ymd_opt(1970, 1, 2).unwrap()), 1);
+        assert_eq!(rescale(12345, 2, 4), 1234500);
+        assert_eq!(rescale(12345, 2, 0), 123);
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use super::connection::DbConnection;
+use super::types::{AuditAction, AuditEntry, AuditLogPage};
+
+/// One JSON entry per line, only ever appended to. Kept apart from the query
+/// history, which the user can edit and which is trimmed.
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// `previous_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What happened, before it is stamped and chained
+pub struct AuditEvent<'a> {
+    pub action: AuditAction,
+    pub statement: Option<&'a str>,
+    pub error: Option<&'a str>,
+    pub duration_ms: Option<u64>,
+}
+
+impl AuditEvent<'_> {
+    /// Connect or disconnect, which carry no statement
+    pub fn session(action: AuditAction) -> Self {
+        Self {
+            action,
+            statement: None,
+            error: None,
+            duration_ms: None,
+        }
+    }
+}
+
+/// Sequence and hash of the last entry written, so appending does not have
+/// to read the file back
+#[derive(Default)]
+pub struct AuditLog {
+    head: Mutex<Option<(u64, String)>>,
+}
+
+impl AuditLog {
+    /// Append an entry for `event` on `conn`, chained to the previous one
+    pub fn record(
+        &self,
+        app: &AppHandle,
+        conn: &DbConnection,
+        event: AuditEvent,
+    ) -> Result<(), String> {
+        let path = audit_path(app)?;
+        let mut head = self.head.lock().unwrap();
+        if head.is_none() {
+            *head = Some(
+                read_entries(&path)?
+                    .0
+                    .last()
+                    .map(|last| (last.sequence, last.hash.clone()))
+                    .unwrap_or((0, GENESIS_HASH.to_string())),
+            );
+        }
+        let (sequence, previous_hash) = head.as_ref().unwrap();
+
+        let mut entry = AuditEntry {
+            sequence: sequence + 1,
+            timestamp: Utc::now(),
+            action: event.action,
+            server: conn.endpoint(),
+            database: conn.last_known_database(),
+            login: conn.login().to_string(),
+            os_user: Some(whoami::username()),
+            statement: event.statement.map(str::to_string),
+            error: event.error.map(str::to_string),
+            duration_ms: event.duration_ms,
+            previous_hash: previous_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry_hash(&entry);
+
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open the audit log: {}", e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write the audit log: {}", e))?;
+        *head = Some((entry.sequence, entry.hash));
+        Ok(())
+    }
+}
+
+fn audit_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create the data directory: {}", e))?;
+    Ok(dir.join(AUDIT_FILE))
+}
+
+/// SHA-256 over the entry as written, with the hash field empty
+fn entry_hash(entry: &AuditEntry) -> String {
+    let unsealed = AuditEntry {
+        hash: String::new(),
+        ..entry.clone()
+    };
+    let json = serde_json::to_vec(&unsealed).unwrap_or_default();
+    Sha256::digest(&json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Sequence of the first entry that was altered, removed or inserted, or
+/// None when the chain is intact
+pub fn verify(entries: &[AuditEntry]) -> Option<u64> {
+    let mut previous = (0, GENESIS_HASH);
+    for entry in entries {
+        if entry.sequence != previous.0 + 1
+            || entry.previous_hash != previous.1
+            || entry.hash != entry_hash(entry)
+        {
+            return Some(entry.sequence.min(previous.0 + 1));
+        }
+        previous = (entry.sequence, &entry.hash);
+    }
+    None
+}
+
+/// Entries up to the first line that is not a valid entry, and whether such
+/// a line was found. A missing file is an empty log.
+fn read_entries(path: &PathBuf) -> Result<(Vec<AuditEntry>, bool), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), false)),
+        Err(e) => return Err(format!("Failed to read the audit log: {}", e)),
+    };
+    let mut entries = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => return Ok((entries, true)),
+        }
+    }
+    Ok((entries, false))
+}
+
+/// The newest `limit` entries, newest first, with the state of the whole chain
+pub fn page(app: &AppHandle, limit: usize) -> Result<AuditLogPage, String> {
+    let (entries, corrupt_line) = read_entries(&audit_path(app)?)?;
+    let first_broken_sequence = verify(&entries)
+        .or_else(|| corrupt_line.then(|| entries.last().map_or(1, |e| e.sequence + 1)));
+    Ok(AuditLogPage {
+        total: entries.len(),
+        intact: first_broken_sequence.is_none(),
+        first_broken_sequence,
+        entries: entries.into_iter().rev().take(limit).collect(),
+    })
+}
+
+/// The log file as is, so the chain can be checked outside the app
+pub fn export(app: &AppHandle) -> Result<String, String> {
+    match fs::read_to_string(audit_path(app)?) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("Failed to read the audit log: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(statements: &[&str]) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for (i, statement) in statements.iter().enumerate() {
+            let mut entry = AuditEntry {
+                sequence: i as u64 + 1,
+                timestamp: Utc::now(),
+                action: AuditAction::Execute,
+                server: "sql01:1433".to_string(),
+                database: "Sales".to_string(),
+                login: "app".to_string(),
+                os_user: None,
+                statement: Some(statement.to_string()),
+                error: None,
+                duration_ms: Some(5),
+                previous_hash: entries
+                    .last()
+                    .map_or(GENESIS_HASH.to_string(), |e| e.hash.clone()),
+                hash: String::new(),
+            };
+            entry.hash = entry_hash(&entry);
+            entries.push(entry);
+        }
+        entries
+    }
+
+    #[test]
+    fn test_tampering_breaks_the_chain() {
+        let entries = chain(&["SELECT 1", "DELETE FROM dbo.Orders", "SELECT 2"]);
+        assert_eq!(verify(&entries), None);
+
+        let mut edited = entries.clone();
+        edited[1].statement = Some("SELECT 3".to_string());
+        assert_eq!(verify(&edited), Some(2));
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert_eq!(verify(&removed), Some(2));
+
+        // Rehashing the edited entry does not help: the next link breaks
+        edited[1].hash = entry_hash(&edited[1]);
+        assert_eq!(verify(&edited), Some(3));
+    }
+}
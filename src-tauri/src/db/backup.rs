@@ -0,0 +1,154 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::encryption::{decrypt_bytes, derive_passphrase_key, encrypt_bytes, random_bytes};
+use super::types::{
+    ConnectionConfig, ConnectionGroup, PlanHistoryEntry, QueryHistoryEntry, Snippet,
+};
+use crate::settings::AppSettings;
+
+// Backups move app data between machines, so they cannot use the
+// machine-derived key: the payload is encrypted with a key derived from a
+// user passphrase, and connection passwords travel decrypted inside it.
+
+const FORMAT: &str = "sqlplanfordummies-backup";
+const VERSION: u32 = 1;
+const MIN_PASSPHRASE_CHARS: usize = 8;
+
+/// The file as written to disk
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Archive {
+    format: String,
+    version: u32,
+    kdf: String,
+    salt: String,
+    data: String,
+}
+
+/// Everything that is exported, before encryption
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDataBackup {
+    pub exported_at: DateTime<Utc>,
+    pub connections: Vec<BackupConnection>,
+    pub groups: Vec<ConnectionGroup>,
+    pub query_history: Vec<QueryHistoryEntry>,
+    pub plan_history: Vec<PlanHistoryEntry>,
+    pub snippets: Vec<Snippet>,
+    pub settings: AppSettings,
+}
+
+/// A saved connection with its password in the clear; `encrypted_password`
+/// is left empty and re-encrypted for the importing machine
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupConnection {
+    #[serde(flatten)]
+    pub config: ConnectionConfig,
+    pub password: String,
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_CHARS
+        ));
+    }
+    Ok(())
+}
+
+/// Encrypt a backup into the archive text
+pub fn seal(backup: &AppDataBackup, passphrase: &str) -> Result<String, String> {
+    check_passphrase(passphrase)?;
+    let payload = serde_json::to_vec(backup).map_err(|e| e.to_string())?;
+    let salt = random_bytes::<16>();
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    let archive = Archive {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        kdf: "argon2id".to_string(),
+        salt: BASE64.encode(salt),
+        data: BASE64.encode(encrypt_bytes(&key, &payload)?),
+    };
+    serde_json::to_string_pretty(&archive).map_err(|e| e.to_string())
+}
+
+/// Decrypt archive text written by `seal`
+pub fn open(archive: &str, passphrase: &str) -> Result<AppDataBackup, String> {
+    let archive: Archive =
+        serde_json::from_str(archive).map_err(|_| "Not a SqlPlanForDummies backup file")?;
+    if archive.format != FORMAT {
+        return Err("Not a SqlPlanForDummies backup file".into());
+    }
+    if archive.version > VERSION {
+        return Err(format!(
+            "Backup was made by a newer version of the app (format {})",
+            archive.version
+        ));
+    }
+
+    let salt = BASE64.decode(&archive.salt).map_err(|e| e.to_string())?;
+    let data = BASE64.decode(&archive.data).map_err(|e| e.to_string())?;
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    // GCM authentication fails the same way for a wrong passphrase and a
+    // damaged file
+    let payload =
+        decrypt_bytes(&key, &data).map_err(|_| "Wrong passphrase or damaged backup file")?;
+    serde_json::from_slice(&payload).map_err(|e| format!("Backup contents are invalid: {}", e))
+}
+
+/// Add `incoming` to `existing`, replacing items with the same id.
+/// Returns how many items were imported.
+pub fn merge_by_id<T>(existing: &mut Vec<T>, incoming: Vec<T>, id: impl Fn(&T) -> &str) -> usize {
+    let count = incoming.len();
+    for item in incoming {
+        match existing.iter().position(|e| id(e) == id(&item)) {
+            Some(index) => existing[index] = item,
+            None => existing.push(item),
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup() -> AppDataBackup {
+        AppDataBackup {
+            exported_at: Utc::now(),
+            connections: Vec::new(),
+            groups: vec![ConnectionGroup {
+                id: "g1".into(),
+                name: "prod".into(),
+                created_at: Utc::now(),
+            }],
+            query_history: Vec::new(),
+            plan_history: Vec::new(),
+            snippets: Vec::new(),
+            settings: AppSettings::default(),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let archive = seal(&backup(), "correct horse").unwrap();
+        let restored = open(&archive, "correct horse").unwrap();
+        assert_eq!(restored.groups[0].name, "prod");
+
+        assert!(open(&archive, "wrong passphrase").is_err());
+        assert!(seal(&backup(), "short").is_err());
+        assert!(open("{}", "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_merge_by_id() {
+        let mut existing = vec![("a", 1), ("b", 2)];
+        let imported = merge_by_id(&mut existing, vec![("b", 3), ("c", 4)], |item| item.0);
+        assert_eq!(imported, 2);
+        assert_eq!(existing, vec![("a", 1), ("b", 3), ("c", 4)]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::query_hash;
+use super::types::{
+    AppError, Baseline, BaselineComparison, BaselineQuery, BaselineQueryStatus, BaselineSummary,
+    QueryBaselineComparison, QueryResult,
+};
+use crate::plan;
+
+// Workload baselines: while recording, every query the user runs is kept
+// with its plan and timing. A later session recorded the same way is
+// compared query by query, matching runs by literal-free fingerprint so
+// the same query with other values still lines up.
+
+/// Queries kept per recording; plans make each one large
+const MAX_RECORDED_QUERIES: usize = 5000;
+
+/// Mean duration change, in percent, that counts as slower or faster
+const DURATION_CHANGE_PERCENT: f64 = 25.0;
+
+/// Differences below this are timer noise
+const MIN_DURATION_DELTA_MS: f64 = 5.0;
+
+#[derive(Default)]
+pub struct BaselineRecorder {
+    recording: Mutex<Option<Baseline>>,
+}
+
+impl BaselineRecorder {
+    pub fn start(&self, name: &str) -> Result<BaselineSummary, String> {
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(current) = recording.as_ref() {
+            return Err(format!("Already recording \"{}\"", current.name));
+        }
+        let baseline = Baseline {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            started_at: Utc::now(),
+            ended_at: None,
+            queries: Vec::new(),
+            truncated: false,
+        };
+        let summary = summarize(&baseline);
+        *recording = Some(baseline);
+        Ok(summary)
+    }
+
+    /// Keep an execution if a recording is running
+    pub fn record(&self, sql: &str, result: &Result<QueryResult, AppError>, duration_ms: u64) {
+        let mut recording = self.recording.lock().unwrap();
+        let Some(baseline) = recording.as_mut() else {
+            return;
+        };
+        if baseline.queries.len() >= MAX_RECORDED_QUERIES {
+            baseline.truncated = true;
+            return;
+        }
+        let plan_xml = result.as_ref().ok().and_then(|r| r.plan_xml.clone());
+        baseline.queries.push(BaselineQuery {
+            fingerprint: query_hash::fingerprint(sql),
+            sql: sql.to_string(),
+            executed_at: Utc::now(),
+            duration_ms: result.as_ref().map_or(duration_ms, |r| r.duration_ms),
+            error: result.as_ref().err().map(|e| e.message().to_string()),
+            plan_hash: plan_xml.as_deref().map(query_hash::plan_hash),
+            plan_xml,
+        });
+    }
+
+    pub fn stop(&self) -> Option<Baseline> {
+        let mut baseline = self.recording.lock().unwrap().take()?;
+        baseline.ended_at = Some(Utc::now());
+        Some(baseline)
+    }
+
+    pub fn status(&self) -> Option<BaselineSummary> {
+        self.recording.lock().unwrap().as_ref().map(summarize)
+    }
+}
+
+pub fn summarize(baseline: &Baseline) -> BaselineSummary {
+    BaselineSummary {
+        id: baseline.id.clone(),
+        name: baseline.name.clone(),
+        started_at: baseline.started_at,
+        ended_at: baseline.ended_at,
+        query_count: baseline.queries.len(),
+        distinct_queries: baseline
+            .queries
+            .iter()
+            .map(|q| q.fingerprint.as_str())
+            .collect::<HashSet<_>>()
+            .len(),
+        truncated: baseline.truncated,
+    }
+}
+
+/// Runs of each fingerprint, in recording order
+fn by_fingerprint(baseline: &Baseline) -> BTreeMap<&str, Vec<&BaselineQuery>> {
+    let mut groups: BTreeMap<&str, Vec<&BaselineQuery>> = BTreeMap::new();
+    for query in &baseline.queries {
+        groups.entry(&query.fingerprint).or_default().push(query);
+    }
+    groups
+}
+
+fn average_duration(runs: &[&BaselineQuery]) -> Option<f64> {
+    let durations: Vec<f64> = runs
+        .iter()
+        .filter(|q| q.error.is_none())
+        .map(|q| q.duration_ms as f64)
+        .collect();
+    (!durations.is_empty()).then(|| durations.iter().sum::<f64>() / durations.len() as f64)
+}
+
+/// Latest captured plan
+fn latest_plan<'a>(runs: &[&'a BaselineQuery]) -> Option<&'a BaselineQuery> {
+    runs.iter().rev().find(|q| q.plan_xml.is_some()).copied()
+}
+
+/// Compare `session` with `baseline` query by query
+pub fn compare(baseline: &Baseline, session: &Baseline) -> BaselineComparison {
+    let before = by_fingerprint(baseline);
+    let after = by_fingerprint(session);
+    let fingerprints: Vec<&str> = before
+        .keys()
+        .chain(after.keys().filter(|f| !before.contains_key(*f)))
+        .copied()
+        .collect();
+
+    let mut queries: Vec<QueryBaselineComparison> = fingerprints
+        .into_iter()
+        .map(|fingerprint| {
+            let runs_before = before.get(fingerprint).map_or(&[][..], Vec::as_slice);
+            let runs_after = after.get(fingerprint).map_or(&[][..], Vec::as_slice);
+            compare_query(&baseline.id, fingerprint, runs_before, runs_after)
+        })
+        .collect();
+
+    queries.sort_by(|a, b| {
+        let rank = |q: &QueryBaselineComparison| q.status != BaselineQueryStatus::Regressed;
+        rank(a).cmp(&rank(b)).then(
+            b.duration_change_percent
+                .unwrap_or(0.0)
+                .total_cmp(&a.duration_change_percent.unwrap_or(0.0)),
+        )
+    });
+    let count = |status| queries.iter().filter(|q| q.status == status).count();
+    BaselineComparison {
+        baseline: summarize(baseline),
+        session: summarize(session),
+        regressed: count(BaselineQueryStatus::Regressed),
+        improved: count(BaselineQueryStatus::Improved),
+        queries,
+    }
+}
+
+fn compare_query(
+    baseline_id: &str,
+    fingerprint: &str,
+    runs_before: &[&BaselineQuery],
+    runs_after: &[&BaselineQuery],
+) -> QueryBaselineComparison {
+    let errors = |runs: &[&BaselineQuery]| runs.iter().filter(|q| q.error.is_some()).count();
+    let avg_before = average_duration(runs_before);
+    let avg_after = average_duration(runs_after);
+    let duration_change_percent = match (avg_before, avg_after) {
+        (Some(before), Some(after)) if before > 0.0 => Some((after - before) / before * 100.0),
+        _ => None,
+    };
+
+    let plan_before = latest_plan(runs_before);
+    let plan_after = latest_plan(runs_after);
+    let plan_changed = match (plan_before, plan_after) {
+        (Some(before), Some(after)) => before.plan_hash != after.plan_hash,
+        _ => false,
+    };
+    let parsed = plan_changed
+        .then(|| {
+            let before = plan::parser::parse_plan(plan_before?.plan_xml.as_deref()?).ok()?;
+            let after = plan::parser::parse_plan(plan_after?.plan_xml.as_deref()?).ok()?;
+            Some((before, after))
+        })
+        .flatten();
+    let plan_diff = parsed
+        .as_ref()
+        .map(|(before, after)| plan::compare::compare(before, after));
+    let plan_regression = parsed
+        .as_ref()
+        .and_then(|(before, after)| plan::regression::detect(baseline_id, before, after));
+
+    let (errors_before, errors_after) = (errors(runs_before), errors(runs_after));
+    let delta_ms = avg_after.unwrap_or(0.0) - avg_before.unwrap_or(0.0);
+    let status = if runs_after.is_empty() {
+        BaselineQueryStatus::Missing
+    } else if runs_before.is_empty() {
+        BaselineQueryStatus::New
+    } else if (errors_after > 0 && errors_before == 0)
+        || plan_regression.is_some()
+        || (delta_ms >= MIN_DURATION_DELTA_MS
+            && duration_change_percent.is_some_and(|p| p >= DURATION_CHANGE_PERCENT))
+    {
+        BaselineQueryStatus::Regressed
+    } else if -delta_ms >= MIN_DURATION_DELTA_MS
+        && duration_change_percent.is_some_and(|p| p <= -DURATION_CHANGE_PERCENT)
+    {
+        BaselineQueryStatus::Improved
+    } else {
+        BaselineQueryStatus::Unchanged
+    };
+
+    QueryBaselineComparison {
+        fingerprint: fingerprint.to_string(),
+        sql: runs_after
+            .last()
+            .or(runs_before.last())
+            .map(|q| q.sql.clone())
+            .unwrap_or_default(),
+        status,
+        runs_before: runs_before.len(),
+        runs_after: runs_after.len(),
+        errors_before,
+        errors_after,
+        avg_duration_before_ms: avg_before,
+        avg_duration_after_ms: avg_after,
+        duration_change_percent,
+        plan_changed,
+        plan_diff,
+        plan_regression,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(sql: &str, duration_ms: u64, error: Option<&str>) -> BaselineQuery {
+        BaselineQuery {
+            fingerprint: query_hash::fingerprint(sql),
+            sql: sql.to_string(),
+            executed_at: Utc::now(),
+            duration_ms,
+            error: error.map(str::to_string),
+            plan_xml: None,
+            plan_hash: None,
+        }
+    }
+
+    fn baseline(queries: Vec<BaselineQuery>) -> Baseline {
+        Baseline {
+            id: Uuid::new_v4().to_string(),
+            name: "Before index change".to_string(),
+            started_at: Utc::now(),
+            ended_at: Some(Utc::now()),
+            queries,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_sessions_compare_by_fingerprint() {
+        let before = baseline(vec![
+            run("SELECT * FROM dbo.Orders WHERE Id = 1", 100, None),
+            run("SELECT * FROM dbo.Orders WHERE Id = 2", 120, None),
+            run("SELECT COUNT(*) FROM dbo.Customers", 40, None),
+            run("EXEC dbo.NightlyCleanup", 900, None),
+        ]);
+        let after = baseline(vec![
+            run("SELECT * FROM dbo.Orders WHERE Id = 7", 300, None),
+            run("SELECT COUNT(*) FROM dbo.Customers", 10, None),
+            run("SELECT 1", 1, None),
+        ]);
+
+        let comparison = compare(&before, &after);
+        assert_eq!(comparison.regressed, 1);
+        assert_eq!(comparison.improved, 1);
+        let orders = &comparison.queries[0];
+        assert_eq!(orders.status, BaselineQueryStatus::Regressed);
+        assert_eq!(orders.runs_before, 2);
+        assert_eq!(orders.avg_duration_before_ms, Some(110.0));
+        assert!(orders.sql.ends_with("Id = 7"));
+
+        let status = |sql: &str| {
+            comparison
+                .queries
+                .iter()
+                .find(|q| q.fingerprint == query_hash::fingerprint(sql))
+                .map(|q| q.status)
+        };
+        assert_eq!(
+            status("EXEC dbo.NightlyCleanup"),
+            Some(BaselineQueryStatus::Missing)
+        );
+        assert_eq!(status("SELECT 1"), Some(BaselineQueryStatus::New));
+
+        let failing = compare(
+            &before,
+            &baseline(vec![run("EXEC dbo.NightlyCleanup", 0, Some("Deadlock"))]),
+        );
+        assert_eq!(failing.regressed, 1);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+
+use super::connection::TiberiusClient;
+use super::rows::{get_i64, get_string};
+use super::types::{BlockingNode, BlockingSession, BlockingTree};
+
+/// Every session that is blocked, plus every session blocking one. The lead
+/// blocker is often idle with an open transaction, so sessions come from
+/// dm_exec_sessions and only the blocked ones have a request.
+const BLOCKING_SESSIONS_SQL: &str = "\
+WITH blocked AS (
+    SELECT session_id, blocking_session_id FROM sys.dm_exec_requests
+    WHERE blocking_session_id <> 0 AND blocking_session_id <> session_id
+), involved AS (
+    SELECT session_id FROM blocked
+    UNION SELECT blocking_session_id FROM blocked WHERE blocking_session_id > 0
+)
+SELECT s.session_id,
+       CAST(ISNULL(NULLIF(r.blocking_session_id, s.session_id), 0) AS int),
+       COALESCE(r.status, s.status),
+       r.command,
+       r.wait_type,
+       CAST(r.wait_time AS bigint),
+       r.wait_resource,
+       CAST(COALESCE(r.total_elapsed_time,
+            DATEDIFF(millisecond, s.last_request_end_time, SYSDATETIME())) AS bigint),
+       DB_NAME(COALESCE(r.database_id, s.database_id)),
+       s.login_name,
+       s.host_name,
+       s.program_name,
+       CAST(s.open_transaction_count AS int),
+       CAST(COALESCE(
+           SUBSTRING(rt.text, r.statement_start_offset / 2 + 1,
+               (CASE r.statement_end_offset WHEN -1 THEN DATALENGTH(rt.text)
+                ELSE r.statement_end_offset END - r.statement_start_offset) / 2 + 1),
+           ct.text) AS nvarchar(max))
+FROM involved i
+JOIN sys.dm_exec_sessions s ON s.session_id = i.session_id
+LEFT JOIN sys.dm_exec_requests r ON r.session_id = s.session_id
+LEFT JOIN sys.dm_exec_connections c ON c.session_id = s.session_id AND c.parent_connection_id IS NULL
+OUTER APPLY sys.dm_exec_sql_text(r.sql_handle) rt
+OUTER APPLY sys.dm_exec_sql_text(c.most_recent_sql_handle) ct";
+
+/// Arrange blocked sessions under their blockers. Sessions whose blocker is
+/// not in the list (or is an orphaned transaction) become roots, and so does
+/// the lowest session of each blocking cycle.
+pub fn build_tree(sessions: Vec<BlockingSession>, own_session_id: Option<i64>) -> BlockingTree {
+    let by_id: HashMap<i64, &BlockingSession> =
+        sessions.iter().map(|s| (s.session_id, s)).collect();
+    let mut waiters: HashMap<i64, Vec<&BlockingSession>> = HashMap::new();
+    for session in &sessions {
+        if session.blocking_session_id > 0 {
+            waiters
+                .entry(session.blocking_session_id)
+                .or_default()
+                .push(session);
+        }
+    }
+    for list in waiters.values_mut() {
+        list.sort_by_key(|s| s.session_id);
+    }
+
+    let mut ordered: Vec<&BlockingSession> = sessions.iter().collect();
+    ordered.sort_by_key(|s| s.session_id);
+    let mut visited = HashSet::new();
+    let mut roots: Vec<BlockingNode> = ordered
+        .iter()
+        .filter(|s| !by_id.contains_key(&s.blocking_session_id))
+        .map(|s| build_node(s, &waiters, &mut visited))
+        .collect();
+
+    // Whatever no root reaches waits in a circle
+    let mut cycles = Vec::new();
+    for session in &ordered {
+        if visited.contains(&session.session_id) {
+            continue;
+        }
+        let mut chain = vec![session.session_id];
+        let mut next = session.blocking_session_id;
+        while let Some(blocker) = by_id.get(&next) {
+            if let Some(start) = chain.iter().position(|id| *id == next) {
+                let mut cycle = chain.split_off(start);
+                cycle.sort_unstable();
+                roots.push(build_node(by_id[&cycle[0]], &waiters, &mut visited));
+                cycles.push(cycle);
+                break;
+            }
+            if visited.contains(&next) {
+                break;
+            }
+            chain.push(next);
+            next = blocker.blocking_session_id;
+        }
+    }
+
+    roots.sort_by(|a, b| {
+        b.total_blocked
+            .cmp(&a.total_blocked)
+            .then(a.session.session_id.cmp(&b.session.session_id))
+    });
+    BlockingTree {
+        roots,
+        blocked_sessions: sessions
+            .iter()
+            .filter(|s| s.blocking_session_id != 0)
+            .count(),
+        cycles,
+        own_session_id,
+        captured_at: Utc::now(),
+    }
+}
+
+fn build_node(
+    session: &BlockingSession,
+    waiters: &HashMap<i64, Vec<&BlockingSession>>,
+    visited: &mut HashSet<i64>,
+) -> BlockingNode {
+    visited.insert(session.session_id);
+    let mut blocked = Vec::new();
+    for waiter in waiters.get(&session.session_id).into_iter().flatten() {
+        if !visited.contains(&waiter.session_id) {
+            blocked.push(build_node(waiter, waiters, visited));
+        }
+    }
+    BlockingNode {
+        session: session.clone(),
+        total_blocked: blocked.iter().map(|b| 1 + b.total_blocked).sum(),
+        blocked,
+    }
+}
+
+/// Current blocking chains on the server; needs VIEW SERVER STATE to see
+/// sessions other than our own. `own_session_id` is the user's session, which
+/// may differ from the one running this query.
+pub async fn blocking_tree(
+    client: &mut TiberiusClient,
+    own_session_id: Option<i64>,
+) -> Result<BlockingTree, String> {
+    let rows = client
+        .simple_query(BLOCKING_SESSIONS_SQL)
+        .await
+        .map_err(|e| format!("Failed to read blocking sessions: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read blocking sessions: {}", e))?;
+    let sessions = rows
+        .iter()
+        .filter_map(|row| {
+            Some(BlockingSession {
+                session_id: get_i64(row, 0)?,
+                blocking_session_id: get_i64(row, 1).unwrap_or(0),
+                status: get_string(row, 2),
+                command: get_string(row, 3),
+                wait_type: get_string(row, 4),
+                wait_time_ms: get_i64(row, 5),
+                wait_resource: get_string(row, 6).filter(|r| !r.is_empty()),
+                elapsed_ms: get_i64(row, 7),
+                database_name: get_string(row, 8),
+                login_name: get_string(row, 9),
+                host_name: get_string(row, 10),
+                program_name: get_string(row, 11),
+                open_transaction_count: get_i64(row, 12),
+                sql_text: get_string(row, 13),
+            })
+        })
+        .collect();
+    Ok(build_tree(sessions, own_session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(session_id: i64, blocking_session_id: i64) -> BlockingSession {
+        BlockingSession {
+            session_id,
+            blocking_session_id,
+            ..Default::default()
+        }
+    }
+
+    fn shape(node: &BlockingNode) -> String {
+        if node.blocked.is_empty() {
+            return node.session.session_id.to_string();
+        }
+        let children: Vec<String> = node.blocked.iter().map(shape).collect();
+        format!("{}({})", node.session.session_id, children.join(" "))
+    }
+
+    #[test]
+    fn test_chains_under_lead_blockers() {
+        // 52 blocks 53 and 60; 53 blocks 54. 70 blocks 71.
+        let tree = build_tree(
+            vec![
+                session(54, 53),
+                session(52, 0),
+                session(53, 52),
+                session(60, 52),
+                session(71, 70),
+                session(70, 0),
+            ],
+            Some(54),
+        );
+        let shapes: Vec<String> = tree.roots.iter().map(shape).collect();
+        assert_eq!(shapes, vec!["52(53(54) 60)", "70(71)"]);
+        assert_eq!(tree.roots[0].total_blocked, 3);
+        assert_eq!(tree.blocked_sessions, 4);
+        assert!(tree.cycles.is_empty());
+    }
+
+    #[test]
+    fn test_orphaned_blocker_and_cycle() {
+        // -2 is an orphaned distributed transaction; 80 and 81 wait on each other
+        let tree = build_tree(
+            vec![
+                session(61, -2),
+                session(81, 80),
+                session(80, 81),
+                session(82, 81),
+            ],
+            None,
+        );
+        let shapes: Vec<String> = tree.roots.iter().map(shape).collect();
+        assert_eq!(shapes, vec!["80(81(82))", "61"]);
+        assert_eq!(tree.cycles, vec![vec![80, 81]]);
+        assert_eq!(tree.blocked_sessions, 4);
+    }
+}
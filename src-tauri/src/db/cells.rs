@@ -0,0 +1,157 @@
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+
+// Display formatting for cell values that have no natural JSON form.
+
+/// Bytes shown inline before a binary value is cut off; the rest is
+/// available through fetch_cell.
+pub const BINARY_PREVIEW_BYTES: usize = 256;
+
+/// Binary value as an SSMS-style literal (`0x0A1B...`)
+pub fn hex_literal(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(2 + bytes.len() * 2);
+    out.push_str("0x");
+    for b in bytes {
+        out.push_str(&format!("{:02X}", b));
+    }
+    out
+}
+
+/// Hex literal for display, truncated with the total length for large blobs.
+/// Returns whether the value was cut off.
+pub fn binary_preview(bytes: &[u8]) -> (String, bool) {
+    if bytes.len() <= BINARY_PREVIEW_BYTES {
+        return (hex_literal(bytes), false);
+    }
+    let preview = format!(
+        "{}... ({} bytes)",
+        hex_literal(&bytes[..BINARY_PREVIEW_BYTES]),
+        bytes.len()
+    );
+    (preview, true)
+}
+
+/// Re-indent an XML document or fragment (xml cells, plan XML) two spaces per
+/// level. Text-only elements stay on one line; entities are kept as written.
+pub fn pretty_print_xml(xml: &str) -> Result<String, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut out = String::with_capacity(xml.len() + xml.len() / 2);
+    let mut depth = 0usize;
+    // Text was written after the last start tag, so its end tag follows inline
+    let mut after_text = false;
+
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            format!(
+                "Invalid XML at position {}: {}",
+                reader.buffer_position(),
+                e
+            )
+        })?;
+        match event {
+            Event::Start(e) => {
+                new_line(&mut out, depth);
+                out.push('<');
+                out.push_str(&String::from_utf8_lossy(&e));
+                out.push('>');
+                depth += 1;
+                after_text = false;
+            }
+            Event::End(e) => {
+                depth = depth.saturating_sub(1);
+                if !after_text {
+                    new_line(&mut out, depth);
+                }
+                out.push_str("</");
+                out.push_str(&String::from_utf8_lossy(e.name().as_ref()));
+                out.push('>');
+                after_text = false;
+            }
+            Event::Empty(e) => {
+                new_line(&mut out, depth);
+                out.push('<');
+                out.push_str(&String::from_utf8_lossy(&e));
+                out.push_str(" />");
+                after_text = false;
+            }
+            Event::Text(t) => {
+                out.push_str(&String::from_utf8_lossy(&t));
+                after_text = true;
+            }
+            Event::CData(t) => {
+                out.push_str("<![CDATA[");
+                out.push_str(&String::from_utf8_lossy(&t));
+                out.push_str("]]>");
+                after_text = true;
+            }
+            Event::Comment(t) => {
+                new_line(&mut out, depth);
+                out.push_str("<!--");
+                out.push_str(&String::from_utf8_lossy(&t));
+                out.push_str("-->");
+            }
+            Event::Decl(d) => {
+                new_line(&mut out, depth);
+                out.push_str("<?");
+                out.push_str(&String::from_utf8_lossy(&d));
+                out.push_str("?>");
+            }
+            Event::PI(p) => {
+                new_line(&mut out, depth);
+                out.push_str("<?");
+                out.push_str(&String::from_utf8_lossy(&p));
+                out.push_str("?>");
+            }
+            Event::DocType(d) => {
+                new_line(&mut out, depth);
+                out.push_str("<!DOCTYPE ");
+                out.push_str(&String::from_utf8_lossy(&d));
+                out.push('>');
+            }
+            Event::Eof => break,
+        }
+    }
+
+    Ok(out)
+}
+
+fn new_line(out: &mut String, depth: usize) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_preview() {
+        assert_eq!(
+            binary_preview(&[0x00, 0xAB, 0x10]),
+            ("0x00AB10".into(), false)
+        );
+        assert_eq!(hex_literal(&[]), "0x");
+
+        let blob = vec![0xFF; BINARY_PREVIEW_BYTES + 1];
+        let (text, truncated) = binary_preview(&blob);
+        assert!(truncated);
+        assert!(text.ends_with(&format!("FF... ({} bytes)", BINARY_PREVIEW_BYTES + 1)));
+    }
+
+    #[test]
+    fn test_pretty_print_xml() {
+        let xml = r#"<root><a x="1">text &amp; more</a><b><c/></b></root>"#;
+        assert_eq!(
+            pretty_print_xml(xml).unwrap(),
+            "<root>\n  <a x=\"1\">text &amp; more</a>\n  <b>\n    <c />\n  </b>\n</root>"
+        );
+        // XML columns may hold fragments with several top-level elements
+        assert_eq!(pretty_print_xml("<a/><b/>").unwrap(), "<a />\n<b />");
+    }
+}
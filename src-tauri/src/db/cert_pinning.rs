@@ -0,0 +1,167 @@
+use sha2::{Digest, Sha256};
+
+use super::store;
+use super::tls;
+use super::transport;
+use super::types::{ConnectionConfig, ConnectionTransport};
+
+// Trust on first use for server certificates. The fingerprint is checked
+// inside the TLS handshake of every session (see tls.rs), so a session
+// cannot log in over a certificate other than the pinned one. It is stored
+// with the saved connection the first time and must match on every later
+// connect; sessions opened beside a connection (monitoring, siblings,
+// reconnects) are held to the certificate its first session saw.
+
+/// SHA-256 of the DER certificate as colon-separated hex, the way
+/// certificate viewers show it
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Compare the presented fingerprint with the pinned one. A mismatch is an
+/// error, and so is a pinned server presenting no certificate: a pinned
+/// server that stops encrypting is what a downgrade looks like. A server
+/// that never encrypted has nothing pinned and connects as before.
+pub fn check(host: &str, pinned: Option<&str>, presented: Option<&str>) -> Result<(), String> {
+    match (pinned, presented) {
+        (Some(_), None) => Err(format!(
+            "{} offered no encryption, but its certificate is pinned. Refusing to connect.",
+            host
+        )),
+        (None, _) => Ok(()),
+        (Some(pinned), Some(presented)) if pinned.eq_ignore_ascii_case(presented) => Ok(()),
+        (Some(pinned), Some(presented)) => Err(format!(
+            "Server certificate changed for {}: expected {}, got {}. \
+             If the certificate was renewed, accept the new one and connect again.",
+            host, pinned, presented
+        )),
+    }
+}
+
+/// Store the fingerprint as the saved connection's pin
+pub fn pin(app: &tauri::AppHandle, connection_id: &str, fingerprint: &str) -> Result<(), String> {
+    let mut connections = store::get_connections(app)?;
+    let connection = connections
+        .iter_mut()
+        .find(|c| c.id == connection_id)
+        .ok_or("Connection not found")?;
+    connection.cert_fingerprint = Some(fingerprint.to_string());
+    store::save_connections(app, &connections)
+}
+
+/// Pin the certificate a session on the saved connection was opened with,
+/// if nothing is pinned yet
+pub fn pin_first_use(
+    app: &tauri::AppHandle,
+    connection: &ConnectionConfig,
+    presented: Option<&str>,
+) -> Result<(), String> {
+    match (&connection.cert_fingerprint, presented) {
+        (None, Some(fingerprint)) => {
+            tracing::info!(connection = %connection.name, %fingerprint, "Server certificate pinned");
+            pin(app, &connection.id, fingerprint)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether the saved connection points at this endpoint, i.e. whether its
+/// pin applies there
+pub fn same_server(
+    connection: &ConnectionConfig,
+    host: &str,
+    port: u16,
+    transport: &ConnectionTransport,
+) -> bool {
+    connection.host.eq_ignore_ascii_case(host)
+        && connection.port == port
+        && &connection.transport == transport
+}
+
+/// The pin of a saved connection to the same server, for connections typed
+/// in by hand
+pub fn pinned_for(
+    app: &tauri::AppHandle,
+    host: &str,
+    port: u16,
+    transport: &ConnectionTransport,
+) -> Result<Option<String>, String> {
+    Ok(store::get_connections(app)?
+        .into_iter()
+        .filter(|c| same_server(c, host, port, transport))
+        .find_map(|c| c.cert_fingerprint))
+}
+
+/// Fingerprint of the certificate the server presents now, without logging in
+pub async fn server_fingerprint(
+    host: &str,
+    port: u16,
+    transport: &ConnectionTransport,
+) -> Result<String, String> {
+    let server = match transport {
+        ConnectionTransport::NamedPipe { pipe_path } => {
+            transport::pipe_server(pipe_path).unwrap_or(host)
+        }
+        ConnectionTransport::Tcp => host,
+    };
+    let stream = transport::open(format!("{}:{}", host, port), transport).await?;
+    let (_, presented) = tls::secure(stream, server, None).await?;
+    presented.ok_or_else(|| {
+        format!(
+            "{} does not support encryption, so it has no certificate to accept",
+            host
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pins_on_first_use_and_rejects_changes() {
+        let pin = fingerprint(b"certificate");
+        assert_eq!(pin.len(), 32 * 3 - 1);
+
+        assert!(check("db", None, Some(&pin)).is_ok());
+        assert!(check("db", Some(&pin), Some(&pin.to_lowercase())).is_ok());
+        let err = check("db", Some(&pin), Some(&fingerprint(b"renewed"))).unwrap_err();
+        assert!(err.starts_with("Server certificate changed for db"));
+    }
+
+    #[test]
+    fn test_only_pinned_server_without_encryption_is_refused() {
+        let pin = fingerprint(b"certificate");
+        let err = check("db", Some(&pin), None).unwrap_err();
+        assert!(err.contains("its certificate is pinned"));
+        assert!(check("db", None, None).is_ok());
+    }
+
+    #[test]
+    fn test_pin_applies_to_the_same_endpoint_only() {
+        let connection: ConnectionConfig = serde_json::from_value(serde_json::json!({
+            "id": "c",
+            "name": "c",
+            "host": "db.example.com",
+            "port": 1433,
+            "database": "master",
+            "username": "sa",
+            "encryptedPassword": "",
+            "lastUsed": null,
+            "createdAt": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        let tcp = ConnectionTransport::Tcp;
+        assert!(same_server(&connection, "DB.example.com", 1433, &tcp));
+        assert!(!same_server(&connection, "other.example.com", 1433, &tcp));
+        assert!(!same_server(&connection, "db.example.com", 1434, &tcp));
+        let pipe = ConnectionTransport::NamedPipe {
+            pipe_path: r"\\db\pipe\sql\query".to_string(),
+        };
+        assert!(!same_server(&connection, "db.example.com", 1433, &pipe));
+    }
+}
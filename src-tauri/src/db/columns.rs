@@ -0,0 +1,81 @@
+use tiberius::{Column, ColumnType};
+
+use super::types::ColumnInfo;
+
+// Result set column metadata for the grid. The driver reports the wire type
+// of each column but not its declared length or nullability, so those are
+// only filled in where the type fixes them.
+
+/// SQL Server type name, byte length, precision and scale of a wire type
+fn describe_type(column_type: ColumnType) -> (&'static str, Option<i64>, Option<u8>, Option<u8>) {
+    match column_type {
+        ColumnType::Null => ("null", None, None, None),
+        ColumnType::Bit | ColumnType::Bitn => ("bit", Some(1), None, None),
+        ColumnType::Int1 => ("tinyint", Some(1), Some(3), Some(0)),
+        ColumnType::Int2 => ("smallint", Some(2), Some(5), Some(0)),
+        ColumnType::Int4 => ("int", Some(4), Some(10), Some(0)),
+        ColumnType::Int8 => ("bigint", Some(8), Some(19), Some(0)),
+        ColumnType::Intn => ("int", None, None, Some(0)),
+        ColumnType::Float4 => ("real", Some(4), Some(24), None),
+        ColumnType::Float8 => ("float", Some(8), Some(53), None),
+        ColumnType::Floatn => ("float", None, None, None),
+        ColumnType::Money => ("money", Some(8), Some(19), Some(4)),
+        ColumnType::Money4 => ("smallmoney", Some(4), Some(10), Some(4)),
+        ColumnType::Decimaln => ("decimal", None, None, None),
+        ColumnType::Numericn => ("numeric", None, None, None),
+        ColumnType::Datetime4 => ("smalldatetime", Some(4), Some(16), Some(0)),
+        ColumnType::Datetime => ("datetime", Some(8), Some(23), Some(3)),
+        ColumnType::Datetimen => ("datetime", None, None, None),
+        ColumnType::Daten => ("date", Some(3), Some(10), Some(0)),
+        ColumnType::Timen => ("time", None, None, None),
+        ColumnType::Datetime2 => ("datetime2", None, None, None),
+        ColumnType::DatetimeOffsetn => ("datetimeoffset", None, None, None),
+        ColumnType::Guid => ("uniqueidentifier", Some(16), None, None),
+        ColumnType::BigVarBin => ("varbinary", None, None, None),
+        ColumnType::BigBinary => ("binary", None, None, None),
+        ColumnType::BigVarChar => ("varchar", None, None, None),
+        ColumnType::BigChar => ("char", None, None, None),
+        ColumnType::NVarchar => ("nvarchar", None, None, None),
+        ColumnType::NChar => ("nchar", None, None, None),
+        ColumnType::Xml => ("xml", None, None, None),
+        ColumnType::Udt => ("udt", None, None, None),
+        ColumnType::Text => ("text", None, None, None),
+        ColumnType::Image => ("image", None, None, None),
+        ColumnType::NText => ("ntext", None, None, None),
+        ColumnType::SSVariant => ("sql_variant", None, None, None),
+    }
+}
+
+pub fn column_info(column: &Column) -> ColumnInfo {
+    let (type_name, max_length, precision, scale) = describe_type(column.column_type());
+    ColumnInfo {
+        name: column.name().to_string(),
+        type_name: type_name.to_string(),
+        nullable: None,
+        max_length,
+        precision,
+        scale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_info_from_wire_type() {
+        let info = column_info(&Column::new("Total".to_string(), ColumnType::Money));
+        assert_eq!(info.name, "Total");
+        assert_eq!(info.type_name, "money");
+        assert_eq!(
+            (info.max_length, info.precision, info.scale),
+            (Some(8), Some(19), Some(4))
+        );
+
+        // Declared length is not on the wire
+        let info = column_info(&Column::new("Name".to_string(), ColumnType::NVarchar));
+        assert_eq!(info.type_name, "nvarchar");
+        assert_eq!(info.max_length, None);
+        assert_eq!(info.nullable, None);
+    }
+}
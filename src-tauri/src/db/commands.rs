@@ -1,77 +1,993 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use chrono::Utc;
+use tauri::{Emitter, Manager};
 use uuid::Uuid;
 
-use super::connection::{AppState, DbConnection};
+use super::audit_log::{self, AuditEvent, AuditLog};
+use super::backup::{self, AppDataBackup, BackupConnection};
+use super::baseline::{self, BaselineRecorder};
+use super::blocking;
+use super::cells;
+use super::cert_pinning;
+use super::compat_levels;
+use super::completion;
+use super::configuration;
+use super::connection::{AppState, ConnectionTarget, DbConnection, SessionOption};
+use super::connection_import;
+use super::copy_formats;
+use super::dependencies;
 use super::encryption;
+use super::errors;
+use super::exec_context;
+use super::folder_analysis;
+use super::formatter;
+use super::fragmentation;
+use super::hints;
+use super::history_groups;
+use super::memory_grants;
+use super::metadata_cache::CompletionLoad;
+use super::monitor;
+use super::multi_database;
+use super::notify;
+use super::parameters::{self, ParameterSet};
+use super::permissions;
+use super::plan_bundle;
+use super::plan_writer::PlanHistoryWriter;
+use super::progress;
+use super::query_hash;
+use super::query_stats;
+use super::query_store;
+use super::redact;
+use super::result_diff;
+use super::row_estimate;
+use super::rows;
+use super::scheduler::{self, Scheduler};
+use super::set_options;
+use super::sizes;
+use super::snippets;
+use super::splitter;
+use super::statistics;
 use super::store;
+use super::table_browser;
+use super::timing;
+use super::transport;
 use super::types::*;
+use super::validate;
+use super::wait_stats;
+use super::what_if;
+use crate::plan;
+use crate::plan::types::{ParallelismReport, PlanImageFormat, PlanRegression, PlanThumbnail};
+use crate::settings;
+use crate::settings::profile::{self, Capability};
+use crate::xevents::XeState;
 
 #[tauri::command]
-pub async fn test_connection(request: ConnectionRequest) -> Result<String, String> {
-    let conn = DbConnection::connect(
-        &request.host,
-        request.port,
-        &request.database,
-        &request.username,
-        &request.password,
-    )
-    .await?;
+pub async fn test_connection(
+    request: ConnectionRequest,
+    app: tauri::AppHandle,
+) -> Result<ConnectionTestReport, AppError> {
+    let mut report = ConnectionTestReport {
+        success: false,
+        failed_step: None,
+        error: None,
+        server_version: None,
+        edition: None,
+        current_database: None,
+        login_name: None,
+        encrypted: None,
+        net_transport: None,
+        auth_scheme: None,
+        connect_ms: None,
+        latency_ms: None,
+        permissions: Vec::new(),
+    };
 
-    // Verify with a simple query
-    let result = conn
-        .execute_query("SELECT 1 AS test", &PlanType::None)
-        .await?;
+    let pinned = cert_pinning::pinned_for(&app, &request.host, request.port, &request.transport)?;
+    let target = ConnectionTarget::from_request(&request, pinned);
+    let started = Instant::now();
+    let conn = match DbConnection::connect_diagnosed(target).await {
+        Ok(conn) => conn,
+        Err((step, error)) => {
+            report.failed_step = Some(step);
+            report.error = Some(error.into());
+            return Ok(report);
+        }
+    };
+    report.connect_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
 
-    if result.rows.is_empty() {
-        return Err("Connection test failed: no response from server".into());
+    let mut client = conn.client.lock().await;
+
+    // Round trip of a trivial query
+    let started = Instant::now();
+    client
+        .simple_query("SELECT 1")
+        .await?
+        .into_results()
+        .await
+        .map_err(|e| AppError::from(e).context("Connection test failed"))?;
+    report.latency_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+
+    let row = client
+        .simple_query(
+            "SELECT CAST(SERVERPROPERTY('ProductVersion') AS nvarchar(128)), \
+                    CAST(SERVERPROPERTY('Edition') AS nvarchar(128)), \
+                    DB_NAME(), SUSER_SNAME()",
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server info"))?
+        .into_row()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server info"))?;
+    if let Some(row) = row {
+        report.server_version = rows::get_string(&row, 0);
+        report.edition = rows::get_string(&row, 1);
+        report.current_database = rows::get_string(&row, 2);
+        report.login_name = rows::get_string(&row, 3);
+    }
+
+    // Needs VIEW SERVER STATE for other sessions, but a session may always see
+    // its own row; left empty on servers that refuse
+    if let Ok(stream) = client
+        .simple_query(
+            "SELECT encrypt_option, net_transport, auth_scheme \
+             FROM sys.dm_exec_connections WHERE session_id = @@SPID",
+        )
+        .await
+    {
+        if let Ok(Some(row)) = stream.into_row().await {
+            report.encrypted = rows::get_string(&row, 0).map(|e| e.eq_ignore_ascii_case("TRUE"));
+            report.net_transport = rows::get_string(&row, 1);
+            report.auth_scheme = rows::get_string(&row, 2);
+        }
     }
 
-    Ok("Connection successful".into())
+    report.permissions = permissions::check(&mut client).await?;
+    report.success = true;
+    Ok(report)
 }
 
 #[tauri::command]
 pub async fn connect_db(
     request: ConnectionRequest,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
-    let conn = DbConnection::connect(
-        &request.host,
-        request.port,
-        &request.database,
-        &request.username,
-        &request.password,
-    )
-    .await?;
+) -> Result<String, AppError> {
+    let pinned = cert_pinning::pinned_for(&app, &request.host, request.port, &request.transport)?;
+    let mut conn = DbConnection::connect(ConnectionTarget::from_request(&request, pinned)).await?;
+    conn.read_only = request.read_only;
+    let app_settings = settings::load(&app)?;
+    conn.set_keep_alive(app_settings.keep_alive_interval());
+    conn.cast_unsupported_types
+        .store(app_settings.cast_unsupported_types, Ordering::Relaxed);
+    conn.metadata_cache
+        .set_ttl(app_settings.metadata_cache_ttl());
+    conn.profile_read_only.store(
+        !app_settings.profile.allows(Capability::ModifyData),
+        Ordering::Relaxed,
+    );
+    warn_missing_permissions(&conn, &app).await;
+    audit(&app, &conn, AuditEvent::session(AuditAction::Connect));
 
-    *state.connection.lock().await = Some(conn);
+    *state.connection.lock().await = Some(Arc::new(conn));
+    cancel_queued_queries(&app, &state);
+    tracing::info!(
+        host = %request.host,
+        port = request.port,
+        database = %request.database,
+        read_only = request.read_only,
+        "Connected"
+    );
     Ok(format!(
-        "Connected to {}:{}/{}",
-        request.host, request.port, request.database
+        "Connected to {}/{}",
+        transport::endpoint(&request.host, request.port, &request.transport),
+        request.database
     ))
 }
 
+/// Tell the UI up front which features this login cannot use. A failing
+/// check must not fail the connect.
+async fn warn_missing_permissions(conn: &DbConnection, app: &tauri::AppHandle) {
+    let report = {
+        let mut client = conn.client.lock().await;
+        permissions::report(&mut client).await
+    };
+    if let Ok(report) = report {
+        if !report.unavailable_features.is_empty() {
+            let _ = app.emit("permission-warnings", &report);
+        }
+    }
+}
+
+/// Permissions of the active connection in its current database
+#[tauri::command]
+pub async fn check_permissions(
+    state: tauri::State<'_, AppState>,
+) -> Result<PermissionReport, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    Ok(permissions::report(&mut client).await?)
+}
+
+/// Queries queued for a connection that was replaced or closed must not run
+/// on whatever comes next
+fn cancel_queued_queries(app: &tauri::AppHandle, state: &AppState) {
+    let cancelled = state.execution_queue.cancel_all();
+    if cancelled > 0 {
+        tracing::info!(
+            cancelled,
+            "Cancelled queued queries after the connection changed"
+        );
+        emit_queue_changed(app, state);
+    }
+}
+
+#[tauri::command]
+pub async fn disconnect_db(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    if let Some(conn) = state.connection.lock().await.take() {
+        audit(&app, &conn, AuditEvent::session(AuditAction::Disconnect));
+    }
+    cancel_queued_queries(&app, &state);
+    Ok(())
+}
+
+/// Record an audit entry; a failed write is logged but never fails the
+/// command that caused it
+pub(super) fn audit(app: &tauri::AppHandle, conn: &DbConnection, event: AuditEvent) {
+    if let Err(e) = app.state::<AuditLog>().record(app, conn, event) {
+        tracing::error!(error = %e, "Failed to write the audit log");
+    }
+}
+
+/// Errors raised by recent queries and logins, newest first, with the
+/// server's error number, severity, state and line
+#[tauri::command]
+pub async fn get_recent_errors() -> Result<Vec<RecentError>, AppError> {
+    Ok(errors::recent())
+}
+
 #[tauri::command]
-pub async fn disconnect_db(state: tauri::State<'_, AppState>) -> Result<(), String> {
-    *state.connection.lock().await = None;
+pub async fn clear_recent_errors() -> Result<(), AppError> {
+    errors::clear();
     Ok(())
 }
 
+/// Newest audit entries and whether the hash chain is intact
+#[tauri::command]
+pub async fn get_audit_log(
+    limit: Option<usize>,
+    app: tauri::AppHandle,
+) -> Result<AuditLogPage, AppError> {
+    Ok(audit_log::page(&app, limit.unwrap_or(500))?)
+}
+
+/// The audit log file as JSON lines, hashes included
+#[tauri::command]
+pub async fn export_audit_log(app: tauri::AppHandle) -> Result<String, AppError> {
+    Ok(audit_log::export(&app)?)
+}
+
+/// Switch the active connection to another database. `connection_id`, when
+/// given, must be the saved connection that is currently open.
+#[tauri::command]
+pub async fn use_database(
+    connection_id: Option<String>,
+    db_name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AppError> {
+    let conn = state.active_connection().await?;
+    if connection_id.is_some() && connection_id != conn.saved_connection_id {
+        return Err(AppError::connection(
+            "Connection is not the active connection",
+        ));
+    }
+    conn.use_database(&db_name).await
+}
+
+#[tauri::command]
+pub async fn get_current_database(state: tauri::State<'_, AppState>) -> Result<String, AppError> {
+    let conn = state.active_connection().await?;
+    conn.current_database().await
+}
+
+/// Full value of a binary cell shown truncated in the last query result
+#[tauri::command]
+pub async fn fetch_cell(
+    result_set_index: Option<usize>,
+    row_index: usize,
+    column_index: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AppError> {
+    let conn = state.active_connection().await?;
+    conn.truncated_cell(result_set_index.unwrap_or(0), row_index, column_index)
+        .ok_or_else(|| "Cell value is no longer available. Re-run the query to load it.".into())
+}
+
+/// Indent XML from a cell or an execution plan for display
+/// Sort a cached result set; `keys` replaces the previous sort and an
+/// empty list restores the original order. Filters set earlier still apply.
+#[tauri::command]
+pub async fn sort_results(
+    result_id: String,
+    keys: Vec<SortKey>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    state: tauri::State<'_, AppState>,
+) -> Result<ResultView, AppError> {
+    let conn = state.active_connection().await?;
+    conn.result_cache
+        .sort(&result_id, keys, offset.unwrap_or(0), limit)
+        .map_err(AppError::from)
+}
+
+/// Filter a cached result set; `filters` replaces the previous ones and the
+/// current sort is kept
+#[tauri::command]
+pub async fn filter_results(
+    result_id: String,
+    filters: Vec<ResultFilter>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    state: tauri::State<'_, AppState>,
+) -> Result<ResultView, AppError> {
+    let conn = state.active_connection().await?;
+    conn.result_cache
+        .filter(&result_id, filters, offset.unwrap_or(0), limit)
+        .map_err(AppError::from)
+}
+
+/// Rows of a cached result set as text for the clipboard. `rows` are
+/// positions in the original result set (the current view when omitted);
+/// `table_name` is the target of INSERT statements.
+#[tauri::command]
+pub async fn copy_results(
+    result_id: String,
+    format: CopyFormat,
+    rows: Option<Vec<usize>>,
+    columns: Option<Vec<usize>>,
+    table_name: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AppError> {
+    let conn = state.active_connection().await?;
+    let (columns, rows) =
+        conn.result_cache
+            .selection(&result_id, rows.as_deref(), columns.as_deref())?;
+    Ok(copy_formats::render(
+        format,
+        &columns,
+        &rows,
+        table_name.as_deref(),
+    )?)
+}
+
+/// Run a query and write its first result set to a Parquet or Arrow IPC
+/// file, with typed columns. Rows are streamed to the file in batches, so
+/// results too large for the grid can be exported.
+#[tauri::command]
+pub async fn export_results(
+    sql: String,
+    path: String,
+    format: ExportFormat,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<ResultExport, AppError> {
+    let conn = state.active_connection().await?;
+    let file = PathBuf::from(path.trim());
+    let started = Instant::now();
+    let summary = conn
+        .export_results(&sql, &file, format, |rows_written| {
+            let _ = app.emit(
+                "export-progress",
+                &ExportProgress {
+                    path: path.clone(),
+                    rows_written,
+                },
+            );
+        })
+        .await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+    audit(
+        &app,
+        &conn,
+        AuditEvent {
+            action: AuditAction::Execute,
+            statement: Some(&sql),
+            error: summary.as_ref().err().map(AppError::message),
+            duration_ms: Some(duration_ms),
+        },
+    );
+    let summary = summary?;
+
+    Ok(ResultExport {
+        path: file.display().to_string(),
+        format,
+        rows: summary.rows,
+        columns: summary.columns,
+        skipped_result_sets: summary.skipped_result_sets,
+        file_size_bytes: std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0),
+        duration_ms,
+    })
+}
+
+/// One page of a table's rows for "view data". Adjacent pages seek by the
+/// table's unique key when it has one; other pages use OFFSET / FETCH.
+#[tauri::command]
+pub async fn browse_table(
+    request: BrowseTableRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<TablePage, AppError> {
+    let page_size = request.page_size.clamp(1, table_browser::MAX_PAGE_SIZE);
+    let conn = state.active_connection().await?;
+    let (columns, keys) = {
+        let mut client = conn.client.lock().await;
+        table_browser::table_schema(&mut client, &request.table).await?
+    };
+    let order_column = request
+        .order_column
+        .as_deref()
+        .map(|name| {
+            columns
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(name))
+                .map(|c| c.name.clone())
+                .ok_or_else(|| format!("Column not found: {}", name))
+        })
+        .transpose()?;
+    let key = table_browser::choose_key(&keys);
+
+    let (strategy, seek) = table_browser::plan_seek(
+        key.as_deref(),
+        order_column.as_deref(),
+        request.page,
+        request.cursor.as_ref(),
+    );
+    let query = table_browser::page_query(
+        &request.table,
+        key.as_deref(),
+        order_column.as_deref(),
+        request.descending,
+        page_size,
+        &seek,
+    )?;
+    let result = conn.execute_query(&query.sql, &PlanType::None).await?;
+    let mut result_set = result
+        .result_sets
+        .into_iter()
+        .next()
+        .ok_or("The page query returned no rows")?;
+
+    let more = result_set.rows.len() > page_size;
+    result_set.rows.truncate(page_size);
+    if query.reversed {
+        result_set.rows.reverse();
+    }
+    result_set.rows_affected = result_set.rows.len() as i64;
+    // The cached copy still has the look-ahead row
+    conn.result_cache.insert(&mut result_set);
+
+    let key_columns: Vec<String> = key
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    let cursor = match strategy {
+        PagingStrategy::Keyset => {
+            let positions: Option<Vec<usize>> = key_columns
+                .iter()
+                .map(|k| result_set.columns.iter().position(|c| &c.name == k))
+                .collect();
+            let key_of = |row: &Vec<serde_json::Value>| {
+                positions
+                    .as_ref()
+                    .map(|p| p.iter().map(|&i| row[i].clone()).collect::<Vec<_>>())
+            };
+            match (result_set.rows.first(), result_set.rows.last()) {
+                (Some(first), Some(last)) => {
+                    key_of(first)
+                        .zip(key_of(last))
+                        .map(|(first_key, last_key)| PageCursor {
+                            page: request.page,
+                            first_key,
+                            last_key,
+                        })
+                }
+                _ => None,
+            }
+        }
+        PagingStrategy::Offset => None,
+    };
+
+    Ok(TablePage {
+        table: request.table,
+        page: request.page,
+        page_size,
+        has_previous: if query.reversed {
+            more
+        } else {
+            request.page > 0
+        },
+        has_next: query.reversed || more,
+        result_set,
+        strategy,
+        key_columns,
+        cursor,
+    })
+}
+
+#[tauri::command]
+pub async fn pretty_print_xml(xml: String) -> Result<String, AppError> {
+    Ok(cells::pretty_print_xml(&xml)?)
+}
+
 #[tauri::command]
 pub async fn execute_query(
     request: QueryRequest,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<QueryResult, AppError> {
+    run_tracked_query(
+        &request.sql,
+        &request.plan_type,
+        request.query_id,
+        request.retry.unwrap_or_default(),
+        &app,
+        &state,
+    )
+    .await
+}
+
+/// Run only the statement under the cursor, like SSMS "execute selection"
+#[tauri::command]
+pub async fn execute_statement_at(
+    request: StatementQueryRequest,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<StatementQueryResult, AppError> {
+    let offset = splitter::utf16_to_byte_offset(&request.sql, request.cursor_offset);
+    let span = splitter::statement_at(&request.sql, offset).ok_or("No statement to execute")?;
+    let statement_sql = request.sql[span.start..span.end].to_string();
+
+    let result = run_tracked_query(
+        &statement_sql,
+        &request.plan_type,
+        None,
+        RetryPolicy::default(),
+        &app,
+        &state,
+    )
+    .await?;
+    Ok(StatementQueryResult {
+        statement_start: splitter::byte_to_utf16_offset(&request.sql, span.start),
+        statement_end: splitter::byte_to_utf16_offset(&request.sql, span.end),
+        statement_sql,
+        result,
+    })
+}
+
+/// Run one statement in each of the given databases, one at a time or on
+/// up to `concurrency` sessions beside the user's. A failing database is
+/// reported with its error and does not stop the rest.
+#[tauri::command]
+pub async fn execute_multi(
+    request: MultiDatabaseRequest,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<MultiDatabaseReport, AppError> {
+    let conn = state.active_connection().await?;
+    let started = Instant::now();
+    let results = multi_database::run(&conn, &request, |session, result, completed, total| {
+        audit(
+            &app,
+            session,
+            AuditEvent {
+                action: AuditAction::Execute,
+                statement: Some(&request.sql),
+                error: result.error.as_deref(),
+                duration_ms: Some(result.duration_ms),
+            },
+        );
+        let _ = app.emit(
+            "multi-database-progress",
+            &MultiDatabaseProgress {
+                database: result.database.clone(),
+                error: result.error.clone(),
+                completed,
+                total,
+            },
+        );
+    })
+    .await?;
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(MultiDatabaseReport {
+        succeeded: results.len() - failed,
+        failed,
+        results,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Run one statement on each saved connection of a connection group (and
+/// any listed beside it), each on a connection of its own. A server that
+/// cannot be reached or fails the statement does not stop the rest.
+#[tauri::command]
+pub async fn execute_on_server_group(
+    request: ServerGroupRequest,
+    app: tauri::AppHandle,
+) -> Result<ServerGroupReport, AppError> {
+    let started = Instant::now();
+    let results = multi_database::run_on_servers(&app, &request, |result, completed, total| {
+        let _ = app.emit(
+            "server-group-progress",
+            &ServerGroupProgress {
+                connection_id: result.connection_id.clone(),
+                connection_name: result.connection_name.clone(),
+                error: result.error.clone(),
+                completed,
+                total,
+            },
+        );
+    })
+    .await?;
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(ServerGroupReport {
+        succeeded: results.len() - failed,
+        failed,
+        results,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Run a query on two connections (or twice on the current one) and list
+/// the rows that differ, paired by the chosen key columns. Both sides run
+/// without a plan; only their first result sets are compared.
+#[tauri::command]
+pub async fn diff_results(
+    request: ResultDiffRequest,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<ResultDiffReport, AppError> {
+    let right_sql = request.right_sql.as_deref().unwrap_or(&request.sql);
+    let (left, right) = futures_util::future::join(
+        diff_side(
+            &app,
+            &state,
+            request.left_connection_id.as_deref(),
+            &request.sql,
+        ),
+        diff_side(
+            &app,
+            &state,
+            request.right_connection_id.as_deref(),
+            right_sql,
+        ),
+    )
+    .await;
+    let ((left, left_rows), (right, right_rows)) = (left?, right?);
+
+    let key_columns = request.key_columns;
+    // Hashing and comparing large results is CPU work
+    let diff = tokio::task::spawn_blocking(move || {
+        result_diff::diff(&left_rows, &right_rows, &key_columns)
+    })
+    .await
+    .map_err(|e| format!("Result comparison failed: {}", e))??;
+    Ok(ResultDiffReport { left, right, diff })
+}
+
+/// Run one side of a diff on a saved connection, or on the active one when
+/// no id is given, and return its first result set
+async fn diff_side(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    connection_id: Option<&str>,
+    sql: &str,
+) -> Result<(ResultDiffSide, ResultSet), AppError> {
+    let (conn, source) = match connection_id {
+        Some(id) => {
+            let connection = store::get_connections(app)?
+                .into_iter()
+                .find(|c| c.id == id)
+                .ok_or("Connection not found")?;
+            let key = password_key(app, state).await?;
+            let profile_read_only = !settings::load(app)?.profile.allows(Capability::ModifyData);
+            let conn =
+                multi_database::open_saved(app, &connection, &key, profile_read_only).await?;
+            (Arc::new(conn), connection.name)
+        }
+        None => (
+            state.active_connection().await?,
+            "Current connection".to_string(),
+        ),
+    };
+
+    let started = Instant::now();
+    let result = conn.execute_query(sql, &PlanType::None).await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+    audit(
+        app,
+        &conn,
+        AuditEvent {
+            action: AuditAction::Execute,
+            statement: Some(sql),
+            error: result.as_ref().err().map(AppError::message),
+            duration_ms: Some(duration_ms),
+        },
+    );
+    let result_set = result
+        .map_err(|e| e.context(&source))?
+        .result_sets
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{}: the query returned no result set", source))?;
+
+    let side = ResultDiffSide {
+        source,
+        database: conn.last_known_database(),
+        duration_ms,
+    };
+    Ok((side, result_set))
+}
+
+/// Statement boundaries of a script; GO lines and semicolons are excluded
+#[tauri::command]
+pub async fn split_statements(sql: String) -> Result<Vec<ScriptStatement>, AppError> {
+    Ok(splitter::split_statements(&sql)
+        .into_iter()
+        .map(|span| ScriptStatement {
+            start: splitter::byte_to_utf16_offset(&sql, span.start),
+            end: splitter::byte_to_utf16_offset(&sql, span.end),
+            sql: sql[span.start..span.end].to_string(),
+        })
+        .collect())
+}
+
+/// Execute on the active connection once the query's turn in the execution
+/// queue comes, registering it as running and notifying on completion.
+async fn run_tracked_query(
+    sql: &str,
+    plan_type: &PlanType,
+    query_id: Option<String>,
+    retry: RetryPolicy,
+    app: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<QueryResult, AppError> {
+    let query_id = query_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut ticket = state.execution_queue.enqueue(&query_id, sql);
+    emit_queue_changed(app, state);
+    let turn = ticket.wait_turn().await;
+    emit_queue_changed(app, state);
+    turn?;
+
+    let started = std::time::Instant::now();
+    state.running_queries.lock().await.insert(
+        query_id.clone(),
+        RunningQuery {
+            id: query_id.clone(),
+            sql: sql.to_string(),
+            started_at: Utc::now(),
+        },
+    );
+
+    if let Some(xe) = app.try_state::<XeState>() {
+        xe.record_query(&query_id, sql);
+    }
+
+    // The state lock is released so monitoring commands can run meanwhile
+    let result = match state.active_connection().await {
+        Ok(conn) => {
+            let poller = progress::spawn_poller(app.clone(), conn.clone(), query_id.clone());
+            let result = conn.execute_query_with_retry(sql, plan_type, &retry).await;
+            if let Some(poller) = poller {
+                poller.abort();
+            }
+            audit(
+                app,
+                &conn,
+                AuditEvent {
+                    action: AuditAction::Execute,
+                    statement: Some(sql),
+                    error: result.as_ref().err().map(AppError::message),
+                    duration_ms: Some(started.elapsed().as_millis() as u64),
+                },
+            );
+            result
+        }
+        Err(e) => Err(e),
+    };
+
+    state.running_queries.lock().await.remove(&query_id);
+    let elapsed = started.elapsed();
+    if let Some(recorder) = app.try_state::<BaselineRecorder>() {
+        recorder.record(sql, &result, elapsed.as_millis() as u64);
+    }
+    match &result {
+        Ok(r) => tracing::info!(
+            query_id = %query_id,
+            duration_ms = elapsed.as_millis() as u64,
+            result_sets = r.result_sets.len(),
+            "Query finished"
+        ),
+        Err(e) => tracing::warn!(
+            query_id = %query_id,
+            duration_ms = elapsed.as_millis() as u64,
+            error = %e,
+            "Query failed"
+        ),
+    }
+    notify::query_finished(app, elapsed, &result);
+    result
+}
+
+fn emit_queue_changed(app: &tauri::AppHandle, state: &AppState) {
+    let _ = app.emit("query-queue-changed", state.execution_queue.pending());
+}
+
+/// Queries waiting for the connection, next to run first
+#[tauri::command]
+pub async fn list_pending_queries(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PendingQuery>, AppError> {
+    Ok(state.execution_queue.pending())
+}
+
+/// Take a waiting query out of the queue; its execute call fails as
+/// cancelled. Running queries are not affected.
+#[tauri::command]
+pub async fn cancel_pending(
+    query_id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    if !state.execution_queue.cancel(&query_id) {
+        return Err("Query is not waiting in the queue".into());
+    }
+    emit_queue_changed(&app, &state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_running_queries(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<RunningQuery>, AppError> {
+    let mut queries: Vec<RunningQuery> = state
+        .running_queries
+        .lock()
+        .await
+        .values()
+        .cloned()
+        .collect();
+    queries.sort_by_key(|q| q.started_at);
+    Ok(queries)
+}
+
+/// Syntax-check (Parse) or bind-check (Compile) a batch without running it
+#[tauri::command]
+pub async fn validate_query(
+    sql: String,
+    mode: ValidationMode,
+    state: tauri::State<'_, AppState>,
+) -> Result<ValidationResult, AppError> {
+    let conn = state.active_connection().await?;
+
+    let option = match mode {
+        ValidationMode::Parse => SessionOption::ParseOnly,
+        ValidationMode::Compile => SessionOption::NoExec,
+    };
+    let (first_error, errors) = conn.check_batch(&sql, option).await?;
+    Ok(validate::build_result(&sql, first_error, errors)?)
+}
+
+/// Key that protects saved passwords: the master passphrase key when one is
+/// set (failing while the store is locked), otherwise the machine key
+pub(super) async fn password_key(
+    app: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<[u8; 32], AppError> {
+    if store::get_master_key_info(app)?.is_none() {
+        return Ok(encryption::machine_key());
+    }
+    state.master_key.lock().await.ok_or_else(|| {
+        "Saved passwords are locked. Unlock them with your master passphrase.".into()
+    })
+}
+
+#[tauri::command]
+pub async fn get_store_lock_status(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<StoreLockStatus, AppError> {
+    let passphrase_enabled = store::get_master_key_info(&app)?.is_some();
+    Ok(StoreLockStatus {
+        passphrase_enabled,
+        unlocked: !passphrase_enabled || state.master_key.lock().await.is_some(),
+    })
+}
+
+/// Protect saved passwords with a master passphrase instead of the machine key
+#[tauri::command]
+pub async fn enable_master_passphrase(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    if store::get_master_key_info(&app)?.is_some() {
+        return Err("A master passphrase is already set".into());
+    }
+    let (key, info) = encryption::create_master_key(&passphrase)?;
+    let connections = reencrypt_passwords(&app, &encryption::machine_key(), &key)?;
+    store::save_master_key_info(&app, Some(&info), &connections)?;
+    *state.master_key.lock().await = Some(key);
+    Ok(())
+}
+
+/// Go back to the machine key; needs the current passphrase
+#[tauri::command]
+pub async fn disable_master_passphrase(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let info = store::get_master_key_info(&app)?.ok_or("No master passphrase is set")?;
+    let key = encryption::unlock_master_key(&passphrase, &info)?;
+    let connections = reencrypt_passwords(&app, &key, &encryption::machine_key())?;
+    store::save_master_key_info(&app, None, &connections)?;
+    *state.master_key.lock().await = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unlock_store(
+    passphrase: String,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<QueryResult, String> {
-    let lock = state.connection.lock().await;
-    let conn = lock.as_ref().ok_or("Not connected to database")?;
-    conn.execute_query(&request.sql, &request.plan_type).await
+) -> Result<(), AppError> {
+    let info = store::get_master_key_info(&app)?.ok_or("No master passphrase is set")?;
+    let key = encryption::unlock_master_key(&passphrase, &info)?;
+    *state.master_key.lock().await = Some(key);
+    Ok(())
+}
+
+/// Forget the master key; an open connection stays open
+#[tauri::command]
+pub async fn lock_store(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    *state.master_key.lock().await = None;
+    Ok(())
+}
+
+fn reencrypt_passwords(
+    app: &tauri::AppHandle,
+    from: &[u8; 32],
+    to: &[u8; 32],
+) -> Result<Vec<ConnectionConfig>, AppError> {
+    store::get_connections(app)?
+        .into_iter()
+        .map(|mut config| {
+            let password = encryption::decrypt_password_with(from, &config.encrypted_password)
+                .map_err(|_| format!("Cannot decrypt the password of '{}'", config.name))?;
+            config.encrypted_password = encryption::encrypt_password_with(to, &password)?;
+            Ok(config)
+        })
+        .collect()
 }
 
 #[tauri::command]
 pub async fn save_connection(
     request: SaveConnectionRequest,
     app: tauri::AppHandle,
-) -> Result<ConnectionConfig, String> {
-    let encrypted_password = encryption::encrypt_password(&request.password)?;
+    state: tauri::State<'_, AppState>,
+) -> Result<ConnectionConfig, AppError> {
+    let key = password_key(&app, &state).await?;
+    let encrypted_password = encryption::encrypt_password_with(&key, &request.password)?;
 
     let config = ConnectionConfig {
         id: Uuid::new_v4().to_string(),
@@ -83,6 +999,12 @@ pub async fn save_connection(
         encrypted_password,
         last_used: Some(Utc::now()),
         created_at: Utc::now(),
+        group_id: request.group_id,
+        tags: normalize_tags(request.tags),
+        read_only: request.read_only,
+        transport: request.transport,
+        auth: request.auth,
+        cert_fingerprint: None,
     };
 
     let mut connections = store::get_connections(&app)?;
@@ -92,88 +1014,1948 @@ pub async fn save_connection(
     Ok(config)
 }
 
+/// Connections in an SSMS registered servers export (.regsrvr) or an Azure
+/// Data Studio settings.json, for the user to review and add passwords to.
+/// Nothing is saved here; see save_imported_connections.
 #[tauri::command]
-pub async fn get_connections(app: tauri::AppHandle) -> Result<Vec<ConnectionConfig>, String> {
-    store::get_connections(&app)
+pub async fn import_connections(
+    path: String,
+    app: tauri::AppHandle,
+) -> Result<Vec<ImportedConnection>, AppError> {
+    let bytes =
+        std::fs::read(path.trim()).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut imported = connection_import::parse(&folder_analysis::decode_script(&bytes)?)?;
+    let saved = store::get_connections(&app)?;
+    for connection in &mut imported {
+        connection.already_saved = saved.iter().any(|c| {
+            c.host.eq_ignore_ascii_case(&connection.host)
+                && c.port == connection.port
+                && c.database.eq_ignore_ascii_case(&connection.database)
+                && c.username.eq_ignore_ascii_case(&connection.username)
+        });
+    }
+    Ok(imported)
 }
 
+/// Save reviewed imports with their passwords, creating connection groups
+/// named after the source's server groups where none exists yet
 #[tauri::command]
-pub async fn delete_connection(id: String, app: tauri::AppHandle) -> Result<(), String> {
-    let mut connections = store::get_connections(&app)?;
-    connections.retain(|c| c.id != id);
-    store::save_connections(&app, &connections)?;
-    Ok(())
+pub async fn save_imported_connections(
+    connections: Vec<ImportConnectionEntry>,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ConnectionConfig>, AppError> {
+    let key = password_key(&app, &state).await?;
+    let mut groups = store::get_connection_groups(&app)?;
+    let mut saved = store::get_connections(&app)?;
+    let groups_before = groups.len();
+
+    let mut added = Vec::with_capacity(connections.len());
+    for entry in connections {
+        let group_id = match entry.group.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => {
+                match groups.iter().find(|g| g.name.eq_ignore_ascii_case(name)) {
+                    Some(group) => Some(group.id.clone()),
+                    None => {
+                        let group = ConnectionGroup {
+                            id: Uuid::new_v4().to_string(),
+                            name: name.to_string(),
+                            created_at: Utc::now(),
+                        };
+                        groups.push(group.clone());
+                        Some(group.id)
+                    }
+                }
+            }
+            _ => None,
+        };
+        added.push(ConnectionConfig {
+            id: Uuid::new_v4().to_string(),
+            name: entry.name,
+            host: entry.host,
+            port: entry.port,
+            database: entry.database,
+            username: entry.username,
+            encrypted_password: encryption::encrypt_password_with(&key, &entry.password)?,
+            last_used: None,
+            created_at: Utc::now(),
+            group_id,
+            tags: Vec::new(),
+            read_only: false,
+            transport: ConnectionTransport::Tcp,
+            auth: ConnectionAuth::SqlLogin,
+            cert_fingerprint: None,
+        });
+    }
+
+    if groups.len() > groups_before {
+        store::save_connection_groups(&app, &groups)?;
+    }
+    saved.extend(added.iter().cloned());
+    store::save_connections(&app, &saved)?;
+    tracing::info!(count = added.len(), "Connections imported");
+    Ok(added)
 }
 
+/// Trust the certificate the server presents now, after it changed (e.g.
+/// was renewed), and return its fingerprint
 #[tauri::command]
-pub async fn connect_saved(
+pub async fn accept_server_certificate(
+    connection_id: String,
+    app: tauri::AppHandle,
+) -> Result<String, AppError> {
+    let connection = store::get_connections(&app)?
+        .into_iter()
+        .find(|c| c.id == connection_id)
+        .ok_or("Connection not found")?;
+    let fingerprint =
+        cert_pinning::server_fingerprint(&connection.host, connection.port, &connection.transport)
+            .await?;
+    cert_pinning::pin(&app, &connection_id, &fingerprint)?;
+    tracing::info!(connection = %connection.name, %fingerprint, "Server certificate accepted");
+    Ok(fingerprint)
+}
+
+#[tauri::command]
+pub async fn get_connections(app: tauri::AppHandle) -> Result<Vec<ConnectionConfig>, AppError> {
+    Ok(store::get_connections(&app)?)
+}
+
+/// Edit a saved connection in place, re-encrypting the password only when a
+/// new one is given
+#[tauri::command]
+pub async fn update_connection(
     id: String,
+    request: UpdateConnectionRequest,
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<ConnectionConfig, AppError> {
+    let encrypted_password = match &request.password {
+        Some(password) => {
+            let key = password_key(&app, &state).await?;
+            Some(encryption::encrypt_password_with(&key, password)?)
+        }
+        None => None,
+    };
+
     let mut connections = store::get_connections(&app)?;
-    let conn_config = connections
+    let conn = connections
         .iter_mut()
         .find(|c| c.id == id)
         .ok_or("Connection not found")?;
+    // The pin belongs to the server; another endpoint must be trusted anew
+    if !cert_pinning::same_server(conn, &request.host, request.port, &request.transport) {
+        conn.cert_fingerprint = None;
+    }
+    conn.name = request.name;
+    conn.host = request.host;
+    conn.port = request.port;
+    conn.database = request.database;
+    conn.username = request.username;
+    conn.group_id = request.group_id;
+    conn.tags = normalize_tags(request.tags);
+    conn.read_only = request.read_only;
+    conn.transport = request.transport;
+    conn.auth = request.auth;
+    if let Some(encrypted_password) = encrypted_password {
+        conn.encrypted_password = encrypted_password;
+    }
+    let updated = conn.clone();
+    store::save_connections(&app, &connections)?;
+    Ok(updated)
+}
 
-    let password = encryption::decrypt_password(&conn_config.encrypted_password)?;
-
-    let conn = DbConnection::connect(
-        &conn_config.host,
-        conn_config.port,
-        &conn_config.database,
-        &conn_config.username,
-        &password,
-    )
-    .await?;
+/// Copy a saved connection, e.g. to point it at another database. The
+/// encrypted password is copied as is.
+#[tauri::command]
+pub async fn duplicate_connection(
+    id: String,
+    name: Option<String>,
+    database: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<ConnectionConfig, AppError> {
+    let mut connections = store::get_connections(&app)?;
+    let original = connections
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or("Connection not found")?;
 
-    let display = format!(
-        "Connected to {}:{}/{}",
-        conn_config.host, conn_config.port, conn_config.database
-    );
+    let copy = ConnectionConfig {
+        id: Uuid::new_v4().to_string(),
+        name: name
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| format!("{} (copy)", original.name)),
+        database: database.unwrap_or_else(|| original.database.clone()),
+        last_used: None,
+        created_at: Utc::now(),
+        ..original.clone()
+    };
+    connections.push(copy.clone());
+    store::save_connections(&app, &connections)?;
+    Ok(copy)
+}
 
-    conn_config.last_used = Some(Utc::now());
+#[tauri::command]
+pub async fn delete_connection(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut connections = store::get_connections(&app)?;
+    connections.retain(|c| c.id != id);
     store::save_connections(&app, &connections)?;
 
-    *state.connection.lock().await = Some(conn);
-    Ok(display)
+    let mut workspaces = store::get_workspaces(&app)?;
+    workspaces.retain(|w| w.connection_id != id);
+    store::save_workspaces(&app, &workspaces)?;
+    Ok(())
 }
 
+/// Remember the editor and database for a saved connection
 #[tauri::command]
-pub async fn get_query_history(app: tauri::AppHandle) -> Result<Vec<QueryHistoryEntry>, String> {
-    store::get_query_history(&app)
+pub async fn save_workspace(
+    mut workspace: WorkspaceState,
+    app: tauri::AppHandle,
+) -> Result<WorkspaceState, AppError> {
+    let mut workspaces = store::get_workspaces(&app)?;
+    let previous = workspaces
+        .iter()
+        .position(|w| w.connection_id == workspace.connection_id)
+        .map(|i| workspaces.remove(i));
+
+    workspace.recent_databases = previous.map(|p| p.recent_databases).unwrap_or_default();
+    if let Some(database) = workspace.database.as_deref().filter(|d| !d.is_empty()) {
+        store::remember_database(&mut workspace.recent_databases, database);
+    }
+    workspace.updated_at = Some(Utc::now());
+
+    workspaces.push(workspace.clone());
+    store::save_workspaces(&app, &workspaces)?;
+    Ok(workspace)
+}
+
+/// Workspace of a saved connection, or the one used last when no id is given.
+/// A plan that has since left history is not restored.
+#[tauri::command]
+pub async fn restore_workspace(
+    connection_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Option<WorkspaceState>, AppError> {
+    let workspaces = store::get_workspaces(&app)?;
+    let workspace = match connection_id {
+        Some(id) => workspaces.into_iter().find(|w| w.connection_id == id),
+        None => workspaces.into_iter().max_by_key(|w| w.updated_at),
+    };
+    let Some(mut workspace) = workspace else {
+        return Ok(None);
+    };
+
+    if let Some(plan_id) = &workspace.plan_id {
+        if !store::get_plan_history(&app)?
+            .iter()
+            .any(|p| &p.id == plan_id)
+        {
+            workspace.plan_id = None;
+        }
+    }
+    Ok(Some(workspace))
+}
+
+#[tauri::command]
+pub async fn get_connection_groups(
+    app: tauri::AppHandle,
+) -> Result<Vec<ConnectionGroup>, AppError> {
+    Ok(store::get_connection_groups(&app)?)
+}
+
+#[tauri::command]
+pub async fn create_connection_group(
+    name: String,
+    app: tauri::AppHandle,
+) -> Result<ConnectionGroup, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Group name cannot be empty".into());
+    }
+
+    let mut groups = store::get_connection_groups(&app)?;
+    if groups.iter().any(|g| g.name.eq_ignore_ascii_case(&name)) {
+        return Err(format!("A group named '{}' already exists", name).into());
+    }
+
+    let group = ConnectionGroup {
+        id: Uuid::new_v4().to_string(),
+        name,
+        created_at: Utc::now(),
+    };
+    groups.push(group.clone());
+    store::save_connection_groups(&app, &groups)?;
+    Ok(group)
+}
+
+#[tauri::command]
+pub async fn rename_connection_group(
+    id: String,
+    name: String,
+    app: tauri::AppHandle,
+) -> Result<ConnectionGroup, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Group name cannot be empty".into());
+    }
+
+    let mut groups = store::get_connection_groups(&app)?;
+    if groups
+        .iter()
+        .any(|g| g.id != id && g.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(format!("A group named '{}' already exists", name).into());
+    }
+    let group = groups
+        .iter_mut()
+        .find(|g| g.id == id)
+        .ok_or("Group not found")?;
+    group.name = name;
+    let renamed = group.clone();
+    store::save_connection_groups(&app, &groups)?;
+    Ok(renamed)
+}
+
+/// Deletes the group; its connections are kept and become ungrouped.
+#[tauri::command]
+pub async fn delete_connection_group(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut groups = store::get_connection_groups(&app)?;
+    groups.retain(|g| g.id != id);
+    store::save_connection_groups(&app, &groups)?;
+
+    let mut connections = store::get_connections(&app)?;
+    for conn in connections
+        .iter_mut()
+        .filter(|c| c.group_id.as_deref() == Some(&id))
+    {
+        conn.group_id = None;
+    }
+    store::save_connections(&app, &connections)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_connection_group(
+    connection_id: String,
+    group_id: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<ConnectionConfig, AppError> {
+    if let Some(group_id) = &group_id {
+        let groups = store::get_connection_groups(&app)?;
+        if !groups.iter().any(|g| &g.id == group_id) {
+            return Err("Group not found".into());
+        }
+    }
+
+    let mut connections = store::get_connections(&app)?;
+    let conn = connections
+        .iter_mut()
+        .find(|c| c.id == connection_id)
+        .ok_or("Connection not found")?;
+    conn.group_id = group_id;
+    let updated = conn.clone();
+    store::save_connections(&app, &connections)?;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn set_connection_tags(
+    connection_id: String,
+    tags: Vec<String>,
+    app: tauri::AppHandle,
+) -> Result<ConnectionConfig, AppError> {
+    let mut connections = store::get_connections(&app)?;
+    let conn = connections
+        .iter_mut()
+        .find(|c| c.id == connection_id)
+        .ok_or("Connection not found")?;
+    conn.tags = normalize_tags(tags);
+    let updated = conn.clone();
+    store::save_connections(&app, &connections)?;
+    Ok(updated)
+}
+
+/// Trim, drop empties and de-duplicate tags case-insensitively, keeping first spelling
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !result.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            result.push(tag.to_string());
+        }
+    }
+    result
+}
+
+#[tauri::command]
+pub async fn connect_saved(
+    id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AppError> {
+    let mut connections = store::get_connections(&app)?;
+    let conn_config = connections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or("Connection not found")?;
+
+    let key = password_key(&app, &state).await?;
+    let password = encryption::decrypt_password_with(&key, &conn_config.encrypted_password)?;
+    let target = ConnectionTarget::from_saved(conn_config, password);
+    let mut conn = DbConnection::connect(target).await?;
+    conn.read_only = conn_config.read_only;
+    conn.saved_connection_id = Some(conn_config.id.clone());
+    let app_settings = settings::load(&app)?;
+    conn.set_keep_alive(app_settings.keep_alive_interval());
+    conn.cast_unsupported_types
+        .store(app_settings.cast_unsupported_types, Ordering::Relaxed);
+    conn.metadata_cache
+        .set_ttl(app_settings.metadata_cache_ttl());
+    conn.profile_read_only.store(
+        !app_settings.profile.allows(Capability::ModifyData),
+        Ordering::Relaxed,
+    );
+    warn_missing_permissions(&conn, &app).await;
+    audit(&app, &conn, AuditEvent::session(AuditAction::Connect));
+
+    let display = format!(
+        "Connected to {}/{}",
+        transport::endpoint(&conn_config.host, conn_config.port, &conn_config.transport),
+        conn_config.database
+    );
+
+    conn_config.last_used = Some(Utc::now());
+    let saved = conn_config.clone();
+    store::save_connections(&app, &connections)?;
+    cert_pinning::pin_first_use(&app, &saved, conn.server_certificate())?;
+
+    *state.connection.lock().await = Some(Arc::new(conn));
+    cancel_queued_queries(&app, &state);
+    Ok(display)
+}
+
+#[tauri::command]
+pub async fn get_query_history(app: tauri::AppHandle) -> Result<Vec<QueryHistoryEntry>, AppError> {
+    Ok(store::get_query_history(&app)?)
 }
 
 #[tauri::command]
 pub async fn save_query_history_entry(
     entry: QueryHistoryEntry,
     app: tauri::AppHandle,
-) -> Result<(), String> {
-    let mut history = store::get_query_history(&app)?;
+) -> Result<(), AppError> {
+    record_query(&app, entry)
+}
+
+/// Hash the entry's SQL and put it at the top of history
+pub(super) fn record_query(
+    app: &tauri::AppHandle,
+    mut entry: QueryHistoryEntry,
+) -> Result<(), AppError> {
+    entry.sql_hash = Some(query_hash::sql_hash(&entry.sql));
+    entry.fingerprint = Some(query_hash::fingerprint(&entry.sql));
+    let mut history = store::get_query_history(app)?;
     history.insert(0, entry);
-    if history.len() > 100 {
-        history.truncate(100);
-    }
+    store::trim_query_history(&mut history);
+    store::save_query_history(app, &history)?;
+    Ok(())
+}
+
+/// Star a history entry so it survives trimming, optionally naming it
+#[tauri::command]
+pub async fn pin_query_history_entry(
+    id: String,
+    label: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<QueryHistoryEntry, AppError> {
+    let mut history = store::get_query_history(&app)?;
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id == id)
+        .ok_or("Query not found in history")?;
+    entry.pinned = true;
+    entry.label = label
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty());
+    let pinned = entry.clone();
+    store::save_query_history(&app, &history)?;
+    Ok(pinned)
+}
+
+/// Unstar an entry; it is trimmed like any other from now on
+#[tauri::command]
+pub async fn unpin_query_history_entry(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut history = store::get_query_history(&app)?;
+    let entry = history
+        .iter_mut()
+        .find(|e| e.id == id)
+        .ok_or("Query not found in history")?;
+    entry.pinned = false;
+    entry.label = None;
+    store::trim_query_history(&mut history);
     store::save_query_history(&app, &history)?;
     Ok(())
 }
 
+/// History grouped by literal-free fingerprint, with run counts and timings
 #[tauri::command]
-pub async fn get_plan_history(app: tauri::AppHandle) -> Result<Vec<PlanHistoryEntry>, String> {
-    store::get_plan_history(&app)
+pub async fn get_query_history_groups(
+    app: tauri::AppHandle,
+) -> Result<Vec<QueryHistoryGroup>, AppError> {
+    let queries = store::get_query_history(&app)?;
+    let plans = store::get_plan_history(&app)?;
+    Ok(history_groups::group_history(&queries, &plans))
 }
 
 #[tauri::command]
-pub async fn save_plan_history_entry(
-    entry: PlanHistoryEntry,
+pub async fn get_favorite_queries(
+    app: tauri::AppHandle,
+) -> Result<Vec<QueryHistoryEntry>, AppError> {
+    Ok(store::get_query_history(&app)?
+        .into_iter()
+        .filter(|e| e.pinned)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn save_snippet(
+    request: SaveSnippetRequest,
+    app: tauri::AppHandle,
+) -> Result<Snippet, AppError> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Snippet name cannot be empty".into());
+    }
+    let folder = snippets::normalize_folder(request.folder.as_deref());
+    let placeholders = snippets::placeholders(&request.sql);
+
+    let mut all = store::get_snippets(&app)?;
+    let snippet = match request.id {
+        Some(id) => {
+            let existing = all
+                .iter_mut()
+                .find(|s| s.id == id)
+                .ok_or("Snippet not found")?;
+            existing.name = name;
+            existing.folder = folder;
+            existing.sql = request.sql;
+            existing.description = request.description;
+            existing.placeholders = placeholders;
+            existing.updated_at = Utc::now();
+            existing.clone()
+        }
+        None => {
+            let now = Utc::now();
+            let snippet = Snippet {
+                id: Uuid::new_v4().to_string(),
+                name,
+                folder,
+                sql: request.sql,
+                description: request.description,
+                placeholders,
+                created_at: now,
+                updated_at: now,
+            };
+            all.push(snippet.clone());
+            snippet
+        }
+    };
+    store::save_snippets(&app, &all)?;
+    Ok(snippet)
+}
+
+/// Snippets sorted by folder and name; `folder` limits the list to that
+/// folder and its subfolders
+#[tauri::command]
+pub async fn list_snippets(
+    folder: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<Vec<Snippet>, AppError> {
+    let folder = snippets::normalize_folder(folder.as_deref());
+    let mut all: Vec<Snippet> = store::get_snippets(&app)?
+        .into_iter()
+        .filter(|s| match (&folder, &s.folder) {
+            (None, _) => true,
+            (Some(wanted), Some(f)) => f == wanted || f.starts_with(&format!("{}/", wanted)),
+            (Some(_), None) => false,
+        })
+        .collect();
+    all.sort_by(|a, b| {
+        (a.folder.as_deref().unwrap_or(""), a.name.to_lowercase())
+            .cmp(&(b.folder.as_deref().unwrap_or(""), b.name.to_lowercase()))
+    });
+    Ok(all)
+}
+
+#[tauri::command]
+pub async fn list_snippet_folders(app: tauri::AppHandle) -> Result<Vec<String>, AppError> {
+    let mut folders: Vec<String> = store::get_snippets(&app)?
+        .into_iter()
+        .filter_map(|s| s.folder)
+        .collect();
+    folders.sort();
+    folders.dedup();
+    Ok(folders)
+}
+
+/// Move every snippet in `from` (and its subfolders) under `to`
+#[tauri::command]
+pub async fn rename_snippet_folder(
+    from: String,
+    to: String,
+    app: tauri::AppHandle,
+) -> Result<(), AppError> {
+    let from = snippets::normalize_folder(Some(&from)).ok_or("Folder name cannot be empty")?;
+    let to = snippets::normalize_folder(Some(&to)).ok_or("Folder name cannot be empty")?;
+    let mut all = store::get_snippets(&app)?;
+    for snippet in &mut all {
+        let Some(folder) = &snippet.folder else {
+            continue;
+        };
+        if *folder == from {
+            snippet.folder = Some(to.clone());
+        } else if let Some(rest) = folder.strip_prefix(&format!("{}/", from)) {
+            snippet.folder = Some(format!("{}/{}", to, rest));
+        }
+    }
+    Ok(store::save_snippets(&app, &all)?)
+}
+
+#[tauri::command]
+pub async fn delete_snippet(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut all = store::get_snippets(&app)?;
+    let before = all.len();
+    all.retain(|s| s.id != id);
+    if all.len() == before {
+        return Err("Snippet not found".into());
+    }
+    Ok(store::save_snippets(&app, &all)?)
+}
+
+/// Snippet SQL with its placeholders filled in, ready to run
+#[tauri::command]
+pub async fn render_snippet(
+    id: String,
+    values: HashMap<String, String>,
     app: tauri::AppHandle,
-) -> Result<(), String> {
+) -> Result<String, AppError> {
+    let snippet = store::get_snippets(&app)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or("Snippet not found")?;
+    Ok(snippets::render(&snippet.sql, &values)?)
+}
+
+/// Everything the app stores, encrypted with `passphrase`, as archive text
+/// for the frontend to write to a file
+#[tauri::command]
+pub async fn export_app_data(
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, AppError> {
+    let key = password_key(&app, &state).await?;
+    let connections = store::get_connections(&app)?
+        .into_iter()
+        .map(|mut config| {
+            let password = encryption::decrypt_password_with(&key, &config.encrypted_password)
+                .map_err(|_| format!("Cannot decrypt the password of '{}'", config.name))?;
+            config.encrypted_password = String::new();
+            Ok(BackupConnection { config, password })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let data = AppDataBackup {
+        exported_at: Utc::now(),
+        connections,
+        groups: store::get_connection_groups(&app)?,
+        query_history: store::get_query_history(&app)?,
+        plan_history: store::get_plan_history(&app)?,
+        snippets: store::get_snippets(&app)?,
+        settings: settings::load(&app)?,
+    };
+    Ok(backup::seal(&data, &passphrase)?)
+}
+
+/// Merge an exported archive into this machine's data. Items with the same
+/// id are replaced; settings are taken from the archive.
+#[tauri::command]
+pub async fn import_app_data(
+    archive: String,
+    passphrase: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<AppDataImportSummary, AppError> {
+    let data = backup::open(&archive, &passphrase)?;
+    let key = password_key(&app, &state).await?;
+
+    let imported_connections = data
+        .connections
+        .into_iter()
+        .map(|c| {
+            let mut config = c.config;
+            config.encrypted_password = encryption::encrypt_password_with(&key, &c.password)?;
+            Ok(config)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut connections = store::get_connections(&app)?;
+    let connection_count = backup::merge_by_id(&mut connections, imported_connections, |c| &c.id);
+
+    let mut groups = store::get_connection_groups(&app)?;
+    let group_count = backup::merge_by_id(&mut groups, data.groups, |g| &g.id);
+
+    let mut queries = store::get_query_history(&app)?;
+    let query_count = backup::merge_by_id(&mut queries, data.query_history, |q| &q.id);
+    queries.sort_by_key(|q| std::cmp::Reverse(q.executed_at));
+    store::trim_query_history(&mut queries);
+
+    let mut plans = store::get_plan_history(&app)?;
+    let plan_count = backup::merge_by_id(&mut plans, data.plan_history, |p| &p.id);
+    plans.sort_by_key(|p| std::cmp::Reverse(p.executed_at));
+    plans.truncate(50);
+
+    let mut snippets = store::get_snippets(&app)?;
+    let snippet_count = backup::merge_by_id(&mut snippets, data.snippets, |s| &s.id);
+
+    store::save_connection_groups(&app, &groups)?;
+    store::save_connections(&app, &connections)?;
+    store::save_query_history(&app, &queries)?;
+    store::save_plan_history(&app, &plans)?;
+    store::save_snippets(&app, &snippets)?;
+    settings::save(&app, &data.settings)?;
+
+    Ok(AppDataImportSummary {
+        connections: connection_count,
+        groups: group_count,
+        queries: query_count,
+        plans: plan_count,
+        snippets: snippet_count,
+    })
+}
+
+#[tauri::command]
+pub async fn get_plan_history(app: tauri::AppHandle) -> Result<Vec<PlanHistoryEntry>, AppError> {
     let mut history = store::get_plan_history(&app)?;
-    history.insert(0, entry);
-    if history.len() > 50 {
-        history.truncate(50);
+    // Entries saved before plan hashes existed
+    for entry in history.iter_mut().filter(|e| e.plan_hash.is_none()) {
+        entry.plan_hash = Some(query_hash::plan_hash(&entry.plan_xml));
+    }
+    Ok(history)
+}
+
+/// Attach a note to a plan, or to one of its operators
+#[tauri::command]
+pub async fn add_plan_annotation(
+    request: AddPlanAnnotationRequest,
+    app: tauri::AppHandle,
+) -> Result<PlanAnnotation, AppError> {
+    let text = request.text.trim().to_string();
+    if text.is_empty() {
+        return Err("Annotation text cannot be empty".into());
     }
-    store::save_plan_history(&app, &history)?;
+    let annotation = PlanAnnotation {
+        id: Uuid::new_v4().to_string(),
+        plan_hash: request.plan_hash,
+        plan_id: request.plan_id,
+        node_id: request.node_id,
+        text,
+        created_at: Utc::now(),
+    };
+    let mut all = store::get_plan_annotations(&app)?;
+    all.push(annotation.clone());
+    store::save_plan_annotations(&app, &all)?;
+    Ok(annotation)
+}
+
+/// Notes on plans with this hash, oldest first; `node_id` keeps those on
+/// that operator only
+#[tauri::command]
+pub async fn get_plan_annotations(
+    plan_hash: String,
+    node_id: Option<i64>,
+    app: tauri::AppHandle,
+) -> Result<Vec<PlanAnnotation>, AppError> {
+    let mut annotations: Vec<PlanAnnotation> = store::get_plan_annotations(&app)?
+        .into_iter()
+        .filter(|a| a.plan_hash == plan_hash && (node_id.is_none() || a.node_id == node_id))
+        .collect();
+    annotations.sort_by_key(|a| a.created_at);
+    Ok(annotations)
+}
+
+/// Zip a plan with its query, analysis findings, environment and
+/// annotations; returned base64-encoded for the frontend to save
+#[tauri::command]
+pub async fn export_plan_bundle(
+    request: ExportPlanBundleRequest,
+    app: tauri::AppHandle,
+) -> Result<String, AppError> {
+    let bytes = plan_bundle::write(&plan_bundle::collect(&app, request)?)?;
+    Ok(BASE64.encode(bytes))
+}
+
+/// Open a bundle from export_plan_bundle; its annotations are added to this
+/// machine's so they show on the plan
+#[tauri::command]
+pub async fn import_plan_bundle(
+    data: String,
+    app: tauri::AppHandle,
+) -> Result<PlanBundle, AppError> {
+    let bytes = BASE64
+        .decode(data.trim())
+        .map_err(|_| "Not a SqlPlanForDummies plan bundle")?;
+    let bundle = plan_bundle::read(&bytes)?;
+    if !bundle.annotations.is_empty() {
+        let mut all = store::get_plan_annotations(&app)?;
+        backup::merge_by_id(&mut all, bundle.annotations.clone(), |a| &a.id);
+        store::save_plan_annotations(&app, &all)?;
+    }
+    Ok(bundle)
+}
+
+#[tauri::command]
+pub async fn delete_plan_annotation(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut all = store::get_plan_annotations(&app)?;
+    let before = all.len();
+    all.retain(|a| a.id != id);
+    if all.len() == before {
+        return Err("Annotation not found".into());
+    }
+    Ok(store::save_plan_annotations(&app, &all)?)
+}
+
+/// Keep every query run from now on, with its plan and timing, until the
+/// recording is stopped
+#[tauri::command]
+pub async fn start_baseline_recording(
+    name: String,
+    recorder: tauri::State<'_, BaselineRecorder>,
+) -> Result<BaselineSummary, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Baseline name is required".into());
+    }
+    Ok(recorder.start(name)?)
+}
+
+/// The recording in progress, if any
+#[tauri::command]
+pub async fn get_baseline_recording(
+    recorder: tauri::State<'_, BaselineRecorder>,
+) -> Result<Option<BaselineSummary>, AppError> {
+    Ok(recorder.status())
+}
+
+/// End the recording and save it as a baseline
+#[tauri::command]
+pub async fn stop_baseline_recording(
+    app: tauri::AppHandle,
+    recorder: tauri::State<'_, BaselineRecorder>,
+) -> Result<BaselineSummary, AppError> {
+    let recorded = recorder.stop().ok_or("No baseline is being recorded")?;
+    let summary = baseline::summarize(&recorded);
+    let mut baselines = store::get_baselines(&app)?;
+    baselines.push(recorded);
+    store::save_baselines(&app, &baselines)?;
+    Ok(summary)
+}
+
+#[tauri::command]
+pub async fn list_baselines(app: tauri::AppHandle) -> Result<Vec<BaselineSummary>, AppError> {
+    Ok(store::get_baselines(&app)?
+        .iter()
+        .map(baseline::summarize)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn delete_baseline(id: String, app: tauri::AppHandle) -> Result<(), AppError> {
+    let mut baselines = store::get_baselines(&app)?;
+    baselines.retain(|b| b.id != id);
+    Ok(store::save_baselines(&app, &baselines)?)
+}
+
+/// Compare a later recording (`session_id`) with a baseline query by query
+#[tauri::command]
+pub async fn compare_baselines(
+    baseline_id: String,
+    session_id: String,
+    app: tauri::AppHandle,
+) -> Result<BaselineComparison, AppError> {
+    let baselines = store::get_baselines(&app)?;
+    let find = |id: &str| {
+        baselines
+            .iter()
+            .find(|b| b.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Baseline not found: {}", id))
+    };
+    let (before, after) = (find(&baseline_id)?, find(&session_id)?);
+    // Parsing and diffing the plans is CPU work
+    tokio::task::spawn_blocking(move || baseline::compare(&before, &after))
+        .await
+        .map_err(|e| AppError::new(format!("Baseline comparison failed: {}", e)))
+}
+
+#[tauri::command]
+pub async fn list_scheduled_queries(
+    app: tauri::AppHandle,
+) -> Result<Vec<ScheduledQuery>, AppError> {
+    Ok(store::get_scheduled_queries(&app)?)
+}
+
+/// Create or update a schedule and (re)start its task; the first run is one
+/// interval from now
+#[tauri::command]
+pub async fn save_scheduled_query(
+    request: SaveScheduledQueryRequest,
+    app: tauri::AppHandle,
+    scheduler: tauri::State<'_, Scheduler>,
+) -> Result<ScheduledQuery, AppError> {
+    if !store::get_connections(&app)?
+        .iter()
+        .any(|c| c.id == request.connection_id)
+    {
+        return Err("Scheduled queries need a saved connection".into());
+    }
+    let mut schedules = store::get_scheduled_queries(&app)?;
+    let schedule = match &request.id {
+        Some(id) => schedules
+            .iter_mut()
+            .find(|s| &s.id == id)
+            .ok_or("Scheduled query not found")?,
+        None => {
+            schedules.push(ScheduledQuery {
+                id: Uuid::new_v4().to_string(),
+                name: String::new(),
+                sql: String::new(),
+                connection_id: String::new(),
+                plan_type: PlanType::Estimated,
+                interval_minutes: 0,
+                enabled: false,
+                created_at: Utc::now(),
+                last_run_at: None,
+                last_error: None,
+            });
+            schedules.last_mut().unwrap()
+        }
+    };
+    schedule.name = request.name.trim().to_string();
+    schedule.sql = request.sql;
+    schedule.connection_id = request.connection_id;
+    schedule.plan_type = request.plan_type;
+    schedule.interval_minutes = request.interval_minutes;
+    schedule.enabled = request.enabled;
+    scheduler::validate(schedule)?;
+    let saved = schedule.clone();
+
+    store::save_scheduled_queries(&app, &schedules)?;
+    scheduler.start(&app, &saved);
+    Ok(saved)
+}
+
+#[tauri::command]
+pub async fn delete_scheduled_query(
+    id: String,
+    app: tauri::AppHandle,
+    scheduler: tauri::State<'_, Scheduler>,
+) -> Result<(), AppError> {
+    scheduler.stop(&id);
+    let mut schedules = store::get_scheduled_queries(&app)?;
+    schedules.retain(|s| s.id != id);
+    Ok(store::save_scheduled_queries(&app, &schedules)?)
+}
+
+/// Run a schedule now, outside its interval
+#[tauri::command]
+pub async fn run_scheduled_query(
+    id: String,
+    app: tauri::AppHandle,
+) -> Result<ScheduledRunEvent, AppError> {
+    Ok(scheduler::run(&app, &id).await?)
+}
+
+/// Shape preview of a saved plan for the history list, rendered on first
+/// request and cached
+#[tauri::command]
+pub async fn render_plan_thumbnail(
+    plan_id: String,
+    format: Option<PlanImageFormat>,
+    app: tauri::AppHandle,
+) -> Result<PlanThumbnail, AppError> {
+    let format = format.unwrap_or(PlanImageFormat::Svg);
+    if let Some(cached) = store::get_plan_thumbnail(&app, &plan_id, format)? {
+        return Ok(cached);
+    }
+
+    let history = store::get_plan_history(&app)?;
+    let plan_xml = history
+        .iter()
+        .find(|p| p.id == plan_id)
+        .map(|p| p.plan_xml.clone())
+        .ok_or_else(|| format!("Plan not found: {}", plan_id))?;
+    let thumbnail = tokio::task::spawn_blocking(move || {
+        plan::render::render_thumbnail(plan_id, &plan_xml, format)
+    })
+    .await
+    .map_err(|e| format!("Plan rendering failed: {}", e))??;
+
+    let plan_ids: HashSet<&str> = history.iter().map(|p| p.id.as_str()).collect();
+    store::save_plan_thumbnail(&app, &thumbnail, &plan_ids)?;
+    Ok(thumbnail)
+}
+
+/// Add a plan to history; it is written to disk in the background
+#[tauri::command]
+pub async fn save_plan_history_entry(
+    mut entry: PlanHistoryEntry,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    writer: tauri::State<'_, PlanHistoryWriter>,
+) -> Result<(), AppError> {
+    if entry.context.is_none() {
+        entry.context = capture_execution_context(&entry.connection_id, &state).await;
+    }
+    record_plan(&app, &writer, entry).await?;
     Ok(())
 }
+
+/// Hash the plan, compare it with the query's previous plan and add it to
+/// history, announcing a regression. Returns the regression found.
+pub(super) async fn record_plan(
+    app: &tauri::AppHandle,
+    writer: &PlanHistoryWriter,
+    mut entry: PlanHistoryEntry,
+) -> Result<Option<PlanRegression>, AppError> {
+    // Reading and writing the store decompresses and compresses plans, so it
+    // runs off the async runtime
+    let handle = app.clone();
+    let query_id = entry.query_id.clone();
+    let sql_preview = entry.sql_preview.clone();
+    let plan_xml = entry.plan_xml.clone();
+    let (sql_hash, fingerprint, plan_hash, previous) =
+        tokio::task::spawn_blocking(move || -> Result<_, String> {
+            // The preview is truncated, so prefer the full SQL of the query entry
+            let query_sql = store::get_query_history(&handle)?
+                .into_iter()
+                .find(|q| q.id == query_id)
+                .map(|q| q.sql);
+            let sql = query_sql.as_deref().unwrap_or(&sql_preview);
+            let sql_hash = query_hash::sql_hash(sql);
+            // History is newest first, so this is the plan the query had last time
+            let previous = store::latest_plan_for(&handle, &sql_hash)?;
+            Ok((
+                sql_hash,
+                query_hash::fingerprint(sql),
+                query_hash::plan_hash(&plan_xml),
+                previous,
+            ))
+        })
+        .await
+        .map_err(|e| format!("Saving the plan failed: {}", e))??;
+    entry.sql_hash = Some(sql_hash);
+    entry.fingerprint = Some(fingerprint);
+    entry.plan_hash = Some(plan_hash);
+
+    if let Some(previous) = &previous {
+        entry.regression = detect_regression(previous, &entry).await;
+    }
+    let event = entry
+        .regression
+        .clone()
+        .map(|regression| PlanRegressionEvent {
+            plan_id: entry.id.clone(),
+            query_id: entry.query_id.clone(),
+            sql_preview: entry.sql_preview.clone(),
+            regression,
+        });
+
+    let retention = settings::load(app)?.plan_retention;
+    let regression = entry.regression.clone();
+    let handle = app.clone();
+    let evicted = tokio::task::spawn_blocking(move || {
+        store::push_plan_history_entry(&handle, &entry, &retention)
+    })
+    .await
+    .map_err(|e| format!("Saving the plan failed: {}", e))??;
+    if evicted > 0 {
+        tracing::debug!(evicted, "Evicted plans from history");
+    }
+    writer.schedule();
+
+    if let Some(event) = event {
+        let _ = app.emit("plan-regression", &event);
+    }
+    Ok(regression)
+}
+
+/// Parsing both plans can take a while for big batches; run it off the async
+/// runtime. Plans that fail to parse are never reported as regressions.
+async fn detect_regression(
+    previous: &PlanHistoryEntry,
+    current: &PlanHistoryEntry,
+) -> Option<PlanRegression> {
+    let previous_id = previous.id.clone();
+    let previous_xml = previous.plan_xml.clone();
+    let current_xml = current.plan_xml.clone();
+    tokio::task::spawn_blocking(move || {
+        let before = plan::parser::parse_plan(&previous_xml).ok()?;
+        let after = plan::parser::parse_plan(&current_xml).ok()?;
+        plan::regression::detect(&previous_id, &before, &after)
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Environment of the active connection, if it is the one the plan came from.
+/// Failures only cost the context, never the history entry.
+async fn capture_execution_context(
+    connection_id: &str,
+    state: &AppState,
+) -> Option<ExecutionContext> {
+    let conn = state.active_connection().await.ok()?;
+    if conn
+        .saved_connection_id
+        .as_deref()
+        .is_some_and(|id| id != connection_id)
+    {
+        return None;
+    }
+    let mut client = conn.client.lock().await;
+    exec_context::capture(&mut client)
+        .await
+        .map_err(|e| tracing::warn!(error = %e, "Failed to capture execution context"))
+        .ok()
+}
+
+/// All saved plans of the same query (by normalized SQL), oldest first.
+/// With `across_literals` plans of runs with other literal values count too.
+#[tauri::command]
+pub async fn get_plans_for_query(
+    query_id: String,
+    across_literals: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<Vec<PlanHistoryEntry>, AppError> {
+    let queries = store::get_query_history(&app)?;
+    let plans = store::get_plan_history(&app)?;
+    let across_literals = across_literals.unwrap_or(false);
+
+    let key_of_query = |q: &QueryHistoryEntry| {
+        if across_literals {
+            q.fingerprint
+                .clone()
+                .unwrap_or_else(|| query_hash::fingerprint(&q.sql))
+        } else {
+            q.sql_hash
+                .clone()
+                .unwrap_or_else(|| query_hash::sql_hash(&q.sql))
+        }
+    };
+    let key_of_plan = |p: &PlanHistoryEntry| {
+        if across_literals {
+            p.fingerprint.clone()
+        } else {
+            p.sql_hash.clone()
+        }
+    };
+    let key = queries
+        .iter()
+        .find(|q| q.id == query_id)
+        .map(key_of_query)
+        .or_else(|| {
+            plans
+                .iter()
+                .find(|p| p.query_id == query_id)
+                .and_then(key_of_plan)
+        })
+        .ok_or("Query not found in history")?;
+
+    let query_ids: HashSet<&str> = queries
+        .iter()
+        .filter(|q| key_of_query(q) == key)
+        .map(|q| q.id.as_str())
+        .collect();
+
+    let mut matching: Vec<PlanHistoryEntry> = plans
+        .iter()
+        .filter(|p| {
+            key_of_plan(p).as_deref() == Some(key.as_str())
+                || query_ids.contains(p.query_id.as_str())
+        })
+        .cloned()
+        .collect();
+    matching.sort_by_key(|p| p.executed_at);
+    Ok(matching)
+}
+
+/// Completion metadata of the current database, from the connection's
+/// metadata cache while it is fresh. `refresh` reloads it from scratch.
+#[tauri::command]
+pub async fn get_completion_metadata(
+    refresh: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<CompletionMetadata, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    let load = if refresh.unwrap_or(false) {
+        CompletionLoad::Full
+    } else {
+        CompletionLoad::IfStale
+    };
+    Ok(conn.metadata_cache.completion(&mut client, load).await?)
+}
+
+/// Re-reads only objects created or altered since the cached load for the
+/// current database, falling back to a full load when nothing is cached yet.
+#[tauri::command]
+pub async fn refresh_completion_metadata(
+    state: tauri::State<'_, AppState>,
+) -> Result<CompletionMetadata, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    conn.metadata_cache
+        .completion(&mut client, CompletionLoad::Incremental)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Drop cached metadata of the current database (every database when
+/// `all_databases` is set) and load the current one again
+#[tauri::command]
+pub async fn refresh_metadata(
+    all_databases: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<CompletionMetadata, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    if all_databases.unwrap_or(false) {
+        conn.metadata_cache.clear(None).await;
+    } else {
+        let database = completion::current_database(&mut client).await?;
+        conn.metadata_cache.clear(Some(&database)).await;
+    }
+    conn.metadata_cache
+        .completion(&mut client, CompletionLoad::Full)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Snapshot server wait stats and return what accumulated since the previous
+/// call on this connection. The new snapshot becomes the next baseline, so
+/// calling before and after a query brackets its waits.
+#[tauri::command]
+pub async fn get_wait_stats(
+    reset: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<WaitStatsDelta, AppError> {
+    let conn = state.active_connection().await?;
+    let current = conn
+        .monitor
+        .run(|client| Box::pin(wait_stats::snapshot(client)))
+        .await?;
+
+    let mut baseline = conn.wait_stats_baseline.lock().await;
+    if reset.unwrap_or(false) {
+        *baseline = None;
+    }
+    let delta = wait_stats::delta(baseline.as_ref(), &current);
+    *baseline = Some(current);
+    Ok(delta)
+}
+
+/// Rewrite a script with the selected hints and, when a plan type is given,
+/// capture the plan before and after for side-by-side comparison
+#[tauri::command]
+pub async fn apply_hints(
+    request: ApplyHintsRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<ApplyHintsResult, AppError> {
+    let sql = hints::apply_hints(&request.sql, &request.hints)?;
+
+    let plan_type = match request.plan_type {
+        Some(PlanType::None) | None => {
+            return Ok(ApplyHintsResult {
+                sql,
+                original_plan_xml: None,
+                hinted_plan_xml: None,
+                comparison: None,
+            })
+        }
+        Some(plan_type) => plan_type,
+    };
+
+    let conn = state.active_connection().await?;
+    let original_plan_xml = conn.execute_query(&request.sql, &plan_type).await?.plan_xml;
+    let hinted_plan_xml = conn.execute_query(&sql, &plan_type).await?.plan_xml;
+
+    let comparison = match (&original_plan_xml, &hinted_plan_xml) {
+        (Some(before), Some(after)) => Some(plan::compare::compare(
+            &plan::parser::parse_plan_async(before.clone()).await?,
+            &plan::parser::parse_plan_async(after.clone()).await?,
+        )),
+        _ => None,
+    };
+    Ok(ApplyHintsResult {
+        sql,
+        original_plan_xml,
+        hinted_plan_xml,
+        comparison,
+    })
+}
+
+/// Capture the estimated plan under other compatibility levels and the
+/// legacy CE, each compared with the plan under the current level. Levels
+/// are forced with USE HINT unless ALTER DATABASE is allowed and permitted,
+/// in which case the original level is always restored.
+#[tauri::command]
+pub async fn compare_across_compat_levels(
+    request: CompatComparisonRequest,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<CompatComparisonResult, AppError> {
+    let conn = state.active_connection().await?;
+    let levels = {
+        let mut client = conn.client.lock().await;
+        compat_levels::database_levels(&mut client).await?
+    };
+    let variants =
+        compat_levels::plan_variants(&request.levels, &levels, request.include_legacy_ce)?;
+    let alter_database = request.allow_alter_database
+        && levels.can_alter
+        && !conn.modifications_blocked()
+        && profile::require(&app, Capability::AlterDatabase).is_ok();
+
+    let baseline_plan_xml = conn
+        .execute_query(&request.sql, &PlanType::Estimated)
+        .await?
+        .plan_xml;
+    let baseline = match baseline_plan_xml.clone() {
+        Some(xml) => Some(plan::parser::parse_plan_async(xml).await?),
+        None => None,
+    };
+
+    let mut results = Vec::with_capacity(variants.len());
+    for variant in variants {
+        let method = if alter_database && variant.level != levels.current {
+            CompatMethod::AlterDatabase
+        } else {
+            CompatMethod::UseHint
+        };
+        let captured =
+            capture_compat_variant(&conn, &request.sql, &variant, levels.current, method).await;
+        let (plan_xml, error) = match captured {
+            Ok(xml) => (xml, None),
+            Err(e) => (None, Some(e.into())),
+        };
+        let parsed = match plan_xml.clone() {
+            Some(xml) => plan::parser::parse_plan_async(xml).await.ok(),
+            None => None,
+        };
+        results.push(CompatPlanVariant {
+            label: variant.label(),
+            compatibility_level: variant.level,
+            legacy_ce: variant.legacy_ce,
+            method,
+            estimated_cost: parsed.as_ref().map(plan_cost),
+            comparison: baseline
+                .as_ref()
+                .zip(parsed.as_ref())
+                .map(|(before, after)| plan::compare::compare(before, after)),
+            plan_xml,
+            error,
+        });
+    }
+
+    Ok(CompatComparisonResult {
+        current_level: levels.current,
+        server_max_level: levels.server_max,
+        fell_back_to_use_hint: request.allow_alter_database && !alter_database,
+        baseline_cost: baseline.as_ref().map(plan_cost),
+        baseline_plan_xml,
+        variants: results,
+    })
+}
+
+async fn capture_compat_variant(
+    conn: &DbConnection,
+    sql: &str,
+    variant: &compat_levels::Variant,
+    current_level: u16,
+    method: CompatMethod,
+) -> Result<Option<String>, AppError> {
+    let use_hints =
+        compat_levels::use_hints(variant, current_level, method == CompatMethod::UseHint);
+    let sql = if use_hints.is_empty() {
+        sql.to_string()
+    } else {
+        hints::apply_hints(
+            sql,
+            &QueryHints {
+                recompile: false,
+                maxdop: None,
+                force_order: false,
+                use_hints,
+                table_hints: Vec::new(),
+            },
+        )?
+    };
+
+    if method == CompatMethod::UseHint {
+        return Ok(conn
+            .execute_query(&sql, &PlanType::Estimated)
+            .await?
+            .plan_xml);
+    }
+
+    compat_levels::set_level(&mut *conn.client.lock().await, variant.level).await?;
+    let result = conn.execute_query(&sql, &PlanType::Estimated).await;
+    if let Err(e) = compat_levels::set_level(&mut *conn.client.lock().await, current_level).await {
+        tracing::error!(error = %e, level = current_level, "Failed to restore the compatibility level");
+        return Err(format!(
+            "{}. The database may still be at level {}.",
+            e, variant.level
+        )
+        .into());
+    }
+    Ok(result?.plan_xml)
+}
+
+/// Why a query gets a different plan in the app than in SSMS: compile it
+/// under the session's SET options and under the SSMS defaults and compare
+#[tauri::command]
+pub async fn diagnose_plan_mismatch(
+    sql: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<PlanMismatchDiagnosis, AppError> {
+    let conn = state.active_connection().await?;
+    let options = set_options::session_options(&mut *conn.client.lock().await).await?;
+    let differences = set_options::differences(options);
+
+    let session_plan_xml = conn
+        .execute_query(&sql, &PlanType::Estimated)
+        .await?
+        .plan_xml;
+    let ssms_plan_xml = if differences.is_empty() {
+        session_plan_xml.clone()
+    } else {
+        set_options::apply(
+            &mut *conn.client.lock().await,
+            &set_options::set_batch(&differences, true),
+        )
+        .await?;
+        let result = conn.execute_query(&sql, &PlanType::Estimated).await;
+        if let Err(e) = set_options::apply(
+            &mut *conn.client.lock().await,
+            &set_options::set_batch(&differences, false),
+        )
+        .await
+        {
+            tracing::error!(error = %e, "Failed to restore SET options");
+            return Err(format!(
+                "{}. The session may still use the SSMS defaults; reconnect to reset it.",
+                e
+            )
+            .into());
+        }
+        result?.plan_xml
+    };
+
+    let session_plan = match session_plan_xml.clone() {
+        Some(xml) => Some(plan::parser::parse_plan_async(xml).await?),
+        None => None,
+    };
+    let ssms_plan = match ssms_plan_xml.clone() {
+        Some(xml) => Some(plan::parser::parse_plan_async(xml).await?),
+        None => None,
+    };
+    let plan_hashes = |plan: &Option<plan::types::ParsedPlan>| -> Vec<String> {
+        plan.iter()
+            .flat_map(|plan| &plan.statements)
+            .filter_map(|statement| statement.query_plan_hash.clone())
+            .collect()
+    };
+    let session_plan_hashes = plan_hashes(&session_plan);
+    let ssms_plan_hashes = plan_hashes(&ssms_plan);
+    let (verdict, explanation) =
+        set_options::verdict(&differences, &session_plan_hashes, &ssms_plan_hashes);
+
+    Ok(PlanMismatchDiagnosis {
+        session_options: exec_context::set_options_from_bitmask(options),
+        differences,
+        session_plan_hashes,
+        ssms_plan_hashes,
+        comparison: session_plan
+            .as_ref()
+            .zip(ssms_plan.as_ref())
+            .map(|(before, after)| plan::compare::compare(before, after)),
+        session_plan_xml,
+        ssms_plan_xml,
+        verdict,
+        explanation,
+    })
+}
+
+fn plan_cost(plan: &plan::types::ParsedPlan) -> f64 {
+    plan.statements
+        .iter()
+        .map(|statement| statement.statement_sub_tree_cost)
+        .sum()
+}
+
+/// Parameter sniffing diagnosis: run the query once per parameter set, each
+/// compiled afresh, and compare the actual plans
+#[tauri::command]
+pub async fn compare_plans_for_parameters(
+    request: ParameterSniffingRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<ParameterSniffingResult, AppError> {
+    let first_batch =
+        parameters::recompiled_batch(&request.sql, &request.parameters, ParameterSet::First)?;
+    let second_batch =
+        parameters::recompiled_batch(&request.sql, &request.parameters, ParameterSet::Second)?;
+
+    let conn = state.active_connection().await?;
+    let first = run_with_actual_plan(&conn, first_batch).await?;
+    let second = run_with_actual_plan(&conn, second_batch).await?;
+
+    let comparison = match (&first.plan_xml, &second.plan_xml) {
+        (Some(a), Some(b)) => Some(plan::sniffing::compare_runs(
+            &plan::parser::parse_plan_async(a.clone()).await?,
+            &plan::parser::parse_plan_async(b.clone()).await?,
+        )),
+        _ => None,
+    };
+    Ok(ParameterSniffingResult {
+        first,
+        second,
+        comparison,
+    })
+}
+
+async fn run_with_actual_plan(
+    conn: &DbConnection,
+    batch: String,
+) -> Result<ParameterRun, AppError> {
+    let result = conn.execute_query(&batch, &PlanType::Actual).await?;
+    Ok(ParameterRun {
+        batch,
+        plan_xml: result.plan_xml,
+        duration_ms: result.duration_ms,
+        rows_returned: result.result_sets.iter().map(|r| r.rows.len()).sum(),
+    })
+}
+
+/// Granted vs required vs used memory per statement of a plan. Actual plans
+/// carry every figure; for estimated plans the last execution of the same
+/// plan in sys.dm_exec_query_stats supplies the grant and the memory used.
+#[tauri::command]
+pub async fn get_memory_grant_info(
+    plan_xml: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<MemoryGrantReport, AppError> {
+    let plan = plan::parser::parse_plan_async(plan_xml).await?;
+    let missing_usage: Vec<String> = plan
+        .statements
+        .iter()
+        .filter(|s| {
+            s.memory_grant
+                .as_ref()
+                .is_some_and(|g| g.max_used_kb.is_none())
+        })
+        .filter_map(|s| s.query_plan_hash.clone())
+        .collect();
+
+    let mut cached = HashMap::new();
+    if !missing_usage.is_empty() {
+        if let Ok(conn) = state.active_connection().await {
+            let mut client = conn.client.lock().await;
+            cached = memory_grants::cached_grants(&mut client, &missing_usage).await?;
+        }
+    }
+    Ok(memory_grants::report(&plan, &cached))
+}
+
+/// Who is blocking whom right now: blocked sessions arranged under their lead
+/// blockers, with waits and the SQL on both sides
+#[tauri::command]
+pub async fn get_blocking_tree(
+    state: tauri::State<'_, AppState>,
+) -> Result<BlockingTree, AppError> {
+    let conn = state.active_connection().await?;
+    let own_session_id = conn.session_id();
+    conn.monitor
+        .run(|client| Box::pin(blocking::blocking_tree(client, own_session_id)))
+        .await
+        .map_err(AppError::from)
+}
+
+/// Stop the query running on the active connection. Its session is killed
+/// from the monitoring connection and reopened in the same database.
+#[tauri::command]
+pub async fn kill_running_query(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    let conn = state.active_connection().await?;
+    conn.kill_running_query().await
+}
+
+/// End another session on the server, e.g. the head of a blocking chain.
+/// Needs a profile that allows it.
+#[tauri::command]
+pub async fn kill_session(
+    session_id: i64,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    profile::require(&app, Capability::KillSessions)?;
+    let conn = state.active_connection().await?;
+    if conn.session_id() == Some(session_id) {
+        return Err("Use kill_running_query to stop the app's own session".into());
+    }
+    conn.monitor
+        .run(|client| Box::pin(monitor::kill_session(client, session_id)))
+        .await?;
+    tracing::warn!(session_id, "Killed a server session");
+    Ok(())
+}
+
+/// Force a Query Store plan for a query, or unforce it
+#[tauri::command]
+pub async fn force_plan(
+    query_id: i64,
+    plan_id: i64,
+    unforce: bool,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    profile::require(&app, Capability::ForcePlans)?;
+    let conn = state.active_connection().await?;
+    if conn.modifications_blocked() {
+        return Err(
+            "Forcing plans changes Query Store, which is blocked on a read-only connection".into(),
+        );
+    }
+    let mut client = conn.client.lock().await;
+    Ok(query_store::force_plan(&mut client, query_id, plan_id, !unforce).await?)
+}
+
+/// Fast approximate row count of a table or single-table SELECT, to warn
+/// before fetching a huge result
+#[tauri::command]
+pub async fn estimate_rowcount(
+    sql: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<RowCountEstimate, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    Ok(row_estimate::estimate_rowcount(&mut client, &sql).await?)
+}
+
+/// Pretty-print a script. Without explicit options the style saved in
+/// settings is used, so the editor and history display format alike.
+#[tauri::command]
+pub async fn format_sql(
+    sql: String,
+    options: Option<FormatOptions>,
+    app: tauri::AppHandle,
+) -> Result<String, AppError> {
+    let options = match options {
+        Some(options) => options,
+        None => settings::load(&app)?.format_options,
+    };
+    Ok(formatter::format_sql(&sql, &options))
+}
+
+/// A query with its literals, and optionally object names, replaced by
+/// placeholders, for pasting into a ticket or forum post
+#[tauri::command]
+pub async fn redact_query(sql: String, options: Option<RedactOptions>) -> Result<String, AppError> {
+    Ok(redact::redact_sql(&sql, options.unwrap_or_default()))
+}
+
+/// Instance-wide settings from sys.configurations
+#[tauri::command]
+pub async fn get_server_configuration(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ServerConfigOption>, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    configuration::server_configuration(&mut client).await
+}
+
+/// Whether each statement of the plan went parallel, explained against the
+/// connected server's cost threshold and MAXDOP
+#[tauri::command]
+pub async fn analyze_parallelism(
+    plan_xml: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<ParallelismReport, AppError> {
+    let plan = plan::parser::parse_plan_async(plan_xml).await?;
+    let settings = {
+        let conn = state.active_connection().await?;
+        let mut client = conn.client.lock().await;
+        configuration::parallelism_settings(&mut client).await?
+    };
+    Ok(plan::parallelism::analyze(&plan, &settings))
+}
+
+/// Compatibility level, statistics and scoped configuration of a database
+#[tauri::command]
+pub async fn get_database_options(
+    database: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<DatabaseOptions, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    configuration::database_options(&mut client, database.as_deref()).await
+}
+
+/// Most expensive statements in the plan cache, for the server dashboard
+#[tauri::command]
+pub async fn get_top_queries(
+    request: TopQueriesRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TopQuery>, AppError> {
+    let conn = state.active_connection().await?;
+    conn.monitor
+        .run(move |client| {
+            Box::pin(async move { query_stats::top_queries(client, &request).await })
+        })
+        .await
+        .map_err(AppError::from)
+}
+
+/// Plan the server has cached for a plan handle or for similar query text
+#[tauri::command]
+pub async fn get_cached_plan(
+    request: CachedPlanRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<CachedPlan>, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+
+    if let Some(handle) = request
+        .plan_handle
+        .as_deref()
+        .filter(|h| !h.trim().is_empty())
+    {
+        return Ok(query_stats::cached_plan_by_handle(&mut client, handle)
+            .await?
+            .into_iter()
+            .collect());
+    }
+    match request.query_text.as_deref() {
+        Some(text) => {
+            Ok(query_stats::cached_plans_by_text(&mut client, text, request.limit).await?)
+        }
+        None => Err("Provide a plan handle or query text".into()),
+    }
+}
+
+/// Objects referencing `object` and referenced by it, through SQL modules
+/// and foreign keys, as a graph `depth` levels deep (1 by default)
+#[tauri::command]
+pub async fn get_object_dependencies(
+    object: String,
+    depth: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<DependencyGraph, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    Ok(dependencies::object_graph(&mut client, &object, depth.unwrap_or(1)).await?)
+}
+
+/// Statistics objects of a table, to check for stale or sampled stats
+#[tauri::command]
+pub async fn get_statistics_info(
+    table: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<StatisticsInfo>, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    statistics::statistics_info(&mut client, &table).await
+}
+
+/// Histogram of one statistics object with keys typed by the column, to
+/// chart next to a predicate whose estimate looks wrong
+#[tauri::command]
+pub async fn get_stats_histogram(
+    table: String,
+    stats_name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<StatsHistogram, AppError> {
+    let conn = state.active_connection().await?;
+    let mut client = conn.client.lock().await;
+    statistics::stats_histogram(&mut client, &table, &stats_name).await
+}
+
+/// Leaf-level fragmentation of a table's indexes (or the whole database's)
+/// with REBUILD/REORGANIZE statements where the thresholds call for one
+#[tauri::command]
+pub async fn get_index_fragmentation(
+    request: FragmentationRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<FragmentationReport, AppError> {
+    let conn = state.active_connection().await?;
+    conn.monitor
+        .run(move |client| {
+            Box::pin(async move { fragmentation::index_fragmentation(client, &request).await })
+        })
+        .await
+        .map_err(AppError::from)
+}
+
+/// Rows and reserved/used/data/index space per user table, largest first;
+/// one table when `table` is given
+#[tauri::command]
+pub async fn get_table_sizes(
+    table: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TableSize>, AppError> {
+    let conn = state.active_connection().await?;
+    conn.monitor
+        .run(move |client| {
+            Box::pin(async move { sizes::table_sizes(client, table.as_deref()).await })
+        })
+        .await
+}
+
+/// Space per index and heap, largest first; one table's when `table` is given
+#[tauri::command]
+pub async fn get_index_sizes(
+    table: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<IndexSize>, AppError> {
+    let conn = state.active_connection().await?;
+    conn.monitor
+        .run(move |client| {
+            Box::pin(async move { sizes::index_sizes(client, table.as_deref()).await })
+        })
+        .await
+}
+
+/// Estimated plans and analyzer findings for every .sql file under a
+/// folder, one GO batch at a time. Batches are only compiled, never run, so
+/// one that needs a table an earlier batch would create reports an error.
+#[tauri::command]
+pub async fn analyze_folder(
+    path: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<FolderAnalysisReport, AppError> {
+    let folder = PathBuf::from(path.trim());
+    if !folder.is_dir() {
+        return Err(format!("Not a folder: {}", folder.display()).into());
+    }
+    let (paths, truncated) = folder_analysis::sql_files(&folder)?;
+    let conn = state.active_connection().await?;
+    let app_settings = settings::load(&app)?;
+    let rules = plan::rules::all_rules(&app_settings.custom_analysis_rules);
+
+    let mut files = Vec::with_capacity(paths.len());
+    for (index, file) in paths.iter().enumerate() {
+        let relative = file
+            .strip_prefix(&folder)
+            .unwrap_or(file)
+            .display()
+            .to_string();
+        let mut result = FolderFileResult {
+            path: relative.clone(),
+            batches: Vec::new(),
+            error: None,
+        };
+        let script = std::fs::read(file)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| folder_analysis::decode_script(&bytes));
+        match script {
+            Err(e) => result.error = Some(e),
+            Ok(script) => {
+                for span in splitter::split_batches(&script) {
+                    let sql = &script[span.start..span.end];
+                    let mut batch = FolderBatchResult {
+                        line: folder_analysis::line_of(&script, span.start),
+                        preview: timing::preview(sql),
+                        estimated_cost: None,
+                        issues: Vec::new(),
+                        error: None,
+                    };
+                    match conn.execute_query(sql, &PlanType::Estimated).await {
+                        Ok(QueryResult {
+                            plan_xml: Some(plan_xml),
+                            ..
+                        }) => match plan::parser::parse_plan_async(plan_xml).await {
+                            Ok(parsed) => {
+                                batch.estimated_cost = Some(
+                                    parsed
+                                        .statements
+                                        .iter()
+                                        .map(|s| s.statement_sub_tree_cost)
+                                        .sum(),
+                                );
+                                batch.issues = plan::rules::analyze(
+                                    &parsed,
+                                    &rules,
+                                    &app_settings.disabled_analysis_rules,
+                                )
+                                .statements
+                                .into_iter()
+                                .flat_map(|s| s.issues)
+                                .collect();
+                            }
+                            Err(e) => batch.error = Some(e),
+                        },
+                        Ok(_) => {}
+                        Err(e) => batch.error = Some(e.into()),
+                    }
+                    result.batches.push(batch);
+                }
+            }
+        }
+        files.push(result);
+        let _ = app.emit(
+            "folder-analysis-progress",
+            &FolderAnalysisProgress {
+                path: relative,
+                files_done: index + 1,
+                files_total: paths.len(),
+            },
+        );
+    }
+    Ok(folder_analysis::report(&folder, files, truncated))
+}
+
+/// Estimated plan with and without a hypothetical index, compared
+#[tauri::command]
+pub async fn what_if_index(
+    request: WhatIfIndexRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<WhatIfIndexResult, AppError> {
+    let conn = state.active_connection().await?;
+    if conn.modifications_blocked() {
+        return Err(
+            "Hypothetical indexes need DDL, which is blocked on a read-only connection".into(),
+        );
+    }
+
+    let original_plan_xml = conn
+        .execute_query(&request.sql, &PlanType::Estimated)
+        .await?
+        .plan_xml
+        .ok_or("No estimated plan returned for the query")?;
+
+    let (index_name, what_if_plan_xml) = {
+        let mut client = conn.client.lock().await;
+        what_if::plan_with_hypothetical_index(&mut client, &request).await?
+    };
+
+    let before = plan::parser::parse_plan_async(original_plan_xml.clone()).await?;
+    let after = plan::parser::parse_plan_async(what_if_plan_xml.clone()).await?;
+    Ok(WhatIfIndexResult {
+        index_used: plan::compare::uses_index(&after, &index_name),
+        comparison: plan::compare::compare(&before, &after),
+        index_name,
+        original_plan_xml,
+        what_if_plan_xml,
+    })
+}
@@ -0,0 +1,184 @@
+use super::connection::TiberiusClient;
+use super::rows::get_i64;
+
+/// Levels QUERY_OPTIMIZER_COMPATIBILITY_LEVEL_n accepts
+const KNOWN_LEVELS: &[u16] = &[100, 110, 120, 130, 140, 150, 160];
+
+/// EngineEdition of Azure SQL Database and Managed Instance, which support
+/// the newest level whatever ProductMajorVersion says
+const AZURE_EDITIONS: &[i64] = &[5, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatabaseLevels {
+    pub current: u16,
+    pub server_max: u16,
+    /// The login may ALTER the current database
+    pub can_alter: bool,
+}
+
+/// One compilation to compare with the plan under the current level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variant {
+    pub level: u16,
+    pub legacy_ce: bool,
+}
+
+impl Variant {
+    pub fn label(&self) -> String {
+        if self.legacy_ce {
+            format!("Level {}, legacy CE", self.level)
+        } else {
+            format!("Level {}", self.level)
+        }
+    }
+}
+
+/// Highest level of a SQL Server major version (13 = 2016 -> 130)
+pub fn max_level_for_version(major_version: i64) -> u16 {
+    let level = (major_version.clamp(10, 99) * 10) as u16;
+    level
+        .min(*KNOWN_LEVELS.last().unwrap())
+        .max(KNOWN_LEVELS[0])
+}
+
+/// Each requested level other than the current one, oldest first, then the
+/// legacy CE under the current level
+pub fn plan_variants(
+    levels: &[u16],
+    database: &DatabaseLevels,
+    include_legacy_ce: bool,
+) -> Result<Vec<Variant>, String> {
+    let mut wanted: Vec<u16> = levels.to_vec();
+    wanted.sort_unstable();
+    wanted.dedup();
+
+    let mut variants = Vec::new();
+    for level in wanted {
+        if !KNOWN_LEVELS.contains(&level) {
+            return Err(format!("{} is not a compatibility level", level));
+        }
+        if level > database.server_max {
+            return Err(format!(
+                "Compatibility level {} is not supported by this server (highest is {})",
+                level, database.server_max
+            ));
+        }
+        if level != database.current {
+            variants.push(Variant {
+                level,
+                legacy_ce: false,
+            });
+        }
+    }
+    if include_legacy_ce {
+        variants.push(Variant {
+            level: database.current,
+            legacy_ce: true,
+        });
+    }
+    if variants.is_empty() {
+        return Err(format!(
+            "Pick a compatibility level other than the current one ({}) or the legacy CE",
+            database.current
+        ));
+    }
+    Ok(variants)
+}
+
+/// USE HINT names that compile the query as `variant`; the level hint is
+/// left out when the database itself is switched
+pub fn use_hints(variant: &Variant, current: u16, level_by_hint: bool) -> Vec<String> {
+    let mut hints = Vec::new();
+    if level_by_hint && variant.level != current {
+        hints.push(format!(
+            "QUERY_OPTIMIZER_COMPATIBILITY_LEVEL_{}",
+            variant.level
+        ));
+    }
+    if variant.legacy_ce {
+        hints.push("FORCE_LEGACY_CARDINALITY_ESTIMATION".to_string());
+    }
+    hints
+}
+
+pub async fn database_levels(client: &mut TiberiusClient) -> Result<DatabaseLevels, String> {
+    let row = client
+        .simple_query(
+            "SELECT CAST(compatibility_level AS int), \
+                    CAST(CAST(SERVERPROPERTY('ProductMajorVersion') AS nvarchar(10)) AS int), \
+                    CAST(SERVERPROPERTY('EngineEdition') AS int), \
+                    HAS_PERMS_BY_NAME(DB_NAME(), 'DATABASE', 'ALTER') \
+             FROM sys.databases WHERE database_id = DB_ID()",
+        )
+        .await
+        .map_err(|e| format!("Failed to read the compatibility level: {}", e))?
+        .into_row()
+        .await
+        .map_err(|e| format!("Failed to read the compatibility level: {}", e))?
+        .ok_or("Failed to read the compatibility level")?;
+    let current = get_i64(&row, 0).ok_or("Failed to read the compatibility level")? as u16;
+    let server_max = if get_i64(&row, 2).is_some_and(|e| AZURE_EDITIONS.contains(&e)) {
+        *KNOWN_LEVELS.last().unwrap()
+    } else {
+        get_i64(&row, 1)
+            .map(max_level_for_version)
+            .unwrap_or(current)
+    };
+    Ok(DatabaseLevels {
+        current,
+        server_max: server_max.max(current),
+        can_alter: get_i64(&row, 3) == Some(1),
+    })
+}
+
+pub async fn set_level(client: &mut TiberiusClient, level: u16) -> Result<(), String> {
+    client
+        .simple_query(format!(
+            "ALTER DATABASE CURRENT SET COMPATIBILITY_LEVEL = {}",
+            level
+        ))
+        .await
+        .map_err(|e| format!("Failed to set compatibility level {}: {}", level, e))?
+        .into_results()
+        .await
+        .map_err(|e| format!("Failed to set compatibility level {}: {}", level, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DB: DatabaseLevels = DatabaseLevels {
+        current: 130,
+        server_max: 150,
+        can_alter: false,
+    };
+
+    #[test]
+    fn test_variants_skip_current_and_check_server() {
+        let variants = plan_variants(&[150, 130, 110, 150], &DB, true).unwrap();
+        let labels: Vec<String> = variants.iter().map(Variant::label).collect();
+        assert_eq!(
+            labels,
+            vec!["Level 110", "Level 150", "Level 130, legacy CE"]
+        );
+
+        assert!(plan_variants(&[160], &DB, false)
+            .unwrap_err()
+            .contains("highest is 150"));
+        assert!(plan_variants(&[130], &DB, false).is_err());
+
+        assert_eq!(
+            use_hints(&variants[1], 130, true),
+            vec!["QUERY_OPTIMIZER_COMPATIBILITY_LEVEL_150"]
+        );
+        assert!(use_hints(&variants[1], 130, false).is_empty());
+        assert_eq!(
+            use_hints(&variants[2], 130, true),
+            vec!["FORCE_LEGACY_CARDINALITY_ESTIMATION"]
+        );
+        assert_eq!(max_level_for_version(15), 150);
+        assert_eq!(max_level_for_version(17), 160);
+    }
+}
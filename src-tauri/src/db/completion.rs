@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{NaiveDateTime, Utc};
+use tiberius::Row;
+
+use super::connection::TiberiusClient;
+use super::rows::{get_datetime, get_i64, get_string};
+use super::types::{CompletionMetadata, CompletionObject, CompletionObjectKind};
+
+const OBJECT_FILTER: &str = "('U', 'V', 'P', 'FN', 'IF', 'TF') AND o.is_ms_shipped = 0";
+
+/// Cached completion metadata for one database on a connection.
+pub struct CompletionCacheEntry {
+    pub metadata: CompletionMetadata,
+    /// Server clock at load time; modify_date is compared against this on refresh
+    server_loaded_at: NaiveDateTime,
+}
+
+fn object_kind(type_code: &str) -> Option<CompletionObjectKind> {
+    match type_code.trim() {
+        "U" => Some(CompletionObjectKind::Table),
+        "V" => Some(CompletionObjectKind::View),
+        "P" => Some(CompletionObjectKind::Procedure),
+        "FN" | "IF" | "TF" => Some(CompletionObjectKind::Function),
+        _ => None,
+    }
+}
+
+fn objects_query(modified_since: Option<NaiveDateTime>) -> String {
+    format!(
+        "SELECT o.object_id, s.name, o.name, o.type \
+         FROM sys.objects o WITH (NOLOCK) \
+         JOIN sys.schemas s WITH (NOLOCK) ON o.schema_id = s.schema_id \
+         WHERE o.type IN {}{} \
+         ORDER BY s.name, o.name",
+        OBJECT_FILTER,
+        since_filter(modified_since)
+    )
+}
+
+fn columns_query(modified_since: Option<NaiveDateTime>) -> String {
+    format!(
+        "SELECT c.object_id, c.name, TYPE_NAME(c.user_type_id) \
+         FROM sys.columns c WITH (NOLOCK) \
+         JOIN sys.objects o WITH (NOLOCK) ON c.object_id = o.object_id \
+         WHERE o.type IN {}{} \
+         ORDER BY c.object_id, c.column_id",
+        OBJECT_FILTER,
+        since_filter(modified_since)
+    )
+}
+
+fn since_filter(modified_since: Option<NaiveDateTime>) -> String {
+    modified_since
+        .map(|t| format!(" AND o.modify_date > '{}'", t.format("%Y-%m-%dT%H:%M:%S%.3f")))
+        .unwrap_or_default()
+}
+
+async fn query_rows(client: &mut TiberiusClient, sql: &str) -> Result<Vec<Row>, String> {
+    client
+        .simple_query(sql)
+        .await
+        .map_err(|e| format!("Failed to query completion metadata: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read completion metadata: {}", e))
+}
+
+/// Current database name of the connection, used as the cache key.
+pub async fn current_database(client: &mut TiberiusClient) -> Result<String, String> {
+    let row = client
+        .simple_query("SELECT DB_NAME()")
+        .await
+        .map_err(|e| e.to_string())?
+        .into_row()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row.and_then(|r| get_string(&r, 0)).unwrap_or_default())
+}
+
+/// Load completion metadata from scratch, or — when `previous` is given — only
+/// re-read objects whose modify_date moved and drop objects that disappeared.
+pub async fn load(
+    client: &mut TiberiusClient,
+    previous: Option<&CompletionCacheEntry>,
+) -> Result<CompletionCacheEntry, String> {
+    let since = previous.map(|p| p.server_loaded_at);
+    let header = query_rows(client, "SELECT DB_NAME(), GETDATE()").await?;
+    let object_rows = query_rows(client, &objects_query(since)).await?;
+    let column_rows = query_rows(client, &columns_query(since)).await?;
+    let id_rows = query_rows(
+        client,
+        &format!(
+            "SELECT o.object_id FROM sys.objects o WITH (NOLOCK) WHERE o.type IN {}",
+            OBJECT_FILTER
+        ),
+    )
+    .await?;
+
+    let database = header.first().and_then(|r| get_string(r, 0)).unwrap_or_default();
+    let server_loaded_at = header
+        .first()
+        .and_then(|r| get_datetime(r, 1))
+        .unwrap_or_else(|| Utc::now().naive_utc());
+
+    let mut schemas: Vec<String> = previous
+        .map(|p| p.metadata.schemas.clone())
+        .unwrap_or_default();
+    let mut schema_index: HashMap<String, usize> = schemas
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.clone(), i))
+        .collect();
+
+    let mut fresh: Vec<CompletionObject> = Vec::new();
+    for row in &object_rows {
+        let (Some(object_id), Some(schema), Some(name), Some(type_code)) = (
+            get_i64(row, 0),
+            get_string(row, 1),
+            get_string(row, 2),
+            get_string(row, 3),
+        ) else {
+            continue;
+        };
+        let Some(kind) = object_kind(&type_code) else {
+            continue;
+        };
+        let schema = *schema_index.entry(schema.clone()).or_insert_with(|| {
+            schemas.push(schema);
+            schemas.len() - 1
+        });
+        fresh.push(CompletionObject {
+            object_id: object_id as i32,
+            schema,
+            name,
+            kind,
+            columns: Vec::new(),
+        });
+    }
+
+    let positions: HashMap<i32, usize> = fresh
+        .iter()
+        .enumerate()
+        .map(|(i, o)| (o.object_id, i))
+        .collect();
+    for row in &column_rows {
+        let Some(object_id) = get_i64(row, 0) else {
+            continue;
+        };
+        if let Some(&pos) = positions.get(&(object_id as i32)) {
+            let name = get_string(row, 1).unwrap_or_default();
+            let type_name = get_string(row, 2).unwrap_or_default();
+            fresh[pos].columns.push((name, type_name));
+        }
+    }
+
+    let existing_ids: HashSet<i32> = id_rows
+        .iter()
+        .filter_map(|r| get_i64(r, 0))
+        .map(|id| id as i32)
+        .collect();
+    let (schemas, objects) = merge(
+        previous.map(|p| &p.metadata),
+        schemas,
+        fresh,
+        &existing_ids,
+    );
+    let name_index = name_index(&objects);
+
+    Ok(CompletionCacheEntry {
+        metadata: CompletionMetadata {
+            database,
+            schemas,
+            objects,
+            name_index,
+            loaded_at: Utc::now(),
+        },
+        server_loaded_at,
+    })
+}
+
+/// Objects of the previous load that still exist and were not re-read, plus
+/// the re-read ones. Objects dropped since are left out, and so are schemas
+/// no object uses any more; schema indexes are renumbered to match.
+fn merge(
+    previous: Option<&CompletionMetadata>,
+    schemas: Vec<String>,
+    fresh: Vec<CompletionObject>,
+    existing_ids: &HashSet<i32>,
+) -> (Vec<String>, Vec<CompletionObject>) {
+    let fresh_ids: HashSet<i32> = fresh.iter().map(|o| o.object_id).collect();
+    let mut objects: Vec<CompletionObject> = previous
+        .map(|p| {
+            p.objects
+                .iter()
+                .filter(|o| existing_ids.contains(&o.object_id) && !fresh_ids.contains(&o.object_id))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    objects.extend(fresh);
+
+    let mut used: Vec<usize> = objects.iter().map(|o| o.schema).collect();
+    used.sort_unstable_by(|a, b| schemas[*a].cmp(&schemas[*b]));
+    used.dedup();
+    let renumbered: HashMap<usize, usize> = used.iter().enumerate().map(|(new, &old)| (old, new)).collect();
+    for object in &mut objects {
+        object.schema = renumbered[&object.schema];
+    }
+    let schemas: Vec<String> = used.into_iter().map(|i| schemas[i].clone()).collect();
+
+    objects.sort_by(|a, b| {
+        schemas[a.schema]
+            .cmp(&schemas[b.schema])
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    (schemas, objects)
+}
+
+/// Positions of `objects` ordered by lowercased name, so the editor finds
+/// every object starting with what was typed by binary search instead of
+/// scanning the whole list on each keystroke
+fn name_index(objects: &[CompletionObject]) -> Vec<usize> {
+    let names: Vec<String> = objects.iter().map(|o| o.name.to_lowercase()).collect();
+    let mut index: Vec<usize> = (0..objects.len()).collect();
+    index.sort_by(|a, b| names[*a].cmp(&names[*b]));
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(object_id: i32, schema: usize, name: &str) -> CompletionObject {
+        CompletionObject {
+            object_id,
+            schema,
+            name: name.to_string(),
+            kind: CompletionObjectKind::Table,
+            columns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_refresh_drops_removed_objects_and_schemas() {
+        let previous = CompletionMetadata {
+            database: "Sales".into(),
+            schemas: vec!["archive".into(), "dbo".into()],
+            objects: vec![object(1, 0, "OldOrders"), object(2, 1, "Orders"), object(3, 1, "Customers")],
+            name_index: Vec::new(),
+            loaded_at: Utc::now(),
+        };
+        // OldOrders and with it the archive schema are gone, Orders was
+        // altered and a table appeared in a new schema
+        let schemas = vec!["archive".into(), "dbo".into(), "staging".into()];
+        let fresh = vec![object(2, 1, "Orders"), object(4, 2, "Import")];
+        let existing: HashSet<i32> = [2, 3, 4].into();
+
+        let (schemas, objects) = merge(Some(&previous), schemas, fresh, &existing);
+        assert_eq!(schemas, vec!["dbo", "staging"]);
+        let names: Vec<(&str, &str)> = objects
+            .iter()
+            .map(|o| (schemas[o.schema].as_str(), o.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![("dbo", "Customers"), ("dbo", "Orders"), ("staging", "Import")]);
+    }
+
+    #[test]
+    fn test_name_index_orders_by_lowercased_name() {
+        let objects = vec![object(1, 0, "orders"), object(2, 1, "Customers"), object(3, 0, "OrderLines")];
+        assert_eq!(name_index(&objects), vec![1, 2, 0]);
+    }
+}
@@ -0,0 +1,86 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::Value;
+
+// Plan XML is highly repetitive; zstd shrinks it roughly tenfold. The store
+// keeps it base64-encoded under `planXmlZstd` in place of `planXml`, and
+// entries written before compression keep loading as they are.
+
+const LEVEL: i32 = 3;
+
+const PLAIN_FIELD: &str = "planXml";
+const COMPRESSED_FIELD: &str = "planXmlZstd";
+
+pub fn compress_text(text: &str) -> Result<String, String> {
+    let compressed = zstd::stream::encode_all(text.as_bytes(), LEVEL)
+        .map_err(|e| format!("Compression failed: {}", e))?;
+    Ok(BASE64.encode(compressed))
+}
+
+pub fn decompress_text(encoded: &str) -> Result<String, String> {
+    let compressed = BASE64
+        .decode(encoded)
+        .map_err(|e| format!("Invalid compressed data: {}", e))?;
+    let bytes = zstd::stream::decode_all(compressed.as_slice())
+        .map_err(|e| format!("Decompression failed: {}", e))?;
+    String::from_utf8(bytes).map_err(|e| format!("Invalid compressed data: {}", e))
+}
+
+/// Replace a plan history entry's plan XML with its compressed form
+pub fn pack_plan_entry(entry: &mut Value) -> Result<(), String> {
+    let Some(object) = entry.as_object_mut() else {
+        return Ok(());
+    };
+    if let Some(Value::String(xml)) = object.remove(PLAIN_FIELD) {
+        object.insert(
+            COMPRESSED_FIELD.to_string(),
+            Value::String(compress_text(&xml)?),
+        );
+    }
+    Ok(())
+}
+
+/// Undo `pack_plan_entry`; entries stored uncompressed pass through
+pub fn unpack_plan_entry(entry: &mut Value) -> Result<(), String> {
+    let Some(object) = entry.as_object_mut() else {
+        return Ok(());
+    };
+    if let Some(Value::String(encoded)) = object.remove(COMPRESSED_FIELD) {
+        object.insert(
+            PLAIN_FIELD.to_string(),
+            Value::String(decompress_text(&encoded)?),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plan_entry_round_trip() {
+        let xml = "<ShowPlanXML><BatchSequence /></ShowPlanXML>";
+        let mut entry = json!({ "id": "p1", "planXml": xml });
+
+        pack_plan_entry(&mut entry).unwrap();
+        assert!(entry.get("planXml").is_none());
+        assert!(entry["planXmlZstd"].is_string());
+
+        unpack_plan_entry(&mut entry).unwrap();
+        assert_eq!(entry, json!({ "id": "p1", "planXml": xml }));
+    }
+
+    #[test]
+    fn test_uncompressed_entry_passes_through() {
+        let mut entry = json!({ "id": "p1", "planXml": "<ShowPlanXML />" });
+        unpack_plan_entry(&mut entry).unwrap();
+        assert_eq!(entry["planXml"], "<ShowPlanXML />");
+    }
+
+    #[test]
+    fn test_decompress_rejects_garbage() {
+        assert!(decompress_text("not base64!").is_err());
+        assert!(decompress_text(&BASE64.encode(b"plain text")).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use tiberius::Row;
+
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_identifier, quote_literal};
+use super::rows::{get_i64, get_string};
+use super::types::{AppError, DatabaseOptions, ScopedConfiguration, ServerConfigOption};
+use crate::plan::types::ParallelismSettings;
+
+/// sys.configurations entries that change the plans the optimizer produces or
+/// how much memory and parallelism they get
+const PLAN_AFFECTING_OPTIONS: &[&str] = &[
+    "cost threshold for parallelism",
+    "max degree of parallelism",
+    "optimize for ad hoc workloads",
+    "max server memory (MB)",
+    "min memory per query (KB)",
+    "query wait (s)",
+    "query governor cost limit",
+    "max worker threads",
+];
+
+/// Scoped configurations worth pointing out when tuning plans
+const PLAN_AFFECTING_SCOPED: &[&str] = &[
+    "MAXDOP",
+    "LEGACY_CARDINALITY_ESTIMATION",
+    "PARAMETER_SNIFFING",
+    "QUERY_OPTIMIZER_HOTFIXES",
+    "BATCH_MODE_ON_ROWSTORE",
+    "BATCH_MODE_ADAPTIVE_JOINS",
+    "BATCH_MODE_MEMORY_GRANT_FEEDBACK",
+    "ROW_MODE_MEMORY_GRANT_FEEDBACK",
+    "INTERLEAVED_EXECUTION_TVF",
+    "TSQL_SCALAR_UDF_INLINING",
+    "PARAMETER_SENSITIVE_PLAN_OPTIMIZATION",
+];
+
+pub async fn server_configuration(
+    client: &mut TiberiusClient,
+) -> Result<Vec<ServerConfigOption>, AppError> {
+    // value and value_in_use are sql_variant, which the driver cannot read
+    let rows = client
+        .simple_query(
+            "SELECT name, CAST(value AS bigint), CAST(value_in_use AS bigint), \
+                    CAST(minimum AS bigint), CAST(maximum AS bigint), description, \
+                    is_dynamic, is_advanced \
+             FROM sys.configurations ORDER BY name",
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server configuration"))?
+        .into_first_result()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read server configuration"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let name = get_string(row, 0).unwrap_or_default();
+            let value = get_i64(row, 1).unwrap_or(0);
+            let value_in_use = get_i64(row, 2).unwrap_or(0);
+            ServerConfigOption {
+                affects_plans: PLAN_AFFECTING_OPTIONS
+                    .iter()
+                    .any(|o| o.eq_ignore_ascii_case(&name)),
+                name,
+                value,
+                value_in_use,
+                minimum: get_i64(row, 3).unwrap_or(0),
+                maximum: get_i64(row, 4).unwrap_or(0),
+                description: get_string(row, 5).unwrap_or_default(),
+                is_dynamic: get_bool(row, 6),
+                is_advanced: get_bool(row, 7),
+                pending_restart: value != value_in_use,
+            }
+        })
+        .collect())
+}
+
+/// Options of `database`, or of the current database when None
+pub async fn database_options(
+    client: &mut TiberiusClient,
+    database: Option<&str>,
+) -> Result<DatabaseOptions, AppError> {
+    let filter = match database {
+        Some(name) => format!("name = {}", quote_literal(name)),
+        None => "database_id = DB_ID()".to_string(),
+    };
+    let row = client
+        .simple_query(format!(
+            "SELECT name, CAST(compatibility_level AS int), \
+                    is_auto_create_stats_on, is_auto_update_stats_on, \
+                    is_auto_update_stats_async_on, is_parameterization_forced, \
+                    is_read_committed_snapshot_on, snapshot_isolation_state_desc, \
+                    recovery_model_desc, collation_name \
+             FROM sys.databases WHERE {}",
+            filter
+        ))
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read database options"))?
+        .into_row()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read database options"))?
+        .ok_or_else(|| format!("Database not found: {}", database.unwrap_or("current")))?;
+
+    let name = get_string(&row, 0).unwrap_or_default();
+    let scoped_configurations = scoped_configurations(client, &name).await;
+    Ok(DatabaseOptions {
+        compatibility_level: get_i64(&row, 1),
+        auto_create_statistics: get_bool(&row, 2),
+        auto_update_statistics: get_bool(&row, 3),
+        auto_update_statistics_async: get_bool(&row, 4),
+        forced_parameterization: get_bool(&row, 5),
+        read_committed_snapshot: get_bool(&row, 6),
+        snapshot_isolation: get_string(&row, 7),
+        recovery_model: get_string(&row, 8),
+        collation: get_string(&row, 9),
+        scoped_configurations,
+        name,
+    })
+}
+
+/// sys.database_scoped_configurations exists from SQL Server 2016; older
+/// servers and databases the login cannot open have none
+async fn scoped_configurations(
+    client: &mut TiberiusClient,
+    database: &str,
+) -> Vec<ScopedConfiguration> {
+    let sql = format!(
+        "SELECT name, CAST(value AS nvarchar(256)), CAST(value_for_secondary AS nvarchar(256)) \
+         FROM {}.sys.database_scoped_configurations ORDER BY name",
+        quote_identifier(database)
+    );
+    let rows = match client.simple_query(sql).await {
+        Ok(stream) => stream.into_first_result().await.unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+    rows.iter()
+        .map(|row| {
+            let name = get_string(row, 0).unwrap_or_default();
+            ScopedConfiguration {
+                affects_plans: PLAN_AFFECTING_SCOPED
+                    .iter()
+                    .any(|o| o.eq_ignore_ascii_case(&name)),
+                name,
+                value: get_string(row, 1),
+                value_for_secondary: get_string(row, 2),
+            }
+        })
+        .collect()
+}
+
+/// Cost threshold and MAXDOP in use, plus the current database's MAXDOP and
+/// the CPU count where the login can read them
+pub async fn parallelism_settings(
+    client: &mut TiberiusClient,
+) -> Result<ParallelismSettings, AppError> {
+    let row = client
+        .simple_query(
+            "SELECT CAST(MAX(CASE WHEN name = 'cost threshold for parallelism' \
+                              THEN value_in_use END) AS int), \
+                    CAST(MAX(CASE WHEN name = 'max degree of parallelism' \
+                              THEN value_in_use END) AS int) \
+             FROM sys.configurations",
+        )
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read parallelism settings"))?
+        .into_row()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read parallelism settings"))?
+        .ok_or("Failed to read parallelism settings")?;
+
+    Ok(ParallelismSettings {
+        cost_threshold: get_i64(&row, 0).unwrap_or(5),
+        server_max_dop: get_i64(&row, 1).unwrap_or(0),
+        // Scoped configurations need SQL Server 2016, the CPU count VIEW SERVER STATE
+        database_max_dop: optional_int(
+            client,
+            "SELECT CAST(value AS int) FROM sys.database_scoped_configurations \
+             WHERE name = 'MAXDOP'",
+        )
+        .await,
+        cpu_count: optional_int(client, "SELECT cpu_count FROM sys.dm_os_sys_info").await,
+    })
+}
+
+async fn optional_int(client: &mut TiberiusClient, sql: &str) -> Option<i64> {
+    let row = client
+        .simple_query(sql)
+        .await
+        .ok()?
+        .into_row()
+        .await
+        .ok()??;
+    get_i64(&row, 0)
+}
+
+fn get_bool(row: &Row, idx: usize) -> bool {
+    row.try_get::<bool, _>(idx).ok().flatten().unwrap_or(false)
+}
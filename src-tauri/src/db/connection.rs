@@ -1,266 +1,605 @@
-use std::sync::Arc;
-use tiberius::{AuthMethod, Client, Column, Config, Row};
-use tokio::net::TcpStream;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use futures_util::TryStreamExt;
+use tiberius::{AuthMethod, Client, Column, Config, EncryptionLevel, QueryItem, Row};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tokio_util::compat::TokioAsyncWriteCompatExt;
+use tracing::instrument::WithSubscriber;
 
-use super::types::{PlanType, QueryResult};
+use super::arrow_export::{self, ExportSummary};
+use super::cells;
+use super::columns;
+use super::errors;
+use super::completion;
+use super::identifiers::quote_identifier;
+use super::keep_alive;
+use super::kerberos;
+use super::metadata_cache::{self, MetadataCache};
+use super::monitor::{self, MonitorConnection};
+use super::query_queue::ExecutionQueue;
+use super::rows::get_i64;
+use super::result_grid::ResultCache;
+use super::retry;
+use super::safe_mode::{classify_batch, StatementClass};
+use super::server_messages::{MessageCapture, RowCountTracker, ServerError};
+use super::timing::{self, StatementEnds};
+use super::tls::{self, TdsStream};
+use super::transport;
+use super::type_casts::{self, CastScope};
+use super::types::{
+    AppError, ColumnInfo, ConnectionAuth, ConnectionConfig, ConnectionRequest, ConnectionStep,
+    ConnectionTransport, ExportFormat, PlanType, QueryResult, ResultSet, RetryPolicy, RunningQuery,
+    StatementTiming,
+};
+use super::wait_stats::{self, WaitStatsSnapshot};
+use crate::plan::{parser, spills};
 
-type TiberiusClient = Client<tokio_util::compat::Compat<TcpStream>>;
+pub type TiberiusClient = Client<tokio_util::compat::Compat<TdsStream>>;
 
 pub struct DbConnection {
     pub client: Arc<Mutex<TiberiusClient>>,
+    /// sys.* metadata keyed by database name
+    pub metadata_cache: MetadataCache,
+    /// Last snapshot taken by get_wait_stats
+    pub wait_stats_baseline: Mutex<Option<WaitStatsSnapshot>>,
+    /// Session SET option that may still be ON for this session
+    pending_option_reset: Mutex<Option<SessionOption>>,
+    /// Safe mode: batches that can modify anything are rejected before execution
+    pub read_only: bool,
+    /// The app profile forbids modifications, whatever the connection allows
+    pub profile_read_only: AtomicBool,
+    /// Id of the saved connection this was opened from, if any
+    pub saved_connection_id: Option<String>,
+    /// Database the session was last known to be in
+    current_database: StdMutex<String>,
+    /// Full values of binary cells truncated in the last result, by
+    /// (result set, row, column)
+    truncated_cells: StdMutex<HashMap<(usize, usize, usize), Vec<u8>>>,
+    /// Recent result sets, for sorting and filtering on this side
+    pub result_cache: ResultCache,
+    /// When the connection last talked to the server, used by the keep-alive task
+    last_activity: Arc<StdMutex<Instant>>,
+    keep_alive: StdMutex<Option<JoinHandle<()>>>,
+    /// Retry queries that fail on unsupported column types with those columns cast
+    pub cast_unsupported_types: AtomicBool,
+    /// Where the session was opened, to open the monitoring session and to
+    /// reopen this one after a KILL
+    target: ConnectionTarget,
+    /// Server session id (@@SPID) of the user's session
+    session_id: StdMutex<Option<i64>>,
+    /// Set while kill_running_query stops the session, so the query it
+    /// stopped reports being cancelled
+    kill_requested: AtomicBool,
+    pub monitor: MonitorConnection,
 }
 
-pub struct AppState {
-    pub connection: Arc<Mutex<Option<DbConnection>>>,
+/// Everything needed to open another session as the same login
+#[derive(Clone)]
+pub struct ConnectionTarget {
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    pub transport: ConnectionTransport,
+    pub auth: ConnectionAuth,
+    /// Fingerprint the server certificate must have; `None` takes the first
+    /// one seen, and later sessions are held to it
+    pub certificate: Option<String>,
 }
 
-impl DbConnection {
-    // Helper function to rewrite queries with date columns cast to datetime
-    async fn rewrite_query_with_date_cast(
-        client: &mut TiberiusClient,
-        sql: &str,
-    ) -> Result<String, String> {
-        // Try to extract table name from simple queries like "SELECT * FROM table"
-        let sql_trimmed = sql.trim();
-        let sql_lower = sql_trimmed.to_lowercase();
-
-        // Handle simple SELECT * FROM table_name patterns
-        // Normalize whitespace to handle various spacing patterns
-        let normalized = sql_lower.split_whitespace().collect::<Vec<_>>().join(" ");
-
-        // Also check for patterns like "select*from" (no spaces around *)
-        let has_select_star_from = normalized.starts_with("select * from")
-            || normalized.starts_with("select*from")
-            || (sql_lower.contains("select") && sql_lower.contains("*") && sql_lower.contains("from"));
-
-        if has_select_star_from {
-            // Extract table name (simple pattern matching)
-            let after_from = if let Some(pos) = sql_lower.find("from") {
-                sql_trimmed[pos + 4..].trim()
-            } else {
-                return Ok(sql.to_string());
-            };
+impl ConnectionTarget {
+    pub fn from_request(request: &ConnectionRequest, certificate: Option<String>) -> Self {
+        Self {
+            host: request.host.clone(),
+            port: request.port,
+            database: request.database.clone(),
+            username: request.username.clone(),
+            password: request.password.clone(),
+            transport: request.transport.clone(),
+            auth: request.auth.clone(),
+            certificate,
+        }
+    }
 
-            // Get just the table name (before any WHERE, ORDER BY, etc.)
-            let table_name = after_from
-                .split_whitespace()
-                .next()
-                .unwrap_or("")
-                .trim_end_matches(';');
+    pub fn from_saved(connection: &ConnectionConfig, password: String) -> Self {
+        Self {
+            host: connection.host.clone(),
+            port: connection.port,
+            database: connection.database.clone(),
+            username: connection.username.clone(),
+            password,
+            transport: connection.transport.clone(),
+            auth: connection.auth.clone(),
+            certificate: connection.cert_fingerprint.clone(),
+        }
+    }
+}
 
-            if table_name.is_empty() {
-                return Ok(sql.to_string());
-            }
+/// Log in to the server; `application_name` labels the session in
+/// sys.dm_exec_sessions
+pub async fn open_client(
+    target: &ConnectionTarget,
+    application_name: Option<&str>,
+) -> Result<TiberiusClient, (ConnectionStep, AppError)> {
+    open_session(target, application_name)
+        .await
+        .map(|(client, _)| client)
+}
 
-            // Query for columns with their actual system type
-            // This resolves user-defined alias types to their base types
-            // Check both tables and views using sys.objects
-            let metadata_query = format!(
-                "SELECT c.name, c.system_type_id, c.user_type_id, t.name as type_name, st.name as system_type_name \
-                FROM sys.columns c \
-                INNER JOIN sys.objects o ON c.object_id = o.object_id \
-                INNER JOIN sys.types t ON c.user_type_id = t.user_type_id \
-                INNER JOIN sys.types st ON c.system_type_id = st.user_type_id \
-                WHERE LOWER(o.name) = LOWER('{}') \
-                AND o.type IN ('U', 'V') \
-                ORDER BY c.column_id",
-                table_name.replace("'", "''")
-            );
-
-            let stream = match client.simple_query(&metadata_query).await {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Warning: Failed to query column metadata: {}. Date casting will not be applied.", e);
-                    return Ok(sql.to_string());
-                }
-            };
+/// Like `open_client`, also returning the fingerprint of the server
+/// certificate the session was opened over, if the server encrypts
+async fn open_session(
+    target: &ConnectionTarget,
+    application_name: Option<&str>,
+) -> Result<(TiberiusClient, Option<String>), (ConnectionStep, AppError)> {
+    let mut config = Config::new();
+    // Over a pipe the host only names the server for login and TLS
+    let host = match &target.transport {
+        ConnectionTransport::NamedPipe { pipe_path } => {
+            transport::pipe_server(pipe_path).unwrap_or(&target.host)
+        }
+        ConnectionTransport::Tcp => &target.host,
+    };
+    config.host(host);
+    config.port(target.port);
+    config.database(&target.database);
+    // The SPN may rename the host for Kerberos; the stream goes to the server
+    let addr = config.get_addr();
+    match &target.auth {
+        ConnectionAuth::SqlLogin => {
+            config.authentication(AuthMethod::sql_server(&target.username, &target.password))
+        }
+        ConnectionAuth::Kerberos { spn, realm } => {
+            kerberos::configure(&mut config, host, target.port, spn.as_deref(), realm.as_deref())
+                .map_err(|e| (ConnectionStep::Login, AppError::connection(e)))?
+        }
+    }
+    if let Some(name) = application_name {
+        config.application_name(name);
+    }
+    // The TLS session is ours, so the pin is checked in its handshake
+    config.encryption(EncryptionLevel::NotSupported);
 
-            let result_sets = match stream.into_results().await {
-                Ok(r) => r,
-                Err(e) => {
-                    eprintln!("Warning: Failed to retrieve column metadata results: {}. Date casting will not be applied.", e);
-                    return Ok(sql.to_string());
-                }
+    let stream = transport::open(addr, &target.transport)
+        .await
+        .map_err(|e| (ConnectionStep::Tcp, AppError::connection(e)))?;
+    let (stream, certificate) = tls::secure(stream, host, target.certificate.as_deref())
+        .await
+        .map_err(|e| {
+            let error = AppError::connection(e);
+            errors::record(&error, None);
+            (ConnectionStep::Tls, error)
+        })?;
+
+    let client = Client::connect(config, stream.compat_write())
+        .await
+        .map_err(|e| {
+            // Whatever the server said, the login is what failed
+            let error = AppError::ConnectionError {
+                message: format!("SQL Server connection failed: {}", e),
+                engine: AppError::from_tiberius(&e).engine().cloned().map(Box::new),
             };
+            errors::record(&error, None);
+            (failed_connection_step(&e), error)
+        })?;
+    Ok((client, certificate))
+}
 
-            let mut columns: Vec<String> = Vec::new();
-            let mut has_type_casting = false;
-
-            for result_set in &result_sets {
-                for row in result_set {
-                    let col_name_result = row.try_get::<&str, _>(0);
-                    let system_type_id_result = row.try_get::<u8, _>(1);
-                    let user_type_id_result = row.try_get::<i32, _>(2);
-                    let system_type_name_result = row.try_get::<&str, _>(4);
-
-                    if let Some(col_name) = col_name_result.ok().flatten() {
-                        // Extract values once to avoid move issues
-                        let system_type_id = system_type_id_result.ok().flatten();
-                        let user_type_id = user_type_id_result.ok().flatten();
-                        let system_type_name = system_type_name_result.ok().flatten();
-
-                        let mut needs_cast = false;
-                        let mut cast_type = String::new();
-
-                        // Check if it's a date type (needs casting to datetime for Tiberius compatibility)
-                        if let Some(sys_type_id) = system_type_id {
-                            if sys_type_id == 40 {
-                                needs_cast = true;
-                                cast_type = "datetime".to_string();
-                            }
-                        }
+async fn read_session_id(client: &mut TiberiusClient) -> Option<i64> {
+    let row = client
+        .simple_query("SELECT CAST(@@SPID AS int)")
+        .await
+        .ok()?
+        .into_row()
+        .await
+        .ok()??;
+    get_i64(&row, 0)
+}
 
-                        // Check if it's an alias type (user_type_id != system_type_id)
-                        // If so, cast to the base system type
-                        if !needs_cast {
-                            if let (Some(sys_type_id), Some(usr_type_id)) = (system_type_id, user_type_id) {
-                                // If user_type_id differs from system_type_id, it's an alias type
-                                if sys_type_id as i32 != usr_type_id {
-                                    if let Some(sys_type_name) = system_type_name {
-                                        needs_cast = true;
-                                        cast_type = sys_type_name.to_string();
-                                    }
-                                }
-                            }
-                        }
+impl Drop for DbConnection {
+    fn drop(&mut self) {
+        if let Some(task) = self.keep_alive.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+}
 
-                        if needs_cast && !cast_type.is_empty() {
-                            columns.push(format!("CAST([{}] AS {}) AS [{}]", col_name, cast_type, col_name));
-                            has_type_casting = true;
-                        } else {
-                            columns.push(format!("[{}]", col_name));
-                        }
-                    }
-                }
-            }
+pub struct AppState {
+    pub connection: Arc<Mutex<Option<Arc<DbConnection>>>>,
+    /// Queries in flight keyed by id, for notifications and progress display
+    pub running_queries: Arc<Mutex<HashMap<String, RunningQuery>>>,
+    /// Queries waiting for the active connection
+    pub execution_queue: Arc<ExecutionQueue>,
+    /// Key derived from the master passphrase while the store is unlocked
+    pub master_key: Arc<Mutex<Option<[u8; 32]>>>,
+}
 
-            if has_type_casting && !columns.is_empty() {
-                // Rebuild the query with explicit column list
-                let column_list = columns.join(", ");
+impl AppState {
+    /// The open connection, shared so long work can release the state lock
+    pub async fn active_connection(&self) -> Result<Arc<DbConnection>, AppError> {
+        self.connection
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| AppError::connection("Not connected to database"))
+    }
+}
 
-                // Get everything after "FROM table_name" (WHERE, ORDER BY, etc.)
-                let from_pos = match sql_lower.find("from") {
-                    Some(pos) => pos,
-                    None => return Ok(sql.to_string()),
-                };
-                let after_from = &sql_trimmed[from_pos + 4..].trim_start();
-                let table_end_pos = after_from.find(table_name).map(|p| p + table_name.len()).unwrap_or(0);
-                let rest_of_query = &after_from[table_end_pos..];
+impl DbConnection {
+    pub async fn connect(target: ConnectionTarget) -> Result<Self, AppError> {
+        Self::connect_diagnosed(target).await.map_err(|(_, e)| e)
+    }
 
-                return Ok(format!("SELECT {} FROM {}{}", column_list, table_name, rest_of_query));
-            }
-        }
+    /// Like `connect`, but failures also say which step of the handshake broke
+    pub async fn connect_diagnosed(
+        mut target: ConnectionTarget,
+    ) -> Result<Self, (ConnectionStep, AppError)> {
+        let (mut client, certificate) = open_session(&target, None).await?;
+        let session_id = read_session_id(&mut client).await;
+        target.certificate = certificate;
+
+        Ok(Self {
+            client: Arc::new(Mutex::new(client)),
+            metadata_cache: MetadataCache::new(metadata_cache::DEFAULT_TTL),
+            wait_stats_baseline: Mutex::new(None),
+            pending_option_reset: Mutex::new(None),
+            read_only: false,
+            profile_read_only: AtomicBool::new(false),
+            saved_connection_id: None,
+            current_database: StdMutex::new(target.database.clone()),
+            truncated_cells: StdMutex::new(HashMap::new()),
+            result_cache: ResultCache::default(),
+            last_activity: Arc::new(StdMutex::new(Instant::now())),
+            keep_alive: StdMutex::new(None),
+            cast_unsupported_types: AtomicBool::new(false),
+            monitor: MonitorConnection::new(target.clone()),
+            target,
+            session_id: StdMutex::new(session_id),
+            kill_requested: AtomicBool::new(false),
+        })
+    }
 
-        Ok(sql.to_string())
+    /// Another session as the same login, in the database this one is in and
+    /// with its safe mode and casting settings, for work run beside the
+    /// user's session
+    pub async fn open_sibling(&self) -> Result<DbConnection, AppError> {
+        let mut target = self.target.clone();
+        target.database = self.last_known_database();
+        let mut sibling = Self::connect(target).await?;
+        sibling.read_only = self.read_only;
+        sibling.saved_connection_id = self.saved_connection_id.clone();
+        sibling
+            .profile_read_only
+            .store(self.profile_read_only.load(Ordering::Relaxed), Ordering::Relaxed);
+        sibling
+            .cast_unsupported_types
+            .store(self.cast_unsupported_types.load(Ordering::Relaxed), Ordering::Relaxed);
+        Ok(sibling)
     }
 
-    pub async fn connect(
-        host: &str,
-        port: u16,
-        database: &str,
-        username: &str,
-        password: &str,
-    ) -> Result<Self, String> {
-        let mut config = Config::new();
-        config.host(host);
-        config.port(port);
-        config.database(database);
-        config.authentication(AuthMethod::sql_server(username, password));
-        config.trust_cert();
+    /// Stop whatever the user's session is running by killing it from the
+    /// monitoring session, then log in again in the same database. Session
+    /// state (SET options, temp tables, open transactions) is lost.
+    pub async fn kill_running_query(&self) -> Result<(), AppError> {
+        let session_id = self
+            .session_id
+            .lock()
+            .unwrap()
+            .ok_or("The session id of the connection is unknown")?;
+        self.kill_requested.store(true, Ordering::Relaxed);
+        let killed = self
+            .monitor
+            .run(|client| Box::pin(monitor::kill_session(client, session_id)))
+            .await;
+        if let Err(e) = killed {
+            self.kill_requested.store(false, Ordering::Relaxed);
+            return Err(e.into());
+        }
+        tracing::info!(session_id, "Killed the user session");
 
-        let tcp = TcpStream::connect(config.get_addr())
+        // The killed query returns its error and releases the client
+        let mut client = self.client.lock().await;
+        self.kill_requested.store(false, Ordering::Relaxed);
+        *client = open_client(&self.target, None)
             .await
-            .map_err(|e| format!("TCP connection failed: {}", e))?;
-        tcp.set_nodelay(true).ok();
+            .map_err(|(_, e)| e.context("Query was killed but reconnecting failed"))?;
+        *self.session_id.lock().unwrap() = read_session_id(&mut client).await;
+        *self.pending_option_reset.lock().await = None;
+        let database = self.current_database.lock().unwrap().clone();
+        if database != self.target.database {
+            client
+                .simple_query(format!("USE {}", quote_identifier(&database)))
+                .await
+                .map_err(|e| AppError::from(e).context("Reconnected but failed to switch database"))?
+                .into_results()
+                .await
+                .map_err(|e| AppError::from(e).context("Reconnected but failed to switch database"))?;
+        }
+        self.touch();
+        Ok(())
+    }
+
+    /// (Re)start the keep-alive task; `None` stops it.
+    pub fn set_keep_alive(&self, interval: Option<Duration>) {
+        let mut task = self.keep_alive.lock().unwrap();
+        if let Some(previous) = task.take() {
+            previous.abort();
+        }
+        if let Some(interval) = interval.filter(|i| !i.is_zero()) {
+            *task = Some(keep_alive::spawn(
+                self.client.clone(),
+                self.last_activity.clone(),
+                interval,
+            ));
+        }
+    }
+
+    /// Safe mode or the profile rule out anything that modifies the database
+    pub fn modifications_blocked(&self) -> bool {
+        self.read_only || self.profile_read_only.load(Ordering::Relaxed)
+    }
+
+    /// Server session id (@@SPID) of the user's session
+    pub fn session_id(&self) -> Option<i64> {
+        *self.session_id.lock().unwrap()
+    }
+
+    /// Fingerprint of the server certificate every session of this
+    /// connection is held to
+    pub fn server_certificate(&self) -> Option<&str> {
+        self.target.certificate.as_deref()
+    }
 
-        let client = Client::connect(config, tcp.compat_write())
+    /// Server the session is connected to, as the user would type it
+    pub fn endpoint(&self) -> String {
+        transport::endpoint(&self.target.host, self.target.port, &self.target.transport)
+    }
+
+    pub fn login(&self) -> &str {
+        &self.target.username
+    }
+
+    /// Database the session was last known to be in, without a round trip
+    pub fn last_known_database(&self) -> String {
+        self.current_database.lock().unwrap().clone()
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Full hex value of a binary cell that was truncated in the last result
+    pub fn truncated_cell(&self, result_set: usize, row: usize, column: usize) -> Option<String> {
+        self.truncated_cells
+            .lock()
+            .unwrap()
+            .get(&(result_set, row, column))
+            .map(|bytes| cells::hex_literal(bytes))
+    }
+
+    /// Switch the session to another database without reconnecting
+    pub async fn use_database(&self, database: &str) -> Result<String, AppError> {
+        let mut client = self.client.lock().await;
+        self.touch();
+        client
+            .simple_query(format!("USE {}", quote_identifier(database)))
             .await
-            .map_err(|e| format!("SQL Server connection failed: {}", e))?;
+            .map_err(|e| AppError::from(e).context("Failed to switch database"))?
+            .into_results()
+            .await
+            .map_err(|e| AppError::from(e).context("Failed to switch database"))?;
+        self.refresh_current_database(&mut client).await
+    }
 
-        Ok(Self {
-            client: Arc::new(Mutex::new(client)),
-        })
+    /// Ask the server, since a USE inside a query batch also moves the session.
+    /// While a query holds the client the last known name is returned instead.
+    pub async fn current_database(&self) -> Result<String, AppError> {
+        let Ok(mut client) = self.client.try_lock() else {
+            return Ok(self.current_database.lock().unwrap().clone());
+        };
+        self.touch();
+        self.refresh_current_database(&mut client).await
+    }
+
+    async fn refresh_current_database(&self, client: &mut TiberiusClient) -> Result<String, AppError> {
+        let database = completion::current_database(client).await?;
+        *self.current_database.lock().unwrap() = database.clone();
+        Ok(database)
     }
 
     pub async fn execute_query(
         &self,
         sql: &str,
         plan_type: &PlanType,
-    ) -> Result<QueryResult, String> {
+    ) -> Result<QueryResult, AppError> {
+        self.execute_query_with_retry(sql, plan_type, &RetryPolicy::default())
+            .await
+    }
+
+    /// Like `execute_query`, re-running deadlock victims and bounding lock
+    /// waits as the policy asks
+    pub async fn execute_query_with_retry(
+        &self,
+        sql: &str,
+        plan_type: &PlanType,
+        policy: &RetryPolicy,
+    ) -> Result<QueryResult, AppError> {
+        // Estimated plans are compiled but never executed, so they stay allowed
+        if !matches!(plan_type, PlanType::Estimated) {
+            self.check_modifications_allowed(sql)?;
+        }
+
+        let mut client = self.client.lock().await;
+        self.touch();
+
+        // A previous plan capture may have failed to switch its SET option off
+        self.restore_session_options(&mut client).await?;
+
+        let previous_lock_timeout = match policy.lock_timeout_ms {
+            Some(ms) => Some(set_lock_timeout(&mut client, ms).await?),
+            None => None,
+        };
+
+        let mut attempt = 0;
+        let outcome = loop {
+            match self.run_query_with_casts(&mut client, sql, plan_type).await {
+                Err(e) if attempt < policy.deadlock_retries && retry::is_deadlock_victim(&e) => {
+                    attempt += 1;
+                    let delay = retry::retry_delay(policy.backoff_ms, attempt);
+                    tracing::info!(attempt, delay_ms = delay.as_millis() as u64, "Retrying deadlock victim");
+                    tokio::time::sleep(delay).await;
+                }
+                outcome => break outcome,
+            }
+        };
+
+        // Even a failed batch may have run its DDL before the error
+        if !matches!(plan_type, PlanType::Estimated) && metadata_cache::changes_schema(sql) {
+            self.metadata_cache.invalidate_all().await;
+        }
+
+        if let Some(previous) = previous_lock_timeout {
+            if let Err(e) = set_lock_timeout(&mut client, previous).await {
+                tracing::warn!(error = %e, "Failed to restore LOCK_TIMEOUT");
+            }
+        }
+
+        match outcome {
+            Ok(mut result) => {
+                if attempt > 0 {
+                    result.messages.insert(
+                        0,
+                        format!("Note: Retried {} time(s) after being chosen as a deadlock victim.", attempt),
+                    );
+                }
+                Ok(result)
+            }
+            // The session was killed from kill_running_query while this ran
+            Err(e) if self.kill_requested.swap(false, Ordering::Relaxed) => {
+                Err(AppError::cancelled(format!("Query was cancelled: {}", e)))
+            }
+            Err(e) if attempt > 0 => {
+                let message = format!("{}\n(Retried {} time(s) after deadlocks)", e, attempt);
+                Err(e.with_message(message))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Refuse a batch that can modify data while safe mode or the profile
+    /// blocks modifications
+    fn check_modifications_allowed(&self, sql: &str) -> Result<(), AppError> {
+        if !self.modifications_blocked() {
+            return Ok(());
+        }
+        match classify_batch(sql) {
+            StatementClass::Modifying(keyword) if self.read_only => Err(AppError::permission(format!(
+                "Connection is read-only: {} statements are blocked. Disable safe mode on the connection to run them.",
+                keyword
+            ))),
+            StatementClass::Modifying(keyword) => Err(AppError::permission(format!(
+                "The ReadOnly profile blocks {} statements. Change the profile to run them.",
+                keyword
+            ))),
+            StatementClass::ReadOnly => Ok(()),
+        }
+    }
+
+    /// Run a query and write its first result set to a Parquet or Arrow
+    /// IPC file, reading rows in batches instead of into a result grid
+    pub async fn export_results(
+        &self,
+        sql: &str,
+        path: &Path,
+        format: ExportFormat,
+        on_progress: impl Fn(u64),
+    ) -> Result<ExportSummary, AppError> {
+        self.check_modifications_allowed(sql)?;
+
         let mut client = self.client.lock().await;
+        self.touch();
+        self.restore_session_options(&mut client).await?;
+
+        let stream = client
+            .simple_query(sql)
+            .await
+            .map_err(|e| query_error(&e, false, "Query failed: "))?;
+        let summary = arrow_export::write(stream, path, format, on_progress).await;
+        if metadata_cache::changes_schema(sql) {
+            self.metadata_cache.invalidate_all().await;
+        }
+        Ok(summary?)
+    }
 
-        // Automatically rewrite queries with date columns
-        let original_sql = sql;
-        let sql = Self::rewrite_query_with_date_cast(&mut client, sql).await?;
-        let date_cast_applied = sql != original_sql;
+    /// Run a batch, retrying once with unsupported columns cast
+    async fn run_query_with_casts(
+        &self,
+        client: &mut TiberiusClient,
+        sql: &str,
+        plan_type: &PlanType,
+    ) -> Result<QueryResult, AppError> {
+        match self.run_query(client, sql, plan_type).await {
+            // Retry once with the offending columns cast, using the server's
+            // description of the result set. Only hierarchyid is read as text
+            // unless casting is turned on.
+            Err(e) if e.message().contains("column type") => {
+                let scope = if self.cast_unsupported_types.load(Ordering::Relaxed) {
+                    CastScope::All
+                } else {
+                    CastScope::HierarchyId
+                };
+                let database = self.current_database.lock().unwrap().clone();
+                let Some(rewrite) =
+                    type_casts::rewrite_with_casts(client, &self.metadata_cache, &database, sql, scope).await
+                else {
+                    return Err(e);
+                };
+                tracing::info!(columns = rewrite.cast_columns.len(), "Retrying query with unsupported column types cast");
+                let mut result = self.run_query(client, &rewrite.sql, plan_type).await?;
+                result.messages.insert(
+                    0,
+                    format!(
+                        "Note: Cast {} to a supported type after the query failed with an unsupported column type.",
+                        rewrite.cast_columns.join(", ")
+                    ),
+                );
+                Ok(result)
+            }
+            outcome => outcome,
+        }
+    }
 
+    async fn run_query(
+        &self,
+        client: &mut TiberiusClient,
+        sql: &str,
+        plan_type: &PlanType,
+    ) -> Result<QueryResult, AppError> {
         let start = std::time::Instant::now();
         let mut messages: Vec<String> = Vec::new();
         let mut plan_xml: Option<String> = None;
-        let mut columns: Vec<String> = Vec::new();
-        let mut rows: Vec<Vec<serde_json::Value>> = Vec::new();
+        let mut result_sets: Vec<ResultSet> = Vec::new();
         let mut rows_affected: i64 = 0;
-
-        if date_cast_applied {
-            messages.push("Note: Alias types and date columns automatically cast to their base types for compatibility.".to_string());
-        }
+        let mut timings: Vec<StatementTiming> = Vec::new();
+        let mut query_waits = None;
+        let mut truncated_cells = HashMap::new();
 
         match plan_type {
             PlanType::Estimated => {
                 // SHOWPLAN_XML returns the plan without executing
-                client
-                    .simple_query("SET SHOWPLAN_XML ON")
-                    .await
-                    .map_err(|e| format!("Failed to enable SHOWPLAN_XML: {}", e))?
-                    .into_results()
-                    .await
-                    .map_err(|e| e.to_string())?;
-
-                let stream = client
-                    .simple_query(sql)
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that cannot be used with execution plans.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                • Use 'No Plan' mode (though unsupported types will still cause errors)\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            format!("Query failed: {}", err_msg)
-                        }
-                    })?;
-
-                let result_sets = stream
-                    .into_results()
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that cannot be used with execution plans.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                • Use 'No Plan' mode (though unsupported types will still cause errors)\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            err_msg
-                        }
-                    })?;
+                let batch = self
+                    .run_with_session_option(client, SessionOption::ShowplanXml, sql, &mut messages)
+                    .await?;
+                // Compile time only; nothing ran, so no timings
 
                 let mut plan_xmls: Vec<String> = Vec::new();
-                for result_set in &result_sets {
-                    for row in result_set {
+                for result_set in &batch.result_sets {
+                    for row in &result_set.rows {
                         if let Some(xml) = row.try_get::<&str, _>(0).ok().flatten() {
                             plan_xmls.push(xml.to_string());
                         }
@@ -268,175 +607,455 @@ impl DbConnection {
                 }
                 plan_xml = merge_showplan_xmls(plan_xmls);
 
-                client
-                    .simple_query("SET SHOWPLAN_XML OFF")
-                    .await
-                    .map_err(|e| format!("Failed to disable SHOWPLAN_XML: {}", e))?
-                    .into_results()
-                    .await
-                    .map_err(|e| e.to_string())?;
-
                 messages.push("Estimated execution plan generated.".to_string());
             }
             PlanType::Actual => {
+                let waits_before = wait_stats::session_snapshot(client).await.ok();
                 // STATISTICS XML returns results + plan
-                client
-                    .simple_query("SET STATISTICS XML ON")
-                    .await
-                    .map_err(|e| format!("Failed to enable STATISTICS XML: {}", e))?
-                    .into_results()
-                    .await
-                    .map_err(|e| e.to_string())?;
-
-                let stream = client
-                    .simple_query(sql)
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that cannot be used with execution plans.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                • Use 'No Plan' mode (though unsupported types will still cause errors)\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            format!("Query failed: {}", err_msg)
-                        }
-                    })?;
-
-                let result_sets = stream
-                    .into_results()
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that cannot be used with execution plans.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                • Use 'No Plan' mode (though unsupported types will still cause errors)\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            err_msg
+                let batch = self
+                    .run_with_session_option(client, SessionOption::StatisticsXml, sql, &mut messages)
+                    .await?;
+                timings = batch.timings;
+                if let Some(before) = &waits_before {
+                    if let Ok(after) = wait_stats::session_snapshot(client).await {
+                        let delta = wait_stats::delta(Some(before), &after);
+                        if let Some(top) = delta.waits.first() {
+                            messages.push(format!(
+                                "Top wait: {} ({:.0}% of {} ms waited)",
+                                top.wait_type,
+                                top.percent,
+                                delta.waits.iter().map(|w| w.wait_time_ms).sum::<i64>()
+                            ));
                         }
-                    })?;
-
-                let mut plan_xmls: Vec<String> = Vec::new();
-                for result_set in &result_sets {
-                    if result_set.is_empty() {
-                        continue;
+                        query_waits = Some(delta);
                     }
+                }
 
-                    let first_row = &result_set[0];
-                    if let Some(xml) = first_row.try_get::<&str, _>(0).ok().flatten() {
-                        if xml.contains("ShowPlanXML") {
-                            plan_xmls.push(xml.to_string());
-                            continue;
+                rows_affected = batch.rows_affected;
+                let mut plan_xmls: Vec<String> = Vec::new();
+                for result_set in batch.result_sets {
+                    if let Some(first_row) = result_set.rows.first() {
+                        if let Some(xml) = first_row.try_get::<&str, _>(0).ok().flatten() {
+                            if xml.contains("ShowPlanXML") {
+                                plan_xmls.push(xml.to_string());
+                                continue;
+                            }
                         }
                     }
 
-                    rows_affected += result_set.len() as i64;
+                    let index = result_sets.len();
+                    result_sets.push(build_result_set(index, result_set, &mut truncated_cells));
                 }
                 plan_xml = merge_showplan_xmls(plan_xmls);
 
-                client
-                    .simple_query("SET STATISTICS XML OFF")
-                    .await
-                    .map_err(|e| format!("Failed to disable STATISTICS XML: {}", e))?
-                    .into_results()
-                    .await
-                    .map_err(|e| e.to_string())?;
+                // The grant DMV row is gone once the query finishes; the actual
+                // plan records the same requested / granted / used figures
+                let plan = match plan_xml.clone() {
+                    Some(xml) => parser::parse_plan_async(xml).await.ok(),
+                    None => None,
+                };
+                if let Some(plan) = plan {
+                    for spill in spills::find_spills(&plan) {
+                        messages.push(format!("Warning: {}", spill.message));
+                    }
+                }
 
                 messages.push(format!(
-                    "Query executed. {} row(s) returned with actual execution plan.",
-                    rows_affected
+                    "Query executed. {} row(s) returned{} with actual execution plan.",
+                    total_rows(&result_sets),
+                    affected_suffix(rows_affected)
                 ));
             }
             PlanType::None => {
-                let stream = client
-                    .simple_query(sql)
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that are not supported by the database client.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            format!("Query failed: {}", err_msg)
-                        }
-                    })?;
-
-                let result_sets = stream
-                    .into_results()
-                    .await
-                    .map_err(|e| {
-                        let err_msg = e.to_string();
-                        if err_msg.contains("column type") {
-                            format!(
-                                "Query contains unsupported column types that are not supported by the database client.\n\
-                                Unsupported types include: date, geometry, geography, hierarchyid, and certain CLR types.\n\
-                                \nWorkarounds:\n\
-                                • Cast date columns to datetime: SELECT CAST(LicenseValidTo AS datetime) AS LicenseValidTo\n\
-                                • Exclude these columns from your SELECT statement\n\
-                                \nOriginal error: {}", err_msg
-                            )
-                        } else {
-                            err_msg
-                        }
-                    })?;
-
-                for result_set in &result_sets {
-                    if result_set.is_empty() {
-                        continue;
-                    }
-
-                    if columns.is_empty() {
-                        columns = result_set[0]
-                            .columns()
-                            .iter()
-                            .map(|c| c.name().to_string())
-                            .collect();
-                    }
+                let batch = run_batch(client, sql, false, &mut messages).await?;
 
-                    for row in result_set {
-                        let row_data = extract_row_values(row);
-                        rows.push(row_data);
-                        rows_affected += 1;
-                    }
+                rows_affected = batch.rows_affected;
+                timings = batch.timings;
+                for result_set in batch.result_sets {
+                    let index = result_sets.len();
+                    result_sets.push(build_result_set(index, result_set, &mut truncated_cells));
                 }
 
-                messages.push(format!("Query executed. {} row(s) returned.", rows_affected));
+                messages.push(format!(
+                    "Query executed. {} row(s) returned{}.",
+                    total_rows(&result_sets),
+                    affected_suffix(rows_affected)
+                ));
             }
         }
 
         let duration = start.elapsed();
+        self.touch();
+        *self.truncated_cells.lock().unwrap() = truncated_cells;
+        for result_set in &mut result_sets {
+            self.result_cache.insert(result_set);
+        }
         messages.push(format!("Execution time: {:.2}ms", duration.as_secs_f64() * 1000.0));
 
         Ok(QueryResult {
-            columns,
-            rows,
+            result_sets,
+            rows_affected,
+            timings,
             messages,
             plan_xml,
             duration_ms: duration.as_millis() as u64,
-            rows_affected,
+            wait_stats: query_waits,
+        })
+    }
+
+    /// Run `sql` with a session SET option switched on. The option is
+    /// switched off again whether or not the query succeeded; if that fails the
+    /// option stays recorded in `pending_option_reset` and is retried before the
+    /// next query, so the session never silently keeps skipping execution.
+    async fn run_with_session_option(
+        &self,
+        client: &mut TiberiusClient,
+        option: SessionOption,
+        sql: &str,
+        messages: &mut Vec<String>,
+    ) -> Result<BatchOutput, AppError> {
+        self.enable_session_option(client, option).await?;
+        let outcome = run_batch(client, sql, true, messages).await;
+        self.disable_session_option(client, option, outcome.is_ok(), messages)
+            .await;
+        outcome
+    }
+
+    async fn enable_session_option(
+        &self,
+        client: &mut TiberiusClient,
+        option: SessionOption,
+    ) -> Result<(), AppError> {
+        *self.pending_option_reset.lock().await = Some(option);
+        run_set_statement(client, option.on_statement())
+            .await
+            // Nothing was switched on, but retrying OFF is harmless
+            .map_err(|e| e.context(&format!("Failed to enable {}", option.name())))
+    }
+
+    /// A failed reset is reported as a message when the caller succeeded,
+    /// otherwise only logged so it does not hide the original error.
+    async fn disable_session_option(
+        &self,
+        client: &mut TiberiusClient,
+        option: SessionOption,
+        succeeded: bool,
+        messages: &mut Vec<String>,
+    ) {
+        match run_set_statement(client, option.off_statement()).await {
+            Ok(()) => *self.pending_option_reset.lock().await = None,
+            Err(e) => {
+                let warning = format!(
+                    "Failed to disable {}: {}. It will be reset before the next query.",
+                    option.name(),
+                    e
+                );
+                if succeeded {
+                    messages.push(warning);
+                } else {
+                    tracing::warn!("{}", warning);
+                }
+            }
+        }
+    }
+
+    /// Parse (PARSEONLY) or compile (NOEXEC) a batch without running it. Returns
+    /// the first error as tiberius reports it, with its line, plus every ERROR
+    /// token the server sent for the batch.
+    pub async fn check_batch(
+        &self,
+        sql: &str,
+        option: SessionOption,
+    ) -> Result<(Option<tiberius::error::Error>, Vec<ServerError>), AppError> {
+        let mut client = self.client.lock().await;
+        self.touch();
+        self.restore_session_options(&mut client).await?;
+        self.enable_session_option(&mut client, option).await?;
+
+        let capture = MessageCapture::default();
+        let outcome = async {
+            client.simple_query(sql).await?.into_results().await
+        }
+        .with_subscriber(capture.clone())
+        .await;
+
+        let mut warnings = Vec::new();
+        self.disable_session_option(&mut client, option, true, &mut warnings)
+            .await;
+        for warning in warnings {
+            tracing::warn!("{}", warning);
+        }
+
+        Ok((outcome.err(), capture.take_errors()))
+    }
+
+    /// Switch off any session option left on by a failed cleanup
+    async fn restore_session_options(&self, client: &mut TiberiusClient) -> Result<(), AppError> {
+        let mut pending = self.pending_option_reset.lock().await;
+        if let Some(option) = *pending {
+            run_set_statement(client, option.off_statement())
+                .await
+                .map_err(|e| {
+                    let message = format!(
+                        "Session still has {} enabled and it could not be reset: {}. Reconnect to continue.",
+                        option.name(),
+                        e
+                    );
+                    e.with_message(message)
+                })?;
+            *pending = None;
+        }
+        Ok(())
+    }
+}
+
+/// SET options that change what the session does with a batch: capture
+/// execution plans, or only parse / compile it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionOption {
+    ShowplanXml,
+    StatisticsXml,
+    ParseOnly,
+    NoExec,
+}
+
+impl SessionOption {
+    fn name(&self) -> &'static str {
+        match self {
+            SessionOption::ShowplanXml => "SHOWPLAN_XML",
+            SessionOption::StatisticsXml => "STATISTICS XML",
+            SessionOption::ParseOnly => "PARSEONLY",
+            SessionOption::NoExec => "NOEXEC",
+        }
+    }
+
+    fn on_statement(&self) -> &'static str {
+        match self {
+            SessionOption::ShowplanXml => "SET SHOWPLAN_XML ON",
+            SessionOption::StatisticsXml => "SET STATISTICS XML ON",
+            SessionOption::ParseOnly => "SET PARSEONLY ON",
+            SessionOption::NoExec => "SET NOEXEC ON",
+        }
+    }
+
+    fn off_statement(&self) -> &'static str {
+        match self {
+            SessionOption::ShowplanXml => "SET SHOWPLAN_XML OFF",
+            SessionOption::StatisticsXml => "SET STATISTICS XML OFF",
+            SessionOption::ParseOnly => "SET PARSEONLY OFF",
+            SessionOption::NoExec => "SET NOEXEC OFF",
+        }
+    }
+}
+
+/// Which part of the handshake a tiberius connect error comes from
+fn failed_connection_step(error: &tiberius::error::Error) -> ConnectionStep {
+    match error {
+        tiberius::error::Error::Tls(_) => ConnectionStep::Tls,
+        // Cannot open database requested by the login
+        tiberius::error::Error::Server(token) if token.code() == 4060 => {
+            ConnectionStep::DatabaseAccess
+        }
+        _ => ConnectionStep::Login,
+    }
+}
+
+/// SET LOCK_TIMEOUT for the session, returning the value it replaced
+async fn set_lock_timeout(client: &mut TiberiusClient, timeout_ms: i64) -> Result<i64, AppError> {
+    let previous = client
+        .simple_query("SELECT CAST(@@LOCK_TIMEOUT AS int)")
+        .await?
+        .into_row()
+        .await?
+        .and_then(|row| get_i64(&row, 0))
+        .unwrap_or(-1);
+    run_set_statement(client, &format!("SET LOCK_TIMEOUT {}", timeout_ms.max(-1)))
+        .await
+        .map_err(|e| e.context("Failed to set LOCK_TIMEOUT"))?;
+    Ok(previous)
+}
+
+async fn run_set_statement(client: &mut TiberiusClient, statement: &str) -> Result<(), AppError> {
+    client.simple_query(statement).await?.into_results().await?;
+    Ok(())
+}
+
+/// One result set of a batch as read from the server
+struct BatchResultSet {
+    columns: Vec<ColumnInfo>,
+    rows: Vec<Row>,
+}
+
+/// Everything a batch returned
+struct BatchOutput {
+    result_sets: Vec<BatchResultSet>,
+    /// Rows changed by statements that returned no result set (INSERT,
+    /// UPDATE, DELETE, MERGE, SELECT INTO); 0 with NOCOUNT ON
+    rows_affected: i64,
+    /// Per-statement durations, when the DONE tokens could be matched to
+    /// the statements of the batch
+    timings: Vec<StatementTiming>,
+}
+
+fn build_result_set(
+    index: usize,
+    result_set: BatchResultSet,
+    truncated_cells: &mut HashMap<(usize, usize, usize), Vec<u8>>,
+) -> ResultSet {
+    let rows: Vec<Vec<serde_json::Value>> = result_set
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(row_index, row)| {
+            extract_row_values(row, |column, bytes| {
+                truncated_cells.insert((index, row_index, column), bytes.to_vec());
+            })
+        })
+        .collect();
+    ResultSet {
+        columns: result_set.columns,
+        rows_affected: rows.len() as i64,
+        rows,
+        result_id: None,
+    }
+}
+
+fn total_rows(result_sets: &[ResultSet]) -> i64 {
+    result_sets.iter().map(|r| r.rows_affected).sum()
+}
+
+fn affected_suffix(rows_affected: i64) -> String {
+    if rows_affected > 0 {
+        format!(", {} row(s) affected", rows_affected)
+    } else {
+        String::new()
+    }
+}
+
+/// Run a batch and collect all result sets, explaining unsupported column type errors.
+/// Informational messages (PRINT, RAISERROR < 10, row counts) are appended to `messages`
+/// in the order the server sent them.
+async fn run_batch(
+    client: &mut TiberiusClient,
+    sql: &str,
+    with_plan: bool,
+    messages: &mut Vec<String>,
+) -> Result<BatchOutput, AppError> {
+    let capture = MessageCapture::default();
+    let started = Instant::now();
+    let outcome = async {
+        let mut stream = client
+            .simple_query(sql)
+            .await
+            .map_err(|e| query_error(&e, with_plan, "Query failed: "))?;
+
+        // Walk the stream instead of into_results so result sets without
+        // rows keep their columns
+        let mut result_sets: Vec<BatchResultSet> = Vec::new();
+        let mut row_counts = RowCountTracker::default();
+        let mut statement_ends = StatementEnds::default();
+        while let Some(item) = stream
+            .try_next()
+            .await
+            .map_err(|e| query_error(&e, with_plan, ""))?
+        {
+            row_counts.add_counts(capture.take_row_counts());
+            statement_ends.add(capture.take_done_times());
+            match item {
+                QueryItem::Metadata(meta) => {
+                    row_counts.result_set_started();
+                    if timing::is_plan_rowset(meta.columns()) {
+                        statement_ends.plan_rowset_started();
+                    }
+                    result_sets.push(BatchResultSet {
+                        columns: meta.columns().iter().map(columns::column_info).collect(),
+                        rows: Vec::new(),
+                    });
+                }
+                QueryItem::Row(row) => {
+                    if let Some(result_set) = result_sets.last_mut() {
+                        result_set.rows.push(row);
+                    }
+                }
+            }
+        }
+        row_counts.add_counts(capture.take_row_counts());
+        statement_ends.add(capture.take_done_times());
+        Ok::<_, AppError>(BatchOutput {
+            result_sets,
+            rows_affected: row_counts.rows_affected,
+            timings: timing::statement_timings(sql, started, &statement_ends.done_at),
         })
     }
+    .with_subscriber(capture.clone())
+    .await;
+
+    let server_messages = capture.take();
+    match outcome {
+        Ok(result_sets) => {
+            messages.extend(server_messages);
+            Ok(result_sets)
+        }
+        Err(e) => {
+            errors::record(&e, Some(sql));
+            // Output printed before the failing statement is still useful context
+            if server_messages.is_empty() {
+                Err(e)
+            } else {
+                let message = format!("{}\n{}", server_messages.join("\n"), e);
+                Err(e.with_message(message))
+            }
+        }
+    }
+}
+
+/// The driver's error with the explanation shown for it
+fn query_error(e: &tiberius::error::Error, with_plan: bool, prefix: &str) -> AppError {
+    let error = AppError::from_tiberius(e);
+    let message = describe_query_error(error.message().to_string(), with_plan, prefix);
+    error.with_message(message)
+}
+
+fn describe_query_error(err_msg: String, with_plan: bool, prefix: &str) -> String {
+    // Error 262, raised when SET SHOWPLAN_XML / STATISTICS XML is not allowed
+    if with_plan && err_msg.contains("SHOWPLAN permission denied") {
+        return format!(
+            "Execution plans need the SHOWPLAN permission in this database.\n\
+            Ask a DBA to run: GRANT SHOWPLAN TO [your user]\n\
+            or run the query with 'No Plan'.\n\
+            \nOriginal error: {}",
+            err_msg
+        );
+    }
+    if !err_msg.contains("column type") {
+        return format!("{}{}", prefix, err_msg);
+    }
+
+    if with_plan {
+        format!(
+            "Query contains unsupported column types that cannot be used with execution plans.\n\
+            Unsupported types include: geometry, geography, hierarchyid, and certain CLR types.\n\
+            \nWorkarounds:\n\
+            • Read spatial columns as text: SELECT Shape.STAsText() AS Shape\n\
+            • Exclude these columns from your SELECT statement\n\
+            • Turn on 'Cast unsupported column types' in settings to retry with them cast automatically\n\
+            • Use 'No Plan' mode (though unsupported types will still cause errors)\n\
+            \nOriginal error: {}", err_msg
+        )
+    } else {
+        format!(
+            "Query contains unsupported column types that are not supported by the database client.\n\
+            Unsupported types include: geometry, geography, hierarchyid, and certain CLR types.\n\
+            \nWorkarounds:\n\
+            • Read spatial columns as text: SELECT Shape.STAsText() AS Shape\n\
+            • Exclude these columns from your SELECT statement\n\
+            • Turn on 'Cast unsupported column types' in settings to retry with them cast automatically\n\
+            \nOriginal error: {}", err_msg
+        )
+    }
 }
 
-fn merge_showplan_xmls(xmls: Vec<String>) -> Option<String> {
+pub fn merge_showplan_xmls(xmls: Vec<String>) -> Option<String> {
     if xmls.is_empty() {
         return None;
     }
@@ -463,79 +1082,83 @@ fn merge_showplan_xmls(xmls: Vec<String>) -> Option<String> {
     Some(base)
 }
 
-fn extract_row_values(row: &Row) -> Vec<serde_json::Value> {
+/// `on_truncated` receives the column index and full bytes of every binary
+/// value that was shortened for display.
+fn extract_row_values(
+    row: &Row,
+    mut on_truncated: impl FnMut(usize, &[u8]),
+) -> Vec<serde_json::Value> {
     let columns: &[Column] = row.columns();
     let mut values = Vec::with_capacity(columns.len());
 
-    for i in 0..columns.len() {
-        // Try each type and distinguish between NULL and type mismatch
-        let val =
-            // String types
-            match row.try_get::<&str, _>(i) {
-                Ok(Some(v)) => serde_json::Value::String(v.to_string()),
-                Ok(None) => serde_json::Value::Null,
-                Err(_) => {
-                    // Not a string, try numeric types
-                    match row.try_get::<i32, _>(i) {
-                        Ok(Some(v)) => serde_json::json!(v),
-                        Ok(None) => serde_json::Value::Null,
-                        Err(_) => {
-                            match row.try_get::<i64, _>(i) {
-                                Ok(Some(v)) => serde_json::json!(v),
-                                Ok(None) => serde_json::Value::Null,
-                                Err(_) => {
-                                    match row.try_get::<i16, _>(i) {
-                                        Ok(Some(v)) => serde_json::json!(v),
-                                        Ok(None) => serde_json::Value::Null,
-                                        Err(_) => {
-                                            match row.try_get::<f32, _>(i) {
-                                                Ok(Some(v)) => serde_json::json!(v),
-                                                Ok(None) => serde_json::Value::Null,
-                                                Err(_) => {
-                                                    match row.try_get::<f64, _>(i) {
-                                                        Ok(Some(v)) => serde_json::json!(v),
-                                                        Ok(None) => serde_json::Value::Null,
-                                                        Err(_) => {
-                                                            match row.try_get::<u8, _>(i) {
-                                                                Ok(Some(v)) => serde_json::json!(v),
-                                                                Ok(None) => serde_json::Value::Null,
-                                                                Err(_) => {
-                                                                    match row.try_get::<bool, _>(i) {
-                                                                        Ok(Some(v)) => serde_json::json!(v),
-                                                                        Ok(None) => serde_json::Value::Null,
-                                                                        Err(_) => {
-                                                                            match row.try_get::<chrono::NaiveDateTime, _>(i) {
-                                                                                Ok(Some(v)) => serde_json::Value::String(v.to_string()),
-                                                                                Ok(None) => serde_json::Value::Null,
-                                                                                Err(_) => {
-                                                                                    match row.try_get::<uuid::Uuid, _>(i) {
-                                                                                        Ok(Some(v)) => serde_json::Value::String(v.to_string()),
-                                                                                        Ok(None) => serde_json::Value::Null,
-                                                                                        Err(_) => {
-                                                                                            // For truly unsupported types (geometry, geography, etc.)
-                                                                                            serde_json::Value::String(format!("[Unsupported type: {}]", columns[i].name()))
-                                                                                        }
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    for (i, column) in columns.iter().enumerate() {
+        // Try each type in turn; NULL is only reported by a matching type
+        let val = cell(row, i, |v: &str| serde_json::Value::String(v.to_string()))
+            .or_else(|| cell(row, i, |v: i32| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: i64| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: i16| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: f32| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: f64| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: u8| serde_json::json!(v)))
+            .or_else(|| cell(row, i, |v: bool| serde_json::json!(v)))
+            // Date and time types as ISO-8601, keeping all fractional digits and the offset
+            .or_else(|| {
+                cell(row, i, |v: chrono::NaiveDateTime| {
+                    serde_json::Value::String(v.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+                })
+            })
+            .or_else(|| {
+                cell(row, i, |v: chrono::DateTime<chrono::FixedOffset>| {
+                    serde_json::Value::String(
+                        v.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false),
+                    )
+                })
+            })
+            .or_else(|| {
+                cell(row, i, |v: chrono::NaiveDate| {
+                    serde_json::Value::String(v.format("%Y-%m-%d").to_string())
+                })
+            })
+            .or_else(|| {
+                cell(row, i, |v: chrono::NaiveTime| {
+                    serde_json::Value::String(v.format("%H:%M:%S%.f").to_string())
+                })
+            })
+            .or_else(|| cell(row, i, |v: uuid::Uuid| serde_json::Value::String(v.to_string())))
+            .or_else(|| {
+                cell(row, i, |v: &tiberius::xml::XmlData| {
+                    serde_json::Value::String(v.to_string())
+                })
+            })
+            // binary, varbinary, image and rowversion
+            .or_else(|| {
+                cell(row, i, |v: &[u8]| {
+                    let (text, truncated) = cells::binary_preview(v);
+                    if truncated {
+                        on_truncated(i, v);
                     }
-                }
-            };
+                    serde_json::Value::String(text)
+                })
+            })
+            // For truly unsupported types (geometry, geography, etc.)
+            .unwrap_or_else(|| {
+                serde_json::Value::String(format!("[Unsupported type: {}]", column.name()))
+            });
         values.push(val);
     }
 
     values
 }
+
+/// Read column `idx` as `T`: `None` on a type mismatch, JSON null for NULL
+fn cell<'a, T: tiberius::FromSql<'a>>(
+    row: &'a Row,
+    idx: usize,
+    to_json: impl FnOnce(T) -> serde_json::Value,
+) -> Option<serde_json::Value> {
+    match row.try_get::<T, _>(idx) {
+        Ok(Some(v)) => Some(to_json(v)),
+        Ok(None) => Some(serde_json::Value::Null),
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde_json::Value;
+
+use super::types::ImportedConnection;
+
+// Onboarding users who already keep dozens of servers in SSMS or Azure Data
+// Studio. SSMS exports registered servers as .regsrvr XML; Azure Data Studio
+// keeps connections in its settings.json. Neither export carries a usable
+// password (SSMS encrypts it for the Windows user, ADS keeps it in the OS
+// keychain), so the user is asked for each one before saving.
+
+const DEFAULT_PORT: u16 = 1433;
+const DEFAULT_DATABASE: &str = "master";
+const WINDOWS_AUTH_WARNING: &str =
+    "Uses Windows authentication; enter a SQL login to connect from here";
+
+/// Connections in a .regsrvr export or an Azure Data Studio settings file
+pub fn parse(text: &str) -> Result<Vec<ImportedConnection>, String> {
+    let connections = if text.trim_start().starts_with('<') {
+        parse_registered_servers(text)?
+    } else {
+        parse_azure_data_studio(text)?
+    };
+    if connections.is_empty() {
+        return Err("The file contains no SQL Server connections".into());
+    }
+    Ok(connections)
+}
+
+#[derive(Default)]
+struct RegisteredServer {
+    fields: HashMap<String, String>,
+    parent_uri: Option<String>,
+}
+
+/// SSMS registered servers: each RegisteredServer element holds its
+/// properties as child elements and points at its group through Parent/Uri
+fn parse_registered_servers(xml: &str) -> Result<Vec<ImportedConnection>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut path: Vec<String> = Vec::new();
+    let mut server: Option<(usize, RegisteredServer)> = None;
+    let mut connections = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if name == "RegisteredServer" && server.is_none() {
+                    server = Some((path.len(), RegisteredServer::default()));
+                }
+                path.push(name);
+            }
+            Ok(Event::End(_)) => {
+                path.pop();
+                if server
+                    .as_ref()
+                    .is_some_and(|(depth, _)| *depth == path.len())
+                {
+                    let (_, registered) = server.take().unwrap_or_default();
+                    connections.extend(registered_server(registered));
+                }
+            }
+            Ok(Event::Text(t)) => {
+                let Some((depth, registered)) = server.as_mut() else {
+                    continue;
+                };
+                let text = t.unescape().map_err(|e| e.to_string())?.to_string();
+                if path.get(*depth + 1).is_some_and(|p| p == "Parent")
+                    && path.last().is_some_and(|p| p == "Uri")
+                {
+                    registered.parent_uri = Some(text);
+                } else if path.len() == *depth + 2 {
+                    registered.fields.insert(path[*depth + 1].clone(), text);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid registered servers file: {}", e)),
+            _ => {}
+        }
+    }
+    Ok(connections)
+}
+
+fn registered_server(server: RegisteredServer) -> Option<ImportedConnection> {
+    let fields = server.fields;
+    // Analysis, Integration and Reporting Services servers share the format
+    if fields
+        .get("ServerType")
+        .is_some_and(|t| t != "DatabaseEngine")
+    {
+        return None;
+    }
+    let server_name = fields.get("ServerName")?;
+    let settings = fields
+        .get("ConnectionStringWithEncryptedPassword")
+        .map(|cs| connection_string(cs))
+        .unwrap_or_default();
+
+    let (host, port, mut warnings) = split_server_name(server_name);
+    let integrated = settings
+        .get("integrated security")
+        .or(settings.get("trusted_connection"))
+        .is_some_and(|v| matches!(v.to_ascii_lowercase().as_str(), "true" | "sspi" | "yes"));
+    if integrated {
+        warnings.push(WINDOWS_AUTH_WARNING.to_string());
+    }
+    let database = settings
+        .get("initial catalog")
+        .or(settings.get("database"))
+        .filter(|d| !d.is_empty());
+
+    Some(ImportedConnection {
+        name: fields
+            .get("Name")
+            .filter(|n| !n.is_empty())
+            .unwrap_or(server_name)
+            .clone(),
+        host,
+        port,
+        database: database.map_or(DEFAULT_DATABASE, |d| d).to_string(),
+        username: if integrated {
+            String::new()
+        } else {
+            settings
+                .get("user id")
+                .or(settings.get("uid"))
+                .cloned()
+                .unwrap_or_default()
+        },
+        group: server.parent_uri.as_deref().and_then(group_path),
+        warnings,
+        already_saved: false,
+    })
+}
+
+/// "/RegisteredServersStore/ServerGroup/DatabaseEngineServerGroup/ServerGroup/Prod/ServerGroup/EU"
+/// -> "Prod / EU"; servers directly under the root have no group
+fn group_path(uri: &str) -> Option<String> {
+    let groups: Vec<&str> = uri
+        .split("/ServerGroup/")
+        .skip(2)
+        .map(|g| g.trim_end_matches('/'))
+        .filter(|g| !g.is_empty())
+        .collect();
+    (!groups.is_empty()).then(|| groups.join(" / "))
+}
+
+/// Keys lower-cased, as SqlClient treats them
+fn connection_string(text: &str) -> HashMap<String, String> {
+    text.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect()
+}
+
+/// "tcp:host\INSTANCE,1444" -> host and port. Named instances are found
+/// through the SQL Browser service, which is not used here, so one without
+/// a port keeps the default and says so.
+fn split_server_name(server: &str) -> (String, u16, Vec<String>) {
+    let mut warnings = Vec::new();
+    let server = server.trim();
+    let server = server
+        .strip_prefix("tcp:")
+        .or(server.strip_prefix("TCP:"))
+        .unwrap_or(server);
+    let (server, port) = match server.rsplit_once(',') {
+        Some((host, port)) => match port.trim().parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (server, None),
+        },
+        None => (server, None),
+    };
+    let (host, instance) = match server.split_once('\\') {
+        Some((host, instance)) => (host, Some(instance)),
+        None => (server, None),
+    };
+    let host = match host {
+        "." | "(local)" | "(localdb)" => "localhost",
+        host => host,
+    };
+    if let (Some(instance), None) = (instance, port) {
+        warnings.push(format!(
+            "Named instance {} needs its TCP port; {} is assumed",
+            instance, DEFAULT_PORT
+        ));
+    }
+    (host.to_string(), port.unwrap_or(DEFAULT_PORT), warnings)
+}
+
+/// Azure Data Studio settings.json: `datasource.connections` with their
+/// `datasource.connectionGroups`
+fn parse_azure_data_studio(text: &str) -> Result<Vec<ImportedConnection>, String> {
+    let settings: Value = serde_json::from_str(&strip_json_comments(text))
+        .map_err(|e| format!("Not a registered servers or Azure Data Studio file: {}", e))?;
+    let connections = settings
+        .get("datasource.connections")
+        .and_then(Value::as_array)
+        .ok_or("No datasource.connections in the Azure Data Studio settings")?;
+
+    let groups: HashMap<&str, (&str, Option<&str>)> = settings
+        .get("datasource.connectionGroups")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|g| {
+            Some((
+                g.get("id")?.as_str()?,
+                (
+                    g.get("name")?.as_str()?,
+                    g.get("parentId").and_then(Value::as_str),
+                ),
+            ))
+        })
+        .collect();
+
+    let imported = connections
+        .iter()
+        .filter(|c| {
+            c.get("providerName")
+                .and_then(Value::as_str)
+                .is_none_or(|p| p == "MSSQL")
+        })
+        .filter_map(|c| {
+            let options = c.get("options")?;
+            let option = |key: &str| {
+                options
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+            };
+            let server = option("server")?;
+            let (host, mut port, mut warnings) = split_server_name(server);
+            if let Some(explicit) = options.get("port").and_then(Value::as_u64) {
+                port = u16::try_from(explicit).unwrap_or(port);
+                warnings.retain(|w| !w.starts_with("Named instance"));
+            }
+            let integrated = option("authenticationType").is_some_and(|t| t != "SqlLogin");
+            if integrated {
+                warnings.push(WINDOWS_AUTH_WARNING.to_string());
+            }
+            let database = option("database").unwrap_or(DEFAULT_DATABASE);
+            Some(ImportedConnection {
+                name: option("connectionName")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{}/{}", server, database)),
+                host,
+                port,
+                database: database.to_string(),
+                username: if integrated {
+                    String::new()
+                } else {
+                    option("user").unwrap_or_default().to_string()
+                },
+                group: group_name(&groups, c.get("groupId").and_then(Value::as_str)),
+                warnings,
+                already_saved: false,
+            })
+        })
+        .collect();
+    Ok(imported)
+}
+
+/// Path of an Azure Data Studio group below its hidden ROOT group
+fn group_name<'a>(
+    groups: &HashMap<&'a str, (&'a str, Option<&'a str>)>,
+    mut id: Option<&'a str>,
+) -> Option<String> {
+    let mut names = Vec::new();
+    // Bounded in case of a parent cycle
+    while let Some((name, parent)) = id.and_then(|id| groups.get(id)) {
+        if *name == "ROOT" || names.len() > groups.len() {
+            break;
+        }
+        names.insert(0, *name);
+        id = *parent;
+    }
+    (!names.is_empty()).then(|| names.join(" / "))
+}
+
+/// settings.json allows // and /* */ comments, which serde_json does not
+fn strip_json_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_servers_export() {
+        let xml = r#"<?xml version="1.0"?>
+<model xmlns="http://schemas.serviceml.org/sml/2007/02">
+  <xs:bufferData xmlns:xs="http://schemas.microsoft.com/sqlserver/2008/07/extendedmarkup">
+    <instances>
+      <document><data>
+        <RegisteredServers:RegisteredServer xmlns:RegisteredServers="http://schemas.microsoft.com/sqlserver/RegisteredServers/2007/08">
+          <RegisteredServers:Parent>
+            <sfc:Reference xmlns:sfc="http://schemas.microsoft.com/sqlserver/sfc/serialization/2007/08" sml:ref="true" xmlns:sml="http://schemas.serviceml.org/sml/2007/02">
+              <sml:Uri>/RegisteredServersStore/ServerGroup/DatabaseEngineServerGroup/ServerGroup/Prod/ServerGroup/EU</sml:Uri>
+            </sfc:Reference>
+          </RegisteredServers:Parent>
+          <RegisteredServers:Name type="string">Orders (EU)</RegisteredServers:Name>
+          <RegisteredServers:ServerName type="string">sql-eu-01\ORDERS,1444</RegisteredServers:ServerName>
+          <RegisteredServers:ConnectionStringWithEncryptedPassword type="string">data source=sql-eu-01\ORDERS,1444;initial catalog=Orders;user id=report;password=AQAAANCMnd8BFdERjHoAwE</RegisteredServers:ConnectionStringWithEncryptedPassword>
+          <RegisteredServers:ServerType type="ServerType">DatabaseEngine</RegisteredServers:ServerType>
+        </RegisteredServers:RegisteredServer>
+      </data></document>
+      <document><data>
+        <RegisteredServers:RegisteredServer xmlns:RegisteredServers="http://schemas.microsoft.com/sqlserver/RegisteredServers/2007/08">
+          <RegisteredServers:Name type="string">Dev</RegisteredServers:Name>
+          <RegisteredServers:ServerName type="string">.\SQLEXPRESS</RegisteredServers:ServerName>
+          <RegisteredServers:ConnectionStringWithEncryptedPassword type="string">server=.\SQLEXPRESS;integrated security=true</RegisteredServers:ConnectionStringWithEncryptedPassword>
+          <RegisteredServers:ServerType type="ServerType">DatabaseEngine</RegisteredServers:ServerType>
+        </RegisteredServers:RegisteredServer>
+      </data></document>
+    </instances>
+  </xs:bufferData>
+</model>"#;
+
+        let connections = parse(xml).unwrap();
+        assert_eq!(connections.len(), 2);
+        let eu = &connections[0];
+        assert_eq!(eu.name, "Orders (EU)");
+        assert_eq!((eu.host.as_str(), eu.port), ("sql-eu-01", 1444));
+        assert_eq!(eu.database, "Orders");
+        assert_eq!(eu.username, "report");
+        assert_eq!(eu.group.as_deref(), Some("Prod / EU"));
+        assert!(eu.warnings.is_empty());
+
+        let dev = &connections[1];
+        assert_eq!((dev.host.as_str(), dev.port), ("localhost", 1433));
+        assert_eq!(dev.database, "master");
+        assert_eq!(dev.group, None);
+        assert_eq!(dev.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_azure_data_studio_settings() {
+        let json = r#"{
+            // Connections
+            "workbench.colorTheme": "Default Dark Azure Data Studio",
+            "datasource.connectionGroups": [
+                { "name": "ROOT", "id": "root" },
+                { "name": "Staging", "id": "g1", "parentId": "root" }
+            ],
+            "datasource.connections": [
+                {
+                    "options": {
+                        "connectionName": "",
+                        "server": "tcp:staging.example.com,14330",
+                        "database": "Sales",
+                        "authenticationType": "SqlLogin",
+                        "user": "app_reader",
+                        "password": ""
+                    },
+                    "groupId": "g1",
+                    "providerName": "MSSQL" /* the only provider imported */
+                },
+                { "options": { "server": "pg.example.com" }, "providerName": "PGSQL" }
+            ]
+        }"#;
+
+        let connections = parse(json).unwrap();
+        assert_eq!(connections.len(), 1);
+        let staging = &connections[0];
+        assert_eq!(staging.name, "tcp:staging.example.com,14330/Sales");
+        assert_eq!(
+            (staging.host.as_str(), staging.port),
+            ("staging.example.com", 14330)
+        );
+        assert_eq!(staging.username, "app_reader");
+        assert_eq!(staging.group.as_deref(), Some("Staging"));
+    }
+}
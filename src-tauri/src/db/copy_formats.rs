@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use super::identifiers::{binary_literal, quote_identifier, quote_literal, quote_object_name};
+use super::types::{ColumnInfo, CopyFormat};
+
+// Text renderings of result rows for the clipboard. Escaping depends on the
+// target (pipes in Markdown, quotes in CSV, literals in T-SQL), so it is done
+// here rather than by the grid.
+
+/// Rows per INSERT; SQL Server rejects a VALUES list longer than this
+const INSERT_BATCH_ROWS: usize = 1000;
+
+/// Target of INSERT statements when the caller names none
+const DEFAULT_INSERT_TABLE: &str = "Results";
+
+const NUMERIC_TYPES: &[&str] = &[
+    "bit",
+    "tinyint",
+    "smallint",
+    "int",
+    "bigint",
+    "decimal",
+    "numeric",
+    "money",
+    "smallmoney",
+    "float",
+    "real",
+];
+
+const BINARY_TYPES: &[&str] = &["binary", "varbinary", "image", "timestamp", "rowversion"];
+
+pub fn render(
+    format: CopyFormat,
+    columns: &[ColumnInfo],
+    rows: &[Vec<Value>],
+    table_name: Option<&str>,
+) -> Result<String, String> {
+    match format {
+        CopyFormat::Markdown => Ok(markdown(columns, rows)),
+        CopyFormat::Insert => insert_statements(columns, rows, table_name),
+        CopyFormat::Csv => Ok(csv(columns, rows)),
+        CopyFormat::Json => json(columns, rows),
+    }
+}
+
+fn is_numeric(column: &ColumnInfo) -> bool {
+    NUMERIC_TYPES
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(&column.type_name))
+}
+
+/// Cell text, with bits as 1/0; NULL is None
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(if *b { "1" } else { "0" }.to_string()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn markdown(columns: &[ColumnInfo], rows: &[Vec<Value>]) -> String {
+    let escape = |s: &str| {
+        s.replace('\\', "\\\\")
+            .replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace(['\r', '\n'], "<br>")
+    };
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut out = line(columns.iter().map(|c| escape(&c.name)).collect());
+    out.push_str(&line(
+        columns
+            .iter()
+            .map(|c| if is_numeric(c) { "---:" } else { "---" }.to_string())
+            .collect(),
+    ));
+    for row in rows {
+        out.push_str(&line(
+            row.iter()
+                .map(|v| {
+                    text(v)
+                        .map(|t| escape(&t))
+                        .unwrap_or_else(|| "NULL".to_string())
+                })
+                .collect(),
+        ));
+    }
+    out
+}
+
+fn csv(columns: &[ColumnInfo], rows: &[Vec<Value>]) -> String {
+    let field = |s: &str| {
+        if s.contains([',', '"', '\r', '\n']) || s.starts_with(' ') || s.ends_with(' ') {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut out = columns
+        .iter()
+        .map(|c| field(&c.name))
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push_str("\r\n");
+    for row in rows {
+        // NULL is an empty field
+        let cells: Vec<String> = row
+            .iter()
+            .map(|v| text(v).map(|t| field(&t)).unwrap_or_default())
+            .collect();
+        out.push_str(&cells.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// T-SQL literal of a cell for a column of `column`'s type
+fn sql_literal(column: &ColumnInfo, value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            if is_numeric(column) && s.trim().parse::<f64>().is_ok() {
+                s.trim().to_string()
+            } else if BINARY_TYPES
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(&column.type_name))
+            {
+                // A truncated preview is not valid hex and is copied as text
+                binary_literal(s).unwrap_or_else(|_| quote_literal(s))
+            } else {
+                quote_literal(s)
+            }
+        }
+        other => quote_literal(&other.to_string()),
+    }
+}
+
+fn insert_statements(
+    columns: &[ColumnInfo],
+    rows: &[Vec<Value>],
+    table_name: Option<&str>,
+) -> Result<String, String> {
+    let table = match table_name.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(name) => quote_object_name(name)?,
+        None => quote_identifier(DEFAULT_INSERT_TABLE),
+    };
+    let column_list = columns
+        .iter()
+        .map(|c| quote_identifier(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut out = String::new();
+    for batch in rows.chunks(INSERT_BATCH_ROWS) {
+        out.push_str(&format!("INSERT INTO {} ({}) VALUES\n", table, column_list));
+        let values: Vec<String> = batch
+            .iter()
+            .map(|row| {
+                let literals: Vec<String> = columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| sql_literal(column, value))
+                    .collect();
+                format!("    ({})", literals.join(", "))
+            })
+            .collect();
+        out.push_str(&values.join(",\n"));
+        out.push_str(";\n");
+    }
+    Ok(out)
+}
+
+/// Array of objects keyed by column name, keys in column order. Unnamed
+/// columns become "column<n>" and repeated names get a numeric suffix.
+fn json(columns: &[ColumnInfo], rows: &[Vec<Value>]) -> Result<String, String> {
+    let mut seen = HashSet::new();
+    let keys: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let base = if column.name.is_empty() {
+                format!("column{}", i + 1)
+            } else {
+                column.name.clone()
+            };
+            let mut key = base.clone();
+            let mut n = 2;
+            while !seen.insert(key.clone()) {
+                key = format!("{}_{}", base, n);
+                n += 1;
+            }
+            key
+        })
+        .collect();
+
+    // serde_json's Map sorts keys, so objects are written by hand
+    let mut objects = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut fields = Vec::with_capacity(keys.len());
+        for (key, value) in keys.iter().zip(row) {
+            fields.push(format!(
+                "    {}: {}",
+                serde_json::to_string(key).map_err(|e| e.to_string())?,
+                serde_json::to_string(value).map_err(|e| e.to_string())?
+            ));
+        }
+        objects.push(format!("  {{\n{}\n  }}", fields.join(",\n")));
+    }
+    if objects.is_empty() {
+        return Ok("[]".to_string());
+    }
+    Ok(format!("[\n{}\n]", objects.join(",\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn columns() -> Vec<ColumnInfo> {
+        let column = |name: &str, type_name: &str| ColumnInfo {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            nullable: None,
+            max_length: None,
+            precision: None,
+            scale: None,
+        };
+        vec![
+            column("Id", "int"),
+            column("Name", "nvarchar"),
+            column("Price", "decimal"),
+        ]
+    }
+
+    fn rows() -> Vec<Vec<Value>> {
+        vec![
+            vec![json!(1), json!("O'Brien | Sons"), json!("9.50")],
+            vec![json!(2), json!(null), json!(null)],
+        ]
+    }
+
+    #[test]
+    fn test_insert_statements_quote_and_keep_nulls() {
+        assert_eq!(
+            render(CopyFormat::Insert, &columns(), &rows(), Some("dbo.Orders")).unwrap(),
+            "INSERT INTO [dbo].[Orders] ([Id], [Name], [Price]) VALUES\n    \
+             (1, N'O''Brien | Sons', 9.50),\n    (2, NULL, NULL);\n"
+        );
+        assert!(render(CopyFormat::Insert, &columns(), &rows(), Some("a..b")).is_err());
+    }
+
+    #[test]
+    fn test_markdown_and_csv_escaping() {
+        assert_eq!(
+            render(CopyFormat::Markdown, &columns(), &rows(), None).unwrap(),
+            "| Id | Name | Price |\n| ---: | --- | ---: |\n\
+             | 1 | O'Brien \\| Sons | 9.50 |\n| 2 | NULL | NULL |\n"
+        );
+        let rows = vec![vec![
+            json!(1),
+            json!("say \"hi\", then\nleave"),
+            json!(null),
+        ]];
+        assert_eq!(
+            render(CopyFormat::Csv, &columns(), &rows, None).unwrap(),
+            "Id,Name,Price\r\n1,\"say \"\"hi\"\", then\nleave\",\r\n"
+        );
+    }
+
+    #[test]
+    fn test_json_keys_are_unique() {
+        let mut columns = columns();
+        columns[2].name = "Name".to_string();
+        let out = render(CopyFormat::Json, &columns, &rows()[..1], None).unwrap();
+        assert_eq!(
+            out,
+            "[\n  {\n    \"Id\": 1,\n    \"Name\": \"O'Brien | Sons\",\n    \"Name_2\": \"9.50\"\n  }\n]"
+        );
+        let parsed: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(parsed[0]["Name_2"], json!("9.50"));
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+
+use tiberius::Row;
+
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_literal, quote_object_name};
+use super::rows::{get_i64, get_string};
+use super::types::{DependencyEdge, DependencyGraph, DependencyKind, DependencyNode};
+
+/// Levels of dependencies followed from the root at most
+pub const MAX_DEPTH: u32 = 3;
+
+/// The graph stops growing past this many objects
+const MAX_NODES: usize = 200;
+
+/// An object as a dependency row names it. Objects in other databases, and
+/// references that do not resolve, have no id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectRef {
+    pub object_id: Option<i64>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub name: String,
+    pub type_desc: Option<String>,
+}
+
+impl ObjectRef {
+    /// Node id: the qualified name, case-insensitive like the catalog
+    fn key(&self) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        if let Some(database) = &self.database {
+            parts.push(database);
+        }
+        parts.push(self.schema.as_deref().unwrap_or(""));
+        parts.push(&self.name);
+        parts.join(".").to_lowercase()
+    }
+}
+
+/// `from` depends on `to`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyRow {
+    pub kind: DependencyKind,
+    pub from: ObjectRef,
+    pub to: ObjectRef,
+    /// Foreign key name
+    pub label: Option<String>,
+}
+
+/// Nodes and edges collected level by level; duplicate rows (one per
+/// referenced column) collapse into one edge
+pub struct GraphBuilder {
+    nodes: Vec<DependencyNode>,
+    index: HashMap<String, usize>,
+    edges: Vec<DependencyEdge>,
+    seen_edges: HashSet<(String, String, DependencyKind, Option<String>)>,
+    truncated: bool,
+}
+
+impl GraphBuilder {
+    pub fn new(root: &ObjectRef) -> Self {
+        let mut builder = Self {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            edges: Vec::new(),
+            seen_edges: HashSet::new(),
+            truncated: false,
+        };
+        builder.node(root, 0);
+        builder
+    }
+
+    /// Id of the node for `object`, added at `depth` if new; None once the
+    /// graph is full
+    fn node(&mut self, object: &ObjectRef, depth: u32) -> Option<String> {
+        let key = object.key();
+        if let Some(&i) = self.index.get(&key) {
+            // A later row may know the type the first one lacked
+            if self.nodes[i].type_desc.is_none() {
+                self.nodes[i].type_desc = object.type_desc.clone();
+            }
+            return Some(key);
+        }
+        if self.nodes.len() >= MAX_NODES {
+            self.truncated = true;
+            return None;
+        }
+        self.index.insert(key.clone(), self.nodes.len());
+        self.nodes.push(DependencyNode {
+            id: key.clone(),
+            object_id: object.object_id,
+            database: object.database.clone(),
+            schema: object.schema.clone(),
+            name: object.name.clone(),
+            type_desc: object.type_desc.clone(),
+            depth,
+        });
+        Some(key)
+    }
+
+    /// Add a level of rows; returns the ids of objects seen for the first
+    /// time, to expand at the next level
+    pub fn add_rows(&mut self, rows: &[DependencyRow], depth: u32) -> Vec<i64> {
+        let mut new_ids = Vec::new();
+        for row in rows {
+            let known = |b: &Self, o: &ObjectRef| b.index.contains_key(&o.key());
+            let from_new = !known(self, &row.from);
+            let to_new = !known(self, &row.to);
+            let (Some(from), Some(to)) = (self.node(&row.from, depth), self.node(&row.to, depth))
+            else {
+                continue;
+            };
+            for (object, is_new) in [(&row.from, from_new), (&row.to, to_new)] {
+                if let (true, Some(id)) = (is_new, object.object_id) {
+                    new_ids.push(id);
+                }
+            }
+            if self
+                .seen_edges
+                .insert((from.clone(), to.clone(), row.kind, row.label.clone()))
+            {
+                self.edges.push(DependencyEdge {
+                    from,
+                    to,
+                    kind: row.kind,
+                    label: row.label.clone(),
+                });
+            }
+        }
+        new_ids
+    }
+
+    pub fn build(self) -> DependencyGraph {
+        DependencyGraph {
+            root: self.nodes[0].id.clone(),
+            nodes: self.nodes,
+            edges: self.edges,
+            truncated: self.truncated,
+        }
+    }
+}
+
+/// Expression dependencies and foreign keys in either direction of the
+/// given objects
+fn dependencies_sql(object_ids: &[i64]) -> String {
+    let ids = object_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "SELECT N'expression', d.referencing_id, NULL, OBJECT_SCHEMA_NAME(d.referencing_id), \
+                OBJECT_NAME(d.referencing_id), ro.type_desc, \
+                d.referenced_id, d.referenced_database_name, \
+                COALESCE(d.referenced_schema_name, OBJECT_SCHEMA_NAME(d.referenced_id)), \
+                d.referenced_entity_name, eo.type_desc, NULL \
+         FROM sys.sql_expression_dependencies d \
+         LEFT JOIN sys.objects ro ON ro.object_id = d.referencing_id \
+         LEFT JOIN sys.objects eo ON eo.object_id = d.referenced_id \
+         WHERE d.referencing_class = 1 \
+           AND (d.referenced_id IN ({ids}) OR d.referencing_id IN ({ids})) \
+         UNION ALL \
+         SELECT N'foreignKey', fk.parent_object_id, NULL, OBJECT_SCHEMA_NAME(fk.parent_object_id), \
+                OBJECT_NAME(fk.parent_object_id), N'USER_TABLE', \
+                fk.referenced_object_id, NULL, OBJECT_SCHEMA_NAME(fk.referenced_object_id), \
+                OBJECT_NAME(fk.referenced_object_id), N'USER_TABLE', fk.name \
+         FROM sys.foreign_keys fk \
+         WHERE fk.parent_object_id IN ({ids}) OR fk.referenced_object_id IN ({ids})",
+        ids = ids
+    )
+}
+
+fn object_ref(row: &Row, first: usize) -> Option<ObjectRef> {
+    Some(ObjectRef {
+        object_id: get_i64(row, first),
+        database: get_string(row, first + 1),
+        schema: get_string(row, first + 2),
+        name: get_string(row, first + 3)?,
+        type_desc: get_string(row, first + 4),
+    })
+}
+
+async fn query_rows(client: &mut TiberiusClient, sql: String) -> Result<Vec<Row>, String> {
+    client
+        .simple_query(sql)
+        .await
+        .map_err(|e| format!("Failed to read dependencies: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read dependencies: {}", e))
+}
+
+/// What references `object` and what it references, following new objects
+/// up to `depth` levels out
+pub async fn object_graph(
+    client: &mut TiberiusClient,
+    object: &str,
+    depth: u32,
+) -> Result<DependencyGraph, String> {
+    let sql = format!(
+        "SELECT o.object_id, NULL, SCHEMA_NAME(o.schema_id), o.name, o.type_desc \
+         FROM sys.objects o WHERE o.object_id = OBJECT_ID({})",
+        quote_literal(&quote_object_name(object)?)
+    );
+    let root = query_rows(client, sql)
+        .await?
+        .first()
+        .and_then(|row| object_ref(row, 0))
+        .ok_or_else(|| format!("Object not found: {}", object))?;
+
+    let mut builder = GraphBuilder::new(&root);
+    let mut frontier: Vec<i64> = root.object_id.into_iter().collect();
+    for level in 1..=depth.clamp(1, MAX_DEPTH) {
+        if frontier.is_empty() {
+            break;
+        }
+        let rows: Vec<DependencyRow> = query_rows(client, dependencies_sql(&frontier))
+            .await?
+            .iter()
+            .filter_map(|row| {
+                Some(DependencyRow {
+                    kind: match get_string(row, 0)?.as_str() {
+                        "foreignKey" => DependencyKind::ForeignKey,
+                        _ => DependencyKind::Expression,
+                    },
+                    from: object_ref(row, 1)?,
+                    to: object_ref(row, 6)?,
+                    label: get_string(row, 11),
+                })
+            })
+            .collect();
+        frontier = builder.add_rows(&rows, level);
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(id: Option<i64>, schema: &str, name: &str, type_desc: Option<&str>) -> ObjectRef {
+        ObjectRef {
+            object_id: id,
+            database: None,
+            schema: Some(schema.to_string()),
+            name: name.to_string(),
+            type_desc: type_desc.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_rows_collapse_into_graph() {
+        let orders = object(Some(1), "dbo", "Orders", Some("USER_TABLE"));
+        let lines = object(Some(2), "dbo", "OrderLines", Some("USER_TABLE"));
+        let view = object(Some(3), "dbo", "vOrders", Some("VIEW"));
+        let mut builder = GraphBuilder::new(&orders);
+
+        let row = |kind, from: &ObjectRef, to: &ObjectRef, label: Option<&str>| DependencyRow {
+            kind,
+            from: from.clone(),
+            to: to.clone(),
+            label: label.map(str::to_string),
+        };
+        let new_ids = builder.add_rows(
+            &[
+                row(
+                    DependencyKind::ForeignKey,
+                    &lines,
+                    &orders,
+                    Some("FK_Lines_Orders"),
+                ),
+                row(DependencyKind::Expression, &view, &orders, None),
+                // One row per referenced column
+                row(DependencyKind::Expression, &view, &orders, None),
+                row(
+                    DependencyKind::Expression,
+                    &view,
+                    &object(None, "DBO", "ORDERS", None),
+                    None,
+                ),
+            ],
+            1,
+        );
+        assert_eq!(new_ids, vec![2, 3]);
+
+        let graph = builder.build();
+        assert_eq!(graph.root, "dbo.orders");
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.edges[0].from, "dbo.orderlines");
+        assert_eq!(graph.edges[0].label.as_deref(), Some("FK_Lines_Orders"));
+        assert!(!graph.truncated);
+    }
+}
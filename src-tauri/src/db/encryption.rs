@@ -1,9 +1,12 @@
 use aes_gcm::aead::Aead;
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use argon2::Argon2;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use rand::Rng;
 use sha2::{Digest, Sha256};
 
+use super::types::MasterKeyInfo;
+
 fn derive_key() -> [u8; 32] {
     let hostname = hostname::get()
         .unwrap_or_default()
@@ -21,28 +24,41 @@ fn derive_key() -> [u8; 32] {
     key
 }
 
-pub fn encrypt_password(password: &str) -> Result<String, String> {
-    let key = derive_key();
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+/// Key for data protected by a user passphrase rather than the machine
+pub fn derive_passphrase_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Random bytes for salts and nonces
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill(&mut bytes[..]);
+    bytes
+}
+
+/// AES-256-GCM with a random nonce, returned as nonce || ciphertext
+pub fn encrypt_bytes(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
 
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill(&mut nonce_bytes);
+    let nonce_bytes = random_bytes::<12>();
     let nonce = Nonce::from_slice(&nonce_bytes);
 
     let ciphertext = cipher
-        .encrypt(nonce, password.as_bytes())
+        .encrypt(nonce, plaintext)
         .map_err(|e| e.to_string())?;
 
     let mut combined = nonce_bytes.to_vec();
     combined.extend_from_slice(&ciphertext);
-    Ok(BASE64.encode(&combined))
+    Ok(combined)
 }
 
-pub fn decrypt_password(encrypted: &str) -> Result<String, String> {
-    let key = derive_key();
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())?;
+pub fn decrypt_bytes(key: &[u8; 32], combined: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
 
-    let combined = BASE64.decode(encrypted).map_err(|e| e.to_string())?;
     if combined.len() < 12 {
         return Err("Invalid encrypted data".into());
     }
@@ -50,13 +66,61 @@ pub fn decrypt_password(encrypted: &str) -> Result<String, String> {
     let (nonce_bytes, ciphertext) = combined.split_at(12);
     let nonce = Nonce::from_slice(nonce_bytes);
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|e| e.to_string())?;
+    cipher.decrypt(nonce, ciphertext).map_err(|e| e.to_string())
+}
 
+/// Key for saved passwords when no master passphrase is set
+pub fn machine_key() -> [u8; 32] {
+    derive_key()
+}
+
+pub fn encrypt_password_with(key: &[u8; 32], password: &str) -> Result<String, String> {
+    let combined = encrypt_bytes(key, password.as_bytes())?;
+    Ok(BASE64.encode(&combined))
+}
+
+pub fn decrypt_password_with(key: &[u8; 32], encrypted: &str) -> Result<String, String> {
+    let combined = BASE64.decode(encrypted).map_err(|e| e.to_string())?;
+    let plaintext = decrypt_bytes(key, &combined)?;
     String::from_utf8(plaintext).map_err(|e| e.to_string())
 }
 
+pub fn encrypt_password(password: &str) -> Result<String, String> {
+    encrypt_password_with(&machine_key(), password)
+}
+
+pub fn decrypt_password(encrypted: &str) -> Result<String, String> {
+    decrypt_password_with(&machine_key(), encrypted)
+}
+
+const MASTER_KEY_CHECK: &[u8] = b"SqlPlanForDummies master key";
+
+/// Derive a key from a new master passphrase, with what is needed to
+/// recognize the passphrase later
+pub fn create_master_key(passphrase: &str) -> Result<([u8; 32], MasterKeyInfo), String> {
+    if passphrase.chars().count() < 8 {
+        return Err("Master passphrase must be at least 8 characters".into());
+    }
+    let salt = random_bytes::<16>();
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    let info = MasterKeyInfo {
+        salt: BASE64.encode(salt),
+        verifier: BASE64.encode(encrypt_bytes(&key, MASTER_KEY_CHECK)?),
+    };
+    Ok((key, info))
+}
+
+/// The key for `passphrase`, if it is the master passphrase
+pub fn unlock_master_key(passphrase: &str, info: &MasterKeyInfo) -> Result<[u8; 32], String> {
+    let salt = BASE64.decode(&info.salt).map_err(|e| e.to_string())?;
+    let verifier = BASE64.decode(&info.verifier).map_err(|e| e.to_string())?;
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    match decrypt_bytes(&key, &verifier) {
+        Ok(check) if check == MASTER_KEY_CHECK => Ok(key),
+        _ => Err("Wrong master passphrase".into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,7 +166,7 @@ mod tests {
     #[test]
     fn test_decrypt_too_short() {
         // Valid base64 but too short (< 12 bytes)
-        let short_data = BASE64.encode(&[1, 2, 3]);
+        let short_data = BASE64.encode([1, 2, 3]);
         let result = decrypt_password(&short_data);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Invalid encrypted data"));
@@ -160,4 +224,23 @@ mod tests {
         let decrypted = decrypt_password(&encrypted).unwrap();
         assert_eq!(decrypted, special);
     }
+
+    #[test]
+    fn test_master_key_unlock() {
+        let (key, info) = create_master_key("long enough passphrase").unwrap();
+        assert_eq!(
+            unlock_master_key("long enough passphrase", &info).unwrap(),
+            key
+        );
+        assert!(unlock_master_key("another passphrase", &info).is_err());
+        assert!(create_master_key("short").is_err());
+    }
+
+    #[test]
+    fn test_passphrase_key_roundtrip() {
+        let (key, _) = create_master_key("long enough passphrase").unwrap();
+        let encrypted = encrypt_password_with(&key, "secret").unwrap();
+        assert_eq!(decrypt_password_with(&key, &encrypted).unwrap(), "secret");
+        assert!(decrypt_password(&encrypted).is_err());
+    }
 }
@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::Utc;
+
+use super::timing;
+use super::types::{AppError, EngineError, ErrorExplanation, RecentError};
+
+// Errors keep what SQL Server said about them (number, severity, state,
+// line) next to the text the user sees. The latest ones are kept for the
+// errors panel, whichever session raised them, with a plain-English
+// explanation for common error numbers. Explanations live in
+// error_numbers.json next to this file. Commands return errors sorted by
+// kind (connection, query, permission, cancelled, timeout) so the frontend
+// can branch on the kind instead of matching the text.
+
+/// Errors kept for get_recent_errors; older ones are dropped
+pub const MAX_RECENT_ERRORS: usize = 100;
+
+static EXPLANATIONS: OnceLock<Vec<ErrorExplanation>> = OnceLock::new();
+
+/// Newest last. Errors are raised deep in session code that has no app
+/// handle, so the log is process-wide rather than managed state.
+static RECENT: Mutex<VecDeque<RecentError>> = Mutex::new(VecDeque::new());
+
+pub fn explanations() -> &'static [ErrorExplanation] {
+    EXPLANATIONS.get_or_init(|| {
+        serde_json::from_str(include_str!("error_numbers.json"))
+            .expect("error_numbers.json is valid")
+    })
+}
+
+pub fn explain(number: u32) -> Option<&'static ErrorExplanation> {
+    explanations().iter().find(|e| e.number == number)
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Server errors about what the login may do rather than about the statement
+const PERMISSION_ERRORS: [u32; 7] = [229, 230, 262, 297, 300, 916, 15247];
+/// Server errors raised while logging in or opening the database
+const CONNECTION_ERRORS: [u32; 5] = [4060, 4064, 18452, 18456, 40613];
+/// Lock request (LOCK_TIMEOUT) and memory grant timeouts
+const TIMEOUT_ERRORS: [u32; 2] = [1222, 8645];
+
+impl AppError {
+    /// An error of no particular kind
+    pub fn new(message: impl Into<String>) -> Self {
+        AppError::Other {
+            message: message.into(),
+        }
+    }
+
+    pub fn connection(message: impl Into<String>) -> Self {
+        AppError::ConnectionError {
+            message: message.into(),
+            engine: None,
+        }
+    }
+
+    pub fn permission(message: impl Into<String>) -> Self {
+        AppError::PermissionError {
+            message: message.into(),
+            engine: None,
+        }
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        AppError::Cancelled {
+            message: message.into(),
+        }
+    }
+
+    /// The driver's error, sorted by kind, with the server's details when it
+    /// carries them
+    pub fn from_tiberius(error: &tiberius::error::Error) -> Self {
+        use tiberius::error::Error;
+
+        let message = error.to_string();
+        let token = match error {
+            Error::Server(token) => token,
+            Error::Io { .. } | Error::Tls(_) | Error::Routing { .. } => {
+                return AppError::ConnectionError {
+                    message,
+                    engine: None,
+                }
+            }
+            _ => {
+                return AppError::QueryError {
+                    code: None,
+                    line: None,
+                    message,
+                    engine: None,
+                }
+            }
+        };
+        let engine = Some(Box::new(EngineError {
+            number: token.code(),
+            severity: token.class(),
+            state: token.state(),
+            line: token.line(),
+            procedure: non_empty(token.procedure()),
+            server: non_empty(token.server()),
+            explanation: explain(token.code()).cloned(),
+        }));
+        match token.code() {
+            code if PERMISSION_ERRORS.contains(&code) => {
+                AppError::PermissionError { message, engine }
+            }
+            code if CONNECTION_ERRORS.contains(&code) => {
+                AppError::ConnectionError { message, engine }
+            }
+            code if TIMEOUT_ERRORS.contains(&code) => AppError::Timeout { message, engine },
+            code => AppError::QueryError {
+                code: Some(code),
+                line: Some(token.line()),
+                message,
+                engine,
+            },
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::ConnectionError { message, .. }
+            | AppError::QueryError { message, .. }
+            | AppError::PermissionError { message, .. }
+            | AppError::Cancelled { message }
+            | AppError::Timeout { message, .. }
+            | AppError::Other { message } => message,
+        }
+    }
+
+    /// What the server reported, when SQL Server raised the error
+    pub fn engine(&self) -> Option<&EngineError> {
+        match self {
+            AppError::ConnectionError { engine, .. }
+            | AppError::QueryError { engine, .. }
+            | AppError::PermissionError { engine, .. }
+            | AppError::Timeout { engine, .. } => engine.as_deref(),
+            AppError::Cancelled { .. } | AppError::Other { .. } => None,
+        }
+    }
+
+    /// SQL Server error number
+    pub fn code(&self) -> Option<u32> {
+        self.engine().map(|e| e.number)
+    }
+
+    /// Same kind and details with the text shown to the user
+    pub fn with_message(mut self, text: impl Into<String>) -> Self {
+        match &mut self {
+            AppError::ConnectionError { message, .. }
+            | AppError::QueryError { message, .. }
+            | AppError::PermissionError { message, .. }
+            | AppError::Cancelled { message }
+            | AppError::Timeout { message, .. }
+            | AppError::Other { message } => *message = text.into(),
+        }
+        self
+    }
+
+    /// Say what was being done: "`context`: `message`"
+    pub fn context(self, context: &str) -> Self {
+        let message = format!("{}: {}", context, self.message());
+        self.with_message(message)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<AppError> for String {
+    fn from(error: AppError) -> Self {
+        match error {
+            AppError::ConnectionError { message, .. }
+            | AppError::QueryError { message, .. }
+            | AppError::PermissionError { message, .. }
+            | AppError::Cancelled { message }
+            | AppError::Timeout { message, .. }
+            | AppError::Other { message } => message,
+        }
+    }
+}
+
+/// Helpers and other modules still report errors as text
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::new(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::new(message)
+    }
+}
+
+impl From<tiberius::error::Error> for AppError {
+    fn from(error: tiberius::error::Error) -> Self {
+        AppError::from_tiberius(&error)
+    }
+}
+
+/// Keep an error for the errors panel
+pub fn record(error: &AppError, statement: Option<&str>) {
+    let mut recent = RECENT.lock().unwrap();
+    if recent.len() == MAX_RECENT_ERRORS {
+        recent.pop_front();
+    }
+    recent.push_back(RecentError {
+        occurred_at: Utc::now(),
+        statement_preview: statement.map(timing::preview),
+        error: error.clone(),
+    });
+}
+
+/// Kept errors, newest first
+pub fn recent() -> Vec<RecentError> {
+    RECENT.lock().unwrap().iter().rev().cloned().collect()
+}
+
+pub fn clear() {
+    RECENT.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explanations() {
+        let numbers: Vec<u32> = explanations().iter().map(|e| e.number).collect();
+        let mut distinct = numbers.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), numbers.len(), "error numbers repeat");
+
+        for number in [208, 1205, 2627, 8152, 18456] {
+            assert!(explain(number).is_some(), "no explanation for {}", number);
+        }
+        assert!(explain(50000).is_none());
+    }
+
+    #[test]
+    fn test_recent_errors_are_capped() {
+        for i in 0..MAX_RECENT_ERRORS + 5 {
+            record(&AppError::new(format!("error {}", i)), Some("SELECT  1"));
+        }
+        let recent = recent();
+        assert_eq!(recent.len(), MAX_RECENT_ERRORS);
+        assert_eq!(
+            recent[0].error.message(),
+            format!("error {}", MAX_RECENT_ERRORS + 4)
+        );
+        assert_eq!(recent[0].statement_preview.as_deref(), Some("SELECT 1"));
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let io = tiberius::error::Error::Io {
+            kind: tiberius::error::IoErrorKind::ConnectionReset,
+            message: "reset".into(),
+        };
+        assert!(matches!(
+            AppError::from(io),
+            AppError::ConnectionError { engine: None, .. }
+        ));
+        let conversion = tiberius::error::Error::Conversion("bad value".into());
+        assert!(matches!(
+            AppError::from(conversion),
+            AppError::QueryError { code: None, .. }
+        ));
+
+        let error = AppError::permission("Connection is read-only").context("Query failed");
+        assert_eq!(error.message(), "Query failed: Connection is read-only");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "permissionError",
+                "message": "Query failed: Connection is read-only",
+                "engine": null
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(AppError::cancelled("Stopped")).unwrap(),
+            serde_json::json!({ "kind": "cancelled", "message": "Stopped" })
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::Utc;
+
+use super::connection::TiberiusClient;
+use super::rows::{get_i64, get_string};
+use super::types::ExecutionContext;
+
+/// @@OPTIONS bits, in the order SSMS lists them
+const SET_OPTION_BITS: &[(i64, &str)] = &[
+    (1, "DISABLE_DEF_CNST_CHK"),
+    (2, "IMPLICIT_TRANSACTIONS"),
+    (4, "CURSOR_CLOSE_ON_COMMIT"),
+    (8, "ANSI_WARNINGS"),
+    (16, "ANSI_PADDING"),
+    (32, "ANSI_NULLS"),
+    (64, "ARITHABORT"),
+    (128, "ARITHIGNORE"),
+    (256, "QUOTED_IDENTIFIER"),
+    (512, "NOCOUNT"),
+    (1024, "ANSI_NULL_DFLT_ON"),
+    (2048, "ANSI_NULL_DFLT_OFF"),
+    (4096, "CONCAT_NULL_YIELDS_NULL"),
+    (8192, "NUMERIC_ROUNDABORT"),
+    (16384, "XACT_ABORT"),
+];
+
+/// Names of the SET options that are ON in an @@OPTIONS value
+pub fn set_options_from_bitmask(options: i64) -> Vec<String> {
+    SET_OPTION_BITS
+        .iter()
+        .filter(|(bit, _)| options & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Cardinality estimator the optimizer uses for a database: the legacy (70)
+/// model below compatibility level 120 or when forced by the scoped config,
+/// otherwise the model matching the compatibility level.
+pub fn cardinality_estimator_version(compatibility_level: i64, legacy_forced: bool) -> i64 {
+    if compatibility_level < 120 || legacy_forced {
+        70
+    } else {
+        compatibility_level
+    }
+}
+
+/// Environment of the session right now, for explaining plan differences later
+pub async fn capture(client: &mut TiberiusClient) -> Result<ExecutionContext, String> {
+    let row = client
+        .simple_query(
+            "SELECT CAST(SERVERPROPERTY('ProductVersion') AS nvarchar(128)), \
+                    CAST(SERVERPROPERTY('Edition') AS nvarchar(128)), \
+                    DB_NAME(), \
+                    CAST(d.compatibility_level AS int), \
+                    CAST(@@OPTIONS AS int) \
+             FROM sys.databases d WHERE d.database_id = DB_ID()",
+        )
+        .await
+        .map_err(|e| format!("Failed to read execution context: {}", e))?
+        .into_row()
+        .await
+        .map_err(|e| format!("Failed to read execution context: {}", e))?
+        .ok_or("Failed to read execution context: no rows")?;
+
+    let compatibility_level = get_i64(&row, 3);
+    let legacy_forced = legacy_cardinality_estimation(client).await;
+
+    Ok(ExecutionContext {
+        captured_at: Utc::now(),
+        server_version: get_string(&row, 0).unwrap_or_default(),
+        edition: get_string(&row, 1),
+        database_name: get_string(&row, 2).unwrap_or_default(),
+        compatibility_level,
+        set_options: set_options_from_bitmask(get_i64(&row, 4).unwrap_or(0)),
+        cardinality_estimator_version: compatibility_level
+            .map(|level| cardinality_estimator_version(level, legacy_forced)),
+    })
+}
+
+/// LEGACY_CARDINALITY_ESTIMATION scoped configuration; the view only exists
+/// from SQL Server 2016, earlier versions count as not forced.
+async fn legacy_cardinality_estimation(client: &mut TiberiusClient) -> bool {
+    let row = match client
+        .simple_query(
+            "SELECT CAST(value AS int) FROM sys.database_scoped_configurations \
+             WHERE name = 'LEGACY_CARDINALITY_ESTIMATION'",
+        )
+        .await
+    {
+        Ok(stream) => stream.into_row().await.ok().flatten(),
+        Err(_) => None,
+    };
+    row.and_then(|r| get_i64(&r, 0)).unwrap_or(0) == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_options_from_bitmask() {
+        // SSMS defaults: ANSI_WARNINGS, ANSI_PADDING, ANSI_NULLS, ARITHABORT,
+        // QUOTED_IDENTIFIER, ANSI_NULL_DFLT_ON, CONCAT_NULL_YIELDS_NULL
+        assert_eq!(
+            set_options_from_bitmask(5496),
+            vec![
+                "ANSI_WARNINGS",
+                "ANSI_PADDING",
+                "ANSI_NULLS",
+                "ARITHABORT",
+                "QUOTED_IDENTIFIER",
+                "ANSI_NULL_DFLT_ON",
+                "CONCAT_NULL_YIELDS_NULL"
+            ]
+        );
+        assert!(set_options_from_bitmask(0).is_empty());
+    }
+
+    #[test]
+    fn test_cardinality_estimator_version() {
+        assert_eq!(cardinality_estimator_version(110, false), 70);
+        assert_eq!(cardinality_estimator_version(150, false), 150);
+        assert_eq!(cardinality_estimator_version(150, true), 70);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::types::{FolderAnalysisReport, FolderFileResult, FolderIssueCount};
+use crate::plan::types::IssueSeverity;
+
+// Vetting a set of migration scripts before deployment: every .sql file
+// under a folder is split into its GO batches, each batch is compiled for an
+// estimated plan (nothing runs), and the analyzer's findings are totalled
+// per rule. Files are taken in path order, the order migrations run in.
+
+/// Files analyzed in one go; the report says when more were left out
+pub const MAX_FILES: usize = 500;
+
+/// Every .sql file under `folder`, sorted by path, and whether the list was
+/// cut at MAX_FILES
+pub fn sql_files(folder: &Path) -> Result<(Vec<PathBuf>, bool), String> {
+    let mut files = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(path),
+                Ok(kind)
+                    if kind.is_file()
+                        && path
+                            .extension()
+                            .is_some_and(|ext| ext.eq_ignore_ascii_case("sql")) =>
+                {
+                    files.push(path)
+                }
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    let truncated = files.len() > MAX_FILES;
+    files.truncate(MAX_FILES);
+    Ok((files, truncated))
+}
+
+/// Script text; SSMS saves UTF-16 with a byte order mark, editors UTF-8
+pub fn decode_script(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |units: Vec<u16>| String::from_utf16(&units).map_err(|e| e.to_string());
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|e| e.to_string())
+        }
+        [0xFF, 0xFE, rest @ ..] => utf16(
+            rest.chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        [0xFE, 0xFF, rest @ ..] => utf16(
+            rest.chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect(),
+        ),
+        _ => String::from_utf8(bytes.to_vec()).map_err(|_| "Not UTF-8 or UTF-16 text".to_string()),
+    }
+}
+
+/// 1-based line of a byte offset
+pub fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// Totals and per-rule counts over the analyzed files
+pub fn report(
+    folder: &Path,
+    files: Vec<FolderFileResult>,
+    truncated: bool,
+) -> FolderAnalysisReport {
+    let batches = || files.iter().flat_map(|f| &f.batches);
+    let issues = || batches().flat_map(|b| &b.issues);
+    let severity_count = |severity| issues().filter(|i| i.severity == severity).count();
+
+    let mut counts: HashMap<&str, (FolderIssueCount, HashSet<&str>)> = HashMap::new();
+    for file in &files {
+        for issue in file.batches.iter().flat_map(|b| &b.issues) {
+            let (count, in_files) = counts.entry(&issue.rule_id).or_insert_with(|| {
+                (
+                    FolderIssueCount {
+                        rule_id: issue.rule_id.clone(),
+                        title: issue.title.clone(),
+                        severity: issue.severity,
+                        count: 0,
+                        files: 0,
+                    },
+                    HashSet::new(),
+                )
+            });
+            count.count += 1;
+            in_files.insert(&file.path);
+        }
+    }
+    let mut issue_counts: Vec<FolderIssueCount> = counts
+        .into_values()
+        .map(|(mut count, in_files)| {
+            count.files = in_files.len();
+            count
+        })
+        .collect();
+    issue_counts.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.severity.cmp(&a.severity))
+            .then(a.rule_id.cmp(&b.rule_id))
+    });
+
+    FolderAnalysisReport {
+        folder: folder.display().to_string(),
+        batch_count: batches().count(),
+        failed_batches: batches().filter(|b| b.error.is_some()).count(),
+        critical_issues: severity_count(IssueSeverity::Critical),
+        warning_issues: severity_count(IssueSeverity::Warning),
+        info_issues: severity_count(IssueSeverity::Info),
+        total_estimated_cost: batches().filter_map(|b| b.estimated_cost).sum(),
+        issue_counts,
+        truncated,
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::FolderBatchResult;
+    use crate::plan::types::PlanIssue;
+
+    fn issue(rule_id: &str, severity: IssueSeverity) -> PlanIssue {
+        PlanIssue {
+            rule_id: rule_id.to_string(),
+            severity,
+            title: rule_id.to_string(),
+            description: String::new(),
+            node_id: Some(1),
+            impact: 50.0,
+        }
+    }
+
+    fn batch(cost: Option<f64>, issues: Vec<PlanIssue>, error: Option<&str>) -> FolderBatchResult {
+        FolderBatchResult {
+            line: 1,
+            preview: "SELECT ...".to_string(),
+            estimated_cost: cost,
+            issues,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_report_totals_rules_across_files() {
+        let files = vec![
+            FolderFileResult {
+                path: "001_orders.sql".to_string(),
+                batches: vec![
+                    batch(
+                        Some(1.5),
+                        vec![
+                            issue("table-scan", IssueSeverity::Warning),
+                            issue("key-lookup", IssueSeverity::Warning),
+                        ],
+                        None,
+                    ),
+                    batch(None, Vec::new(), Some("Invalid object name 'dbo.New'.")),
+                ],
+                error: None,
+            },
+            FolderFileResult {
+                path: "002_customers.sql".to_string(),
+                batches: vec![batch(
+                    Some(0.5),
+                    vec![
+                        issue("table-scan", IssueSeverity::Warning),
+                        issue("implicit-conversion", IssueSeverity::Critical),
+                    ],
+                    None,
+                )],
+                error: None,
+            },
+        ];
+
+        let report = report(Path::new("migrations"), files, false);
+        assert_eq!(report.batch_count, 3);
+        assert_eq!(report.failed_batches, 1);
+        assert_eq!(report.critical_issues, 1);
+        assert_eq!(report.warning_issues, 3);
+        assert_eq!(report.total_estimated_cost, 2.0);
+        assert_eq!(report.issue_counts[0].rule_id, "table-scan");
+        assert_eq!(report.issue_counts[0].files, 2);
+        assert_eq!(report.issue_counts[1].rule_id, "implicit-conversion");
+
+        assert_eq!(
+            decode_script(&[0xFF, 0xFE, b'G', 0, b'O', 0]).unwrap(),
+            "GO"
+        );
+        assert_eq!(line_of("SELECT 1\nGO\nSELECT 2", 12), 3);
+    }
+}
@@ -0,0 +1,668 @@
+// T-SQL pretty-printer behind the editor's "format document" and the history
+// view. Only layout and keyword case change: every other token is written
+// back as typed, so a formatted script runs exactly like the original.
+// Clauses start on their own line, lists and AND/OR conditions continue one
+// indent deeper, and subqueries are indented inside their parentheses.
+
+use super::splitter::NON_BLOCK_BEGINS;
+use super::tsql_lexer::{tokenize, TokenKind};
+use super::types::{CommaPlacement, FormatOptions, KeywordCase};
+
+/// Words whose case follows `KeywordCase`; anything else keeps its case
+pub(super) const KEYWORDS: &[&str] = &[
+    "ADD",
+    "ALL",
+    "ALTER",
+    "AND",
+    "ANY",
+    "APPLY",
+    "AS",
+    "ASC",
+    "BEGIN",
+    "BETWEEN",
+    "BREAK",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CATCH",
+    "CHECK",
+    "CLOSE",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONSTRAINT",
+    "CONTINUE",
+    "CONVERT",
+    "CREATE",
+    "CROSS",
+    "CURSOR",
+    "DATABASE",
+    "DEALLOCATE",
+    "DECLARE",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXEC",
+    "EXECUTE",
+    "EXISTS",
+    "FETCH",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "FUNCTION",
+    "GO",
+    "GOTO",
+    "GRANT",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "IN",
+    "INDEX",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "KEY",
+    "LEFT",
+    "LIKE",
+    "MATCHED",
+    "MERGE",
+    "NEXT",
+    "NOCOUNT",
+    "NOT",
+    "NULL",
+    "OF",
+    "OFF",
+    "OFFSET",
+    "ON",
+    "ONLY",
+    "OPEN",
+    "OPTION",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PERCENT",
+    "PIVOT",
+    "PRIMARY",
+    "PRINT",
+    "PROC",
+    "PROCEDURE",
+    "RAISERROR",
+    "REFERENCES",
+    "RETURN",
+    "RETURNS",
+    "RIGHT",
+    "ROLLBACK",
+    "ROW",
+    "ROWS",
+    "SCHEMA",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "THEN",
+    "THROW",
+    "TIES",
+    "TOP",
+    "TRAN",
+    "TRANSACTION",
+    "TRIGGER",
+    "TRUNCATE",
+    "TRY",
+    "UNION",
+    "UNIQUE",
+    "UNPIVOT",
+    "UPDATE",
+    "USE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WAITFOR",
+    "WHEN",
+    "WHERE",
+    "WHILE",
+    "WITH",
+];
+
+/// Keywords that are called like functions, so no space before their "("
+const FUNCTION_KEYWORDS: &[&str] = &["CAST", "CONVERT", "LEFT", "RIGHT"];
+
+/// Keywords that begin a statement when semicolons are left out
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "DECLARE",
+    "IF",
+    "WHILE",
+    "BEGIN",
+    "ELSE",
+    "PRINT",
+    "EXEC",
+    "EXECUTE",
+    "RETURN",
+    "USE",
+    "THROW",
+    "RAISERROR",
+    "TRUNCATE",
+    "CREATE",
+    "ALTER",
+    "DROP",
+    "MERGE",
+    "COMMIT",
+    "ROLLBACK",
+    "WAITFOR",
+    "GOTO",
+    "BREAK",
+    "CONTINUE",
+];
+
+/// Words between a join type and JOIN
+const JOIN_MODIFIERS: &[&str] = &[
+    "INNER", "LEFT", "RIGHT", "FULL", "CROSS", "OUTER", "HASH", "LOOP", "MERGE", "REMOTE",
+];
+
+#[derive(Debug, Clone, Copy)]
+struct Tok<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    /// The source had a line break before this token
+    newline_before: bool,
+    /// ... and at least one empty line
+    blank_before: bool,
+}
+
+fn lex(sql: &str) -> Vec<Tok<'_>> {
+    let mut gap_start = 0;
+    tokenize(sql)
+        .into_iter()
+        .map(|token| {
+            let newlines = sql[gap_start..token.start].matches('\n').count();
+            gap_start = token.end;
+            Tok {
+                kind: token.kind,
+                text: token.text(sql),
+                newline_before: newlines > 0,
+                blank_before: newlines > 1,
+            }
+        })
+        .collect()
+}
+
+/// What the current clause does with commas and AND/OR
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Clause {
+    None,
+    /// SELECT, GROUP BY, ORDER BY, SET and VALUES: one item per line
+    List,
+    /// WHERE, HAVING and join conditions: one condition per line
+    Condition,
+    /// INSERT column list
+    Insert,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Break {
+    Line(usize),
+    Blank(usize),
+}
+
+/// A statement, or a subquery inside parentheses
+struct Frame {
+    base: usize,
+    clause: Clause,
+    /// The next AND belongs to BETWEEN
+    between: bool,
+}
+
+enum Paren {
+    Subquery { close_level: usize },
+    Inline,
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    out: String,
+    line_level: usize,
+    line_empty: bool,
+    pending: Option<Break>,
+    frames: Vec<Frame>,
+    parens: Vec<Paren>,
+    case_depth: usize,
+    /// First keyword of the current top-level statement
+    head: Option<String>,
+    /// No space before the next token (after a unary operator)
+    glue_next: bool,
+}
+
+/// Reformat a script. Text that is not SQL (unterminated strings and the like)
+/// is still written back token for token.
+pub fn format_sql(sql: &str, options: &FormatOptions) -> String {
+    let tokens = lex(sql);
+    let mut formatter = Formatter {
+        options,
+        out: String::with_capacity(sql.len() + sql.len() / 4),
+        line_level: 0,
+        line_empty: true,
+        pending: None,
+        frames: vec![Frame::new(0)],
+        parens: Vec::new(),
+        case_depth: 0,
+        head: None,
+        glue_next: false,
+    };
+    for i in 0..tokens.len() {
+        formatter.token(&tokens, i);
+    }
+
+    let mut out = formatter.out.trim_end().to_string();
+    if !out.is_empty() && sql.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+impl Frame {
+    fn new(base: usize) -> Self {
+        Self {
+            base,
+            clause: Clause::None,
+            between: false,
+        }
+    }
+}
+
+impl Formatter<'_> {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the statement frame is never popped")
+    }
+
+    /// Clauses and conditions break lines unless inside (...) that is not a subquery
+    fn at_clause_level(&self) -> bool {
+        !matches!(self.parens.last(), Some(Paren::Inline))
+    }
+
+    fn token(&mut self, tokens: &[Tok], i: usize) {
+        let tok = tokens[i];
+        let significant =
+            |t: &&Tok| !matches!(t.kind, TokenKind::LineComment | TokenKind::BlockComment);
+        let prev = tokens[..i].iter().rev().find(significant);
+        let prev_upper = prev.map(|t| t.text.to_ascii_uppercase());
+        let next_upper = tokens[i + 1..]
+            .iter()
+            .find(significant)
+            .map(|t| t.text.to_ascii_uppercase());
+
+        let mut pending = self.pending.take();
+        let mut rule = None;
+        match tok.kind {
+            TokenKind::LineComment | TokenKind::BlockComment => {
+                if tok.newline_before {
+                    pending = Some(pending.unwrap_or(Break::Line(self.line_level)));
+                } else {
+                    // A comment that trailed a line stays on it
+                    self.pending = pending;
+                    pending = None;
+                }
+                self.emit(tok, tokens[..i].last(), pending, None);
+                if tok.kind == TokenKind::LineComment {
+                    self.pending = Some(self.pending.unwrap_or(Break::Line(self.line_level)));
+                }
+                return;
+            }
+            TokenKind::Word => {
+                let word = tok.text.to_ascii_uppercase();
+                rule = self.word_break(&word, prev_upper.as_deref(), next_upper.as_deref());
+            }
+            TokenKind::CloseParen => {
+                if let Some(Paren::Subquery { close_level }) = self.parens.pop() {
+                    self.frames.pop();
+                    rule = Some(close_level);
+                }
+            }
+            TokenKind::Comma if self.at_clause_level() && self.frame().clause == Clause::List => {
+                let level = self.frame().base + 1;
+                match self.options.comma_placement {
+                    CommaPlacement::Leading => rule = Some(level),
+                    CommaPlacement::Trailing => {
+                        self.emit(tok, prev, pending, None);
+                        self.pending = Some(Break::Line(level));
+                        return;
+                    }
+                }
+            }
+            TokenKind::BatchSeparator => {
+                rule = Some(0);
+            }
+            _ => {}
+        }
+
+        self.emit(tok, prev, pending, rule);
+
+        match tok.kind {
+            TokenKind::OpenParen => {
+                if matches!(next_upper.as_deref(), Some("SELECT" | "WITH")) {
+                    self.parens.push(Paren::Subquery {
+                        close_level: self.line_level,
+                    });
+                    self.frames.push(Frame::new(self.line_level + 1));
+                } else {
+                    self.parens.push(Paren::Inline);
+                }
+            }
+            // A block's statements are indented one level
+            TokenKind::Word
+                if self.frames.len() == 1
+                    && self.parens.is_empty()
+                    && tok.text.eq_ignore_ascii_case("BEGIN")
+                    && !next_upper
+                        .as_deref()
+                        .is_some_and(|n| NON_BLOCK_BEGINS.contains(&n)) =>
+            {
+                self.frame().base += 1;
+            }
+            TokenKind::Operator => {
+                let unary = matches!(tok.text, "-" | "+" | "~")
+                    && prev.is_none_or(|p| match p.kind {
+                        TokenKind::Operator | TokenKind::OpenParen | TokenKind::Comma => true,
+                        TokenKind::Word => is_keyword(p.text),
+                        _ => false,
+                    });
+                self.glue_next = unary;
+            }
+            TokenKind::Semicolon if self.frames.len() == 1 && self.parens.is_empty() => {
+                self.end_statement();
+                self.pending = Some(Break::Blank(self.frame().base));
+            }
+            TokenKind::BatchSeparator => {
+                self.frames = vec![Frame::new(0)];
+                self.parens.clear();
+                self.end_statement();
+                self.pending = Some(Break::Blank(0));
+            }
+            _ => {}
+        }
+    }
+
+    fn end_statement(&mut self) {
+        self.head = None;
+        self.case_depth = 0;
+        let frame = self.frame();
+        frame.clause = Clause::None;
+        frame.between = false;
+    }
+
+    /// Line break (at an indent level) before a word, updating the clause state
+    fn word_break(&mut self, word: &str, prev: Option<&str>, next: Option<&str>) -> Option<usize> {
+        if word == "CASE" {
+            self.case_depth += 1;
+            return None;
+        }
+        if self.case_depth > 0 && matches!(word, "WHEN" | "THEN" | "ELSE" | "END") {
+            if word == "END" {
+                self.case_depth -= 1;
+            }
+            return None;
+        }
+        if !self.at_clause_level() {
+            return None;
+        }
+
+        if word == "END" && self.frames.len() == 1 {
+            let frame = self.frame();
+            frame.base = frame.base.saturating_sub(1);
+            frame.clause = Clause::None;
+            return Some(frame.base);
+        }
+
+        let statement_start = self.head.is_none();
+        let head = self.head.as_deref();
+        let clause = match word {
+            "SELECT" | "UPDATE" if prev.is_none_or(|p| !matches!(p, "ON" | "FOR" | "THEN")) => {
+                Some(if word == "SELECT" {
+                    Clause::List
+                } else {
+                    Clause::Other
+                })
+            }
+            "SET" if prev.is_none_or(|p| !matches!(p, "UPDATE" | "DELETE")) => Some(Clause::List),
+            "VALUES" => Some(Clause::List),
+            "GROUP" | "ORDER" if next == Some("BY") => Some(Clause::List),
+            "WHERE" | "HAVING" => Some(Clause::Condition),
+            "FROM" if prev.is_none_or(|p| !matches!(p, "DELETE" | "DISTINCT")) => {
+                Some(Clause::Other)
+            }
+            "INSERT" if prev != Some("THEN") => Some(Clause::Insert),
+            "DELETE" if prev.is_none_or(|p| !matches!(p, "ON" | "THEN")) => Some(Clause::Other),
+            "INNER" | "LEFT" | "RIGHT" | "FULL"
+                if matches!(
+                    next,
+                    Some("JOIN" | "OUTER" | "HASH" | "LOOP" | "MERGE" | "REMOTE")
+                ) =>
+            {
+                Some(Clause::Condition)
+            }
+            "CROSS" if matches!(next, Some("JOIN" | "APPLY")) => Some(Clause::Condition),
+            "OUTER"
+                if next == Some("APPLY") && !matches!(prev, Some("LEFT" | "RIGHT" | "FULL")) =>
+            {
+                Some(Clause::Condition)
+            }
+            "JOIN" if !prev.is_some_and(|p| JOIN_MODIFIERS.contains(&p)) => Some(Clause::Condition),
+            "UNION" | "EXCEPT" | "INTERSECT" | "USING" => Some(Clause::Other),
+            "WHEN" if head == Some("MERGE") => Some(Clause::Other),
+            "OPTION" if next == Some("(") => Some(Clause::Other),
+            "WITH" if statement_start => Some(Clause::Other),
+            "ALTER" | "DROP" if head == Some("ALTER") => None,
+            "IF" if head == Some("DROP") => None,
+            "EXEC" | "EXECUTE" if prev == Some("WITH") => None,
+            w if STATEMENT_KEYWORDS.contains(&w) => Some(Clause::Other),
+            _ => None,
+        };
+
+        match clause {
+            Some(clause) => {
+                if statement_start || STATEMENT_KEYWORDS.contains(&word) {
+                    self.head = Some(word.to_string());
+                }
+                let frame = self.frame();
+                frame.clause = clause;
+                frame.between = false;
+                Some(frame.base)
+            }
+            None => {
+                let frame = self.frame();
+                match word {
+                    "BETWEEN" => {
+                        frame.between = true;
+                        None
+                    }
+                    "AND" if frame.between => {
+                        frame.between = false;
+                        None
+                    }
+                    "AND" | "OR" if frame.clause == Clause::Condition => Some(frame.base + 1),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, tok: Tok, prev: Option<&Tok>, pending: Option<Break>, rule: Option<usize>) {
+        let brk = match (pending, rule) {
+            (Some(Break::Blank(_)), Some(level)) => Some(Break::Blank(level)),
+            (_, Some(level)) => Some(Break::Line(level)),
+            (pending, None) => pending,
+        };
+        match brk {
+            Some(Break::Line(level)) => self.newline(level, tok.blank_before),
+            Some(Break::Blank(level)) => self.newline(level, true),
+            None => {
+                if !self.line_empty && self.space_before(tok, prev) {
+                    self.out.push(' ');
+                }
+            }
+        }
+
+        if tok.kind == TokenKind::Word || tok.kind == TokenKind::BatchSeparator {
+            self.out.push_str(&self.keyword_case(tok.text));
+        } else {
+            self.out.push_str(tok.text);
+        }
+        self.line_empty = false;
+        self.glue_next = false;
+    }
+
+    fn newline(&mut self, level: usize, blank: bool) {
+        if blank {
+            let trimmed = self.out.trim_end().len();
+            self.out.truncate(trimmed);
+            if !self.out.is_empty() {
+                self.out.push_str("\n\n");
+            }
+        } else {
+            let trimmed = self.out.trim_end_matches(' ').len();
+            self.out.truncate(trimmed);
+            if !self.out.is_empty() && !self.out.ends_with('\n') {
+                self.out.push('\n');
+            }
+        }
+        if !self.out.is_empty() {
+            let width = level * self.options.indent_width;
+            self.out.extend(std::iter::repeat_n(' ', width));
+        }
+        self.line_level = level;
+        self.line_empty = true;
+    }
+
+    fn space_before(&self, tok: Tok, prev: Option<&Tok>) -> bool {
+        let Some(prev) = prev else {
+            return false;
+        };
+        if self.glue_next {
+            return false;
+        }
+        match (prev.kind, tok.kind) {
+            (
+                _,
+                TokenKind::Comma | TokenKind::CloseParen | TokenKind::Semicolon | TokenKind::Dot,
+            ) => false,
+            (TokenKind::OpenParen | TokenKind::Dot, _) => false,
+            (TokenKind::Word, TokenKind::OpenParen) if is_keyword(prev.text) => {
+                !FUNCTION_KEYWORDS.contains(&prev.text.to_ascii_uppercase().as_str())
+            }
+            // Function call, unless this names a table or module being defined
+            (TokenKind::Word | TokenKind::QuotedName, TokenKind::OpenParen) => {
+                self.declares_columns()
+            }
+            _ => true,
+        }
+    }
+
+    /// `INSERT INTO t (a, b)`, `CREATE TABLE t (...)` and the like
+    fn declares_columns(&self) -> bool {
+        let clause = self.frames.last().map(|f| f.clause);
+        self.parens.is_empty()
+            && (clause == Some(Clause::Insert)
+                || (clause == Some(Clause::Other)
+                    && matches!(self.head.as_deref(), Some("CREATE" | "ALTER"))))
+    }
+
+    fn keyword_case(&self, text: &str) -> String {
+        if !is_keyword(text) {
+            return text.to_string();
+        }
+        match self.options.keyword_case {
+            KeywordCase::Upper => text.to_ascii_uppercase(),
+            KeywordCase::Lower => text.to_ascii_lowercase(),
+            KeywordCase::Preserve => text.to_string(),
+        }
+    }
+}
+
+fn is_keyword(text: &str) -> bool {
+    let upper = text.to_ascii_uppercase();
+    KEYWORDS.contains(&upper.as_str()) || text.eq_ignore_ascii_case("GO")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(sql: &str) -> String {
+        format_sql(sql, &FormatOptions::default())
+    }
+
+    #[test]
+    fn test_breaks_clauses_lists_and_conditions() {
+        assert_eq!(
+            format("select a, b from dbo.t where a = 1 and b between 1 and 2 order by a"),
+            "SELECT a,\n    b\nFROM dbo.t\nWHERE a = 1\n    AND b BETWEEN 1 AND 2\nORDER BY a"
+        );
+    }
+
+    #[test]
+    fn test_style_options() {
+        let options = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            comma_placement: CommaPlacement::Leading,
+            indent_width: 2,
+        };
+        assert_eq!(
+            format_sql("SELECT a, b FROM t GROUP BY a, b", &options),
+            "select a\n  , b\nfrom t\ngroup by a\n  , b"
+        );
+    }
+
+    #[test]
+    fn test_indents_subqueries_and_joins() {
+        assert_eq!(
+            format("select * from t inner join u on u.id = t.id and u.x = 1 where t.id in (select id from v)"),
+            "SELECT *\nFROM t\nINNER JOIN u ON u.id = t.id\n    AND u.x = 1\nWHERE t.id IN (\n    SELECT id\n    FROM v\n)"
+        );
+    }
+
+    #[test]
+    fn test_keeps_strings_comments_and_function_calls() {
+        assert_eq!(
+            format("select 'from x' as [select], -- note\n count(*), cast(n as int) from t"),
+            "SELECT 'from x' AS [select], -- note\n    count(*),\n    CAST(n AS int)\nFROM t"
+        );
+    }
+
+    #[test]
+    fn test_statements_blocks_and_batches() {
+        assert_eq!(
+            format("select 1; select 2\ngo\nif @x = -1 begin select case when a = 1 then 'y' else 'n' end from t end"),
+            "SELECT 1;\n\nSELECT 2\nGO\n\nIF @x = -1\nBEGIN\n    SELECT CASE WHEN a = 1 THEN 'y' ELSE 'n' END\n    FROM t\nEND"
+        );
+    }
+
+    #[test]
+    fn test_insert_column_list() {
+        assert_eq!(
+            format("insert into dbo.t(a, b) values (1, 'x'), (2, 'y')"),
+            "INSERT INTO dbo.t (a, b)\nVALUES (1, 'x'),\n    (2, 'y')"
+        );
+    }
+
+    #[test]
+    fn test_formatting_only_changes_whitespace_and_keyword_case() {
+        let sql = "WITH c AS (SELECT TOP (5) x FROM [t] WHERE y <> N'a''b' /* c */) \
+                   SELECT c.x, x.* FROM c CROSS APPLY dbo.f(c.x) x OPTION (RECOMPILE)";
+        let tokens = |s: &str| -> Vec<String> {
+            lex(s).iter().map(|t| t.text.to_ascii_uppercase()).collect()
+        };
+        assert_eq!(tokens(&format(sql)), tokens(sql));
+    }
+}
@@ -0,0 +1,210 @@
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_identifier, quote_literal, quote_object_name};
+use super::rows::{get_f64, get_i64, get_string};
+use super::types::{
+    FragmentationReport, FragmentationRequest, FragmentationScanMode, IndexFragmentation,
+    MaintenanceAction,
+};
+
+// "The plan says scan - maybe the index is shattered": leaf-level
+// fragmentation per index partition, with the usual maintenance guidance
+// applied. Light fragmentation is reorganized (online, interruptible), heavy
+// fragmentation rebuilt, and small indexes are left alone since their pages
+// are read in one go regardless.
+
+const DEFAULT_REORGANIZE_PERCENT: f64 = 5.0;
+const DEFAULT_REBUILD_PERCENT: f64 = 30.0;
+const DEFAULT_MIN_PAGE_COUNT: i64 = 1000;
+
+fn mode_name(mode: FragmentationScanMode) -> &'static str {
+    match mode {
+        FragmentationScanMode::Limited => "LIMITED",
+        FragmentationScanMode::Sampled => "SAMPLED",
+        FragmentationScanMode::Detailed => "DETAILED",
+    }
+}
+
+/// Fragmentation of the table's indexes, or of every user table's, with a
+/// REBUILD/REORGANIZE statement where the thresholds call for one
+pub async fn index_fragmentation(
+    client: &mut TiberiusClient,
+    request: &FragmentationRequest,
+) -> Result<FragmentationReport, String> {
+    let (check, object_id) = match request.table.as_deref().map(str::trim) {
+        Some(table) if !table.is_empty() => {
+            let object = quote_literal(&quote_object_name(table)?);
+            (
+                format!(
+                    "IF OBJECT_ID({object}) IS NULL \
+                         RAISERROR(N'Table not found: %s', 16, 1, {name}); ",
+                    name = quote_literal(table),
+                ),
+                format!("OBJECT_ID({object})"),
+            )
+        }
+        _ => (String::new(), "NULL".to_string()),
+    };
+
+    // index_level = 0 keeps the leaf level when DETAILED also returns the
+    // upper levels; LOB and row-overflow pages are not fragmented this way
+    let sql = format!(
+        "{check}\
+         SELECT s.name, o.name, i.name, ps.index_id, ps.index_type_desc, ps.partition_number, \
+                (SELECT COUNT(*) FROM sys.partitions p \
+                 WHERE p.object_id = ps.object_id AND p.index_id = ps.index_id), \
+                ps.avg_fragmentation_in_percent, ps.page_count, ps.fragment_count, \
+                ps.avg_page_space_used_in_percent \
+         FROM sys.dm_db_index_physical_stats(DB_ID(), {object_id}, NULL, NULL, N'{mode}') ps \
+         JOIN sys.objects o ON o.object_id = ps.object_id \
+         JOIN sys.schemas s ON s.schema_id = o.schema_id \
+         LEFT JOIN sys.indexes i ON i.object_id = ps.object_id AND i.index_id = ps.index_id \
+         WHERE o.is_ms_shipped = 0 AND ps.index_level = 0 \
+           AND ps.alloc_unit_type_desc = N'IN_ROW_DATA' \
+           AND ps.index_type_desc IN (N'HEAP', N'CLUSTERED INDEX', N'NONCLUSTERED INDEX') \
+         ORDER BY ps.avg_fragmentation_in_percent DESC, ps.page_count DESC",
+        mode = mode_name(request.scan_mode),
+    );
+
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| format!("Failed to read index fragmentation: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read index fragmentation: {}", e))?;
+
+    let indexes: Vec<IndexFragmentation> = rows
+        .iter()
+        .map(|row| {
+            let mut index = IndexFragmentation {
+                schema_name: get_string(row, 0).unwrap_or_default(),
+                table_name: get_string(row, 1).unwrap_or_default(),
+                index_name: get_string(row, 2),
+                index_id: get_i64(row, 3).unwrap_or(0),
+                index_type: get_string(row, 4).unwrap_or_default(),
+                partition_number: get_i64(row, 5).unwrap_or(1),
+                partitioned: get_i64(row, 6).unwrap_or(1) > 1,
+                avg_fragmentation_percent: get_f64(row, 7).unwrap_or(0.0),
+                page_count: get_i64(row, 8).unwrap_or(0),
+                fragment_count: get_i64(row, 9),
+                avg_page_space_used_percent: get_f64(row, 10),
+                action: MaintenanceAction::None,
+                script: None,
+            };
+            index.action = recommend(&index, request);
+            index.script = maintenance_script(&index);
+            index
+        })
+        .collect();
+
+    Ok(FragmentationReport {
+        scan_mode: request.scan_mode,
+        script: indexes
+            .iter()
+            .filter_map(|i| i.script.as_deref())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        indexes,
+    })
+}
+
+fn recommend(index: &IndexFragmentation, request: &FragmentationRequest) -> MaintenanceAction {
+    let reorganize = request
+        .reorganize_percent
+        .unwrap_or(DEFAULT_REORGANIZE_PERCENT);
+    let rebuild = request.rebuild_percent.unwrap_or(DEFAULT_REBUILD_PERCENT);
+    let min_pages = request.min_page_count.unwrap_or(DEFAULT_MIN_PAGE_COUNT);
+    let fragmentation = index.avg_fragmentation_percent;
+
+    if index.page_count < min_pages {
+        MaintenanceAction::None
+    } else if fragmentation >= rebuild {
+        MaintenanceAction::Rebuild
+    } else if fragmentation >= reorganize && index.index_name.is_some() {
+        // Heaps have no REORGANIZE
+        MaintenanceAction::Reorganize
+    } else {
+        MaintenanceAction::None
+    }
+}
+
+fn maintenance_script(index: &IndexFragmentation) -> Option<String> {
+    let verb = match index.action {
+        MaintenanceAction::None => return None,
+        MaintenanceAction::Reorganize => "REORGANIZE",
+        MaintenanceAction::Rebuild => "REBUILD",
+    };
+    let table = format!(
+        "{}.{}",
+        quote_identifier(&index.schema_name),
+        quote_identifier(&index.table_name)
+    );
+    let target = match &index.index_name {
+        Some(name) => format!("ALTER INDEX {} ON {}", quote_identifier(name), table),
+        None => format!("ALTER TABLE {}", table),
+    };
+    let partition = if index.partitioned {
+        format!(" PARTITION = {}", index.partition_number)
+    } else {
+        String::new()
+    };
+    Some(format!("{} {}{};", target, verb, partition))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(name: Option<&str>, fragmentation: f64, page_count: i64) -> IndexFragmentation {
+        IndexFragmentation {
+            schema_name: "Sales".to_string(),
+            table_name: "Order Lines".to_string(),
+            index_name: name.map(str::to_string),
+            index_id: if name.is_some() { 2 } else { 0 },
+            index_type: "NONCLUSTERED INDEX".to_string(),
+            partition_number: 1,
+            partitioned: false,
+            avg_fragmentation_percent: fragmentation,
+            page_count,
+            fragment_count: None,
+            avg_page_space_used_percent: None,
+            action: MaintenanceAction::None,
+            script: None,
+        }
+    }
+
+    #[test]
+    fn test_thresholds_pick_the_statement() {
+        let request = FragmentationRequest {
+            table: None,
+            scan_mode: FragmentationScanMode::Limited,
+            reorganize_percent: None,
+            rebuild_percent: None,
+            min_page_count: None,
+        };
+        let script = |mut index: IndexFragmentation| {
+            index.action = recommend(&index, &request);
+            maintenance_script(&index)
+        };
+
+        assert_eq!(script(index(Some("IX_Product"), 2.0, 50_000)), None);
+        assert_eq!(
+            script(index(Some("IX_Product"), 12.0, 50_000)).as_deref(),
+            Some("ALTER INDEX [IX_Product] ON [Sales].[Order Lines] REORGANIZE;")
+        );
+        assert_eq!(script(index(Some("IX_Product"), 80.0, 200)), None);
+        assert_eq!(script(index(None, 12.0, 50_000)), None);
+        assert_eq!(
+            script(index(None, 45.0, 50_000)).as_deref(),
+            Some("ALTER TABLE [Sales].[Order Lines] REBUILD;")
+        );
+
+        let mut partition = index(Some("IX_Product"), 45.0, 50_000);
+        partition.partitioned = true;
+        partition.partition_number = 3;
+        assert_eq!(
+            script(partition).as_deref(),
+            Some("ALTER INDEX [IX_Product] ON [Sales].[Order Lines] REBUILD PARTITION = 3;")
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+
+use super::identifiers::quote_identifier;
+use super::splitter::split_statements;
+use super::tsql_lexer::{code_tokens, Token, TokenKind};
+use super::types::QueryHints;
+
+// Adds query hints (OPTION clause) and table index hints to the DML
+// statements of a script. Statements are found with the splitter and
+// rewritten token by token, so hints never land inside comments, strings or
+// a different statement, and an existing OPTION / WITH (...) list is extended
+// rather than duplicated.
+
+const DML_KEYWORDS: &[&str] = &["SELECT", "INSERT", "UPDATE", "DELETE", "MERGE"];
+
+/// Words that follow a table reference and therefore cannot be its alias
+const NOT_ALIASES: &[&str] = &[
+    "WHERE",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "OUTER",
+    "ON",
+    "GROUP",
+    "ORDER",
+    "HAVING",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "OPTION",
+    "WITH",
+    "FOR",
+    "SET",
+    "OUTPUT",
+    "APPLY",
+    "PIVOT",
+    "UNPIVOT",
+    "TABLESAMPLE",
+    "WHEN",
+    "USING",
+    "SELECT",
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "MERGE",
+];
+
+pub fn apply_hints(sql: &str, hints: &QueryHints) -> Result<String, String> {
+    let option_hints = option_hints(hints)?;
+    let table_hints = table_hints(hints);
+    if option_hints.is_empty() && table_hints.is_empty() {
+        return Err("No hints selected".to_string());
+    }
+
+    let mut edits: Vec<(usize, String)> = Vec::new();
+    let mut hinted_statements = 0;
+    let mut tables_found = vec![false; table_hints.len()];
+
+    let all_tokens = code_tokens(sql);
+    for span in split_statements(sql) {
+        let tokens: Vec<Token> = all_tokens
+            .iter()
+            .filter(|t| t.start >= span.start && t.end <= span.end)
+            .copied()
+            .collect();
+        if !is_dml(sql, &tokens) {
+            continue;
+        }
+        hinted_statements += 1;
+
+        for (i, (table, indexes)) in table_hints.iter().enumerate() {
+            let found = add_table_hint(sql, &tokens, table, indexes, &mut edits);
+            tables_found[i] |= found;
+        }
+        if !option_hints.is_empty() {
+            add_option_hints(sql, &tokens, span.end, &option_hints, &mut edits);
+        }
+    }
+
+    if hinted_statements == 0 {
+        return Err(
+            "The script has no SELECT, INSERT, UPDATE, DELETE or MERGE statement to hint"
+                .to_string(),
+        );
+    }
+    if let Some((table, _)) = table_hints
+        .iter()
+        .zip(&tables_found)
+        .find(|(_, found)| !**found)
+        .map(|(hint, _)| hint)
+    {
+        return Err(format!("Table {} is not referenced in the query", table));
+    }
+
+    // Apply back to front so earlier offsets stay valid
+    edits.sort_by_key(|(pos, _)| std::cmp::Reverse(*pos));
+    let mut out = sql.to_string();
+    for (pos, text) in edits {
+        out.insert_str(pos, &text);
+    }
+    Ok(out)
+}
+
+/// Contents of the OPTION clause, in SQL Server's documented order
+fn option_hints(hints: &QueryHints) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    if hints.recompile {
+        out.push("RECOMPILE".to_string());
+    }
+    if let Some(maxdop) = hints.maxdop {
+        out.push(format!("MAXDOP {}", maxdop));
+    }
+    if hints.force_order {
+        out.push("FORCE ORDER".to_string());
+    }
+    if !hints.use_hints.is_empty() {
+        for name in &hints.use_hints {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid USE HINT name: {}", name));
+            }
+        }
+        let names: Vec<String> = hints
+            .use_hints
+            .iter()
+            .map(|n| format!("'{}'", n.to_ascii_uppercase()))
+            .collect();
+        out.push(format!("USE HINT ({})", names.join(", ")));
+    }
+    Ok(out)
+}
+
+/// Index hints grouped by table, keeping the order the user picked them in
+fn table_hints(hints: &QueryHints) -> Vec<(String, Vec<String>)> {
+    let mut grouped: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for hint in &hints.table_hints {
+        grouped
+            .entry(normalize_name(&hint.table))
+            .or_insert_with(|| (hint.table.clone(), Vec::new()))
+            .1
+            .push(hint.index.clone());
+    }
+    grouped.into_values().collect()
+}
+
+fn word(sql: &str, token: &Token) -> Option<String> {
+    (token.kind == TokenKind::Word).then(|| sql[token.start..token.end].to_ascii_uppercase())
+}
+
+/// DML statement, including one led by a CTE
+fn is_dml(sql: &str, tokens: &[Token]) -> bool {
+    let Some(first) = tokens.first().and_then(|t| word(sql, t)) else {
+        return false;
+    };
+    if first != "WITH" {
+        return DML_KEYWORDS.contains(&first.as_str());
+    }
+    let mut depth = 0usize;
+    for token in tokens {
+        match token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth = depth.saturating_sub(1),
+            TokenKind::Word
+                if depth == 0
+                    && word(sql, token).is_some_and(|w| DML_KEYWORDS.contains(&w.as_str())) =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// "Sales.[Order Lines]" -> "sales.order lines"
+fn normalize_name(name: &str) -> String {
+    name.split('.')
+        .map(|part| {
+            part.trim()
+                .trim_start_matches(['[', '"'])
+                .trim_end_matches([']', '"'])
+                .to_lowercase()
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// A hint for "Orders" matches dbo.Orders; one for "dbo.Orders" needs the schema
+fn names_match(reference: &str, wanted: &str) -> bool {
+    reference == wanted || reference.ends_with(&format!(".{}", wanted))
+}
+
+/// Hint every FROM/JOIN reference to `table`; returns whether one was found
+fn add_table_hint(
+    sql: &str,
+    tokens: &[Token],
+    table: &str,
+    indexes: &[String],
+    edits: &mut Vec<(usize, String)>,
+) -> bool {
+    let wanted = normalize_name(table);
+    let index_list = indexes
+        .iter()
+        .map(|i| quote_identifier(i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut found = false;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if !word(sql, token).is_some_and(|w| w == "FROM" || w == "JOIN") {
+            continue;
+        }
+
+        // Multi-part object name
+        let mut j = i + 1;
+        let name_start = j;
+        while let Some(t) = tokens.get(j) {
+            let is_part = t.kind == TokenKind::QuotedName
+                || (t.kind == TokenKind::Word
+                    && !word(sql, t).is_some_and(|w| NOT_ALIASES.contains(&w.as_str())));
+            if !is_part && t.kind != TokenKind::Dot {
+                break;
+            }
+            j += 1;
+            if is_part && tokens.get(j).map(|n| n.kind) != Some(TokenKind::Dot) {
+                break;
+            }
+        }
+        if j == name_start {
+            continue;
+        }
+        let reference = normalize_name(&sql[tokens[name_start].start..tokens[j - 1].end]);
+        if !names_match(&reference, &wanted) {
+            continue;
+        }
+
+        // Optional alias, with or without AS
+        let mut insert_after = tokens[j - 1].end;
+        if tokens.get(j).and_then(|t| word(sql, t)).as_deref() == Some("AS") {
+            j += 1;
+        }
+        if let Some(t) = tokens.get(j) {
+            let alias = t.kind == TokenKind::QuotedName
+                || word(sql, t).is_some_and(|w| !NOT_ALIASES.contains(&w.as_str()));
+            if alias {
+                insert_after = t.end;
+                j += 1;
+            }
+        }
+
+        // Existing table hints: add to the list
+        let existing = tokens.get(j).and_then(|t| word(sql, t)).as_deref() == Some("WITH")
+            && tokens.get(j + 1).map(|t| t.kind) == Some(TokenKind::OpenParen);
+        if existing {
+            if let Some(close) = matching_paren(tokens, j + 1) {
+                edits.push((tokens[close].start, format!(", INDEX({})", index_list)));
+            }
+        } else {
+            edits.push((insert_after, format!(" WITH (INDEX({}))", index_list)));
+        }
+        found = true;
+    }
+    found
+}
+
+fn add_option_hints(
+    sql: &str,
+    tokens: &[Token],
+    statement_end: usize,
+    option_hints: &[String],
+    edits: &mut Vec<(usize, String)>,
+) {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth = depth.saturating_sub(1),
+            TokenKind::Word if depth == 0 => {
+                let is_option = word(sql, token).as_deref() == Some("OPTION")
+                    && tokens.get(i + 1).map(|t| t.kind) == Some(TokenKind::OpenParen);
+                if !is_option {
+                    continue;
+                }
+                let Some(close) = matching_paren(tokens, i + 1) else {
+                    return;
+                };
+                // Skip hints the clause already has
+                let existing = sql[tokens[i + 1].end..tokens[close].start].to_ascii_uppercase();
+                let missing: Vec<&str> = option_hints
+                    .iter()
+                    .filter(|h| !existing.contains(hint_keyword(h)))
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    edits.push((tokens[close].start, format!(", {}", missing.join(", "))));
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+    edits.push((
+        statement_end,
+        format!(" OPTION ({})", option_hints.join(", ")),
+    ));
+}
+
+/// "MAXDOP 4" -> "MAXDOP", "USE HINT (...)" -> "USE HINT"
+fn hint_keyword(hint: &str) -> &str {
+    hint.split(|c: char| c.is_ascii_digit() || c == '(')
+        .next()
+        .unwrap_or(hint)
+        .trim()
+}
+
+fn matching_paren(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::TableIndexHint;
+
+    fn hints() -> QueryHints {
+        QueryHints {
+            recompile: false,
+            maxdop: None,
+            force_order: false,
+            use_hints: Vec::new(),
+            table_hints: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_option_clause_added_or_extended() {
+        let h = QueryHints {
+            recompile: true,
+            maxdop: Some(1),
+            ..hints()
+        };
+        assert_eq!(
+            apply_hints("SELECT * FROM t WHERE x = ';';\nPRINT 'done'", &h).unwrap(),
+            "SELECT * FROM t WHERE x = ';' OPTION (RECOMPILE, MAXDOP 1);\nPRINT 'done'"
+        );
+        assert_eq!(
+            apply_hints("SELECT * FROM t OPTION (MAXDOP 4)", &h).unwrap(),
+            "SELECT * FROM t OPTION (MAXDOP 4, RECOMPILE)"
+        );
+        assert!(apply_hints("PRINT 1", &h).is_err());
+    }
+
+    #[test]
+    fn test_index_hints_follow_alias() {
+        let h = QueryHints {
+            table_hints: vec![TableIndexHint {
+                table: "Orders".into(),
+                index: "IX_Date".into(),
+            }],
+            use_hints: vec!["force_legacy_cardinality_estimation".into()],
+            ..hints()
+        };
+        assert_eq!(
+            apply_hints(
+                "SELECT * FROM dbo.Orders AS o JOIN [dbo].[Orders] o2 WITH (NOLOCK) ON o.Id = o2.Id",
+                &h
+            )
+            .unwrap(),
+            "SELECT * FROM dbo.Orders AS o WITH (INDEX([IX_Date])) JOIN [dbo].[Orders] o2 WITH (NOLOCK, INDEX([IX_Date])) ON o.Id = o2.Id \
+             OPTION (USE HINT ('FORCE_LEGACY_CARDINALITY_ESTIMATION'))"
+        );
+        assert!(apply_hints("SELECT * FROM Customers", &h).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use super::query_hash;
+use super::types::{PlanHistoryEntry, QueryHistoryEntry, QueryHistoryGroup};
+
+/// Group history by fingerprint, most frequently run first. Entries saved
+/// before fingerprints existed are fingerprinted on the fly.
+pub fn group_history(
+    queries: &[QueryHistoryEntry],
+    plans: &[PlanHistoryEntry],
+) -> Vec<QueryHistoryGroup> {
+    let mut groups: Vec<QueryHistoryGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut group_of_query: HashMap<&str, usize> = HashMap::new();
+    let mut total_ms: Vec<u64> = Vec::new();
+
+    for query in queries {
+        let fingerprint = query
+            .fingerprint
+            .clone()
+            .unwrap_or_else(|| query_hash::fingerprint(&query.sql));
+        let i = *index.entry(fingerprint.clone()).or_insert_with(|| {
+            groups.push(QueryHistoryGroup {
+                fingerprint,
+                fingerprint_sql: query_hash::fingerprint_sql(&query.sql),
+                latest_sql: query.sql.clone(),
+                run_count: 0,
+                failure_count: 0,
+                avg_duration_ms: 0.0,
+                last_executed_at: query.executed_at,
+                plan_count: 0,
+            });
+            total_ms.push(0);
+            groups.len() - 1
+        });
+        group_of_query.insert(query.id.as_str(), i);
+
+        let group = &mut groups[i];
+        group.run_count += 1;
+        if query.success {
+            total_ms[i] += query.duration_ms;
+        } else {
+            group.failure_count += 1;
+        }
+        if query.executed_at > group.last_executed_at {
+            group.last_executed_at = query.executed_at;
+            group.latest_sql = query.sql.clone();
+        }
+    }
+
+    for plan in plans {
+        let i = plan
+            .fingerprint
+            .as_ref()
+            .and_then(|f| index.get(f))
+            .or_else(|| group_of_query.get(plan.query_id.as_str()));
+        if let Some(&i) = i {
+            groups[i].plan_count += 1;
+        }
+    }
+
+    for (group, total) in groups.iter_mut().zip(total_ms) {
+        let successes = group.run_count - group.failure_count;
+        if successes > 0 {
+            group.avg_duration_ms = total as f64 / successes as f64;
+        }
+    }
+    groups.sort_by(|a, b| {
+        b.run_count
+            .cmp(&a.run_count)
+            .then(b.last_executed_at.cmp(&a.last_executed_at))
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn query(
+        id: &str,
+        sql: &str,
+        duration_ms: u64,
+        success: bool,
+        age_s: i64,
+    ) -> QueryHistoryEntry {
+        QueryHistoryEntry {
+            id: id.to_string(),
+            sql: sql.to_string(),
+            connection_id: "c".to_string(),
+            connection_name: "c".to_string(),
+            executed_at: Utc::now() - Duration::seconds(age_s),
+            duration_ms,
+            success,
+            error: None,
+            sql_hash: None,
+            fingerprint: None,
+            pinned: false,
+            label: None,
+            scheduled_query_id: None,
+        }
+    }
+
+    #[test]
+    fn test_group_history_by_fingerprint() {
+        let queries = vec![
+            query("1", "SELECT * FROM t WHERE id = 42", 300, true, 0),
+            query("2", "SELECT 1", 5, true, 5),
+            query("3", "select * from t where id = 7", 100, true, 10),
+            query("4", "SELECT * FROM t WHERE id = 8", 0, false, 20),
+        ];
+        let groups = group_history(&queries, &[]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].fingerprint_sql, "select * from t where id = ?");
+        assert_eq!(groups[0].run_count, 3);
+        assert_eq!(groups[0].failure_count, 1);
+        assert_eq!(groups[0].avg_duration_ms, 200.0);
+        assert_eq!(groups[0].latest_sql, "SELECT * FROM t WHERE id = 42");
+    }
+}
@@ -0,0 +1,100 @@
+// Quoting helpers for building catalog and DDL statements from user input
+
+/// `[name]`, with closing brackets escaped
+pub fn quote_identifier(name: &str) -> String {
+    format!("[{}]", name.replace(']', "]]"))
+}
+
+/// Quote a possibly multi-part name ("dbo.Orders", "[Sales].[Order Lines]")
+pub fn quote_object_name(name: &str) -> Result<String, String> {
+    let parts = split_object_name(name);
+    if parts.is_empty() || parts.len() > 3 || parts.iter().any(|p| p.is_empty()) {
+        return Err(format!("Invalid object name: {}", name));
+    }
+    Ok(parts
+        .iter()
+        .map(|p| quote_identifier(p))
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+/// `N'text'`, with single quotes escaped
+pub fn quote_literal(text: &str) -> String {
+    format!("N'{}'", text.replace('\'', "''"))
+}
+
+/// Validate a hex literal such as a plan handle (`0x06000500...`) so it can be
+/// embedded as a varbinary constant
+pub fn binary_literal(hex: &str) -> Result<String, String> {
+    let digits = hex
+        .trim()
+        .strip_prefix("0x")
+        .or_else(|| hex.trim().strip_prefix("0X"))
+        .unwrap_or(hex.trim());
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err(format!("Invalid binary value: {}", hex));
+    }
+    Ok(format!("0x{}", digits.to_ascii_uppercase()))
+}
+
+/// Split on dots outside brackets/quotes and unquote each part
+fn split_object_name(name: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = name.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' | '"' => {
+                let close = if c == '[' { ']' } else { '"' };
+                while let Some(next) = chars.next() {
+                    if next == close {
+                        if chars.peek() == Some(&close) {
+                            chars.next();
+                            current.push(close);
+                            continue;
+                        }
+                        break;
+                    }
+                    current.push(next);
+                }
+            }
+            '.' => parts.push(std::mem::take(&mut current)),
+            c if c.is_whitespace() => {}
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_object_name() {
+        assert_eq!(quote_object_name("dbo.Orders").unwrap(), "[dbo].[Orders]");
+        assert_eq!(
+            quote_object_name("[Sales].[Order]]Lines]").unwrap(),
+            "[Sales].[Order]]Lines]"
+        );
+        assert_eq!(quote_object_name("Orders").unwrap(), "[Orders]");
+        assert!(quote_object_name("a..b").is_err());
+        assert!(quote_object_name("").is_err());
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quote_literal("O'Brien"), "N'O''Brien'");
+    }
+
+    #[test]
+    fn test_binary_literal() {
+        assert_eq!(binary_literal("0x06000a").unwrap(), "0x06000A");
+        assert_eq!(binary_literal("06000A").unwrap(), "0x06000A");
+        assert!(binary_literal("0x123").is_err());
+        assert!(binary_literal("0x12; DROP TABLE t").is_err());
+    }
+}
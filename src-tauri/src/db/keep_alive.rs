@@ -0,0 +1,41 @@
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use super::connection::TiberiusClient;
+
+// Firewalls and load balancers silently drop idle TCP sessions, which then
+// surface as a confusing failure on the next query. The keep-alive task pings
+// the server once the connection has been idle for a full interval; it never
+// waits on the client lock, so it stays out of the way of running queries.
+
+pub fn spawn(
+    client: Arc<Mutex<TiberiusClient>>,
+    last_activity: Arc<StdMutex<Instant>>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let idle = last_activity.lock().unwrap().elapsed();
+            if idle < interval {
+                tokio::time::sleep(interval - idle).await;
+                continue;
+            }
+
+            // A query holds the lock: the connection is busy, not idle
+            let Ok(mut client) = client.try_lock() else {
+                tokio::time::sleep(interval).await;
+                continue;
+            };
+
+            let ping = async { client.simple_query("SELECT 1").await?.into_row().await };
+            if let Err(e) = ping.await {
+                tracing::warn!(error = %e, "Keep-alive ping failed");
+            }
+            drop(client);
+            *last_activity.lock().unwrap() = Instant::now();
+        }
+    })
+}
@@ -0,0 +1,188 @@
+use tiberius::Config;
+
+// Integrated authentication for non-Windows machines joined to (or with
+// tickets for) an Active Directory domain. On Windows tiberius negotiates
+// through SSPI; on Linux and macOS it asks GSSAPI for a Kerberos ticket to
+// MSSQLSvc/host:port, which needs the `kerberos` feature and the system
+// GSSAPI library. The ticket comes from the user's credential cache (kinit).
+//
+// Tiberius names the service principal after the configured host and port
+// and leaves the realm to krb5.conf, so an SPN override replaces the host
+// and port it logs in with (the TCP connection still goes to the real
+// server), and a configured realm is checked against the realm krb5.conf
+// maps the SPN host to.
+
+const DEFAULT_KRB5_CONFIG: &str = "/etc/krb5.conf";
+
+/// Set up integrated authentication, with the SPN taken from `spn` when
+/// the server's is not MSSQLSvc/host:port
+pub fn configure(
+    config: &mut Config,
+    host: &str,
+    port: u16,
+    spn: Option<&str>,
+    realm: Option<&str>,
+) -> Result<(), String> {
+    let (spn_host, spn_port, spn_realm) = match spn.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(spn) => parse_spn(spn, port)?,
+        None => (host.to_string(), port, None),
+    };
+    let realm = realm
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .or(spn_realm.as_deref());
+    if let Some(realm) = realm {
+        check_realm(&spn_host, realm)?;
+    }
+    integrated(config, spn_host, spn_port)
+}
+
+#[cfg(any(windows, all(unix, feature = "kerberos")))]
+fn integrated(config: &mut Config, spn_host: String, spn_port: u16) -> Result<(), String> {
+    config.host(spn_host);
+    config.port(spn_port);
+    config.authentication(tiberius::AuthMethod::Integrated);
+    Ok(())
+}
+
+#[cfg(not(any(windows, all(unix, feature = "kerberos"))))]
+fn integrated(_config: &mut Config, _spn_host: String, _spn_port: u16) -> Result<(), String> {
+    Err(
+        "Kerberos authentication is not available in this build; it needs the \
+         `kerberos` feature and the system GSSAPI library"
+            .to_string(),
+    )
+}
+
+/// Host, port and realm of an SPN like `MSSQLSvc/db01.corp.example.com:1433@CORP.EXAMPLE.COM`;
+/// the service class and the port may be left out
+fn parse_spn(spn: &str, default_port: u16) -> Result<(String, u16, Option<String>), String> {
+    let (name, realm) = match spn.rsplit_once('@') {
+        Some((name, realm)) => (name, Some(realm.to_string())),
+        None => (spn, None),
+    };
+    let name = match name.split_once('/') {
+        Some((class, name)) if class.eq_ignore_ascii_case("MSSQLSvc") => name,
+        Some((class, _)) => {
+            return Err(format!(
+                "Invalid SPN '{}': SQL Server uses the MSSQLSvc service class, not {}",
+                spn, class
+            ))
+        }
+        None => name,
+    };
+    let (host, port) = match name.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| format!("Invalid SPN '{}': the port must be a number", spn))?;
+            (host, port)
+        }
+        None => (name, default_port),
+    };
+    if host.is_empty() {
+        return Err(format!("Invalid SPN '{}': no host", spn));
+    }
+    Ok((host.to_string(), port, realm))
+}
+
+/// Fail early when krb5.conf would look the SPN up in another realm than
+/// the configured one; without a readable krb5.conf GSSAPI decides
+fn check_realm(spn_host: &str, realm: &str) -> Result<(), String> {
+    let path = std::env::var("KRB5_CONFIG")
+        .ok()
+        .and_then(|paths| paths.split(':').next().map(str::to_string))
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| DEFAULT_KRB5_CONFIG.to_string());
+    let Ok(conf) = std::fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    match realm_for_host(&conf, spn_host) {
+        Some(mapped) if !mapped.eq_ignore_ascii_case(realm) => Err(format!(
+            "Kerberos would look up the SQL Server principal for {} in realm {}, not {}; \
+             map the host to {} under [domain_realm] in {}",
+            spn_host, mapped, realm, realm, path
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Realm krb5.conf assigns to a host: an exact [domain_realm] entry, else
+/// the longest matching `.domain` entry, else [libdefaults] default_realm
+fn realm_for_host(conf: &str, host: &str) -> Option<String> {
+    let host = host.to_ascii_lowercase();
+    let mut section = String::new();
+    let mut default_realm = None;
+    let mut exact = None;
+    let mut domain: Option<(usize, String)> = None;
+
+    for line in conf.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_ascii_lowercase();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim().to_string());
+        match section.as_str() {
+            "libdefaults" if key == "default_realm" => default_realm = Some(value),
+            "domain_realm" if key == host => exact = Some(value),
+            "domain_realm"
+                if key.starts_with('.')
+                    && host.ends_with(&key)
+                    && domain.as_ref().is_none_or(|(len, _)| key.len() > *len) =>
+            {
+                domain = Some((key.len(), value))
+            }
+            _ => {}
+        }
+    }
+    exact.or(domain.map(|(_, realm)| realm)).or(default_realm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spn_and_realm_resolution() {
+        assert_eq!(
+            parse_spn(
+                "MSSQLSvc/db01.corp.example.com:14330@CORP.EXAMPLE.COM",
+                1433
+            )
+            .unwrap(),
+            (
+                "db01.corp.example.com".to_string(),
+                14330,
+                Some("CORP.EXAMPLE.COM".to_string())
+            )
+        );
+        assert_eq!(
+            parse_spn("db01.corp.example.com", 1433).unwrap(),
+            ("db01.corp.example.com".to_string(), 1433, None)
+        );
+        assert!(parse_spn("HTTP/db01:1433", 1433).is_err());
+
+        let conf = "[libdefaults]\n  default_realm = CORP.EXAMPLE.COM\n\n\
+                    [domain_realm]\n  .example.com = CORP.EXAMPLE.COM\n  \
+                    .emea.example.com = EMEA.EXAMPLE.COM\n  legacy.example.com = OLD.EXAMPLE.COM\n";
+        assert_eq!(
+            realm_for_host(conf, "DB01.emea.example.com").as_deref(),
+            Some("EMEA.EXAMPLE.COM")
+        );
+        assert_eq!(
+            realm_for_host(conf, "legacy.example.com").as_deref(),
+            Some("OLD.EXAMPLE.COM")
+        );
+        assert_eq!(
+            realm_for_host(conf, "db02.other.net").as_deref(),
+            Some("CORP.EXAMPLE.COM")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use super::connection::TiberiusClient;
+use super::identifiers::binary_literal;
+use super::rows::{get_f64, get_string};
+use super::types::{MemoryGrantReport, StatementMemoryGrant};
+use crate::plan::types::ParsedPlan;
+
+/// Grants below this are too small to be worth tuning
+const MIN_REPORTED_GRANT_KB: f64 = 1024.0;
+
+/// Share of a grant that must go unused before it counts as excessive
+const EXCESSIVE_UNUSED_PERCENT: f64 = 50.0;
+
+/// Last grant the plan cache recorded for a plan, from sys.dm_exec_query_stats
+/// (SQL Server 2016 SP2 / 2017 and later)
+#[derive(Debug, Clone, Default)]
+pub struct CachedGrant {
+    pub granted_kb: Option<f64>,
+    pub used_kb: Option<f64>,
+    pub ideal_kb: Option<f64>,
+}
+
+/// One entry per statement that asked for memory, from MemoryGrantInfo in the
+/// plan. Estimated plans have no used figure; `cached` fills it in from the
+/// last execution of the same plan when available.
+pub fn report(plan: &ParsedPlan, cached: &HashMap<String, CachedGrant>) -> MemoryGrantReport {
+    let statements = plan
+        .statements
+        .iter()
+        .filter_map(|stmt| {
+            let grant = stmt.memory_grant.as_ref()?;
+            let last = stmt
+                .query_plan_hash
+                .as_ref()
+                .and_then(|hash| binary_literal(hash).ok())
+                .and_then(|hash| cached.get(&hash));
+            let mut entry = StatementMemoryGrant {
+                statement_id: stmt.statement_id,
+                statement_text: stmt.statement_text.clone(),
+                query_plan_hash: stmt.query_plan_hash.clone(),
+                required_kb: grant.serial_required_kb,
+                desired_kb: grant.serial_desired_kb,
+                requested_kb: grant.requested_kb,
+                granted_kb: grant.granted_kb.or(last.and_then(|l| l.granted_kb)),
+                used_kb: grant.max_used_kb.or(last.and_then(|l| l.used_kb)),
+                ideal_kb: last.and_then(|l| l.ideal_kb),
+                grant_wait_ms: grant.grant_wait_ms,
+                unused_percent: None,
+                from_query_stats: grant.max_used_kb.is_none() && last.is_some(),
+                warnings: Vec::new(),
+            };
+            assess(&mut entry);
+            Some(entry)
+        })
+        .collect();
+    MemoryGrantReport { statements }
+}
+
+fn assess(entry: &mut StatementMemoryGrant) {
+    if let (Some(granted), Some(used)) = (entry.granted_kb, entry.used_kb) {
+        if granted > 0.0 {
+            let unused = (granted - used).max(0.0) / granted * 100.0;
+            entry.unused_percent = Some(unused);
+            if granted >= MIN_REPORTED_GRANT_KB && unused >= EXCESSIVE_UNUSED_PERCENT {
+                entry.warnings.push(format!(
+                    "Excessive grant: {:.0} KB granted but only {:.0} KB used ({:.0}% unused)",
+                    granted, used, unused
+                ));
+            }
+            if used >= granted {
+                entry.warnings.push(format!(
+                    "The whole grant of {:.0} KB was used; operators may have spilled to tempdb",
+                    granted
+                ));
+            }
+        }
+    }
+    if let (Some(requested), Some(granted)) = (entry.requested_kb, entry.granted_kb) {
+        if granted < requested {
+            entry.warnings.push(format!(
+                "Granted {:.0} KB of the {:.0} KB requested",
+                granted, requested
+            ));
+        }
+    }
+    if let Some(wait) = entry.grant_wait_ms.filter(|w| *w > 0.0) {
+        entry
+            .warnings
+            .push(format!("Waited {:.0} ms for the memory grant", wait));
+    }
+}
+
+/// Last grant of each cached plan with one of `plan_hashes`, keyed by the
+/// hash as a binary literal. Servers without the grant columns yield nothing.
+pub async fn cached_grants(
+    client: &mut TiberiusClient,
+    plan_hashes: &[String],
+) -> Result<HashMap<String, CachedGrant>, String> {
+    let literals = plan_hashes
+        .iter()
+        .map(|h| binary_literal(h))
+        .collect::<Result<Vec<_>, _>>()?;
+    if literals.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let sql = format!(
+        "SELECT CONVERT(varchar(20), query_plan_hash, 1), \
+                CAST(last_grant_kb AS float), CAST(last_used_grant_kb AS float), \
+                CAST(last_ideal_grant_kb AS float) \
+         FROM sys.dm_exec_query_stats \
+         WHERE query_plan_hash IN ({}) \
+         ORDER BY last_execution_time",
+        literals.join(", ")
+    );
+    let rows = match client.simple_query(sql).await {
+        Ok(stream) => stream.into_first_result().await.unwrap_or_default(),
+        Err(_) => return Ok(HashMap::new()),
+    };
+
+    // Ordered oldest first, so the most recent execution wins
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let hash = binary_literal(&get_string(row, 0)?).ok()?;
+            Some((
+                hash,
+                CachedGrant {
+                    granted_kb: get_f64(row, 1),
+                    used_kb: get_f64(row, 2),
+                    ideal_kb: get_f64(row, 3),
+                },
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    fn plan(grant: &str) -> ParsedPlan {
+        parse_plan(&format!(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q" QueryPlanHash="0xABCD">
+                <QueryPlan><MemoryGrantInfo {grant} /></QueryPlan>
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_excessive_grant_from_actual_plan() {
+        let report = report(
+            &plan(r#"RequestedMemory="102400" GrantedMemory="102400" MaxUsedMemory="2048""#),
+            &HashMap::new(),
+        );
+        let stmt = &report.statements[0];
+        assert_eq!(stmt.unused_percent, Some(98.0));
+        assert!(!stmt.from_query_stats);
+        assert_eq!(stmt.warnings.len(), 1);
+        assert!(stmt.warnings[0].starts_with("Excessive grant"));
+    }
+
+    #[test]
+    fn test_estimated_plan_uses_cached_grant() {
+        let cached = HashMap::from([(
+            "0xABCD".to_string(),
+            CachedGrant {
+                granted_kb: Some(4096.0),
+                used_kb: Some(4096.0),
+                ideal_kb: Some(8192.0),
+            },
+        )]);
+        let report = report(&plan(r#"RequestedMemory="4096""#), &cached);
+        let stmt = &report.statements[0];
+        assert!(stmt.from_query_stats);
+        assert_eq!(stmt.ideal_kb, Some(8192.0));
+        assert_eq!(stmt.unused_percent, Some(0.0));
+        assert!(stmt.warnings[0].contains("may have spilled"));
+    }
+}
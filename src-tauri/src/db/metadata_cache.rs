@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use super::completion::{self, CompletionCacheEntry};
+use super::connection::TiberiusClient;
+use super::tsql_lexer::{code_tokens, TokenKind};
+use super::type_casts::DescribedColumn;
+use super::types::CompletionMetadata;
+
+// Per-connection cache of what the app reads from sys.* views: completion
+// metadata and result set descriptions used by the cast retry. Entries are
+// keyed by database and go stale after the TTL or when the connection runs
+// a batch that changes the schema.
+
+/// Until the metadataCacheMinutes setting is applied
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Result set descriptions kept per database before the oldest are dropped
+const MAX_RESULT_SETS: usize = 200;
+
+/// Statement keywords that change what sys.objects and sys.columns return
+const DDL_KEYWORDS: &[&str] = &["CREATE", "ALTER", "DROP"];
+
+/// Procedures that rename or otherwise change objects
+const DDL_PROCEDURES: &[&str] = &["sp_rename", "sp_addtype", "sp_droptype"];
+
+struct Cached<T> {
+    value: T,
+    loaded_at: Instant,
+    stale: bool,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            loaded_at: Instant::now(),
+            stale: false,
+        }
+    }
+
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        !self.stale && self.loaded_at.elapsed() < ttl
+    }
+}
+
+#[derive(Default)]
+struct CachedDatabase {
+    completion: Option<Cached<CompletionCacheEntry>>,
+    /// Keyed by statement text
+    result_sets: HashMap<String, Cached<Vec<DescribedColumn>>>,
+}
+
+/// How `MetadataCache::completion` may use what is cached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionLoad {
+    /// Cached metadata while fresh; a stale entry is brought up to date
+    IfStale,
+    /// Re-read objects changed since the cached load
+    Incremental,
+    /// Re-read everything
+    Full,
+}
+
+pub struct MetadataCache {
+    ttl_secs: AtomicU64,
+    databases: Mutex<HashMap<String, CachedDatabase>>,
+}
+
+impl MetadataCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl_secs: AtomicU64::new(ttl.as_secs()),
+            databases: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.ttl_secs.store(ttl.as_secs(), Ordering::Relaxed);
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs.load(Ordering::Relaxed))
+    }
+
+    /// Completion metadata of the session's current database
+    pub async fn completion(
+        &self,
+        client: &mut TiberiusClient,
+        load: CompletionLoad,
+    ) -> Result<CompletionMetadata, String> {
+        let database = completion::current_database(client).await?;
+        let ttl = self.ttl();
+        let mut databases = self.databases.lock().await;
+        let cached = databases.entry(database).or_default();
+
+        if let Some(entry) = &cached.completion {
+            if load == CompletionLoad::IfStale && entry.is_fresh(ttl) {
+                return Ok(entry.value.metadata.clone());
+            }
+        }
+        let previous = match load {
+            CompletionLoad::Full => None,
+            _ => cached.completion.as_ref().map(|c| &c.value),
+        };
+        let entry = completion::load(client, previous).await?;
+        let metadata = entry.metadata.clone();
+        cached.completion = Some(Cached::new(entry));
+        Ok(metadata)
+    }
+
+    /// Description of a statement's result set, while fresh
+    pub async fn result_set(&self, database: &str, sql: &str) -> Option<Vec<DescribedColumn>> {
+        let ttl = self.ttl();
+        let databases = self.databases.lock().await;
+        databases
+            .get(database)?
+            .result_sets
+            .get(sql)
+            .filter(|c| c.is_fresh(ttl))
+            .map(|c| c.value.clone())
+    }
+
+    pub async fn store_result_set(&self, database: &str, sql: &str, columns: Vec<DescribedColumn>) {
+        let mut databases = self.databases.lock().await;
+        let result_sets = &mut databases
+            .entry(database.to_string())
+            .or_default()
+            .result_sets;
+        if result_sets.len() >= MAX_RESULT_SETS {
+            if let Some(oldest) = result_sets
+                .iter()
+                .min_by_key(|(_, c)| c.loaded_at)
+                .map(|(sql, _)| sql.clone())
+            {
+                result_sets.remove(&oldest);
+            }
+        }
+        result_sets.insert(sql.to_string(), Cached::new(columns));
+    }
+
+    /// Mark everything stale after a schema change. The database a DDL
+    /// statement touched is not worked out, since three-part names and USE
+    /// inside the batch can point anywhere.
+    pub async fn invalidate_all(&self) {
+        let mut databases = self.databases.lock().await;
+        for cached in databases.values_mut() {
+            if let Some(entry) = &mut cached.completion {
+                entry.stale = true;
+            }
+            cached.result_sets.clear();
+        }
+    }
+
+    /// Forget one database, or every database when None
+    pub async fn clear(&self, database: Option<&str>) {
+        let mut databases = self.databases.lock().await;
+        match database {
+            Some(database) => {
+                databases.remove(database);
+            }
+            None => databases.clear(),
+        }
+    }
+}
+
+/// The batch may create, alter, drop or rename objects. DDL on temp tables
+/// and table variables is ignored.
+pub fn changes_schema(sql: &str) -> bool {
+    let tokens = code_tokens(sql);
+    let words: Vec<&str> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, token)| {
+            // Qualified names (dbo.Create) are not keywords
+            token.kind == TokenKind::Word && (*i == 0 || tokens[i - 1].kind != TokenKind::Dot)
+        })
+        .map(|(_, token)| token.text(sql))
+        .collect();
+
+    words.iter().enumerate().any(|(i, word)| {
+        if DDL_PROCEDURES.iter().any(|p| p.eq_ignore_ascii_case(word)) {
+            return true;
+        }
+        if !DDL_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+            return false;
+        }
+        // CREATE TABLE #t, DROP TABLE IF EXISTS #t
+        let target = words[i + 1..].iter().find(|w| {
+            !["TABLE", "IF", "EXISTS"]
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(w))
+        });
+        !target.is_some_and(|t| t.starts_with('#') || t.starts_with('@'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_changes_are_detected() {
+        assert!(changes_schema("CREATE TABLE dbo.Orders (Id int)"));
+        assert!(changes_schema("SELECT 1;\nALTER VIEW v AS SELECT 2"));
+        assert!(changes_schema("drop table if exists dbo.Old"));
+        assert!(changes_schema("EXEC sp_rename 'dbo.A', 'B'"));
+
+        assert!(!changes_schema("SELECT * FROM Orders"));
+        assert!(!changes_schema("SELECT 'DROP TABLE x' -- CREATE VIEW"));
+        assert!(!changes_schema("CREATE TABLE #work (Id int)"));
+        assert!(!changes_schema("DROP TABLE IF EXISTS #work"));
+        assert!(!changes_schema("SELECT s.[Create] FROM s"));
+    }
+}
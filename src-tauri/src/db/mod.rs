@@ -3,3 +3,65 @@ pub mod encryption;
 pub mod connection;
 pub mod commands;
 pub mod store;
+pub mod rows;
+pub mod completion;
+pub mod wait_stats;
+pub mod safe_mode;
+pub mod notify;
+pub mod splitter;
+pub mod server_messages;
+pub mod query_hash;
+pub mod identifiers;
+pub mod what_if;
+pub mod keep_alive;
+pub mod validate;
+pub mod cells;
+pub mod exec_context;
+pub mod query_stats;
+pub mod statistics;
+pub mod hints;
+pub mod permissions;
+pub mod snippets;
+pub mod timing;
+pub mod compression;
+pub mod backup;
+pub mod memory_grants;
+pub mod parameters;
+pub mod history_groups;
+pub mod configuration;
+pub mod row_estimate;
+pub mod formatter;
+pub mod tsql_lexer;
+pub mod type_casts;
+pub mod blocking;
+pub mod plan_writer;
+pub mod columns;
+pub mod transport;
+pub mod query_queue;
+pub mod monitor;
+pub mod progress;
+pub mod retry;
+pub mod compat_levels;
+pub mod metadata_cache;
+pub mod result_grid;
+pub mod copy_formats;
+pub mod table_browser;
+pub mod dependencies;
+pub mod set_options;
+pub mod audit_log;
+pub mod query_store;
+pub mod plan_bundle;
+pub mod redact;
+pub mod scheduler;
+pub mod baseline;
+pub mod folder_analysis;
+pub mod fragmentation;
+pub mod sizes;
+pub mod connection_import;
+pub mod cert_pinning;
+pub mod kerberos;
+pub mod multi_database;
+pub mod arrow_export;
+pub mod result_diff;
+pub mod errors;
+pub mod tls;
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use tokio::sync::Mutex;
+
+use super::connection::{open_client, ConnectionTarget, TiberiusClient};
+use super::types::AppError;
+
+/// Application name of the monitoring session, so activity views can tell
+/// it apart from the user's own
+pub const MONITOR_APP_NAME: &str = "SqlPlanForDummies monitor";
+
+/// A second session as the same login for work that must not wait behind
+/// the user's query on the main client: DMV polling, progress and KILL.
+/// Opened on first use and kept for the life of the connection.
+pub struct MonitorConnection {
+    target: ConnectionTarget,
+    client: Mutex<Option<Arc<Mutex<TiberiusClient>>>>,
+}
+
+impl MonitorConnection {
+    pub fn new(target: ConnectionTarget) -> Self {
+        Self {
+            target,
+            client: Mutex::new(None),
+        }
+    }
+
+    async fn client(&self) -> Result<Arc<Mutex<TiberiusClient>>, AppError> {
+        let mut slot = self.client.lock().await;
+        if let Some(client) = slot.as_ref() {
+            return Ok(client.clone());
+        }
+        let client = open_client(&self.target, Some(MONITOR_APP_NAME))
+            .await
+            .map_err(|(_, e)| e.context("Failed to open the monitoring connection"))?;
+        tracing::debug!(host = %self.target.host, "Monitoring connection opened");
+        let client = Arc::new(Mutex::new(client));
+        *slot = Some(client.clone());
+        Ok(client)
+    }
+
+    /// Run `f` on the monitoring session. A failed call drops the session so
+    /// the next one reconnects instead of reusing a broken stream.
+    pub async fn run<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        E: From<AppError>,
+        F: for<'c> FnOnce(&'c mut TiberiusClient) -> BoxFuture<'c, Result<T, E>>,
+    {
+        let client = self.client().await?;
+        let result = {
+            let mut client = client.lock().await;
+            f(&mut client).await
+        };
+        if result.is_err() {
+            let mut slot = self.client.lock().await;
+            if slot.as_ref().is_some_and(|c| Arc::ptr_eq(c, &client)) {
+                *slot = None;
+            }
+        }
+        result
+    }
+}
+
+/// End a session on the server; whatever it was running is rolled back
+pub async fn kill_session(client: &mut TiberiusClient, session_id: i64) -> Result<(), String> {
+    client
+        .simple_query(format!("KILL {}", session_id))
+        .await
+        .map_err(|e| format!("Failed to kill session {}: {}", session_id, e))?
+        .into_results()
+        .await
+        .map_err(|e| format!("Failed to kill session {}: {}", session_id, e))?;
+    Ok(())
+}
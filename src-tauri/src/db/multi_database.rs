@@ -0,0 +1,347 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use futures_util::future::{join_all, try_join_all};
+use tauri::{AppHandle, Manager};
+
+use super::audit_log::AuditEvent;
+use super::cert_pinning;
+use super::commands::{audit, password_key};
+use super::connection::{AppState, ConnectionTarget, DbConnection};
+use super::encryption;
+use super::store;
+use super::transport;
+use super::types::{
+    AppError, AuditAction, ConnectionConfig, MultiDatabaseRequest, MultiDatabaseResult,
+    QueryResult, ServerGroupRequest, ServerGroupResult,
+};
+use crate::settings;
+use crate::settings::profile::Capability;
+
+// "Run this in every tenant database": one statement against a list of
+// databases on the current server. Each worker is a session of its own, so
+// the user's session keeps its database and stays free for other queries;
+// a worker switches database before each run, and one database failing does
+// not stop the others.
+//
+// The same fan-out runs across servers: a connection group's saved
+// connections, each logged in to on a connection of its own for the run and
+// closed after it, as the scheduler does.
+
+/// Most sessions opened at once, to keep a fan-out from loading the server
+pub const MAX_CONCURRENCY: usize = 8;
+
+/// Databases in request order, without blanks and repeats
+fn distinct_databases(databases: &[String]) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::with_capacity(databases.len());
+    for database in databases.iter().map(|d| d.trim()) {
+        if !database.is_empty() && !distinct.iter().any(|d| d.eq_ignore_ascii_case(database)) {
+            distinct.push(database.to_string());
+        }
+    }
+    distinct
+}
+
+/// Sessions to open: the requested concurrency (sequential by default)
+/// within 1..=MAX_CONCURRENCY, and no more than there are databases
+fn worker_count(requested: Option<usize>, databases: usize) -> usize {
+    requested
+        .unwrap_or(1)
+        .clamp(1, MAX_CONCURRENCY)
+        .min(databases.max(1))
+}
+
+/// Run the statement in each database; `on_finished` gets the session that
+/// ran it, its result, and how many of how many databases are done. Results
+/// come back in the order of the request.
+pub async fn run(
+    conn: &DbConnection,
+    request: &MultiDatabaseRequest,
+    on_finished: impl Fn(&DbConnection, &MultiDatabaseResult, usize, usize),
+) -> Result<Vec<MultiDatabaseResult>, String> {
+    let databases = distinct_databases(&request.databases);
+    if databases.is_empty() {
+        return Err("No databases selected".to_string());
+    }
+    if request.sql.trim().is_empty() {
+        return Err("No statement to run".to_string());
+    }
+
+    let workers = worker_count(request.concurrency, databases.len());
+    let sessions = try_join_all((0..workers).map(|_| conn.open_sibling())).await?;
+    tracing::info!(
+        databases = databases.len(),
+        workers,
+        "Running statement in several databases"
+    );
+
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<MultiDatabaseResult>>> = Mutex::new(vec![None; databases.len()]);
+
+    join_all(sessions.iter().map(|session| async {
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(database) = databases.get(index) else {
+                break;
+            };
+            let result = run_in(session, database, request).await;
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            on_finished(session, &result, done, databases.len());
+            results.lock().unwrap()[index] = Some(result);
+        }
+    }))
+    .await;
+
+    Ok(results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+async fn run_in(
+    session: &DbConnection,
+    database: &str,
+    request: &MultiDatabaseRequest,
+) -> MultiDatabaseResult {
+    let started = Instant::now();
+    let outcome = match session.use_database(database).await {
+        Ok(_) => {
+            session
+                .execute_query(&request.sql, &request.plan_type)
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(e.into())),
+    };
+    MultiDatabaseResult {
+        database: database.to_string(),
+        result,
+        error,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Saved connections to run on, in saved order: the group's members, then
+/// listed connections that are not among them
+fn group_members(
+    connections: &[ConnectionConfig],
+    request: &ServerGroupRequest,
+) -> Result<Vec<ConnectionConfig>, String> {
+    let mut members: Vec<ConnectionConfig> = match &request.group_id {
+        Some(group_id) => connections
+            .iter()
+            .filter(|c| c.group_id.as_deref() == Some(group_id.as_str()))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    };
+    for id in &request.connection_ids {
+        if members.iter().any(|c| &c.id == id) {
+            continue;
+        }
+        let connection = connections
+            .iter()
+            .find(|c| &c.id == id)
+            .ok_or("Connection not found")?;
+        members.push(connection.clone());
+    }
+    Ok(members)
+}
+
+/// Run the statement on each saved connection of the request; `on_finished`
+/// gets each server's result and how many of how many servers are done.
+/// Results come back in the order of the connections.
+pub async fn run_on_servers(
+    app: &AppHandle,
+    request: &ServerGroupRequest,
+    on_finished: impl Fn(&ServerGroupResult, usize, usize),
+) -> Result<Vec<ServerGroupResult>, String> {
+    if request.sql.trim().is_empty() {
+        return Err("No statement to run".to_string());
+    }
+    let connections = group_members(&store::get_connections(app)?, request)?;
+    if connections.is_empty() {
+        return Err("No connections selected".to_string());
+    }
+    let key = password_key(app, &app.state::<AppState>()).await?;
+    let profile_read_only = !settings::load(app)?.profile.allows(Capability::ModifyData);
+
+    let workers = worker_count(request.concurrency, connections.len());
+    tracing::info!(
+        servers = connections.len(),
+        workers,
+        "Running statement on a server group"
+    );
+
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<ServerGroupResult>>> = Mutex::new(vec![None; connections.len()]);
+
+    join_all((0..workers).map(|_| async {
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(connection) = connections.get(index) else {
+                break;
+            };
+            let result = run_on(app, connection, &key, profile_read_only, request).await;
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            on_finished(&result, done, connections.len());
+            results.lock().unwrap()[index] = Some(result);
+        }
+    }))
+    .await;
+
+    Ok(results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+async fn run_on(
+    app: &AppHandle,
+    connection: &ConnectionConfig,
+    key: &[u8; 32],
+    profile_read_only: bool,
+    request: &ServerGroupRequest,
+) -> ServerGroupResult {
+    let started = Instant::now();
+    let outcome = connect_and_run(app, connection, key, profile_read_only, request).await;
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(e.into())),
+    };
+    ServerGroupResult {
+        connection_id: connection.id.clone(),
+        connection_name: connection.name.clone(),
+        server: transport::endpoint(&connection.host, connection.port, &connection.transport),
+        database: connection.database.clone(),
+        result,
+        error,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Log in with the saved connection's settings and run the statement; the
+/// connection is closed when it is dropped
+async fn connect_and_run(
+    app: &AppHandle,
+    connection: &ConnectionConfig,
+    key: &[u8; 32],
+    profile_read_only: bool,
+    request: &ServerGroupRequest,
+) -> Result<QueryResult, AppError> {
+    let conn = open_saved(app, connection, key, profile_read_only).await?;
+
+    let started = Instant::now();
+    let result = conn.execute_query(&request.sql, &request.plan_type).await;
+    audit(
+        app,
+        &conn,
+        AuditEvent {
+            action: AuditAction::Execute,
+            statement: Some(&request.sql),
+            error: result.as_ref().err().map(AppError::message),
+            duration_ms: Some(started.elapsed().as_millis() as u64),
+        },
+    );
+    result
+}
+
+/// A session of its own on a saved connection, with its safe mode, for
+/// work run beside the user's session
+pub async fn open_saved(
+    app: &AppHandle,
+    connection: &ConnectionConfig,
+    key: &[u8; 32],
+    profile_read_only: bool,
+) -> Result<DbConnection, AppError> {
+    let password = encryption::decrypt_password_with(key, &connection.encrypted_password)?;
+    let target = ConnectionTarget::from_saved(connection, password);
+    let mut conn = DbConnection::connect(target).await?;
+    cert_pinning::pin_first_use(app, connection, conn.server_certificate())?;
+    conn.read_only = connection.read_only;
+    conn.saved_connection_id = Some(connection.id.clone());
+    conn.profile_read_only
+        .store(profile_read_only, Ordering::Relaxed);
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::PlanType;
+
+    #[test]
+    fn test_databases_and_workers() {
+        let requested = ["Tenant1", " tenant1 ", "", "Tenant2", "Tenant3"].map(String::from);
+        assert_eq!(
+            distinct_databases(&requested),
+            vec!["Tenant1", "Tenant2", "Tenant3"]
+        );
+
+        assert_eq!(worker_count(None, 3), 1);
+        assert_eq!(worker_count(Some(0), 3), 1);
+        assert_eq!(worker_count(Some(4), 3), 3);
+        assert_eq!(worker_count(Some(50), 100), MAX_CONCURRENCY);
+    }
+
+    fn saved(id: &str, group_id: Option<&str>) -> ConnectionConfig {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "host": "localhost",
+            "port": 1433,
+            "database": "master",
+            "username": "sa",
+            "encryptedPassword": "",
+            "lastUsed": null,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "groupId": group_id,
+        }))
+        .unwrap()
+    }
+
+    fn request(group_id: Option<&str>, connection_ids: &[&str]) -> ServerGroupRequest {
+        ServerGroupRequest {
+            sql: "SELECT 1".to_string(),
+            group_id: group_id.map(String::from),
+            connection_ids: connection_ids.iter().map(|id| id.to_string()).collect(),
+            concurrency: None,
+            plan_type: PlanType::None,
+        }
+    }
+
+    #[test]
+    fn test_group_members() {
+        let connections = [
+            saved("prod-eu", Some("prod")),
+            saved("dev", None),
+            saved("prod-us", Some("prod")),
+        ];
+        let ids = |members: Vec<ConnectionConfig>| -> Vec<String> {
+            members.into_iter().map(|c| c.id).collect()
+        };
+
+        assert_eq!(
+            ids(group_members(&connections, &request(Some("prod"), &[])).unwrap()),
+            vec!["prod-eu", "prod-us"]
+        );
+        assert_eq!(
+            ids(group_members(&connections, &request(Some("prod"), &["dev", "prod-us"])).unwrap()),
+            vec!["prod-eu", "prod-us", "dev"]
+        );
+        assert!(group_members(&connections, &request(None, &["gone"])).is_err());
+        assert!(group_members(&connections, &request(Some("empty"), &[]))
+            .unwrap()
+            .is_empty());
+    }
+}
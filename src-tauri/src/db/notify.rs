@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use super::types::{AppError, QueryResult};
+use crate::settings;
+
+/// Fire a desktop notification when a query ran past the configured threshold,
+/// so users can switch away from the window during long executions.
+pub fn query_finished(app: &AppHandle, elapsed: Duration, result: &Result<QueryResult, AppError>) {
+    let Ok(settings) = settings::load(app) else {
+        return;
+    };
+    if !settings.notify_long_queries
+        || (elapsed.as_millis() as u64) < settings.long_query_threshold_ms
+    {
+        return;
+    }
+
+    let duration = format_duration(elapsed);
+    let (title, body) = match result {
+        Ok(result) if result.result_sets.is_empty() => (
+            "Query finished",
+            format!(
+                "Completed in {}, {} row(s) affected",
+                duration, result.rows_affected
+            ),
+        ),
+        Ok(result) => (
+            "Query finished",
+            format!(
+                "Completed in {}, {} row(s) returned",
+                duration,
+                result
+                    .result_sets
+                    .iter()
+                    .map(|r| r.rows_affected)
+                    .sum::<i64>()
+            ),
+        ),
+        Err(e) => ("Query failed", format!("Failed after {}: {}", duration, e)),
+    };
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!(error = %e, "Failed to show query notification");
+    }
+}
+
+fn format_duration(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    }
+}
@@ -0,0 +1,141 @@
+use super::hints;
+use super::identifiers::quote_literal;
+use super::types::{QueryHints, QueryParameter};
+
+/// Which of the two values of each parameter to bind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterSet {
+    First,
+    Second,
+}
+
+/// `EXEC sp_executesql` of `sql` with OPTION (RECOMPILE) on every statement,
+/// so each parameter set gets a plan compiled for its own values rather than
+/// the one cached for whichever set ran first.
+pub fn recompiled_batch(
+    sql: &str,
+    parameters: &[QueryParameter],
+    set: ParameterSet,
+) -> Result<String, String> {
+    let hinted = hints::apply_hints(
+        sql,
+        &QueryHints {
+            recompile: true,
+            maxdop: None,
+            force_order: false,
+            use_hints: Vec::new(),
+            table_hints: Vec::new(),
+        },
+    )?;
+
+    let mut declarations = Vec::new();
+    let mut bindings = Vec::new();
+    for param in parameters {
+        let name = parameter_name(&param.name)?;
+        let sql_type = parameter_type(&param.sql_type)?;
+        declarations.push(format!("{} {}", name, sql_type));
+        let value = match set {
+            ParameterSet::First => &param.first_value,
+            ParameterSet::Second => &param.second_value,
+        };
+        bindings.push(format!(
+            "{} = {}",
+            name,
+            value
+                .as_deref()
+                .map(quote_literal)
+                .unwrap_or_else(|| "NULL".to_string())
+        ));
+    }
+
+    let mut batch = format!("EXEC sp_executesql {}", quote_literal(&hinted));
+    if !declarations.is_empty() {
+        batch.push_str(&format!(
+            ", {}, {}",
+            quote_literal(&declarations.join(", ")),
+            bindings.join(", ")
+        ));
+    }
+    Ok(batch)
+}
+
+/// `@name`, with the @ added when missing
+fn parameter_name(name: &str) -> Result<String, String> {
+    let bare = name.trim().trim_start_matches('@');
+    if bare.is_empty()
+        || bare.starts_with(|c: char| c.is_ascii_digit())
+        || !bare
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '#' || c == '$')
+    {
+        return Err(format!("Invalid parameter name: {}", name));
+    }
+    Ok(format!("@{}", bare))
+}
+
+/// Type names go into the declaration string unquoted; allow only what a
+/// type can contain, e.g. `nvarchar(50)`, `decimal(18, 2)`, `varchar(max)`
+fn parameter_type(sql_type: &str) -> Result<&str, String> {
+    let sql_type = sql_type.trim();
+    if sql_type.is_empty()
+        || !sql_type
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | ')' | ',' | ' ' | '.'))
+    {
+        return Err(format!("Invalid parameter type: {}", sql_type));
+    }
+    Ok(sql_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(
+        name: &str,
+        sql_type: &str,
+        first: Option<&str>,
+        second: Option<&str>,
+    ) -> QueryParameter {
+        QueryParameter {
+            name: name.to_string(),
+            sql_type: sql_type.to_string(),
+            first_value: first.map(str::to_string),
+            second_value: second.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_recompiled_batch_binds_each_set() {
+        let params = [
+            param("@customer", "int", Some("42"), Some("7")),
+            param("city", "nvarchar(50)", Some("O'Hare"), None),
+        ];
+        let sql = "SELECT * FROM dbo.Orders WHERE CustomerId = @customer AND City = @city";
+        assert_eq!(
+            recompiled_batch(sql, &params, ParameterSet::First).unwrap(),
+            "EXEC sp_executesql N'SELECT * FROM dbo.Orders WHERE CustomerId = @customer AND City = @city OPTION (RECOMPILE)', \
+             N'@customer int, @city nvarchar(50)', @customer = N'42', @city = N'O''Hare'"
+        );
+        assert!(recompiled_batch(sql, &params, ParameterSet::Second)
+            .unwrap()
+            .ends_with("@customer = N'7', @city = NULL"));
+    }
+
+    #[test]
+    fn test_rejects_injected_names_and_types() {
+        let sql = "SELECT * FROM dbo.Orders WHERE Id = @id";
+        assert!(recompiled_batch(
+            sql,
+            &[param("@id; DROP", "int", None, None)],
+            ParameterSet::First
+        )
+        .is_err());
+        assert!(recompiled_batch(
+            sql,
+            &[param("@id", "int'; --", None, None)],
+            ParameterSet::First
+        )
+        .is_err());
+    }
+}
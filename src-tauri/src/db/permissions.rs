@@ -0,0 +1,138 @@
+use super::connection::TiberiusClient;
+use super::rows::{get_i64, get_string};
+use super::types::{PermissionCheck, PermissionReport};
+
+/// A permission the app relies on and the features that break without it
+struct RequiredPermission {
+    permission: &'static str,
+    /// Securable class for HAS_PERMS_BY_NAME: SERVER or DATABASE
+    scope: &'static str,
+    features: &'static [&'static str],
+}
+
+const REQUIRED_PERMISSIONS: &[RequiredPermission] = &[
+    RequiredPermission {
+        permission: "SHOWPLAN",
+        scope: "DATABASE",
+        features: &[
+            "Estimated execution plans",
+            "Actual execution plans",
+            "What-if index analysis",
+            "Query hint comparison",
+        ],
+    },
+    RequiredPermission {
+        permission: "VIEW SERVER STATE",
+        scope: "SERVER",
+        features: &["Wait statistics", "Top queries", "Plan cache lookup"],
+    },
+    RequiredPermission {
+        permission: "VIEW DEFINITION",
+        scope: "DATABASE",
+        features: &["Completion for objects owned by other users"],
+    },
+];
+
+fn permissions_sql() -> String {
+    REQUIRED_PERMISSIONS
+        .iter()
+        .map(|p| {
+            // Database permissions are checked in the current database
+            let securable = match p.scope {
+                "DATABASE" => "DB_NAME(), N'DATABASE'",
+                _ => "NULL, NULL",
+            };
+            format!(
+                "SELECT N'{}', HAS_PERMS_BY_NAME({}, N'{}')",
+                p.permission, securable, p.permission
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ")
+}
+
+/// Effective permissions of the login / database user on this connection
+pub async fn check(client: &mut TiberiusClient) -> Result<Vec<PermissionCheck>, String> {
+    let rows = client
+        .simple_query(permissions_sql())
+        .await
+        .map_err(|e| format!("Failed to check permissions: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to check permissions: {}", e))?;
+
+    Ok(REQUIRED_PERMISSIONS
+        .iter()
+        .map(|p| {
+            let granted = rows
+                .iter()
+                .find(|row| get_string(row, 0).as_deref() == Some(p.permission))
+                .and_then(|row| get_i64(row, 1))
+                == Some(1);
+            PermissionCheck {
+                permission: p.permission.to_string(),
+                scope: p.scope.to_string(),
+                granted,
+                features: p.features.iter().map(|f| f.to_string()).collect(),
+            }
+        })
+        .collect())
+}
+
+/// Permission checks plus the features they leave unavailable
+pub async fn report(client: &mut TiberiusClient) -> Result<PermissionReport, String> {
+    let permissions = check(client).await?;
+    Ok(PermissionReport {
+        unavailable_features: unavailable_features(&permissions),
+        permissions,
+    })
+}
+
+fn unavailable_features(checks: &[PermissionCheck]) -> Vec<String> {
+    let mut features: Vec<String> = Vec::new();
+    for feature in checks
+        .iter()
+        .filter(|c| !c.granted)
+        .flat_map(|c| &c.features)
+    {
+        if !features.contains(feature) {
+            features.push(feature.clone());
+        }
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions_sql() {
+        let sql = permissions_sql();
+        assert!(sql.starts_with(
+            "SELECT N'SHOWPLAN', HAS_PERMS_BY_NAME(DB_NAME(), N'DATABASE', N'SHOWPLAN')"
+        ));
+        assert!(sql.contains(
+            "SELECT N'VIEW SERVER STATE', HAS_PERMS_BY_NAME(NULL, NULL, N'VIEW SERVER STATE')"
+        ));
+    }
+
+    #[test]
+    fn test_unavailable_features() {
+        let check = |permission: &str, granted: bool, features: &[&str]| PermissionCheck {
+            permission: permission.to_string(),
+            scope: "DATABASE".to_string(),
+            granted,
+            features: features.iter().map(|f| f.to_string()).collect(),
+        };
+        let checks = vec![
+            check("SHOWPLAN", false, &["Plans", "Hints"]),
+            check("VIEW SERVER STATE", true, &["Waits"]),
+            check("OTHER", false, &["Hints", "Other"]),
+        ];
+        assert_eq!(
+            unavailable_features(&checks),
+            vec!["Plans", "Hints", "Other"]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::io::{Cursor, Read, Write};
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::query_hash;
+use super::redact::Redactor;
+use super::store;
+use super::types::{ExportPlanBundleRequest, PlanBundle};
+use crate::plan;
+use crate::settings;
+
+// A bundle is a plain zip, so the plan can also be opened in SSMS straight
+// from the archive: plan.sqlplan next to query.sql and JSON files for the
+// analysis, environment and annotations. manifest.json identifies it.
+
+const FORMAT: &str = "sqlplanfordummies-plan-bundle";
+const VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const PLAN_FILE: &str = "plan.sqlplan";
+const QUERY_FILE: &str = "query.sql";
+const ANALYSIS_FILE: &str = "analysis.json";
+const ENVIRONMENT_FILE: &str = "environment.json";
+const ANNOTATIONS_FILE: &str = "annotations.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    version: u32,
+    created_at: DateTime<Utc>,
+    app_version: String,
+    plan_hash: String,
+    source_plan_id: Option<String>,
+}
+
+/// Gather the plan, its query, the findings of the enabled analysis rules,
+/// the environment it was captured in and its annotations. With `redact`
+/// set, the plan and query are redacted before the rules run, so the
+/// findings cannot quote the values either.
+pub fn collect(app: &AppHandle, request: ExportPlanBundleRequest) -> Result<PlanBundle, String> {
+    let (mut plan_xml, mut sql, mut context) = match &request.plan_id {
+        Some(plan_id) => {
+            let entry = store::get_plan_history(app)?
+                .into_iter()
+                .find(|p| &p.id == plan_id)
+                .ok_or("Plan not found in history")?;
+            // The preview is truncated, so prefer the full SQL of the query entry
+            let sql = store::get_query_history(app)?
+                .into_iter()
+                .find(|q| q.id == entry.query_id)
+                .map(|q| q.sql)
+                .or(request.sql)
+                .unwrap_or(entry.sql_preview);
+            (entry.plan_xml, Some(sql), entry.context)
+        }
+        None => (
+            request.plan_xml.ok_or("Provide a plan id or plan XML")?,
+            request.sql,
+            None,
+        ),
+    };
+
+    // Annotations are keyed by the original plan
+    let plan_hash = query_hash::plan_hash(&plan_xml);
+    if let Some(options) = request.redact {
+        let mut redactor = Redactor::new(options);
+        plan_xml = redactor.plan_xml(&plan_xml);
+        sql = sql.map(|sql| redactor.sql(&sql));
+        if let Some(context) = context.as_mut() {
+            context.database_name = redactor.name("Database", &context.database_name);
+        }
+    }
+
+    let app_settings = settings::load(app)?;
+    let analysis = plan::parser::parse_plan(&plan_xml).ok().map(|parsed| {
+        plan::rules::analyze(
+            &parsed,
+            &plan::rules::all_rules(&app_settings.custom_analysis_rules),
+            &app_settings.disabled_analysis_rules,
+        )
+    });
+    let annotations = store::get_plan_annotations(app)?
+        .into_iter()
+        .filter(|a| a.plan_hash == plan_hash)
+        .collect();
+
+    Ok(PlanBundle {
+        created_at: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        plan_hash,
+        source_plan_id: request.plan_id,
+        plan_xml,
+        sql,
+        analysis,
+        context,
+        annotations,
+    })
+}
+
+/// Zip the bundle into one file
+pub fn write(bundle: &PlanBundle) -> Result<Vec<u8>, String> {
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: bundle.created_at,
+        app_version: bundle.app_version.clone(),
+        plan_hash: bundle.plan_hash.clone(),
+        source_plan_id: bundle.source_plan_id.clone(),
+    };
+    let mut files: Vec<(&str, String)> = vec![
+        (MANIFEST_FILE, to_json(&manifest)?),
+        (PLAN_FILE, bundle.plan_xml.clone()),
+    ];
+    if let Some(sql) = &bundle.sql {
+        files.push((QUERY_FILE, sql.clone()));
+    }
+    if let Some(analysis) = &bundle.analysis {
+        files.push((ANALYSIS_FILE, to_json(analysis)?));
+    }
+    if let Some(context) = &bundle.context {
+        files.push((ENVIRONMENT_FILE, to_json(context)?));
+    }
+    files.push((ANNOTATIONS_FILE, to_json(&bundle.annotations)?));
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, contents) in files {
+        zip.start_file(name, options)
+            .and_then(|_| zip.write_all(contents.as_bytes()).map_err(Into::into))
+            .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    }
+    let cursor = zip
+        .finish()
+        .map_err(|e| format!("Failed to write the bundle: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+/// Unzip a file written by `write`
+pub fn read(bytes: &[u8]) -> Result<PlanBundle, String> {
+    let mut zip =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|_| "Not a SqlPlanForDummies plan bundle")?;
+    let manifest: Manifest =
+        read_json(&mut zip, MANIFEST_FILE)?.ok_or("Not a SqlPlanForDummies plan bundle")?;
+    if manifest.format != FORMAT {
+        return Err("Not a SqlPlanForDummies plan bundle".into());
+    }
+    if manifest.version > VERSION {
+        return Err(format!(
+            "Bundle was made by a newer version of the app (format {})",
+            manifest.version
+        ));
+    }
+
+    Ok(PlanBundle {
+        created_at: manifest.created_at,
+        app_version: manifest.app_version,
+        plan_hash: manifest.plan_hash,
+        source_plan_id: manifest.source_plan_id,
+        plan_xml: read_text(&mut zip, PLAN_FILE)?.ok_or("The bundle has no plan")?,
+        sql: read_text(&mut zip, QUERY_FILE)?,
+        analysis: read_json(&mut zip, ANALYSIS_FILE)?,
+        context: read_json(&mut zip, ENVIRONMENT_FILE)?,
+        annotations: read_json(&mut zip, ANNOTATIONS_FILE)?.unwrap_or_default(),
+    })
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+fn read_text(zip: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<String>, String> {
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", name, e)),
+    };
+    let mut text = String::new();
+    file.read_to_string(&mut text)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    Ok(Some(text))
+}
+
+fn read_json<T: DeserializeOwned>(
+    zip: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    read_text(zip, name)?
+        .map(|text| serde_json::from_str(&text).map_err(|e| format!("{} is invalid: {}", name, e)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::PlanAnnotation;
+
+    #[test]
+    fn test_bundle_round_trips() {
+        let bundle = PlanBundle {
+            created_at: Utc::now(),
+            app_version: "2.4.0".to_string(),
+            plan_hash: "1f2e3d4c5b6a7988".to_string(),
+            source_plan_id: Some("plan-1".to_string()),
+            plan_xml: "<ShowPlanXML/>".to_string(),
+            sql: Some("SELECT * FROM dbo.Orders".to_string()),
+            analysis: None,
+            context: None,
+            annotations: vec![PlanAnnotation {
+                id: "a1".to_string(),
+                plan_hash: "1f2e3d4c5b6a7988".to_string(),
+                plan_id: Some("plan-1".to_string()),
+                node_id: Some(3),
+                text: "Key lookup: add Amount to IX_Orders_Date".to_string(),
+                created_at: Utc::now(),
+            }],
+        };
+
+        let read_back = read(&write(&bundle).unwrap()).unwrap();
+        assert_eq!(read_back.plan_xml, bundle.plan_xml);
+        assert_eq!(read_back.sql, bundle.sql);
+        assert_eq!(read_back.source_plan_id.as_deref(), Some("plan-1"));
+        assert!(read_back.analysis.is_none());
+        assert_eq!(read_back.annotations.len(), 1);
+        assert_eq!(read_back.annotations[0].node_id, Some(3));
+
+        assert!(read(b"not a zip").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+use super::store;
+
+/// Plans saved within this window of each other are written together
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Writes plan history in the background. Saving a plan only updates the
+/// store in memory and schedules a write, so a burst of executions costs one
+/// disk write instead of rewriting the whole history each time. The store
+/// plugin saves whatever is still pending when the app exits.
+#[derive(Default)]
+pub struct PlanHistoryWriter {
+    pending: Notify,
+}
+
+impl PlanHistoryWriter {
+    pub fn schedule(&self) {
+        self.pending.notify_one();
+    }
+}
+
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let writer = app.state::<PlanHistoryWriter>();
+        loop {
+            writer.pending.notified().await;
+            tokio::time::sleep(DEBOUNCE).await;
+            let handle = app.clone();
+            match tokio::task::spawn_blocking(move || store::flush_plan_history(&handle)).await {
+                Ok(Ok(())) => tracing::debug!("Plan history written"),
+                Ok(Err(e)) => tracing::warn!(error = %e, "Failed to write plan history"),
+                Err(e) => tracing::warn!(error = %e, "Plan history writer failed"),
+            }
+        }
+    });
+}
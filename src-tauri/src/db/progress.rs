@@ -0,0 +1,165 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tauri::Emitter;
+use tokio::task::JoinHandle;
+
+use super::connection::{DbConnection, TiberiusClient};
+use super::rows::{get_i64, get_string};
+use super::types::{OperatorProgress, QueryProgress};
+
+/// Queries finishing sooner never touch the monitoring connection
+const FIRST_POLL: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Operators that have not closed stay below this, since the estimate is
+/// only a guess at when they will
+const OPEN_OPERATOR_CAP: f64 = 99.0;
+
+/// One row per operator; parallel operators report a row per thread, each
+/// carrying the estimate for that thread
+fn profile_sql(session_id: i64) -> String {
+    format!(
+        "SELECT node_id, MAX(physical_operator_name), SUM(row_count), SUM(estimate_row_count), \
+                CAST(MIN(CASE WHEN close_time > 0 THEN 1 ELSE 0 END) AS int) \
+         FROM sys.dm_exec_query_profiles \
+         WHERE session_id = {} \
+         GROUP BY node_id \
+         ORDER BY node_id",
+        session_id
+    )
+}
+
+async fn read_operators(
+    client: &mut TiberiusClient,
+    session_id: i64,
+) -> Result<Vec<OperatorProgress>, String> {
+    let rows = client
+        .simple_query(profile_sql(session_id))
+        .await
+        .map_err(|e| format!("Failed to read query progress: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read query progress: {}", e))?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some(OperatorProgress {
+                node_id: get_i64(row, 0)?,
+                physical_op: get_string(row, 1).unwrap_or_default(),
+                row_count: get_i64(row, 2).unwrap_or(0),
+                estimate_row_count: get_i64(row, 3).unwrap_or(0),
+                finished: get_i64(row, 4) == Some(1),
+                percent: 0.0,
+            })
+        })
+        .collect())
+}
+
+/// Fill in each operator's percentage and weigh them by estimated rows.
+/// None when the server reports no operators (not running yet, or the
+/// profiling infrastructure is off).
+pub fn compute(
+    query_id: &str,
+    elapsed: Duration,
+    mut operators: Vec<OperatorProgress>,
+) -> Option<QueryProgress> {
+    if operators.is_empty() {
+        return None;
+    }
+    let mut done = 0.0;
+    let mut total = 0.0;
+    for op in &mut operators {
+        op.percent = if op.finished {
+            100.0
+        } else {
+            let estimate = op.estimate_row_count.max(1) as f64;
+            (op.row_count as f64 / estimate * 100.0).min(OPEN_OPERATOR_CAP)
+        };
+        let weight = op.estimate_row_count.max(1) as f64;
+        done += weight * op.percent / 100.0;
+        total += weight;
+    }
+    Some(QueryProgress {
+        query_id: query_id.to_string(),
+        percent: (done / total * 100.0).min(OPEN_OPERATOR_CAP),
+        elapsed_ms: elapsed.as_millis() as u64,
+        operators,
+    })
+}
+
+/// Poll the user's session from the monitoring connection while a query
+/// runs, emitting "query-progress". Abort the task when the query returns.
+/// Polling stops on the first error, e.g. without VIEW SERVER STATE.
+pub fn spawn_poller(
+    app: tauri::AppHandle,
+    conn: Arc<DbConnection>,
+    query_id: String,
+) -> Option<JoinHandle<()>> {
+    let session_id = conn.session_id()?;
+    Some(tokio::spawn(async move {
+        let started = Instant::now();
+        tokio::time::sleep(FIRST_POLL).await;
+        loop {
+            let operators = conn
+                .monitor
+                .run(|client| Box::pin(read_operators(client, session_id)))
+                .await;
+            match operators {
+                Ok(operators) => {
+                    if let Some(progress) = compute(&query_id, started.elapsed(), operators) {
+                        let _ = app.emit("query-progress", &progress);
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!(error = %e, query_id = %query_id, "Stopped polling query progress");
+                    return;
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(
+        node_id: i64,
+        row_count: i64,
+        estimate_row_count: i64,
+        finished: bool,
+    ) -> OperatorProgress {
+        OperatorProgress {
+            node_id,
+            physical_op: "Table Scan".to_string(),
+            row_count,
+            estimate_row_count,
+            finished,
+            percent: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_weighs_operators_by_estimate() {
+        let progress = compute(
+            "q",
+            Duration::from_secs(3),
+            vec![
+                op(0, 50, 100, false),
+                op(1, 900, 900, true),
+                op(2, 5000, 1000, false),
+            ],
+        )
+        .unwrap();
+        let percents: Vec<f64> = progress.operators.iter().map(|o| o.percent).collect();
+        // An open operator past its estimate is capped
+        assert_eq!(percents, vec![50.0, 100.0, 99.0]);
+        // (50 + 900 + 990) / 2000
+        assert!((progress.percent - 97.0).abs() < 1e-9);
+        assert_eq!(progress.elapsed_ms, 3000);
+
+        assert!(compute("q", Duration::ZERO, Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use sha2::{Digest, Sha256};
+
+// Identity of a query's text for linking history entries: comments are
+// dropped, whitespace collapsed and everything outside string literals and
+// quoted identifiers lowercased, so reformatting a query keeps its history.
+
+pub fn normalize_sql(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut pending_space = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+                pending_space = true;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('/') if chars.peek() == Some(&'*') => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        Some('*') if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                pending_space = true;
+            }
+            c if c.is_whitespace() => pending_space = true,
+            '\'' | '"' | '[' => {
+                push_separator(&mut out, &mut pending_space);
+                let close = if c == '[' { ']' } else { c };
+                out.push(c);
+                while let Some(next) = chars.next() {
+                    out.push(next);
+                    if next == close {
+                        if chars.peek() == Some(&close) {
+                            out.push(chars.next().unwrap());
+                            continue;
+                        }
+                        break;
+                    }
+                }
+            }
+            _ => {
+                push_separator(&mut out, &mut pending_space);
+                out.extend(c.to_lowercase());
+            }
+        }
+    }
+
+    out.trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+        .to_string()
+}
+
+fn push_separator(out: &mut String, pending_space: &mut bool) {
+    if *pending_space && !out.is_empty() {
+        out.push(' ');
+    }
+    *pending_space = false;
+}
+
+/// Short stable hash of the normalized text
+pub fn sql_hash(sql: &str) -> String {
+    let digest = Sha256::digest(normalize_sql(sql).as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Normalized SQL with string and number literals replaced by `?` and literal
+/// lists collapsed, so `WHERE id IN (1, 2)` and `WHERE id IN (7)` match
+pub fn fingerprint_sql(sql: &str) -> String {
+    let normalized = normalize_sql(sql);
+    let mut out = String::with_capacity(normalized.len());
+    let mut chars = normalized.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                out.push(c);
+                while let Some(next) = chars.next() {
+                    out.push(next);
+                    if next == close {
+                        if chars.peek() == Some(&close) {
+                            out.push(chars.next().unwrap());
+                            continue;
+                        }
+                        break;
+                    }
+                }
+            }
+            '\'' => {
+                while let Some(next) = chars.next() {
+                    if next == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            chars.next();
+                            continue;
+                        }
+                        break;
+                    }
+                }
+                // N'...' is one literal
+                if out.ends_with('n') && !ends_with_word_char(&out[..out.len() - 1]) {
+                    out.pop();
+                }
+                out.push('?');
+            }
+            c if c.is_ascii_digit() && !ends_with_word_char(&out) => {
+                while chars
+                    .peek()
+                    .is_some_and(|n| n.is_ascii_alphanumeric() || *n == '.')
+                {
+                    chars.next();
+                }
+                out.push('?');
+            }
+            _ => out.push(c),
+        }
+    }
+
+    loop {
+        let collapsed = out.replace("?, ?", "?").replace("?,?", "?");
+        if collapsed == out {
+            return out;
+        }
+        out = collapsed;
+    }
+}
+
+fn ends_with_word_char(text: &str) -> bool {
+    text.chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '$'))
+}
+
+/// Short stable hash of the fingerprint, shared by runs that differ only in
+/// literal values
+pub fn fingerprint(sql: &str) -> String {
+    let digest = Sha256::digest(fingerprint_sql(sql).as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Key shared by captures of the same plan shape: the statements'
+/// QueryPlanHash values, or the whole XML for plans without them
+pub fn plan_hash(plan_xml: &str) -> String {
+    const ATTRIBUTE: &str = "QueryPlanHash=\"";
+    let hashes: Vec<&str> = plan_xml
+        .match_indices(ATTRIBUTE)
+        .filter_map(|(i, _)| {
+            let value = &plan_xml[i + ATTRIBUTE.len()..];
+            value.find('"').map(|end| &value[..end])
+        })
+        .collect();
+    let digest = if hashes.is_empty() {
+        Sha256::digest(plan_xml.as_bytes())
+    } else {
+        Sha256::digest(hashes.join(",").as_bytes())
+    };
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting_does_not_change_hash() {
+        let a = "SELECT *\n  FROM dbo.Orders -- recent\nWHERE Id = 1;";
+        let b = "select * /* all */ from   dbo.orders where id = 1";
+        assert_eq!(normalize_sql(a), "select * from dbo.orders where id = 1");
+        assert_eq!(sql_hash(a), sql_hash(b));
+    }
+
+    #[test]
+    fn test_literals_keep_case() {
+        assert_ne!(
+            sql_hash("SELECT * FROM t WHERE name = 'Bob'"),
+            sql_hash("SELECT * FROM t WHERE name = 'bob'")
+        );
+        assert_eq!(
+            normalize_sql("SELECT [My  Col] FROM t"),
+            "select [My  Col] from t"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_strips_literals() {
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM t WHERE Name = N'Bob' AND Id IN (1, 2, 3) AND x > 0x1F"),
+            "select * from t where name = ? and id in (?) and x > ?"
+        );
+        assert_eq!(
+            fingerprint("select top 10 * from t2 where id = 5"),
+            fingerprint("SELECT TOP 500 *\n FROM t2 WHERE id = 42;")
+        );
+        // Digits inside names and variables are not literals
+        assert_eq!(
+            fingerprint_sql("SELECT col1 FROM [Table 2] WHERE @p1 = 1.5"),
+            "select col1 from [Table 2] where @p1 = ?"
+        );
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM t WHERE a = 'it''s'"),
+            "select * from t where a = ?"
+        );
+    }
+
+    #[test]
+    fn test_plan_hash_follows_query_plan_hash() {
+        let plan = |cost: &str| {
+            format!(
+                r#"<StmtSimple StatementSubTreeCost="{}" QueryHash="0xAA" QueryPlanHash="0x1F2E"/>"#,
+                cost
+            )
+        };
+        // Costs differ between captures of the same shape
+        assert_eq!(plan_hash(&plan("0.5")), plan_hash(&plan("7.25")));
+        assert_ne!(
+            plan_hash(&plan("0.5")),
+            plan_hash(&plan("0.5").replace("0x1F2E", "0x3D4C"))
+        );
+        assert_ne!(plan_hash("<ShowPlanXML/>"), plan_hash("<ShowPlanXML />"));
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use tokio::sync::Notify;
+
+use super::types::{AppError, PendingQuery};
+
+/// Queries waiting for the active connection. A session runs one batch at a
+/// time, so without the queue a second execute would sit on the client lock
+/// with nothing to show for it; for the same reason the
+/// `max_concurrent_queries` setting never goes above one.
+#[derive(Default)]
+pub struct ExecutionQueue {
+    inner: Mutex<QueueInner>,
+    changed: Notify,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    running: bool,
+    pending: VecDeque<PendingQuery>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Turn {
+    Start,
+    Wait,
+    Cancelled,
+}
+
+impl QueueInner {
+    /// Whether `query_id` may start now; starting takes it out of the queue
+    fn turn(&mut self, query_id: &str) -> Turn {
+        match self.pending.iter().position(|q| q.id == query_id) {
+            None => Turn::Cancelled,
+            Some(0) if !self.running => {
+                self.pending.pop_front();
+                self.running = true;
+                Turn::Start
+            }
+            Some(_) => Turn::Wait,
+        }
+    }
+
+    fn snapshot(&self) -> Vec<PendingQuery> {
+        self.pending
+            .iter()
+            .enumerate()
+            .map(|(i, q)| PendingQuery {
+                position: i + 1,
+                ..q.clone()
+            })
+            .collect()
+    }
+}
+
+impl ExecutionQueue {
+    /// Put a query at the back of the queue. Dropping the ticket leaves the
+    /// queue, or frees the slot once the query has started.
+    pub fn enqueue(&self, query_id: &str, sql: &str) -> QueueTicket<'_> {
+        self.inner.lock().unwrap().pending.push_back(PendingQuery {
+            id: query_id.to_string(),
+            sql: sql.to_string(),
+            queued_at: Utc::now(),
+            position: 0,
+        });
+        self.changed.notify_waiters();
+        QueueTicket {
+            queue: self,
+            query_id: query_id.to_string(),
+            started: false,
+        }
+    }
+
+    /// Waiting queries, next to run first
+    pub fn pending(&self) -> Vec<PendingQuery> {
+        self.inner.lock().unwrap().snapshot()
+    }
+
+    /// Take a query out of the queue before it starts; false when it is not
+    /// waiting (already running, finished or unknown)
+    pub fn cancel(&self, query_id: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.pending.len();
+        inner.pending.retain(|q| q.id != query_id);
+        let removed = inner.pending.len() != before;
+        drop(inner);
+        if removed {
+            self.changed.notify_waiters();
+        }
+        removed
+    }
+
+    /// Drop every waiting query, e.g. when the connection they were queued
+    /// for goes away
+    pub fn cancel_all(&self) -> usize {
+        let removed = std::mem::take(&mut self.inner.lock().unwrap().pending).len();
+        if removed > 0 {
+            self.changed.notify_waiters();
+        }
+        removed
+    }
+}
+
+pub struct QueueTicket<'a> {
+    queue: &'a ExecutionQueue,
+    query_id: String,
+    started: bool,
+}
+
+impl QueueTicket<'_> {
+    /// Wait until the query is first in line and no other query is running
+    pub async fn wait_turn(&mut self) -> Result<(), AppError> {
+        loop {
+            let changed = self.queue.changed.notified();
+            tokio::pin!(changed);
+            // Register before checking so a wake-up in between is not lost
+            changed.as_mut().enable();
+            let turn = self.queue.inner.lock().unwrap().turn(&self.query_id);
+            match turn {
+                Turn::Start => {
+                    self.started = true;
+                    self.queue.changed.notify_waiters();
+                    return Ok(());
+                }
+                Turn::Cancelled => {
+                    return Err(AppError::cancelled("Query was cancelled before it started"))
+                }
+                Turn::Wait => changed.await,
+            }
+        }
+    }
+}
+
+impl Drop for QueueTicket<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.queue.inner.lock().unwrap();
+        if self.started {
+            inner.running = false;
+        } else {
+            inner.pending.retain(|q| q.id != self.query_id);
+        }
+        drop(inner);
+        self.queue.changed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(ids: &[&str]) -> QueueInner {
+        let mut inner = QueueInner::default();
+        for id in ids {
+            inner.pending.push_back(PendingQuery {
+                id: id.to_string(),
+                sql: format!("SELECT '{}'", id),
+                queued_at: Utc::now(),
+                position: 0,
+            });
+        }
+        inner
+    }
+
+    #[test]
+    fn test_runs_one_at_a_time_in_order() {
+        let mut inner = queued(&["a", "b", "c"]);
+        assert_eq!(inner.turn("b"), Turn::Wait);
+        assert_eq!(inner.turn("a"), Turn::Start);
+        // "b" is first in line but "a" still holds the session
+        assert_eq!(inner.turn("b"), Turn::Wait);
+        inner.running = false;
+        assert_eq!(inner.turn("b"), Turn::Start);
+        assert_eq!(inner.turn("a"), Turn::Cancelled);
+
+        let pending = inner.snapshot();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].id.as_str(), pending[0].position), ("c", 1));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_before_start_is_reported_as_cancelled() {
+        let queue = ExecutionQueue::default();
+        let _running = queue.enqueue("a", "SELECT 1");
+        let mut waiting = queue.enqueue("b", "SELECT 2");
+        assert!(queue.cancel("b"));
+        assert!(matches!(
+            waiting.wait_turn().await,
+            Err(AppError::Cancelled { .. })
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use tiberius::Row;
+
+use super::cells::hex_literal;
+use super::connection::TiberiusClient;
+use super::identifiers::{binary_literal, quote_literal};
+use super::query_hash::normalize_sql;
+use super::rows::{get_bytes, get_datetime, get_f64, get_i64, get_string};
+use super::types::{CachedPlan, TopQueriesRequest, TopQuery, TopQueryMetric};
+
+const DEFAULT_LIMIT: u32 = 25;
+const MAX_LIMIT: u32 = 500;
+
+/// Cached batches read per text search, most used first; scoring happens locally
+const TEXT_SEARCH_CANDIDATES: u32 = 200;
+const DEFAULT_TEXT_MATCHES: u32 = 10;
+const MAX_TEXT_MATCHES: u32 = 50;
+
+/// Token overlap (0..1) a cached batch needs to count as the same query
+const MIN_TEXT_SIMILARITY: f64 = 0.5;
+
+fn order_column(metric: TopQueryMetric) -> &'static str {
+    match metric {
+        TopQueryMetric::Cpu => "qs.total_worker_time",
+        TopQueryMetric::Reads => "qs.total_logical_reads",
+        TopQueryMetric::Duration => "qs.total_elapsed_time",
+        TopQueryMetric::Executions => "qs.execution_count",
+    }
+}
+
+fn top_queries_sql(request: &TopQueriesRequest) -> String {
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (plan_column, plan_apply) = if request.include_plans {
+        (
+            "CAST(qp.query_plan AS nvarchar(max))",
+            "OUTER APPLY sys.dm_exec_query_plan(qs.plan_handle) qp",
+        )
+    } else {
+        ("CAST(NULL AS nvarchar(max))", "")
+    };
+
+    // Offsets are in bytes of nvarchar text; -1 means "to the end of the batch"
+    format!(
+        "SELECT TOP ({limit}) \
+             qs.plan_handle, qs.query_hash, \
+             SUBSTRING(st.text, qs.statement_start_offset / 2 + 1, \
+                 (CASE WHEN qs.statement_end_offset = -1 THEN DATALENGTH(st.text) \
+                       ELSE qs.statement_end_offset END - qs.statement_start_offset) / 2 + 1), \
+             DB_NAME(st.dbid), \
+             qs.execution_count, \
+             CAST(qs.total_worker_time / 1000.0 AS float), \
+             qs.total_logical_reads, \
+             CAST(qs.total_elapsed_time / 1000.0 AS float), \
+             qs.last_execution_time, \
+             {plan_column} \
+         FROM sys.dm_exec_query_stats qs \
+         CROSS APPLY sys.dm_exec_sql_text(qs.sql_handle) st \
+         {plan_apply} \
+         ORDER BY {order} DESC",
+        order = order_column(request.metric),
+    )
+}
+
+/// Most expensive cached statements by the requested total
+pub async fn top_queries(
+    client: &mut TiberiusClient,
+    request: &TopQueriesRequest,
+) -> Result<Vec<TopQuery>, String> {
+    let rows = client
+        .simple_query(top_queries_sql(request))
+        .await
+        .map_err(|e| format!("Failed to read query stats: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read query stats: {}", e))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let execution_count = get_i64(row, 4).unwrap_or(0);
+            let total_cpu_ms = get_f64(row, 5).unwrap_or(0.0);
+            let total_logical_reads = get_i64(row, 6).unwrap_or(0);
+            let total_elapsed_ms = get_f64(row, 7).unwrap_or(0.0);
+            let per_execution = |total: f64| {
+                if execution_count > 0 {
+                    total / execution_count as f64
+                } else {
+                    0.0
+                }
+            };
+            TopQuery {
+                plan_handle: get_bytes(row, 0)
+                    .map(|h| hex_literal(&h))
+                    .unwrap_or_default(),
+                query_hash: get_bytes(row, 1).map(|h| hex_literal(&h)),
+                statement_text: get_string(row, 2).unwrap_or_default(),
+                database_name: get_string(row, 3),
+                execution_count,
+                total_cpu_ms,
+                avg_cpu_ms: per_execution(total_cpu_ms),
+                total_logical_reads,
+                avg_logical_reads: per_execution(total_logical_reads as f64),
+                total_elapsed_ms,
+                avg_elapsed_ms: per_execution(total_elapsed_ms),
+                last_execution_time: get_datetime(row, 8),
+                plan_xml: get_string(row, 9),
+            }
+        })
+        .collect())
+}
+
+fn cached_plans_sql(top: Option<u32>, with_plan: bool, filter: &str) -> String {
+    let top = top.map(|n| format!("TOP ({}) ", n)).unwrap_or_default();
+    let (plan_column, plan_apply) = if with_plan {
+        (
+            "CAST(qp.query_plan AS nvarchar(max))",
+            "OUTER APPLY sys.dm_exec_query_plan(cp.plan_handle) qp",
+        )
+    } else {
+        ("CAST(NULL AS nvarchar(max))", "")
+    };
+    format!(
+        "SELECT {top}cp.plan_handle, cp.objtype, cp.usecounts, cp.size_in_bytes, \
+             DB_NAME(st.dbid), st.text, {plan_column} \
+         FROM sys.dm_exec_cached_plans cp \
+         CROSS APPLY sys.dm_exec_sql_text(cp.plan_handle) st \
+         {plan_apply} \
+         WHERE {filter} \
+         ORDER BY cp.usecounts DESC"
+    )
+}
+
+async fn read_cached_plans(
+    client: &mut TiberiusClient,
+    sql: String,
+) -> Result<Vec<CachedPlan>, String> {
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| format!("Failed to read plan cache: {}", e))?
+        .into_first_result()
+        .await
+        .map_err(|e| format!("Failed to read plan cache: {}", e))?;
+    Ok(rows.iter().map(cached_plan_from_row).collect())
+}
+
+fn cached_plan_from_row(row: &Row) -> CachedPlan {
+    CachedPlan {
+        plan_handle: get_bytes(row, 0)
+            .map(|h| hex_literal(&h))
+            .unwrap_or_default(),
+        object_type: get_string(row, 1).unwrap_or_default(),
+        use_counts: get_i64(row, 2).unwrap_or(0),
+        size_bytes: get_i64(row, 3).unwrap_or(0),
+        database_name: get_string(row, 4),
+        sql_text: get_string(row, 5).unwrap_or_default(),
+        plan_xml: get_string(row, 6),
+        match_score: None,
+    }
+}
+
+/// The cached plan for a plan handle (hex literal), if still in cache
+pub async fn cached_plan_by_handle(
+    client: &mut TiberiusClient,
+    plan_handle: &str,
+) -> Result<Option<CachedPlan>, String> {
+    let filter = format!("cp.plan_handle = {}", binary_literal(plan_handle)?);
+    let mut plans = read_cached_plans(client, cached_plans_sql(None, true, &filter)).await?;
+    Ok(plans.pop())
+}
+
+/// Cached plans whose batch text resembles `query_text`, best match first.
+/// Formatting, comments and keyword case do not matter.
+pub async fn cached_plans_by_text(
+    client: &mut TiberiusClient,
+    query_text: &str,
+    limit: Option<u32>,
+) -> Result<Vec<CachedPlan>, String> {
+    let normalized = normalize_sql(query_text);
+    let wanted = tokens(&normalized);
+    if wanted.is_empty() {
+        return Err("Query text is empty".to_string());
+    }
+
+    // Narrow the cache down on the server by the most distinctive word; the
+    // search batch itself is cached too and must not match
+    let mut filter = "st.text NOT LIKE N'%sys.dm[_]exec[_]cached[_]plans%'".to_string();
+    if let Some(word) = wanted
+        .iter()
+        .filter(|w| w.len() >= 3)
+        .max_by_key(|w| w.len())
+    {
+        filter.push_str(&format!(
+            " AND st.text LIKE {} ESCAPE '\\'",
+            quote_literal(&format!("%{}%", escape_like(word)))
+        ));
+    }
+    let candidates = read_cached_plans(
+        client,
+        cached_plans_sql(Some(TEXT_SEARCH_CANDIDATES), false, &filter),
+    )
+    .await?;
+
+    let mut matches: Vec<CachedPlan> = candidates
+        .into_iter()
+        .filter_map(|mut plan| {
+            let score = similarity(&wanted, &tokens(&normalize_sql(&plan.sql_text)));
+            plan.match_score = Some(score);
+            (score >= MIN_TEXT_SIMILARITY).then_some(plan)
+        })
+        .collect();
+    matches.sort_by(|a, b| b.match_score.partial_cmp(&a.match_score).unwrap());
+    matches.truncate(
+        limit
+            .unwrap_or(DEFAULT_TEXT_MATCHES)
+            .clamp(1, MAX_TEXT_MATCHES) as usize,
+    );
+    if matches.is_empty() {
+        return Ok(matches);
+    }
+
+    // Plan XML only for the survivors
+    let handles = matches
+        .iter()
+        .map(|m| binary_literal(&m.plan_handle))
+        .collect::<Result<Vec<_>, _>>()?;
+    let filter = format!("cp.plan_handle IN ({})", handles.join(", "));
+    let plans = read_cached_plans(client, cached_plans_sql(None, true, &filter)).await?;
+    for m in &mut matches {
+        m.plan_xml = plans
+            .iter()
+            .find(|p| p.plan_handle == m.plan_handle)
+            .and_then(|p| p.plan_xml.clone());
+    }
+    Ok(matches)
+}
+
+/// Words and numbers of normalized SQL; punctuation is ignored
+fn tokens(normalized: &str) -> HashSet<String> {
+    normalized
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '@' || c == '#'))
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Dice coefficient of two token sets
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn escape_like(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_' | '[') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_queries_sql() {
+        let sql = top_queries_sql(&TopQueriesRequest {
+            metric: TopQueryMetric::Reads,
+            limit: Some(10_000),
+            include_plans: false,
+        });
+        assert!(sql.starts_with("SELECT TOP (500)"));
+        assert!(sql.ends_with("ORDER BY qs.total_logical_reads DESC"));
+        assert!(!sql.contains("dm_exec_query_plan"));
+
+        let sql = top_queries_sql(&TopQueriesRequest {
+            metric: TopQueryMetric::Cpu,
+            limit: None,
+            include_plans: true,
+        });
+        assert!(sql.starts_with("SELECT TOP (25)"));
+        assert!(sql.contains("OUTER APPLY sys.dm_exec_query_plan(qs.plan_handle) qp"));
+    }
+
+    #[test]
+    fn test_text_similarity_ignores_formatting() {
+        let a = tokens(&normalize_sql("SELECT * FROM dbo.Orders WHERE Id = @id"));
+        let b = tokens(&normalize_sql(
+            "select *\n  from dbo.orders -- lookup\n where id = @id",
+        ));
+        assert_eq!(similarity(&a, &b), 1.0);
+
+        let other = tokens(&normalize_sql("SELECT Name, City FROM Sales.Customers"));
+        assert!(similarity(&a, &other) < MIN_TEXT_SIMILARITY);
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("order_lines"), "order\\_lines");
+        assert_eq!(escape_like("50%[x]"), "50\\%\\[x]");
+    }
+}
@@ -0,0 +1,27 @@
+use super::connection::TiberiusClient;
+
+/// Make Query Store compile `query_id` with `plan_id` from now on, or stop
+/// doing so
+pub async fn force_plan(
+    client: &mut TiberiusClient,
+    query_id: i64,
+    plan_id: i64,
+    force: bool,
+) -> Result<(), String> {
+    let (procedure, verb) = if force {
+        ("sp_query_store_force_plan", "force")
+    } else {
+        ("sp_query_store_unforce_plan", "unforce")
+    };
+    client
+        .simple_query(format!(
+            "EXEC sys.{} @query_id = {}, @plan_id = {}",
+            procedure, query_id, plan_id
+        ))
+        .await
+        .map_err(|e| format!("Failed to {} plan {}: {}", verb, plan_id, e))?
+        .into_results()
+        .await
+        .map_err(|e| format!("Failed to {} plan {}: {}", verb, plan_id, e))?;
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use quick_xml::escape::{escape, unescape};
+
+use super::formatter::KEYWORDS;
+use super::tsql_lexer::{tokenize, Token, TokenKind};
+use super::types::RedactOptions;
+
+// Literal values, and on request object names, are replaced by placeholders
+// before a plan or query leaves the machine. A Redactor gives each distinct
+// value one placeholder wherever it appears, so the query text, the plan's
+// predicates and its parameter list still line up: 'jane@example.com' is
+// ?1 in all three, and [Customers] is Table1.
+
+/// Plan attributes holding T-SQL text or a single literal
+const SQL_ATTRIBUTES: &[&str] = &[
+    "StatementText",
+    "ParameterizedText",
+    "ScalarString",
+    "ConstValue",
+    "ParameterCompiledValue",
+    "ParameterRuntimeValue",
+];
+
+/// Plan attributes holding object names, with their placeholder prefix
+const NAME_ATTRIBUTES: &[(&str, &str)] = &[
+    ("Database", "Database"),
+    ("Schema", "Schema"),
+    ("Table", "Table"),
+    ("Index", "Index"),
+    ("Statistics", "Statistics"),
+    ("Column", "Column"),
+    // Missing index columns
+    ("Name", "Column"),
+    ("Alias", "Alias"),
+    ("ProcName", "Object"),
+];
+
+/// Words a table or procedure name follows in a query
+const OBJECT_CONTEXT: &[&str] = &[
+    "FROM",
+    "JOIN",
+    "INTO",
+    "UPDATE",
+    "TABLE",
+    "MERGE",
+    "USING",
+    "USE",
+    "EXEC",
+    "EXECUTE",
+    "PROC",
+    "PROCEDURE",
+    "VIEW",
+    "FUNCTION",
+];
+
+/// Names the optimizer makes up, e.g. Expr1002; they say nothing about the
+/// schema and keep the plan readable
+const INTERNAL_NAME_PREFIXES: &[&str] = &["Expr", "Bmk", "Uniq", "IsBaseRow", "PtnId", "Chk"];
+
+#[derive(Default)]
+pub struct Redactor {
+    options: RedactOptions,
+    literals: HashMap<String, String>,
+    names: HashMap<String, String>,
+    name_counts: HashMap<&'static str, usize>,
+}
+
+impl Redactor {
+    pub fn new(options: RedactOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// The query with literals replaced by ?1, ?2, ... and comments emptied.
+    /// With object names on, quoted names, multi-part names, the names after
+    /// FROM, JOIN and the like, and any name already seen in a plan are
+    /// replaced too.
+    pub fn sql(&mut self, sql: &str) -> String {
+        let tokens = tokenize(sql);
+        let mut out = String::with_capacity(sql.len());
+        let mut copied = 0;
+        let mut previous: Option<&Token> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let text = token.text(sql);
+            let replacement = match token.kind {
+                TokenKind::StringLiteral => Some(self.literal(&unquote(text))),
+                TokenKind::Number => Some(self.literal(text)),
+                TokenKind::LineComment => Some("--".to_string()),
+                TokenKind::BlockComment => Some("/**/".to_string()),
+                TokenKind::Word | TokenKind::QuotedName
+                    if self.options.object_names
+                        && self.is_object_name(sql, &tokens, i, previous) =>
+                {
+                    Some(self.name("Object", text))
+                }
+                _ => None,
+            };
+            if let Some(replacement) = replacement {
+                out.push_str(&sql[copied..token.start]);
+                out.push_str(&replacement);
+                copied = token.end;
+            }
+            if !token.is_comment() {
+                previous = Some(token);
+            }
+        }
+        out.push_str(&sql[copied..]);
+        out
+    }
+
+    /// The plan with literals replaced in statement text, predicates,
+    /// constants and the parameter list. Runtime counters are numbers the
+    /// plan measured, not values from the query, and are kept.
+    pub fn plan_xml(&mut self, xml: &str) -> String {
+        // Learn the plan's names first, so the statement text at the top of
+        // the plan already knows its bare column names
+        if self.options.object_names {
+            for (attribute, value) in attributes(xml) {
+                if let Some(&(_, prefix)) = NAME_ATTRIBUTES.iter().find(|(a, _)| *a == attribute) {
+                    self.names_in(prefix, &value);
+                }
+            }
+        }
+
+        let mut out = String::with_capacity(xml.len());
+        let mut copied = 0;
+        for (attribute, start, end) in attribute_ranges(xml) {
+            let raw = &xml[start..end];
+            let value = unescape(raw).unwrap_or(raw.into());
+            let redacted = if SQL_ATTRIBUTES.contains(&attribute) {
+                self.sql(&value)
+            } else if let Some(&(_, prefix)) = NAME_ATTRIBUTES
+                .iter()
+                .find(|(a, _)| self.options.object_names && *a == attribute)
+            {
+                self.names_in(prefix, &value)
+            } else {
+                continue;
+            };
+            out.push_str(&xml[copied..start]);
+            out.push_str(&escape(redacted.as_str()));
+            copied = end;
+        }
+        out.push_str(&xml[copied..]);
+        out
+    }
+
+    /// Placeholder for an object name, or the name itself with object names
+    /// off. Quoted names stay quoted.
+    pub fn name(&mut self, prefix: &'static str, name: &str) -> String {
+        let bare = unquote(name);
+        if !self.options.object_names || is_internal_name(&bare) {
+            return name.to_string();
+        }
+        let placeholder = match self.names.get(&bare.to_lowercase()) {
+            Some(placeholder) => placeholder.clone(),
+            None => {
+                let count = self.name_counts.entry(prefix).or_default();
+                *count += 1;
+                let placeholder = format!("{}{}", prefix, count);
+                self.names.insert(bare.to_lowercase(), placeholder.clone());
+                placeholder
+            }
+        };
+        match name.chars().next() {
+            Some('[') => format!("[{}]", placeholder),
+            Some('"') => format!("\"{}\"", placeholder),
+            _ => placeholder,
+        }
+    }
+
+    fn literal(&mut self, value: &str) -> String {
+        let next = self.literals.len() + 1;
+        self.literals
+            .entry(value.to_string())
+            .or_insert_with(|| format!("?{}", next))
+            .clone()
+    }
+
+    /// Every name in a (multi-part) name attribute; variables are kept
+    fn names_in(&mut self, prefix: &'static str, value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        let mut copied = 0;
+        for token in tokenize(value) {
+            let text = token.text(value);
+            let is_name = match token.kind {
+                TokenKind::QuotedName => true,
+                TokenKind::Word => !text.starts_with('@'),
+                _ => false,
+            };
+            if is_name {
+                out.push_str(&value[copied..token.start]);
+                out.push_str(&self.name(prefix, text));
+                copied = token.end;
+            }
+        }
+        out.push_str(&value[copied..]);
+        out
+    }
+
+    fn is_object_name(
+        &self,
+        sql: &str,
+        tokens: &[Token],
+        i: usize,
+        previous: Option<&Token>,
+    ) -> bool {
+        let token = &tokens[i];
+        if token.kind == TokenKind::QuotedName {
+            return true;
+        }
+        let text = token.text(sql);
+        if text.starts_with('@') {
+            return false;
+        }
+        let dotted = previous.is_some_and(|p| p.kind == TokenKind::Dot && p.end == token.start)
+            || tokens
+                .get(i + 1)
+                .is_some_and(|n| n.kind == TokenKind::Dot && n.start == token.end);
+        dotted
+            || self.names.contains_key(&text.to_lowercase())
+            || (!KEYWORDS.iter().any(|k| text.eq_ignore_ascii_case(k))
+                && previous.is_some_and(|p| {
+                    p.kind == TokenKind::Word
+                        && OBJECT_CONTEXT
+                            .iter()
+                            .any(|word| p.text(sql).eq_ignore_ascii_case(word))
+                }))
+    }
+}
+
+/// A query with its literals (and object names) replaced
+pub fn redact_sql(sql: &str, options: RedactOptions) -> String {
+    Redactor::new(options).sql(sql)
+}
+
+/// A plan with its literals (and object names) replaced
+pub fn redact_plan_xml(xml: &str, options: RedactOptions) -> String {
+    Redactor::new(options).plan_xml(xml)
+}
+
+/// Text of a string literal or quoted name, without quotes or the N prefix
+fn unquote(text: &str) -> String {
+    let text = text
+        .strip_prefix(['N', 'n'])
+        .filter(|rest| rest.starts_with('\''))
+        .unwrap_or(text);
+    let close = match text.chars().next() {
+        Some('[') => ']',
+        Some(c @ ('\'' | '"')) => c,
+        _ => return text.to_string(),
+    };
+    let inner = &text[1..];
+    let inner = inner.strip_suffix(close).unwrap_or(inner);
+    inner.replace(&format!("{}{}", close, close), &close.to_string())
+}
+
+fn is_internal_name(name: &str) -> bool {
+    INTERNAL_NAME_PREFIXES.iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Name and raw value range of every attribute, in document order
+fn attribute_ranges(xml: &str) -> Vec<(&str, usize, usize)> {
+    let bytes = xml.as_bytes();
+    let mut ranges = Vec::new();
+    for (i, _) in xml.match_indices("=\"") {
+        let name_start = xml[..i]
+            .rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(0, |p| p + 1);
+        if name_start == i || name_start == 0 || !bytes[name_start - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let start = i + 2;
+        if let Some(len) = xml[start..].find('"') {
+            ranges.push((&xml[name_start..i], start, start + len));
+        }
+    }
+    ranges
+}
+
+/// Name and unescaped value of every attribute
+fn attributes(xml: &str) -> Vec<(&str, String)> {
+    attribute_ranges(xml)
+        .into_iter()
+        .map(|(name, start, end)| {
+            let raw = &xml[start..end];
+            (
+                name,
+                unescape(raw).map_or(raw.to_string(), |v| v.into_owned()),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"<StmtSimple StatementText="SELECT * FROM dbo.Customers WHERE Email = &apos;jane@example.com&apos; AND Region = @p1">
+        <QueryPlan>
+          <RelOp NodeId="0" PhysicalOp="Index Seek" EstimateRows="1">
+            <RunTimeInformation>
+              <RunTimeCountersPerThread Thread="0" ActualRows="42" ActualExecutions="1" />
+            </RunTimeInformation>
+            <IndexScan>
+              <Object Database="[Sales]" Schema="[dbo]" Table="[Customers]" Index="[IX_Customers_Email]" />
+              <Predicate>
+                <ScalarOperator ScalarString="[Sales].[dbo].[Customers].[Email]=N'jane@example.com'">
+                  <Identifier><ColumnReference Database="[Sales]" Schema="[dbo]" Table="[Customers]" Column="Email" /></Identifier>
+                </ScalarOperator>
+              </Predicate>
+            </IndexScan>
+          </RelOp>
+          <ParameterList>
+            <ColumnReference Column="@p1" ParameterCompiledValue="N'EMEA'" ParameterRuntimeValue="N'APAC'" />
+          </ParameterList>
+        </QueryPlan>
+      </StmtSimple>"#;
+
+    #[test]
+    fn test_literals_share_placeholders() {
+        let options = RedactOptions::default();
+        assert_eq!(
+            redact_sql(
+                "SELECT TOP 10 * FROM t WHERE a = 'x' OR b = N'x' -- jane\nAND c IN (10, 2.5)",
+                options
+            ),
+            "SELECT TOP ?1 * FROM t WHERE a = ?2 OR b = ?2 --\nAND c IN (?1, ?3)"
+        );
+
+        let redacted = redact_plan_xml(PLAN, options);
+        assert!(!redacted.contains("jane"));
+        assert!(redacted.contains("Email = ?1 AND Region = @p1"));
+        assert!(redacted.contains("[Email]=?1"));
+        assert!(redacted
+            .contains(r#"Column="@p1" ParameterCompiledValue="?2" ParameterRuntimeValue="?3""#));
+        assert!(redacted.contains(r#"ActualRows="42""#));
+        assert!(redacted.contains(r#"Table="[Customers]""#));
+    }
+
+    #[test]
+    fn test_object_names_are_replaced() {
+        let options = RedactOptions { object_names: true };
+        let redacted = redact_plan_xml(PLAN, options);
+        assert!(!redacted.contains("Customers"));
+        assert!(!redacted.contains("Email"));
+        assert!(redacted.contains(
+            r#"Database="[Database1]" Schema="[Schema1]" Table="[Table1]" Index="[Index1]""#
+        ));
+        assert!(redacted.contains("FROM Schema1.Table1 WHERE Column1 = ?1"));
+        assert!(redacted.contains(r#"Column="@p1""#));
+        assert!(redacted.contains(r#"ActualRows="42""#));
+
+        let mut redactor = Redactor::new(options);
+        assert_eq!(
+            redactor.sql("SELECT o.Amount, Expr1002 FROM [Sales].dbo.Orders o WHERE o.Id = @id"),
+            "SELECT Object1.Object2, Expr1002 FROM [Object3].Object4.Object5 Object1 WHERE Object1.Object6 = @id"
+        );
+        assert_eq!(redactor.name("Table", "[Orders]"), "[Object5]");
+    }
+}
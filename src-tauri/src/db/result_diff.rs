@@ -0,0 +1,296 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde_json::Value;
+
+use super::types::{ChangedRow, ResultDiff, ResultSet};
+
+// "Did my rewrite change the results?": rows of two result sets paired by
+// key columns, or compared whole when no key is chosen. Rows are bucketed by
+// a hash of their key and checked value by value within a bucket, so large
+// results diff in one pass over each side.
+
+/// Rows listed per kind of difference; the counts cover all of them
+pub const MAX_LISTED_ROWS: usize = 500;
+
+/// Positions of one column in the left and right result sets
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColumnPair {
+    left: usize,
+    right: usize,
+}
+
+fn find_column(result: &ResultSet, name: &str) -> Option<usize> {
+    result
+        .columns
+        .iter()
+        .position(|c| c.name.eq_ignore_ascii_case(name))
+}
+
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    match value {
+        Value::Null => 0u8.hash(hasher),
+        Value::Bool(b) => {
+            1u8.hash(hasher);
+            b.hash(hasher);
+        }
+        Value::String(s) => {
+            2u8.hash(hasher);
+            s.hash(hasher);
+        }
+        other => {
+            3u8.hash(hasher);
+            other.to_string().hash(hasher);
+        }
+    }
+}
+
+fn hash_key(row: &[Value], columns: impl Iterator<Item = usize>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for i in columns {
+        hash_value(&row[i], &mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Values of the compared columns, in the left result's column order
+fn project(row: &[Value], columns: &[ColumnPair], left: bool) -> Vec<Value> {
+    columns
+        .iter()
+        .map(|c| row[if left { c.left } else { c.right }].clone())
+        .collect()
+}
+
+/// Row-level differences between two result sets. Columns are matched by
+/// name; rows are paired by `key_columns`, in order when a key repeats.
+/// With no key columns a row only matches an identical one.
+pub fn diff(
+    left: &ResultSet,
+    right: &ResultSet,
+    key_columns: &[String],
+) -> Result<ResultDiff, String> {
+    let mut columns = Vec::new();
+    let mut pairs = Vec::new();
+    let mut left_only_columns = Vec::new();
+    for (i, column) in left.columns.iter().enumerate() {
+        match find_column(right, &column.name) {
+            Some(r) => {
+                columns.push(column.name.clone());
+                pairs.push(ColumnPair { left: i, right: r });
+            }
+            None => left_only_columns.push(column.name.clone()),
+        }
+    }
+    let right_only_columns: Vec<String> = right
+        .columns
+        .iter()
+        .filter(|c| find_column(left, &c.name).is_none())
+        .map(|c| c.name.clone())
+        .collect();
+    if pairs.is_empty() {
+        return Err("The two results have no column names in common".into());
+    }
+
+    let keys: Vec<ColumnPair> = if key_columns.is_empty() {
+        pairs.clone()
+    } else {
+        key_columns
+            .iter()
+            .map(|name| {
+                columns
+                    .iter()
+                    .position(|c| c.eq_ignore_ascii_case(name))
+                    .map(|i| pairs[i])
+                    .ok_or_else(|| format!("Key column '{}' is not in both results", name))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let same_key = |l: &[Value], r: &[Value]| keys.iter().all(|k| l[k.left] == r[k.right]);
+
+    // Right rows by key hash; a bucket may hold several keys on a collision
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut duplicate_keys = 0;
+    for (i, row) in right.rows.iter().enumerate() {
+        let bucket = buckets
+            .entry(hash_key(row, keys.iter().map(|k| k.right)))
+            .or_default();
+        if bucket
+            .iter()
+            .any(|&j| keys.iter().all(|k| right.rows[j][k.right] == row[k.right]))
+        {
+            duplicate_keys += 1;
+        }
+        bucket.push(i);
+    }
+
+    let mut result = ResultDiff {
+        columns,
+        key_columns: keys
+            .iter()
+            .map(|k| left.columns[k.left].name.clone())
+            .collect(),
+        left_only_columns,
+        right_only_columns,
+        left_row_count: left.rows.len(),
+        right_row_count: right.rows.len(),
+        unchanged: 0,
+        changed: 0,
+        removed: 0,
+        added: 0,
+        duplicate_keys: 0,
+        changed_rows: Vec::new(),
+        removed_rows: Vec::new(),
+        added_rows: Vec::new(),
+    };
+
+    let mut matched = vec![false; right.rows.len()];
+    let mut left_seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, row) in left.rows.iter().enumerate() {
+        let hash = hash_key(row, keys.iter().map(|k| k.left));
+        let seen = left_seen.entry(hash).or_default();
+        if seen
+            .iter()
+            .any(|&j| keys.iter().all(|k| left.rows[j][k.left] == row[k.left]))
+        {
+            duplicate_keys += 1;
+        }
+        seen.push(i);
+
+        let partner = buckets.get(&hash).and_then(|bucket| {
+            bucket
+                .iter()
+                .copied()
+                .find(|&j| !matched[j] && same_key(row, &right.rows[j]))
+        });
+        let Some(j) = partner else {
+            result.removed += 1;
+            if result.removed_rows.len() < MAX_LISTED_ROWS {
+                result.removed_rows.push(project(row, &pairs, true));
+            }
+            continue;
+        };
+        matched[j] = true;
+
+        let changed_columns: Vec<usize> = pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| row[c.left] != right.rows[j][c.right])
+            .map(|(index, _)| index)
+            .collect();
+        if changed_columns.is_empty() {
+            result.unchanged += 1;
+            continue;
+        }
+        result.changed += 1;
+        if result.changed_rows.len() < MAX_LISTED_ROWS {
+            result.changed_rows.push(ChangedRow {
+                left: project(row, &pairs, true),
+                right: project(&right.rows[j], &pairs, false),
+                changed_columns,
+            });
+        }
+    }
+
+    for (j, row) in right.rows.iter().enumerate() {
+        if matched[j] {
+            continue;
+        }
+        result.added += 1;
+        if result.added_rows.len() < MAX_LISTED_ROWS {
+            result.added_rows.push(project(row, &pairs, false));
+        }
+    }
+    // Whole-row keys repeat for every duplicate row, which is not worth a warning
+    if !key_columns.is_empty() {
+        result.duplicate_keys = duplicate_keys;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::ColumnInfo;
+    use serde_json::json;
+
+    fn result(columns: &[&str], rows: Vec<Vec<Value>>) -> ResultSet {
+        ResultSet {
+            columns: columns
+                .iter()
+                .map(|name| ColumnInfo {
+                    name: name.to_string(),
+                    type_name: "int".into(),
+                    nullable: None,
+                    max_length: None,
+                    precision: None,
+                    scale: None,
+                })
+                .collect(),
+            rows_affected: rows.len() as i64,
+            rows,
+            result_id: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_by_key() {
+        let left = result(
+            &["Id", "Total", "Note"],
+            vec![
+                vec![json!(1), json!(10), json!("a")],
+                vec![json!(2), json!(20), json!("b")],
+                vec![json!(3), json!(30), json!("c")],
+            ],
+        );
+        let right = result(
+            &["id", "total", "Extra"],
+            vec![
+                vec![json!(3), json!(31), json!(0)],
+                vec![json!(1), json!(10), json!(0)],
+                vec![json!(4), json!(40), json!(0)],
+            ],
+        );
+        let diff = diff(&left, &right, &["ID".to_string()]).unwrap();
+
+        assert_eq!(diff.columns, vec!["Id", "Total"]);
+        assert_eq!(diff.key_columns, vec!["Id"]);
+        assert_eq!(diff.left_only_columns, vec!["Note"]);
+        assert_eq!(diff.right_only_columns, vec!["Extra"]);
+        assert_eq!(
+            (diff.unchanged, diff.changed, diff.removed, diff.added),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(diff.changed_rows[0].left, vec![json!(3), json!(30)]);
+        assert_eq!(diff.changed_rows[0].right, vec![json!(3), json!(31)]);
+        assert_eq!(diff.changed_rows[0].changed_columns, vec![1]);
+        assert_eq!(diff.removed_rows, vec![vec![json!(2), json!(20)]]);
+        assert_eq!(diff.added_rows, vec![vec![json!(4), json!(40)]]);
+
+        assert!(super::diff(&left, &right, &["Note".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_diff_whole_rows_and_duplicates() {
+        let left = result(
+            &["a", "b"],
+            vec![
+                vec![json!(1), json!(null)],
+                vec![json!(1), json!(null)],
+                vec![json!(2), json!("x")],
+            ],
+        );
+        let right = result(
+            &["a", "b"],
+            vec![vec![json!(1), json!(null)], vec![json!(2), json!("y")]],
+        );
+
+        let whole = diff(&left, &right, &[]).unwrap();
+        assert_eq!((whole.unchanged, whole.removed, whole.added), (1, 2, 1));
+        assert_eq!(whole.duplicate_keys, 0);
+
+        let keyed = diff(&left, &right, &["a".to_string()]).unwrap();
+        assert_eq!((keyed.unchanged, keyed.changed, keyed.removed), (1, 1, 1));
+        assert_eq!(keyed.duplicate_keys, 1);
+    }
+}
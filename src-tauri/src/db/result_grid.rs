@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::types::{ColumnInfo, FilterOperator, ResultFilter, ResultSet, ResultView, SortKey};
+
+// Copies of recent result sets kept on the Rust side, so the grid can sort
+// and filter large results without holding and reordering every row in the
+// webview. Each cached set remembers its sort and filters; a command changes
+// one of them and the row order is rebuilt from the original rows.
+
+/// Larger result sets are not cached and get no result id
+pub const MAX_CACHED_ROWS: usize = 200_000;
+
+/// Result sets kept; the oldest is dropped first
+const MAX_CACHED_RESULTS: usize = 8;
+
+struct CachedResult {
+    id: String,
+    columns: Vec<ColumnInfo>,
+    rows: Vec<Vec<Value>>,
+    sort: Vec<SortKey>,
+    filters: Vec<ResultFilter>,
+    /// Indexes into `rows`, filtered and in sort order
+    view: Vec<usize>,
+}
+
+impl CachedResult {
+    fn rebuild(&mut self) {
+        let mut view: Vec<usize> = (0..self.rows.len())
+            .filter(|&i| {
+                self.filters
+                    .iter()
+                    .all(|f| matches_filter(self.rows[i].get(f.column), f))
+            })
+            .collect();
+        if !self.sort.is_empty() {
+            // Stable, so equal rows keep their original order
+            view.sort_by(|&a, &b| compare_rows(&self.rows[a], &self.rows[b], &self.sort));
+        }
+        self.view = view;
+    }
+
+    fn check_column(&self, column: usize) -> Result<(), String> {
+        if column < self.columns.len() {
+            Ok(())
+        } else {
+            Err(format!("Result set has no column {}", column))
+        }
+    }
+
+    fn window(&self, offset: usize, limit: Option<usize>) -> ResultView {
+        let end = limit
+            .map(|l| offset.saturating_add(l))
+            .unwrap_or(usize::MAX)
+            .min(self.view.len());
+        let indexes = self.view.get(offset.min(end)..end).unwrap_or_default();
+        ResultView {
+            result_id: self.id.clone(),
+            total_rows: self.rows.len(),
+            matching_rows: self.view.len(),
+            offset,
+            row_indexes: indexes.to_vec(),
+            rows: indexes.iter().map(|&i| self.rows[i].clone()).collect(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ResultCache {
+    results: Mutex<VecDeque<CachedResult>>,
+}
+
+impl ResultCache {
+    /// Keep a copy of `result_set` and give it a result id, unless it is
+    /// over the row limit
+    pub fn insert(&self, result_set: &mut ResultSet) {
+        if result_set.rows.len() > MAX_CACHED_ROWS {
+            return;
+        }
+        let id = Uuid::new_v4().to_string();
+        let mut results = self.results.lock().unwrap();
+        if results.len() >= MAX_CACHED_RESULTS {
+            results.pop_front();
+        }
+        results.push_back(CachedResult {
+            id: id.clone(),
+            columns: result_set.columns.clone(),
+            view: (0..result_set.rows.len()).collect(),
+            rows: result_set.rows.clone(),
+            sort: Vec::new(),
+            filters: Vec::new(),
+        });
+        result_set.result_id = Some(id);
+    }
+
+    /// Replace the sort of a cached result; an empty list restores the
+    /// original order
+    pub fn sort(
+        &self,
+        result_id: &str,
+        keys: Vec<SortKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<ResultView, String> {
+        self.with_result(result_id, |result| {
+            for key in &keys {
+                result.check_column(key.column)?;
+            }
+            // Paging through a sorted result passes the same keys again
+            if result.sort != keys {
+                result.sort = keys;
+                result.rebuild();
+            }
+            Ok(result.window(offset, limit))
+        })
+    }
+
+    /// Replace the filters of a cached result; rows must match all of them
+    pub fn filter(
+        &self,
+        result_id: &str,
+        filters: Vec<ResultFilter>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<ResultView, String> {
+        self.with_result(result_id, |result| {
+            for filter in &filters {
+                result.check_column(filter.column)?;
+            }
+            if result.filters != filters {
+                result.filters = filters;
+                result.rebuild();
+            }
+            Ok(result.window(offset, limit))
+        })
+    }
+
+    /// Columns and rows picked from a cached result, in the order asked
+    /// for. Without `rows`, the rows of the current view in view order;
+    /// without `columns`, every column.
+    pub fn selection(
+        &self,
+        result_id: &str,
+        rows: Option<&[usize]>,
+        columns: Option<&[usize]>,
+    ) -> Result<(Vec<ColumnInfo>, Vec<Vec<Value>>), String> {
+        self.with_result(result_id, |result| {
+            let column_indexes: Vec<usize> = match columns {
+                Some(columns) => columns.to_vec(),
+                None => (0..result.columns.len()).collect(),
+            };
+            for &column in &column_indexes {
+                result.check_column(column)?;
+            }
+            let row_indexes = rows.unwrap_or(&result.view);
+            if let Some(&row) = row_indexes.iter().find(|&&r| r >= result.rows.len()) {
+                return Err(format!("Result set has no row {}", row));
+            }
+            Ok((
+                column_indexes
+                    .iter()
+                    .map(|&c| result.columns[c].clone())
+                    .collect(),
+                row_indexes
+                    .iter()
+                    .map(|&r| {
+                        column_indexes
+                            .iter()
+                            .map(|&c| result.rows[r].get(c).cloned().unwrap_or_default())
+                            .collect()
+                    })
+                    .collect(),
+            ))
+        })
+    }
+
+    fn with_result<T>(
+        &self,
+        result_id: &str,
+        f: impl FnOnce(&mut CachedResult) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut results = self.results.lock().unwrap();
+        let result = results
+            .iter_mut()
+            .find(|r| r.id == result_id)
+            .ok_or("The result set is no longer cached; run the query again")?;
+        f(result)
+    }
+}
+
+fn compare_rows(a: &[Value], b: &[Value], keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let ordering = compare_values(
+            a.get(key.column).unwrap_or(&Value::Null),
+            b.get(key.column).unwrap_or(&Value::Null),
+        );
+        let ordering = if key.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// NULLs first, numbers by value (also when sent as text, e.g. decimals),
+/// then text ignoring case
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => match (as_number(a), as_number(b)) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            _ => {
+                let (x, y) = (display(a), display(b));
+                x.to_lowercase()
+                    .cmp(&y.to_lowercase())
+                    .then_with(|| x.cmp(&y))
+            }
+        },
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn matches_filter(value: Option<&Value>, filter: &ResultFilter) -> bool {
+    let value = value.unwrap_or(&Value::Null);
+    let operand = filter.value.as_deref().unwrap_or_default();
+    match filter.operator {
+        FilterOperator::IsNull => value.is_null(),
+        FilterOperator::IsNotNull => !value.is_null(),
+        _ if value.is_null() => false,
+        FilterOperator::Contains => display(value)
+            .to_lowercase()
+            .contains(&operand.to_lowercase()),
+        FilterOperator::StartsWith => display(value)
+            .to_lowercase()
+            .starts_with(&operand.to_lowercase()),
+        FilterOperator::Equals => equals_ignoring_case(value, operand),
+        FilterOperator::NotEquals => !equals_ignoring_case(value, operand),
+        FilterOperator::GreaterThan => compare_values(value, &operand_value(operand)).is_gt(),
+        FilterOperator::LessThan => compare_values(value, &operand_value(operand)).is_lt(),
+    }
+}
+
+fn equals_ignoring_case(value: &Value, operand: &str) -> bool {
+    match (as_number(value), operand.trim().parse::<f64>()) {
+        (Some(x), Ok(y)) => x == y,
+        _ => display(value).to_lowercase() == operand.to_lowercase(),
+    }
+}
+
+fn operand_value(operand: &str) -> Value {
+    Value::String(operand.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::ColumnInfo;
+    use serde_json::json;
+
+    fn result_set() -> ResultSet {
+        let column = |name: &str, type_name: &str| ColumnInfo {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            nullable: None,
+            max_length: None,
+            precision: None,
+            scale: None,
+        };
+        ResultSet {
+            columns: vec![column("Name", "nvarchar"), column("Total", "decimal")],
+            rows: vec![
+                vec![json!("beta"), json!("10.50")],
+                vec![json!("Alpha"), json!("9.75")],
+                vec![json!(null), json!("100")],
+                vec![json!("alpha"), json!(null)],
+            ],
+            rows_affected: 4,
+            result_id: None,
+        }
+    }
+
+    #[test]
+    fn test_sort_and_filter_compose() {
+        let cache = ResultCache::default();
+        let mut set = result_set();
+        cache.insert(&mut set);
+        let id = set.result_id.unwrap();
+
+        let by_total = vec![SortKey {
+            column: 1,
+            descending: true,
+        }];
+        let view = cache.sort(&id, by_total, 0, None).unwrap();
+        // Numbers sent as text sort by value; NULL sorts first, so last descending
+        assert_eq!(view.row_indexes, vec![2, 0, 1, 3]);
+
+        let filters = vec![ResultFilter {
+            column: 0,
+            operator: FilterOperator::StartsWith,
+            value: Some("AL".to_string()),
+        }];
+        let view = cache.filter(&id, filters, 0, Some(1)).unwrap();
+        assert_eq!((view.total_rows, view.matching_rows), (4, 2));
+        assert_eq!(view.row_indexes, vec![1]);
+        assert_eq!(view.rows, vec![vec![json!("Alpha"), json!("9.75")]]);
+
+        let over = vec![ResultFilter {
+            column: 1,
+            operator: FilterOperator::GreaterThan,
+            value: Some("10".to_string()),
+        }];
+        let view = cache.filter(&id, over, 0, None).unwrap();
+        assert_eq!(view.row_indexes, vec![2, 0]);
+
+        assert!(cache
+            .sort(
+                &id,
+                vec![SortKey {
+                    column: 5,
+                    descending: false
+                }],
+                0,
+                None
+            )
+            .is_err());
+        assert!(cache.sort("gone", Vec::new(), 0, None).is_err());
+    }
+}
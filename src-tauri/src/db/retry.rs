@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use super::types::AppError;
+
+/// Longest wait between two attempts, however many retries are allowed
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The batch was chosen as a deadlock victim (error 1205) and rolled back
+pub fn is_deadlock_victim(error: &AppError) -> bool {
+    error.code() == Some(1205)
+}
+
+/// Wait before retry number `attempt` (1-based): the base delay, doubled for
+/// each further retry
+pub fn retry_delay(backoff_ms: u64, attempt: u32) -> Duration {
+    let factor = 1u64 << attempt.saturating_sub(1).min(16);
+    Duration::from_millis(backoff_ms.saturating_mul(factor)).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::types::EngineError;
+
+    #[test]
+    fn test_deadlock_detection_and_backoff() {
+        let error = |code| AppError::QueryError {
+            code: Some(code),
+            line: Some(3),
+            message: "Transaction was deadlocked".into(),
+            engine: Some(Box::new(EngineError {
+                number: code,
+                severity: 13,
+                state: 51,
+                line: 3,
+                procedure: None,
+                server: None,
+                explanation: None,
+            })),
+        };
+        assert!(is_deadlock_victim(&error(1205)));
+        assert!(!is_deadlock_victim(&error(1222)));
+        assert!(!is_deadlock_victim(&AppError::new("code: 1205,")));
+
+        assert_eq!(retry_delay(200, 1), Duration::from_millis(200));
+        assert_eq!(retry_delay(200, 3), Duration::from_millis(800));
+        assert_eq!(retry_delay(200, 40), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,233 @@
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_literal, quote_object_name};
+use super::rows::get_i64;
+use super::tsql_lexer::{code_tokens, TokenKind};
+use super::types::RowCountEstimate;
+
+/// Results at least this large get a warning before they are fetched
+const LARGE_RESULT_ROWS: i64 = 100_000;
+
+/// Words after the table that make the query more than a single-table read
+const NOT_SIMPLE: &[&str] = &[
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "OUTER",
+    "APPLY",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "PIVOT",
+    "UNPIVOT",
+];
+
+/// What estimate_rowcount counts: one table, and what the query does with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateTarget {
+    /// Quoted object name
+    pub table: String,
+    pub has_filter: bool,
+    pub has_top: bool,
+}
+
+/// Accepts a bare table name ("dbo.Orders") or a single-table SELECT.
+/// Joins, subqueries in FROM and multi-statement scripts are not estimated.
+pub fn estimate_target(input: &str) -> Result<EstimateTarget, String> {
+    let tokens = code_tokens(input);
+    let word = |i: usize| -> Option<String> {
+        tokens
+            .get(i)
+            .filter(|t| t.kind == TokenKind::Word)
+            .map(|t| input[t.start..t.end].to_ascii_uppercase())
+    };
+    let not_simple = || "Only single-table SELECT statements can be estimated".to_string();
+
+    if word(0).as_deref() != Some("SELECT") {
+        return Ok(EstimateTarget {
+            table: quote_object_name(input.trim().trim_end_matches(';'))?,
+            has_filter: false,
+            has_top: false,
+        });
+    }
+
+    let mut depth = 0usize;
+    let mut from = None;
+    let mut has_top = false;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth = depth.saturating_sub(1),
+            TokenKind::Semicolon if i + 1 < tokens.len() => return Err(not_simple()),
+            TokenKind::Word if depth == 0 => match word(i).as_deref() {
+                Some("TOP") => has_top = true,
+                Some("FROM") if from.is_none() => from = Some(i),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    let from = from.ok_or("The query has no FROM clause")?;
+
+    // Name parts and dots right after FROM
+    let mut end = from + 1;
+    while tokens.get(end).is_some_and(|t| {
+        matches!(
+            t.kind,
+            TokenKind::Word | TokenKind::QuotedName | TokenKind::Dot
+        )
+    }) {
+        let is_part = tokens[end].kind != TokenKind::Dot;
+        let follows_part = end > from + 1 && tokens[end - 1].kind != TokenKind::Dot;
+        if is_part && follows_part {
+            break; // alias
+        }
+        end += 1;
+    }
+    if end == from + 1 {
+        return Err(not_simple());
+    }
+    let name = &input[tokens[from + 1].start..tokens[end - 1].end];
+
+    let mut depth = 0usize;
+    let mut has_filter = false;
+    for (i, token) in tokens.iter().enumerate().skip(end) {
+        match token.kind {
+            TokenKind::OpenParen => depth += 1,
+            TokenKind::CloseParen => depth = depth.saturating_sub(1),
+            TokenKind::Comma if depth == 0 => return Err(not_simple()),
+            TokenKind::Word if depth == 0 => {
+                let w = word(i).unwrap_or_default();
+                if NOT_SIMPLE.contains(&w.as_str()) {
+                    return Err(not_simple());
+                }
+                if w == "WHERE" {
+                    has_filter = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(EstimateTarget {
+        table: quote_object_name(name)?,
+        has_filter,
+        has_top,
+    })
+}
+
+/// Row count of the table from partition metadata, without scanning it
+pub async fn estimate_rowcount(
+    client: &mut TiberiusClient,
+    input: &str,
+) -> Result<RowCountEstimate, String> {
+    let target = estimate_target(input)?;
+    let object = quote_literal(&target.table);
+
+    // dm_db_partition_stats needs VIEW DATABASE STATE; sys.partitions does not
+    let mut source = "sys.dm_db_partition_stats";
+    let mut rows = count_rows(
+        client,
+        format!(
+            "SELECT SUM(row_count) FROM sys.dm_db_partition_stats \
+             WHERE object_id = OBJECT_ID({}) AND index_id IN (0, 1)",
+            object
+        ),
+    )
+    .await;
+    if rows.is_err() {
+        source = "sys.partitions";
+        rows = count_rows(
+            client,
+            format!(
+                "SELECT SUM(rows) FROM sys.partitions \
+                 WHERE object_id = OBJECT_ID({}) AND index_id IN (0, 1)",
+                object
+            ),
+        )
+        .await;
+    }
+    let approximate_rows = rows?.ok_or_else(|| format!("Table not found: {}", target.table))?;
+
+    let warning = (!target.has_top && approximate_rows >= LARGE_RESULT_ROWS).then(|| {
+        format!(
+            "This will return {}{} rows, add a TOP?",
+            if target.has_filter { "up to ~" } else { "~" },
+            format_count(approximate_rows)
+        )
+    });
+    Ok(RowCountEstimate {
+        table: target.table,
+        approximate_rows,
+        source: source.to_string(),
+        filtered: target.has_filter,
+        warning,
+    })
+}
+
+async fn count_rows(client: &mut TiberiusClient, sql: String) -> Result<Option<i64>, String> {
+    let row = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_row()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row.and_then(|r| get_i64(&r, 0)))
+}
+
+/// "950", "12K", "4.2M"
+fn format_count(rows: i64) -> String {
+    match rows {
+        r if r >= 1_000_000 => format!("{:.1}M", r as f64 / 1_000_000.0),
+        r if r >= 1_000 => format!("{:.0}K", r as f64 / 1_000.0),
+        r => r.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(table: &str, has_filter: bool, has_top: bool) -> EstimateTarget {
+        EstimateTarget {
+            table: table.to_string(),
+            has_filter,
+            has_top,
+        }
+    }
+
+    #[test]
+    fn test_estimate_target() {
+        assert_eq!(
+            estimate_target("dbo.Orders").unwrap(),
+            target("[dbo].[Orders]", false, false)
+        );
+        assert_eq!(
+            estimate_target("SELECT * FROM [Sales].[Order Lines] ol").unwrap(),
+            target("[Sales].[Order Lines]", false, false)
+        );
+        assert_eq!(
+            estimate_target("select top 10 id from Orders where id in (select 1) order by id;")
+                .unwrap(),
+            target("[Orders]", true, true)
+        );
+    }
+
+    #[test]
+    fn test_joins_are_not_estimated() {
+        assert!(estimate_target("SELECT * FROM a JOIN b ON a.id = b.id").is_err());
+        assert!(estimate_target("SELECT * FROM a, b").is_err());
+        assert!(estimate_target("SELECT * FROM (SELECT 1 x) t").is_err());
+        assert!(estimate_target("SELECT 1").is_err());
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(950), "950");
+        assert_eq!(format_count(12_400), "12K");
+        assert_eq!(format_count(4_200_000), "4.2M");
+    }
+}
@@ -0,0 +1,66 @@
+use tiberius::Row;
+
+// Helpers for reading catalog/DMV rows whose column types vary between
+// server versions (e.g. COUNT(*) is int, but SUM(...) over bigint is bigint).
+
+pub fn get_string(row: &Row, idx: usize) -> Option<String> {
+    row.try_get::<&str, _>(idx)
+        .ok()
+        .flatten()
+        .map(|v| v.to_string())
+}
+
+pub fn get_i64(row: &Row, idx: usize) -> Option<i64> {
+    row.try_get::<i64, _>(idx)
+        .ok()
+        .flatten()
+        .or_else(|| row.try_get::<i32, _>(idx).ok().flatten().map(i64::from))
+        .or_else(|| row.try_get::<i16, _>(idx).ok().flatten().map(i64::from))
+        .or_else(|| row.try_get::<u8, _>(idx).ok().flatten().map(i64::from))
+}
+
+pub fn get_datetime(row: &Row, idx: usize) -> Option<chrono::NaiveDateTime> {
+    row.try_get::<chrono::NaiveDateTime, _>(idx).ok().flatten()
+}
+
+pub fn get_f64(row: &Row, idx: usize) -> Option<f64> {
+    row.try_get::<f64, _>(idx)
+        .ok()
+        .flatten()
+        .or_else(|| row.try_get::<f32, _>(idx).ok().flatten().map(f64::from))
+        .or_else(|| get_i64(row, idx).map(|v| v as f64))
+}
+
+pub fn get_bytes(row: &Row, idx: usize) -> Option<Vec<u8>> {
+    row.try_get::<&[u8], _>(idx)
+        .ok()
+        .flatten()
+        .map(|v| v.to_vec())
+}
+
+/// Any scalar value as text, for columns whose type follows user data
+/// (e.g. a histogram key in DBCC SHOW_STATISTICS output)
+pub fn get_display_string(row: &Row, idx: usize) -> Option<String> {
+    get_string(row, idx)
+        .or_else(|| get_i64(row, idx).map(|v| v.to_string()))
+        .or_else(|| get_f64(row, idx).map(|v| v.to_string()))
+        .or_else(|| {
+            row.try_get::<tiberius::numeric::Numeric, _>(idx)
+                .ok()
+                .flatten()
+                .map(|v| v.to_string())
+        })
+        .or_else(|| get_datetime(row, idx).map(|v| v.format("%Y-%m-%dT%H:%M:%S%.f").to_string()))
+        .or_else(|| {
+            row.try_get::<chrono::NaiveDate, _>(idx)
+                .ok()
+                .flatten()
+                .map(|v| v.format("%Y-%m-%d").to_string())
+        })
+        .or_else(|| {
+            row.try_get::<uuid::Uuid, _>(idx)
+                .ok()
+                .flatten()
+                .map(|v| v.to_string())
+        })
+}
@@ -0,0 +1,151 @@
+// Statement classifier for read-only connections. It walks the batch as
+// T-SQL tokens (skipping comments, string literals and quoted identifiers) so
+// that keywords are recognised anywhere in a statement — a CTE followed by
+// DELETE, SELECT ... INTO, or a second statement after a SELECT — not just
+// as the first word.
+
+use super::tsql_lexer::{code_tokens, TokenKind};
+
+/// Keywords that start a statement able to change data, schema, or server state
+const MODIFYING_KEYWORDS: &[&str] = &[
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "MERGE",
+    "CREATE",
+    "ALTER",
+    "DROP",
+    "TRUNCATE",
+    "GRANT",
+    "REVOKE",
+    "DENY",
+    "EXEC",
+    "EXECUTE",
+    "BULK",
+    "BACKUP",
+    "RESTORE",
+    "DBCC",
+    "KILL",
+    "SHUTDOWN",
+    "RECONFIGURE",
+    "WRITETEXT",
+    "UPDATETEXT",
+    "ENABLE",
+    "DISABLE",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementClass {
+    ReadOnly,
+    /// Contains a modifying statement; holds the keyword that triggered it
+    Modifying(String),
+}
+
+/// Classify a whole batch; it is read-only only if no statement can modify anything.
+pub fn classify_batch(sql: &str) -> StatementClass {
+    let words = keywords(sql);
+    let mut previous: Option<&str> = None;
+
+    for (i, word) in words.iter().enumerate() {
+        let upper = word.to_ascii_uppercase();
+
+        if MODIFYING_KEYWORDS.contains(&upper.as_str()) {
+            // ENABLE/DISABLE only modify as ENABLE TRIGGER / DISABLE TRIGGER
+            if (upper == "ENABLE" || upper == "DISABLE")
+                && !words
+                    .get(i + 1)
+                    .map(|w| w.eq_ignore_ascii_case("TRIGGER"))
+                    .unwrap_or(false)
+            {
+                previous = Some(word);
+                continue;
+            }
+            return StatementClass::Modifying(upper);
+        }
+
+        // SELECT ... INTO creates a table; INSERT INTO was caught above
+        if upper == "INTO"
+            && !previous
+                .map(|p| p.eq_ignore_ascii_case("INSERT"))
+                .unwrap_or(false)
+        {
+            return StatementClass::Modifying("SELECT INTO".into());
+        }
+
+        previous = Some(word);
+    }
+
+    StatementClass::ReadOnly
+}
+
+/// Bare words of the batch, with comments, literals and quoted identifiers removed
+fn keywords(sql: &str) -> Vec<&str> {
+    let tokens = code_tokens(sql);
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, token)| {
+            let text = token.text(sql);
+            // Variables and temp-table names are never keywords, and identifiers
+            // qualified with a dot (dbo.Update) are names
+            token.kind == TokenKind::Word
+                && !text.starts_with('@')
+                && !text.starts_with('#')
+                && (*i == 0 || tokens[i - 1].kind != TokenKind::Dot)
+        })
+        .map(|(_, token)| token.text(sql))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifying(sql: &str) -> bool {
+        matches!(classify_batch(sql), StatementClass::Modifying(_))
+    }
+
+    #[test]
+    fn test_plain_selects_are_read_only() {
+        assert_eq!(
+            classify_batch("SELECT * FROM Orders"),
+            StatementClass::ReadOnly
+        );
+        assert!(!modifying("WITH x AS (SELECT 1 AS a) SELECT a FROM x"));
+        assert!(!modifying(
+            "SET NOCOUNT ON; SELECT TOP 10 * FROM dbo.Orders ORDER BY Id DESC"
+        ));
+    }
+
+    #[test]
+    fn test_keywords_in_literals_and_comments_are_ignored() {
+        assert!(!modifying(
+            "SELECT 'DELETE FROM x' AS txt -- UPDATE y\nFROM t"
+        ));
+        assert!(!modifying(
+            "SELECT [Update], \"Delete\" FROM t /* DROP TABLE t */"
+        ));
+        assert!(!modifying("SELECT 'it''s; DROP TABLE t' FROM t"));
+        assert!(!modifying("SELECT @update FROM #delete"));
+    }
+
+    #[test]
+    fn test_modifying_statements_anywhere_in_batch() {
+        assert!(modifying("UPDATE Orders SET Status = 1"));
+        assert!(modifying("SELECT 1; DELETE FROM Orders"));
+        assert!(modifying(
+            "WITH old AS (SELECT * FROM Orders) DELETE FROM old"
+        ));
+        assert!(modifying("  /* leading */ insert into t values (1)"));
+        assert!(modifying("EXEC dbo.DoStuff"));
+        assert!(modifying("DISABLE TRIGGER trg ON t"));
+    }
+
+    #[test]
+    fn test_select_into_is_modifying() {
+        assert_eq!(
+            classify_batch("SELECT * INTO dbo.Copy FROM dbo.Orders"),
+            StatementClass::Modifying("SELECT INTO".into())
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use uuid::Uuid;
+
+use super::audit_log::AuditEvent;
+use super::cert_pinning;
+use super::commands::{audit, password_key, record_plan, record_query};
+use super::connection::{AppState, ConnectionTarget, DbConnection};
+use super::encryption;
+use super::exec_context;
+use super::plan_writer::PlanHistoryWriter;
+use super::store;
+use super::types::{
+    AppError, AuditAction, ConnectionConfig, ExecutionContext, PlanHistoryEntry, PlanType,
+    QueryHistoryEntry, QueryResult, RetryPolicy, ScheduledQuery, ScheduledRunEvent,
+};
+use crate::settings;
+use crate::settings::profile::Capability;
+
+// "Watch this query overnight": each enabled schedule has a task that runs
+// its query on a connection of its own every interval, so the user's session
+// is never touched. Runs land in query and plan history tagged with the
+// schedule, where the usual regression check compares each plan with the
+// previous one.
+
+/// Shortest interval between runs, to keep schedules from loading the server
+pub const MIN_INTERVAL_MINUTES: u32 = 5;
+
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    /// (Re)start the schedule's task, or stop it when it is disabled
+    pub fn start(&self, app: &AppHandle, schedule: &ScheduledQuery) {
+        self.stop(&schedule.id);
+        if !schedule.enabled {
+            return;
+        }
+        let app = app.clone();
+        let id = schedule.id.clone();
+        let interval = Duration::from_secs(u64::from(schedule.interval_minutes) * 60);
+        let task = tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = run(&app, &id).await {
+                    tracing::warn!(schedule = %id, error = %e, "Scheduled query could not run");
+                }
+            }
+        });
+        self.tasks.lock().unwrap().insert(schedule.id.clone(), task);
+    }
+
+    pub fn stop(&self, id: &str) {
+        if let Some(task) = self.tasks.lock().unwrap().remove(id) {
+            task.abort();
+        }
+    }
+}
+
+/// Start the enabled schedules when the app starts
+pub fn spawn(app: &AppHandle) {
+    let scheduler = app.state::<Scheduler>();
+    match store::get_scheduled_queries(app) {
+        Ok(schedules) => {
+            for schedule in &schedules {
+                scheduler.start(app, schedule);
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "Failed to load scheduled queries"),
+    }
+}
+
+/// Run a schedule once and record the outcome. Errors of the query itself
+/// are part of the event; Err means the run could not be recorded.
+pub async fn run(app: &AppHandle, id: &str) -> Result<ScheduledRunEvent, String> {
+    let schedule = store::get_scheduled_queries(app)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or("Scheduled query not found")?;
+    let connection = store::get_connections(app)?
+        .into_iter()
+        .find(|c| c.id == schedule.connection_id);
+
+    let executed_at = Utc::now();
+    let outcome = match &connection {
+        Some(connection) => execute(app, connection, &schedule).await,
+        None => Err("The schedule's saved connection no longer exists".to_string()),
+    };
+    let error = outcome.as_ref().err().cloned();
+
+    let query_id = Uuid::new_v4().to_string();
+    record_query(
+        app,
+        QueryHistoryEntry {
+            id: query_id.clone(),
+            sql: schedule.sql.clone(),
+            connection_id: schedule.connection_id.clone(),
+            connection_name: connection
+                .as_ref()
+                .map_or(String::new(), |c| c.name.clone()),
+            executed_at,
+            duration_ms: outcome.as_ref().map_or(0, |(result, _)| result.duration_ms),
+            success: outcome.is_ok(),
+            error: error.clone(),
+            sql_hash: None,
+            fingerprint: None,
+            pinned: false,
+            label: None,
+            scheduled_query_id: Some(schedule.id.clone()),
+        },
+    )?;
+
+    let mut plan_id = None;
+    let mut regression = None;
+    if let Ok((
+        QueryResult {
+            plan_xml: Some(plan_xml),
+            ..
+        },
+        context,
+    )) = outcome
+    {
+        let id = Uuid::new_v4().to_string();
+        let entry = PlanHistoryEntry {
+            id: id.clone(),
+            query_id: query_id.clone(),
+            plan_xml,
+            plan_type: format!("{:?}", schedule.plan_type),
+            executed_at,
+            connection_id: schedule.connection_id.clone(),
+            sql_preview: schedule.sql.chars().take(100).collect(),
+            sql_hash: None,
+            fingerprint: None,
+            context,
+            regression: None,
+            plan_hash: None,
+            scheduled_query_id: Some(schedule.id.clone()),
+        };
+        regression = record_plan(app, &app.state::<PlanHistoryWriter>(), entry).await?;
+        plan_id = Some(id);
+    }
+
+    // Re-read: the schedule may have been edited while it ran
+    let mut schedules = store::get_scheduled_queries(app)?;
+    if let Some(stored) = schedules.iter_mut().find(|s| s.id == schedule.id) {
+        stored.last_run_at = Some(executed_at);
+        stored.last_error = error.clone();
+        store::save_scheduled_queries(app, &schedules)?;
+    }
+
+    let event = ScheduledRunEvent {
+        scheduled_query_id: schedule.id,
+        query_id,
+        plan_id,
+        error,
+        regression,
+    };
+    let _ = app.emit("scheduled-query-run", &event);
+    Ok(event)
+}
+
+/// Connect, run the query with its plan and capture the environment. The
+/// connection is closed when it is dropped.
+async fn execute(
+    app: &AppHandle,
+    connection: &ConnectionConfig,
+    schedule: &ScheduledQuery,
+) -> Result<(QueryResult, Option<ExecutionContext>), String> {
+    let key = password_key(app, &app.state::<AppState>()).await?;
+    let password = encryption::decrypt_password_with(&key, &connection.encrypted_password)?;
+    let target = ConnectionTarget::from_saved(connection, password);
+    let mut conn = DbConnection::connect(target).await?;
+    cert_pinning::pin_first_use(app, connection, conn.server_certificate())?;
+    conn.read_only = connection.read_only;
+    conn.saved_connection_id = Some(connection.id.clone());
+    conn.profile_read_only.store(
+        !settings::load(app)?.profile.allows(Capability::ModifyData),
+        Ordering::Relaxed,
+    );
+
+    let started = std::time::Instant::now();
+    let result = conn
+        .execute_query_with_retry(&schedule.sql, &schedule.plan_type, &RetryPolicy::default())
+        .await;
+    audit(
+        app,
+        &conn,
+        AuditEvent {
+            action: AuditAction::Execute,
+            statement: Some(&schedule.sql),
+            error: result.as_ref().err().map(AppError::message),
+            duration_ms: Some(started.elapsed().as_millis() as u64),
+        },
+    );
+    let result = result?;
+
+    let mut client = conn.client.lock().await;
+    let context = exec_context::capture(&mut client)
+        .await
+        .map_err(|e| tracing::warn!(error = %e, "Failed to capture execution context"))
+        .ok();
+    Ok((result, context))
+}
+
+/// Check a schedule before it is saved
+pub fn validate(schedule: &ScheduledQuery) -> Result<(), String> {
+    if schedule.name.trim().is_empty() {
+        return Err("Schedule name is required".into());
+    }
+    if schedule.sql.trim().is_empty() {
+        return Err("The scheduled query is empty".into());
+    }
+    if matches!(schedule.plan_type, PlanType::None) {
+        return Err("Scheduled queries capture a plan: choose Estimated or Actual".into());
+    }
+    if schedule.interval_minutes < MIN_INTERVAL_MINUTES {
+        return Err(format!(
+            "Runs must be at least {} minutes apart",
+            MIN_INTERVAL_MINUTES
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedules_need_a_plan_and_a_sane_interval() {
+        let mut schedule = ScheduledQuery {
+            id: "s1".to_string(),
+            name: "Nightly orders report".to_string(),
+            sql: "SELECT * FROM dbo.Orders WHERE OrderDate > DATEADD(day, -1, GETDATE())"
+                .to_string(),
+            connection_id: "c1".to_string(),
+            plan_type: PlanType::Actual,
+            interval_minutes: 60,
+            enabled: true,
+            created_at: Utc::now(),
+            last_run_at: None,
+            last_error: None,
+        };
+        assert!(validate(&schedule).is_ok());
+
+        schedule.interval_minutes = 1;
+        assert_eq!(
+            validate(&schedule),
+            Err("Runs must be at least 5 minutes apart".to_string())
+        );
+
+        schedule.interval_minutes = 60;
+        schedule.plan_type = PlanType::None;
+        assert!(validate(&schedule).is_err());
+    }
+}
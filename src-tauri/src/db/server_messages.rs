@@ -0,0 +1,433 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Subscriber;
+use tracing::{Dispatch, Event, Metadata};
+
+// tiberius 0.12 does not expose INFO and DONE tokens through QueryStream; it
+// only reports them as tracing events while the stream is polled. The query
+// future is run with `MessageCapture` as its subscriber so those events are
+// collected in order: PRINT / RAISERROR (severity < 10) text as-is, and DONE
+// tokens carrying a row count as "(n rows affected)". When every DONE token
+// was read is kept as well, for per-statement timings. ERROR tokens are kept
+// separately: tiberius only returns the first one of a batch as the error.
+// Every other event and span is passed on to the application's subscriber,
+// so logging keeps working inside a captured query. Token events are not:
+// they carry PRINT text and other query output. Row counts are read from the
+// text tiberius logs for DONE tokens, which is not a stable interface: the
+// version is pinned exactly in Cargo.toml, and a test feeds real tokens
+// through tiberius so an upgrade that changes the text fails it.
+
+const TOKEN_TARGET: &str = "tiberius::tds::stream::token";
+
+/// Environment changes are logged at the same level as INFO tokens;
+/// the server sends its own message for the ones users care about.
+const ENV_CHANGE_PREFIXES: &[&str] = &[
+    "Database change from",
+    "Packet size change from",
+    "SQL collation change",
+    "Begin transaction",
+    "Commit transaction",
+    "Rollback transaction",
+    "Defect transaction",
+    "Server requested routing",
+    "Fallback mirror server",
+    "Ignored env change",
+];
+
+/// An ERROR token as logged by tiberius; the line number is not included
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerError {
+    pub code: u32,
+    pub message: String,
+}
+
+#[derive(Clone)]
+pub struct MessageCapture {
+    messages: Arc<Mutex<Vec<String>>>,
+    errors: Arc<Mutex<Vec<ServerError>>>,
+    /// Counts of DONE tokens not yet taken, in arrival order
+    row_counts: Arc<Mutex<Vec<u64>>>,
+    /// When each DONE token was read, with or without a row count
+    done_at: Arc<Mutex<Vec<Instant>>>,
+    /// Subscriber that was current when the capture was created
+    forward: Dispatch,
+}
+
+impl Default for MessageCapture {
+    fn default() -> Self {
+        Self {
+            messages: Default::default(),
+            errors: Default::default(),
+            row_counts: Default::default(),
+            done_at: Default::default(),
+            forward: tracing::dispatcher::get_default(Dispatch::clone),
+        }
+    }
+}
+
+impl MessageCapture {
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.messages.lock().unwrap())
+    }
+
+    pub fn take_errors(&self) -> Vec<ServerError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    /// Row counts reported since the last call. A DONE token arrives after
+    /// the rows of its statement, so it shows up here once the next item of
+    /// the stream has been read.
+    pub fn take_row_counts(&self) -> Vec<u64> {
+        std::mem::take(&mut *self.row_counts.lock().unwrap())
+    }
+
+    pub fn take_done_times(&self) -> Vec<Instant> {
+        std::mem::take(&mut *self.done_at.lock().unwrap())
+    }
+}
+
+/// Turn one tiberius token event into the line SSMS would show, if any
+fn message_for_event(level: &tracing::Level, text: &str) -> Option<String> {
+    if *level == tracing::Level::INFO {
+        if ENV_CHANGE_PREFIXES.iter().any(|p| text.starts_with(p)) {
+            return None;
+        }
+        return Some(text.to_string());
+    }
+    rows_affected(text).map(|rows| {
+        if rows == 1 {
+            "(1 row affected)".to_string()
+        } else {
+            format!("({} rows affected)", rows)
+        }
+    })
+}
+
+/// Row count of a DONE token, only when the server flagged it as valid (NOCOUNT OFF).
+/// Formatted by tiberius as "Done with status BitFlags<DoneStatus>(.., More | Count) (3 rows left)".
+fn rows_affected(text: &str) -> Option<u64> {
+    let status = text.strip_prefix("Done with status ")?;
+    let flags_end = status.find(')')?;
+    if !status[..flags_end].contains("Count") {
+        return None;
+    }
+    let count = status[flags_end + 1..]
+        .trim()
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    Some(count)
+}
+
+/// Splits DONE row counts between result sets and modifying statements.
+/// The first count after a result set started is that result set's own.
+#[derive(Default)]
+pub struct RowCountTracker {
+    awaiting_result_set_count: bool,
+    pub rows_affected: i64,
+}
+
+impl RowCountTracker {
+    pub fn add_counts(&mut self, counts: Vec<u64>) {
+        for count in counts {
+            if self.awaiting_result_set_count {
+                self.awaiting_result_set_count = false;
+            } else {
+                self.rows_affected += count as i64;
+            }
+        }
+    }
+
+    pub fn result_set_started(&mut self) {
+        self.awaiting_result_set_count = true;
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: Option<String>,
+    code: Option<u64>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "code" {
+            self.code = Some(value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        }
+    }
+}
+
+fn is_token_event(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event() && metadata.target() == TOKEN_TARGET
+}
+
+impl Subscriber for MessageCapture {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        is_token_event(metadata) || self.forward.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<tracing::level_filters::LevelFilter> {
+        Some(tracing::level_filters::LevelFilter::TRACE)
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.forward.new_span(span)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        self.forward.record(span, values)
+    }
+
+    fn record_follows_from(&self, span: &Id, follows: &Id) {
+        self.forward.record_follows_from(span, follows)
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        if !is_token_event(event.metadata()) {
+            self.forward.event(event);
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let level = event.metadata().level();
+        if *level == tracing::Level::ERROR {
+            if let Some(message) = visitor.message {
+                self.errors.lock().unwrap().push(ServerError {
+                    code: visitor.code.unwrap_or(0) as u32,
+                    message,
+                });
+            }
+            return;
+        }
+        let Some(text) = visitor.message else {
+            return;
+        };
+        if *level != tracing::Level::INFO {
+            if text.starts_with("Done with status ") {
+                self.done_at.lock().unwrap().push(Instant::now());
+            }
+            if let Some(count) = rows_affected(&text) {
+                self.row_counts.lock().unwrap().push(count);
+            }
+        }
+        if let Some(message) = message_for_event(level, &text) {
+            self.messages.lock().unwrap().push(message);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        self.forward.enter(span)
+    }
+
+    fn exit(&self, span: &Id) {
+        self.forward.exit(span)
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        self.forward.clone_span(id)
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        self.forward.try_close(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::instrument::WithSubscriber;
+    use tracing::Level;
+
+    #[test]
+    fn test_info_messages_pass_through_except_env_changes() {
+        assert_eq!(
+            message_for_event(&Level::INFO, "Changed database context to 'master'."),
+            Some("Changed database context to 'master'.".into())
+        );
+        assert_eq!(
+            message_for_event(&Level::INFO, "Database change from 'a' to 'master'"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_done_row_counts() {
+        assert_eq!(
+            message_for_event(
+                &Level::TRACE,
+                "Done with status BitFlags<DoneStatus>(0b10001, More | Count) (3 rows left)"
+            ),
+            Some("(3 rows affected)".into())
+        );
+        assert_eq!(
+            rows_affected("Done with status BitFlags<DoneStatus>(0b10000, Count) (1 row left)"),
+            Some(1)
+        );
+        assert_eq!(
+            rows_affected("Done with status BitFlags<DoneStatus>(0b10000, Count)"),
+            Some(0)
+        );
+        // NOCOUNT ON: no Count flag, nothing to report
+        assert_eq!(
+            rows_affected("Done with status BitFlags<DoneStatus>(0b1, More)"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_row_counts_split_between_result_sets_and_dml() {
+        let mut tracker = RowCountTracker::default();
+        // UPDATE (5 rows), then a SELECT returning 2 rows, then a DELETE (3 rows)
+        tracker.add_counts(vec![5]);
+        tracker.result_set_started();
+        tracker.add_counts(vec![2, 3]);
+        assert_eq!(tracker.rows_affected, 8);
+    }
+
+    /// Stands in for the application's subscriber
+    struct Counter(Arc<Mutex<usize>>);
+
+    impl Subscriber for Counter {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, _event: &Event<'_>) {
+            *self.0.lock().unwrap() += 1;
+        }
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_token_events_are_captured_and_others_forwarded() {
+        let forwarded = Arc::new(Mutex::new(0));
+        let app = Dispatch::new(Counter(forwarded.clone()));
+        let capture = tracing::dispatcher::with_default(&app, MessageCapture::default);
+
+        tracing::dispatcher::with_default(&Dispatch::new(capture.clone()), || {
+            tracing::info!(target: TOKEN_TARGET, message = "Hello from PRINT");
+            tracing::warn!("Application event");
+        });
+        tracing::dispatcher::with_default(&Dispatch::new(capture.clone()), || {
+            tracing::trace!(
+                target: TOKEN_TARGET,
+                "Done with status BitFlags<DoneStatus>(0b1, More)"
+            );
+        });
+        assert_eq!(capture.take(), vec!["Hello from PRINT"]);
+        assert_eq!(capture.take_done_times().len(), 1);
+        assert_eq!(*forwarded.lock().unwrap(), 1);
+    }
+
+    /// A TDS message as one packet
+    fn packet(kind: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![kind, 0x01];
+        packet.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 1, 0]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Read packets up to the end of the client's message
+    async fn read_message(stream: &mut tokio::net::TcpStream) {
+        use tokio::io::AsyncReadExt;
+        loop {
+            let mut header = [0u8; 8];
+            stream.read_exact(&mut header).await.unwrap();
+            let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+            let mut payload = vec![0; len - 8];
+            stream.read_exact(&mut payload).await.unwrap();
+            if header[1] & 0x01 != 0 {
+                return;
+            }
+        }
+    }
+
+    /// DONE token with `status` and a row count (TDS 7.2+)
+    fn done(status: u16, rows: u64) -> Vec<u8> {
+        let mut token = vec![0xFD];
+        token.extend_from_slice(&status.to_le_bytes());
+        token.extend_from_slice(&0u16.to_le_bytes());
+        token.extend_from_slice(&rows.to_le_bytes());
+        token
+    }
+
+    /// A server that accepts any login and answers one batch with DONE
+    /// tokens for two statements: NOCOUNT off with 3 rows, then 1 row
+    async fn fake_server(listener: tokio::net::TcpListener) {
+        use tokio::io::AsyncWriteExt;
+        let (mut stream, _) = listener.accept().await.unwrap();
+        read_message(&mut stream).await;
+        // PRELOGIN response: encryption not supported
+        let prelogin = [0x01, 0x00, 0x06, 0x00, 0x01, 0xFF, 0x02];
+        stream.write_all(&packet(0x04, &prelogin)).await.unwrap();
+        read_message(&mut stream).await;
+        stream.write_all(&packet(0x04, &done(0, 0))).await.unwrap();
+        read_message(&mut stream).await;
+        let mut tokens = done(0x11, 3);
+        tokens.extend(done(0x10, 1));
+        stream.write_all(&packet(0x04, &tokens)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_row_counts_from_tiberius_done_tokens() {
+        use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(fake_server(listener));
+
+        let mut config = tiberius::Config::new();
+        config.authentication(tiberius::AuthMethod::sql_server("sa", "sa"));
+        config.encryption(tiberius::EncryptionLevel::NotSupported);
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut client = tiberius::Client::connect(config, tcp.compat_write())
+            .await
+            .unwrap();
+
+        let capture = MessageCapture::default();
+        async {
+            client
+                .simple_query("UPDATE t SET x = 1; UPDATE u SET y = 2")
+                .await
+                .unwrap()
+                .into_results()
+                .await
+                .unwrap();
+        }
+        .with_subscriber(capture.clone())
+        .await;
+
+        assert_eq!(capture.take_row_counts(), vec![3, 1]);
+        assert_eq!(
+            capture.take(),
+            vec!["(3 rows affected)", "(1 row affected)"]
+        );
+        assert_eq!(capture.take_done_times().len(), 2);
+        drop(client);
+        server.await.unwrap();
+    }
+}
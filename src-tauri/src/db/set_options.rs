@@ -0,0 +1,149 @@
+use super::connection::TiberiusClient;
+use super::rows::get_i64;
+use super::types::{MismatchVerdict, SetOptionDifference};
+
+/// SET options that are part of the plan cache key, as @@OPTIONS bits. A
+/// session differing in any of them compiles and caches its own plan.
+const PLAN_AFFECTING_OPTIONS: &[(i64, &str)] = &[
+    (8, "ANSI_WARNINGS"),
+    (16, "ANSI_PADDING"),
+    (32, "ANSI_NULLS"),
+    (64, "ARITHABORT"),
+    (256, "QUOTED_IDENTIFIER"),
+    (1024, "ANSI_NULL_DFLT_ON"),
+    (4096, "CONCAT_NULL_YIELDS_NULL"),
+    (8192, "NUMERIC_ROUNDABORT"),
+];
+
+/// @@OPTIONS of a fresh SSMS query window: the ANSI options, ARITHABORT,
+/// QUOTED_IDENTIFIER, ANSI_NULL_DFLT_ON and CONCAT_NULL_YIELDS_NULL ON
+pub const SSMS_DEFAULT_OPTIONS: i64 = 5496;
+
+/// Plan-affecting options where the session and SSMS disagree
+pub fn differences(session_options: i64) -> Vec<SetOptionDifference> {
+    PLAN_AFFECTING_OPTIONS
+        .iter()
+        .filter(|(bit, _)| (session_options ^ SSMS_DEFAULT_OPTIONS) & bit != 0)
+        .map(|(bit, name)| SetOptionDifference {
+            name: name.to_string(),
+            session_value: session_options & bit != 0,
+            ssms_value: SSMS_DEFAULT_OPTIONS & bit != 0,
+        })
+        .collect()
+}
+
+/// SET statements switching the differing options to the SSMS values, or
+/// back to the session's
+pub fn set_batch(differences: &[SetOptionDifference], to_ssms: bool) -> String {
+    differences
+        .iter()
+        .map(|difference| {
+            let on = if to_ssms {
+                difference.ssms_value
+            } else {
+                difference.session_value
+            };
+            format!("SET {} {};", difference.name, if on { "ON" } else { "OFF" })
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What the option differences and plan hashes say about the discrepancy
+pub fn verdict(
+    differences: &[SetOptionDifference],
+    session_hashes: &[String],
+    ssms_hashes: &[String],
+) -> (MismatchVerdict, String) {
+    if differences.is_empty() {
+        return (
+            MismatchVerdict::NotSetOptions,
+            "The session already uses the SSMS defaults, so SET options do not explain a \
+             difference. Compare parameter values, statistics and the database context instead."
+                .to_string(),
+        );
+    }
+    let names = differences
+        .iter()
+        .map(|difference| difference.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if session_hashes != ssms_hashes {
+        (
+            MismatchVerdict::PlansDiffer,
+            format!(
+                "The optimizer picks a different plan under the SSMS defaults. The SET options \
+                 that differ ({}) explain the discrepancy.",
+                names
+            ),
+        )
+    } else {
+        (
+            MismatchVerdict::SeparateCacheEntries,
+            format!(
+                "Both settings compile the same plan, but because {} differ the app and SSMS \
+                 use separate plan cache entries. A slow cached plan on one side was most \
+                 likely compiled for other parameter values (parameter sniffing).",
+                names
+            ),
+        )
+    }
+}
+
+/// @@OPTIONS of the session
+pub async fn session_options(client: &mut TiberiusClient) -> Result<i64, String> {
+    let row = client
+        .simple_query("SELECT CAST(@@OPTIONS AS int)")
+        .await
+        .map_err(|e| format!("Failed to read SET options: {}", e))?
+        .into_row()
+        .await
+        .map_err(|e| format!("Failed to read SET options: {}", e))?
+        .ok_or("Failed to read SET options: no rows")?;
+    Ok(get_i64(&row, 0).unwrap_or(0))
+}
+
+/// Run a batch from `set_batch` on the session
+pub async fn apply(client: &mut TiberiusClient, batch: &str) -> Result<(), String> {
+    client
+        .simple_query(batch)
+        .await
+        .map_err(|e| format!("Failed to change SET options: {}", e))?
+        .into_results()
+        .await
+        .map_err(|e| format!("Failed to change SET options: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithabort_off_is_reported_and_reverted() {
+        // Typical ADO.NET / driver session: SSMS defaults minus ARITHABORT
+        let diffs = differences(SSMS_DEFAULT_OPTIONS & !64);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].name, "ARITHABORT");
+        assert_eq!(set_batch(&diffs, true), "SET ARITHABORT ON;");
+        assert_eq!(set_batch(&diffs, false), "SET ARITHABORT OFF;");
+
+        // Options outside the cache key, like NOCOUNT, are ignored
+        assert!(differences(SSMS_DEFAULT_OPTIONS | 512).is_empty());
+
+        let hash = vec!["0x1A2B".to_string()];
+        let other = vec!["0x3C4D".to_string()];
+        assert_eq!(
+            verdict(&diffs, &hash, &other).0,
+            MismatchVerdict::PlansDiffer
+        );
+        assert_eq!(
+            verdict(&diffs, &hash, &hash).0,
+            MismatchVerdict::SeparateCacheEntries
+        );
+        assert_eq!(
+            verdict(&[], &hash, &other).0,
+            MismatchVerdict::NotSetOptions
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use tiberius::Row;
+
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_literal, quote_object_name};
+use super::rows::{get_i64, get_string};
+use super::types::{AppError, IndexSize, TableSize};
+
+// Storage per table and per index from sys.dm_db_partition_stats, whose
+// page counts are the allocation units' totals per partition (in-row, LOB
+// and row-overflow), so a suggested index can be weighed against what its
+// table already takes.
+
+const KB_PER_PAGE: i64 = 8;
+
+/// `IF ... RAISERROR` guard and `WHERE` condition limiting a query to one
+/// table, or nothing for the whole database
+fn table_filter(table: Option<&str>) -> Result<(String, String), AppError> {
+    match table.map(str::trim) {
+        Some(table) if !table.is_empty() => {
+            let object = quote_literal(&quote_object_name(table)?);
+            Ok((
+                format!(
+                    "IF OBJECT_ID({object}) IS NULL \
+                         RAISERROR(N'Table not found: %s', 16, 1, {name}); ",
+                    name = quote_literal(table),
+                ),
+                format!("AND o.object_id = OBJECT_ID({object}) "),
+            ))
+        }
+        _ => Ok((String::new(), String::new())),
+    }
+}
+
+/// Split page totals the way sp_spaceused does: data is the heap or
+/// clustered index leaf, the rest of the used pages are index pages
+fn table_size(
+    schema_name: String,
+    table_name: String,
+    rows: i64,
+    reserved_pages: i64,
+    used_pages: i64,
+    data_pages: i64,
+) -> TableSize {
+    TableSize {
+        schema_name,
+        table_name,
+        rows,
+        reserved_kb: reserved_pages * KB_PER_PAGE,
+        used_kb: used_pages * KB_PER_PAGE,
+        data_kb: data_pages * KB_PER_PAGE,
+        index_kb: (used_pages - data_pages).max(0) * KB_PER_PAGE,
+        unused_kb: (reserved_pages - used_pages).max(0) * KB_PER_PAGE,
+    }
+}
+
+/// User tables, largest first
+pub async fn table_sizes(
+    client: &mut TiberiusClient,
+    table: Option<&str>,
+) -> Result<Vec<TableSize>, AppError> {
+    let (check, filter) = table_filter(table)?;
+    let sql = format!(
+        "{check}\
+         SELECT s.name, o.name, \
+                SUM(CASE WHEN ps.index_id < 2 THEN ps.row_count ELSE 0 END), \
+                SUM(ps.reserved_page_count), SUM(ps.used_page_count), \
+                SUM(CASE WHEN ps.index_id < 2 \
+                         THEN ps.in_row_data_page_count + ps.lob_used_page_count \
+                              + ps.row_overflow_used_page_count \
+                         ELSE 0 END) \
+         FROM sys.dm_db_partition_stats ps \
+         JOIN sys.objects o ON o.object_id = ps.object_id \
+         JOIN sys.schemas s ON s.schema_id = o.schema_id \
+         WHERE o.type = 'U' AND o.is_ms_shipped = 0 {filter}\
+         GROUP BY s.name, o.name \
+         ORDER BY SUM(ps.reserved_page_count) DESC, s.name, o.name"
+    );
+
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read table sizes"))?
+        .into_first_result()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read table sizes"))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            table_size(
+                get_string(row, 0).unwrap_or_default(),
+                get_string(row, 1).unwrap_or_default(),
+                get_i64(row, 2).unwrap_or(0),
+                get_i64(row, 3).unwrap_or(0),
+                get_i64(row, 4).unwrap_or(0),
+                get_i64(row, 5).unwrap_or(0),
+            )
+        })
+        .collect())
+}
+
+/// Indexes and heaps of user tables, largest first
+pub async fn index_sizes(
+    client: &mut TiberiusClient,
+    table: Option<&str>,
+) -> Result<Vec<IndexSize>, AppError> {
+    let (check, filter) = table_filter(table)?;
+    let sql = format!(
+        "{check}\
+         SELECT s.name, o.name, i.name, i.index_id, i.type_desc, COUNT(*), \
+                SUM(ps.row_count), SUM(ps.reserved_page_count), SUM(ps.used_page_count), \
+                SUM(ps.in_row_used_page_count), SUM(ps.lob_used_page_count), \
+                SUM(ps.row_overflow_used_page_count) \
+         FROM sys.dm_db_partition_stats ps \
+         JOIN sys.indexes i ON i.object_id = ps.object_id AND i.index_id = ps.index_id \
+         JOIN sys.objects o ON o.object_id = ps.object_id \
+         JOIN sys.schemas s ON s.schema_id = o.schema_id \
+         WHERE o.type = 'U' AND o.is_ms_shipped = 0 {filter}\
+         GROUP BY s.name, o.name, i.name, i.index_id, i.type_desc \
+         ORDER BY SUM(ps.reserved_page_count) DESC, s.name, o.name, i.index_id"
+    );
+
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read index sizes"))?
+        .into_first_result()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read index sizes"))?;
+
+    let kb = |row: &Row, idx| get_i64(row, idx).unwrap_or(0) * KB_PER_PAGE;
+    Ok(rows
+        .iter()
+        .map(|row| IndexSize {
+            schema_name: get_string(row, 0).unwrap_or_default(),
+            table_name: get_string(row, 1).unwrap_or_default(),
+            index_name: get_string(row, 2),
+            index_id: get_i64(row, 3).unwrap_or(0),
+            index_type: get_string(row, 4).unwrap_or_default(),
+            partitions: get_i64(row, 5).unwrap_or(1),
+            rows: get_i64(row, 6).unwrap_or(0),
+            reserved_kb: kb(row, 7),
+            used_kb: kb(row, 8),
+            in_row_kb: kb(row, 9),
+            lob_kb: kb(row, 10),
+            row_overflow_kb: kb(row, 11),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_pages_split_like_sp_spaceused() {
+        let size = table_size("dbo".into(), "Orders".into(), 120_000, 1_500, 1_400, 1_100);
+        assert_eq!(size.reserved_kb, 12_000);
+        assert_eq!(size.used_kb, 11_200);
+        assert_eq!(size.data_kb, 8_800);
+        assert_eq!(size.index_kb, 2_400);
+        assert_eq!(size.unused_kb, 800);
+
+        assert!(table_filter(Some("dbo.Orders"))
+            .unwrap()
+            .1
+            .contains("OBJECT_ID(N'[dbo].[Orders]')"));
+        assert_eq!(table_filter(None).unwrap(), (String::new(), String::new()));
+    }
+}
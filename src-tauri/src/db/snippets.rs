@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use super::types::SnippetPlaceholder;
+
+// Snippet SQL may contain placeholders written as {{name}} or
+// {{name:default}}. Values are substituted as typed, including inside string
+// literals, so '{{database}}' works as expected.
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// A placeholder occurrence: byte range in the SQL, name and default
+struct Occurrence<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+    default: Option<&'a str>,
+}
+
+fn occurrences(sql: &str) -> Vec<Occurrence<'_>> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = sql[pos..].find(OPEN).map(|i| pos + i) {
+        let inner_start = open + OPEN.len();
+        let Some(close) = sql[inner_start..].find(CLOSE).map(|i| inner_start + i) else {
+            break;
+        };
+        let inner = &sql[inner_start..close];
+        let (name, default) = match inner.split_once(':') {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (inner.trim(), None),
+        };
+        if is_placeholder_name(name) {
+            found.push(Occurrence {
+                start: open,
+                end: close + CLOSE.len(),
+                name,
+                default,
+            });
+            pos = close + CLOSE.len();
+        } else {
+            // Not a placeholder (e.g. JSON in a literal); keep scanning after "{{"
+            pos = inner_start;
+        }
+    }
+    found
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Distinct placeholders in order of first use. A default given on any
+/// occurrence applies to all of them.
+pub fn placeholders(sql: &str) -> Vec<SnippetPlaceholder> {
+    let mut result: Vec<SnippetPlaceholder> = Vec::new();
+    for occurrence in occurrences(sql) {
+        match result.iter_mut().find(|p| p.name == occurrence.name) {
+            Some(existing) => {
+                if existing.default.is_none() {
+                    existing.default = occurrence.default.map(str::to_string);
+                }
+            }
+            None => result.push(SnippetPlaceholder {
+                name: occurrence.name.to_string(),
+                default: occurrence.default.map(str::to_string),
+            }),
+        }
+    }
+    result
+}
+
+/// Fill in placeholders from `values`, falling back to their defaults
+pub fn render(sql: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let defaults = placeholders(sql);
+    let missing: Vec<&str> = defaults
+        .iter()
+        .filter(|p| p.default.is_none() && !values.contains_key(&p.name))
+        .map(|p| p.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing values for: {}", missing.join(", ")));
+    }
+
+    let mut out = String::with_capacity(sql.len());
+    let mut pos = 0;
+    for occurrence in occurrences(sql) {
+        out.push_str(&sql[pos..occurrence.start]);
+        let value = values.get(occurrence.name).map(String::as_str).or_else(|| {
+            defaults
+                .iter()
+                .find(|p| p.name == occurrence.name)
+                .and_then(|p| p.default.as_deref())
+        });
+        out.push_str(value.unwrap_or_default());
+        pos = occurrence.end;
+    }
+    out.push_str(&sql[pos..]);
+    Ok(out)
+}
+
+/// Folder paths are "/"-separated; blank segments and surrounding spaces are
+/// dropped so "Diagnostics / Waits/" and "Diagnostics/Waits" are the same
+pub fn normalize_folder(folder: Option<&str>) -> Option<String> {
+    let path = folder?
+        .split('/')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    (!path.is_empty()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders() {
+        let found = placeholders(
+            "SELECT TOP ({{top:10}}) * FROM {{table}} WHERE db = '{{table}}' AND j = '{{\"a\": 1}}'",
+        );
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name, "top");
+        assert_eq!(found[0].default.as_deref(), Some("10"));
+        assert_eq!(found[1].name, "table");
+        assert_eq!(found[1].default, None);
+    }
+
+    #[test]
+    fn test_render() {
+        let sql = "SELECT TOP ({{top:10}}) * FROM {{table}}";
+        let mut values = HashMap::new();
+        assert_eq!(
+            render(sql, &values),
+            Err("Missing values for: table".to_string())
+        );
+
+        values.insert("table".to_string(), "dbo.Orders".to_string());
+        assert_eq!(
+            render(sql, &values).unwrap(),
+            "SELECT TOP (10) * FROM dbo.Orders"
+        );
+
+        values.insert("top".to_string(), "5".to_string());
+        assert_eq!(
+            render(sql, &values).unwrap(),
+            "SELECT TOP (5) * FROM dbo.Orders"
+        );
+    }
+
+    #[test]
+    fn test_normalize_folder() {
+        assert_eq!(
+            normalize_folder(Some(" Diagnostics / Waits/ ")),
+            Some("Diagnostics/Waits".to_string())
+        );
+        assert_eq!(normalize_folder(Some(" / ")), None);
+        assert_eq!(normalize_folder(None), None);
+    }
+}
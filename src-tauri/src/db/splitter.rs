@@ -0,0 +1,464 @@
+// T-SQL statement splitter used to run the statement under the editor cursor.
+// Statements end at a semicolon, a GO separator line, or — since semicolons
+// are optional in T-SQL — where a new statement keyword starts at the top
+// level. Parentheses, BEGIN...END / CASE...END blocks and module bodies
+// (CREATE PROCEDURE etc.) are kept whole.
+
+use super::tsql_lexer::{code_tokens, tokenize, Token, TokenKind};
+
+/// Keywords that can begin a statement
+const STATEMENT_STARTS: &[&str] = &[
+    "SELECT",
+    "INSERT",
+    "UPDATE",
+    "DELETE",
+    "MERGE",
+    "DECLARE",
+    "SET",
+    "EXEC",
+    "EXECUTE",
+    "PRINT",
+    "IF",
+    "WHILE",
+    "BEGIN",
+    "CREATE",
+    "ALTER",
+    "DROP",
+    "TRUNCATE",
+    "USE",
+    "RAISERROR",
+    "THROW",
+    "RETURN",
+    "GRANT",
+    "REVOKE",
+    "DENY",
+    "COMMIT",
+    "ROLLBACK",
+    "SAVE",
+    "WAITFOR",
+    "BACKUP",
+    "RESTORE",
+    "DBCC",
+    "OPEN",
+    "FETCH",
+    "CLOSE",
+    "DEALLOCATE",
+    "BREAK",
+    "CONTINUE",
+    "GOTO",
+    "KILL",
+    "CHECKPOINT",
+];
+
+/// Object types whose body runs until the end of the batch
+const MODULE_TYPES: &[&str] = &["PROC", "PROCEDURE", "FUNCTION", "TRIGGER", "VIEW"];
+
+/// BEGIN followed by one of these is a statement, not a block
+pub(super) const NON_BLOCK_BEGINS: &[&str] = &[
+    "TRAN",
+    "TRANSACTION",
+    "DISTRIBUTED",
+    "DIALOG",
+    "CONVERSATION",
+];
+
+/// Byte range of one statement, excluding the terminating semicolon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatementSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// WITH opens a statement only as a CTE (`WITH name AS (` / `WITH name (cols) AS (`)
+/// or XMLNAMESPACES, never as a table hint, `WITH TIES` or an option list.
+fn starts_cte(sql: &str, rest: &[Token]) -> bool {
+    let word = |t: Option<&Token>| {
+        t.filter(|t| t.kind == TokenKind::Word)
+            .map(|t| sql[t.start..t.end].to_ascii_uppercase())
+    };
+    let Some(name) = word(rest.first()) else {
+        return false;
+    };
+    if name == "XMLNAMESPACES" {
+        return true;
+    }
+    match rest.get(1).map(|t| t.kind) {
+        // Column list before AS
+        Some(TokenKind::OpenParen) => {
+            let close = rest
+                .iter()
+                .position(|t| t.kind == TokenKind::CloseParen)
+                .unwrap_or(rest.len());
+            word(rest.get(close + 1)).as_deref() == Some("AS")
+        }
+        Some(TokenKind::Word) => {
+            word(rest.get(1)).as_deref() == Some("AS")
+                && rest.get(2).map(|t| t.kind) == Some(TokenKind::OpenParen)
+        }
+        _ => false,
+    }
+}
+
+/// State of the statement currently being collected
+#[derive(Default)]
+struct Statement {
+    span: Option<StatementSpan>,
+    /// Uppercased leading words, used to recognise module definitions
+    leading: Vec<String>,
+    /// Statement kind; for a CTE this becomes the DML keyword after it
+    head: Option<String>,
+    module: bool,
+    seen_values: bool,
+    seen_set: bool,
+    /// IF/WHILE already has its body statement
+    seen_body: bool,
+}
+
+impl Statement {
+    fn extend(&mut self, token: &Token) {
+        match &mut self.span {
+            Some(span) => span.end = token.end,
+            None => {
+                self.span = Some(StatementSpan {
+                    start: token.start,
+                    end: token.end,
+                })
+            }
+        }
+    }
+
+    /// Whether a statement keyword at the top level still belongs to this statement
+    fn continues_with(&mut self, keyword: &str, previous: Option<&str>) -> bool {
+        if self.module {
+            return true;
+        }
+        if matches!(
+            previous,
+            Some("UNION" | "ALL" | "EXCEPT" | "INTERSECT" | "FOR" | "ON" | "OR")
+        ) {
+            return true;
+        }
+        // END TRY is always followed by its BEGIN CATCH
+        if keyword == "BEGIN" && previous == Some("TRY") {
+            return true;
+        }
+
+        match self.head.as_deref() {
+            Some("WITH")
+                if matches!(keyword, "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "MERGE") =>
+            {
+                self.head = Some(keyword.to_string());
+                true
+            }
+            // The first statement after the condition is the body, as is one after ELSE
+            Some("IF" | "WHILE") if previous == Some("ELSE") || !self.seen_body => {
+                self.seen_body = true;
+                true
+            }
+            Some("INSERT") => matches!(keyword, "SELECT" | "EXEC" | "EXECUTE") && !self.seen_values,
+            Some("UPDATE") => keyword == "SET" && !self.seen_set,
+            Some("ALTER") => matches!(keyword, "SET" | "ALTER" | "DROP"),
+            Some("DROP") => keyword == "IF",
+            Some("MERGE" | "GRANT" | "REVOKE" | "DENY") => true,
+            _ => false,
+        }
+    }
+
+    fn record_word(&mut self, upper: &str) {
+        if self.head.is_none() {
+            self.head = Some(upper.to_string());
+        }
+        if self.leading.len() < 4 {
+            self.leading.push(upper.to_string());
+            if matches!(self.leading[0].as_str(), "CREATE" | "ALTER")
+                && MODULE_TYPES.contains(&upper)
+            {
+                self.module = true;
+            }
+        }
+        match (self.head.as_deref(), upper) {
+            (Some("INSERT"), "VALUES") => self.seen_values = true,
+            (Some("UPDATE"), "SET") => self.seen_set = true,
+            _ => {}
+        }
+    }
+}
+
+/// Split a script into statements; GO lines and semicolons are not part of any statement.
+pub fn split_statements(sql: &str) -> Vec<StatementSpan> {
+    let tokens = code_tokens(sql);
+    let mut spans = Vec::new();
+    let mut current = Statement::default();
+    let mut paren_depth = 0usize;
+    let mut block_depth = 0usize;
+    let mut previous_word: Option<String> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::BatchSeparator => {
+                spans.extend(std::mem::take(&mut current).span);
+                paren_depth = 0;
+                block_depth = 0;
+                previous_word = None;
+                continue;
+            }
+            TokenKind::Semicolon => {
+                if paren_depth == 0 && block_depth == 0 && !current.module {
+                    spans.extend(std::mem::take(&mut current).span);
+                    previous_word = None;
+                } else {
+                    current.extend(token);
+                }
+                continue;
+            }
+            TokenKind::OpenParen => paren_depth += 1,
+            TokenKind::CloseParen => paren_depth = paren_depth.saturating_sub(1),
+            TokenKind::Word => {
+                let after_dot = index > 0 && tokens[index - 1].kind == TokenKind::Dot;
+                let upper = sql[token.start..token.end].to_ascii_uppercase();
+
+                if !after_dot {
+                    let starts_statement = STATEMENT_STARTS.contains(&upper.as_str())
+                        || (upper == "WITH" && starts_cte(sql, &tokens[index + 1..]));
+                    if paren_depth == 0
+                        && block_depth == 0
+                        && current.span.is_some()
+                        && starts_statement
+                        && !current.continues_with(&upper, previous_word.as_deref())
+                    {
+                        spans.extend(std::mem::take(&mut current).span);
+                    }
+
+                    match upper.as_str() {
+                        "BEGIN" => {
+                            let next = tokens
+                                .get(index + 1)
+                                .filter(|t| t.kind == TokenKind::Word)
+                                .map(|t| sql[t.start..t.end].to_ascii_uppercase());
+                            if !next.is_some_and(|n| NON_BLOCK_BEGINS.contains(&n.as_str())) {
+                                block_depth += 1;
+                            }
+                        }
+                        "CASE" => block_depth += 1,
+                        "END" => block_depth = block_depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    current.record_word(&upper);
+                }
+                previous_word = Some(upper);
+            }
+            _ => {}
+        }
+        current.extend(token);
+    }
+
+    spans.extend(std::mem::take(&mut current).span);
+    spans
+}
+
+/// Split a script into the batches between its GO lines, as SSMS sends
+/// them. Batches with nothing but comments are left out.
+pub fn split_batches(sql: &str) -> Vec<StatementSpan> {
+    let mut batches = Vec::new();
+    let mut current: Option<StatementSpan> = None;
+    let mut has_code = false;
+    for token in tokenize(sql) {
+        if token.kind == TokenKind::BatchSeparator {
+            batches.extend(current.take().filter(|_| has_code));
+            has_code = false;
+            continue;
+        }
+        has_code |= !token.is_comment();
+        current
+            .get_or_insert(StatementSpan {
+                start: token.start,
+                end: token.end,
+            })
+            .end = token.end;
+    }
+    batches.extend(current.filter(|_| has_code));
+    batches
+}
+
+/// The statement containing `offset`, or the closest one before it when the
+/// cursor sits between statements.
+pub fn statement_at(sql: &str, offset: usize) -> Option<StatementSpan> {
+    let spans = split_statements(sql);
+    spans
+        .iter()
+        .rev()
+        .find(|s| s.start <= offset)
+        .or_else(|| spans.first())
+        .copied()
+}
+
+/// Convert a UTF-16 code unit offset (as used by the editor) into a byte offset.
+pub fn utf16_to_byte_offset(text: &str, utf16_offset: usize) -> usize {
+    let mut units = 0;
+    for (byte_index, ch) in text.char_indices() {
+        if units >= utf16_offset {
+            return byte_index;
+        }
+        units += ch.len_utf16();
+    }
+    text.len()
+}
+
+pub fn byte_to_utf16_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset.min(text.len())].encode_utf16().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str) -> Vec<&str> {
+        split_statements(sql)
+            .into_iter()
+            .map(|s| &sql[s.start..s.end])
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_on_semicolons_and_go() {
+        let sql = "SELECT 1; SELECT ';'\nGO\nPRINT 'x' -- trailing; comment\n";
+        assert_eq!(texts(sql), vec!["SELECT 1", "SELECT ';'", "PRINT 'x'"]);
+    }
+
+    #[test]
+    fn test_splits_without_semicolons() {
+        let sql = "SELECT * FROM a\nSELECT * FROM b WHERE x IN (SELECT x FROM c)\nUPDATE t SET a = 1 WHERE b = 2\nDELETE FROM t";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "SELECT * FROM a",
+                "SELECT * FROM b WHERE x IN (SELECT x FROM c)",
+                "UPDATE t SET a = 1 WHERE b = 2",
+                "DELETE FROM t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compound_statements_stay_whole() {
+        let sql = "SELECT a FROM t UNION ALL SELECT a FROM u\n\
+                   INSERT INTO t (a) SELECT a FROM u\n\
+                   WITH c AS (SELECT 1 AS a) DELETE FROM t WHERE a IN (SELECT a FROM c)\n\
+                   IF @x = 1 BEGIN SELECT 1; SELECT 2; END ELSE SELECT 3\n\
+                   DROP TABLE IF EXISTS dbo.t";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "SELECT a FROM t UNION ALL SELECT a FROM u",
+                "INSERT INTO t (a) SELECT a FROM u",
+                "WITH c AS (SELECT 1 AS a) DELETE FROM t WHERE a IN (SELECT a FROM c)",
+                "IF @x = 1 BEGIN SELECT 1; SELECT 2; END ELSE SELECT 3",
+                "DROP TABLE IF EXISTS dbo.t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_module_body_runs_to_go() {
+        let sql = "CREATE OR ALTER PROCEDURE dbo.p AS\nSELECT 1;\nSELECT 2;\nGO\nEXEC dbo.p";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "CREATE OR ALTER PROCEDURE dbo.p AS\nSELECT 1;\nSELECT 2;",
+                "EXEC dbo.p"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_separators_inside_strings_comments_and_names_are_ignored() {
+        let sql = "SELECT 'a;\nGO\nb' AS [x;y]; /* ; GO\nGO\n */ SELECT \"p;q\" -- ;\nSELECT N'it''s; fine'";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "SELECT 'a;\nGO\nb' AS [x;y]",
+                "SELECT \"p;q\"",
+                "SELECT N'it''s; fine'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_go_variants() {
+        let sql = "SELECT 1\r\ngo\r\nSELECT 2\n  GO 3 -- repeat\nSELECT GO FROM t\nGO";
+        assert_eq!(texts(sql), vec!["SELECT 1", "SELECT 2", "SELECT GO FROM t"]);
+    }
+
+    #[test]
+    fn test_nested_blocks_and_case() {
+        let sql = "WHILE @i < 3 BEGIN IF @i = 1 BEGIN SET @i = @i + 1; END ELSE BEGIN PRINT CASE WHEN @i > 1 THEN 'a' ELSE 'b' END; END SET @i += 1 END\n\
+                   BEGIN TRAN\n\
+                   BEGIN TRY SELECT 1 END TRY BEGIN CATCH THROW; END CATCH\n\
+                   COMMIT";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "WHILE @i < 3 BEGIN IF @i = 1 BEGIN SET @i = @i + 1; END ELSE BEGIN PRINT CASE WHEN @i > 1 THEN 'a' ELSE 'b' END; END SET @i += 1 END",
+                "BEGIN TRAN",
+                "BEGIN TRY SELECT 1 END TRY BEGIN CATCH THROW; END CATCH",
+                "COMMIT",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hints_and_qualified_keywords_do_not_split() {
+        let sql = "SELECT * FROM t WITH (NOLOCK) WHERE x = 1 ORDER BY x OFFSET 0 ROWS\n\
+                   SELECT TOP (1) WITH TIES dbo.[Update].[Select] FROM dbo.[Update]\n\
+                   MERGE t USING s ON t.id = s.id WHEN MATCHED THEN UPDATE SET a = 1 WHEN NOT MATCHED THEN INSERT (a) VALUES (1);";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "SELECT * FROM t WITH (NOLOCK) WHERE x = 1 ORDER BY x OFFSET 0 ROWS",
+                "SELECT TOP (1) WITH TIES dbo.[Update].[Select] FROM dbo.[Update]",
+                "MERGE t USING s ON t.id = s.id WHEN MATCHED THEN UPDATE SET a = 1 WHEN NOT MATCHED THEN INSERT (a) VALUES (1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_and_comment_only_scripts() {
+        assert!(texts("").is_empty());
+        assert!(texts("  -- nothing\n/* here */ ;\nGO\n").is_empty());
+        assert_eq!(texts("SELECT 1 /* unterminated"), vec!["SELECT 1"]);
+    }
+
+    #[test]
+    fn test_batches_split_on_go_lines() {
+        let sql = "-- migration 12\nCREATE TABLE t (id int);\nGO\n\n/* only a note */\nGO 2\nINSERT t VALUES (1);\nSELECT 'GO' FROM t\ngo";
+        let batches: Vec<&str> = split_batches(sql)
+            .into_iter()
+            .map(|b| &sql[b.start..b.end])
+            .collect();
+        assert_eq!(
+            batches,
+            vec![
+                "-- migration 12\nCREATE TABLE t (id int);",
+                "INSERT t VALUES (1);\nSELECT 'GO' FROM t"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_at_cursor() {
+        let sql = "SELECT 1;\n\nSELECT 2;";
+        let second = sql.find("SELECT 2").unwrap();
+        assert_eq!(statement_at(sql, second + 3).unwrap().start, second);
+        // Cursor on the blank line picks the statement above
+        assert_eq!(statement_at(sql, 10).unwrap().start, 0);
+        assert_eq!(statement_at(sql, 0).unwrap().start, 0);
+    }
+
+    #[test]
+    fn test_utf16_offsets() {
+        let sql = "SELECT 'é😀' AS x";
+        let byte = utf16_to_byte_offset(sql, 11);
+        assert_eq!(&sql[byte..], "' AS x");
+        assert_eq!(byte_to_utf16_offset(sql, byte), 11);
+    }
+}
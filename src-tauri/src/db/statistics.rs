@@ -0,0 +1,233 @@
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_identifier, quote_literal, quote_object_name};
+use super::rows::{get_datetime, get_display_string, get_f64, get_i64, get_string};
+use super::types::{
+    AppError, HistogramKeyKind, HistogramStep, StatisticsInfo, StatsHistogram, TypedHistogramStep,
+};
+
+/// Statistics objects of a table with their freshness and histograms
+pub async fn statistics_info(
+    client: &mut TiberiusClient,
+    table: &str,
+) -> Result<Vec<StatisticsInfo>, AppError> {
+    let object = quote_literal(&quote_object_name(table)?);
+
+    let sql = format!(
+        "IF OBJECT_ID({object}) IS NULL \
+             RAISERROR(N'Table not found: %s', 16, 1, {name}); \
+         SELECT s.stats_id, s.name, s.auto_created, s.user_created, s.no_recompute, \
+                s.has_filter, s.filter_definition, \
+                sp.last_updated, sp.rows, sp.rows_sampled, sp.steps, sp.modification_counter, \
+                STUFF((SELECT N',' + c.name \
+                       FROM sys.stats_columns sc \
+                       JOIN sys.columns c ON c.object_id = sc.object_id AND c.column_id = sc.column_id \
+                       WHERE sc.object_id = s.object_id AND sc.stats_id = s.stats_id \
+                       ORDER BY sc.stats_column_id \
+                       FOR XML PATH(''), TYPE).value('.', 'nvarchar(max)'), 1, 1, N'') \
+         FROM sys.stats s \
+         OUTER APPLY sys.dm_db_stats_properties(s.object_id, s.stats_id) sp \
+         WHERE s.object_id = OBJECT_ID({object}) \
+         ORDER BY s.stats_id",
+        name = quote_literal(table),
+    );
+
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read statistics"))?
+        .into_first_result()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read statistics"))?;
+
+    let mut stats: Vec<StatisticsInfo> = rows
+        .iter()
+        .map(|row| StatisticsInfo {
+            stats_id: get_i64(row, 0).unwrap_or(0),
+            name: get_string(row, 1).unwrap_or_default(),
+            auto_created: row.try_get::<bool, _>(2).ok().flatten().unwrap_or(false),
+            user_created: row.try_get::<bool, _>(3).ok().flatten().unwrap_or(false),
+            no_recompute: row.try_get::<bool, _>(4).ok().flatten().unwrap_or(false),
+            filter_definition: row
+                .try_get::<bool, _>(5)
+                .ok()
+                .flatten()
+                .filter(|has| *has)
+                .and_then(|_| get_string(row, 6)),
+            last_updated: get_datetime(row, 7),
+            rows: get_i64(row, 8),
+            rows_sampled: get_i64(row, 9),
+            steps: get_i64(row, 10),
+            modification_counter: get_i64(row, 11),
+            columns: get_string(row, 12)
+                .map(|c| c.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            histogram: Vec::new(),
+        })
+        .collect();
+
+    for stat in &mut stats {
+        stat.histogram = histogram(client, table, stat.stats_id, &stat.name).await?;
+    }
+    Ok(stats)
+}
+
+/// sys.dm_db_stats_histogram where available (SQL Server 2016 SP1 CU2+),
+/// otherwise DBCC SHOW_STATISTICS ... WITH HISTOGRAM
+async fn histogram(
+    client: &mut TiberiusClient,
+    table: &str,
+    stats_id: i64,
+    stats_name: &str,
+) -> Result<Vec<HistogramStep>, AppError> {
+    let object = quote_literal(&quote_object_name(table)?);
+    // Style 126 renders dates as ISO-8601, like the DBCC fallback's keys
+    let dmv = format!(
+        "SELECT CONVERT(nvarchar(4000), range_high_key, 126), range_rows, equal_rows, \
+                distinct_range_rows, average_range_rows \
+         FROM sys.dm_db_stats_histogram(OBJECT_ID({object}), {}) \
+         ORDER BY step_number",
+        stats_id
+    );
+    let rows = match client.simple_query(dmv).await {
+        Ok(stream) => stream.into_first_result().await.ok(),
+        Err(_) => None,
+    };
+    let rows = match rows {
+        Some(rows) => rows,
+        None => {
+            let dbcc = format!(
+                "DBCC SHOW_STATISTICS ({object}, {}) WITH HISTOGRAM, NO_INFOMSGS",
+                quote_identifier(stats_name)
+            );
+            client
+                .simple_query(dbcc)
+                .await
+                .map_err(|e| {
+                    AppError::from(e)
+                        .context(&format!("Failed to read histogram of {}", stats_name))
+                })?
+                .into_first_result()
+                .await
+                .map_err(|e| {
+                    AppError::from(e)
+                        .context(&format!("Failed to read histogram of {}", stats_name))
+                })?
+        }
+    };
+
+    Ok(rows
+        .iter()
+        .map(|row| HistogramStep {
+            range_hi_key: get_display_string(row, 0),
+            range_rows: get_f64(row, 1).unwrap_or(0.0),
+            equal_rows: get_f64(row, 2).unwrap_or(0.0),
+            distinct_range_rows: get_f64(row, 3).unwrap_or(0.0),
+            average_range_rows: get_f64(row, 4).unwrap_or(0.0),
+        })
+        .collect())
+}
+
+/// Histogram of one statistics object of `table` with typed keys
+pub async fn stats_histogram(
+    client: &mut TiberiusClient,
+    table: &str,
+    stats_name: &str,
+) -> Result<StatsHistogram, AppError> {
+    let object = quote_literal(&quote_object_name(table)?);
+    let sql = format!(
+        "SELECT s.stats_id, c.name, TYPE_NAME(c.system_type_id) \
+         FROM sys.stats s \
+         JOIN sys.stats_columns sc ON sc.object_id = s.object_id AND sc.stats_id = s.stats_id \
+              AND sc.stats_column_id = 1 \
+         JOIN sys.columns c ON c.object_id = sc.object_id AND c.column_id = sc.column_id \
+         WHERE s.object_id = OBJECT_ID({object}) AND s.name = {name}",
+        name = quote_literal(stats_name),
+    );
+    let row = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read statistics"))?
+        .into_row()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read statistics"))?
+        .ok_or_else(|| format!("Statistics not found: {} on {}", stats_name, table))?;
+    let stats_id = get_i64(&row, 0).unwrap_or(0);
+    let column = get_string(&row, 1).unwrap_or_default();
+    let key_type = get_string(&row, 2).unwrap_or_default();
+
+    let key_kind = key_kind(&key_type);
+    let steps = histogram(client, table, stats_id, stats_name)
+        .await?
+        .into_iter()
+        .map(|step| TypedHistogramStep {
+            range_hi_key: typed_key(key_kind, step.range_hi_key),
+            range_rows: step.range_rows,
+            equal_rows: step.equal_rows,
+            distinct_range_rows: step.distinct_range_rows,
+            average_range_rows: step.average_range_rows,
+        })
+        .collect();
+
+    Ok(StatsHistogram {
+        stats_name: stats_name.to_string(),
+        column,
+        key_type,
+        key_kind,
+        steps,
+    })
+}
+
+fn key_kind(type_name: &str) -> HistogramKeyKind {
+    match type_name.to_ascii_lowercase().as_str() {
+        "bit" | "tinyint" | "smallint" | "int" | "bigint" | "decimal" | "numeric" | "money"
+        | "smallmoney" | "float" | "real" => HistogramKeyKind::Numeric,
+        "date" | "time" | "datetime" | "datetime2" | "smalldatetime" | "datetimeoffset" => {
+            HistogramKeyKind::Temporal
+        }
+        _ => HistogramKeyKind::Text,
+    }
+}
+
+/// Numeric keys as JSON numbers; text that does not parse stays a string
+fn typed_key(kind: HistogramKeyKind, text: Option<String>) -> serde_json::Value {
+    let Some(text) = text else {
+        return serde_json::Value::Null;
+    };
+    if kind == HistogramKeyKind::Numeric {
+        let trimmed = text.trim();
+        if let Ok(n) = trimmed.parse::<i64>() {
+            return n.into();
+        }
+        if let Some(n) = trimmed
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+        {
+            return serde_json::Value::Number(n);
+        }
+    }
+    serde_json::Value::String(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_histogram_keys_are_typed_by_column_type() {
+        assert_eq!(key_kind("int"), HistogramKeyKind::Numeric);
+        assert_eq!(key_kind("datetime2"), HistogramKeyKind::Temporal);
+        assert_eq!(key_kind("nvarchar"), HistogramKeyKind::Text);
+
+        let numeric = |text: &str| typed_key(HistogramKeyKind::Numeric, Some(text.to_string()));
+        assert_eq!(numeric("42"), json!(42));
+        assert_eq!(numeric("19.9500"), json!(19.95));
+        assert_eq!(numeric("1.500000000000000e+002"), json!(150.0));
+        assert_eq!(
+            typed_key(HistogramKeyKind::Text, Some("00042".to_string())),
+            json!("00042")
+        );
+        assert_eq!(typed_key(HistogramKeyKind::Numeric, None), json!(null));
+    }
+}
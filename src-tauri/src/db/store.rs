@@ -1,12 +1,69 @@
+use serde::{Deserialize, Serialize};
 use tauri::AppHandle;
-use tauri_plugin_store::StoreExt;
+use tauri_plugin_store::{Store, StoreExt};
 
-use super::types::{ConnectionConfig, PlanHistoryEntry, QueryHistoryEntry};
+use std::collections::{HashMap, HashSet};
+
+use super::compression;
+use super::types::{
+    Baseline, ConnectionConfig, ConnectionGroup, MasterKeyInfo, PlanAnnotation, PlanHistoryEntry,
+    QueryHistoryEntry, ScheduledQuery, Snippet, WorkspaceState,
+};
+use crate::plan::types::{PlanImageFormat, PlanThumbnail};
+use crate::settings::PlanRetention;
 
 const CONNECTIONS_STORE: &str = "connections.json";
 const HISTORY_STORE: &str = "history.json";
+const SNIPPETS_STORE: &str = "snippets.json";
+const WORKSPACE_STORE: &str = "workspace.json";
+const THUMBNAILS_STORE: &str = "plan_thumbnails.json";
+const ANNOTATIONS_STORE: &str = "plan_annotations.json";
+const SCHEDULES_STORE: &str = "scheduled_queries.json";
+const BASELINES_STORE: &str = "baselines.json";
+
+// Plans are stored one per key in the history store, with a small index of
+// id, connection, query hash and stored size, newest first. Saving a plan
+// then touches only its own key and the index, however long the history.
+const PLAN_INDEX_KEY: &str = "planIndex";
+const PLAN_KEY_PREFIX: &str = "plan:";
+const LEGACY_PLAN_HISTORY_KEY: &str = "planHistory";
+
+/// Databases remembered per connection in the recent list
+pub const RECENT_DATABASES_LIMIT: usize = 10;
+
+/// Unpinned queries kept in history; pinned ones do not count
+pub const QUERY_HISTORY_LIMIT: usize = 100;
+
+/// Version 2 added connection groups and tags
+const CONNECTIONS_SCHEMA_VERSION: u64 = 2;
+
+/// Bring connections.json up to the current schema. Older entries deserialize
+/// with default group/tags; rewriting them stamps the file with the version.
+fn migrate_connections(app: &AppHandle) -> Result<(), String> {
+    let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
+    let version = store.get("version").and_then(|v| v.as_u64()).unwrap_or(1);
+    if version >= CONNECTIONS_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if let Some(raw) = store.get("connections") {
+        let connections: Vec<ConnectionConfig> = serde_json::from_value(raw)
+            .map_err(|e| format!("Failed to migrate connections: {}", e))?;
+        store.set(
+            "connections",
+            serde_json::to_value(connections).map_err(|e| e.to_string())?,
+        );
+    }
+    if !store.has("groups") {
+        store.set("groups", serde_json::json!([]));
+    }
+    store.set("version", CONNECTIONS_SCHEMA_VERSION);
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
 
 pub fn get_connections(app: &AppHandle) -> Result<Vec<ConnectionConfig>, String> {
+    migrate_connections(app)?;
     let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
     let connections: Vec<ConnectionConfig> = store
         .get("connections")
@@ -25,6 +82,59 @@ pub fn save_connections(app: &AppHandle, connections: &[ConnectionConfig]) -> Re
     Ok(())
 }
 
+pub fn get_connection_groups(app: &AppHandle) -> Result<Vec<ConnectionGroup>, String> {
+    migrate_connections(app)?;
+    let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
+    let groups: Vec<ConnectionGroup> = store
+        .get("groups")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(groups)
+}
+
+pub fn save_connection_groups(app: &AppHandle, groups: &[ConnectionGroup]) -> Result<(), String> {
+    let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "groups",
+        serde_json::to_value(groups).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Set while saved passwords are encrypted with a master passphrase
+pub fn get_master_key_info(app: &AppHandle) -> Result<Option<MasterKeyInfo>, String> {
+    let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get("masterKey")
+        .and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// Write the master key info together with the connections encrypted by it,
+/// so the two never disagree on disk
+pub fn save_master_key_info(
+    app: &AppHandle,
+    info: Option<&MasterKeyInfo>,
+    connections: &[ConnectionConfig],
+) -> Result<(), String> {
+    let store = app.store(CONNECTIONS_STORE).map_err(|e| e.to_string())?;
+    match info {
+        Some(info) => store.set(
+            "masterKey",
+            serde_json::to_value(info).map_err(|e| e.to_string())?,
+        ),
+        None => {
+            store.delete("masterKey");
+        }
+    }
+    store.set(
+        "connections",
+        serde_json::to_value(connections).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn get_query_history(app: &AppHandle) -> Result<Vec<QueryHistoryEntry>, String> {
     let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
     let history: Vec<QueryHistoryEntry> = store
@@ -44,21 +154,518 @@ pub fn save_query_history(app: &AppHandle, history: &[QueryHistoryEntry]) -> Res
     Ok(())
 }
 
+/// Drop the oldest unpinned entries beyond `QUERY_HISTORY_LIMIT`.
+/// History is newest first.
+pub fn trim_query_history(history: &mut Vec<QueryHistoryEntry>) {
+    let mut unpinned = 0;
+    history.retain(|entry| {
+        if entry.pinned {
+            return true;
+        }
+        unpinned += 1;
+        unpinned <= QUERY_HISTORY_LIMIT
+    });
+}
+
+/// Plan XML is stored compressed; see `compression`
 pub fn get_plan_history(app: &AppHandle) -> Result<Vec<PlanHistoryEntry>, String> {
+    migrate_plan_history(app)?;
     let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
-    let history: Vec<PlanHistoryEntry> = store
-        .get("planHistory")
-        .and_then(|v| serde_json::from_value(v).ok())
-        .unwrap_or_default();
+    // An entry that no longer decodes is skipped rather than failing the list
+    let history = get_plan_index(app)?
+        .iter()
+        .filter_map(|indexed| match load_plan(&store, &indexed.id) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::warn!(plan_id = %indexed.id, error = %e, "Skipping unreadable plan history entry");
+                None
+            }
+        })
+        .collect();
     Ok(history)
 }
 
+/// The latest plan saved for the query, read without loading the rest of
+/// the history
+pub fn latest_plan_for(
+    app: &AppHandle,
+    sql_hash: &str,
+) -> Result<Option<PlanHistoryEntry>, String> {
+    migrate_plan_history(app)?;
+    let Some(indexed) = get_plan_index(app)?
+        .into_iter()
+        .find(|p| p.sql_hash.as_deref() == Some(sql_hash))
+    else {
+        return Ok(None);
+    };
+    let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    match load_plan(&store, &indexed.id) {
+        Ok(entry) => Ok(Some(entry)),
+        Err(e) => {
+            tracing::warn!(plan_id = %indexed.id, error = %e, "Skipping unreadable plan history entry");
+            Ok(None)
+        }
+    }
+}
+
 pub fn save_plan_history(app: &AppHandle, history: &[PlanHistoryEntry]) -> Result<(), String> {
+    migrate_plan_history(app)?;
     let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    let previous = get_plan_index(app)?;
+    let mut index = Vec::with_capacity(history.len());
+    for entry in history {
+        let packed = serde_json::to_value(entry).map_err(|e| e.to_string())?;
+        index.push(store_plan(&store, packed)?);
+    }
+    let kept: HashSet<&str> = index.iter().map(|p| p.id.as_str()).collect();
+    for removed in previous.iter().filter(|p| !kept.contains(p.id.as_str())) {
+        store.delete(plan_key(&removed.id));
+    }
     store.set(
-        "planHistory",
-        serde_json::to_value(history).map_err(|e| e.to_string())?,
+        PLAN_INDEX_KEY,
+        serde_json::to_value(&index).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Add a plan to the front of the in-memory history and evict whatever the
+/// retention settings no longer allow. Only the new entry is compressed and
+/// stored, eviction works from the index, and nothing is written to disk;
+/// see `flush_plan_history`.
+pub fn push_plan_history_entry(
+    app: &AppHandle,
+    entry: &PlanHistoryEntry,
+    retention: &PlanRetention,
+) -> Result<usize, String> {
+    migrate_plan_history(app)?;
+    let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    let mut index = get_plan_index(app)?;
+    let packed = serde_json::to_value(entry).map_err(|e| e.to_string())?;
+    let indexed = store_plan(&store, packed)?;
+    index.retain(|p| p.id != indexed.id);
+    index.insert(0, indexed);
+
+    let (index, evicted) = evict_plans(index, retention);
+    for plan in &evicted {
+        store.delete(plan_key(&plan.id));
+    }
+    store.set(
+        PLAN_INDEX_KEY,
+        serde_json::to_value(&index).map_err(|e| e.to_string())?,
+    );
+    Ok(evicted.len())
+}
+
+/// Split the index, newest first, into the plans retention keeps and the
+/// ones it evicts. Only the recorded sizes are used; no plan is read.
+fn evict_plans(
+    index: Vec<PlanIndexEntry>,
+    retention: &PlanRetention,
+) -> (Vec<PlanIndexEntry>, Vec<PlanIndexEntry>) {
+    let sizes: Vec<(&str, usize)> = index
+        .iter()
+        .map(|p| (p.connection_id.as_str(), p.size))
+        .collect();
+    let keep = plans_to_keep(&sizes, retention);
+    let (kept, evicted): (Vec<_>, Vec<_>) =
+        index.into_iter().zip(keep).partition(|&(_, keep)| keep);
+    (
+        kept.into_iter().map(|(plan, _)| plan).collect(),
+        evicted.into_iter().map(|(plan, _)| plan).collect(),
+    )
+}
+
+/// What saving a plan needs to know about the plans already in history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlanIndexEntry {
+    id: String,
+    connection_id: String,
+    #[serde(default)]
+    sql_hash: Option<String>,
+    /// Stored (compressed) size in bytes
+    size: usize,
+}
+
+fn plan_key(id: &str) -> String {
+    format!("{}{}", PLAN_KEY_PREFIX, id)
+}
+
+fn get_plan_index(app: &AppHandle) -> Result<Vec<PlanIndexEntry>, String> {
+    let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get(PLAN_INDEX_KEY)
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default())
+}
+
+fn load_plan<R: tauri::Runtime>(store: &Store<R>, id: &str) -> Result<PlanHistoryEntry, String> {
+    let mut entry = store.get(plan_key(id)).ok_or("Plan entry is missing")?;
+    compression::unpack_plan_entry(&mut entry)?;
+    serde_json::from_value(entry).map_err(|e| e.to_string())
+}
+
+/// Compress a serialized entry and store it under its own key
+fn store_plan<R: tauri::Runtime>(
+    store: &Store<R>,
+    mut entry: serde_json::Value,
+) -> Result<PlanIndexEntry, String> {
+    compression::pack_plan_entry(&mut entry)?;
+    let indexed = index_plan(&entry).ok_or("Plan history entry has no id")?;
+    store.set(plan_key(&indexed.id), entry);
+    Ok(indexed)
+}
+
+fn index_plan(entry: &serde_json::Value) -> Option<PlanIndexEntry> {
+    let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).map(str::to_string);
+    Some(PlanIndexEntry {
+        id: field("id")?,
+        connection_id: field("connectionId").unwrap_or_default(),
+        sql_hash: field("sqlHash"),
+        size: entry.to_string().len(),
+    })
+}
+
+/// Older versions kept the whole history as one array under `planHistory`.
+/// Split it into one key per plan plus the index.
+fn migrate_plan_history(app: &AppHandle) -> Result<(), String> {
+    let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    let Some(legacy) = store.get(LEGACY_PLAN_HISTORY_KEY) else {
+        return Ok(());
+    };
+    let stored: Vec<serde_json::Value> = serde_json::from_value(legacy).unwrap_or_default();
+    let mut index = Vec::with_capacity(stored.len());
+    for entry in stored {
+        match index_plan(&entry) {
+            Some(indexed) => {
+                store.set(plan_key(&indexed.id), entry);
+                index.push(indexed);
+            }
+            None => tracing::warn!("Dropping plan history entry without an id"),
+        }
+    }
+    store.set(
+        PLAN_INDEX_KEY,
+        serde_json::to_value(&index).map_err(|e| e.to_string())?,
+    );
+    store.delete(LEGACY_PLAN_HISTORY_KEY);
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write pending history changes to disk
+pub fn flush_plan_history(app: &AppHandle) -> Result<(), String> {
+    let store = app.store(HISTORY_STORE).map_err(|e| e.to_string())?;
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Which plans to keep, given their connection and stored size, newest
+/// first. Once the size limit is reached every older plan goes too; the
+/// newest plan is kept even if it alone is over the limit.
+pub fn plans_to_keep(plans: &[(&str, usize)], retention: &PlanRetention) -> Vec<bool> {
+    let max_bytes = retention.max_bytes();
+    let mut per_connection: HashMap<&str, usize> = HashMap::new();
+    let mut total = 0;
+    let mut full = false;
+    plans
+        .iter()
+        .enumerate()
+        .map(|(i, &(connection_id, size))| {
+            if full || (i > 0 && total + size > max_bytes) {
+                full = true;
+                return false;
+            }
+            let count = per_connection.entry(connection_id).or_default();
+            if *count >= retention.limit_for(connection_id) {
+                return false;
+            }
+            *count += 1;
+            total += size;
+            true
+        })
+        .collect()
+}
+
+pub fn get_snippets(app: &AppHandle) -> Result<Vec<Snippet>, String> {
+    let store = app.store(SNIPPETS_STORE).map_err(|e| e.to_string())?;
+    let snippets: Vec<Snippet> = store
+        .get("snippets")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(snippets)
+}
+
+pub fn save_snippets(app: &AppHandle, snippets: &[Snippet]) -> Result<(), String> {
+    let store = app.store(SNIPPETS_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "snippets",
+        serde_json::to_value(snippets).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_plan_annotations(app: &AppHandle) -> Result<Vec<PlanAnnotation>, String> {
+    let store = app.store(ANNOTATIONS_STORE).map_err(|e| e.to_string())?;
+    let annotations: Vec<PlanAnnotation> = store
+        .get("annotations")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(annotations)
+}
+
+pub fn save_plan_annotations(
+    app: &AppHandle,
+    annotations: &[PlanAnnotation],
+) -> Result<(), String> {
+    let store = app.store(ANNOTATIONS_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "annotations",
+        serde_json::to_value(annotations).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_scheduled_queries(app: &AppHandle) -> Result<Vec<ScheduledQuery>, String> {
+    let store = app.store(SCHEDULES_STORE).map_err(|e| e.to_string())?;
+    let schedules: Vec<ScheduledQuery> = store
+        .get("schedules")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(schedules)
+}
+
+pub fn save_scheduled_queries(app: &AppHandle, schedules: &[ScheduledQuery]) -> Result<(), String> {
+    let store = app.store(SCHEDULES_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "schedules",
+        serde_json::to_value(schedules).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn get_baselines(app: &AppHandle) -> Result<Vec<Baseline>, String> {
+    let store = app.store(BASELINES_STORE).map_err(|e| e.to_string())?;
+    let baselines: Vec<Baseline> = store
+        .get("baselines")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(baselines)
+}
+
+pub fn save_baselines(app: &AppHandle, baselines: &[Baseline]) -> Result<(), String> {
+    let store = app.store(BASELINES_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "baselines",
+        serde_json::to_value(baselines).map_err(|e| e.to_string())?,
     );
     store.save().map_err(|e| e.to_string())?;
     Ok(())
 }
+
+pub fn get_workspaces(app: &AppHandle) -> Result<Vec<WorkspaceState>, String> {
+    let store = app.store(WORKSPACE_STORE).map_err(|e| e.to_string())?;
+    let workspaces: Vec<WorkspaceState> = store
+        .get("workspaces")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(workspaces)
+}
+
+pub fn save_workspaces(app: &AppHandle, workspaces: &[WorkspaceState]) -> Result<(), String> {
+    let store = app.store(WORKSPACE_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "workspaces",
+        serde_json::to_value(workspaces).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn thumbnail_key(plan_id: &str, format: PlanImageFormat) -> String {
+    match format {
+        PlanImageFormat::Svg => format!("{}.svg", plan_id),
+        PlanImageFormat::Png => format!("{}.png", plan_id),
+    }
+}
+
+fn get_thumbnails(app: &AppHandle) -> Result<HashMap<String, PlanThumbnail>, String> {
+    let store = app.store(THUMBNAILS_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get("thumbnails")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default())
+}
+
+pub fn get_plan_thumbnail(
+    app: &AppHandle,
+    plan_id: &str,
+    format: PlanImageFormat,
+) -> Result<Option<PlanThumbnail>, String> {
+    Ok(get_thumbnails(app)?.remove(&thumbnail_key(plan_id, format)))
+}
+
+/// Cache a thumbnail, dropping those of plans no longer in `plan_ids`
+pub fn save_plan_thumbnail(
+    app: &AppHandle,
+    thumbnail: &PlanThumbnail,
+    plan_ids: &HashSet<&str>,
+) -> Result<(), String> {
+    let mut thumbnails = get_thumbnails(app)?;
+    thumbnails.retain(|_, t| plan_ids.contains(t.plan_id.as_str()));
+    thumbnails.insert(
+        thumbnail_key(&thumbnail.plan_id, thumbnail.image.format),
+        thumbnail.clone(),
+    );
+    let store = app.store(THUMBNAILS_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "thumbnails",
+        serde_json::to_value(thumbnails).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Move `database` to the front of the most-recently-used list
+pub fn remember_database(recent: &mut Vec<String>, database: &str) {
+    recent.retain(|d| !d.eq_ignore_ascii_case(database));
+    recent.insert(0, database.to_string());
+    recent.truncate(RECENT_DATABASES_LIMIT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(id: usize, pinned: bool) -> QueryHistoryEntry {
+        QueryHistoryEntry {
+            id: id.to_string(),
+            sql: "SELECT 1".to_string(),
+            connection_id: "c".to_string(),
+            connection_name: "c".to_string(),
+            executed_at: Utc::now(),
+            duration_ms: 0,
+            success: true,
+            error: None,
+            sql_hash: None,
+            fingerprint: None,
+            pinned,
+            label: None,
+            scheduled_query_id: None,
+        }
+    }
+
+    #[test]
+    fn test_trim_query_history_keeps_pinned_entries() {
+        let mut history: Vec<QueryHistoryEntry> = (0..QUERY_HISTORY_LIMIT + 10)
+            .map(|i| entry(i, false))
+            .collect();
+        history.push(entry(999, true));
+
+        trim_query_history(&mut history);
+
+        assert_eq!(history.len(), QUERY_HISTORY_LIMIT + 1);
+        assert_eq!(history[0].id, "0");
+        assert_eq!(history.last().unwrap().id, "999");
+    }
+
+    #[test]
+    fn test_plans_to_keep_applies_connection_and_size_limits() {
+        let retention = PlanRetention {
+            max_size_mb: 1,
+            max_plans_per_connection: 2,
+            connection_limits: HashMap::from([("b".to_string(), 1)]),
+        };
+        let mb = 1024 * 1024;
+        let plans = [
+            ("a", 100),
+            ("b", 100),
+            ("a", 100),
+            ("b", 100),
+            ("a", 100),
+            ("c", mb),
+            ("c", 1),
+        ];
+        assert_eq!(
+            plans_to_keep(&plans, &retention),
+            vec![true, true, true, false, false, false, false]
+        );
+
+        // An oversized plan still stays when it is the newest
+        assert_eq!(
+            plans_to_keep(&[("a", 2 * mb), ("a", 1)], &retention),
+            vec![true, false]
+        );
+    }
+
+    #[test]
+    fn test_evict_plans_uses_indexed_sizes() {
+        let retention = PlanRetention {
+            max_size_mb: 1,
+            max_plans_per_connection: 2,
+            connection_limits: HashMap::new(),
+        };
+        let indexed = |id: &str, connection_id: &str, size: usize| PlanIndexEntry {
+            id: id.to_string(),
+            connection_id: connection_id.to_string(),
+            sql_hash: None,
+            size,
+        };
+        let index = vec![
+            indexed("p4", "a", 100),
+            indexed("p3", "a", 100),
+            indexed("p2", "a", 100),
+            indexed("p1", "b", 1024 * 1024),
+        ];
+
+        let (kept, evicted) = evict_plans(index, &retention);
+        let ids = |plans: &[PlanIndexEntry]| plans.iter().map(|p| p.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&kept), ["p4", "p3"]);
+        assert_eq!(ids(&evicted), ["p2", "p1"]);
+    }
+
+    #[test]
+    fn test_index_plan_reads_stored_entry() {
+        let mut stored = serde_json::json!({
+            "id": "p1",
+            "connectionId": "c",
+            "sqlHash": "h",
+            "planXml": "<ShowPlanXML />",
+        });
+        compression::pack_plan_entry(&mut stored).unwrap();
+
+        let indexed = index_plan(&stored).unwrap();
+        assert_eq!(indexed.id, "p1");
+        assert_eq!(indexed.connection_id, "c");
+        assert_eq!(indexed.sql_hash.as_deref(), Some("h"));
+        assert_eq!(indexed.size, stored.to_string().len());
+
+        assert!(index_plan(&serde_json::json!({ "connectionId": "c" })).is_none());
+    }
+
+    #[test]
+    fn test_remember_database_keeps_most_recent_first() {
+        let mut recent: Vec<String> = (0..RECENT_DATABASES_LIMIT)
+            .map(|i| format!("db{}", i))
+            .collect();
+        remember_database(&mut recent, "DB3");
+        assert_eq!(recent[0], "DB3");
+        assert_eq!(recent.len(), RECENT_DATABASES_LIMIT);
+        assert_eq!(
+            recent
+                .iter()
+                .filter(|d| d.eq_ignore_ascii_case("db3"))
+                .count(),
+            1
+        );
+
+        remember_database(&mut recent, "new");
+        assert_eq!(recent[0], "new");
+        assert_eq!(recent.len(), RECENT_DATABASES_LIMIT);
+        assert!(!recent.contains(&"db9".to_string()));
+    }
+}
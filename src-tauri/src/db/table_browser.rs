@@ -0,0 +1,392 @@
+use serde_json::Value;
+
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_identifier, quote_literal, quote_object_name};
+use super::rows::{get_i64, get_string};
+use super::types::{PageCursor, PagingStrategy};
+
+// Page queries for browsing a table's data. With a primary key or a unique
+// index on non-nullable columns, next and previous pages seek from the key
+// of the rows on screen (keyset pagination), so a page costs the same
+// wherever it is. Otherwise, or when jumping to an arbitrary page, pages
+// are read with OFFSET / FETCH.
+
+/// Largest page browse_table returns
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+/// Key types whose grid values convert back to the same key exactly
+const SEEKABLE_TYPES: &[&str] = &[
+    "tinyint",
+    "smallint",
+    "int",
+    "bigint",
+    "bit",
+    "char",
+    "varchar",
+    "nchar",
+    "nvarchar",
+    "uniqueidentifier",
+    "date",
+    "datetime",
+    "datetime2",
+    "smalldatetime",
+    "datetimeoffset",
+    "time",
+];
+
+/// Column of the table, in column order
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableColumn {
+    pub name: String,
+    pub type_name: String,
+}
+
+/// Column of a unique index, as read from the catalog
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexKeyColumn {
+    pub index_id: i64,
+    pub name: String,
+    pub type_name: String,
+    pub nullable: bool,
+}
+
+/// Where a page starts
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seek {
+    /// First page of the keyset
+    Start,
+    /// Rows after the key of the last row on screen
+    After(Vec<Value>),
+    /// Rows before the key of the first row on screen, read backwards
+    Before(Vec<Value>),
+    Offset(usize),
+}
+
+pub struct PageQuery {
+    pub sql: String,
+    /// The rows come back in reverse order and must be flipped
+    pub reversed: bool,
+}
+
+/// First unique key the rows can be paged by: the primary key when usable,
+/// then other unique indexes in index order. Rows are ordered by primary key
+/// first, then index id, then key ordinal.
+pub fn choose_key(columns: &[IndexKeyColumn]) -> Option<Vec<TableColumn>> {
+    let mut start = 0;
+    while start < columns.len() {
+        let index_id = columns[start].index_id;
+        let end = columns[start..]
+            .iter()
+            .position(|c| c.index_id != index_id)
+            .map(|p| start + p)
+            .unwrap_or(columns.len());
+        let key = &columns[start..end];
+        if key.iter().all(|c| {
+            !c.nullable
+                && SEEKABLE_TYPES
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(&c.type_name))
+        }) {
+            return Some(
+                key.iter()
+                    .map(|c| TableColumn {
+                        name: c.name.clone(),
+                        type_name: c.type_name.clone(),
+                    })
+                    .collect(),
+            );
+        }
+        start = end;
+    }
+    None
+}
+
+/// How the requested page is read. Keyset paging needs a key the table is
+/// ordered by and a cursor of the page next to the requested one.
+pub fn plan_seek(
+    key: Option<&[TableColumn]>,
+    order_column: Option<&str>,
+    page: usize,
+    cursor: Option<&PageCursor>,
+) -> (PagingStrategy, Seek) {
+    let keyset = key.is_some_and(|key| {
+        order_column.is_none_or(|order| order.eq_ignore_ascii_case(&key[0].name))
+    });
+    if !keyset {
+        return (PagingStrategy::Offset, Seek::Offset(page));
+    }
+    let seek = match cursor {
+        _ if page == 0 => Seek::Start,
+        Some(cursor) if cursor.page + 1 == page => Seek::After(cursor.last_key.clone()),
+        Some(cursor) if cursor.page == page + 1 => Seek::Before(cursor.first_key.clone()),
+        _ => Seek::Offset(page),
+    };
+    (PagingStrategy::Keyset, seek)
+}
+
+/// T-SQL literal of a key value typed for its column, so comparisons stay
+/// seekable (no N'' against a varchar key)
+fn key_literal(column: &TableColumn, value: &Value) -> Result<String, String> {
+    match value {
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(if *b { "1" } else { "0" }.to_string()),
+        Value::String(s)
+            if column.type_name.eq_ignore_ascii_case("varchar")
+                || column.type_name.eq_ignore_ascii_case("char") =>
+        {
+            Ok(format!("'{}'", s.replace('\'', "''")))
+        }
+        Value::String(s) => Ok(quote_literal(s)),
+        other => Err(format!(
+            "Cannot page by {}: unexpected key value {}",
+            column.name, other
+        )),
+    }
+}
+
+/// `(k1 > v1) OR (k1 = v1 AND k2 > v2) ...`
+fn seek_predicate(key: &[TableColumn], values: &[Value], greater: bool) -> Result<String, String> {
+    if values.len() != key.len() {
+        return Err("The page cursor does not match the table key".to_string());
+    }
+    let op = if greater { ">" } else { "<" };
+    let mut terms = Vec::new();
+    for i in 0..key.len() {
+        let mut parts = Vec::new();
+        for j in 0..i {
+            parts.push(format!(
+                "{} = {}",
+                quote_identifier(&key[j].name),
+                key_literal(&key[j], &values[j])?
+            ));
+        }
+        parts.push(format!(
+            "{} {} {}",
+            quote_identifier(&key[i].name),
+            op,
+            key_literal(&key[i], &values[i])?
+        ));
+        terms.push(format!("({})", parts.join(" AND ")));
+    }
+    Ok(terms.join(" OR "))
+}
+
+fn order_by(columns: &[&str], descending: bool) -> String {
+    let direction = if descending { "DESC" } else { "ASC" };
+    columns
+        .iter()
+        .map(|c| format!("{} {}", quote_identifier(c), direction))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// SELECT for one page, reading one row past the page to tell whether more
+/// follow
+pub fn page_query(
+    table: &str,
+    key: Option<&[TableColumn]>,
+    order_column: Option<&str>,
+    descending: bool,
+    page_size: usize,
+    seek: &Seek,
+) -> Result<PageQuery, String> {
+    let table = quote_object_name(table)?;
+    let fetch = page_size + 1;
+    let key_names: Vec<&str> = key
+        .unwrap_or_default()
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+
+    let query = |predicate: Option<String>, descending: bool| {
+        format!(
+            "SELECT TOP ({}) * FROM {}{} ORDER BY {}",
+            fetch,
+            table,
+            predicate
+                .map(|p| format!(" WHERE {}", p))
+                .unwrap_or_default(),
+            order_by(&key_names, descending)
+        )
+    };
+    let sql = match seek {
+        Seek::Start => query(None, descending),
+        Seek::After(values) => {
+            let key = key.ok_or("Keyset paging needs a key")?;
+            query(Some(seek_predicate(key, values, !descending)?), descending)
+        }
+        Seek::Before(values) => {
+            let key = key.ok_or("Keyset paging needs a key")?;
+            let sql = query(Some(seek_predicate(key, values, descending)?), !descending);
+            return Ok(PageQuery {
+                sql,
+                reversed: true,
+            });
+        }
+        Seek::Offset(page) => {
+            // The key breaks ties so pages never overlap
+            let mut order: Vec<&str> = order_column.into_iter().collect();
+            order.extend(
+                key_names
+                    .iter()
+                    .filter(|k| !order_column.is_some_and(|o| o.eq_ignore_ascii_case(k))),
+            );
+            let order = if order.is_empty() {
+                "(SELECT NULL)".to_string()
+            } else {
+                order_by(&order, descending)
+            };
+            format!(
+                "SELECT * FROM {} ORDER BY {} OFFSET {} ROWS FETCH NEXT {} ROWS ONLY",
+                table,
+                order,
+                page.saturating_mul(page_size),
+                fetch
+            )
+        }
+    };
+    Ok(PageQuery {
+        sql,
+        reversed: false,
+    })
+}
+
+/// Columns and unique keys of a table or view; an error when it does not exist
+pub async fn table_schema(
+    client: &mut TiberiusClient,
+    table: &str,
+) -> Result<(Vec<TableColumn>, Vec<IndexKeyColumn>), String> {
+    let object = quote_literal(&quote_object_name(table)?);
+    let sql = format!(
+        "IF OBJECT_ID({object}) IS NULL \
+             RAISERROR(N'Table not found: %s', 16, 1, {name}); \
+         SELECT c.name, TYPE_NAME(c.system_type_id) \
+         FROM sys.columns c \
+         WHERE c.object_id = OBJECT_ID({object}) \
+         ORDER BY c.column_id; \
+         SELECT i.index_id, c.name, TYPE_NAME(c.system_type_id), CAST(c.is_nullable AS int) \
+         FROM sys.indexes i \
+         JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id \
+         JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+         WHERE i.object_id = OBJECT_ID({object}) AND i.is_unique = 1 AND i.has_filter = 0 \
+           AND ic.key_ordinal > 0 \
+         ORDER BY i.is_primary_key DESC, i.index_id, ic.key_ordinal",
+        name = quote_literal(table),
+    );
+    let results = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| format!("Failed to read the table schema: {}", e))?
+        .into_results()
+        .await
+        .map_err(|e| format!("Failed to read the table schema: {}", e))?;
+    let columns = results
+        .first()
+        .map(|rows| {
+            rows.iter()
+                .map(|row| TableColumn {
+                    name: get_string(row, 0).unwrap_or_default(),
+                    type_name: get_string(row, 1).unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let keys = results
+        .get(1)
+        .map(|rows| {
+            rows.iter()
+                .map(|row| IndexKeyColumn {
+                    index_id: get_i64(row, 0).unwrap_or_default(),
+                    name: get_string(row, 1).unwrap_or_default(),
+                    type_name: get_string(row, 2).unwrap_or_default(),
+                    nullable: get_i64(row, 3) == Some(1),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((columns, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key_column(index_id: i64, name: &str, type_name: &str, nullable: bool) -> IndexKeyColumn {
+        IndexKeyColumn {
+            index_id,
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            nullable,
+        }
+    }
+
+    fn key() -> Vec<TableColumn> {
+        choose_key(&[
+            key_column(1, "Payload", "varbinary", false),
+            key_column(2, "Email", "nvarchar", true),
+            key_column(3, "TenantId", "int", false),
+            key_column(3, "Code", "varchar", false),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_key_skips_unseekable_indexes() {
+        let names: Vec<String> = key().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["TenantId", "Code"]);
+        assert!(choose_key(&[key_column(1, "Id", "decimal", false)]).is_none());
+    }
+
+    #[test]
+    fn test_adjacent_pages_seek_by_key() {
+        let key = key();
+        let cursor = PageCursor {
+            page: 3,
+            first_key: vec![json!(1), json!("A'1")],
+            last_key: vec![json!(2), json!("B")],
+        };
+
+        let (strategy, seek) = plan_seek(Some(&key), None, 4, Some(&cursor));
+        assert_eq!(strategy, PagingStrategy::Keyset);
+        let query = page_query("dbo.Codes", Some(&key), None, false, 50, &seek).unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT TOP (51) * FROM [dbo].[Codes] WHERE ([TenantId] > 2) OR \
+             ([TenantId] = 2 AND [Code] > 'B') ORDER BY [TenantId] ASC, [Code] ASC"
+        );
+
+        let (_, seek) = plan_seek(Some(&key), Some("tenantid"), 2, Some(&cursor));
+        let query = page_query("dbo.Codes", Some(&key), None, false, 50, &seek).unwrap();
+        assert!(query.reversed);
+        assert_eq!(
+            query.sql,
+            "SELECT TOP (51) * FROM [dbo].[Codes] WHERE ([TenantId] < 1) OR \
+             ([TenantId] = 1 AND [Code] < 'A''1') ORDER BY [TenantId] DESC, [Code] DESC"
+        );
+
+        // Jumping away from the cursor falls back to OFFSET
+        let (_, seek) = plan_seek(Some(&key), None, 9, Some(&cursor));
+        assert_eq!(seek, Seek::Offset(9));
+    }
+
+    #[test]
+    fn test_other_orders_use_offset() {
+        let key = key();
+        let (strategy, seek) = plan_seek(Some(&key), Some("Name"), 2, None);
+        assert_eq!(strategy, PagingStrategy::Offset);
+        let query = page_query("Codes", Some(&key), Some("Name"), true, 10, &seek).unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT * FROM [Codes] ORDER BY [Name] DESC, [TenantId] DESC, [Code] DESC \
+             OFFSET 20 ROWS FETCH NEXT 11 ROWS ONLY"
+        );
+
+        let (_, seek) = plan_seek(None, None, 0, None);
+        let query = page_query("Heap", None, None, false, 10, &seek).unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT * FROM [Heap] ORDER BY (SELECT NULL) OFFSET 0 ROWS FETCH NEXT 11 ROWS ONLY"
+        );
+    }
+}
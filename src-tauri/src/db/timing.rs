@@ -0,0 +1,125 @@
+use std::time::Instant;
+
+use tiberius::Column;
+
+use super::splitter::split_statements;
+use super::types::StatementTiming;
+
+// Per-statement timings for a batch sent to the server as a whole. The
+// server ends each statement of a batch with a DONE token; the time each
+// one was read from the stream closes that statement's interval. Statements
+// are only named when the batch split into as many statements as DONE
+// tokens arrived: SET and DECLARE may send none, and procedures send their
+// own, so other batches get no breakdown rather than a misattributed one.
+
+const PREVIEW_CHARS: usize = 80;
+
+/// Column of the rowset STATISTICS XML sends after each statement
+const PLAN_COLUMN: &str = "Microsoft SQL Server 2005 XML Showplan";
+
+/// Times of the DONE tokens that end statements. With STATISTICS XML each
+/// statement is followed by a plan rowset with a DONE token of its own,
+/// which is left out.
+#[derive(Default)]
+pub struct StatementEnds {
+    pub done_at: Vec<Instant>,
+    skip_next: bool,
+}
+
+impl StatementEnds {
+    pub fn add(&mut self, times: Vec<Instant>) {
+        for time in times {
+            if std::mem::take(&mut self.skip_next) {
+                continue;
+            }
+            self.done_at.push(time);
+        }
+    }
+
+    pub fn plan_rowset_started(&mut self) {
+        self.skip_next = true;
+    }
+}
+
+pub fn is_plan_rowset(columns: &[Column]) -> bool {
+    matches!(columns, [column] if column.name() == PLAN_COLUMN)
+}
+
+/// Time between DONE tokens read since `started`, one entry per statement
+pub fn statement_timings(sql: &str, started: Instant, done_at: &[Instant]) -> Vec<StatementTiming> {
+    let spans = split_statements(sql);
+    if spans.len() < 2 || spans.len() != done_at.len() {
+        return Vec::new();
+    }
+    let mut previous = started;
+    spans
+        .iter()
+        .zip(done_at)
+        .map(|(span, &done)| {
+            let elapsed = done.saturating_duration_since(previous);
+            previous = done;
+            StatementTiming {
+                statement_preview: preview(&sql[span.start..span.end]),
+                elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            }
+        })
+        .collect()
+}
+
+/// Statement on a single line, shortened for display
+pub fn preview(statement: &str) -> String {
+    let collapsed = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= PREVIEW_CHARS {
+        return collapsed;
+    }
+    let cut: String = collapsed.chars().take(PREVIEW_CHARS - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_statement_timings() {
+        let started = Instant::now();
+        let done = [
+            started + Duration::from_millis(5),
+            started + Duration::from_millis(25),
+            started + Duration::from_millis(26),
+        ];
+        let sql = "SELECT 1; UPDATE t SET x = 1\nSELECT @@ROWCOUNT";
+        let timings = statement_timings(sql, started, &done);
+        let previews: Vec<&str> = timings.iter().map(|t| t.statement_preview.as_str()).collect();
+        assert_eq!(previews, vec!["SELECT 1", "UPDATE t SET x = 1", "SELECT @@ROWCOUNT"]);
+        let elapsed: Vec<u64> = timings.iter().map(|t| t.elapsed_ms.round() as u64).collect();
+        assert_eq!(elapsed, vec![5, 20, 1]);
+
+        // A DONE token is missing (or extra), so statements cannot be named
+        assert!(statement_timings(sql, started, &done[..2]).is_empty());
+        assert!(statement_timings("SELECT 1", started, &done[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_plan_rowset_done_tokens_are_skipped() {
+        let started = Instant::now();
+        let at = |ms| started + Duration::from_millis(ms);
+        let mut ends = StatementEnds::default();
+        ends.add(vec![at(1)]);
+        ends.plan_rowset_started();
+        ends.add(vec![at(2), at(7)]);
+        ends.plan_rowset_started();
+        ends.add(vec![at(8)]);
+        assert_eq!(ends.done_at, vec![at(1), at(7)]);
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("SELECT *\n  FROM   t"), "SELECT * FROM t");
+        let long = format!("SELECT {}", "x, ".repeat(50));
+        let shortened = preview(&long);
+        assert!(shortened.chars().count() <= 80);
+        assert!(shortened.ends_with("..."));
+    }
+}
@@ -0,0 +1,596 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ServerName};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use super::cert_pinning;
+use super::transport::DbStream;
+
+// TDS runs TLS inside the session: the handshake travels in PRELOGIN
+// packets, after which TLS records go over the bare stream. Tiberius can
+// only trust every certificate or a CA file, so this side runs the TLS
+// session itself and checks the server certificate against the pin inside
+// that handshake. Tiberius is then told the server does not encrypt and
+// speaks plain TDS through the session, login included. A server that
+// offers no encryption is used unencrypted, unless its certificate is
+// pinned.
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(15);
+
+const PRELOGIN: u8 = 0x12;
+const TABULAR_RESULT: u8 = 0x04;
+const END_OF_MESSAGE: u8 = 0x01;
+const HEADER_LEN: usize = 8;
+const PACKET_SIZE: usize = 4096;
+
+const TOKEN_VERSION: u8 = 0x00;
+const TOKEN_ENCRYPTION: u8 = 0x01;
+const TOKEN_TERMINATOR: u8 = 0xFF;
+const ENCRYPT_ON: u8 = 0x01;
+const ENCRYPT_NOT_SUP: u8 = 0x02;
+
+/// Negotiate encryption on a fresh stream and run the TLS handshake,
+/// holding the server to `pinned` when given. Returns the stream to log in
+/// over and the fingerprint of the certificate the server presented, or
+/// `None` when the server does not encrypt.
+pub async fn secure(
+    stream: DbStream,
+    server: &str,
+    pinned: Option<&str>,
+) -> Result<(TdsStream, Option<String>), String> {
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake(stream, server, pinned))
+        .await
+        .map_err(|_| "Timed out during the TLS handshake".to_string())?
+}
+
+async fn handshake(
+    mut stream: DbStream,
+    server: &str,
+    pinned: Option<&str>,
+) -> Result<(TdsStream, Option<String>), String> {
+    let mut packet_id = 1u8;
+    send(&mut stream, &prelogin_request(), &mut packet_id).await?;
+    let response = receive(&mut stream).await?;
+    if matches!(encryption_option(&response), None | Some(ENCRYPT_NOT_SUP)) {
+        // Without TLS there is no certificate; the check refuses pinned servers
+        cert_pinning::check(server, pinned, None)?;
+        tracing::warn!(
+            server,
+            "Server does not support encryption, connecting unencrypted"
+        );
+        return Ok((
+            TdsStream {
+                session: Session::Plain(stream),
+                request: Some(Vec::new()),
+                reply: prelogin_reply(&response),
+            },
+            None,
+        ));
+    }
+
+    let verifier = Arc::new(PinVerifier {
+        server: server.to_string(),
+        pinned: pinned.map(str::to_string),
+        presented: Mutex::new(None),
+        rejected: Mutex::new(None),
+    });
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier.clone())
+        .with_no_client_auth();
+    let name = ServerName::try_from(server)
+        .or_else(|_| ServerName::try_from("localhost"))
+        .map_err(|e| e.to_string())?;
+
+    let framing = PreloginFraming::new(stream, packet_id);
+    let mut tls = match TlsConnector::from(Arc::new(config))
+        .connect(name, framing)
+        .await
+    {
+        Ok(tls) => tls,
+        Err(e) => {
+            return Err(verifier
+                .rejected
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| format!("TLS handshake failed: {}", e)))
+        }
+    };
+    tls.get_mut().0.handshaking = false;
+    let presented = verifier
+        .presented
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or("Server did not present a certificate")?;
+
+    Ok((
+        TdsStream {
+            session: Session::Tls(Box::new(tls)),
+            request: Some(Vec::new()),
+            reply: prelogin_reply(&response),
+        },
+        Some(presented),
+    ))
+}
+
+/// Checks the certificate against the pin during the handshake. Signatures
+/// are still verified, so the server must hold the certificate's key.
+struct PinVerifier {
+    server: String,
+    pinned: Option<String>,
+    presented: Mutex<Option<String>>,
+    /// Why the certificate was refused, for a clearer error than rustls gives
+    rejected: Mutex<Option<String>>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let presented = cert_pinning::fingerprint(&end_entity.0);
+        match cert_pinning::check(&self.server, self.pinned.as_deref(), Some(&presented)) {
+            Ok(()) => {
+                *self.presented.lock().unwrap() = Some(presented);
+                Ok(ServerCertVerified::assertion())
+            }
+            Err(e) => {
+                *self.rejected.lock().unwrap() = Some(e.clone());
+                Err(rustls::Error::General(e))
+            }
+        }
+    }
+}
+
+/// The stream tiberius logs in over: plain TDS through this side's TLS
+/// session, or straight over the stream when the server does not encrypt.
+/// Tiberius's own PRELOGIN never reaches the server; it is answered with
+/// the server's reply, changed to say there is no encryption to set up.
+pub struct TdsStream {
+    session: Session,
+    /// Tiberius's PRELOGIN as far as it has been written, until complete
+    request: Option<Vec<u8>>,
+    /// What is left of the reply to hand back
+    reply: Vec<u8>,
+}
+
+impl AsyncRead for TdsStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.request.is_some() {
+            return Poll::Ready(Err(io::Error::other(
+                "Read before the pre-login request was sent",
+            )));
+        }
+        if !this.reply.is_empty() {
+            let len = this.reply.len().min(buf.remaining());
+            buf.put_slice(&this.reply[..len]);
+            this.reply.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+        match &mut this.session {
+            Session::Tls(tls) => Pin::new(tls.as_mut()).poll_read(cx, buf),
+            Session::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for TdsStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.request.as_mut() {
+            Some(request) => {
+                request.extend_from_slice(buf);
+                if message_complete(request) {
+                    this.request = None;
+                }
+                Poll::Ready(Ok(buf.len()))
+            }
+            None => match &mut this.session {
+                Session::Tls(tls) => Pin::new(tls.as_mut()).poll_write(cx, buf),
+                Session::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            },
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().session {
+            Session::Tls(tls) => Pin::new(tls.as_mut()).poll_flush(cx),
+            Session::Plain(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().session {
+            Session::Tls(tls) => Pin::new(tls.as_mut()).poll_shutdown(cx),
+            Session::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+enum Session {
+    Tls(Box<TlsStream<PreloginFraming>>),
+    /// The server does not encrypt and nothing is pinned for it
+    Plain(DbStream),
+}
+
+/// Carries the TLS handshake in PRELOGIN packets, then passes the TLS
+/// records through untouched
+struct PreloginFraming {
+    stream: DbStream,
+    handshaking: bool,
+    packet_id: u8,
+    header: [u8; HEADER_LEN],
+    header_read: usize,
+    /// Payload of the current incoming packet not read yet
+    payload_left: usize,
+    /// Handshake bytes written since the last flush
+    outgoing: Vec<u8>,
+    /// Packets being sent
+    sending: Vec<u8>,
+}
+
+impl PreloginFraming {
+    fn new(stream: DbStream, packet_id: u8) -> Self {
+        Self {
+            stream,
+            handshaking: true,
+            packet_id,
+            header: [0; HEADER_LEN],
+            header_read: 0,
+            payload_left: 0,
+            outgoing: Vec::new(),
+            sending: Vec::new(),
+        }
+    }
+}
+
+impl AsyncRead for PreloginFraming {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.handshaking {
+            return Pin::new(&mut this.stream).poll_read(cx, buf);
+        }
+
+        while this.payload_left == 0 {
+            while this.header_read < HEADER_LEN {
+                let mut header = ReadBuf::new(&mut this.header[this.header_read..]);
+                ready!(Pin::new(&mut this.stream).poll_read(cx, &mut header))?;
+                if header.filled().is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                this.header_read += header.filled().len();
+            }
+            this.header_read = 0;
+            let len = u16::from_be_bytes([this.header[2], this.header[3]]) as usize;
+            this.payload_left = len.checked_sub(HEADER_LEN).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid pre-login packet from server",
+                )
+            })?;
+        }
+
+        let read = {
+            let mut payload =
+                ReadBuf::new(buf.initialize_unfilled_to(this.payload_left.min(buf.remaining())));
+            ready!(Pin::new(&mut this.stream).poll_read(cx, &mut payload))?;
+            payload.filled().len()
+        };
+        buf.advance(read);
+        this.payload_left -= read;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for PreloginFraming {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if !this.handshaking {
+            return Pin::new(&mut this.stream).poll_write(cx, buf);
+        }
+        this.outgoing.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.sending.is_empty() && !this.outgoing.is_empty() {
+            this.sending = packets(PRELOGIN, &this.outgoing, &mut this.packet_id);
+            this.outgoing.clear();
+        }
+        while !this.sending.is_empty() {
+            let written = ready!(Pin::new(&mut this.stream).poll_write(cx, &this.sending))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            this.sending.drain(..written);
+        }
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// PRELOGIN payload with a VERSION and an ENCRYPTION option (ON)
+fn prelogin_request() -> Vec<u8> {
+    // Two 5-byte option headers and the terminator, then the option data
+    let data_offset = 2 * 5 + 1;
+    let mut payload = Vec::with_capacity(data_offset + 7);
+    for (token, offset, len) in [
+        (TOKEN_VERSION, data_offset, 6u16),
+        (TOKEN_ENCRYPTION, data_offset + 6, 1),
+    ] {
+        payload.push(token);
+        payload.extend_from_slice(&(offset as u16).to_be_bytes());
+        payload.extend_from_slice(&len.to_be_bytes());
+    }
+    payload.push(TOKEN_TERMINATOR);
+    payload.extend_from_slice(&[0; 6]);
+    payload.push(ENCRYPT_ON);
+    payload
+}
+
+/// Offset of the ENCRYPTION option value in a PRELOGIN payload
+fn encryption_offset(payload: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    while *payload.get(pos)? != TOKEN_TERMINATOR {
+        let token = payload[pos];
+        let offset = u16::from_be_bytes([*payload.get(pos + 1)?, *payload.get(pos + 2)?]);
+        if token == TOKEN_ENCRYPTION {
+            return Some(offset as usize).filter(|&o| o < payload.len());
+        }
+        pos += 5;
+    }
+    None
+}
+
+/// Value of the ENCRYPTION option in a PRELOGIN response
+fn encryption_option(payload: &[u8]) -> Option<u8> {
+    encryption_offset(payload).map(|offset| payload[offset])
+}
+
+/// The server's PRELOGIN response as a packet for tiberius, saying the
+/// server does not encrypt: the session is already encrypted underneath
+fn prelogin_reply(response: &[u8]) -> Vec<u8> {
+    let mut payload = response.to_vec();
+    if let Some(offset) = encryption_offset(&payload) {
+        payload[offset] = ENCRYPT_NOT_SUP;
+    }
+    packets(TABULAR_RESULT, &payload, &mut 1)
+}
+
+/// Whether `bytes` hold whole packets up to one that ends a message
+fn message_complete(bytes: &[u8]) -> bool {
+    let mut pos = 0;
+    while pos + HEADER_LEN <= bytes.len() {
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        if len < HEADER_LEN || pos + len > bytes.len() {
+            return false;
+        }
+        if bytes[pos + 1] & END_OF_MESSAGE != 0 {
+            return true;
+        }
+        pos += len;
+    }
+    false
+}
+
+/// A message as packets of type `kind`
+fn packets(kind: u8, payload: &[u8], packet_id: &mut u8) -> Vec<u8> {
+    let mut packets = Vec::with_capacity(payload.len() + HEADER_LEN);
+    let mut chunks = payload.chunks(PACKET_SIZE - HEADER_LEN).peekable();
+    while let Some(chunk) = chunks.next() {
+        let status = if chunks.peek().is_none() {
+            END_OF_MESSAGE
+        } else {
+            0
+        };
+        packets.extend_from_slice(&[kind, status]);
+        packets.extend_from_slice(&((HEADER_LEN + chunk.len()) as u16).to_be_bytes());
+        packets.extend_from_slice(&[0, 0, *packet_id, 0]);
+        packets.extend_from_slice(chunk);
+        *packet_id = packet_id.wrapping_add(1);
+    }
+    packets
+}
+
+/// Send a message as PRELOGIN packets
+async fn send(stream: &mut DbStream, payload: &[u8], packet_id: &mut u8) -> Result<(), String> {
+    stream
+        .write_all(&packets(PRELOGIN, payload, packet_id))
+        .await
+        .map_err(|e| format!("Failed to send pre-login: {}", e))?;
+    stream
+        .flush()
+        .await
+        .map_err(|e| format!("Failed to send pre-login: {}", e))
+}
+
+/// Read packets up to the end of the message and return their payload
+async fn receive(stream: &mut DbStream) -> Result<Vec<u8>, String> {
+    let mut message = Vec::new();
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        stream
+            .read_exact(&mut header)
+            .await
+            .map_err(|e| format!("Failed to read pre-login response: {}", e))?;
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if len < HEADER_LEN {
+            return Err("Invalid pre-login response from server".to_string());
+        }
+        let start = message.len();
+        message.resize(start + len - HEADER_LEN, 0);
+        stream
+            .read_exact(&mut message[start..])
+            .await
+            .map_err(|e| format!("Failed to read pre-login response: {}", e))?;
+        if header[1] & END_OF_MESSAGE != 0 {
+            return Ok(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use rustls::{PrivateKey, ServerConfig};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Self-signed P-256 certificate for localhost and its PKCS#8 key
+    const TEST_CERT: &str = "MIIBlDCCATugAwIBAgIUc8cWzE2kZaAg1axznKBBX+Lh9IAwCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJbG9jYWxob3N0MCAXDTI2MTAxNzA2NDQxMloYDzIxMjYwOTIzMDY0NDEyWjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAARBIdAFyvnvMYwfaDGX9Ryn7i1BbXxYQkfvGfM1TAWh1WmEBeZDEUNZcyFNDkPhp341SPcQ517lj4oXeXY/l4Cao2kwZzAdBgNVHQ4EFgQUWaGFNtM7GI9cgROXsw7wKTjZOo0wHwYDVR0jBBgwFoAUWaGFNtM7GI9cgROXsw7wKTjZOo0wDwYDVR0TAQH/BAUwAwEB/zAUBgNVHREEDTALgglsb2NhbGhvc3QwCgYIKoZIzj0EAwIDRwAwRAIgM6yiehq0XP00Fc2bunEDkNg6J4O4gwmRz0ZJ6XUz46wCIA7dtJnpjuHJ2Q0eeaukVv/rHYnYOvOKFzUT1c/670hs";
+    const TEST_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgvtQE0BkYmX4WO/uyOxGypGRrb9xsT4znhUjiSw5/h/ihRANCAARBIdAFyvnvMYwfaDGX9Ryn7i1BbXxYQkfvGfM1TAWh1WmEBeZDEUNZcyFNDkPhp341SPcQ517lj4oXeXY/l4Ca";
+
+    /// A server that answers PRELOGIN with `encryption`, runs the TLS
+    /// handshake in PRELOGIN packets unless it does not encrypt, and returns
+    /// the first 5 bytes sent over the session
+    async fn fake_server(listener: TcpListener, encryption: u8) -> Option<Vec<u8>> {
+        let (tcp, _) = listener.accept().await.ok()?;
+        let mut stream = DbStream::Tcp(tcp);
+        receive(&mut stream).await.ok()?;
+        let mut response = prelogin_request();
+        let offset = encryption_offset(&response)?;
+        response[offset] = encryption;
+        send(&mut stream, &response, &mut 1).await.ok()?;
+        let mut received = vec![0; 5];
+        if encryption == ENCRYPT_NOT_SUP {
+            stream.read_exact(&mut received).await.ok()?;
+            return Some(received);
+        }
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(BASE64.decode(TEST_CERT).unwrap())],
+                PrivateKey(BASE64.decode(TEST_KEY).unwrap()),
+            )
+            .unwrap();
+        let mut tls = tokio_rustls::TlsAcceptor::from(Arc::new(config))
+            .accept(PreloginFraming::new(stream, 1))
+            .await
+            .ok()?;
+        tls.get_mut().0.handshaking = false;
+        tls.read_exact(&mut received).await.ok()?;
+        Some(received)
+    }
+
+    async fn connect(
+        encryption: u8,
+        pinned: Option<&str>,
+    ) -> (
+        Result<(TdsStream, Option<String>), String>,
+        tokio::task::JoinHandle<Option<Vec<u8>>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(fake_server(listener, encryption));
+        let stream = DbStream::Tcp(TcpStream::connect(addr).await.unwrap());
+        (secure(stream, "localhost", pinned).await, server)
+    }
+
+    /// Log in the way tiberius does: its PRELOGIN is answered here, without
+    /// encryption, and the login goes over the session
+    async fn log_in(stream: &mut TdsStream) {
+        stream
+            .write_all(&packets(PRELOGIN, &prelogin_request(), &mut 1))
+            .await
+            .unwrap();
+        let mut header = [0u8; HEADER_LEN];
+        stream.read_exact(&mut header).await.unwrap();
+        let mut reply = vec![0; u16::from_be_bytes([header[2], header[3]]) as usize - HEADER_LEN];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(encryption_option(&reply), Some(ENCRYPT_NOT_SUP));
+
+        stream.write_all(b"LOGIN").await.unwrap();
+        stream.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_runs_over_pinned_certificate() {
+        let pin = cert_pinning::fingerprint(&BASE64.decode(TEST_CERT).unwrap());
+        let (connected, server) = connect(ENCRYPT_ON, Some(&pin)).await;
+        let (mut stream, presented) = connected.unwrap();
+        assert_eq!(presented, Some(pin));
+
+        log_in(&mut stream).await;
+        assert_eq!(server.await.unwrap().as_deref(), Some(&b"LOGIN"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_unpinned_server_without_encryption_connects_unencrypted() {
+        let (connected, server) = connect(ENCRYPT_NOT_SUP, None).await;
+        let (mut stream, presented) = connected.unwrap();
+        assert_eq!(presented, None);
+
+        log_in(&mut stream).await;
+        assert_eq!(server.await.unwrap().as_deref(), Some(&b"LOGIN"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_other_certificate_or_no_encryption_is_refused() {
+        let other = cert_pinning::fingerprint(b"another certificate");
+        let err = connect(ENCRYPT_ON, Some(&other)).await.0.err().unwrap();
+        assert!(err.starts_with("Server certificate changed for localhost"));
+
+        let err = connect(ENCRYPT_NOT_SUP, Some(&other))
+            .await
+            .0
+            .err()
+            .unwrap();
+        assert!(err.contains("its certificate is pinned"));
+    }
+
+    #[test]
+    fn test_prelogin_reply_turns_encryption_off() {
+        let request = prelogin_request();
+        assert_eq!(encryption_option(&request), Some(ENCRYPT_ON));
+
+        let reply = prelogin_reply(&request);
+        assert!(message_complete(&reply));
+        assert_eq!(reply[0], TABULAR_RESULT);
+        assert_eq!(
+            encryption_option(&reply[HEADER_LEN..]),
+            Some(ENCRYPT_NOT_SUP)
+        );
+
+        let split = packets(PRELOGIN, &vec![0; PACKET_SIZE * 2], &mut 1);
+        assert!(!message_complete(&split[..PACKET_SIZE]));
+        assert!(!message_complete(&split[..split.len() - 1]));
+        assert!(message_complete(&split));
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use super::types::ConnectionTransport;
+
+/// Stream a TDS session runs over
+pub enum DbStream {
+    Tcp(TcpStream),
+    #[cfg(target_os = "windows")]
+    NamedPipe(tokio::net::windows::named_pipe::NamedPipeClient),
+}
+
+/// Open the transport to `addr` (host:port), or to the configured pipe
+pub async fn open(addr: String, transport: &ConnectionTransport) -> Result<DbStream, String> {
+    match transport {
+        ConnectionTransport::Tcp => {
+            let tcp = TcpStream::connect(addr)
+                .await
+                .map_err(|e| format!("TCP connection failed: {}", e))?;
+            tcp.set_nodelay(true).ok();
+            Ok(DbStream::Tcp(tcp))
+        }
+        ConnectionTransport::NamedPipe { pipe_path } => open_pipe(pipe_path).await,
+    }
+}
+
+/// Where a connection goes, for status messages
+pub fn endpoint(host: &str, port: u16, transport: &ConnectionTransport) -> String {
+    match transport {
+        ConnectionTransport::Tcp => format!("{}:{}", host, port),
+        ConnectionTransport::NamedPipe { pipe_path } => pipe_path.clone(),
+    }
+}
+
+/// Server part of a pipe path like `\\server\pipe\sql\query`; "." is this machine
+pub fn pipe_server(pipe_path: &str) -> Option<&str> {
+    let rest = pipe_path.strip_prefix(r"\\")?;
+    let (server, pipe) = rest.split_once('\\')?;
+    if server.is_empty() || !pipe.to_ascii_lowercase().starts_with("pipe\\") {
+        return None;
+    }
+    Some(if server == "." { "localhost" } else { server })
+}
+
+#[cfg(target_os = "windows")]
+async fn open_pipe(pipe_path: &str) -> Result<DbStream, String> {
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    /// ERROR_PIPE_BUSY: every instance of the pipe is in use
+    const PIPE_BUSY: i32 = 231;
+    const ATTEMPTS: u32 = 100;
+
+    pipe_server(pipe_path).ok_or_else(|| {
+        format!(
+            "Invalid pipe path '{}': expected \\\\server\\pipe\\name",
+            pipe_path
+        )
+    })?;
+    for _ in 0..ATTEMPTS {
+        match ClientOptions::new().open(pipe_path) {
+            Ok(pipe) => return Ok(DbStream::NamedPipe(pipe)),
+            Err(e) if e.raw_os_error() == Some(PIPE_BUSY) => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Err(e) => return Err(format!("Named pipe connection failed: {}", e)),
+        }
+    }
+    Err(format!(
+        "Named pipe connection failed: {} stayed busy",
+        pipe_path
+    ))
+}
+
+#[cfg(not(target_os = "windows"))]
+async fn open_pipe(_pipe_path: &str) -> Result<DbStream, String> {
+    Err("Named pipe connections are only supported on Windows".to_string())
+}
+
+impl AsyncRead for DbStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DbStream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(target_os = "windows")]
+            DbStream::NamedPipe(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for DbStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            DbStream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(target_os = "windows")]
+            DbStream::NamedPipe(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DbStream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(target_os = "windows")]
+            DbStream::NamedPipe(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            DbStream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(target_os = "windows")]
+            DbStream::NamedPipe(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_server() {
+        assert_eq!(pipe_server(r"\\.\pipe\sql\query"), Some("localhost"));
+        assert_eq!(
+            pipe_server(r"\\DBHOST\pipe\MSSQL$SALES\sql\query"),
+            Some("DBHOST")
+        );
+        assert_eq!(pipe_server(r"\\DBHOST\share\file"), None);
+        assert_eq!(pipe_server("localhost"), None);
+    }
+}
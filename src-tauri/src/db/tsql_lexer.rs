@@ -0,0 +1,385 @@
+// T-SQL tokenizer shared by the statement splitter, safe mode, hints, timing,
+// row estimates and the formatter. It knows where strings, quoted names and
+// (nested) comments end, so punctuation and keywords inside them are never
+// mistaken for code, and recognises GO only when it stands on its own line.
+
+/// Two-character operators; every other operator character is a token of its own
+const TWO_CHAR_OPERATORS: &[&str] = &[
+    "<>", "!=", ">=", "<=", "!<", "!>", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "::",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// Keyword or identifier, including @variables and #temp names
+    Word,
+    /// [name] or "name"
+    QuotedName,
+    /// 'text' or N'text'
+    StringLiteral,
+    /// 42, 1.5, 1e3, 0x1F
+    Number,
+    LineComment,
+    BlockComment,
+    Comma,
+    Semicolon,
+    OpenParen,
+    CloseParen,
+    Dot,
+    Operator,
+    /// GO line, with its optional repeat count
+    BatchSeparator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range in the tokenized text
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn text<'a>(&self, sql: &'a str) -> &'a str {
+        &sql[self.start..self.end]
+    }
+
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, TokenKind::LineComment | TokenKind::BlockComment)
+    }
+
+    /// A bare word equal to `word`, ignoring case
+    pub fn is_word(&self, sql: &str, word: &str) -> bool {
+        self.kind == TokenKind::Word && self.text(sql).eq_ignore_ascii_case(word)
+    }
+}
+
+/// Every token of the script, comments included; whitespace is dropped.
+/// An unterminated string or comment runs to the end of the text.
+pub fn tokenize(sql: &str) -> Vec<Token> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let kind = match c {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                // Leave a CR for the whitespace
+                if bytes[i - 1] == b'\r' {
+                    i -= 1;
+                }
+                TokenKind::LineComment
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // Block comments nest in T-SQL
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                TokenKind::BlockComment
+            }
+            b'\'' => {
+                i = skip_quoted(bytes, i);
+                TokenKind::StringLiteral
+            }
+            b'N' | b'n' if bytes.get(i + 1) == Some(&b'\'') => {
+                i = skip_quoted(bytes, i + 1);
+                TokenKind::StringLiteral
+            }
+            b'"' | b'[' => {
+                i = skip_quoted(bytes, i);
+                TokenKind::QuotedName
+            }
+            b',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            b';' => {
+                i += 1;
+                TokenKind::Semicolon
+            }
+            b'(' => {
+                i += 1;
+                TokenKind::OpenParen
+            }
+            b')' => {
+                i += 1;
+                TokenKind::CloseParen
+            }
+            // ".5" is a number unless the dot follows a name
+            b'.' if bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
+                && !tokens.last().is_some_and(|t: &Token| {
+                    t.end == start
+                        && matches!(
+                            t.kind,
+                            TokenKind::Word | TokenKind::QuotedName | TokenKind::CloseParen
+                        )
+                }) =>
+            {
+                i = skip_number(bytes, i);
+                TokenKind::Number
+            }
+            b'.' => {
+                i += 1;
+                TokenKind::Dot
+            }
+            c if c.is_ascii_digit() => {
+                i = skip_number(bytes, i);
+                TokenKind::Number
+            }
+            c if is_word_start(c) => {
+                while i < bytes.len() && (is_word_start(bytes[i]) || bytes[i] == b'$') {
+                    i += 1;
+                }
+                if is_batch_separator(sql, start, i) {
+                    // Swallow the repeat count and any comment after it
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                    while i > start && bytes[i - 1].is_ascii_whitespace() {
+                        i -= 1;
+                    }
+                    TokenKind::BatchSeparator
+                } else {
+                    TokenKind::Word
+                }
+            }
+            _ => {
+                let two = sql.get(i..i + 2).unwrap_or("");
+                i += if TWO_CHAR_OPERATORS.contains(&two) {
+                    2
+                } else {
+                    sql[i..].chars().next().map_or(1, char::len_utf8)
+                };
+                TokenKind::Operator
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+
+    tokens
+}
+
+/// Tokens without comments, for code that looks at neighbouring tokens
+pub fn code_tokens(sql: &str) -> Vec<Token> {
+    tokenize(sql)
+        .into_iter()
+        .filter(|t| !t.is_comment())
+        .collect()
+}
+
+/// GO separates batches only alone on its line, optionally followed by a
+/// repeat count and a line comment
+pub fn is_batch_separator(sql: &str, start: usize, end: usize) -> bool {
+    if !sql[start..end].eq_ignore_ascii_case("GO") {
+        return false;
+    }
+    let line_start = sql[..start].rfind('\n').map(|p| p + 1).unwrap_or(0);
+    let line_end = sql[end..].find('\n').map(|p| end + p).unwrap_or(sql.len());
+    let before = sql[line_start..start].trim();
+    let rest = sql[end..line_end]
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim();
+    before.is_empty() && (rest.is_empty() || rest.starts_with("--"))
+}
+
+fn is_word_start(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'@' | b'#') || c >= 0x80
+}
+
+/// End of a string or quoted name opening at `start`; a doubled closing
+/// character is an escape, not the end
+fn skip_quoted(bytes: &[u8], start: usize) -> usize {
+    let close = if bytes[start] == b'[' {
+        b']'
+    } else {
+        bytes[start]
+    };
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == close {
+            if bytes.get(i + 1) == Some(&close) {
+                i += 2;
+                continue;
+            }
+            break;
+        }
+        i += 1;
+    }
+    (i + 1).min(bytes.len())
+}
+
+/// Digits with an optional fraction and exponent, or a 0x binary literal
+fn skip_number(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+    if bytes[i] == b'0' && matches!(bytes.get(i + 1), Some(b'x' | b'X')) {
+        i += 2;
+        while i < bytes.len() && bytes[i].is_ascii_hexdigit() {
+            i += 1;
+        }
+        return i;
+    }
+    while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+        i += 1;
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(i + 1), Some(b'+' | b'-')));
+        if bytes.get(i + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            i += 1 + sign;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(sql)
+            .into_iter()
+            .map(|t| (t.kind, t.text(sql)))
+            .collect()
+    }
+
+    #[test]
+    fn test_basic_tokens() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("SELECT t.a, @x FROM #t WHERE b <> 1.5;"),
+            vec![
+                (Word, "SELECT"),
+                (Word, "t"),
+                (Dot, "."),
+                (Word, "a"),
+                (Comma, ","),
+                (Word, "@x"),
+                (Word, "FROM"),
+                (Word, "#t"),
+                (Word, "WHERE"),
+                (Word, "b"),
+                (Operator, "<>"),
+                (Number, "1.5"),
+                (Semicolon, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strings_and_quoted_names_hide_punctuation() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("'it''s; -- not a comment' N'ü' [a]]; b] \"x;y\""),
+            vec![
+                (StringLiteral, "'it''s; -- not a comment'"),
+                (StringLiteral, "N'ü'"),
+                (QuotedName, "[a]]; b]"),
+                (QuotedName, "\"x;y\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comments_nest_and_hide_quotes() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("/* a /* b; */ 'x */ SELECT -- it's\r\n1"),
+            vec![
+                (BlockComment, "/* a /* b; */ 'x */"),
+                (Word, "SELECT"),
+                (LineComment, "-- it's"),
+                (Number, "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string_and_comment_run_to_end() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("SELECT 'abc"),
+            vec![(Word, "SELECT"), (StringLiteral, "'abc")]
+        );
+        assert_eq!(
+            kinds("SELECT /* abc"),
+            vec![(Word, "SELECT"), (BlockComment, "/* abc")]
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("1e3 2.5E-2 0x1F .5 -3"),
+            vec![
+                (Number, "1e3"),
+                (Number, "2.5E-2"),
+                (Number, "0x1F"),
+                (Number, ".5"),
+                (Operator, "-"),
+                (Number, "3"),
+            ]
+        );
+        // Dots between names stay dots
+        assert_eq!(kinds("a.b")[1], (Dot, "."));
+    }
+
+    #[test]
+    fn test_batch_separators() {
+        use TokenKind::*;
+        let separators = |sql: &str| -> Vec<String> {
+            tokenize(sql)
+                .into_iter()
+                .filter(|t| t.kind == BatchSeparator)
+                .map(|t| t.text(sql).to_string())
+                .collect()
+        };
+        assert_eq!(separators("SELECT 1\ngo\nSELECT 2"), vec!["go"]);
+        assert_eq!(separators("SELECT 1\r\n  GO 5  \r\n"), vec!["GO 5"]);
+        assert_eq!(
+            separators("SELECT 1\nGO -- next batch\n"),
+            vec!["GO -- next batch"]
+        );
+        // A column named GO, GO inside strings and comments, GO with trailing code
+        assert!(separators("SELECT GO FROM t").is_empty());
+        assert!(separators("SELECT 'a\nGO\nb'").is_empty());
+        assert!(separators("/*\nGO\n*/ SELECT 1").is_empty());
+        assert!(separators("GO SELECT 1").is_empty());
+        assert!(separators("GO5").is_empty());
+    }
+
+    #[test]
+    fn test_code_tokens_and_unicode() {
+        let sql = "SELECT née /* c */ FROM t -- d";
+        let words: Vec<&str> = code_tokens(sql).iter().map(|t| t.text(sql)).collect();
+        assert_eq!(words, vec!["SELECT", "née", "FROM", "t"]);
+        assert!(code_tokens(sql)[0].is_word(sql, "select"));
+    }
+}
@@ -0,0 +1,478 @@
+use super::connection::TiberiusClient;
+use super::identifiers::{quote_identifier, quote_literal};
+use super::metadata_cache::MetadataCache;
+use super::rows::{get_i64, get_string};
+use super::splitter::split_statements;
+use super::tsql_lexer::{code_tokens, Token, TokenKind};
+
+// Retry for queries the client cannot read. When a statement fails with the
+// unsupported column type error, the server describes its result set and the
+// statement is wrapped in a SELECT that casts the offending columns, or reads
+// spatial ones as well-known text. hierarchyid is always read as its path;
+// everything else is opt-in through the castUnsupportedTypes setting.
+
+/// system_type_id of date
+const DATE_TYPE_ID: i64 = 40;
+/// system_type_id shared by CLR types (geometry, geography, hierarchyid)
+const CLR_TYPE_ID: i64 = 240;
+
+/// Alias for the wrapped statement
+const DERIVED_TABLE: &str = "[q]";
+
+/// A result set column as described by sys.dm_exec_describe_first_result_set
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescribedColumn {
+    pub name: Option<String>,
+    pub system_type_id: i64,
+    /// Base type with length or precision, e.g. "nvarchar(50)"
+    pub system_type_name: String,
+    /// Set for alias and CLR types
+    pub user_type_name: Option<String>,
+    pub is_hidden: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastRewrite {
+    pub sql: String,
+    /// Names of the columns that were cast
+    pub cast_columns: Vec<String>,
+}
+
+/// Which columns a rewrite may convert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastScope {
+    /// Only hierarchyid, whose path text loses nothing; always allowed
+    HierarchyId,
+    /// Every unsupported type, with the castUnsupportedTypes setting on
+    All,
+}
+
+/// Type the client can read in place of the column's type, if it needs one
+fn cast_type(column: &DescribedColumn) -> Option<String> {
+    if column.system_type_id == DATE_TYPE_ID {
+        return Some("datetime".to_string());
+    }
+    if column.system_type_id == CLR_TYPE_ID {
+        return Some("varbinary(max)".to_string());
+    }
+    // Alias types are read as their base type
+    column
+        .user_type_name
+        .as_ref()
+        .map(|_| column.system_type_name.clone())
+}
+
+fn is_clr_type(column: &DescribedColumn, names: &[&str]) -> bool {
+    let name = column
+        .user_type_name
+        .as_deref()
+        .unwrap_or(&column.system_type_name);
+    column.system_type_id == CLR_TYPE_ID && names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// Expression that reads `source` as a type the client supports.
+/// hierarchyid becomes its /1/3/ path and spatial values their well-known
+/// text, which the grid can at least show.
+fn converted(column: &DescribedColumn, source: &str) -> Option<String> {
+    if is_clr_type(column, &["hierarchyid"]) {
+        return Some(format!("{}.ToString()", source));
+    }
+    if is_clr_type(column, &["geometry", "geography"]) {
+        return Some(format!("{}.STAsText()", source));
+    }
+    cast_type(column).map(|target| format!("CAST({} AS {})", source, target))
+}
+
+/// Wrap a single SELECT (optionally with CTEs) so the columns the client
+/// cannot read are cast. None when nothing needs a cast, a column outside
+/// `scope` needs one, or the statement cannot be used as a derived table.
+pub fn cast_statement(
+    sql: &str,
+    columns: &[DescribedColumn],
+    scope: CastScope,
+) -> Option<CastRewrite> {
+    let columns: Vec<&DescribedColumn> = columns.iter().filter(|c| !c.is_hidden).collect();
+    let mut names: Vec<&str> = Vec::new();
+    for column in &columns {
+        // A derived table needs every column named, and named once
+        let name = column.name.as_deref().filter(|n| !n.is_empty())?;
+        if names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            return None;
+        }
+        names.push(name);
+    }
+
+    let mut select_list = Vec::new();
+    let mut cast_columns = Vec::new();
+    for (column, name) in columns.iter().zip(&names) {
+        let source = format!("{}.{}", DERIVED_TABLE, quote_identifier(name));
+        match converted(column, &source) {
+            // Retrying would fail on this column again
+            Some(_)
+                if scope == CastScope::HierarchyId && !is_clr_type(column, &["hierarchyid"]) =>
+            {
+                return None;
+            }
+            Some(expression) => {
+                select_list.push(format!("{} AS {}", expression, quote_identifier(name)));
+                cast_columns.push(name.to_string());
+            }
+            None => select_list.push(source),
+        }
+    }
+    if cast_columns.is_empty() {
+        return None;
+    }
+
+    let shape = QueryShape::of(sql)?;
+    let body = &sql[shape.body_start..shape.body_end];
+    // ORDER BY is only allowed in a derived table with TOP or OFFSET
+    let offset = if shape.order_by.is_some() && !shape.has_top && !shape.has_offset {
+        " OFFSET 0 ROWS"
+    } else {
+        ""
+    };
+    let outer_order = shape
+        .order_by
+        .and_then(|start| outer_order_by(&sql[start..shape.body_end], &names))
+        .map(|items| format!(" ORDER BY {}", items))
+        .unwrap_or_default();
+    let option = shape
+        .option_start
+        .map(|start| format!(" {}", sql[start..shape.end].trim()))
+        .unwrap_or_default();
+
+    Some(CastRewrite {
+        sql: format!(
+            "{}SELECT {} FROM ({}{}) AS {}{}{}",
+            &sql[..shape.body_start],
+            select_list.join(", "),
+            body.trim(),
+            offset,
+            DERIVED_TABLE,
+            outer_order,
+            option
+        ),
+        cast_columns,
+    })
+}
+
+/// Where the parts of a SELECT statement are, by byte offset
+struct QueryShape {
+    /// First token of the main SELECT, after any CTEs
+    body_start: usize,
+    /// End of the main SELECT, before any OPTION clause
+    body_end: usize,
+    /// ORDER BY of the main SELECT
+    order_by: Option<usize>,
+    has_top: bool,
+    has_offset: bool,
+    option_start: Option<usize>,
+    /// End of the statement, before a trailing semicolon
+    end: usize,
+}
+
+impl QueryShape {
+    fn of(sql: &str) -> Option<Self> {
+        let mut tokens = code_tokens(sql);
+        while tokens
+            .last()
+            .is_some_and(|t| t.kind == TokenKind::Semicolon)
+        {
+            tokens.pop();
+        }
+        let is_word = |t: &Token, w: &str| t.is_word(sql, w);
+
+        let first = tokens.first()?;
+        let main = if is_word(first, "SELECT") {
+            0
+        } else if is_word(first, "WITH") {
+            // CTE bodies are parenthesized, so the first SELECT at depth 0 is the main one
+            let mut depth = 0usize;
+            tokens.iter().position(|t| {
+                match t.kind {
+                    TokenKind::OpenParen => depth += 1,
+                    TokenKind::CloseParen => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                depth == 0 && is_word(t, "SELECT")
+            })?
+        } else {
+            return None;
+        };
+
+        let mut shape = QueryShape {
+            body_start: tokens[main].start,
+            body_end: tokens.last()?.end,
+            order_by: None,
+            has_top: tokens.get(main + 1).is_some_and(|t| is_word(t, "TOP"))
+                || (tokens
+                    .get(main + 1)
+                    .is_some_and(|t| is_word(t, "DISTINCT") || is_word(t, "ALL"))
+                    && tokens.get(main + 2).is_some_and(|t| is_word(t, "TOP"))),
+            has_offset: false,
+            option_start: None,
+            end: tokens.last()?.end,
+        };
+
+        let mut depth = 0usize;
+        for (i, token) in tokens.iter().enumerate().skip(main + 1) {
+            match token.kind {
+                TokenKind::OpenParen => depth += 1,
+                TokenKind::CloseParen => depth = depth.saturating_sub(1),
+                // A second statement, or a batch separator
+                TokenKind::Semicolon | TokenKind::BatchSeparator => return None,
+                TokenKind::Word if depth == 0 => {
+                    let word = token.text(sql).to_ascii_uppercase();
+                    match word.as_str() {
+                        // SELECT INTO returns nothing; FOR XML / JSON cannot be wrapped
+                        "INTO" | "FOR" => return None,
+                        "ORDER" if tokens.get(i + 1).is_some_and(|t| is_word(t, "BY")) => {
+                            shape.order_by = Some(token.start);
+                            shape.has_offset = false;
+                        }
+                        "UNION" | "EXCEPT" | "INTERSECT" => {
+                            shape.order_by = None;
+                            shape.has_top = false;
+                        }
+                        "OFFSET" if shape.order_by.is_some() => shape.has_offset = true,
+                        "OPTION" => {
+                            shape.body_end = tokens[i - 1].end;
+                            shape.option_start = Some(token.start);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(shape)
+    }
+}
+
+/// The ORDER BY items again, for the outer query, when each one is a plain
+/// output column name or ordinal. Expressions and qualified names only make
+/// sense inside the derived table.
+fn outer_order_by(order_by: &str, names: &[&str]) -> Option<String> {
+    let tokens = code_tokens(order_by);
+    let mut items = Vec::new();
+    for item in tokens[2..].split(|t| t.kind == TokenKind::Comma) {
+        let (column, direction) = match item {
+            [column] => (column, None),
+            [column, direction]
+                if direction.is_word(order_by, "ASC") || direction.is_word(order_by, "DESC") =>
+            {
+                (column, Some(direction.text(order_by).to_ascii_uppercase()))
+            }
+            _ => return None,
+        };
+        let text = column.text(order_by);
+        let name = match column.kind {
+            TokenKind::Word => text.to_string(),
+            TokenKind::QuotedName => text[1..text.len() - 1]
+                .replace("]]", "]")
+                .replace("\"\"", "\""),
+            TokenKind::Number => {
+                let ordinal: usize = text.parse().ok()?;
+                names.get(ordinal.checked_sub(1)?)?.to_string()
+            }
+            _ => return None,
+        };
+        let name = names.iter().find(|n| n.eq_ignore_ascii_case(&name))?;
+        items.push(match direction {
+            Some(direction) => format!("{} {}", quote_identifier(name), direction),
+            None => quote_identifier(name),
+        });
+    }
+    (!items.is_empty()).then(|| items.join(", "))
+}
+
+/// Result set columns of `sql`, from the server's own metadata
+async fn describe_first_result_set(
+    client: &mut TiberiusClient,
+    sql: &str,
+) -> Result<Vec<DescribedColumn>, String> {
+    let query = format!(
+        "SELECT name, system_type_id, system_type_name, user_type_name, is_hidden \
+         FROM sys.dm_exec_describe_first_result_set({}, NULL, 0) \
+         ORDER BY column_ordinal",
+        quote_literal(sql)
+    );
+    let rows = client
+        .simple_query(query)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_first_result()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .map(|row| DescribedColumn {
+            name: get_string(row, 0),
+            system_type_id: get_i64(row, 1).unwrap_or_default(),
+            system_type_name: get_string(row, 2).unwrap_or_default(),
+            user_type_name: get_string(row, 3),
+            is_hidden: row.try_get::<bool, _>(4).ok().flatten().unwrap_or(false),
+        })
+        .collect())
+}
+
+/// Rewrite a query that failed with the unsupported column type error.
+/// Only single-statement queries are rewritten: the server cannot describe a
+/// statement that depends on variables or temp tables set up earlier in the batch.
+/// Descriptions are cached per database, so a repeated query is not described again.
+pub async fn rewrite_with_casts(
+    client: &mut TiberiusClient,
+    cache: &MetadataCache,
+    database: &str,
+    sql: &str,
+    scope: CastScope,
+) -> Option<CastRewrite> {
+    let statements = split_statements(sql);
+    let [statement] = statements.as_slice() else {
+        return None;
+    };
+    let statement = &sql[statement.start..statement.end];
+    let columns = match cache.result_set(database, statement).await {
+        Some(columns) => columns,
+        None => match describe_first_result_set(client, statement).await {
+            Ok(columns) => {
+                cache
+                    .store_result_set(database, statement, columns.clone())
+                    .await;
+                columns
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to describe result set; unsupported types will not be cast");
+                return None;
+            }
+        },
+    };
+    cast_statement(statement, &columns, scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(
+        name: &str,
+        system_type_id: i64,
+        type_name: &str,
+        user_type: Option<&str>,
+    ) -> DescribedColumn {
+        DescribedColumn {
+            name: Some(name.to_string()),
+            system_type_id,
+            system_type_name: type_name.to_string(),
+            user_type_name: user_type.map(str::to_string),
+            is_hidden: false,
+        }
+    }
+
+    fn columns() -> Vec<DescribedColumn> {
+        vec![
+            column("Id", 56, "int", None),
+            column("ValidTo", 40, "date", None),
+        ]
+    }
+
+    fn rewrite(sql: &str) -> Option<String> {
+        cast_statement(sql, &columns(), CastScope::All).map(|r| r.sql)
+    }
+
+    #[test]
+    fn test_join_with_aliases_is_wrapped_whole() {
+        let rewrite = cast_statement(
+            "SELECT o.Id, l.ValidTo FROM dbo.Orders o JOIN dbo.Licenses l ON l.OrderId = o.Id;",
+            &columns(),
+            CastScope::All,
+        )
+        .unwrap();
+        assert_eq!(
+            rewrite.sql,
+            "SELECT [q].[Id], CAST([q].[ValidTo] AS datetime) AS [ValidTo] FROM \
+             (SELECT o.Id, l.ValidTo FROM dbo.Orders o JOIN dbo.Licenses l ON l.OrderId = o.Id) AS [q]"
+        );
+        assert_eq!(rewrite.cast_columns, vec!["ValidTo"]);
+    }
+
+    #[test]
+    fn test_ctes_stay_in_front() {
+        assert_eq!(
+            rewrite("WITH x AS (SELECT Id, ValidTo FROM t) SELECT * FROM x").unwrap(),
+            "WITH x AS (SELECT Id, ValidTo FROM t) SELECT [q].[Id], \
+             CAST([q].[ValidTo] AS datetime) AS [ValidTo] FROM (SELECT * FROM x) AS [q]"
+        );
+    }
+
+    #[test]
+    fn test_order_by_and_option_move_outside() {
+        assert_eq!(
+            rewrite("SELECT * FROM t ORDER BY ValidTo DESC, 1 OPTION (RECOMPILE)").unwrap(),
+            "SELECT [q].[Id], CAST([q].[ValidTo] AS datetime) AS [ValidTo] FROM \
+             (SELECT * FROM t ORDER BY ValidTo DESC, 1 OFFSET 0 ROWS) AS [q] \
+             ORDER BY [ValidTo] DESC, [Id] OPTION (RECOMPILE)"
+        );
+        // An expression is kept inside only; TOP already allows the ORDER BY there
+        assert_eq!(
+            rewrite("SELECT TOP 5 * FROM t ORDER BY t.Id + 1").unwrap(),
+            "SELECT [q].[Id], CAST([q].[ValidTo] AS datetime) AS [ValidTo] FROM \
+             (SELECT TOP 5 * FROM t ORDER BY t.Id + 1) AS [q]"
+        );
+    }
+
+    #[test]
+    fn test_alias_and_clr_types() {
+        let columns = vec![
+            column("Phone", 231, "nvarchar(20)", Some("PhoneNumber")),
+            column("Node", 240, "hierarchyid", Some("hierarchyid")),
+            column("Shape", 240, "geometry", Some("geometry")),
+            column("Location", 240, "geography", Some("geography")),
+            column("Custom", 240, "Money", Some("Money")),
+        ];
+        let rewrite = cast_statement("SELECT * FROM t", &columns, CastScope::All).unwrap();
+        assert_eq!(
+            rewrite.sql,
+            "SELECT CAST([q].[Phone] AS nvarchar(20)) AS [Phone], \
+             [q].[Node].ToString() AS [Node], \
+             [q].[Shape].STAsText() AS [Shape], \
+             [q].[Location].STAsText() AS [Location], \
+             CAST([q].[Custom] AS varbinary(max)) AS [Custom] FROM (SELECT * FROM t) AS [q]"
+        );
+    }
+
+    #[test]
+    fn test_hierarchyid_scope() {
+        let mut columns = vec![
+            column("Id", 56, "int", None),
+            column("Node", 240, "hierarchyid", Some("hierarchyid")),
+        ];
+        assert_eq!(
+            cast_statement("SELECT Id, Node FROM t", &columns, CastScope::HierarchyId)
+                .unwrap()
+                .sql,
+            "SELECT [q].[Id], [q].[Node].ToString() AS [Node] FROM (SELECT Id, Node FROM t) AS [q]"
+        );
+        // A date column would fail the retry as well
+        columns.push(column("ValidTo", 40, "date", None));
+        assert!(cast_statement("SELECT * FROM t", &columns, CastScope::HierarchyId).is_none());
+    }
+
+    #[test]
+    fn test_statements_that_cannot_be_wrapped() {
+        // Nothing to cast
+        assert!(cast_statement("SELECT * FROM t", &columns()[..1], CastScope::All).is_none());
+        assert!(rewrite("SELECT * INTO #t FROM t").is_none());
+        assert!(rewrite("SELECT * FROM t FOR JSON PATH").is_none());
+        assert!(rewrite("EXEC dbo.GetLicenses").is_none());
+        // Unnamed and duplicate output columns
+        let mut unnamed = columns();
+        unnamed[0].name = None;
+        assert!(cast_statement("SELECT 1, ValidTo FROM t", &unnamed, CastScope::All).is_none());
+        let mut duplicate = columns();
+        duplicate[0].name = Some("validto".to_string());
+        assert!(cast_statement("SELECT * FROM t", &duplicate, CastScope::All).is_none());
+    }
+}
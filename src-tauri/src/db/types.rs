@@ -1,6 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::plan::types::{
+    IssueSeverity, ParameterPlanComparison, PlanAnalysis, PlanComparison, PlanIssue, PlanRegression,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectionConfig {
@@ -13,6 +17,78 @@ pub struct ConnectionConfig {
     pub encrypted_password: String,
     pub last_used: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Reject batches that can modify data (safe mode)
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub transport: ConnectionTransport,
+    #[serde(default)]
+    pub auth: ConnectionAuth,
+    /// SHA-256 of the server certificate, pinned on first connect
+    #[serde(default)]
+    pub cert_fingerprint: Option<String>,
+}
+
+/// How the client reaches the server. Named pipes (Windows only) suit local
+/// instances that do not listen on TCP; the pipe path looks like
+/// `\\.\pipe\sql\query`, or `\\.\pipe\MSSQL$NAME\sql\query` for a named
+/// instance.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ConnectionTransport {
+    #[default]
+    Tcp,
+    NamedPipe {
+        #[serde(rename = "pipePath")]
+        pipe_path: String,
+    },
+}
+
+/// How the client logs in. Kerberos uses the ticket in the user's
+/// credential cache instead of a SQL login; `spn` replaces the default
+/// MSSQLSvc/host:port and `realm` is the realm that SPN belongs to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ConnectionAuth {
+    #[default]
+    SqlLogin,
+    Kerberos {
+        #[serde(default)]
+        spn: Option<String>,
+        #[serde(default)]
+        realm: Option<String>,
+    },
+}
+
+/// Salt and check value of the master passphrase; the passphrase itself is
+/// never stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MasterKeyInfo {
+    pub salt: String,
+    /// A known value encrypted with the derived key, to tell a wrong
+    /// passphrase apart before touching any password
+    pub verifier: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreLockStatus {
+    pub passphrase_enabled: bool,
+    pub unlocked: bool,
+}
+
+/// Folder used to organize saved connections (e.g. dev / test / prod)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionGroup {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +99,66 @@ pub struct ConnectionRequest {
     pub database: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub transport: ConnectionTransport,
+    #[serde(default)]
+    pub auth: ConnectionAuth,
+}
+
+/// Stage of opening a connection, to tell users where a failure happened
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionStep {
+    /// Reaching the server over TCP or the named pipe
+    Tcp,
+    Tls,
+    Login,
+    DatabaseAccess,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionTestReport {
+    pub success: bool,
+    /// Set when the test failed, with the error in `error`
+    pub failed_step: Option<ConnectionStep>,
+    pub error: Option<String>,
+    pub server_version: Option<String>,
+    pub edition: Option<String>,
+    pub current_database: Option<String>,
+    pub login_name: Option<String>,
+    /// Whether the session is TLS-encrypted; None when the server would not say
+    pub encrypted: Option<bool>,
+    /// e.g. "TCP", "Shared memory"
+    pub net_transport: Option<String>,
+    /// SQL, NTLM or KERBEROS
+    pub auth_scheme: Option<String>,
+    /// Time to open the connection, including TLS and login
+    pub connect_ms: Option<f64>,
+    /// Round trip of a trivial query on the open connection
+    pub latency_ms: Option<f64>,
+    pub permissions: Vec<PermissionCheck>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionCheck {
+    pub permission: String,
+    /// SERVER or DATABASE
+    pub scope: String,
+    pub granted: bool,
+    /// App features that need this permission
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionReport {
+    pub permissions: Vec<PermissionCheck>,
+    /// Features that will fail on this connection, in display order
+    pub unavailable_features: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +170,73 @@ pub struct SaveConnectionRequest {
     pub database: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Reject batches that can modify data (safe mode)
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub transport: ConnectionTransport,
+    #[serde(default)]
+    pub auth: ConnectionAuth,
+}
+
+/// A connection read from an SSMS registered servers export or Azure Data
+/// Studio settings, before the user supplies its password
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedConnection {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub username: String,
+    /// Server group path in the source ("Prod / EU"); matched to a
+    /// connection group by name on save
+    pub group: Option<String>,
+    /// What could not be carried over (Windows authentication, instance names)
+    pub warnings: Vec<String>,
+    /// A saved connection already has the same server, database and login
+    pub already_saved: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConnectionEntry {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// Edit of a saved connection; the stored password is kept when `password`
+/// is None
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateConnectionRequest {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub transport: ConnectionTransport,
+    #[serde(default)]
+    pub auth: ConnectionAuth,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +245,26 @@ pub struct QueryRequest {
     pub sql: String,
     pub timeout_seconds: Option<u32>,
     pub plan_type: PlanType,
+    /// Id to report the query under in queue events; generated when absent
+    #[serde(default)]
+    pub query_id: Option<String>,
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+}
+
+/// Opt-in handling of lock contention for one execution. A deadlock victim
+/// has its transaction rolled back, so re-running the batch is only safe
+/// when it does not depend on a transaction opened earlier.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    /// Times to re-run the batch after it was chosen as a deadlock victim
+    /// (error 1205)
+    pub deadlock_retries: u32,
+    /// Wait before the first retry; doubled for each further one
+    pub backoff_ms: u64,
+    /// SET LOCK_TIMEOUT for the batch; None keeps the session's setting
+    pub lock_timeout_ms: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,12 +277,345 @@ pub enum PlanType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryResult {
-    pub columns: Vec<String>,
-    pub rows: Vec<Vec<serde_json::Value>>,
+    /// One entry per result set the batch returned, in order
+    pub result_sets: Vec<ResultSet>,
     pub messages: Vec<String>,
     pub plan_xml: Option<String>,
     pub duration_ms: u64,
+    /// Rows changed by INSERT / UPDATE / DELETE / MERGE / SELECT INTO, as
+    /// counted by the server; rows returned are per result set
+    pub rows_affected: i64,
+    /// Per-statement durations from the batch's DONE tokens; empty for single
+    /// statements, estimated plans and batches whose DONE tokens do not match
+    /// their statements
+    pub timings: Vec<StatementTiming>,
+    /// Waits of the session while an actual plan query ran; None for other
+    /// plan types and servers before SQL Server 2016
+    pub wait_stats: Option<WaitStatsDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementTiming {
+    pub statement_preview: String,
+    pub elapsed_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryGrantReport {
+    pub statements: Vec<StatementMemoryGrant>,
+}
+
+/// Memory grant of one statement in KB: what the optimizer required and
+/// asked for, what the server granted and what execution actually used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementMemoryGrant {
+    pub statement_id: i64,
+    pub statement_text: String,
+    pub query_plan_hash: Option<String>,
+    pub required_kb: Option<f64>,
+    pub desired_kb: Option<f64>,
+    pub requested_kb: Option<f64>,
+    pub granted_kb: Option<f64>,
+    pub used_kb: Option<f64>,
+    /// Grant the query would ideally have had, from the plan cache
+    pub ideal_kb: Option<f64>,
+    pub grant_wait_ms: Option<f64>,
+    pub unused_percent: Option<f64>,
+    /// Granted / used came from the last cached execution rather than the plan
+    pub from_query_stats: bool,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultSet {
+    pub columns: Vec<ColumnInfo>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Rows in this result set
     pub rows_affected: i64,
+    /// Id of the copy kept for sort_results and filter_results; None for
+    /// result sets over the cached row limit
+    #[serde(default)]
+    pub result_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortKey {
+    /// Column index in the result set
+    pub column: usize,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterOperator {
+    /// Case-insensitive substring
+    Contains,
+    StartsWith,
+    Equals,
+    NotEquals,
+    GreaterThan,
+    LessThan,
+    IsNull,
+    IsNotNull,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultFilter {
+    pub column: usize,
+    pub operator: FilterOperator,
+    /// Compared as a number when both sides are numeric; unused by the
+    /// NULL checks
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    /// A view, procedure, function or trigger refers to the object by name
+    Expression,
+    ForeignKey,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyNode {
+    /// Lower-cased qualified name, referenced by edges
+    pub id: String,
+    /// None for objects in other databases and unresolved references
+    pub object_id: Option<i64>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub name: String,
+    /// e.g. USER_TABLE, VIEW, SQL_STORED_PROCEDURE
+    pub type_desc: Option<String>,
+    /// Levels away from the root object
+    pub depth: u32,
+}
+
+/// `from` depends on `to`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+    /// Foreign key name
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraph {
+    /// Id of the object asked about
+    pub root: String,
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+    /// Objects were left out to keep the graph readable
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseTableRequest {
+    /// Table or view, optionally schema-qualified
+    pub table: String,
+    pub page_size: usize,
+    /// Zero-based
+    pub page: usize,
+    pub order_column: Option<String>,
+    #[serde(default)]
+    pub descending: bool,
+    /// Cursor of the page on screen; next and previous pages seek from it
+    pub cursor: Option<PageCursor>,
+}
+
+/// Keys of the first and last row of a page, for keyset paging
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageCursor {
+    pub page: usize,
+    pub first_key: Vec<serde_json::Value>,
+    pub last_key: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PagingStrategy {
+    /// Seeks from the key of the adjacent page
+    Keyset,
+    /// OFFSET / FETCH; cost grows with the page number
+    Offset,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TablePage {
+    pub table: String,
+    pub page: usize,
+    pub page_size: usize,
+    pub result_set: ResultSet,
+    pub has_previous: bool,
+    pub has_next: bool,
+    pub strategy: PagingStrategy,
+    /// Columns of the unique key pages are ordered by, if the table has one
+    pub key_columns: Vec<String>,
+    /// Pass back with the next request for keyset paging
+    pub cursor: Option<PageCursor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CopyFormat {
+    Markdown,
+    /// INSERT INTO ... VALUES statements
+    Insert,
+    Csv,
+    /// Array of objects keyed by column name
+    Json,
+}
+
+/// File format of an exported result set
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Parquet,
+    /// Arrow IPC file (Feather v2)
+    ArrowIpc,
+}
+
+/// A query's first result set written to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultExport {
+    pub path: String,
+    pub format: ExportFormat,
+    pub rows: u64,
+    pub columns: usize,
+    /// Result sets after the first, which are not exported
+    pub skipped_result_sets: usize,
+    pub file_size_bytes: u64,
+    pub duration_ms: u64,
+}
+
+/// Payload of the "export-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub path: String,
+    pub rows_written: u64,
+}
+
+/// A window of a cached result set after its sort and filters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultView {
+    pub result_id: String,
+    pub total_rows: usize,
+    /// Rows passing the filters
+    pub matching_rows: usize,
+    pub offset: usize,
+    /// Position of each returned row in the original result set, for
+    /// fetch_cell
+    pub row_indexes: Vec<usize>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Column of a result set. Length, precision and scale are None when the
+/// type does not fix them, and nullability is not reported by the driver.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnInfo {
+    pub name: String,
+    /// SQL Server base type, e.g. "int" or "nvarchar"
+    pub type_name: String,
+    pub nullable: Option<bool>,
+    /// Storage size in bytes
+    pub max_length: Option<i64>,
+    pub precision: Option<u8>,
+    pub scale: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementQueryRequest {
+    /// Full editor text
+    pub sql: String,
+    /// Cursor position in UTF-16 code units, as reported by the editor
+    pub cursor_offset: usize,
+    pub plan_type: PlanType,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementQueryResult {
+    /// Range of the executed statement in UTF-16 code units
+    pub statement_start: usize,
+    pub statement_end: usize,
+    pub statement_sql: String,
+    pub result: QueryResult,
+}
+
+/// One statement of a script, as found by split_statements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStatement {
+    /// Range in UTF-16 code units, like StatementQueryResult
+    pub start: usize,
+    pub end: usize,
+    pub sql: String,
+}
+
+/// A query currently executing on the active connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningQuery {
+    pub id: String,
+    pub sql: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Rows one plan operator has produced so far against its estimate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorProgress {
+    pub node_id: i64,
+    pub physical_op: String,
+    pub row_count: i64,
+    pub estimate_row_count: i64,
+    /// Every thread of the operator has closed it
+    pub finished: bool,
+    pub percent: f64,
+}
+
+/// Progress of a running query from sys.dm_exec_query_profiles, emitted as
+/// "query-progress"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryProgress {
+    pub query_id: String,
+    /// Estimated-row weighted completion of all operators; a guess when
+    /// estimates are off, and never 100 before the query returns
+    pub percent: f64,
+    pub elapsed_ms: u64,
+    pub operators: Vec<OperatorProgress>,
+}
+
+/// A query waiting in the execution queue for its turn on the connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingQuery {
+    pub id: String,
+    pub sql: String,
+    pub queued_at: DateTime<Utc>,
+    /// 1 runs next
+    pub position: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,6 +629,165 @@ pub struct QueryHistoryEntry {
     pub duration_ms: u64,
     pub success: bool,
     pub error: Option<String>,
+    /// Hash of the normalized SQL, set when the entry is saved
+    #[serde(default)]
+    pub sql_hash: Option<String>,
+    /// Hash of the SQL with literals stripped, shared by runs with other values
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Favorites are listed separately and never trimmed from history
+    #[serde(default)]
+    pub pinned: bool,
+    /// User-given name for a favorite
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Set for runs of a scheduled query
+    #[serde(default)]
+    pub scheduled_query_id: Option<String>,
+}
+
+/// History entries that share a fingerprint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryHistoryGroup {
+    pub fingerprint: String,
+    /// The SQL with literals replaced by `?`
+    pub fingerprint_sql: String,
+    /// SQL of the most recent run
+    pub latest_sql: String,
+    pub run_count: usize,
+    pub failure_count: usize,
+    /// Over successful runs only
+    pub avg_duration_ms: f64,
+    pub last_executed_at: DateTime<Utc>,
+    pub plan_count: usize,
+}
+
+/// Where the user left off on a saved connection, restored on the next launch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceState {
+    pub connection_id: String,
+    #[serde(default)]
+    pub database: Option<String>,
+    /// Most recently used first; maintained by save_workspace
+    #[serde(default)]
+    pub recent_databases: Vec<String>,
+    /// Editor contents
+    #[serde(default)]
+    pub query_text: Option<String>,
+    /// Plan history entry that was open
+    #[serde(default)]
+    pub plan_id: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Reusable SQL kept by the user, independent of history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub id: String,
+    pub name: String,
+    /// "/"-separated folder path, None for the top level
+    pub folder: Option<String>,
+    pub sql: String,
+    pub description: Option<String>,
+    /// Derived from `sql` on save
+    pub placeholders: Vec<SnippetPlaceholder>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetPlaceholder {
+    pub name: String,
+    pub default: Option<String>,
+}
+
+/// Create a snippet, or update the one with `id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveSnippetRequest {
+    pub id: Option<String>,
+    pub name: String,
+    pub folder: Option<String>,
+    pub sql: String,
+    pub description: Option<String>,
+}
+
+/// A note on a plan, or on one of its operators when `node_id` is set. Kept
+/// by plan hash, so it shows up on every capture of the same plan shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanAnnotation {
+    pub id: String,
+    pub plan_hash: String,
+    /// History entry the note was written on
+    pub plan_id: Option<String>,
+    pub node_id: Option<i64>,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddPlanAnnotationRequest {
+    pub plan_hash: String,
+    pub plan_id: Option<String>,
+    pub node_id: Option<i64>,
+    pub text: String,
+}
+
+/// What to replace before a plan or query leaves the machine. Literal
+/// values always are; object names only on request, since tuning advice
+/// usually needs them.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RedactOptions {
+    /// Database, schema, table, index and column names too
+    pub object_names: bool,
+}
+
+/// Plan to export: a plan history entry, or a plan that is only on screen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPlanBundleRequest {
+    pub plan_id: Option<String>,
+    pub plan_xml: Option<String>,
+    pub sql: Option<String>,
+    /// Replace literals (and names) in the plan, query and findings
+    #[serde(default)]
+    pub redact: Option<RedactOptions>,
+}
+
+/// Everything needed to review a plan elsewhere, as one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanBundle {
+    pub created_at: DateTime<Utc>,
+    pub app_version: String,
+    pub plan_hash: String,
+    /// Plan history entry the bundle was exported from
+    pub source_plan_id: Option<String>,
+    pub plan_xml: String,
+    pub sql: Option<String>,
+    /// Findings of the analysis rules enabled on the exporting machine
+    pub analysis: Option<PlanAnalysis>,
+    pub context: Option<ExecutionContext>,
+    pub annotations: Vec<PlanAnnotation>,
+}
+
+/// What import_app_data brought in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDataImportSummary {
+    pub connections: usize,
+    pub groups: usize,
+    pub queries: usize,
+    pub plans: usize,
+    pub snippets: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,4 +800,1185 @@ pub struct PlanHistoryEntry {
     pub executed_at: DateTime<Utc>,
     pub connection_id: String,
     pub sql_preview: String,
+    /// Hash of the originating query's normalized SQL, set when the entry is saved
+    #[serde(default)]
+    pub sql_hash: Option<String>,
+    /// Literal-free fingerprint of the originating query
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Server and session environment when the plan was saved
+    #[serde(default)]
+    pub context: Option<ExecutionContext>,
+    /// Set when the plan looks worse than the previous plan of the same query
+    #[serde(default)]
+    pub regression: Option<PlanRegression>,
+    /// Shape of the plan, the key its annotations are stored under
+    #[serde(default)]
+    pub plan_hash: Option<String>,
+    /// Set for plans captured by a scheduled query
+    #[serde(default)]
+    pub scheduled_query_id: Option<String>,
+}
+
+/// Payload of the "plan-regression" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanRegressionEvent {
+    pub plan_id: String,
+    pub query_id: String,
+    pub sql_preview: String,
+    pub regression: PlanRegression,
+}
+
+/// A query run in the background on a saved connection, to watch its plan
+/// over time. Each run is saved to history like a query run by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledQuery {
+    pub id: String,
+    pub name: String,
+    pub sql: String,
+    pub connection_id: String,
+    /// Estimated compiles only; Actual runs the query
+    pub plan_type: PlanType,
+    pub interval_minutes: u32,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Error of the last run, None when it succeeded
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// Create a schedule, or update the one with `id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveScheduledQueryRequest {
+    pub id: Option<String>,
+    pub name: String,
+    pub sql: String,
+    pub connection_id: String,
+    pub plan_type: PlanType,
+    pub interval_minutes: u32,
+    pub enabled: bool,
+}
+
+/// Payload of the "scheduled-query-run" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRunEvent {
+    pub scheduled_query_id: String,
+    pub query_id: String,
+    pub plan_id: Option<String>,
+    pub error: Option<String>,
+    /// Set when the captured plan looks worse than the previous one
+    pub regression: Option<PlanRegression>,
+}
+
+/// One execution captured while a baseline was recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineQuery {
+    /// Literal-free hash the runs of a query are matched by
+    pub fingerprint: String,
+    pub sql: String,
+    pub executed_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    pub plan_xml: Option<String>,
+    pub plan_hash: Option<String>,
+}
+
+/// Every query run during a recording window, to compare a later session
+/// against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Baseline {
+    pub id: String,
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub queries: Vec<BaselineQuery>,
+    /// The recording hit its size limit and later queries were left out
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineSummary {
+    pub id: String,
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub query_count: usize,
+    pub distinct_queries: usize,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BaselineQueryStatus {
+    Unchanged,
+    Improved,
+    Regressed,
+    /// Only run in the new session
+    New,
+    /// Only run in the baseline
+    Missing,
+}
+
+/// One query (by fingerprint) in the baseline and the new session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryBaselineComparison {
+    pub fingerprint: String,
+    /// SQL of the latest run, from the session if it ran there
+    pub sql: String,
+    pub status: BaselineQueryStatus,
+    pub runs_before: usize,
+    pub runs_after: usize,
+    pub errors_before: usize,
+    pub errors_after: usize,
+    /// Mean duration of the successful runs
+    pub avg_duration_before_ms: Option<f64>,
+    pub avg_duration_after_ms: Option<f64>,
+    pub duration_change_percent: Option<f64>,
+    pub plan_changed: bool,
+    /// Statement-by-statement diff of the latest plans when they differ
+    pub plan_diff: Option<PlanComparison>,
+    pub plan_regression: Option<PlanRegression>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaselineComparison {
+    pub baseline: BaselineSummary,
+    pub session: BaselineSummary,
+    /// Regressions first, then by how much slower the query got
+    pub queries: Vec<QueryBaselineComparison>,
+    pub regressed: usize,
+    pub improved: usize,
+}
+
+/// One GO batch of a script in an analyzed folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderBatchResult {
+    /// 1-based line the batch starts on
+    pub line: usize,
+    pub preview: String,
+    pub estimated_cost: Option<f64>,
+    pub issues: Vec<PlanIssue>,
+    /// Why no plan could be compiled, e.g. a table created by an earlier batch
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderFileResult {
+    /// Relative to the analyzed folder
+    pub path: String,
+    pub batches: Vec<FolderBatchResult>,
+    /// The file could not be read
+    pub error: Option<String>,
+}
+
+/// How often one analysis rule fired across the folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderIssueCount {
+    pub rule_id: String,
+    pub title: String,
+    pub severity: IssueSeverity,
+    pub count: usize,
+    pub files: usize,
+}
+
+/// Estimated plans and analyzer findings for every .sql file in a folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderAnalysisReport {
+    pub folder: String,
+    pub files: Vec<FolderFileResult>,
+    pub batch_count: usize,
+    pub failed_batches: usize,
+    pub critical_issues: usize,
+    pub warning_issues: usize,
+    pub info_issues: usize,
+    pub total_estimated_cost: f64,
+    /// Most frequent first
+    pub issue_counts: Vec<FolderIssueCount>,
+    /// The folder had more files than are analyzed in one go
+    pub truncated: bool,
+}
+
+/// Payload of the "folder-analysis-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderAnalysisProgress {
+    pub path: String,
+    pub files_done: usize,
+    pub files_total: usize,
+}
+
+/// Error returned by the database commands, by kind so the frontend can
+/// branch on it. Errors SQL Server raised keep the server's details.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AppError {
+    /// Not connected, or the login, network or TLS handshake failed
+    #[serde(rename_all = "camelCase")]
+    ConnectionError {
+        message: String,
+        #[serde(default)]
+        engine: Option<Box<EngineError>>,
+    },
+    /// The server rejected or failed a statement
+    #[serde(rename_all = "camelCase")]
+    QueryError {
+        /// SQL Server error number; None for driver errors
+        code: Option<u32>,
+        /// 1-based line of the batch the error was raised on
+        line: Option<u32>,
+        message: String,
+        #[serde(default)]
+        engine: Option<Box<EngineError>>,
+    },
+    /// Missing server permission, or blocked by safe mode or the profile
+    #[serde(rename_all = "camelCase")]
+    PermissionError {
+        message: String,
+        #[serde(default)]
+        engine: Option<Box<EngineError>>,
+    },
+    /// The query was cancelled or killed before it finished
+    Cancelled { message: String },
+    /// A lock or memory grant wait ran out
+    #[serde(rename_all = "camelCase")]
+    Timeout {
+        message: String,
+        #[serde(default)]
+        engine: Option<Box<EngineError>>,
+    },
+    /// Anything else: bad input, files, the local store
+    Other { message: String },
+}
+
+/// What SQL Server reported with an error
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineError {
+    pub number: u32,
+    /// 11-16 are errors in the statement, 17 and above resource or server faults
+    pub severity: u8,
+    pub state: u8,
+    /// 1-based line in the batch, or in the procedure when one is named
+    pub line: u32,
+    pub procedure: Option<String>,
+    pub server: Option<String>,
+    /// Plain-English explanation, for common error numbers
+    pub explanation: Option<ErrorExplanation>,
+}
+
+/// Bundled explanation of an error number
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorExplanation {
+    pub number: u32,
+    pub title: String,
+    pub explanation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    pub occurred_at: DateTime<Utc>,
+    /// Start of the batch that failed; None for login failures
+    pub statement_preview: Option<String>,
+    pub error: AppError,
+}
+
+/// One statement to run in several databases of the current server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiDatabaseRequest {
+    pub sql: String,
+    pub databases: Vec<String>,
+    /// Databases run at once, each on a session of its own; one at a time
+    /// when absent
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    pub plan_type: PlanType,
+}
+
+/// Outcome in one database; exactly one of `result` and `error` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiDatabaseResult {
+    pub database: String,
+    pub result: Option<QueryResult>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiDatabaseReport {
+    pub results: Vec<MultiDatabaseResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub duration_ms: u64,
+}
+
+/// Payload of the "multi-database-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiDatabaseProgress {
+    pub database: String,
+    pub error: Option<String>,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// One statement to run on several saved connections: the members of a
+/// connection group and any connections listed on their own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGroupRequest {
+    pub sql: String,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub connection_ids: Vec<String>,
+    /// Servers run at once; one at a time when absent
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    pub plan_type: PlanType,
+}
+
+/// Outcome on one saved connection; exactly one of `result` and `error` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGroupResult {
+    pub connection_id: String,
+    pub connection_name: String,
+    /// host:port or pipe path
+    pub server: String,
+    pub database: String,
+    pub result: Option<QueryResult>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGroupReport {
+    pub results: Vec<ServerGroupResult>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub duration_ms: u64,
+}
+
+/// Payload of the "server-group-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerGroupProgress {
+    pub connection_id: String,
+    pub connection_name: String,
+    pub error: Option<String>,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// A query run twice (on two connections, or the same one) to compare the
+/// results, e.g. before and after a rewrite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDiffRequest {
+    pub sql: String,
+    /// Query for the right side; `sql` again when absent
+    #[serde(default)]
+    pub right_sql: Option<String>,
+    /// Saved connection of each side; the active connection when absent
+    #[serde(default)]
+    pub left_connection_id: Option<String>,
+    #[serde(default)]
+    pub right_connection_id: Option<String>,
+    /// Columns that identify a row; whole rows are compared when empty
+    #[serde(default)]
+    pub key_columns: Vec<String>,
+}
+
+/// A row whose key is on both sides with other values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedRow {
+    pub left: Vec<serde_json::Value>,
+    pub right: Vec<serde_json::Value>,
+    /// Indexes into `columns` of the values that differ
+    pub changed_columns: Vec<usize>,
+}
+
+/// Row-level differences between two result sets. Listed rows hold the
+/// values of `columns`; the lists are capped, the counts are not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDiff {
+    /// Columns on both sides, in the left result's order
+    pub columns: Vec<String>,
+    pub key_columns: Vec<String>,
+    pub left_only_columns: Vec<String>,
+    pub right_only_columns: Vec<String>,
+    pub left_row_count: usize,
+    pub right_row_count: usize,
+    pub unchanged: usize,
+    pub changed: usize,
+    /// Rows only on the left
+    pub removed: usize,
+    /// Rows only on the right
+    pub added: usize,
+    /// Rows whose key repeats a previous row on the same side
+    pub duplicate_keys: usize,
+    pub changed_rows: Vec<ChangedRow>,
+    pub removed_rows: Vec<Vec<serde_json::Value>>,
+    pub added_rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Where one side of a diff ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDiffSide {
+    /// Saved connection name, or "Current connection"
+    pub source: String,
+    pub database: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDiffReport {
+    pub left: ResultDiffSide,
+    pub right: ResultDiffSide,
+    pub diff: ResultDiff,
+}
+
+/// Settings outside the query text that change which plan the optimizer picks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionContext {
+    pub captured_at: DateTime<Utc>,
+    pub server_version: String,
+    pub edition: Option<String>,
+    pub database_name: String,
+    pub compatibility_level: Option<i64>,
+    /// SET options that were ON (from @@OPTIONS)
+    pub set_options: Vec<String>,
+    /// 70 for the legacy model, otherwise the compatibility level it follows
+    pub cardinality_estimator_version: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompletionObjectKind {
+    Table,
+    View,
+    Procedure,
+    Function,
+}
+
+/// Completion metadata for one database, flattened so the payload stays small:
+/// objects reference their schema by index into `schemas`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionMetadata {
+    pub database: String,
+    pub schemas: Vec<String>,
+    pub objects: Vec<CompletionObject>,
+    /// Prefix index: positions in `objects` sorted by lowercased name
+    pub name_index: Vec<usize>,
+    pub loaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionObject {
+    pub object_id: i32,
+    pub schema: usize,
+    pub name: String,
+    pub kind: CompletionObjectKind,
+    /// (column name, type name) pairs in column_id order
+    pub columns: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitStatsDelta {
+    pub captured_at: DateTime<Utc>,
+    /// When the previous snapshot was taken; None on the first call
+    pub baseline_at: Option<DateTime<Utc>>,
+    pub interval_ms: Option<i64>,
+    pub waits: Vec<WaitStatDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitStatDelta {
+    pub wait_type: String,
+    pub waiting_tasks: i64,
+    pub wait_time_ms: i64,
+    pub signal_wait_time_ms: i64,
+    /// Share of the total (non-benign) wait time in this interval
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhatIfIndexRequest {
+    pub sql: String,
+    /// Table to index, optionally schema-qualified
+    pub table: String,
+    pub key_columns: Vec<String>,
+    #[serde(default)]
+    pub included_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhatIfIndexResult {
+    pub index_name: String,
+    /// Whether the optimizer chose the hypothetical index
+    pub index_used: bool,
+    pub original_plan_xml: String,
+    pub what_if_plan_xml: String,
+    pub comparison: PlanComparison,
+}
+
+/// Parse checks syntax only; Compile also resolves object and column names
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ValidationMode {
+    Parse,
+    Compile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationError {
+    /// 1-based line within the batch; only known for the first error
+    pub line: Option<u32>,
+    /// 1-based column (UTF-16), when the message names the offending token
+    pub column: Option<u32>,
+    pub message: String,
+    pub code: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationResult {
+    pub valid: bool,
+    pub errors: Vec<ValidationError>,
+}
+
+/// Total used to rank statements in get_top_queries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TopQueryMetric {
+    Cpu,
+    Reads,
+    Duration,
+    Executions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopQueriesRequest {
+    pub metric: TopQueryMetric,
+    /// Defaults to 25, at most 500
+    pub limit: Option<u32>,
+    /// Also return each statement's cached plan XML
+    #[serde(default)]
+    pub include_plans: bool,
+}
+
+/// One cached statement from sys.dm_exec_query_stats, totals since it was cached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopQuery {
+    /// Hex literal (0x...), usable to look the plan up again
+    pub plan_handle: String,
+    pub query_hash: Option<String>,
+    pub statement_text: String,
+    pub database_name: Option<String>,
+    pub execution_count: i64,
+    pub total_cpu_ms: f64,
+    pub avg_cpu_ms: f64,
+    pub total_logical_reads: i64,
+    pub avg_logical_reads: f64,
+    pub total_elapsed_ms: f64,
+    pub avg_elapsed_ms: f64,
+    pub last_execution_time: Option<NaiveDateTime>,
+    pub plan_xml: Option<String>,
+}
+
+/// Look up by plan handle, or by query text matched loosely against the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedPlanRequest {
+    pub plan_handle: Option<String>,
+    pub query_text: Option<String>,
+    /// Text matches to return; defaults to 10, at most 50
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedPlan {
+    pub plan_handle: String,
+    /// Adhoc, Prepared, Proc, ...
+    pub object_type: String,
+    pub use_counts: i64,
+    pub size_bytes: i64,
+    pub database_name: Option<String>,
+    pub sql_text: String,
+    pub plan_xml: Option<String>,
+    /// Similarity to the searched text (0..1), for text lookups
+    pub match_score: Option<f64>,
+}
+
+/// One statistics object of a table (sys.stats + sys.dm_db_stats_properties)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatisticsInfo {
+    pub stats_id: i64,
+    pub name: String,
+    /// Key columns in order; the histogram covers the first one
+    pub columns: Vec<String>,
+    pub auto_created: bool,
+    pub user_created: bool,
+    pub no_recompute: bool,
+    pub filter_definition: Option<String>,
+    /// None when the statistics were never built
+    pub last_updated: Option<NaiveDateTime>,
+    pub rows: Option<i64>,
+    pub rows_sampled: Option<i64>,
+    pub steps: Option<i64>,
+    /// Changes to the leading column since the last update
+    pub modification_counter: Option<i64>,
+    pub histogram: Vec<HistogramStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramStep {
+    pub range_hi_key: Option<String>,
+    pub range_rows: f64,
+    pub equal_rows: f64,
+    pub distinct_range_rows: f64,
+    pub average_range_rows: f64,
+}
+
+/// How a histogram's keys can be charted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HistogramKeyKind {
+    /// JSON numbers
+    Numeric,
+    /// ISO-8601 strings
+    Temporal,
+    /// Strings in the column's collation order
+    Text,
+}
+
+/// Histogram step with its upper bound as a typed value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedHistogramStep {
+    /// Number, string or null, per `key_kind`
+    pub range_hi_key: serde_json::Value,
+    /// Rows strictly between the previous step and this one
+    pub range_rows: f64,
+    /// Rows equal to `range_hi_key`
+    pub equal_rows: f64,
+    pub distinct_range_rows: f64,
+    pub average_range_rows: f64,
+}
+
+/// Histogram of one statistics object, for charting next to a predicate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsHistogram {
+    pub stats_name: String,
+    /// Leading key column, the one the histogram covers
+    pub column: String,
+    /// SQL type of the column (int, datetime2, nvarchar, ...)
+    pub key_type: String,
+    pub key_kind: HistogramKeyKind,
+    pub steps: Vec<TypedHistogramStep>,
+}
+
+/// How much of each index sys.dm_db_index_physical_stats reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FragmentationScanMode {
+    /// Parent-level pages only; fast, but no page fullness
+    #[default]
+    Limited,
+    /// A 1% sample of leaf pages (all of them for small indexes)
+    Sampled,
+    /// Every page; slow on large tables
+    Detailed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FragmentationRequest {
+    /// One table; every user table of the database when omitted
+    pub table: Option<String>,
+    #[serde(default)]
+    pub scan_mode: FragmentationScanMode,
+    /// Fragmentation (%) from which an index is reorganized; defaults to 5
+    pub reorganize_percent: Option<f64>,
+    /// Fragmentation (%) from which it is rebuilt instead; defaults to 30
+    pub rebuild_percent: Option<f64>,
+    /// Smaller indexes are left alone; defaults to 1000 pages
+    pub min_page_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MaintenanceAction {
+    None,
+    Reorganize,
+    Rebuild,
+}
+
+/// Leaf level of one index partition (sys.dm_db_index_physical_stats)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexFragmentation {
+    pub schema_name: String,
+    pub table_name: String,
+    /// None for a heap
+    pub index_name: Option<String>,
+    pub index_id: i64,
+    /// HEAP, CLUSTERED INDEX or NONCLUSTERED INDEX
+    pub index_type: String,
+    pub partition_number: i64,
+    /// Whether the index has more than one partition
+    pub partitioned: bool,
+    pub avg_fragmentation_percent: f64,
+    pub page_count: i64,
+    pub fragment_count: Option<i64>,
+    /// Not measured in Limited mode
+    pub avg_page_space_used_percent: Option<f64>,
+    pub action: MaintenanceAction,
+    /// ALTER INDEX (ALTER TABLE for heaps) statement for the action
+    pub script: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FragmentationReport {
+    pub scan_mode: FragmentationScanMode,
+    /// Most fragmented first
+    pub indexes: Vec<IndexFragmentation>,
+    /// Every recommended statement, one per line, ready to run
+    pub script: String,
+}
+
+/// Space used by one table across its partitions and indexes, as
+/// sp_spaceused reports it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSize {
+    pub schema_name: String,
+    pub table_name: String,
+    pub rows: i64,
+    pub reserved_kb: i64,
+    pub used_kb: i64,
+    /// Heap or clustered index pages, LOB and row-overflow included
+    pub data_kb: i64,
+    /// Nonclustered indexes and the upper levels of the clustered one
+    pub index_kb: i64,
+    /// Reserved but not yet used
+    pub unused_kb: i64,
+}
+
+/// Space used by one index (or heap) across its partitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexSize {
+    pub schema_name: String,
+    pub table_name: String,
+    /// None for a heap
+    pub index_name: Option<String>,
+    pub index_id: i64,
+    /// HEAP, CLUSTERED, NONCLUSTERED, CLUSTERED COLUMNSTORE, ...
+    pub index_type: String,
+    pub partitions: i64,
+    pub rows: i64,
+    pub reserved_kb: i64,
+    pub used_kb: i64,
+    /// Used space by allocation unit type
+    pub in_row_kb: i64,
+    pub lob_kb: i64,
+    pub row_overflow_kb: i64,
+}
+
+/// Hints for apply_hints; query hints go into the OPTION clause of every DML statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryHints {
+    #[serde(default)]
+    pub recompile: bool,
+    pub maxdop: Option<u32>,
+    #[serde(default)]
+    pub force_order: bool,
+    /// Names for USE HINT, e.g. FORCE_LEGACY_CARDINALITY_ESTIMATION
+    #[serde(default)]
+    pub use_hints: Vec<String>,
+    #[serde(default)]
+    pub table_hints: Vec<TableIndexHint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableIndexHint {
+    /// Table as written in the query, optionally schema-qualified
+    pub table: String,
+    pub index: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyHintsRequest {
+    pub sql: String,
+    pub hints: QueryHints,
+    /// Estimated or Actual to also capture both plans; None only rewrites
+    pub plan_type: Option<PlanType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyHintsResult {
+    pub sql: String,
+    pub original_plan_xml: Option<String>,
+    pub hinted_plan_xml: Option<String>,
+    pub comparison: Option<PlanComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatComparisonRequest {
+    pub sql: String,
+    /// Compatibility levels to compile under, e.g. [130, 150, 160]
+    pub levels: Vec<u16>,
+    /// Also compile under the current level with the legacy cardinality estimator
+    #[serde(default)]
+    pub include_legacy_ce: bool,
+    /// Switch the database's level with ALTER DATABASE when permitted,
+    /// instead of the per-query USE HINT. Affects every session in the
+    /// database until it is switched back.
+    #[serde(default)]
+    pub allow_alter_database: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompatMethod {
+    UseHint,
+    AlterDatabase,
+}
+
+/// The estimated plan under one level or CE variation, compared with the
+/// plan under the database's current level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatPlanVariant {
+    pub label: String,
+    pub compatibility_level: u16,
+    pub legacy_ce: bool,
+    pub method: CompatMethod,
+    pub plan_xml: Option<String>,
+    pub estimated_cost: Option<f64>,
+    pub comparison: Option<PlanComparison>,
+    /// Why this variant has no plan
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatComparisonResult {
+    pub current_level: u16,
+    /// Highest level the server supports
+    pub server_max_level: u16,
+    /// ALTER DATABASE was requested but the login, the profile or safe mode
+    /// does not allow it
+    pub fell_back_to_use_hint: bool,
+    pub baseline_plan_xml: Option<String>,
+    pub baseline_cost: Option<f64>,
+    pub variants: Vec<CompatPlanVariant>,
+}
+
+/// A plan-affecting SET option where the session and SSMS disagree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetOptionDifference {
+    pub name: String,
+    pub session_value: bool,
+    pub ssms_value: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MismatchVerdict {
+    /// The SSMS defaults produce a different plan
+    PlansDiffer,
+    /// Same plan, but the options put app and SSMS in separate cache entries
+    SeparateCacheEntries,
+    /// The session already matches SSMS
+    NotSetOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanMismatchDiagnosis {
+    /// SET options ON in the app's session
+    pub session_options: Vec<String>,
+    pub differences: Vec<SetOptionDifference>,
+    /// QueryPlanHash of each statement
+    pub session_plan_hashes: Vec<String>,
+    pub ssms_plan_hashes: Vec<String>,
+    pub session_plan_xml: Option<String>,
+    pub ssms_plan_xml: Option<String>,
+    pub comparison: Option<PlanComparison>,
+    pub verdict: MismatchVerdict,
+    pub explanation: String,
+}
+
+/// A parameter of the query under investigation, with a value for each run.
+/// Values are sent as strings and converted to `sql_type` by the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryParameter {
+    pub name: String,
+    pub sql_type: String,
+    pub first_value: Option<String>,
+    pub second_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterSniffingRequest {
+    /// Query text referencing the parameters, e.g. `WHERE CustomerId = @customer`
+    pub sql: String,
+    pub parameters: Vec<QueryParameter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterRun {
+    /// The sp_executesql batch that ran
+    pub batch: String,
+    pub plan_xml: Option<String>,
+    pub duration_ms: u64,
+    pub rows_returned: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterSniffingResult {
+    pub first: ParameterRun,
+    pub second: ParameterRun,
+    /// None when the server returned no actual plan for either run
+    pub comparison: Option<ParameterPlanComparison>,
+}
+
+/// A row of sys.configurations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigOption {
+    pub name: String,
+    pub value: i64,
+    pub value_in_use: i64,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub description: String,
+    pub is_dynamic: bool,
+    pub is_advanced: bool,
+    /// Configured value differs from the running one (RECONFIGURE or restart pending)
+    pub pending_restart: bool,
+    /// Influences plan choice, parallelism or memory grants
+    pub affects_plans: bool,
+}
+
+/// Database settings that influence plans, from sys.databases and
+/// sys.database_scoped_configurations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseOptions {
+    pub name: String,
+    pub compatibility_level: Option<i64>,
+    pub auto_create_statistics: bool,
+    pub auto_update_statistics: bool,
+    pub auto_update_statistics_async: bool,
+    pub forced_parameterization: bool,
+    pub read_committed_snapshot: bool,
+    pub snapshot_isolation: Option<String>,
+    pub recovery_model: Option<String>,
+    pub collation: Option<String>,
+    /// Empty before SQL Server 2016
+    pub scoped_configurations: Vec<ScopedConfiguration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopedConfiguration {
+    pub name: String,
+    pub value: Option<String>,
+    pub value_for_secondary: Option<String>,
+    pub affects_plans: bool,
+}
+
+/// Approximate size of a table from partition metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowCountEstimate {
+    pub table: String,
+    pub approximate_rows: i64,
+    /// Catalog view the count came from
+    pub source: String,
+    /// The query has a WHERE clause, so the count is an upper bound
+    pub filtered: bool,
+    /// Set when the result would be large and the query has no TOP
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    /// Keywords are written as typed
+    Preserve,
+}
+
+/// Where the comma goes when a list is broken over several lines
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CommaPlacement {
+    #[default]
+    Trailing,
+    Leading,
+}
+
+/// Style for format_sql; missing fields take the defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatOptions {
+    pub keyword_case: KeywordCase,
+    pub comma_placement: CommaPlacement,
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            keyword_case: KeywordCase::Upper,
+            comma_placement: CommaPlacement::Trailing,
+            indent_width: 4,
+        }
+    }
+}
+
+/// A session that is blocked or blocking others, from sys.dm_exec_sessions
+/// and sys.dm_exec_requests. Idle sessions holding locks have no request.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockingSession {
+    pub session_id: i64,
+    /// 0 when not blocked; negative ids are orphaned or deferred transactions
+    pub blocking_session_id: i64,
+    pub status: Option<String>,
+    pub command: Option<String>,
+    pub wait_type: Option<String>,
+    pub wait_time_ms: Option<i64>,
+    pub wait_resource: Option<String>,
+    /// Running time of the current request, or time since the last one
+    /// finished for an idle session
+    pub elapsed_ms: Option<i64>,
+    pub database_name: Option<String>,
+    pub login_name: Option<String>,
+    pub host_name: Option<String>,
+    pub program_name: Option<String>,
+    pub open_transaction_count: Option<i64>,
+    /// Current statement, or the last batch of an idle session
+    pub sql_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockingNode {
+    #[serde(flatten)]
+    pub session: BlockingSession,
+    /// Sessions waiting on this one
+    pub blocked: Vec<BlockingNode>,
+    /// Sessions waiting on this one directly or further down the chain
+    pub total_blocked: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockingTree {
+    /// One tree per lead blocker, the most disruptive first
+    pub roots: Vec<BlockingNode>,
+    pub blocked_sessions: usize,
+    /// Sessions blocking each other in a cycle; the deadlock monitor will
+    /// pick a victim shortly
+    pub cycles: Vec<Vec<i64>>,
+    /// Session of the active connection, to spot our own stuck query
+    pub own_session_id: Option<i64>,
+    pub captured_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    Connect,
+    Disconnect,
+    Execute,
+}
+
+/// An entry of the audit log. `hash` covers every other field, including
+/// the previous entry's hash, so editing or removing an entry breaks the
+/// chain from there on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub server: String,
+    pub database: String,
+    pub login: String,
+    /// Account signed in to this machine
+    pub os_user: Option<String>,
+    pub statement: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogPage {
+    /// Newest first
+    pub entries: Vec<AuditEntry>,
+    pub total: usize,
+    pub intact: bool,
+    pub first_broken_sequence: Option<u64>,
 }
@@ -0,0 +1,122 @@
+use tiberius::error::Error;
+
+use super::server_messages::ServerError;
+use super::types::{ValidationError, ValidationResult};
+
+// Turns the outcome of a PARSEONLY / NOEXEC round-trip into editor markers.
+// The server reports a line per error but tiberius only keeps it for the first
+// one; the rest come from the captured ERROR tokens without a position.
+
+/// Message prefixes that quote the offending token, in the server's wording
+const TOKEN_MARKERS: &[&str] = &["near the keyword '", "near '", "name '"];
+
+pub fn build_result(
+    sql: &str,
+    first_error: Option<Error>,
+    captured: Vec<ServerError>,
+) -> Result<ValidationResult, String> {
+    let first = match first_error {
+        None => None,
+        Some(Error::Server(token)) => Some(token),
+        // Connection or protocol failure: nothing was validated
+        Some(e) => return Err(format!("Validation failed: {}", e)),
+    };
+
+    let mut errors: Vec<ValidationError> = captured
+        .into_iter()
+        .map(|e| ValidationError {
+            line: None,
+            column: None,
+            message: e.message,
+            code: e.code,
+        })
+        .collect();
+
+    if let Some(token) = first {
+        let line = Some(token.line()).filter(|l| *l > 0);
+        let index = errors
+            .iter()
+            .position(|e| e.code == token.code() && e.message == token.message());
+        let target = match index {
+            Some(i) => &mut errors[i],
+            None => {
+                errors.insert(
+                    0,
+                    ValidationError {
+                        line: None,
+                        column: None,
+                        message: token.message().to_string(),
+                        code: token.code(),
+                    },
+                );
+                &mut errors[0]
+            }
+        };
+        target.line = line;
+        target.column = line.and_then(|l| column_of_token(sql, l, &target.message));
+    }
+
+    Ok(ValidationResult {
+        valid: errors.is_empty(),
+        errors,
+    })
+}
+
+/// 1-based UTF-16 column of the token quoted in `message` on line `line`
+fn column_of_token(sql: &str, line: u32, message: &str) -> Option<u32> {
+    let token = quoted_token(message)?;
+    let text = sql.lines().nth(line as usize - 1)?;
+    let start = text.to_lowercase().find(&token.to_lowercase())?;
+    // Lowercasing can change byte lengths; only trust offsets on char boundaries
+    let prefix = text.get(..start)?;
+    Some(prefix.encode_utf16().count() as u32 + 1)
+}
+
+fn quoted_token(message: &str) -> Option<&str> {
+    let start = TOKEN_MARKERS
+        .iter()
+        .find_map(|marker| message.find(marker).map(|i| i + marker.len()))?;
+    let rest = &message[start..];
+    let end = rest.rfind('\'')?;
+    Some(&rest[..end]).filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoted_token() {
+        assert_eq!(quoted_token("Incorrect syntax near 'FORM'."), Some("FORM"));
+        assert_eq!(
+            quoted_token("Incorrect syntax near the keyword 'FROM'."),
+            Some("FROM")
+        );
+        assert_eq!(
+            quoted_token("Invalid object name 'dbo.Missing'."),
+            Some("dbo.Missing")
+        );
+        assert_eq!(quoted_token("Incorrect syntax."), None);
+    }
+
+    #[test]
+    fn test_column_of_token() {
+        let sql = "SELECT 1\nSELECT * form dbo.Orders";
+        assert_eq!(
+            column_of_token(sql, 2, "Incorrect syntax near 'form'."),
+            Some(10)
+        );
+        assert_eq!(column_of_token(sql, 2, "Incorrect syntax near 'x'."), None);
+        assert_eq!(
+            column_of_token(sql, 5, "Incorrect syntax near 'form'."),
+            None
+        );
+    }
+
+    #[test]
+    fn test_clean_batch_is_valid() {
+        let result = build_result("SELECT 1", None, Vec::new()).unwrap();
+        assert!(result.valid);
+        assert!(result.errors.is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use super::connection::TiberiusClient;
+use super::rows::{get_i64, get_string};
+use super::types::{AppError, WaitStatDelta, WaitStatsDelta};
+
+/// Idle/background waits that accumulate constantly and say nothing about workload
+const BENIGN_WAITS: &[&str] = &[
+    "BROKER_EVENTHANDLER",
+    "BROKER_RECEIVE_WAITFOR",
+    "BROKER_TASK_STOP",
+    "BROKER_TO_FLUSH",
+    "BROKER_TRANSMITTER",
+    "CHECKPOINT_QUEUE",
+    "CHKPT",
+    "CLR_AUTO_EVENT",
+    "CLR_MANUAL_EVENT",
+    "CLR_SEMAPHORE",
+    "DBMIRROR_DBM_EVENT",
+    "DBMIRROR_EVENTS_QUEUE",
+    "DBMIRROR_WORKER_QUEUE",
+    "DBMIRRORING_CMD",
+    "DIRTY_PAGE_POLL",
+    "DISPATCHER_QUEUE_SEMAPHORE",
+    "EXECSYNC",
+    "FSAGENT",
+    "FT_IFTS_SCHEDULER_IDLE_WAIT",
+    "FT_IFTSHC_MUTEX",
+    "HADR_CLUSAPI_CALL",
+    "HADR_FILESTREAM_IOMGR_IOCOMPLETION",
+    "HADR_LOGCAPTURE_WAIT",
+    "HADR_NOTIFICATION_DEQUEUE",
+    "HADR_TIMER_TASK",
+    "HADR_WORK_QUEUE",
+    "KSOURCE_WAKEUP",
+    "LAZYWRITER_SLEEP",
+    "LOGMGR_QUEUE",
+    "MEMORY_ALLOCATION_EXT",
+    "ONDEMAND_TASK_QUEUE",
+    "PARALLEL_REDO_DRAIN_WORKER",
+    "PARALLEL_REDO_LOG_CACHE",
+    "PARALLEL_REDO_TRAN_LIST",
+    "PARALLEL_REDO_WORKER_SYNC",
+    "PARALLEL_REDO_WORKER_WAIT_WORK",
+    "PREEMPTIVE_XE_GETTARGETSTATE",
+    "PWAIT_ALL_COMPONENTS_INITIALIZED",
+    "PWAIT_DIRECTLOGCONSUMER_GETNEXT",
+    "PWAIT_EXTENSIBILITY_CLEANUP_TASK",
+    "QDS_PERSIST_TASK_MAIN_LOOP_SLEEP",
+    "QDS_ASYNC_QUEUE",
+    "QDS_CLEANUP_STALE_QUERIES_TASK_MAIN_LOOP_SLEEP",
+    "QDS_SHUTDOWN_QUEUE",
+    "REDO_THREAD_PENDING_WORK",
+    "REQUEST_FOR_DEADLOCK_SEARCH",
+    "RESOURCE_QUEUE",
+    "SERVER_IDLE_CHECK",
+    "SLEEP_BPOOL_FLUSH",
+    "SLEEP_DBSTARTUP",
+    "SLEEP_DCOMSTARTUP",
+    "SLEEP_MASTERDBREADY",
+    "SLEEP_MASTERMDREADY",
+    "SLEEP_MASTERUPGRADED",
+    "SLEEP_MSDBSTARTUP",
+    "SLEEP_SYSTEMTASK",
+    "SLEEP_TASK",
+    "SLEEP_TEMPDBSTARTUP",
+    "SNI_HTTP_ACCEPT",
+    "SOS_WORK_DISPATCHER",
+    "SP_SERVER_DIAGNOSTICS_SLEEP",
+    "SQLTRACE_BUFFER_FLUSH",
+    "SQLTRACE_INCREMENTAL_FLUSH_SLEEP",
+    "SQLTRACE_WAIT_ENTRIES",
+    "UCS_SESSION_REGISTRATION",
+    "VDI_CLIENT_OTHER",
+    "WAIT_FOR_RESULTS",
+    "WAITFOR",
+    "WAITFOR_TASKSHUTDOWN",
+    "WAIT_XTP_RECOVERY",
+    "WAIT_XTP_HOST_WAIT",
+    "WAIT_XTP_OFFLINE_CKPT_NEW_LOG",
+    "WAIT_XTP_CKPT_CLOSE",
+    "XE_DISPATCHER_JOIN",
+    "XE_DISPATCHER_WAIT",
+    "XE_LIVE_TARGET_TVF",
+    "XE_TIMER_EVENT",
+];
+
+pub fn is_benign_wait(wait_type: &str) -> bool {
+    BENIGN_WAITS.contains(&wait_type)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WaitCounters {
+    pub waiting_tasks: i64,
+    pub wait_time_ms: i64,
+    pub signal_wait_time_ms: i64,
+}
+
+/// Cumulative sys.dm_os_wait_stats counters at a point in time
+#[derive(Debug, Clone)]
+pub struct WaitStatsSnapshot {
+    pub captured_at: DateTime<Utc>,
+    pub waits: HashMap<String, WaitCounters>,
+}
+
+pub async fn snapshot(client: &mut TiberiusClient) -> Result<WaitStatsSnapshot, AppError> {
+    read_snapshot(
+        client,
+        "SELECT wait_type, waiting_tasks_count, wait_time_ms, signal_wait_time_ms \
+         FROM sys.dm_os_wait_stats WHERE wait_time_ms > 0",
+    )
+    .await
+}
+
+/// Waits of this connection's session only; sys.dm_exec_session_wait_stats
+/// needs SQL Server 2016
+pub async fn session_snapshot(client: &mut TiberiusClient) -> Result<WaitStatsSnapshot, AppError> {
+    read_snapshot(
+        client,
+        "SELECT wait_type, waiting_tasks_count, wait_time_ms, signal_wait_time_ms \
+         FROM sys.dm_exec_session_wait_stats WHERE session_id = @@SPID AND wait_time_ms > 0",
+    )
+    .await
+}
+
+async fn read_snapshot(
+    client: &mut TiberiusClient,
+    sql: &str,
+) -> Result<WaitStatsSnapshot, AppError> {
+    let rows = client
+        .simple_query(sql)
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read wait stats"))?
+        .into_first_result()
+        .await
+        .map_err(|e| AppError::from(e).context("Failed to read wait stats"))?;
+
+    let mut waits = HashMap::new();
+    for row in &rows {
+        let Some(wait_type) = get_string(row, 0) else {
+            continue;
+        };
+        if is_benign_wait(&wait_type) {
+            continue;
+        }
+        waits.insert(
+            wait_type,
+            WaitCounters {
+                waiting_tasks: get_i64(row, 1).unwrap_or(0),
+                wait_time_ms: get_i64(row, 2).unwrap_or(0),
+                signal_wait_time_ms: get_i64(row, 3).unwrap_or(0),
+            },
+        );
+    }
+
+    Ok(WaitStatsSnapshot {
+        captured_at: Utc::now(),
+        waits,
+    })
+}
+
+/// Waits accumulated between two snapshots, largest first. Without a
+/// baseline the cumulative totals since server start are returned.
+pub fn delta(baseline: Option<&WaitStatsSnapshot>, current: &WaitStatsSnapshot) -> WaitStatsDelta {
+    let mut waits: Vec<WaitStatDelta> = current
+        .waits
+        .iter()
+        .filter_map(|(wait_type, now)| {
+            let before = baseline
+                .and_then(|b| b.waits.get(wait_type))
+                .copied()
+                .unwrap_or_default();
+            // Counters go backwards after DBCC SQLPERF(...CLEAR) or a restart
+            let before = if now.wait_time_ms < before.wait_time_ms {
+                WaitCounters::default()
+            } else {
+                before
+            };
+            let wait_time_ms = now.wait_time_ms - before.wait_time_ms;
+            if wait_time_ms <= 0 {
+                return None;
+            }
+            Some(WaitStatDelta {
+                wait_type: wait_type.clone(),
+                waiting_tasks: now.waiting_tasks - before.waiting_tasks,
+                wait_time_ms,
+                signal_wait_time_ms: now.signal_wait_time_ms - before.signal_wait_time_ms,
+                percent: 0.0,
+            })
+        })
+        .collect();
+
+    let total: i64 = waits.iter().map(|w| w.wait_time_ms).sum();
+    for w in &mut waits {
+        w.percent = if total > 0 {
+            w.wait_time_ms as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+    }
+    waits.sort_by_key(|w| std::cmp::Reverse(w.wait_time_ms));
+
+    WaitStatsDelta {
+        captured_at: current.captured_at,
+        baseline_at: baseline.map(|b| b.captured_at),
+        interval_ms: baseline.map(|b| (current.captured_at - b.captured_at).num_milliseconds()),
+        waits,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(entries: &[(&str, i64, i64)]) -> WaitStatsSnapshot {
+        WaitStatsSnapshot {
+            captured_at: Utc::now(),
+            waits: entries
+                .iter()
+                .map(|(name, tasks, ms)| {
+                    (
+                        name.to_string(),
+                        WaitCounters {
+                            waiting_tasks: *tasks,
+                            wait_time_ms: *ms,
+                            signal_wait_time_ms: 0,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_delta_between_snapshots() {
+        let before = snap(&[("PAGEIOLATCH_SH", 10, 1000), ("LCK_M_X", 1, 50)]);
+        let after = snap(&[
+            ("PAGEIOLATCH_SH", 14, 1800),
+            ("LCK_M_X", 1, 50),
+            ("CXPACKET", 3, 200),
+        ]);
+
+        let d = delta(Some(&before), &after);
+        assert_eq!(d.waits.len(), 2);
+        assert_eq!(d.waits[0].wait_type, "PAGEIOLATCH_SH");
+        assert_eq!(d.waits[0].wait_time_ms, 800);
+        assert_eq!(d.waits[0].waiting_tasks, 4);
+        assert!((d.waits[0].percent - 80.0).abs() < 1e-9);
+        assert_eq!(d.waits[1].wait_type, "CXPACKET");
+    }
+
+    #[test]
+    fn test_delta_handles_cleared_counters() {
+        let before = snap(&[("WRITELOG", 100, 5000)]);
+        let after = snap(&[("WRITELOG", 2, 40)]);
+        let d = delta(Some(&before), &after);
+        assert_eq!(d.waits[0].wait_time_ms, 40);
+    }
+
+    #[test]
+    fn test_benign_waits_are_recognized() {
+        assert!(is_benign_wait("LAZYWRITER_SLEEP"));
+        assert!(!is_benign_wait("PAGEIOLATCH_SH"));
+    }
+}
@@ -0,0 +1,125 @@
+use tiberius::Row;
+use uuid::Uuid;
+
+use super::connection::{merge_showplan_xmls, TiberiusClient};
+use super::identifiers::{quote_identifier, quote_literal, quote_object_name};
+use super::rows::get_i64;
+use super::types::{AppError, WhatIfIndexRequest};
+
+// Hypothetical indexes the way the Database Engine Tuning Advisor creates them:
+// CREATE INDEX ... WITH STATISTICS_ONLY = -1 builds statistics but no index
+// structure, DBCC AUTOPILOT marks it usable, and under SET AUTOPILOT ON the
+// optimizer returns the estimated plan it would choose with the index present.
+// These are undocumented engine features; the index is always dropped again.
+
+async fn run(client: &mut TiberiusClient, sql: &str) -> Result<Vec<Vec<Row>>, AppError> {
+    client
+        .simple_query(sql)
+        .await?
+        .into_results()
+        .await
+        .map_err(AppError::from)
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| quote_identifier(c.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Estimated plan of `request.sql` with a hypothetical index on the table.
+/// Returns the generated index name and the plan XML.
+pub async fn plan_with_hypothetical_index(
+    client: &mut TiberiusClient,
+    request: &WhatIfIndexRequest,
+) -> Result<(String, String), AppError> {
+    if request.key_columns.is_empty() {
+        return Err("A hypothetical index needs at least one key column".into());
+    }
+
+    let table = quote_object_name(&request.table)?;
+    let index_name = format!("hypo_sqlplan_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let include = if request.included_columns.is_empty() {
+        String::new()
+    } else {
+        format!(" INCLUDE ({})", column_list(&request.included_columns))
+    };
+
+    run(
+        client,
+        &format!(
+            "CREATE NONCLUSTERED INDEX {} ON {} ({}){} WITH STATISTICS_ONLY = -1",
+            quote_identifier(&index_name),
+            table,
+            column_list(&request.key_columns),
+            include
+        ),
+    )
+    .await
+    .map_err(|e| e.context("Failed to create hypothetical index"))?;
+
+    let outcome = estimate_with_index(client, &table, &index_name, &request.sql).await;
+
+    // AUTOPILOT must be off before the DROP, or the DROP itself is only "planned"
+    let _ = run(client, "SET AUTOPILOT OFF").await;
+    if let Err(e) = run(
+        client,
+        &format!("DROP INDEX {} ON {}", quote_identifier(&index_name), table),
+    )
+    .await
+    {
+        tracing::warn!(index = %index_name, error = %e, "Failed to drop hypothetical index");
+    }
+
+    outcome.map(|plan| (index_name, plan))
+}
+
+async fn estimate_with_index(
+    client: &mut TiberiusClient,
+    table: &str,
+    index_name: &str,
+    sql: &str,
+) -> Result<String, AppError> {
+    let ids = run(
+        client,
+        &format!(
+            "SELECT DB_ID(), i.object_id, i.index_id FROM sys.indexes i \
+             WHERE i.object_id = OBJECT_ID({}) AND i.name = {}",
+            quote_literal(table),
+            quote_literal(index_name)
+        ),
+    )
+    .await?;
+    let row = ids
+        .first()
+        .and_then(|rs| rs.first())
+        .ok_or("Hypothetical index was not found after creation")?;
+    let (Some(db_id), Some(object_id), Some(index_id)) =
+        (get_i64(row, 0), get_i64(row, 1), get_i64(row, 2))
+    else {
+        return Err("Could not resolve hypothetical index ids".into());
+    };
+
+    run(
+        client,
+        &format!("DBCC AUTOPILOT(0, {}, {}, {})", db_id, object_id, index_id),
+    )
+    .await
+    .map_err(|e| e.context("DBCC AUTOPILOT failed"))?;
+    run(client, "SET AUTOPILOT ON")
+        .await
+        .map_err(|e| e.context("SET AUTOPILOT ON failed"))?;
+
+    let result_sets = run(client, sql).await?;
+    let plan_xmls: Vec<String> = result_sets
+        .iter()
+        .flatten()
+        .filter_map(|row| row.try_get::<&str, _>(0).ok().flatten())
+        .filter(|xml| xml.contains("ShowPlanXML"))
+        .map(|xml| xml.to_string())
+        .collect();
+
+    merge_showplan_xmls(plan_xmls).ok_or_else(|| AppError::new("No plan returned under AUTOPILOT"))
+}
@@ -1,9 +1,18 @@
+mod ai;
 mod db;
+mod logging;
+mod plan;
+mod settings;
+mod share;
+mod upload;
+mod xevents;
 #[cfg(target_os = "windows")]
 mod xel;
 
 use db::connection::AppState;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tauri::Manager;
 use tokio::sync::Mutex;
 
 #[cfg(target_os = "windows")]
@@ -26,8 +35,27 @@ pub fn run() {
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .manage(AppState {
             connection: Arc::new(Mutex::new(None)),
+            running_queries: Arc::new(Mutex::new(HashMap::new())),
+            execution_queue: Arc::new(db::query_queue::ExecutionQueue::default()),
+            master_key: Arc::new(Mutex::new(None)),
+        })
+        .manage(xevents::XeState::default())
+        .manage(db::plan_writer::PlanHistoryWriter::default())
+        .manage(db::audit_log::AuditLog::default())
+        .manage(share::ShareState::default())
+        .manage(db::scheduler::Scheduler::default())
+        .manage(db::baseline::BaselineRecorder::default())
+        .manage(settings::profile::ProfileChanges::default())
+        .setup(|app| {
+            let logging = logging::init(app.handle())?;
+            app.manage(logging);
+            db::plan_writer::spawn(app.handle().clone());
+            db::scheduler::spawn(app.handle());
+            Ok(())
         });
 
     #[cfg(target_os = "windows")]
@@ -43,15 +71,154 @@ pub fn run() {
             db::commands::test_connection,
             db::commands::connect_db,
             db::commands::disconnect_db,
+            db::commands::get_recent_errors,
+            db::commands::clear_recent_errors,
+            db::commands::get_audit_log,
+            db::commands::export_audit_log,
+            db::commands::check_permissions,
+            db::commands::use_database,
+            db::commands::get_current_database,
             db::commands::execute_query,
+            db::commands::execute_statement_at,
+            db::commands::execute_multi,
+            db::commands::execute_on_server_group,
+            db::commands::diff_results,
+            db::commands::split_statements,
+            db::commands::get_running_queries,
+            db::commands::list_pending_queries,
+            db::commands::cancel_pending,
+            db::commands::fetch_cell,
+            db::commands::sort_results,
+            db::commands::filter_results,
+            db::commands::copy_results,
+            db::commands::export_results,
+            db::commands::browse_table,
+            db::commands::pretty_print_xml,
+            db::commands::validate_query,
             db::commands::save_connection,
+            db::commands::import_connections,
+            db::commands::save_imported_connections,
+            db::commands::accept_server_certificate,
             db::commands::get_connections,
+            db::commands::update_connection,
+            db::commands::duplicate_connection,
             db::commands::delete_connection,
+            db::commands::save_workspace,
+            db::commands::restore_workspace,
             db::commands::connect_saved,
+            db::commands::get_connection_groups,
+            db::commands::create_connection_group,
+            db::commands::rename_connection_group,
+            db::commands::delete_connection_group,
+            db::commands::set_connection_group,
+            db::commands::set_connection_tags,
             db::commands::get_query_history,
             db::commands::save_query_history_entry,
+            db::commands::pin_query_history_entry,
+            db::commands::unpin_query_history_entry,
+            db::commands::get_favorite_queries,
+            db::commands::get_query_history_groups,
+            db::commands::save_snippet,
+            db::commands::list_snippets,
+            db::commands::list_snippet_folders,
+            db::commands::rename_snippet_folder,
+            db::commands::delete_snippet,
+            db::commands::render_snippet,
+            db::commands::export_app_data,
+            db::commands::import_app_data,
+            db::commands::get_store_lock_status,
+            db::commands::enable_master_passphrase,
+            db::commands::disable_master_passphrase,
+            db::commands::unlock_store,
+            db::commands::lock_store,
             db::commands::get_plan_history,
+            db::commands::add_plan_annotation,
+            db::commands::get_plan_annotations,
+            db::commands::delete_plan_annotation,
+            db::commands::export_plan_bundle,
+            db::commands::import_plan_bundle,
+            share::commands::share_plan,
+            share::commands::share_plan_bundle,
+            share::commands::list_shared_plans,
+            share::commands::stop_sharing,
+            upload::commands::list_upload_targets,
+            upload::commands::save_upload_target,
+            upload::commands::delete_upload_target,
+            upload::commands::upload_plan,
             db::commands::save_plan_history_entry,
+            db::commands::list_scheduled_queries,
+            db::commands::save_scheduled_query,
+            db::commands::delete_scheduled_query,
+            db::commands::run_scheduled_query,
+            db::commands::start_baseline_recording,
+            db::commands::get_baseline_recording,
+            db::commands::stop_baseline_recording,
+            db::commands::list_baselines,
+            db::commands::delete_baseline,
+            db::commands::compare_baselines,
+            db::commands::analyze_folder,
+            db::commands::get_index_fragmentation,
+            db::commands::get_table_sizes,
+            db::commands::get_index_sizes,
+            db::commands::render_plan_thumbnail,
+            db::commands::get_plans_for_query,
+            db::commands::get_completion_metadata,
+            db::commands::refresh_completion_metadata,
+            db::commands::refresh_metadata,
+            db::commands::get_wait_stats,
+            db::commands::compare_plans_for_parameters,
+            db::commands::get_memory_grant_info,
+            db::commands::get_blocking_tree,
+            db::commands::kill_running_query,
+            db::commands::kill_session,
+            db::commands::force_plan,
+            db::commands::estimate_rowcount,
+            db::commands::format_sql,
+            db::commands::redact_query,
+            db::commands::get_server_configuration,
+            db::commands::analyze_parallelism,
+            db::commands::get_database_options,
+            db::commands::get_top_queries,
+            db::commands::get_cached_plan,
+            db::commands::get_statistics_info,
+            db::commands::get_stats_histogram,
+            db::commands::get_object_dependencies,
+            db::commands::what_if_index,
+            db::commands::apply_hints,
+            db::commands::compare_across_compat_levels,
+            db::commands::diagnose_plan_mismatch,
+            plan::commands::open_plan_from_clipboard,
+            plan::commands::summarize_plan,
+            plan::commands::render_plan_image,
+            plan::commands::get_plan_theme_preset,
+            plan::commands::analyze_plan,
+            plan::commands::list_analysis_rules,
+            plan::commands::set_analysis_rule_enabled,
+            plan::commands::save_custom_analysis_rule,
+            plan::commands::delete_custom_analysis_rule,
+            plan::commands::validate_rule_expression,
+            plan::commands::compare_plans,
+            plan::commands::analyze_spills,
+            plan::commands::search_plan,
+            plan::commands::get_estimate_skew,
+            plan::commands::explain_plan,
+            plan::commands::get_operator_docs,
+            plan::commands::list_operator_docs,
+            settings::commands::get_settings,
+            settings::commands::update_settings,
+            settings::commands::request_profile_change,
+            settings::commands::change_profile,
+            logging::commands::get_recent_logs,
+            xevents::commands::xe_create_session,
+            xevents::commands::xe_start_session,
+            xevents::commands::xe_stop_session,
+            xevents::commands::xe_drop_session,
+            xevents::commands::xe_get_session,
+            xevents::commands::xe_get_events,
+            xevents::commands::xe_clear_events,
+            ai::commands::get_ai_provider,
+            ai::commands::update_ai_provider,
+            ai::commands::explain_plan_ai,
             #[cfg(target_os = "windows")]
             xel::commands::xel_pick_files,
             #[cfg(target_os = "windows")]
@@ -0,0 +1,16 @@
+use super::{LogEntry, LogLevel, LoggingState};
+
+const DEFAULT_RECENT_LOGS: usize = 200;
+
+/// Latest log events for the diagnostics panel, newest first
+#[tauri::command]
+pub async fn get_recent_logs(
+    limit: Option<usize>,
+    min_level: Option<LogLevel>,
+    logging: tauri::State<'_, LoggingState>,
+) -> Result<Vec<LogEntry>, String> {
+    Ok(logging.recent(
+        limit.unwrap_or(DEFAULT_RECENT_LOGS),
+        min_level.unwrap_or(LogLevel::Trace),
+    ))
+}
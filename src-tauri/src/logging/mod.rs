@@ -0,0 +1,242 @@
+pub mod commands;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt as log_fmt, reload, Layer, Registry};
+
+use crate::settings;
+
+// Application log. Events go to a daily rolling file under the app data dir
+// and to an in-memory buffer read by the diagnostics panel. Events carry ids,
+// durations and counts; query text, results and secrets are never logged.
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE_PREFIX: &str = "sqlplan";
+const MAX_LOG_FILES: usize = 7;
+
+/// Events kept for get_recent_logs
+const RECENT_LOG_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+
+    fn of(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+    /// Structured fields other than the message
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Handles kept for the lifetime of the app
+pub struct LoggingState {
+    filter: reload::Handle<Targets, Registry>,
+    recent: RecentLogs,
+    /// Flushes the file writer when dropped
+    _guard: Option<WorkerGuard>,
+}
+
+impl LoggingState {
+    pub fn set_level(&self, level: LogLevel) -> Result<(), String> {
+        self.filter
+            .reload(targets(level))
+            .map_err(|e| format!("Failed to change log level: {}", e))
+    }
+
+    /// Newest first, at `min_level` or more severe
+    pub fn recent(&self, limit: usize, min_level: LogLevel) -> Vec<LogEntry> {
+        self.recent
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|e| e.level <= min_level)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Dependencies log at the app's level too, except tiberius: its INFO events
+/// carry PRINT output and its debug events the packets of every query.
+fn targets(level: LogLevel) -> Targets {
+    Targets::new()
+        .with_default(level.filter())
+        .with_target("tiberius", LevelFilter::WARN.min(level.filter()))
+}
+
+/// Install the global subscriber at the level saved in settings. A log
+/// directory that cannot be created leaves only the in-memory log.
+pub fn init(app: &AppHandle) -> Result<LoggingState, String> {
+    let level = settings::load(app).map(|s| s.log_level).unwrap_or_default();
+    let (filter, handle) = reload::Layer::new(targets(level));
+    let recent = RecentLogs::default();
+
+    let file = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|dir| {
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix("log")
+                .max_log_files(MAX_LOG_FILES)
+                .build(dir.join(LOG_DIR))
+                .map_err(|e| e.to_string())
+        });
+    let (file_layer, guard, file_error) = match file {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = log_fmt::layer().with_writer(writer).with_ansi(false);
+            (Some(layer), Some(guard), None)
+        }
+        Err(e) => (None, None, Some(e)),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(file_layer)
+        .with(recent.clone())
+        .try_init()
+        .map_err(|e| format!("Failed to start logging: {}", e))?;
+
+    if let Some(e) = file_error {
+        tracing::warn!(error = %e, "Log file unavailable; keeping logs in memory only");
+    }
+    tracing::info!(
+        version = env!("CARGO_PKG_VERSION"),
+        ?level,
+        "Logging started"
+    );
+    Ok(LoggingState {
+        filter: handle,
+        recent,
+        _guard: guard,
+    })
+}
+
+/// Ring buffer of the latest events
+#[derive(Clone, Default)]
+struct RecentLogs {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl<S: Subscriber> Layer<S> for RecentLogs {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let entry = LogEntry {
+            timestamp: Utc::now(),
+            level: LogLevel::of(metadata.level()),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == RECENT_LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_logs_keep_newest_with_fields() {
+        let recent = RecentLogs::default();
+        let subscriber = tracing_subscriber::registry().with(recent.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..RECENT_LOG_CAPACITY + 5 {
+                tracing::debug!(i, "Event");
+            }
+            tracing::warn!(duration_ms = 12, "Slow");
+        });
+
+        let entries = recent.entries.lock().unwrap();
+        assert_eq!(entries.len(), RECENT_LOG_CAPACITY);
+        let last = entries.back().unwrap();
+        assert_eq!(last.level, LogLevel::Warn);
+        assert_eq!(last.message, "Slow");
+        assert_eq!(last.fields["duration_ms"], "12");
+        assert_eq!(entries.front().unwrap().fields["i"], "6");
+    }
+}
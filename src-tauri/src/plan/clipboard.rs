@@ -0,0 +1,100 @@
+use std::borrow::Cow;
+
+// What a paste holds: plan XML copied from SSMS or an email, a deadlock graph
+// from an XE session or the system_health ring buffer, or else a query. Text
+// around the XML (mail quoting, a signature) is dropped, and XML that was
+// pasted escaped is unescaped first.
+
+/// Clipboard text sorted by what reads it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pasted<'a> {
+    /// The ShowPlanXML element, without any XML declaration before it
+    Plan(String),
+    /// The deadlock (or deadlock-list) element
+    DeadlockGraph(String),
+    Sql(&'a str),
+}
+
+/// `<name ...>` through the last `</name>`; None when the element never closes
+fn element<'t>(text: &'t str, name: &str) -> Option<&'t str> {
+    let start = [" ", ">", "\r", "\n", "\t"]
+        .iter()
+        .filter_map(|next| text.find(&format!("<{}{}", name, next)))
+        .min()?;
+    let close = format!("</{}>", name);
+    let end = text.rfind(&close)? + close.len();
+    (end > start).then(|| &text[start..end])
+}
+
+/// Undo the escaping of XML pasted from a page that showed it as text
+fn unescape_pasted(text: &str) -> Cow<'_, str> {
+    if !text.contains("&lt;") || text.contains("<ShowPlanXML") || text.contains("<deadlock") {
+        return Cow::Borrowed(text);
+    }
+    quick_xml::escape::unescape(text).unwrap_or(Cow::Borrowed(text))
+}
+
+pub fn detect(text: &str) -> Result<Pasted<'_>, String> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Err("The clipboard holds no text".into());
+    }
+    let xml = unescape_pasted(trimmed);
+
+    if xml.contains("<ShowPlanXML") {
+        return element(&xml, "ShowPlanXML")
+            .map(|plan| Pasted::Plan(plan.to_string()))
+            .ok_or_else(|| "The clipboard holds an execution plan that was cut off".into());
+    }
+    if xml.contains("<deadlock") {
+        return element(&xml, "deadlock-list")
+            .or_else(|| element(&xml, "deadlock"))
+            .map(|graph| Pasted::DeadlockGraph(graph.to_string()))
+            .ok_or_else(|| "The clipboard holds a deadlock graph that was cut off".into());
+    }
+    Ok(Pasted::Sql(trimmed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_plan() {
+        let pasted = "Hi, the plan is below.\n\n\
+            <?xml version=\"1.0\" encoding=\"utf-16\"?>\n\
+            <ShowPlanXML xmlns=\"http://schemas.microsoft.com/sqlserver/2004/07/showplan\" Version=\"1.5\">\
+            <BatchSequence/></ShowPlanXML>\n\nThanks";
+        let Pasted::Plan(xml) = detect(pasted).unwrap() else {
+            panic!("not detected as a plan");
+        };
+        assert!(xml.starts_with("<ShowPlanXML xmlns="));
+        assert!(xml.ends_with("</ShowPlanXML>"));
+
+        let escaped = "&lt;ShowPlanXML Version=&quot;1.5&quot;&gt;&lt;/ShowPlanXML&gt;";
+        assert_eq!(
+            detect(escaped).unwrap(),
+            Pasted::Plan("<ShowPlanXML Version=\"1.5\"></ShowPlanXML>".into())
+        );
+
+        assert!(detect("<ShowPlanXML Version=\"1.5\"><BatchSequence>").is_err());
+    }
+
+    #[test]
+    fn test_detect_deadlock_and_sql() {
+        let report = "<event name=\"xml_deadlock_report\"><data><value>\
+            <deadlock><victim-list/><process-list/></deadlock></value></data></event>";
+        assert_eq!(
+            detect(report).unwrap(),
+            Pasted::DeadlockGraph("<deadlock><victim-list/><process-list/></deadlock>".into())
+        );
+        let list = "<deadlock-list><deadlock victim=\"p1\"></deadlock></deadlock-list>";
+        assert_eq!(detect(list).unwrap(), Pasted::DeadlockGraph(list.into()));
+
+        assert_eq!(
+            detect("  SELECT * FROM t WHERE a < 5\n").unwrap(),
+            Pasted::Sql("SELECT * FROM t WHERE a < 5")
+        );
+        assert!(detect(" \n").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use super::clipboard::{self, Pasted};
+use super::compare;
+use super::explain;
+use super::operator_docs;
+use super::parser;
+use super::render;
+use super::rules;
+use super::search;
+use super::skew;
+use super::spills;
+use super::summary;
+use super::types::*;
+use crate::settings;
+
+/// Read the clipboard and sort what it holds: a plan comes back parsed, a
+/// deadlock graph as its XML, anything else as a query for the editor
+#[tauri::command]
+pub async fn open_plan_from_clipboard(app: tauri::AppHandle) -> Result<ClipboardContent, String> {
+    let text = app
+        .clipboard()
+        .read_text()
+        .map_err(|e| format!("Cannot read the clipboard: {}", e))?;
+    Ok(match clipboard::detect(&text)? {
+        Pasted::Plan(plan_xml) => {
+            let plan = parser::parse_plan_async(plan_xml.clone()).await?;
+            ClipboardContent::Plan { plan_xml, plan }
+        }
+        Pasted::DeadlockGraph(xml) => ClipboardContent::DeadlockGraph { xml },
+        Pasted::Sql(sql) => ClipboardContent::Sql {
+            sql: sql.to_string(),
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn summarize_plan(plan_xml: String) -> Result<PlanSummary, String> {
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    Ok(summary::summarize(&plan))
+}
+
+/// Render a plan picture; without a `theme` the export theme from settings
+/// is used
+#[tauri::command]
+pub async fn render_plan_image(
+    plan_xml: String,
+    format: PlanImageFormat,
+    scale: Option<f32>,
+    theme: Option<PlanTheme>,
+    app: tauri::AppHandle,
+) -> Result<PlanImage, String> {
+    let theme = match theme {
+        Some(theme) => theme,
+        None => settings::load(&app)?.plan_export_theme,
+    };
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    let (svg, width, height) = render::render_svg(&plan, &theme);
+
+    match format {
+        PlanImageFormat::Svg => Ok(PlanImage {
+            format,
+            width: width.ceil() as u32,
+            height: height.ceil() as u32,
+            data: svg,
+        }),
+        PlanImageFormat::Png => {
+            let scale = scale.unwrap_or(1.0).clamp(0.25, 4.0);
+            // Rasterizing large plans takes a while; keep it off the async runtime
+            let (png, width, height) =
+                tokio::task::spawn_blocking(move || render::render_png(&svg, scale))
+                    .await
+                    .map_err(|e| format!("Plan rendering failed: {}", e))??;
+            Ok(PlanImage {
+                format,
+                width,
+                height,
+                data: BASE64.encode(png),
+            })
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn compare_plans(
+    before_plan_xml: String,
+    after_plan_xml: String,
+) -> Result<PlanComparison, String> {
+    let before = parser::parse_plan_async(before_plan_xml).await?;
+    let after = parser::parse_plan_async(after_plan_xml).await?;
+    Ok(compare::compare(&before, &after))
+}
+
+#[tauri::command]
+pub async fn analyze_spills(plan_xml: String) -> Result<Vec<SpillFinding>, String> {
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    Ok(spills::find_spills(&plan))
+}
+
+/// Node ids matching the query, for highlighting in large plans
+#[tauri::command]
+pub async fn search_plan(
+    plan_xml: String,
+    query: PlanSearchQuery,
+) -> Result<Vec<PlanSearchMatch>, String> {
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    Ok(search::search(&plan, &query))
+}
+
+/// Actual vs estimated rows per node with a severity bucket, for heat-mapping
+#[tauri::command]
+pub async fn get_estimate_skew(plan_xml: String) -> Result<Vec<NodeEstimateSkew>, String> {
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    Ok(skew::estimate_skew(&plan))
+}
+
+/// Rule-based plain-English walkthrough of each statement's plan
+#[tauri::command]
+pub async fn explain_plan(plan_xml: String) -> Result<PlanExplanation, String> {
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    Ok(explain::explain(&plan))
+}
+
+/// Bundled description of an operator, with common causes and fixes
+#[tauri::command]
+pub async fn get_operator_docs(op_name: String) -> Result<OperatorDoc, String> {
+    operator_docs::lookup(&op_name)
+        .cloned()
+        .ok_or_else(|| format!("No documentation for operator {}", op_name))
+}
+
+#[tauri::command]
+pub async fn list_operator_docs() -> Result<Vec<OperatorDoc>, String> {
+    Ok(operator_docs::all().to_vec())
+}
+
+/// A built-in export theme ("light" or "dark"), for seeding the settings editor
+#[tauri::command]
+pub async fn get_plan_theme_preset(name: String) -> Result<PlanTheme, String> {
+    match name.as_str() {
+        "light" => Ok(PlanTheme::light()),
+        "dark" => Ok(PlanTheme::dark()),
+        _ => Err(format!("Unknown plan theme: {}", name)),
+    }
+}
+
+/// Issues the enabled analysis rules, built-in and custom, find in each
+/// statement
+#[tauri::command]
+pub async fn analyze_plan(plan_xml: String, app: tauri::AppHandle) -> Result<PlanAnalysis, String> {
+    let app_settings = settings::load(&app)?;
+    let plan = parser::parse_plan_async(plan_xml).await?;
+    let rules = rules::all_rules(&app_settings.custom_analysis_rules);
+    Ok(rules::analyze(
+        &plan,
+        &rules,
+        &app_settings.disabled_analysis_rules,
+    ))
+}
+
+fn rules_list(app_settings: &settings::AppSettings) -> Vec<AnalysisRuleInfo> {
+    rules::rule_infos(
+        &rules::all_rules(&app_settings.custom_analysis_rules),
+        &app_settings.disabled_analysis_rules,
+    )
+}
+
+#[tauri::command]
+pub async fn list_analysis_rules(app: tauri::AppHandle) -> Result<Vec<AnalysisRuleInfo>, String> {
+    Ok(rules_list(&settings::load(&app)?))
+}
+
+/// Turn a rule on or off in settings; returns the updated rules list
+#[tauri::command]
+pub async fn set_analysis_rule_enabled(
+    rule_id: String,
+    enabled: bool,
+    app: tauri::AppHandle,
+) -> Result<Vec<AnalysisRuleInfo>, String> {
+    let mut app_settings = settings::load(&app)?;
+    if !rules_list(&app_settings)
+        .iter()
+        .any(|rule| rule.id == rule_id)
+    {
+        return Err(format!("Unknown analysis rule: {}", rule_id));
+    }
+    app_settings
+        .disabled_analysis_rules
+        .retain(|id| *id != rule_id);
+    if !enabled {
+        app_settings.disabled_analysis_rules.push(rule_id);
+    }
+    settings::save(&app, &app_settings)?;
+    Ok(rules_list(&app_settings))
+}
+
+/// Add a custom rule, or replace the one with the same id
+#[tauri::command]
+pub async fn save_custom_analysis_rule(
+    rule: CustomRuleDefinition,
+    app: tauri::AppHandle,
+) -> Result<Vec<AnalysisRuleInfo>, String> {
+    rules::validate_custom_rule(&rule)?;
+    let mut app_settings = settings::load(&app)?;
+    let custom = &mut app_settings.custom_analysis_rules;
+    match custom.iter_mut().find(|r| r.id == rule.id) {
+        Some(existing) => *existing = rule,
+        None => custom.push(rule),
+    }
+    settings::save(&app, &app_settings)?;
+    Ok(rules_list(&app_settings))
+}
+
+#[tauri::command]
+pub async fn delete_custom_analysis_rule(
+    rule_id: String,
+    app: tauri::AppHandle,
+) -> Result<Vec<AnalysisRuleInfo>, String> {
+    let mut app_settings = settings::load(&app)?;
+    app_settings
+        .custom_analysis_rules
+        .retain(|r| r.id != rule_id);
+    app_settings
+        .disabled_analysis_rules
+        .retain(|id| *id != rule_id);
+    settings::save(&app, &app_settings)?;
+    Ok(rules_list(&app_settings))
+}
+
+/// Check a rule condition while it is typed; the error names the column
+#[tauri::command]
+pub async fn validate_rule_expression(expression: String) -> Result<(), String> {
+    rules::expression::parse(&expression).map(|_| ())
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use super::summary::object_display_name;
+use super::types::*;
+
+/// Compare two plans of the same batch statement by statement: cost change
+/// and which operators appear only in one of them.
+pub fn compare(before: &ParsedPlan, after: &ParsedPlan) -> PlanComparison {
+    let count = before.statements.len().max(after.statements.len());
+    let statements = (0..count)
+        .map(|i| compare_statement(before.statements.get(i), after.statements.get(i)))
+        .collect();
+    PlanComparison { statements }
+}
+
+fn compare_statement(
+    before: Option<&PlanStatement>,
+    after: Option<&PlanStatement>,
+) -> StatementComparison {
+    let cost_before = before.map(|s| s.statement_sub_tree_cost).unwrap_or(0.0);
+    let cost_after = after.map(|s| s.statement_sub_tree_cost).unwrap_or(0.0);
+
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for label in operator_labels(before) {
+        *counts.entry(label).or_default() -= 1;
+    }
+    for label in operator_labels(after) {
+        *counts.entry(label).or_default() += 1;
+    }
+
+    let mut operators_added = Vec::new();
+    let mut operators_removed = Vec::new();
+    for (label, delta) in counts {
+        let target = if delta > 0 {
+            &mut operators_added
+        } else {
+            &mut operators_removed
+        };
+        for _ in 0..delta.abs() {
+            target.push(label.clone());
+        }
+    }
+    operators_added.sort();
+    operators_removed.sort();
+
+    StatementComparison {
+        statement_id: after.or(before).map(|s| s.statement_id).unwrap_or(0),
+        statement_text: after
+            .or(before)
+            .map(|s| s.statement_text.clone())
+            .unwrap_or_default(),
+        cost_before,
+        cost_after,
+        cost_change_percent: (cost_before > 0.0)
+            .then(|| (cost_after - cost_before) / cost_before * 100.0),
+        operators_added,
+        operators_removed,
+    }
+}
+
+/// "Index Seek (dbo.Orders.IX_Date)" for every operator in the statement
+fn operator_labels(stmt: Option<&PlanStatement>) -> Vec<String> {
+    let mut labels = Vec::new();
+    if let Some(root) = stmt.and_then(|s| s.root.as_ref()) {
+        collect_labels(root, &mut labels);
+    }
+    labels
+}
+
+fn collect_labels(node: &PlanNode, out: &mut Vec<String>) {
+    out.push(match object_display_name(node) {
+        Some(object) => format!("{} ({})", node.physical_op, object),
+        None => node.physical_op.clone(),
+    });
+    for child in &node.children {
+        collect_labels(child, out);
+    }
+}
+
+/// Whether any operator in the plan reads the given index
+pub fn uses_index(plan: &ParsedPlan, index_name: &str) -> bool {
+    fn visit(node: &PlanNode, index_name: &str) -> bool {
+        node.object
+            .as_ref()
+            .and_then(|o| o.index.as_deref())
+            .is_some_and(|i| i.eq_ignore_ascii_case(index_name))
+            || node.children.iter().any(|c| visit(c, index_name))
+    }
+    plan.statements
+        .iter()
+        .filter_map(|s| s.root.as_ref())
+        .any(|root| visit(root, index_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    fn plan(cost: &str, scan: &str) -> ParsedPlan {
+        parse_plan(&format!(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q" StatementSubTreeCost="{cost}">
+                <QueryPlan>
+                  <RelOp NodeId="0" PhysicalOp="Nested Loops" LogicalOp="Inner Join" EstimatedTotalSubtreeCost="{cost}">
+                    <NestedLoops>{scan}</NestedLoops>
+                  </RelOp>
+                </QueryPlan>
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compare_reports_cost_and_operator_changes() {
+        let before = plan(
+            "4",
+            r#"<RelOp NodeId="1" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimatedTotalSubtreeCost="4">
+                 <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[PK_Orders]" /></IndexScan></RelOp>"#,
+        );
+        let after = plan(
+            "1",
+            r#"<RelOp NodeId="1" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimatedTotalSubtreeCost="1">
+                 <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[hypo_1]" /></IndexScan></RelOp>"#,
+        );
+
+        let comparison = compare(&before, &after);
+        let stmt = &comparison.statements[0];
+        assert_eq!(stmt.cost_change_percent, Some(-75.0));
+        assert_eq!(stmt.operators_added, vec!["Index Seek (dbo.Orders.hypo_1)"]);
+        assert_eq!(
+            stmt.operators_removed,
+            vec!["Clustered Index Scan (dbo.Orders.PK_Orders)"]
+        );
+        assert!(uses_index(&after, "HYPO_1"));
+        assert!(!uses_index(&before, "hypo_1"));
+    }
+}
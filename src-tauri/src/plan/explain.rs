@@ -0,0 +1,300 @@
+use super::operator_docs;
+use super::skew::{estimate_ratio, estimated_rows, skew_factor};
+use super::spills;
+use super::summary::object_display_name;
+use super::types::*;
+
+/// Steps after this many are summarized in the narrative, not listed
+const MAX_NARRATIVE_STEPS: usize = 8;
+
+/// Estimates off by this factor get a note
+const NOTEWORTHY_SKEW: f64 = 100.0;
+
+/// Describe each statement's plan in plain English using templates per
+/// operator, walking inputs before the operators that consume them
+pub fn explain(plan: &ParsedPlan) -> PlanExplanation {
+    let spills = spills::find_spills(plan);
+    let statements = plan
+        .statements
+        .iter()
+        .map(|stmt| {
+            let mut steps = Vec::new();
+            let mut notes = Vec::new();
+            if let Some(root) = &stmt.root {
+                visit(root, &mut steps, &mut notes);
+            }
+            notes.extend(
+                spills
+                    .iter()
+                    .filter(|s| s.statement_id == stmt.statement_id)
+                    .map(|s| s.message.clone()),
+            );
+            StatementExplanation {
+                statement_id: stmt.statement_id,
+                statement_text: stmt.statement_text.clone(),
+                narrative: narrative(&steps),
+                steps,
+                notes,
+            }
+        })
+        .collect();
+    PlanExplanation { statements }
+}
+
+fn visit(node: &PlanNode, steps: &mut Vec<ExplanationStep>, notes: &mut Vec<String>) {
+    for child in &node.children {
+        visit(child, steps, notes);
+    }
+    if let Some(text) = describe(node) {
+        steps.push(ExplanationStep {
+            node_id: node.node_id,
+            physical_op: node.physical_op.clone(),
+            text,
+        });
+    }
+
+    if matches!(
+        node.physical_op.as_str(),
+        "Key Lookup" | "RID Lookup" | "Index Spool"
+    ) {
+        // The fix comes from the operator docs so the UI and the
+        // narrative give the same advice
+        let fix = operator_docs::lookup(&node.physical_op)
+            .and_then(|doc| doc.fixes.first())
+            .map(|fix| format!("; {}", lowercase_first(fix)))
+            .unwrap_or_default();
+        notes.push(format!(
+            "{} (node {}) {}{}",
+            node.physical_op,
+            node.node_id,
+            match node.physical_op.as_str() {
+                "Index Spool" => format!("builds a temporary index on {}", table_name(node)),
+                _ => format!("reads {} once per row", table_name(node)),
+            },
+            fix
+        ));
+    }
+    for warning in node.warnings.iter().filter(|w| !w.contains("Spill")) {
+        notes.push(format!(
+            "{} (node {}) has a {} warning",
+            node.physical_op, node.node_id, warning
+        ));
+    }
+    if let (Some(ratio), Some(rt)) = (estimate_ratio(node), &node.runtime) {
+        if skew_factor(ratio) >= NOTEWORTHY_SKEW {
+            notes.push(format!(
+                "{} (node {}) expected {} but got {}; statistics may be stale or the predicate hard to estimate",
+                node.physical_op,
+                node.node_id,
+                format_rows(estimated_rows(node)),
+                format_rows(rt.actual_rows)
+            ));
+        }
+    }
+}
+
+fn describe(node: &PlanNode) -> Option<String> {
+    let table = table_name(node);
+    let rows = rows(node);
+    let text = match node.physical_op.as_str() {
+        "Compute Scalar" | "Constant Scan" => return None,
+        "Table Scan" | "Clustered Index Scan" => {
+            with_predicate(format!("scans the entire {} table ({})", table, rows), node)
+        }
+        "Index Scan" | "Columnstore Index Scan" => with_predicate(
+            format!(
+                "reads all of index {} on {} ({})",
+                index_name(node),
+                table,
+                rows
+            ),
+            node,
+        ),
+        "Index Seek" | "Clustered Index Seek" => {
+            let mut text = format!("seeks into index {} on {}", index_name(node), table);
+            if !node.seek_predicates.is_empty() {
+                text.push_str(&format!(
+                    " to find rows where {}",
+                    node.seek_predicates.join(" and ")
+                ));
+            }
+            with_predicate(format!("{} ({})", text, rows), node)
+        }
+        "Key Lookup" | "RID Lookup" => format!(
+            "looks up the remaining columns in {} for each row, one read per row",
+            table
+        ),
+        "Nested Loops" => format!(
+            "for each row of the first input looks for matches in the second ({}, {})",
+            node.logical_op.to_lowercase(),
+            rows
+        ),
+        "Hash Match" if node.logical_op.contains("Aggregate") => {
+            format!("groups the rows using a hash table ({})", rows)
+        }
+        "Hash Match" => format!(
+            "hash joins the two inputs ({}, {})",
+            node.logical_op.to_lowercase(),
+            rows
+        ),
+        "Merge Join" => format!(
+            "merge joins the two sorted inputs ({}, {})",
+            node.logical_op.to_lowercase(),
+            rows
+        ),
+        "Adaptive Join" => format!(
+            "joins the inputs, choosing hash or nested loops at run time ({})",
+            rows
+        ),
+        "Sort" => format!("sorts {}", rows),
+        "Stream Aggregate" => format!("aggregates the sorted rows into {}", rows),
+        "Filter" => with_predicate(format!("filters the rows down to {}", rows), node),
+        "Top" => format!("keeps only the first {}", rows),
+        "Parallelism" => format!("{} across threads", node.logical_op.to_lowercase()),
+        "Table Spool" | "Index Spool" | "Row Count Spool" => format!(
+            "stores rows in a tempdb worktable to reuse them ({})",
+            node.physical_op.to_lowercase()
+        ),
+        "Concatenation" => format!("appends its inputs one after another ({})", rows),
+        "Segment" | "Sequence Project" => {
+            format!(
+                "numbers or ranks the rows ({})",
+                node.logical_op.to_lowercase()
+            )
+        }
+        "Clustered Index Insert" | "Table Insert" | "Index Insert" => {
+            format!("inserts {} into {}", rows, table)
+        }
+        "Clustered Index Update" | "Table Update" | "Index Update" => {
+            format!("updates {} in {}", rows, table)
+        }
+        "Clustered Index Delete" | "Table Delete" | "Index Delete" => {
+            format!("deletes {} from {}", rows, table)
+        }
+        op => format!("runs {} ({}, {})", op, node.logical_op, rows),
+    };
+    Some(text)
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn with_predicate(mut text: String, node: &PlanNode) -> String {
+    if let Some(predicate) = &node.predicate {
+        text.push_str(&format!(", keeping rows where {}", predicate));
+    }
+    text
+}
+
+/// "It scans ..., then sorts ..., then ..."
+fn narrative(steps: &[ExplanationStep]) -> String {
+    if steps.is_empty() {
+        return "This statement has no operators to explain.".to_string();
+    }
+    let mut parts: Vec<&str> = steps
+        .iter()
+        .take(MAX_NARRATIVE_STEPS)
+        .map(|s| s.text.as_str())
+        .collect();
+    let rest = steps.len().saturating_sub(MAX_NARRATIVE_STEPS);
+    let more = format!("{} more steps", rest);
+    if rest > 0 {
+        parts.push(&more);
+    }
+    format!("It {}.", parts.join(", then "))
+}
+
+fn rows(node: &PlanNode) -> String {
+    match &node.runtime {
+        Some(rt) => format_rows(rt.actual_rows),
+        None => format!("about {}", format_rows(estimated_rows(node))),
+    }
+}
+
+/// "1 row", "350 rows", "12K rows", "1.2M rows"
+fn format_rows(rows: f64) -> String {
+    let rows = rows.round();
+    if rows == 1.0 {
+        "1 row".to_string()
+    } else if rows >= 1_000_000.0 {
+        format!("{:.1}M rows", rows / 1_000_000.0)
+    } else if rows >= 10_000.0 {
+        format!("{:.0}K rows", rows / 1_000.0)
+    } else {
+        format!("{:.0} rows", rows)
+    }
+}
+
+fn table_name(node: &PlanNode) -> String {
+    node.object
+        .as_ref()
+        .and_then(|o| {
+            o.table.as_ref().map(|t| match &o.schema {
+                Some(schema) => format!("{}.{}", schema, t),
+                None => t.clone(),
+            })
+        })
+        .or_else(|| object_display_name(node))
+        .unwrap_or_else(|| "a table".to_string())
+}
+
+fn index_name(node: &PlanNode) -> String {
+    node.object
+        .as_ref()
+        .and_then(|o| o.index.clone())
+        .unwrap_or_else(|| "(unnamed)".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    #[test]
+    fn test_explain_join_narrative() {
+        let plan = parse_plan(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q"><QueryPlan>
+                <RelOp NodeId="0" PhysicalOp="Hash Match" LogicalOp="Inner Join" EstimateRows="5000">
+                  <Hash>
+                    <RelOp NodeId="1" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="1200000">
+                      <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[PK_Orders]" /></IndexScan>
+                    </RelOp>
+                    <RelOp NodeId="2" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimateRows="1">
+                      <IndexScan>
+                        <SeekPredicates><SeekPredicateNew><SeekKeys><Prefix ScanType="EQ">
+                          <RangeColumns><ColumnReference Column="[Id]" /></RangeColumns>
+                          <RangeExpressions><ScalarOperator ScalarString="(7)" /></RangeExpressions>
+                        </Prefix></SeekKeys></SeekPredicateNew></SeekPredicates>
+                        <Object Schema="[dbo]" Table="[Customers]" Index="[PK_Customers]" />
+                      </IndexScan>
+                    </RelOp>
+                  </Hash>
+                </RelOp>
+              </QueryPlan></StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#,
+        )
+        .unwrap();
+        let explanation = explain(&plan);
+        assert_eq!(
+            explanation.statements[0].narrative,
+            "It scans the entire dbo.Orders table (about 1.2M rows), then seeks into index \
+             PK_Customers on dbo.Customers to find rows where Id = (7) (about 1 row), then \
+             hash joins the two inputs (inner join, about 5000 rows)."
+        );
+        assert!(explanation.statements[0].notes.is_empty());
+    }
+
+    #[test]
+    fn test_format_rows() {
+        assert_eq!(format_rows(1.0), "1 row");
+        assert_eq!(format_rows(9_999.0), "9999 rows");
+        assert_eq!(format_rows(35_400.0), "35K rows");
+        assert_eq!(format_rows(1_234_567.0), "1.2M rows");
+    }
+}
@@ -0,0 +1,17 @@
+pub mod types;
+pub mod parser;
+pub mod schema;
+pub mod summary;
+pub mod render;
+pub mod compare;
+pub mod regression;
+pub mod spills;
+pub mod sniffing;
+pub mod search;
+pub mod skew;
+pub mod explain;
+pub mod operator_docs;
+pub mod parallelism;
+pub mod rules;
+pub mod clipboard;
+pub mod commands;
@@ -0,0 +1,54 @@
+use std::sync::OnceLock;
+
+use super::types::OperatorDoc;
+
+// Plain-language descriptions of plan operators, bundled with the app so
+// the UI and the explain generator work offline and say the same thing.
+// The text lives in operator_docs.json next to this file.
+
+static DOCS: OnceLock<Vec<OperatorDoc>> = OnceLock::new();
+
+pub fn all() -> &'static [OperatorDoc] {
+    DOCS.get_or_init(|| {
+        serde_json::from_str(include_str!("operator_docs.json"))
+            .expect("operator_docs.json is valid")
+    })
+}
+
+/// Documentation of a physical operator by name or alias, ignoring case
+pub fn lookup(physical_op: &str) -> Option<&'static OperatorDoc> {
+    let name = physical_op.trim();
+    all().iter().find(|doc| {
+        doc.name.eq_ignore_ascii_case(name)
+            || doc.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docs_cover_explained_operators() {
+        for op in [
+            "Table Scan",
+            "Clustered Index Scan",
+            "Index Seek",
+            "Key Lookup",
+            "RID Lookup",
+            "Nested Loops",
+            "Hash Match",
+            "Merge Join",
+            "Sort",
+            "Stream Aggregate",
+            "Parallelism",
+            "Index Spool",
+            "Table Insert",
+        ] {
+            let doc = lookup(op).unwrap_or_else(|| panic!("no docs for {}", op));
+            assert!(!doc.summary.is_empty() && !doc.fixes.is_empty(), "{}", op);
+        }
+        assert_eq!(lookup("key lookup").unwrap().name, "Key Lookup");
+        assert!(lookup("Made Up Operator").is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use super::types::*;
+
+/// Default cost threshold for parallelism, unchanged since the 1990s
+const DEFAULT_COST_THRESHOLD: i64 = 5;
+
+/// MAXDOP 0 uses every scheduler, but never more than this
+const MAX_DOP_LIMIT: i64 = 64;
+
+/// Whether each statement went parallel and why, explained against the
+/// server's cost threshold and MAXDOP
+pub fn analyze(plan: &ParsedPlan, settings: &ParallelismSettings) -> ParallelismReport {
+    let effective_max_dop = effective_max_dop(settings);
+    let statements = plan
+        .statements
+        .iter()
+        .filter(|stmt| stmt.root.is_some())
+        .map(|stmt| analyze_statement(stmt, settings, effective_max_dop))
+        .collect();
+    ParallelismReport {
+        settings: settings.clone(),
+        effective_max_dop,
+        statements,
+    }
+}
+
+fn effective_max_dop(settings: &ParallelismSettings) -> Option<i64> {
+    let configured = settings
+        .database_max_dop
+        .filter(|dop| *dop > 0)
+        .unwrap_or(settings.server_max_dop);
+    if configured > 0 {
+        return Some(configured);
+    }
+    settings.cpu_count.map(|cpus| cpus.min(MAX_DOP_LIMIT))
+}
+
+fn analyze_statement(
+    stmt: &PlanStatement,
+    settings: &ParallelismSettings,
+    effective_max_dop: Option<i64>,
+) -> StatementParallelism {
+    let dop = stmt.degree_of_parallelism;
+    let went_parallel =
+        dop.is_some_and(|d| d > 1) || stmt.root.as_ref().is_some_and(has_parallel_operator);
+    let cost = stmt.statement_sub_tree_cost;
+    let threshold = settings.cost_threshold;
+    let mut notes = Vec::new();
+
+    let reason = if went_parallel {
+        let mut reason = match dop {
+            Some(dop) => format!("The plan went parallel with DOP {}", dop),
+            None => "The plan went parallel".to_string(),
+        };
+        if let Some(stat) = &stmt.thread_stat {
+            match stat.used_threads.or(stat.reserved_threads) {
+                Some(threads) => reason.push_str(&format!(
+                    ", using {} worker threads in {} parallel branches",
+                    threads, stat.branches
+                )),
+                None => reason.push_str(&format!(" in {} parallel branches", stat.branches)),
+            }
+        }
+        reason.push('.');
+        notes.push(format!(
+            "The optimizer only considers a parallel plan when the serial plan costs more than \
+             the cost threshold for parallelism ({}). The cost shown ({:.2}) is the cheaper \
+             parallel plan's, so it can be below the threshold.",
+            threshold, cost
+        ));
+        if let (Some(dop), Some(max_dop)) = (dop, effective_max_dop) {
+            if dop < max_dop {
+                notes.push(format!(
+                    "DOP {} is below the MAXDOP of {}; a MAXDOP hint, Resource Governor or \
+                     busy schedulers lowered it.",
+                    dop, max_dop
+                ));
+            }
+        }
+        reason
+    } else if let Some(code) = stmt.non_parallel_plan_reason.as_deref() {
+        format!("The plan is serial: {}.", non_parallel_reason(code))
+    } else if effective_max_dop == Some(1) {
+        "The plan is serial because MAXDOP is 1.".to_string()
+    } else if stmt
+        .optimization_level
+        .as_deref()
+        .is_some_and(|l| l.eq_ignore_ascii_case("TRIVIAL"))
+    {
+        "The plan is serial because it is a trivial plan; the optimizer does not consider \
+         parallelism for queries with only one reasonable plan."
+            .to_string()
+    } else if cost <= threshold as f64 {
+        format!(
+            "The plan is serial because its cost ({:.2}) is not above the cost threshold for \
+             parallelism ({}).",
+            cost, threshold
+        )
+    } else {
+        format!(
+            "The plan is serial although its cost ({:.2}) is above the cost threshold for \
+             parallelism ({}); the optimizer found no cheaper parallel plan.",
+            cost, threshold
+        )
+    };
+
+    if threshold == DEFAULT_COST_THRESHOLD {
+        notes.push(format!(
+            "The cost threshold for parallelism is still the default of {}, which lets even \
+             cheap queries go parallel on modern hardware; values of 25 to 50 are common.",
+            DEFAULT_COST_THRESHOLD
+        ));
+    }
+    if settings.server_max_dop == 0 && settings.database_max_dop.unwrap_or(0) == 0 {
+        notes.push(
+            "MAXDOP is 0, so one query can use every scheduler; Microsoft recommends at most 8 \
+             or the number of cores per NUMA node."
+                .to_string(),
+        );
+    }
+
+    StatementParallelism {
+        statement_id: stmt.statement_id,
+        went_parallel,
+        degree_of_parallelism: dop,
+        thread_stat: stmt.thread_stat.clone(),
+        estimated_cost: cost,
+        reason,
+        notes,
+    }
+}
+
+fn has_parallel_operator(node: &PlanNode) -> bool {
+    node.parallel || node.children.iter().any(has_parallel_operator)
+}
+
+/// Plain wording of QueryPlan/@NonParallelPlanReason
+fn non_parallel_reason(code: &str) -> String {
+    let text = match code {
+        "MaxDOPSetToOne" => "MAXDOP is 1 for the server, database or query",
+        "EstimatedDOPIsOne" => "only one scheduler was available when the query was compiled",
+        "CouldNotGenerateValidParallelPlan" => {
+            "the optimizer could not build a valid parallel plan"
+        }
+        "NoParallelPlansInDesktopOrExpressEdition" => {
+            "this edition of SQL Server does not run parallel plans"
+        }
+        "TSQLUserDefinedFunctionsNotParallelizable" => "the query calls a T-SQL scalar function",
+        "NonParallelizableIntrinsicFunction" => {
+            "the query calls a built-in function that cannot run in parallel"
+        }
+        "CLRUserDefinedFunctionRequiresDataAccess" => {
+            "the query calls a CLR function that reads data"
+        }
+        "TableVariableTransactionsDoNotSupportParallelNestedTransaction" => {
+            "the query modifies a table variable"
+        }
+        "DMLQueryReturnsOutputToClient" => "the statement returns OUTPUT rows to the client",
+        "NoParallelForMemoryOptimizedTables" => "the query reads a memory-optimized table",
+        "NoParallelWithRemoteQuery" => "the query reads from a linked server",
+        "NoParallelCursorFetchOverDynamicCursor" | "NoParallelDynamicCursor" => {
+            "the query runs in a dynamic cursor"
+        }
+        "NoParallelFastForwardCursor" => "the query runs in a fast forward cursor",
+        "UpdatingWritebackVariable" => "the statement assigns to a variable",
+        "ParallelismDisabledByTraceFlag" => "a trace flag disables parallelism",
+        _ => return format!("the optimizer reported {}", code),
+    };
+    text.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    fn plan(query_plan: &str, cost: &str) -> ParsedPlan {
+        parse_plan(&format!(
+            r#"<ShowPlanXML Version="1.6"><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementSubTreeCost="{}" StatementOptmLevel="FULL">
+                {}
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#,
+            cost, query_plan
+        ))
+        .unwrap()
+    }
+
+    fn settings() -> ParallelismSettings {
+        ParallelismSettings {
+            cost_threshold: 50,
+            server_max_dop: 8,
+            database_max_dop: Some(0),
+            cpu_count: Some(16),
+        }
+    }
+
+    #[test]
+    fn test_parallel_plan_reports_dop_and_threads() {
+        let plan = plan(
+            r#"<QueryPlan DegreeOfParallelism="4">
+                 <ThreadStat Branches="2" UsedThreads="8">
+                   <ThreadReservation NodeId="0" ReservedThreads="8" />
+                 </ThreadStat>
+                 <RelOp NodeId="0" PhysicalOp="Parallelism" Parallel="true" />
+               </QueryPlan>"#,
+            "62.5",
+        );
+        let report = analyze(&plan, &settings());
+        assert_eq!(report.effective_max_dop, Some(8));
+        let stmt = &report.statements[0];
+        assert!(stmt.went_parallel);
+        assert_eq!(stmt.thread_stat.as_ref().unwrap().reserved_threads, Some(8));
+        assert_eq!(
+            stmt.reason,
+            "The plan went parallel with DOP 4, using 8 worker threads in 2 parallel branches."
+        );
+        assert!(stmt
+            .notes
+            .iter()
+            .any(|n| n.contains("below the MAXDOP of 8")));
+    }
+
+    #[test]
+    fn test_serial_plan_reasons() {
+        let cheap = plan(
+            r#"<QueryPlan><RelOp NodeId="0" PhysicalOp="Table Scan" /></QueryPlan>"#,
+            "12.5",
+        );
+        let report = analyze(&cheap, &settings());
+        assert!(!report.statements[0].went_parallel);
+        assert!(report.statements[0].reason.contains("(12.50) is not above"));
+
+        let forced = plan(
+            r#"<QueryPlan NonParallelPlanReason="TSQLUserDefinedFunctionsNotParallelizable">
+                 <RelOp NodeId="0" PhysicalOp="Compute Scalar" />
+               </QueryPlan>"#,
+            "80",
+        );
+        let report = analyze(&forced, &settings());
+        assert_eq!(
+            report.statements[0].reason,
+            "The plan is serial: the query calls a T-SQL scalar function."
+        );
+    }
+}
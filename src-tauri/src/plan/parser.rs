@@ -0,0 +1,688 @@
+use std::collections::{HashMap, HashSet};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use super::schema;
+use super::types::*;
+
+/// Children of a RelOp that are not the operator-specific element
+const RELOP_COMMON_CHILDREN: &[&str] = &[
+    "OutputList",
+    "Warnings",
+    "MemoryFractions",
+    "RunTimeInformation",
+    "RunTimePartitionSummary",
+    "InternalInfo",
+];
+
+const STATEMENT_ELEMENTS: &[&str] = &[
+    "StmtSimple",
+    "StmtCond",
+    "StmtCursor",
+    "StmtReceive",
+    "StmtUseDb",
+];
+
+/// An open RelOp while its subtree is being read
+struct NodeFrame {
+    node: PlanNode,
+    depth: usize,
+    operator_seen: bool,
+    warnings_depth: Option<usize>,
+    predicate_depth: Option<usize>,
+    seek: Option<SeekState>,
+}
+
+#[derive(Default)]
+struct SeekState {
+    depth: usize,
+    scan_type: Option<String>,
+    columns: Vec<String>,
+    expressions: Vec<String>,
+    range_columns_depth: Option<usize>,
+    range_expressions_depth: Option<usize>,
+}
+
+/// Parse ShowPlan XML with a streaming reader, building the RelOp tree as
+/// elements are encountered rather than materializing a DOM.
+pub fn parse_plan(xml: &str) -> Result<ParsedPlan, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut parser = PlanParser::default();
+    let mut depth = 0usize;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                depth += 1;
+                parser.start(e, depth);
+            }
+            Ok(Event::Empty(ref e)) => {
+                parser.start(e, depth + 1);
+                parser.end(&local_name(e), depth + 1);
+            }
+            Ok(Event::End(ref e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                parser.end(&name, depth);
+                depth = depth.saturating_sub(1);
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(format!(
+                    "Invalid plan XML at position {}: {}",
+                    reader.buffer_position(),
+                    e
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    if !parser.seen_root {
+        return Err("Not a ShowPlan XML document".into());
+    }
+
+    Ok(ParsedPlan {
+        product: parser.build.as_deref().and_then(schema::product_name),
+        unavailable_attributes: schema::unavailable(parser.build.as_deref(), &parser.seen, parser.actual),
+        version: parser.version,
+        build: parser.build,
+        statements: parser.statements,
+    })
+}
+
+/// `parse_plan` on a blocking thread: multi-megabyte plans take long enough
+/// to hold up other commands on the async runtime
+pub async fn parse_plan_async(xml: String) -> Result<ParsedPlan, String> {
+    tokio::task::spawn_blocking(move || parse_plan(&xml))
+        .await
+        .map_err(|e| format!("Plan parsing failed: {}", e))?
+}
+
+#[derive(Default)]
+struct PlanParser {
+    seen_root: bool,
+    version: Option<String>,
+    build: Option<String>,
+    statements: Vec<PlanStatement>,
+    statement_stack: Vec<usize>,
+    frames: Vec<NodeFrame>,
+    /// Names of the schema::FEATURES present
+    seen: HashSet<&'static str>,
+    /// Runtime counters were found
+    actual: bool,
+}
+
+impl PlanParser {
+    fn start(&mut self, e: &BytesStart, depth: usize) {
+        let name = local_name(e);
+        self.note_features(&name, e);
+
+        if name == "ShowPlanXML" {
+            let attrs = extract_attrs(e);
+            self.seen_root = true;
+            self.version = attrs.get("Version").cloned();
+            self.build = attrs.get("Build").cloned();
+            return;
+        }
+
+        if STATEMENT_ELEMENTS.contains(&name.as_str()) {
+            let attrs = extract_attrs(e);
+            self.statements.push(PlanStatement {
+                statement_id: attr_i64(&attrs, "StatementId").unwrap_or(self.statements.len() as i64 + 1),
+                statement_text: attrs.get("StatementText").cloned().unwrap_or_default(),
+                statement_type: attrs.get("StatementType").cloned().unwrap_or_default(),
+                statement_sub_tree_cost: attr_f64(&attrs, "StatementSubTreeCost").unwrap_or(0.0),
+                statement_est_rows: attr_f64(&attrs, "StatementEstRows").unwrap_or(0.0),
+                query_hash: attrs.get("QueryHash").cloned(),
+                query_plan_hash: attrs.get("QueryPlanHash").cloned(),
+                degree_of_parallelism: None,
+                non_parallel_plan_reason: None,
+                optimization_level: attrs.get("StatementOptmLevel").cloned(),
+                thread_stat: None,
+                memory_grant: None,
+                root: None,
+            });
+            self.statement_stack.push(self.statements.len() - 1);
+            return;
+        }
+
+        if name == "QueryPlan" && self.frames.is_empty() {
+            let attrs = extract_attrs(e);
+            if let Some(&idx) = self.statement_stack.last() {
+                self.statements[idx].degree_of_parallelism = attr_i64(&attrs, "DegreeOfParallelism");
+                self.statements[idx].non_parallel_plan_reason = attrs.get("NonParallelPlanReason").cloned();
+                // Before MemoryGrantInfo (SQL Server 2012) only the grant was recorded
+                if let Some(granted) = attr_f64(&attrs, "MemoryGrant") {
+                    self.statements[idx].memory_grant.get_or_insert_with(|| MemoryGrant {
+                        granted_kb: Some(granted),
+                        ..Default::default()
+                    });
+                }
+            }
+            return;
+        }
+
+        if name == "ThreadStat" && self.frames.is_empty() {
+            let attrs = extract_attrs(e);
+            if let Some(&idx) = self.statement_stack.last() {
+                self.statements[idx].thread_stat = Some(ThreadStat {
+                    branches: attr_i64(&attrs, "Branches").unwrap_or(0),
+                    used_threads: attr_i64(&attrs, "UsedThreads"),
+                    reserved_threads: None,
+                });
+            }
+            return;
+        }
+
+        if name == "ThreadReservation" && self.frames.is_empty() {
+            let attrs = extract_attrs(e);
+            if let Some(&idx) = self.statement_stack.last() {
+                if let Some(stat) = self.statements[idx].thread_stat.as_mut() {
+                    let reserved = attr_i64(&attrs, "ReservedThreads").unwrap_or(0);
+                    stat.reserved_threads = Some(stat.reserved_threads.unwrap_or(0) + reserved);
+                }
+            }
+            return;
+        }
+
+        if name == "MemoryGrantInfo" && self.frames.is_empty() {
+            let attrs = extract_attrs(e);
+            if let Some(&idx) = self.statement_stack.last() {
+                self.statements[idx].memory_grant = Some(MemoryGrant {
+                    serial_required_kb: attr_f64(&attrs, "SerialRequiredMemory"),
+                    serial_desired_kb: attr_f64(&attrs, "SerialDesiredMemory"),
+                    requested_kb: attr_f64(&attrs, "RequestedMemory"),
+                    granted_kb: attr_f64(&attrs, "GrantedMemory"),
+                    max_used_kb: attr_f64(&attrs, "MaxUsedMemory"),
+                    grant_wait_ms: attr_f64(&attrs, "GrantWaitTime"),
+                });
+            }
+            return;
+        }
+
+        if name == "RelOp" {
+            let attrs = extract_attrs(e);
+            self.frames.push(NodeFrame {
+                node: PlanNode {
+                    node_id: attr_i64(&attrs, "NodeId").unwrap_or(-1),
+                    physical_op: attrs.get("PhysicalOp").cloned().unwrap_or_default(),
+                    logical_op: attrs.get("LogicalOp").cloned().unwrap_or_default(),
+                    estimate_rows: attr_f64(&attrs, "EstimateRows").unwrap_or(0.0),
+                    estimate_cpu: attr_f64(&attrs, "EstimateCPU").unwrap_or(0.0),
+                    estimate_io: attr_f64(&attrs, "EstimateIO").unwrap_or(0.0),
+                    estimated_total_subtree_cost: attr_f64(&attrs, "EstimatedTotalSubtreeCost")
+                        .unwrap_or(0.0),
+                    estimate_rebinds: attr_f64(&attrs, "EstimateRebinds").unwrap_or(0.0),
+                    estimate_rewinds: attr_f64(&attrs, "EstimateRewinds").unwrap_or(0.0),
+                    parallel: attrs
+                        .get("Parallel")
+                        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                        .unwrap_or(false),
+                    object: None,
+                    predicate: None,
+                    seek_predicates: Vec::new(),
+                    warnings: Vec::new(),
+                    spill: None,
+                    runtime: None,
+                    attributes: attrs,
+                    children: Vec::new(),
+                },
+                depth,
+                operator_seen: false,
+                warnings_depth: None,
+                predicate_depth: None,
+                seek: None,
+            });
+            return;
+        }
+
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+
+        // Direct children of the RelOp
+        if depth == frame.depth + 1 {
+            if name == "Warnings" {
+                frame.warnings_depth = Some(depth);
+            } else if !frame.operator_seen && !RELOP_COMMON_CHILDREN.contains(&name.as_str()) {
+                frame.operator_seen = true;
+                for (k, v) in extract_attrs(e) {
+                    frame.node.attributes.entry(k).or_insert(v);
+                }
+            }
+            return;
+        }
+
+        if frame.warnings_depth.map(|d| depth == d + 1).unwrap_or(false) {
+            record_spill(&mut frame.node, &name, e);
+            frame.node.warnings.push(name);
+            return;
+        }
+
+        if name == "RunTimeCountersPerThread" && depth == frame.depth + 2 {
+            let attrs = extract_attrs(e);
+            let rt = frame.node.runtime.get_or_insert_with(RuntimeCounters::default);
+            rt.thread_count += 1;
+            rt.actual_rows += attr_f64(&attrs, "ActualRows").unwrap_or(0.0);
+            rt.actual_executions += attr_f64(&attrs, "ActualExecutions").unwrap_or(0.0);
+            // Elapsed time overlaps between threads, so take the slowest one
+            if let Some(v) = attr_f64(&attrs, "ActualElapsedms") {
+                rt.actual_elapsed_ms = Some(rt.actual_elapsed_ms.map_or(v, |cur| cur.max(v)));
+            }
+            add_counter(&mut rt.actual_cpu_ms, attr_f64(&attrs, "ActualCPUms"));
+            add_counter(&mut rt.actual_logical_reads, attr_f64(&attrs, "ActualLogicalReads"));
+            add_counter(&mut rt.actual_physical_reads, attr_f64(&attrs, "ActualPhysicalReads"));
+            return;
+        }
+
+        match name.as_str() {
+            "Object" if frame.node.object.is_none() => {
+                let attrs = extract_attrs(e);
+                frame.node.object = Some(PlanObject {
+                    database: attrs.get("Database").map(|s| strip_brackets(s)),
+                    schema: attrs.get("Schema").map(|s| strip_brackets(s)),
+                    table: attrs.get("Table").map(|s| strip_brackets(s)),
+                    index: attrs.get("Index").map(|s| strip_brackets(s)),
+                    alias: attrs.get("Alias").map(|s| strip_brackets(s)),
+                });
+            }
+            "Predicate" if frame.predicate_depth.is_none() && frame.node.predicate.is_none() => {
+                frame.predicate_depth = Some(depth);
+            }
+            "SeekKeys" | "Prefix" | "StartRange" | "EndRange" => {
+                frame.seek = Some(SeekState {
+                    depth,
+                    scan_type: extract_attrs(e).get("ScanType").cloned(),
+                    ..Default::default()
+                });
+            }
+            "RangeColumns" => {
+                if let Some(seek) = frame.seek.as_mut() {
+                    seek.range_columns_depth = Some(depth);
+                }
+            }
+            "RangeExpressions" => {
+                if let Some(seek) = frame.seek.as_mut() {
+                    seek.range_expressions_depth = Some(depth);
+                }
+            }
+            "ColumnReference" => {
+                if let Some(seek) = frame.seek.as_mut() {
+                    if seek.range_columns_depth == Some(depth - 1) {
+                        if let Some(col) = extract_attrs(e).get("Column") {
+                            seek.columns.push(strip_brackets(col));
+                        }
+                    }
+                }
+            }
+            "ScalarOperator" => {
+                let scalar = extract_attrs(e).get("ScalarString").cloned();
+                if let Some(seek) = frame.seek.as_mut() {
+                    if seek.range_expressions_depth == Some(depth - 1) {
+                        seek.expressions.push(scalar.unwrap_or_default());
+                    }
+                } else if frame.predicate_depth.is_some() && frame.node.predicate.is_none() {
+                    frame.node.predicate = scalar;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn note_features(&mut self, name: &str, e: &BytesStart) {
+        if name == "RunTimeCountersPerThread" {
+            self.actual = true;
+        }
+        for feature in schema::FEATURES.iter().filter(|f| f.element == name) {
+            let present = match feature.attribute {
+                None => true,
+                Some(attribute) => matches!(e.try_get_attribute(attribute), Ok(Some(_))),
+            };
+            if present {
+                self.seen.insert(feature.name());
+            }
+        }
+    }
+
+    fn end(&mut self, name: &str, depth: usize) {
+        if STATEMENT_ELEMENTS.contains(&name) {
+            self.statement_stack.pop();
+            return;
+        }
+
+        if name == "RelOp" {
+            let Some(frame) = self.frames.pop() else {
+                return;
+            };
+            if let Some(parent) = self.frames.last_mut() {
+                parent.node.children.push(frame.node);
+            } else if let Some(&idx) = self.statement_stack.last() {
+                let statement = &mut self.statements[idx];
+                if statement.root.is_none() {
+                    statement.root = Some(frame.node);
+                }
+            }
+            return;
+        }
+
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+
+        if frame.warnings_depth == Some(depth) {
+            frame.warnings_depth = None;
+        }
+        if frame.predicate_depth == Some(depth) {
+            frame.predicate_depth = None;
+        }
+        if let Some(seek) = frame.seek.as_mut() {
+            if seek.range_columns_depth == Some(depth) {
+                seek.range_columns_depth = None;
+            }
+            if seek.range_expressions_depth == Some(depth) {
+                seek.range_expressions_depth = None;
+            }
+        }
+        if frame.seek.as_ref().map(|s| s.depth == depth).unwrap_or(false) {
+            let seek = frame.seek.take().unwrap_or_default();
+            let op = match seek.scan_type.as_deref() {
+                Some("GT") => ">",
+                Some("GE") => ">=",
+                Some("LT") => "<",
+                Some("LE") => "<=",
+                Some("IS") => "IS",
+                Some("IS NOT") => "IS NOT",
+                _ => "=",
+            };
+            for (col, expr) in seek.columns.iter().zip(seek.expressions.iter()) {
+                frame.node.seek_predicates.push(format!("{} {} {}", col, op, expr));
+            }
+        }
+    }
+}
+
+/// Fold SpillToTempDb and the *SpillDetails siblings into one entry
+fn record_spill(node: &mut PlanNode, name: &str, e: &BytesStart) {
+    let kind = match name {
+        "SpillToTempDb" => None,
+        "SortSpillDetails" => Some(SpillKind::Sort),
+        "HashSpillDetails" => Some(SpillKind::Hash),
+        "ExchangeSpillDetails" => Some(SpillKind::Exchange),
+        _ => return,
+    };
+    let default_kind = match node.physical_op.as_str() {
+        "Sort" => SpillKind::Sort,
+        "Hash Match" => SpillKind::Hash,
+        op if op.contains("Exchange") || op.contains("Streams") => SpillKind::Exchange,
+        _ => SpillKind::Other,
+    };
+    let attrs = extract_attrs(e);
+    let spill = node.spill.get_or_insert(SpillDetails {
+        kind: default_kind,
+        spill_level: None,
+        spilled_thread_count: None,
+        granted_memory_kb: None,
+        used_memory_kb: None,
+        pages_written: None,
+        pages_read: None,
+    });
+    match kind {
+        None => {
+            spill.spill_level = attr_i64(&attrs, "SpillLevel").or(spill.spill_level);
+            spill.spilled_thread_count =
+                attr_i64(&attrs, "SpilledThreadCount").or(spill.spilled_thread_count);
+        }
+        Some(kind) => {
+            spill.kind = kind;
+            spill.granted_memory_kb = attr_f64(&attrs, "GrantedMemoryKb");
+            spill.used_memory_kb = attr_f64(&attrs, "UsedMemoryKb");
+            spill.pages_written = attr_f64(&attrs, "WritesToTempDb");
+            spill.pages_read = attr_f64(&attrs, "ReadsFromTempDb");
+        }
+    }
+}
+
+fn add_counter(total: &mut Option<f64>, value: Option<f64>) {
+    if let Some(v) = value {
+        *total = Some(total.unwrap_or(0.0) + v);
+    }
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+/// Attributes of an element with entities unescaped
+fn extract_attrs(e: &BytesStart) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for attr in e.attributes().flatten() {
+        let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_string();
+        let val = attr
+            .unescape_value()
+            .map(|v| v.to_string())
+            .unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).to_string());
+        map.insert(key, val);
+    }
+    map
+}
+
+fn attr_f64(attrs: &HashMap<String, String>, key: &str) -> Option<f64> {
+    attrs.get(key).and_then(|v| v.parse().ok())
+}
+
+fn attr_i64(attrs: &HashMap<String, String>, key: &str) -> Option<i64> {
+    attrs.get(key).and_then(|v| v.parse().ok())
+}
+
+pub fn strip_brackets(s: &str) -> String {
+    s.trim_start_matches('[').trim_end_matches(']').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMPLE_PLAN: &str = include_str!("../../../src/test-fixtures/simple-plan.xml");
+
+    #[test]
+    fn test_parse_simple_plan() {
+        let plan = parse_plan(SIMPLE_PLAN).expect("fixture should parse");
+        assert_eq!(plan.version.as_deref(), Some("1.5"));
+        assert_eq!(plan.statements.len(), 1);
+
+        let stmt = &plan.statements[0];
+        assert_eq!(stmt.statement_text, "SELECT * FROM Users");
+        assert_eq!(stmt.degree_of_parallelism, Some(1));
+
+        let root = stmt.root.as_ref().expect("root operator");
+        assert_eq!(root.physical_op, "Table Scan");
+        assert_eq!(root.estimate_rows, 100.0);
+        assert!(root.children.is_empty());
+        let object = root.object.as_ref().expect("scan object");
+        assert_eq!(object.table.as_deref(), Some("Users"));
+        assert_eq!(object.index.as_deref(), Some("PK_Users"));
+        assert_eq!(root.attributes.get("Ordered").map(String::as_str), Some("false"));
+    }
+
+    #[test]
+    fn test_parse_nested_operators_and_runtime() {
+        let xml = r#"<ShowPlanXML xmlns="http://schemas.microsoft.com/sqlserver/2004/07/showplan" Version="1.6">
+          <BatchSequence><Batch><Statements>
+            <StmtSimple StatementId="1" StatementText="SELECT ..." StatementSubTreeCost="1.5">
+              <QueryPlan DegreeOfParallelism="4">
+                <RelOp NodeId="0" PhysicalOp="Nested Loops" LogicalOp="Inner Join" EstimateRows="10" EstimatedTotalSubtreeCost="1.5">
+                  <RunTimeInformation>
+                    <RunTimeCountersPerThread Thread="0" ActualRows="7" ActualExecutions="1" ActualElapsedms="12" />
+                  </RunTimeInformation>
+                  <NestedLoops Optimized="false">
+                    <RelOp NodeId="1" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimateRows="10" EstimatedTotalSubtreeCost="0.5">
+                      <Warnings><NoJoinPredicate /></Warnings>
+                      <IndexScan>
+                        <SeekPredicates><SeekPredicateNew><SeekKeys>
+                          <Prefix ScanType="EQ">
+                            <RangeColumns><ColumnReference Column="[Id]" /></RangeColumns>
+                            <RangeExpressions><ScalarOperator ScalarString="(42)"><Const ConstValue="(42)" /></ScalarOperator></RangeExpressions>
+                          </Prefix>
+                        </SeekKeys></SeekPredicateNew></SeekPredicates>
+                        <Object Table="[Orders]" Index="[IX_Orders]" />
+                      </IndexScan>
+                    </RelOp>
+                    <RelOp NodeId="2" PhysicalOp="Filter" LogicalOp="Filter" EstimateRows="5" EstimatedTotalSubtreeCost="0.4">
+                      <Filter StartupExpression="0">
+                        <RelOp NodeId="3" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="50" EstimatedTotalSubtreeCost="0.3">
+                          <RunTimeInformation>
+                            <RunTimeCountersPerThread Thread="1" ActualRows="30" ActualExecutions="1" />
+                            <RunTimeCountersPerThread Thread="2" ActualRows="20" ActualExecutions="1" />
+                          </RunTimeInformation>
+                          <TableScan><Object Table="[Customers]" /></TableScan>
+                        </RelOp>
+                        <Predicate><ScalarOperator ScalarString="[c].[Active]=(1)" /></Predicate>
+                      </Filter>
+                    </RelOp>
+                  </NestedLoops>
+                </RelOp>
+              </QueryPlan>
+            </StmtSimple>
+          </Statements></Batch></BatchSequence>
+        </ShowPlanXML>"#;
+
+        let plan = parse_plan(xml).unwrap();
+        let stmt = &plan.statements[0];
+        assert_eq!(stmt.degree_of_parallelism, Some(4));
+
+        let root = stmt.root.as_ref().unwrap();
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.runtime.as_ref().unwrap().actual_rows, 7.0);
+        assert!(root.object.is_none());
+
+        let seek = &root.children[0];
+        assert_eq!(seek.warnings, vec!["NoJoinPredicate".to_string()]);
+        assert_eq!(seek.seek_predicates, vec!["Id = (42)".to_string()]);
+        assert_eq!(seek.object.as_ref().unwrap().table.as_deref(), Some("Orders"));
+
+        let filter = &root.children[1];
+        assert_eq!(filter.predicate.as_deref(), Some("[c].[Active]=(1)"));
+        let scan = &filter.children[0];
+        let rt = scan.runtime.as_ref().unwrap();
+        assert_eq!(rt.actual_rows, 50.0);
+        assert_eq!(rt.thread_count, 2);
+        assert_eq!(scan.object.as_ref().unwrap().table.as_deref(), Some("Customers"));
+    }
+
+    #[test]
+    fn test_parse_sql_2008_plan() {
+        let xml = r#"<?xml version="1.0" encoding="utf-16"?>
+        <ShowPlanXML xmlns="http://schemas.microsoft.com/sqlserver/2004/07/showplan" Version="1.1" Build="10.50.4000.0">
+          <BatchSequence><Batch><Statements>
+            <StmtSimple StatementId="1" StatementText="SELECT ..." StatementSubTreeCost="0.5" QueryHash="0x1" QueryPlanHash="0x2">
+              <QueryPlan DegreeOfParallelism="1" MemoryGrant="1024">
+                <RelOp NodeId="0" PhysicalOp="Sort" LogicalOp="Sort" EstimateRows="10" EstimatedTotalSubtreeCost="0.5">
+                  <RunTimeInformation>
+                    <RunTimeCountersPerThread Thread="0" ActualRows="10" ActualExecutions="1" />
+                  </RunTimeInformation>
+                  <Sort Distinct="0" />
+                </RelOp>
+              </QueryPlan>
+            </StmtSimple>
+          </Statements></Batch></BatchSequence>
+        </ShowPlanXML>"#;
+
+        let plan = parse_plan(xml).unwrap();
+        assert_eq!(plan.product.as_deref(), Some("SQL Server 2008 R2"));
+        let stmt = &plan.statements[0];
+        assert_eq!(stmt.memory_grant.as_ref().unwrap().granted_kb, Some(1024.0));
+        assert_eq!(stmt.root.as_ref().unwrap().runtime.as_ref().unwrap().actual_rows, 10.0);
+        assert!(plan.unavailable_attributes.contains(&"ActualElapsedms".to_string()));
+        assert!(!plan.unavailable_attributes.contains(&"QueryHash".to_string()));
+
+        let current = parse_plan(SIMPLE_PLAN).unwrap();
+        assert!(current.unavailable_attributes.is_empty());
+    }
+
+    /// A plan of several megabytes: `width` scans under one Concatenation,
+    /// each with a long predicate, and a nested chain `depth` operators deep
+    fn large_plan(width: usize, depth: usize) -> String {
+        let predicate = format!("[o].[Status]=N'{}'", "x".repeat(400));
+        let scan = |id: usize| {
+            format!(
+                r#"<RelOp NodeId="{id}" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="100" EstimatedTotalSubtreeCost="0.01">
+                  <OutputList><ColumnReference Column="[Id]" /><ColumnReference Column="[Status]" /></OutputList>
+                  <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="90" ActualExecutions="1" ActualElapsedms="1" /></RunTimeInformation>
+                  <IndexScan Ordered="false"><Object Table="[Orders]" Index="[PK_Orders]" />
+                    <Predicate><ScalarOperator ScalarString="{predicate}" /></Predicate>
+                  </IndexScan>
+                </RelOp>"#
+            )
+        };
+        let scans: String = (0..width).map(|i| scan(depth + 1 + i)).collect();
+        let chain: String = (0..depth)
+            .map(|id| format!(r#"<RelOp NodeId="{id}" PhysicalOp="Compute Scalar" LogicalOp="Compute Scalar" EstimateRows="1" EstimatedTotalSubtreeCost="1"><ComputeScalar>"#))
+            .collect();
+        let tree = format!(
+            r#"{chain}<RelOp NodeId="{depth}" PhysicalOp="Concatenation" LogicalOp="Concatenation" EstimateRows="1" EstimatedTotalSubtreeCost="1"><Concatenation>{scans}</Concatenation></RelOp>{}"#,
+            "</ComputeScalar></RelOp>".repeat(depth)
+        );
+        format!(
+            r#"<ShowPlanXML Version="1.6" Build="15.0.2000.5"><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q" StatementSubTreeCost="1"><QueryPlan>{tree}</QueryPlan></StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#
+        )
+    }
+
+    #[test]
+    fn test_parse_multi_megabyte_plan() {
+        let xml = large_plan(5000, 500);
+        assert!(xml.len() > 4_000_000);
+
+        let plan = parse_plan(&xml).unwrap();
+        let mut node = plan.statements[0].root.as_ref().unwrap();
+        let mut depth = 0;
+        while node.physical_op == "Compute Scalar" {
+            node = &node.children[0];
+            depth += 1;
+        }
+        assert_eq!(depth, 500);
+        assert_eq!(node.children.len(), 5000);
+        assert_eq!(node.children[4999].runtime.as_ref().unwrap().actual_rows, 90.0);
+    }
+
+    // Timing is only meaningful in an optimised build:
+    // cargo test --release -- --ignored test_parse_multi_megabyte_plan_time
+    #[test]
+    #[ignore]
+    fn test_parse_multi_megabyte_plan_time() {
+        let xml = large_plan(5000, 500);
+        let started = std::time::Instant::now();
+        parse_plan(&xml).unwrap();
+        let elapsed = started.elapsed();
+        eprintln!("parsed {} bytes in {:?}", xml.len(), elapsed);
+        assert!(
+            elapsed < std::time::Duration::from_secs(1),
+            "parsing a {} byte plan took {:?}",
+            xml.len(),
+            elapsed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_plan_async_leaves_runtime_free() {
+        let xml = large_plan(5000, 500);
+        let parse = tokio::spawn(parse_plan_async(xml));
+        // On this single-threaded runtime the yield lets the spawned task run;
+        // parsing inline would finish it before control comes back
+        tokio::task::yield_now().await;
+        assert!(!parse.is_finished());
+
+        let plan = parse.await.unwrap().unwrap();
+        assert_eq!(plan.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_plan_xml() {
+        assert!(parse_plan("<root><child /></root>").is_err());
+        assert!(parse_plan("<ShowPlanXML><unclosed").is_err());
+    }
+}
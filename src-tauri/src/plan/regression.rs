@@ -0,0 +1,160 @@
+use super::compare;
+use super::types::*;
+
+/// Cost increase, in percent, that counts as a regression on its own
+const COST_REGRESSION_PERCENT: f64 = 20.0;
+
+/// Cost differences below this are noise from trivial plans
+const MIN_COST_DELTA: f64 = 0.01;
+
+/// Operators whose appearance in a changed plan usually means more work:
+/// full scans, blocking operators and per-row lookups
+const KEY_OPERATORS: &[&str] = &[
+    "Table Scan",
+    "Clustered Index Scan",
+    "Index Scan",
+    "Sort",
+    "Hash Match",
+    "Key Lookup",
+    "RID Lookup",
+    "Table Spool",
+    "Index Spool",
+];
+
+/// Flag `current` as a regression of `previous` when the estimated cost rose
+/// noticeably, or when the plan changed shape and picked up scans, sorts,
+/// hashes, lookups or spools.
+pub fn detect(
+    previous_plan_id: &str,
+    previous: &ParsedPlan,
+    current: &ParsedPlan,
+) -> Option<PlanRegression> {
+    let cost_before: f64 = previous
+        .statements
+        .iter()
+        .map(|s| s.statement_sub_tree_cost)
+        .sum();
+    let cost_after: f64 = current
+        .statements
+        .iter()
+        .map(|s| s.statement_sub_tree_cost)
+        .sum();
+    let cost_change_percent =
+        (cost_before > 0.0).then(|| (cost_after - cost_before) / cost_before * 100.0);
+
+    let plan_hash_changed = plan_hashes(previous) != plan_hashes(current);
+
+    let mut reasons = Vec::new();
+    if cost_after - cost_before >= MIN_COST_DELTA
+        && cost_change_percent.is_some_and(|p| p >= COST_REGRESSION_PERCENT)
+    {
+        reasons.push(format!(
+            "Estimated cost rose {:.0}% ({:.4} -> {:.4})",
+            cost_change_percent.unwrap_or(0.0),
+            cost_before,
+            cost_after
+        ));
+    }
+
+    if plan_hash_changed {
+        for stmt in compare::compare(previous, current).statements {
+            for label in stmt
+                .operators_added
+                .iter()
+                .filter(|label| is_key_operator(label))
+            {
+                reasons.push(format!("Statement {}: new {}", stmt.statement_id, label));
+            }
+        }
+    }
+
+    if reasons.is_empty() {
+        return None;
+    }
+    Some(PlanRegression {
+        previous_plan_id: previous_plan_id.to_string(),
+        cost_before,
+        cost_after,
+        cost_change_percent,
+        plan_hash_changed,
+        reasons,
+    })
+}
+
+fn plan_hashes(plan: &ParsedPlan) -> Vec<Option<&str>> {
+    plan.statements
+        .iter()
+        .map(|s| s.query_plan_hash.as_deref())
+        .collect()
+}
+
+/// Operator labels from `compare` are "Physical Op" or "Physical Op (object)"
+fn is_key_operator(label: &str) -> bool {
+    let op = label.split(" (").next().unwrap_or(label);
+    KEY_OPERATORS.contains(&op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    fn plan(hash: &str, cost: &str, op: &str) -> ParsedPlan {
+        parse_plan(&format!(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q" StatementSubTreeCost="{cost}" QueryPlanHash="{hash}">
+                <QueryPlan>
+                  <RelOp NodeId="0" PhysicalOp="{op}" LogicalOp="{op}" EstimatedTotalSubtreeCost="{cost}">
+                    <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[IX_Date]" /></IndexScan>
+                  </RelOp>
+                </QueryPlan>
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cost_increase_is_a_regression() {
+        let findings = detect(
+            "p1",
+            &plan("0x1", "1.0", "Index Seek"),
+            &plan("0x1", "1.5", "Index Seek"),
+        )
+        .unwrap();
+        assert_eq!(findings.cost_change_percent, Some(50.0));
+        assert!(!findings.plan_hash_changed);
+        assert_eq!(findings.reasons.len(), 1);
+    }
+
+    #[test]
+    fn test_new_scan_in_changed_plan_is_a_regression() {
+        let findings = detect(
+            "p1",
+            &plan("0x1", "1.0", "Index Seek"),
+            &plan("0x2", "1.05", "Index Scan"),
+        )
+        .unwrap();
+        assert!(findings.plan_hash_changed);
+        assert_eq!(
+            findings.reasons,
+            vec!["Statement 1: new Index Scan (dbo.Orders.IX_Date)"]
+        );
+    }
+
+    #[test]
+    fn test_cheaper_or_same_plan_is_not_a_regression() {
+        assert!(detect(
+            "p1",
+            &plan("0x1", "1.0", "Index Scan"),
+            &plan("0x2", "0.5", "Index Seek")
+        )
+        .is_none());
+        assert!(detect(
+            "p1",
+            &plan("0x1", "1.0", "Index Seek"),
+            &plan("0x1", "1.0", "Index Seek")
+        )
+        .is_none());
+    }
+}
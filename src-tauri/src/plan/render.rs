@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use resvg::{tiny_skia, usvg};
+
+use super::parser;
+use super::summary::{self, object_display_name};
+use super::types::*;
+
+// Static plan pictures for tickets and docs. Operators are laid out like the
+// SSMS graphical plan: the root on the left, each tree level one column to the
+// right, and a parent on the same row as its first child.
+
+const NODE_WIDTH: f64 = 180.0;
+const NODE_HEIGHT: f64 = 64.0;
+const COLUMN_GAP: f64 = 60.0;
+const ROW_GAP: f64 = 24.0;
+const MARGIN: f64 = 20.0;
+const HEADER_HEIGHT: f64 = 44.0;
+const LABEL_CHARS: usize = 26;
+const HEADER_CHARS: usize = 120;
+
+// Thumbnails keep the layout but draw each operator as a bare block
+const THUMBNAIL_WIDTH: f64 = 160.0;
+const THUMBNAIL_HEIGHT: f64 = 90.0;
+const THUMB_CELL_WIDTH: f64 = 16.0;
+const THUMB_CELL_HEIGHT: f64 = 9.0;
+const THUMB_BLOCK_WIDTH: f64 = 10.0;
+const THUMB_BLOCK_HEIGHT: f64 = 6.0;
+/// Space between stacked statements, in cells
+const THUMB_STATEMENT_GAP: f64 = 1.0;
+
+struct PlacedNode<'a> {
+    node: &'a PlanNode,
+    column: usize,
+    row: usize,
+}
+
+struct StatementLayout<'a> {
+    nodes: Vec<PlacedNode<'a>>,
+    /// (parent, child) indices into `nodes`
+    edges: Vec<(usize, usize)>,
+    columns: usize,
+    rows: usize,
+}
+
+fn layout_statement(root: &PlanNode) -> StatementLayout<'_> {
+    let mut layout = StatementLayout {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+        columns: 0,
+        rows: 0,
+    };
+    let mut next_row = 0;
+    place(root, 0, &mut next_row, &mut layout);
+    layout.rows = next_row;
+    layout
+}
+
+fn place<'a>(
+    node: &'a PlanNode,
+    column: usize,
+    next_row: &mut usize,
+    layout: &mut StatementLayout<'a>,
+) -> usize {
+    let index = layout.nodes.len();
+    layout.nodes.push(PlacedNode {
+        node,
+        column,
+        row: *next_row,
+    });
+    layout.columns = layout.columns.max(column + 1);
+
+    if node.children.is_empty() {
+        *next_row += 1;
+    }
+    for child in &node.children {
+        let child_index = place(child, column + 1, next_row, layout);
+        layout.edges.push((index, child_index));
+    }
+    index
+}
+
+/// Render every statement of the plan as one SVG document, stacked vertically.
+pub fn render_svg(plan: &ParsedPlan, theme: &PlanTheme) -> (String, f64, f64) {
+    let summary = summary::summarize(plan);
+    let layouts: Vec<(&PlanStatement, Option<StatementLayout>)> = plan
+        .statements
+        .iter()
+        .map(|s| (s, s.root.as_ref().map(layout_statement)))
+        .collect();
+
+    let column_width = NODE_WIDTH + COLUMN_GAP;
+    let row_height = NODE_HEIGHT + ROW_GAP;
+    let width = layouts
+        .iter()
+        .filter_map(|(_, l)| l.as_ref())
+        .map(|l| l.columns as f64 * column_width - COLUMN_GAP)
+        .fold(NODE_WIDTH * 3.0, f64::max)
+        + MARGIN * 2.0;
+    let height = layouts
+        .iter()
+        .map(|(_, l)| {
+            HEADER_HEIGHT
+                + l.as_ref()
+                    .map(|l| l.rows as f64 * row_height)
+                    .unwrap_or(0.0)
+        })
+        .sum::<f64>()
+        + MARGIN * 2.0;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="{font}">"#,
+        w = width,
+        h = height,
+        font = escape(&theme.font_family)
+    );
+    if let Some(background) = &theme.background {
+        let _ = write!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            escape(background)
+        );
+    }
+
+    let mut top = MARGIN;
+    for (stmt, layout) in &layouts {
+        let cost_by_node: HashMap<i64, f64> = summary
+            .statements
+            .iter()
+            .find(|s| s.statement_id == stmt.statement_id)
+            .map(|s| {
+                s.operators
+                    .iter()
+                    .map(|o| (o.node_id, o.cost_percent))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="13" font-weight="600" fill="{}">Statement {}: {}</text>"#,
+            MARGIN,
+            top + 16.0,
+            escape(&theme.heading),
+            stmt.statement_id,
+            escape(&truncate(stmt.statement_text.trim(), HEADER_CHARS))
+        );
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="11" fill="{}">Estimated subtree cost {:.4}</text>"#,
+            MARGIN,
+            top + 32.0,
+            escape(&theme.muted_text),
+            stmt.statement_sub_tree_cost
+        );
+        top += HEADER_HEIGHT;
+
+        let Some(layout) = layout else {
+            continue;
+        };
+        let origin = |p: &PlacedNode| {
+            (
+                MARGIN + p.column as f64 * column_width,
+                top + p.row as f64 * row_height,
+            )
+        };
+
+        // Edges first so boxes are drawn over them
+        for &(parent, child) in &layout.edges {
+            let (px, py) = origin(&layout.nodes[parent]);
+            let (cx, cy) = origin(&layout.nodes[child]);
+            let child_node = layout.nodes[child].node;
+            let rows = child_node
+                .runtime
+                .as_ref()
+                .map(|r| r.actual_rows)
+                .unwrap_or(child_node.estimate_rows);
+            let stroke = (1.0 + (rows + 1.0).log10()).min(8.0);
+            let start_x = px + NODE_WIDTH;
+            let start_y = py + NODE_HEIGHT / 2.0;
+            let end_y = cy + NODE_HEIGHT / 2.0;
+            let mid_x = start_x + COLUMN_GAP / 2.0;
+            let _ = write!(
+                svg,
+                r#"<path d="M{start_x} {start_y} H{mid_x} V{end_y} H{cx}" fill="none" stroke="{edge}" stroke-width="{stroke:.1}"/>"#,
+                edge = escape(&theme.edge)
+            );
+        }
+
+        for placed in &layout.nodes {
+            let (x, y) = origin(placed);
+            let node = placed.node;
+            let cost = cost_by_node.get(&node.node_id).copied().unwrap_or(0.0);
+            let fill = if cost >= 50.0 {
+                &theme.node_fill_hot
+            } else if cost >= 20.0 {
+                &theme.node_fill_warm
+            } else {
+                &theme.node_fill
+            };
+            let border = if node.warnings.is_empty() {
+                &theme.node_border
+            } else {
+                &theme.warning_border
+            };
+            let _ = write!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{NODE_WIDTH}" height="{NODE_HEIGHT}" rx="6" fill="{fill}" stroke="{border}"/>"#,
+                fill = escape(fill),
+                border = escape(border)
+            );
+
+            let title = if node.warnings.is_empty() {
+                node.physical_op.clone()
+            } else {
+                format!("! {}", node.physical_op)
+            };
+            let detail = object_display_name(node).unwrap_or_else(|| {
+                if node.logical_op != node.physical_op {
+                    node.logical_op.clone()
+                } else {
+                    String::new()
+                }
+            });
+            let lines = [
+                (title, 13.0, "600", &theme.node_title),
+                (detail, 11.0, "400", &theme.node_text),
+                (format!("Cost: {:.0}%", cost), 11.0, "400", &theme.node_text),
+            ];
+            for (i, (text, size, weight, color)) in lines.iter().enumerate() {
+                if text.is_empty() {
+                    continue;
+                }
+                let _ = write!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" font-weight="{}" fill="{}">{}</text>"#,
+                    x + 8.0,
+                    y + 18.0 + i as f64 * 17.0,
+                    size,
+                    weight,
+                    escape(color),
+                    escape(&truncate(text, LABEL_CHARS))
+                );
+            }
+        }
+
+        top += layout.rows as f64 * row_height;
+    }
+
+    svg.push_str("</svg>");
+    (svg, width, height)
+}
+
+/// Small sketch of the plan's shape for history previews: operators as
+/// blocks shaded by cost, statements stacked, scaled to a fixed size.
+/// Returns the SVG and the operator count.
+pub fn render_thumbnail_svg(plan: &ParsedPlan) -> (String, usize) {
+    let summary = summary::summarize(plan);
+    let layouts: Vec<(i64, StatementLayout)> = plan
+        .statements
+        .iter()
+        .filter_map(|s| Some((s.statement_id, layout_statement(s.root.as_ref()?))))
+        .collect();
+    let operator_count = layouts.iter().map(|(_, l)| l.nodes.len()).sum();
+
+    let columns = layouts.iter().map(|(_, l)| l.columns).max().unwrap_or(1);
+    let rows: f64 = layouts.iter().map(|(_, l)| l.rows as f64).sum::<f64>()
+        + THUMB_STATEMENT_GAP * layouts.len().saturating_sub(1) as f64;
+    let width = columns as f64 * THUMB_CELL_WIDTH;
+    let height = rows.max(1.0) * THUMB_CELL_HEIGHT;
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" preserveAspectRatio="xMinYMid meet">"#,
+        THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, width, height
+    );
+
+    let mut top = 0.0;
+    for (statement_id, layout) in &layouts {
+        let cost_by_node: HashMap<i64, f64> = summary
+            .statements
+            .iter()
+            .find(|s| s.statement_id == *statement_id)
+            .map(|s| {
+                s.operators
+                    .iter()
+                    .map(|o| (o.node_id, o.cost_percent))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let origin = |p: &PlacedNode| {
+            (
+                p.column as f64 * THUMB_CELL_WIDTH,
+                top + p.row as f64 * THUMB_CELL_HEIGHT,
+            )
+        };
+
+        for &(parent, child) in &layout.edges {
+            let (px, py) = origin(&layout.nodes[parent]);
+            let (cx, cy) = origin(&layout.nodes[child]);
+            let _ = write!(
+                svg,
+                r##"<path d="M{} {} H{} V{} H{}" fill="none" stroke="#9ca3af" stroke-width="0.8"/>"##,
+                px + THUMB_BLOCK_WIDTH,
+                py + THUMB_BLOCK_HEIGHT / 2.0,
+                px + THUMB_BLOCK_WIDTH + (THUMB_CELL_WIDTH - THUMB_BLOCK_WIDTH) / 2.0,
+                cy + THUMB_BLOCK_HEIGHT / 2.0,
+                cx
+            );
+        }
+        for placed in &layout.nodes {
+            let (x, y) = origin(placed);
+            let cost = cost_by_node
+                .get(&placed.node.node_id)
+                .copied()
+                .unwrap_or(0.0);
+            let fill = if cost >= 50.0 {
+                "#ef4444"
+            } else if cost >= 20.0 {
+                "#f97316"
+            } else if placed.node.warnings.is_empty() {
+                "#6b7280"
+            } else {
+                "#d97706"
+            };
+            let _ = write!(
+                svg,
+                r#"<rect x="{x}" y="{y}" width="{THUMB_BLOCK_WIDTH}" height="{THUMB_BLOCK_HEIGHT}" rx="1.5" fill="{fill}"/>"#
+            );
+        }
+
+        top += (layout.rows as f64 + THUMB_STATEMENT_GAP) * THUMB_CELL_HEIGHT;
+    }
+
+    svg.push_str("</svg>");
+    (svg, operator_count)
+}
+
+/// Thumbnail of a saved plan; PNG thumbnails are rendered at 2x for HiDPI lists
+pub fn render_thumbnail(
+    plan_id: String,
+    plan_xml: &str,
+    format: PlanImageFormat,
+) -> Result<PlanThumbnail, String> {
+    let plan = parser::parse_plan(plan_xml)?;
+    let (svg, operator_count) = render_thumbnail_svg(&plan);
+    let image = match format {
+        PlanImageFormat::Svg => PlanImage {
+            format,
+            width: THUMBNAIL_WIDTH as u32,
+            height: THUMBNAIL_HEIGHT as u32,
+            data: svg,
+        },
+        PlanImageFormat::Png => {
+            let (png, width, height) = render_png(&svg, 2.0)?;
+            PlanImage {
+                format,
+                width,
+                height,
+                data: BASE64.encode(png),
+            }
+        }
+    };
+    Ok(PlanThumbnail {
+        plan_id,
+        image,
+        operator_count,
+    })
+}
+
+/// Rasterize an SVG produced by `render_svg`; `scale` 2.0 gives a HiDPI image.
+pub fn render_png(svg: &str, scale: f32) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_str(svg, &options)
+        .map_err(|e| format!("Failed to load plan SVG: {}", e))?;
+
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or("Plan image is too large to render")?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or("Plan image is too large to render")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok((png, size.width(), size.height()))
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let single_line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if single_line.chars().count() <= max_chars {
+        single_line
+    } else {
+        let cut: String = single_line.chars().take(max_chars - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    const NESTED_PLAN: &str = r#"<ShowPlanXML xmlns="http://schemas.microsoft.com/sqlserver/2004/07/showplan">
+      <BatchSequence><Batch><Statements>
+        <StmtSimple StatementId="1" StatementText="SELECT * FROM a JOIN b ON a.id = b.id" StatementSubTreeCost="1">
+          <QueryPlan>
+            <RelOp NodeId="0" PhysicalOp="Hash Match" LogicalOp="Inner Join" EstimateRows="10" EstimatedTotalSubtreeCost="1">
+              <Hash>
+                <RelOp NodeId="1" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="10" EstimatedTotalSubtreeCost="0.2" />
+                <RelOp NodeId="2" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="1000" EstimatedTotalSubtreeCost="0.3" />
+              </Hash>
+            </RelOp>
+          </QueryPlan>
+        </StmtSimple>
+      </Statements></Batch></BatchSequence>
+    </ShowPlanXML>"#;
+
+    #[test]
+    fn test_parent_shares_row_with_first_child() {
+        let plan = parse_plan(NESTED_PLAN).unwrap();
+        let layout = layout_statement(plan.statements[0].root.as_ref().unwrap());
+
+        let positions: Vec<(i64, usize, usize)> = layout
+            .nodes
+            .iter()
+            .map(|p| (p.node.node_id, p.column, p.row))
+            .collect();
+        assert_eq!(positions, vec![(0, 0, 0), (1, 1, 0), (2, 1, 1)]);
+        assert_eq!(layout.edges, vec![(0, 1), (0, 2)]);
+        assert_eq!((layout.columns, layout.rows), (2, 2));
+    }
+
+    #[test]
+    fn test_svg_contains_escaped_labels() {
+        let mut plan = parse_plan(NESTED_PLAN).unwrap();
+        plan.statements[0].statement_text = "SELECT 1 WHERE a < b".into();
+        let (svg, width, height) = render_svg(&plan, &PlanTheme::default());
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Hash Match"));
+        assert!(svg.contains("a &lt; b"));
+        assert!(width > 0.0 && height > 0.0);
+    }
+
+    #[test]
+    fn test_svg_uses_theme_colors() {
+        let plan = parse_plan(NESTED_PLAN).unwrap();
+        let (svg, _, _) = render_svg(&plan, &PlanTheme::dark());
+        assert!(svg.contains(r##"fill="#0f172a""##));
+        assert!(!svg.contains("#ffffff"));
+
+        let transparent = PlanTheme {
+            background: None,
+            ..PlanTheme::default()
+        };
+        let (svg, _, _) = render_svg(&plan, &transparent);
+        assert!(!svg.contains(r#"height="100%""#));
+    }
+
+    #[test]
+    fn test_thumbnail_has_a_block_per_operator() {
+        let plan = parse_plan(NESTED_PLAN).unwrap();
+        let (svg, operators) = render_thumbnail_svg(&plan);
+
+        assert_eq!(operators, 3);
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(svg.contains(r#"viewBox="0 0 32 18""#));
+        // Labels are left out of thumbnails
+        assert!(!svg.contains("<text"));
+    }
+}
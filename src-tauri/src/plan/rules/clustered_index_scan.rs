@@ -0,0 +1,69 @@
+use super::{table_name, PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+const MIN_ROWS: f64 = 10_000.0;
+
+pub struct ClusteredIndexScan;
+
+impl PlanRule for ClusteredIndexScan {
+    fn id(&self) -> &str {
+        "clustered-index-scan"
+    }
+
+    fn title(&self) -> &str {
+        "Clustered Index Scan"
+    }
+
+    fn description(&self) -> &str {
+        "A clustered index is read in full for more than 10,000 estimated rows"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Warning
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| {
+                n.node.physical_op == "Clustered Index Scan" && n.node.estimate_rows > MIN_ROWS
+            })
+            .map(|n| {
+                self.issue(
+                    IssueSeverity::Warning,
+                    n.node,
+                    format!(
+                        "Full clustered index scan on {} with {:.0} rows. A non-clustered index might improve performance.",
+                        table_name(n.node),
+                        n.node.estimate_rows
+                    ),
+                    n.cost_percent * 1.5,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::test_plan;
+
+    #[test]
+    fn test_reports_large_scans_only() {
+        let large = test_plan(
+            2.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="250000" EstimatedTotalSubtreeCost="2" />"#,
+        );
+        let small = test_plan(
+            2.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="900" EstimatedTotalSubtreeCost="2" />"#,
+        );
+        let issues = ClusteredIndexScan.evaluate(&PlanTree::new(&large.statements[0]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].impact, 100.0);
+        assert!(ClusteredIndexScan
+            .evaluate(&PlanTree::new(&small.statements[0]))
+            .is_empty());
+    }
+}
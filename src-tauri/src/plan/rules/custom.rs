@@ -0,0 +1,63 @@
+use super::expression::{self, Expr};
+use super::{PlanRule, PlanTree};
+use crate::plan::types::{CustomRuleDefinition, IssueSeverity, PlanIssue};
+
+/// A rule from settings: one issue per operator the condition holds for
+pub struct ExpressionRule {
+    definition: CustomRuleDefinition,
+    condition: Expr,
+}
+
+impl ExpressionRule {
+    pub fn compile(definition: CustomRuleDefinition) -> Result<Self, String> {
+        let condition = expression::parse(&definition.expression)
+            .map_err(|e| format!("Rule '{}': {}", definition.id, e))?;
+        Ok(Self {
+            definition,
+            condition,
+        })
+    }
+}
+
+impl PlanRule for ExpressionRule {
+    fn id(&self) -> &str {
+        &self.definition.id
+    }
+
+    fn title(&self) -> &str {
+        &self.definition.title
+    }
+
+    fn description(&self) -> &str {
+        &self.definition.expression
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        self.definition.severity
+    }
+
+    fn is_custom(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| expression::matches(&self.condition, n))
+            .map(|n| {
+                let description = self.definition.message.clone().unwrap_or_else(|| {
+                    format!(
+                        "{} (node {}) matches {}",
+                        n.node.physical_op, n.node.node_id, self.definition.expression
+                    )
+                });
+                self.issue(
+                    self.definition.severity,
+                    n.node,
+                    description,
+                    n.cost_percent,
+                )
+            })
+            .collect()
+    }
+}
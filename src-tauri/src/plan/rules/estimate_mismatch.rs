@@ -0,0 +1,83 @@
+use super::{PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+/// Actual rows this many times over or under the estimate are reported
+const MISMATCH_FACTOR: f64 = 10.0;
+
+pub struct EstimateMismatch;
+
+impl PlanRule for EstimateMismatch {
+    fn id(&self) -> &str {
+        "estimate-mismatch"
+    }
+
+    fn title(&self) -> &str {
+        "Estimate Mismatch"
+    }
+
+    fn description(&self) -> &str {
+        "Actual rows differ from the estimate by more than 10x (actual plans only)"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Warning
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter_map(|n| {
+                let runtime = n.node.runtime.as_ref()?;
+                // EstimateRows is per execution; actual rows are summed over all of them
+                let estimated = n.node.estimate_rows * runtime.actual_executions.max(1.0);
+                if estimated <= 0.0 {
+                    return None;
+                }
+                let ratio = runtime.actual_rows / estimated;
+                if (1.0 / MISMATCH_FACTOR..=MISMATCH_FACTOR).contains(&ratio) {
+                    return None;
+                }
+                Some(self.issue(
+                    IssueSeverity::Warning,
+                    n.node,
+                    format!(
+                        "{}: Estimated {:.0} rows but got {:.0}. Statistics may be outdated.",
+                        n.node.physical_op, estimated, runtime.actual_rows
+                    ),
+                    ratio.log10().abs() * 20.0,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::test_plan;
+
+    #[test]
+    fn test_compares_over_all_executions() {
+        // 50 executions of an estimated 10 rows: 500 rows is on target
+        let plan = test_plan(
+            1.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Nested Loops" LogicalOp="Inner Join" EstimateRows="1" EstimatedTotalSubtreeCost="1">
+                 <RunTimeInformation>
+                   <RunTimeCountersPerThread Thread="0" ActualRows="0" ActualExecutions="1" />
+                 </RunTimeInformation>
+                 <NestedLoops>
+                   <RelOp NodeId="1" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimateRows="10" EstimatedTotalSubtreeCost="0.5">
+                     <RunTimeInformation>
+                       <RunTimeCountersPerThread Thread="0" ActualRows="500" ActualExecutions="50" />
+                     </RunTimeInformation>
+                   </RelOp>
+                 </NestedLoops>
+               </RelOp>"#,
+        );
+        let issues = EstimateMismatch.evaluate(&PlanTree::new(&plan.statements[0]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].node_id, Some(0));
+        // No rows at all is as far off as it gets
+        assert_eq!(issues[0].impact, 100.0);
+    }
+}
@@ -0,0 +1,72 @@
+use super::{PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+/// Share of the statement cost above which a Sort is reported
+const MIN_COST_PERCENT: f64 = 15.0;
+const CRITICAL_COST_PERCENT: f64 = 30.0;
+
+pub struct ExpensiveSort;
+
+impl PlanRule for ExpensiveSort {
+    fn id(&self) -> &str {
+        "expensive-sort"
+    }
+
+    fn title(&self) -> &str {
+        "Expensive Sort Operation"
+    }
+
+    fn description(&self) -> &str {
+        "A Sort costs more than 15% of the statement"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Critical
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| n.node.physical_op == "Sort" && n.cost_percent > MIN_COST_PERCENT)
+            .map(|n| {
+                let severity = if n.cost_percent > CRITICAL_COST_PERCENT {
+                    IssueSeverity::Critical
+                } else {
+                    IssueSeverity::Warning
+                };
+                self.issue(
+                    severity,
+                    n.node,
+                    format!(
+                        "Sort operation consuming {:.1}% of query cost. Consider an index that provides pre-sorted data.",
+                        n.cost_percent
+                    ),
+                    n.cost_percent,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::test_plan;
+
+    #[test]
+    fn test_uses_own_cost_not_subtree() {
+        // The sort adds 0.2 of 1.0; the scan below it is the expensive part
+        let plan = test_plan(
+            1.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Sort" LogicalOp="Sort" EstimateRows="10" EstimatedTotalSubtreeCost="1">
+                 <Sort>
+                   <RelOp NodeId="1" PhysicalOp="Index Scan" LogicalOp="Index Scan" EstimateRows="10" EstimatedTotalSubtreeCost="0.8" />
+                 </Sort>
+               </RelOp>"#,
+        );
+        let issues = ExpensiveSort.evaluate(&PlanTree::new(&plan.statements[0]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, IssueSeverity::Warning);
+        assert!((issues[0].impact - 20.0).abs() < 1e-9);
+    }
+}
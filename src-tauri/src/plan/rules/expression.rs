@@ -0,0 +1,380 @@
+//! The condition language of user-defined rules: comparisons of plan node
+//! properties joined with `&&`, `||` and `!`, e.g.
+//! `PhysicalOp == 'Clustered Index Scan' && EstimateRows > 100000`.
+//! Property names are case-insensitive; names that are not built in are
+//! looked up in the RelOp attributes.
+
+use super::{executions, TreeNode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Property(String),
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Text(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/// Operators, longest first so `<=` wins over `<`
+const OPERATORS: &[&str] = &["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!"];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push((
+                start,
+                if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                },
+            ));
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("Unterminated string at column {}", start + 1)),
+                    // A doubled quote is a literal quote, as in T-SQL
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((start, Token::Text(text)));
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let value = literal
+                .parse()
+                .map_err(|_| format!("Invalid number '{}' at column {}", literal, start + 1))?;
+            tokens.push((start, Token::Number(value)));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("Unexpected '{}' at column {}", c, start + 1))?;
+            tokens.push((start, Token::Op(op)));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(c, _)| c + 1)
+            .unwrap_or(self.len + 1)
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.eat_op("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.eat_op("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.primary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.primary()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let column = self.column();
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        match token {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Text(s)) => Ok(Expr::Text(s)),
+            Some(Token::Ident(name)) => Ok(match name.to_ascii_lowercase().as_str() {
+                "true" => Expr::Bool(true),
+                "false" => Expr::Bool(false),
+                _ => Expr::Property(name),
+            }),
+            Some(Token::LParen) => {
+                let inner = self.or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(format!("Expected ')' at column {}", self.column()));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(_) => Err(format!("Expected a value at column {}", column)),
+            None => Err("Expression ends unexpectedly".to_string()),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Expr, String> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Err("Expression is empty".to_string());
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        len: source.chars().count(),
+    };
+    let expr = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("Unexpected input at column {}", parser.column()));
+    }
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Missing,
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Text(s) => !s.is_empty(),
+            Value::Missing => false,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+fn property(node: &TreeNode, name: &str) -> Value {
+    let n = node.node;
+    let number = |v: Option<f64>| v.map(Value::Number).unwrap_or(Value::Missing);
+    let text = |v: Option<&String>| v.map(|s| Value::Text(s.clone())).unwrap_or(Value::Missing);
+    let object = n.object.as_ref();
+    let runtime = n.runtime.as_ref();
+    match name.to_ascii_lowercase().as_str() {
+        "physicalop" => Value::Text(n.physical_op.clone()),
+        "logicalop" => Value::Text(n.logical_op.clone()),
+        "estimaterows" => Value::Number(n.estimate_rows),
+        "estimatecpu" => Value::Number(n.estimate_cpu),
+        "estimateio" => Value::Number(n.estimate_io),
+        "estimatedtotalsubtreecost" | "subtreecost" => {
+            Value::Number(n.estimated_total_subtree_cost)
+        }
+        "estimaterebinds" => Value::Number(n.estimate_rebinds),
+        "estimaterewinds" => Value::Number(n.estimate_rewinds),
+        "costpercent" => Value::Number(node.cost_percent),
+        "parallel" => Value::Bool(n.parallel),
+        "executions" => Value::Number(executions(n)),
+        "actualrows" => number(runtime.map(|r| r.actual_rows)),
+        "actualexecutions" => number(runtime.map(|r| r.actual_executions)),
+        "actualelapsedms" => number(runtime.and_then(|r| r.actual_elapsed_ms)),
+        "actualcpums" => number(runtime.and_then(|r| r.actual_cpu_ms)),
+        "actuallogicalreads" => number(runtime.and_then(|r| r.actual_logical_reads)),
+        "database" => text(object.and_then(|o| o.database.as_ref())),
+        "schema" => text(object.and_then(|o| o.schema.as_ref())),
+        "table" => text(object.and_then(|o| o.table.as_ref())),
+        "index" => text(object.and_then(|o| o.index.as_ref())),
+        "warnings" => Value::Number(n.warnings.len() as f64),
+        "spill" => Value::Bool(n.spill.is_some()),
+        _ => n
+            .attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| Value::Text(value.clone()))
+            .unwrap_or(Value::Missing),
+    }
+}
+
+fn value(expr: &Expr, node: &TreeNode) -> Value {
+    match expr {
+        Expr::Property(name) => property(node, name),
+        Expr::Number(n) => Value::Number(*n),
+        Expr::Text(s) => Value::Text(s.clone()),
+        Expr::Bool(b) => Value::Bool(*b),
+        other => Value::Bool(matches(other, node)),
+    }
+}
+
+/// Compare numerically when both sides read as numbers (attributes are
+/// text), otherwise as text. A missing property matches nothing.
+fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    if *left == Value::Missing || *right == Value::Missing {
+        return false;
+    }
+    let ordering = match (left, right) {
+        (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        _ => match (left.as_number(), right.as_number()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => None,
+        },
+    };
+    let Some(ordering) = ordering else {
+        return op == CompareOp::Ne;
+    };
+    match op {
+        CompareOp::Eq => ordering.is_eq(),
+        CompareOp::Ne => ordering.is_ne(),
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        CompareOp::Ge => ordering.is_ge(),
+    }
+}
+
+/// Whether the condition holds for a node
+pub fn matches(expr: &Expr, node: &TreeNode) -> bool {
+    match expr {
+        Expr::Not(inner) => !matches(inner, node),
+        Expr::And(a, b) => matches(a, node) && matches(b, node),
+        Expr::Or(a, b) => matches(a, node) || matches(b, node),
+        Expr::Compare(a, op, b) => compare(&value(a, node), *op, &value(b, node)),
+        other => value(other, node).truthy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::{test_plan, PlanTree};
+
+    #[test]
+    fn test_parse_errors_point_at_column() {
+        assert!(parse("EstimateRows > 10 && (Parallel")
+            .unwrap_err()
+            .contains("')'"));
+        assert_eq!(
+            parse("EstimateRows > ").unwrap_err(),
+            "Expression ends unexpectedly"
+        );
+        assert_eq!(
+            parse("PhysicalOp = 'Sort'").unwrap_err(),
+            "Unexpected '=' at column 12"
+        );
+        assert!(parse("'it''s' == Table").is_ok());
+    }
+
+    #[test]
+    fn test_matches_node_properties() {
+        let plan = test_plan(
+            1.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="250000" EstimatedTotalSubtreeCost="1" Parallel="1" EstimatedRowsRead="900000">
+                 <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[PK_Orders]" /></IndexScan>
+               </RelOp>"#,
+        );
+        let tree = PlanTree::new(&plan.statements[0]);
+        let node = &tree.nodes[0];
+        let check = |source: &str| matches(&parse(source).unwrap(), node);
+
+        assert!(check(
+            "PhysicalOp == 'Clustered Index Scan' && EstimateRows > 100000"
+        ));
+        assert!(check(
+            "table == \"Orders\" && !(CostPercent < 50) && parallel"
+        ));
+        // Raw attributes compare as numbers
+        assert!(check("EstimatedRowsRead >= 900000"));
+        assert!(!check("ActualRows > 0 || Index != 'PK_Orders'"));
+        assert!(!check("NoSuchAttribute == 'x'"));
+    }
+}
@@ -0,0 +1,42 @@
+use super::{PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+const MIN_COST_PERCENT: f64 = 50.0;
+
+pub struct HighCost;
+
+impl PlanRule for HighCost {
+    fn id(&self) -> &str {
+        "high-cost"
+    }
+
+    fn title(&self) -> &str {
+        "High Cost Operation"
+    }
+
+    fn description(&self) -> &str {
+        "One operator costs more than half of the statement"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Critical
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| n.cost_percent > MIN_COST_PERCENT)
+            .map(|n| {
+                self.issue(
+                    IssueSeverity::Critical,
+                    n.node,
+                    format!(
+                        "{} accounts for {:.1}% of total query cost. This is the primary optimization target.",
+                        n.node.physical_op, n.cost_percent
+                    ),
+                    n.cost_percent,
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,74 @@
+use super::{executions, PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+const MIN_EXECUTIONS: f64 = 100.0;
+const CRITICAL_EXECUTIONS: f64 = 1_000.0;
+
+pub struct KeyLookup;
+
+impl PlanRule for KeyLookup {
+    fn id(&self) -> &str {
+        "key-lookup"
+    }
+
+    fn title(&self) -> &str {
+        "Key Lookup Operations"
+    }
+
+    fn description(&self) -> &str {
+        "A Key or RID Lookup runs more than 100 times"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Critical
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| matches!(n.node.physical_op.as_str(), "Key Lookup" | "RID Lookup"))
+            .filter_map(|n| {
+                let executions = executions(n.node);
+                if executions <= MIN_EXECUTIONS {
+                    return None;
+                }
+                let severity = if executions > CRITICAL_EXECUTIONS {
+                    IssueSeverity::Critical
+                } else {
+                    IssueSeverity::Warning
+                };
+                Some(self.issue(
+                    severity,
+                    n.node,
+                    format!(
+                        "{} executed {:.0} times. Consider adding columns to the index to avoid lookups.",
+                        n.node.physical_op, executions
+                    ),
+                    n.cost_percent * 2.0,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::test_plan;
+
+    #[test]
+    fn test_counts_actual_executions() {
+        let plan = test_plan(
+            1.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Key Lookup" LogicalOp="Key Lookup" EstimateRows="1" EstimateRebinds="20" EstimatedTotalSubtreeCost="1">
+                 <RunTimeInformation>
+                   <RunTimeCountersPerThread Thread="0" ActualRows="4000" ActualExecutions="4000" />
+                 </RunTimeInformation>
+               </RelOp>"#,
+        );
+        let issues = KeyLookup.evaluate(&PlanTree::new(&plan.statements[0]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, IssueSeverity::Critical);
+        assert!(issues[0].description.contains("4000 times"));
+    }
+}
@@ -0,0 +1,42 @@
+use super::{PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+const MIN_ROWS: f64 = 100_000.0;
+
+pub struct LargeHash;
+
+impl PlanRule for LargeHash {
+    fn id(&self) -> &str {
+        "large-hash"
+    }
+
+    fn title(&self) -> &str {
+        "Large Hash Operation"
+    }
+
+    fn description(&self) -> &str {
+        "A Hash Match processes more than 100,000 estimated rows"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Warning
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| n.node.physical_op == "Hash Match" && n.node.estimate_rows > MIN_ROWS)
+            .map(|n| {
+                self.issue(
+                    IssueSeverity::Warning,
+                    n.node,
+                    format!(
+                        "Hash Match processing {:.0} rows may require significant memory. Consider if a Merge Join would be more efficient.",
+                        n.node.estimate_rows
+                    ),
+                    n.cost_percent * 1.5,
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,278 @@
+//! Plan analysis rules. Each rule is a module implementing `PlanRule`;
+//! adding one means writing the module and listing it in `builtin_rules`.
+
+mod clustered_index_scan;
+mod custom;
+mod estimate_mismatch;
+mod expensive_sort;
+mod high_cost;
+mod key_lookup;
+mod large_hash;
+mod table_scan;
+
+pub mod expression;
+
+use super::types::*;
+
+pub trait PlanRule: Send + Sync {
+    /// Stable id, used to turn the rule off in settings
+    fn id(&self) -> &str;
+    fn title(&self) -> &str;
+    /// What the rule looks for, for the rules list
+    fn description(&self) -> &str;
+    /// Worst severity the rule reports
+    fn severity(&self) -> IssueSeverity;
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue>;
+
+    /// Defined by the user in settings
+    fn is_custom(&self) -> bool {
+        false
+    }
+
+    /// An issue of this rule about one node
+    fn issue(
+        &self,
+        severity: IssueSeverity,
+        node: &PlanNode,
+        description: String,
+        impact: f64,
+    ) -> PlanIssue {
+        PlanIssue {
+            rule_id: self.id().to_string(),
+            severity,
+            title: self.title().to_string(),
+            description,
+            node_id: Some(node.node_id),
+            impact: impact.clamp(0.0, 100.0),
+        }
+    }
+}
+
+/// One statement's operators, flattened in plan order, with the share of
+/// the statement cost each one adds on top of its children
+pub struct PlanTree<'a> {
+    pub statement: &'a PlanStatement,
+    pub nodes: Vec<TreeNode<'a>>,
+}
+
+pub struct TreeNode<'a> {
+    pub node: &'a PlanNode,
+    pub cost_percent: f64,
+}
+
+impl<'a> PlanTree<'a> {
+    pub fn new(statement: &'a PlanStatement) -> Self {
+        let total = if statement.statement_sub_tree_cost > 0.0 {
+            statement.statement_sub_tree_cost
+        } else {
+            statement
+                .root
+                .as_ref()
+                .map(|r| r.estimated_total_subtree_cost)
+                .unwrap_or(0.0)
+        };
+        let mut nodes = Vec::new();
+        if let Some(root) = &statement.root {
+            flatten(root, total, &mut nodes);
+        }
+        Self { statement, nodes }
+    }
+}
+
+fn flatten<'a>(node: &'a PlanNode, total: f64, out: &mut Vec<TreeNode<'a>>) {
+    let children_cost: f64 = node
+        .children
+        .iter()
+        .map(|c| c.estimated_total_subtree_cost)
+        .sum();
+    let own_cost = (node.estimated_total_subtree_cost - children_cost).max(0.0);
+    out.push(TreeNode {
+        node,
+        cost_percent: if total > 0.0 {
+            own_cost / total * 100.0
+        } else {
+            0.0
+        },
+    });
+    for child in &node.children {
+        flatten(child, total, out);
+    }
+}
+
+/// Executions of an operator: actual when known, otherwise estimated
+pub fn executions(node: &PlanNode) -> f64 {
+    match &node.runtime {
+        Some(runtime) => runtime.actual_executions,
+        None => node.estimate_rebinds + node.estimate_rewinds + 1.0,
+    }
+}
+
+/// Table an operator reads, for issue descriptions
+pub fn table_name(node: &PlanNode) -> String {
+    super::summary::object_display_name(node).unwrap_or_else(|| "unknown table".to_string())
+}
+
+pub fn builtin_rules() -> Vec<Box<dyn PlanRule>> {
+    vec![
+        Box::new(table_scan::TableScan),
+        Box::new(clustered_index_scan::ClusteredIndexScan),
+        Box::new(key_lookup::KeyLookup),
+        Box::new(expensive_sort::ExpensiveSort),
+        Box::new(large_hash::LargeHash),
+        Box::new(high_cost::HighCost),
+        Box::new(estimate_mismatch::EstimateMismatch),
+    ]
+}
+
+/// Built-in rules followed by the user's. A custom rule that no longer
+/// compiles is skipped rather than failing the whole analysis.
+pub fn all_rules(custom: &[CustomRuleDefinition]) -> Vec<Box<dyn PlanRule>> {
+    let mut rules = builtin_rules();
+    for definition in custom {
+        match custom::ExpressionRule::compile(definition.clone()) {
+            Ok(rule) => rules.push(Box::new(rule)),
+            Err(e) => tracing::warn!(error = %e, "Skipping invalid custom analysis rule"),
+        }
+    }
+    rules
+}
+
+/// Check a custom rule before it is saved; its id must not shadow a
+/// built-in rule
+pub fn validate_custom_rule(definition: &CustomRuleDefinition) -> Result<(), String> {
+    if definition.id.trim().is_empty() {
+        return Err("Rule id is required".to_string());
+    }
+    if definition.title.trim().is_empty() {
+        return Err("Rule title is required".to_string());
+    }
+    if builtin_rules()
+        .iter()
+        .any(|rule| rule.id() == definition.id)
+    {
+        return Err(format!("'{}' is the id of a built-in rule", definition.id));
+    }
+    expression::parse(&definition.expression)?;
+    Ok(())
+}
+
+pub fn rule_infos(rules: &[Box<dyn PlanRule>], disabled: &[String]) -> Vec<AnalysisRuleInfo> {
+    rules
+        .iter()
+        .map(|rule| AnalysisRuleInfo {
+            id: rule.id().to_string(),
+            title: rule.title().to_string(),
+            description: rule.description().to_string(),
+            severity: rule.severity(),
+            enabled: !disabled.iter().any(|id| id == rule.id()),
+            custom: rule.is_custom(),
+        })
+        .collect()
+}
+
+/// Run every rule not in `disabled` over each statement
+pub fn analyze(
+    plan: &ParsedPlan,
+    rules: &[Box<dyn PlanRule>],
+    disabled: &[String],
+) -> PlanAnalysis {
+    let enabled: Vec<&dyn PlanRule> = rules
+        .iter()
+        .map(|rule| rule.as_ref())
+        .filter(|rule| !disabled.iter().any(|id| id == rule.id()))
+        .collect();
+    PlanAnalysis {
+        statements: plan
+            .statements
+            .iter()
+            .map(|statement| {
+                let tree = PlanTree::new(statement);
+                let mut issues: Vec<PlanIssue> = enabled
+                    .iter()
+                    .flat_map(|rule| rule.evaluate(&tree))
+                    .collect();
+                issues.sort_by(|a, b| b.impact.total_cmp(&a.impact));
+                StatementAnalysis {
+                    statement_id: tree.statement.statement_id,
+                    issues,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// A single-statement plan around `relops`, costing `cost` in total
+#[cfg(test)]
+pub(crate) fn test_plan(cost: f64, relops: &str) -> ParsedPlan {
+    let xml = format!(
+        r#"<ShowPlanXML Version="1.5"><BatchSequence><Batch><Statements>
+            <StmtSimple StatementId="1" StatementText="q" StatementSubTreeCost="{}">
+              <QueryPlan>{}</QueryPlan>
+            </StmtSimple>
+        </Statements></Batch></BatchSequence></ShowPlanXML>"#,
+        cost, relops
+    );
+    super::parser::parse_plan(&xml).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_rules_are_skipped() {
+        let plan = test_plan(
+            10.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="50000" EstimatedTotalSubtreeCost="10" />"#,
+        );
+        let rules = builtin_rules();
+
+        let issues = &analyze(&plan, &rules, &[]).statements[0].issues;
+        let ids: Vec<&str> = issues.iter().map(|i| i.rule_id.as_str()).collect();
+        assert_eq!(ids, vec!["table-scan", "high-cost"]);
+
+        let disabled = vec!["high-cost".to_string()];
+        let issues = &analyze(&plan, &rules, &disabled).statements[0].issues;
+        assert_eq!(issues.len(), 1);
+        assert!(
+            !rule_infos(&rules, &disabled)
+                .iter()
+                .find(|r| r.id == "high-cost")
+                .unwrap()
+                .enabled
+        );
+    }
+
+    #[test]
+    fn test_custom_rules_join_the_registry() {
+        let plan = test_plan(
+            10.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="50000" EstimatedTotalSubtreeCost="10" />"#,
+        );
+        let custom = |id: &str, expression: &str| CustomRuleDefinition {
+            id: id.to_string(),
+            title: "No heaps".to_string(),
+            expression: expression.to_string(),
+            severity: IssueSeverity::Info,
+            message: None,
+        };
+        let rules = all_rules(&[
+            custom("no-heaps", "PhysicalOp == 'Table Scan'"),
+            custom("broken", "EstimateRows >"),
+        ]);
+        assert_eq!(rules.len(), builtin_rules().len() + 1);
+
+        let disabled = vec!["table-scan".to_string(), "high-cost".to_string()];
+        let issues = &analyze(&plan, &rules, &disabled).statements[0].issues;
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule_id, "no-heaps");
+        assert_eq!(
+            issues[0].description,
+            "Table Scan (node 0) matches PhysicalOp == 'Table Scan'"
+        );
+
+        assert!(validate_custom_rule(&custom("table-scan", "Parallel")).is_err());
+        assert!(validate_custom_rule(&custom("mine", "Parallel ==")).is_err());
+        assert!(validate_custom_rule(&custom("mine", "Parallel")).is_ok());
+    }
+}
@@ -0,0 +1,77 @@
+use super::{table_name, PlanRule, PlanTree};
+use crate::plan::types::{IssueSeverity, PlanIssue};
+
+/// Heap scans over more than this many rows are reported
+const MIN_ROWS: f64 = 1_000.0;
+const CRITICAL_ROWS: f64 = 10_000.0;
+
+pub struct TableScan;
+
+impl PlanRule for TableScan {
+    fn id(&self) -> &str {
+        "table-scan"
+    }
+
+    fn title(&self) -> &str {
+        "Table Scan Detected"
+    }
+
+    fn description(&self) -> &str {
+        "A heap is read in full for more than 1,000 estimated rows"
+    }
+
+    fn severity(&self) -> IssueSeverity {
+        IssueSeverity::Critical
+    }
+
+    fn evaluate(&self, tree: &PlanTree) -> Vec<PlanIssue> {
+        tree.nodes
+            .iter()
+            .filter(|n| n.node.physical_op == "Table Scan" && n.node.estimate_rows > MIN_ROWS)
+            .map(|n| {
+                let severity = if n.node.estimate_rows > CRITICAL_ROWS {
+                    IssueSeverity::Critical
+                } else {
+                    IssueSeverity::Warning
+                };
+                self.issue(
+                    severity,
+                    n.node,
+                    format!(
+                        "Table scan on {} processing {:.0} estimated rows. Consider adding an index.",
+                        table_name(n.node),
+                        n.node.estimate_rows
+                    ),
+                    n.cost_percent * 2.0,
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::rules::test_plan;
+
+    #[test]
+    fn test_severity_follows_row_count() {
+        let plan = test_plan(
+            1.0,
+            r#"<RelOp NodeId="0" PhysicalOp="Nested Loops" LogicalOp="Inner Join" EstimateRows="1" EstimatedTotalSubtreeCost="1">
+                 <NestedLoops>
+                   <RelOp NodeId="1" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="5000" EstimatedTotalSubtreeCost="0.4">
+                     <TableScan><Object Schema="[dbo]" Table="[Log]" /></TableScan>
+                   </RelOp>
+                   <RelOp NodeId="2" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="500" EstimatedTotalSubtreeCost="0.1" />
+                 </NestedLoops>
+               </RelOp>"#,
+        );
+        let issues = TableScan.evaluate(&PlanTree::new(&plan.statements[0]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].node_id, Some(1));
+        assert_eq!(issues[0].severity, IssueSeverity::Warning);
+        assert!(issues[0].description.contains("dbo.Log"));
+        assert!((issues[0].impact - 80.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+// ShowPlanXML grew between SQL Server versions: a plan saved on an older
+// server simply lacks what was added later. The parser leaves such fields
+// empty; this module names the server from the Build attribute and lists
+// what the plan could not provide, so analyses that come up empty can say
+// why instead of looking like a clean result.
+
+/// An element or attribute the analyses read, and the first major version
+/// that writes it
+pub struct SchemaFeature {
+    pub element: &'static str,
+    /// None when the element itself is the feature
+    pub attribute: Option<&'static str>,
+    pub since_major: u32,
+    /// Only actual plans carry it
+    pub actual_only: bool,
+}
+
+pub const FEATURES: &[SchemaFeature] = &[
+    SchemaFeature {
+        element: "StmtSimple",
+        attribute: Some("QueryHash"),
+        since_major: 10,
+        actual_only: false,
+    },
+    SchemaFeature {
+        element: "StmtSimple",
+        attribute: Some("QueryPlanHash"),
+        since_major: 10,
+        actual_only: false,
+    },
+    SchemaFeature {
+        element: "MemoryGrantInfo",
+        attribute: None,
+        since_major: 11,
+        actual_only: false,
+    },
+    SchemaFeature {
+        element: "MemoryGrantInfo",
+        attribute: Some("MaxUsedMemory"),
+        since_major: 11,
+        actual_only: true,
+    },
+    // Per-operator times and I/O: SQL Server 2016, backported to 2014 SP2
+    SchemaFeature {
+        element: "RunTimeCountersPerThread",
+        attribute: Some("ActualElapsedms"),
+        since_major: 13,
+        actual_only: true,
+    },
+    SchemaFeature {
+        element: "RunTimeCountersPerThread",
+        attribute: Some("ActualCPUms"),
+        since_major: 13,
+        actual_only: true,
+    },
+    SchemaFeature {
+        element: "RunTimeCountersPerThread",
+        attribute: Some("ActualLogicalReads"),
+        since_major: 13,
+        actual_only: true,
+    },
+];
+
+impl SchemaFeature {
+    pub fn name(&self) -> &'static str {
+        self.attribute.unwrap_or(self.element)
+    }
+}
+
+/// Major and minor version of a Build attribute ("13.0.5026.0")
+fn build_version(build: &str) -> Option<(u32, u32)> {
+    let mut parts = build.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|m| m.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+/// Product name of the server that produced the plan
+pub fn product_name(build: &str) -> Option<String> {
+    let name = match build_version(build)? {
+        (9, _) => "SQL Server 2005",
+        (10, 50..) => "SQL Server 2008 R2",
+        (10, _) => "SQL Server 2008",
+        (11, _) => "SQL Server 2012",
+        (12, _) => "SQL Server 2014",
+        (13, _) => "SQL Server 2016",
+        (14, _) => "SQL Server 2017",
+        (15, _) => "SQL Server 2019",
+        (16, _) => "SQL Server 2022",
+        (17, _) => "SQL Server 2025",
+        (major, _) if major > 17 => return Some(format!("SQL Server (version {})", major)),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Features the plan lacks because its server predates them; `seen` holds
+/// the names found while parsing, `actual` whether it has runtime counters
+pub fn unavailable(build: Option<&str>, seen: &HashSet<&'static str>, actual: bool) -> Vec<String> {
+    let major = build.and_then(build_version).map(|(major, _)| major);
+    FEATURES
+        .iter()
+        .filter(|f| !seen.contains(f.name()))
+        .filter(|f| {
+            // Without a build only missing runtime counters are telling
+            let predates = major.map_or(f.actual_only, |m| m < f.since_major);
+            predates && (actual || !f.actual_only)
+        })
+        .map(|f| f.name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_builds_report_missing_attributes() {
+        assert_eq!(
+            product_name("10.50.1600.1").as_deref(),
+            Some("SQL Server 2008 R2")
+        );
+        assert_eq!(
+            product_name("16.0.1000.6").as_deref(),
+            Some("SQL Server 2022")
+        );
+        assert_eq!(product_name("unknown"), None);
+
+        let seen: HashSet<&'static str> = ["QueryHash", "QueryPlanHash"].into();
+        assert_eq!(
+            unavailable(Some("10.0.5500.0"), &seen, true),
+            vec![
+                "MemoryGrantInfo",
+                "MaxUsedMemory",
+                "ActualElapsedms",
+                "ActualCPUms",
+                "ActualLogicalReads"
+            ]
+        );
+        assert_eq!(
+            unavailable(Some("10.0.5500.0"), &seen, false),
+            vec!["MemoryGrantInfo"]
+        );
+        assert!(unavailable(Some("15.0.2000.5"), &HashSet::new(), false).is_empty());
+    }
+}
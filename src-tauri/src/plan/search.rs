@@ -0,0 +1,162 @@
+use super::skew::{estimate_ratio, skew_factor};
+use super::summary::object_display_name;
+use super::types::*;
+
+/// Operators of the plan matching every criterion of `query`, in tree order
+pub fn search(plan: &ParsedPlan, query: &PlanSearchQuery) -> Vec<PlanSearchMatch> {
+    let mut matches = Vec::new();
+    for stmt in &plan.statements {
+        if query.statement_id.is_some_and(|id| id != stmt.statement_id) {
+            continue;
+        }
+        if let Some(root) = &stmt.root {
+            visit(stmt.statement_id, root, query, &mut matches);
+        }
+    }
+    matches
+}
+
+fn visit(
+    statement_id: i64,
+    node: &PlanNode,
+    query: &PlanSearchQuery,
+    out: &mut Vec<PlanSearchMatch>,
+) {
+    let ratio = estimate_ratio(node);
+    if matches(node, ratio, query) {
+        out.push(PlanSearchMatch {
+            statement_id,
+            node_id: node.node_id,
+            physical_op: node.physical_op.clone(),
+            object_name: object_display_name(node),
+            estimate_ratio: ratio,
+        });
+    }
+    for child in &node.children {
+        visit(statement_id, child, query, out);
+    }
+}
+
+fn matches(node: &PlanNode, ratio: Option<f64>, query: &PlanSearchQuery) -> bool {
+    if let Some(op) = query.operator.as_deref() {
+        if !node.physical_op.eq_ignore_ascii_case(op) && !node.logical_op.eq_ignore_ascii_case(op) {
+            return false;
+        }
+    }
+    if let Some(object) = query.object.as_deref() {
+        if !object_matches(node, object) {
+            return false;
+        }
+    }
+    if let Some(min) = query.min_estimate_ratio {
+        if !ratio.is_some_and(|r| skew_factor(r) >= min) {
+            return false;
+        }
+    }
+    if let Some(wanted) = query.has_warnings {
+        if node.warnings.is_empty() == wanted {
+            return false;
+        }
+    }
+    if let Some(text) = query.predicate_contains.as_deref() {
+        let text = text.to_lowercase();
+        let found = node
+            .predicate
+            .iter()
+            .chain(&node.seek_predicates)
+            .any(|p| p.to_lowercase().contains(&text));
+        if !found {
+            return false;
+        }
+    }
+    true
+}
+
+/// Compare the trailing parts of the name, so "Orders" matches dbo.Orders and
+/// "dbo.Orders" matches any index on it
+fn object_matches(node: &PlanNode, wanted: &str) -> bool {
+    let Some(object) = &node.object else {
+        return false;
+    };
+    let wanted: Vec<String> = wanted
+        .split('.')
+        .map(|p| p.trim_matches(|c| c == '[' || c == ']').to_lowercase())
+        .collect();
+    let table_path: Vec<String> = [&object.schema, &object.table]
+        .into_iter()
+        .flatten()
+        .map(|p| p.to_lowercase())
+        .collect();
+    let index = object.index.as_ref().map(|i| i.to_lowercase());
+
+    table_path.ends_with(&wanted)
+        || (wanted.len() == 1 && index.as_deref() == Some(wanted[0].as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    const PLAN: &str = r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+      <StmtSimple StatementId="1" StatementText="q">
+        <QueryPlan>
+          <RelOp NodeId="0" PhysicalOp="Hash Match" LogicalOp="Inner Join" EstimateRows="10">
+            <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="5000" ActualExecutions="1" /></RunTimeInformation>
+            <Hash>
+              <RelOp NodeId="1" PhysicalOp="Index Scan" LogicalOp="Index Scan" EstimateRows="100">
+                <Warnings><NoJoinPredicate /></Warnings>
+                <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="120" ActualExecutions="1" /></RunTimeInformation>
+                <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[IX_Date]" /></IndexScan>
+              </RelOp>
+              <RelOp NodeId="2" PhysicalOp="Index Scan" LogicalOp="Index Scan" EstimateRows="100">
+                <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="5" ActualExecutions="1" /></RunTimeInformation>
+                <IndexScan><Object Schema="[dbo]" Table="[Customers]" Index="[PK_Customers]" /></IndexScan>
+              </RelOp>
+            </Hash>
+          </RelOp>
+        </QueryPlan>
+      </StmtSimple>
+    </Statements></Batch></BatchSequence></ShowPlanXML>"#;
+
+    fn ids(query: PlanSearchQuery) -> Vec<i64> {
+        search(&parse_plan(PLAN).unwrap(), &query)
+            .iter()
+            .map(|m| m.node_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_search_by_operator_and_object() {
+        let query = PlanSearchQuery {
+            operator: Some("index scan".to_string()),
+            object: Some("Orders".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec![1]);
+        let query = PlanSearchQuery {
+            object: Some("PK_Customers".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec![2]);
+        let query = PlanSearchQuery {
+            operator: Some("Index Scan".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_search_by_estimate_ratio_and_warnings() {
+        let query = PlanSearchQuery {
+            min_estimate_ratio: Some(10.0),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec![0, 2]);
+        let query = PlanSearchQuery {
+            has_warnings: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(ids(query), vec![1]);
+    }
+}
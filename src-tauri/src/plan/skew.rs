@@ -0,0 +1,106 @@
+use super::types::*;
+
+/// Per-node estimate skew for an actual plan, in tree order. Estimated plans
+/// have no runtime counters and yield nothing.
+pub fn estimate_skew(plan: &ParsedPlan) -> Vec<NodeEstimateSkew> {
+    let mut out = Vec::new();
+    for stmt in &plan.statements {
+        if let Some(root) = &stmt.root {
+            visit(stmt.statement_id, root, &mut out);
+        }
+    }
+    out
+}
+
+fn visit(statement_id: i64, node: &PlanNode, out: &mut Vec<NodeEstimateSkew>) {
+    if let Some(ratio) = estimate_ratio(node) {
+        let factor = skew_factor(ratio);
+        out.push(NodeEstimateSkew {
+            statement_id,
+            node_id: node.node_id,
+            physical_op: node.physical_op.clone(),
+            estimated_rows: estimated_rows(node),
+            actual_rows: node.runtime.as_ref().map(|r| r.actual_rows).unwrap_or(0.0),
+            ratio,
+            skew_factor: factor,
+            underestimated: ratio > 1.0,
+            severity: severity(factor),
+        });
+    }
+    for child in &node.children {
+        visit(statement_id, child, out);
+    }
+}
+
+/// EstimateRows is per execution; actual rows are summed over all of them
+pub fn estimated_rows(node: &PlanNode) -> f64 {
+    node.estimate_rows * (node.estimate_rebinds + node.estimate_rewinds + 1.0)
+}
+
+/// Actual rows over estimated rows; None without runtime counters. Estimates
+/// below one row count as one so tiny estimates do not explode the ratio.
+pub fn estimate_ratio(node: &PlanNode) -> Option<f64> {
+    let actual = node.runtime.as_ref()?.actual_rows;
+    Some(actual.max(1.0) / estimated_rows(node).max(1.0))
+}
+
+/// A ratio of 0.1 and one of 10 are equally wrong
+pub fn skew_factor(ratio: f64) -> f64 {
+    if ratio > 0.0 && ratio < 1.0 {
+        1.0 / ratio
+    } else {
+        ratio
+    }
+}
+
+fn severity(factor: f64) -> SkewSeverity {
+    if factor >= 100.0 {
+        SkewSeverity::Severe
+    } else if factor >= 10.0 {
+        SkewSeverity::Moderate
+    } else if factor >= 2.0 {
+        SkewSeverity::Minor
+    } else {
+        SkewSeverity::Accurate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    #[test]
+    fn test_estimate_skew_buckets() {
+        let plan = parse_plan(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q"><QueryPlan>
+                <RelOp NodeId="0" PhysicalOp="Nested Loops" LogicalOp="Inner Join" EstimateRows="10">
+                  <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="12" ActualExecutions="1" /></RunTimeInformation>
+                  <NestedLoops>
+                    <RelOp NodeId="1" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimateRows="1" EstimateRewinds="99">
+                      <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="50000" ActualExecutions="100" /></RunTimeInformation>
+                      <IndexScan />
+                    </RelOp>
+                    <RelOp NodeId="2" PhysicalOp="Table Scan" LogicalOp="Table Scan" EstimateRows="5000">
+                      <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="100" ActualExecutions="1" /></RunTimeInformation>
+                      <TableScan />
+                    </RelOp>
+                  </NestedLoops>
+                </RelOp>
+              </QueryPlan></StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#,
+        )
+        .unwrap();
+        let skew = estimate_skew(&plan);
+        assert_eq!(skew.len(), 3);
+        assert_eq!(skew[0].severity, SkewSeverity::Accurate);
+        assert_eq!(skew[1].estimated_rows, 100.0);
+        assert_eq!(skew[1].ratio, 500.0);
+        assert!(skew[1].underestimated);
+        assert_eq!(skew[1].severity, SkewSeverity::Severe);
+        assert_eq!(skew[2].skew_factor, 50.0);
+        assert!(!skew[2].underestimated);
+        assert_eq!(skew[2].severity, SkewSeverity::Moderate);
+    }
+}
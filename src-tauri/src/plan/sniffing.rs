@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use super::compare;
+use super::summary::object_display_name;
+use super::types::*;
+
+/// Actual / estimated rows off by this factor either way is worth a mention
+const MISESTIMATE_FACTOR: f64 = 10.0;
+
+/// Misestimates below this many rows on both sides are harmless
+const MIN_MISESTIMATE_ROWS: f64 = 100.0;
+
+/// Compare the actual plans of one query run with two parameter sets. Operators
+/// are paired by label and occurrence so predicates and row counts line up.
+pub fn compare_runs(first: &ParsedPlan, second: &ParsedPlan) -> ParameterPlanComparison {
+    let plan_changed = first
+        .statements
+        .iter()
+        .map(|s| &s.query_plan_hash)
+        .ne(second.statements.iter().map(|s| &s.query_plan_hash));
+
+    let mut operators = Vec::new();
+    let count = first.statements.len().max(second.statements.len());
+    for i in 0..count {
+        let a = first.statements.get(i);
+        let b = second.statements.get(i);
+        let statement_id = b.or(a).map(|s| s.statement_id).unwrap_or(0);
+        pair_operators(statement_id, &flatten(a), &flatten(b), &mut operators);
+    }
+
+    let mut findings = Vec::new();
+    if plan_changed {
+        findings.push(
+            "The two parameter sets compiled to different plans: a cached plan built for one set will be reused for the other"
+                .to_string(),
+        );
+    }
+    for op in &operators {
+        for (rows, set) in [(&op.first, "first"), (&op.second, "second")] {
+            if let Some(message) = rows.as_ref().and_then(|r| misestimate(&op.label, r, set)) {
+                findings.push(message);
+            }
+        }
+        if let (Some(a), Some(b)) = (&op.first, &op.second) {
+            if let (Some(x), Some(y)) = (a.actual_rows, b.actual_rows) {
+                if is_far_apart(x, y) {
+                    findings.push(format!(
+                        "{}: {:.0} rows with the first set vs {:.0} with the second; one plan rarely suits both",
+                        op.label, x, y
+                    ));
+                }
+            }
+        }
+    }
+
+    ParameterPlanComparison {
+        plan_changed,
+        comparison: compare::compare(first, second),
+        operators,
+        findings,
+    }
+}
+
+fn flatten(stmt: Option<&PlanStatement>) -> Vec<(String, OperatorRows)> {
+    fn visit(node: &PlanNode, out: &mut Vec<(String, OperatorRows)>) {
+        let label = match object_display_name(node) {
+            Some(object) => format!("{} ({})", node.physical_op, object),
+            None => node.physical_op.clone(),
+        };
+        out.push((
+            label,
+            OperatorRows {
+                node_id: node.node_id,
+                predicate: node.predicate.clone(),
+                seek_predicates: node.seek_predicates.clone(),
+                estimated_rows: node.estimate_rows
+                    * (node.estimate_rebinds + node.estimate_rewinds + 1.0),
+                actual_rows: node.runtime.as_ref().map(|r| r.actual_rows),
+            },
+        ));
+        for child in &node.children {
+            visit(child, out);
+        }
+    }
+    let mut out = Vec::new();
+    if let Some(root) = stmt.and_then(|s| s.root.as_ref()) {
+        visit(root, &mut out);
+    }
+    out
+}
+
+/// Pair the n-th operator with a label in one plan with the n-th in the other;
+/// first-plan order, then operators only the second plan has
+fn pair_operators(
+    statement_id: i64,
+    first: &[(String, OperatorRows)],
+    second: &[(String, OperatorRows)],
+    out: &mut Vec<CardinalityComparison>,
+) {
+    let mut second_by_key: HashMap<(&str, usize), &OperatorRows> = HashMap::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (label, rows) in second {
+        let n = seen.entry(label.as_str()).or_default();
+        second_by_key.insert((label.as_str(), *n), rows);
+        *n += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (label, rows) in first {
+        let n = seen.entry(label.as_str()).or_default();
+        out.push(CardinalityComparison {
+            statement_id,
+            label: label.clone(),
+            first: Some(rows.clone()),
+            second: second_by_key.remove(&(label.as_str(), *n)).cloned(),
+        });
+        *n += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (label, rows) in second {
+        let n = seen.entry(label.as_str()).or_default();
+        if second_by_key.contains_key(&(label.as_str(), *n)) {
+            out.push(CardinalityComparison {
+                statement_id,
+                label: label.clone(),
+                first: None,
+                second: Some(rows.clone()),
+            });
+        }
+        *n += 1;
+    }
+}
+
+fn misestimate(label: &str, rows: &OperatorRows, set: &str) -> Option<String> {
+    let actual = rows.actual_rows?;
+    is_far_apart(rows.estimated_rows, actual).then(|| {
+        format!(
+            "{}: estimated {:.0} rows but {:.0} came back with the {} parameter set",
+            label, rows.estimated_rows, actual, set
+        )
+    })
+}
+
+fn is_far_apart(a: f64, b: f64) -> bool {
+    if a.max(b) < MIN_MISESTIMATE_ROWS {
+        return false;
+    }
+    let (low, high) = if a < b { (a, b) } else { (b, a) };
+    high >= low.max(1.0) * MISESTIMATE_FACTOR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    fn plan(hash: &str, op: &str, estimate: &str, actual: &str, predicate: &str) -> ParsedPlan {
+        parse_plan(&format!(
+            r#"<ShowPlanXML><BatchSequence><Batch><Statements>
+              <StmtSimple StatementId="1" StatementText="q" QueryPlanHash="{hash}">
+                <QueryPlan>
+                  <RelOp NodeId="0" PhysicalOp="{op}" LogicalOp="{op}" EstimateRows="{estimate}">
+                    <RunTimeInformation><RunTimeCountersPerThread Thread="0" ActualRows="{actual}" ActualExecutions="1" /></RunTimeInformation>
+                    <IndexScan>
+                      <Object Schema="[dbo]" Table="[Orders]" Index="[IX_Customer]" />
+                      <Predicate><ScalarOperator ScalarString="{predicate}" /></Predicate>
+                    </IndexScan>
+                  </RelOp>
+                </QueryPlan>
+              </StmtSimple>
+            </Statements></Batch></BatchSequence></ShowPlanXML>"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_same_plan_with_skewed_rows() {
+        let result = compare_runs(
+            &plan("0x1", "Index Seek", "12", "10", "[CustomerId]=(7)"),
+            &plan("0x1", "Index Seek", "12", "48000", "[CustomerId]=(42)"),
+        );
+        assert!(!result.plan_changed);
+        assert_eq!(result.operators.len(), 1);
+        let op = &result.operators[0];
+        assert_eq!(op.label, "Index Seek (dbo.Orders.IX_Customer)");
+        assert_eq!(
+            op.first.as_ref().unwrap().predicate.as_deref(),
+            Some("[CustomerId]=(7)")
+        );
+        assert_eq!(op.second.as_ref().unwrap().actual_rows, Some(48000.0));
+        assert_eq!(result.findings.len(), 2);
+        assert!(result.findings[0].contains("with the second parameter set"));
+    }
+
+    #[test]
+    fn test_different_plans_pair_unmatched_operators() {
+        let result = compare_runs(
+            &plan("0x1", "Index Seek", "10", "10", "p"),
+            &plan("0x2", "Index Scan", "50000", "48000", "p"),
+        );
+        assert!(result.plan_changed);
+        assert_eq!(result.operators.len(), 2);
+        assert!(result.operators[0].second.is_none());
+        assert!(result.operators[1].first.is_none());
+        assert_eq!(result.findings.len(), 1);
+    }
+}
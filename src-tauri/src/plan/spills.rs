@@ -0,0 +1,150 @@
+use super::types::*;
+
+/// tempdb pages are 8 KB
+const PAGE_KB: f64 = 8.0;
+
+/// Every operator with a SpillToTempDb warning, with a sentence relating the
+/// spill to the memory the operator (or else its statement) asked for
+pub fn find_spills(plan: &ParsedPlan) -> Vec<SpillFinding> {
+    let mut findings = Vec::new();
+    for stmt in &plan.statements {
+        if let Some(root) = &stmt.root {
+            collect(stmt, root, &mut findings);
+        }
+    }
+    findings
+}
+
+fn collect(stmt: &PlanStatement, node: &PlanNode, findings: &mut Vec<SpillFinding>) {
+    if let Some(details) = &node.spill {
+        findings.push(SpillFinding {
+            statement_id: stmt.statement_id,
+            node_id: node.node_id,
+            physical_op: node.physical_op.clone(),
+            details: details.clone(),
+            statement_grant: stmt.memory_grant.clone(),
+            message: describe(node, details, stmt.memory_grant.as_ref()),
+        });
+    }
+    for child in &node.children {
+        collect(stmt, child, findings);
+    }
+}
+
+fn describe(node: &PlanNode, details: &SpillDetails, grant: Option<&MemoryGrant>) -> String {
+    let subject = format!("{} (node {})", node.physical_op, node.node_id);
+    let spilled = match details.pages_written {
+        Some(pages) => format!(
+            "spilled {} pages ({}) to tempdb",
+            group_thousands(pages),
+            format_kb(pages * PAGE_KB)
+        ),
+        None => "spilled to tempdb".to_string(),
+    };
+
+    let mut message = match details.granted_memory_kb {
+        Some(kb) => format!("{} was granted {} but {}", subject, format_kb(kb), spilled),
+        None => format!("{} {}", subject, spilled),
+    };
+    if let Some(level) = details.spill_level {
+        message.push_str(&format!(" at spill level {}", level));
+    }
+    if let Some(threads) = details.spilled_thread_count.filter(|t| *t > 1) {
+        message.push_str(&format!(" on {} threads", threads));
+    }
+    if details.granted_memory_kb.is_none() {
+        if let Some(kb) = grant.and_then(|g| g.requested_kb) {
+            message.push_str(&format!("; the statement requested {}", format_kb(kb)));
+        }
+    }
+    message
+}
+
+fn format_kb(kb: f64) -> String {
+    if kb >= 1024.0 * 1024.0 {
+        format!("{:.1} GB", kb / (1024.0 * 1024.0))
+    } else if kb >= 1024.0 {
+        format!("{:.0} MB", kb / 1024.0)
+    } else {
+        format!("{:.0} KB", kb)
+    }
+}
+
+fn group_thousands(value: f64) -> String {
+    let digits = format!("{:.0}", value);
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    const SPILLING_PLAN: &str = r#"<ShowPlanXML Version="1.6"><BatchSequence><Batch><Statements>
+      <StmtSimple StatementId="1" StatementText="SELECT ... ORDER BY x">
+        <QueryPlan DegreeOfParallelism="1">
+          <MemoryGrantInfo SerialRequiredMemory="512" SerialDesiredMemory="900000" RequestedMemory="2048" GrantedMemory="2048" MaxUsedMemory="2048" GrantWaitTime="0" />
+          <RelOp NodeId="0" PhysicalOp="Sort" LogicalOp="Sort" EstimateRows="10">
+            <Warnings>
+              <SpillToTempDb SpillLevel="1" SpilledThreadCount="1" />
+              <SortSpillDetails GrantedMemoryKb="2048" UsedMemoryKb="2048" WritesToTempDb="40000" ReadsFromTempDb="40000" />
+            </Warnings>
+            <Sort Distinct="0">
+              <RelOp NodeId="1" PhysicalOp="Hash Match" LogicalOp="Aggregate" EstimateRows="10">
+                <Warnings><SpillToTempDb SpillLevel="2" /></Warnings>
+                <Hash />
+              </RelOp>
+            </Sort>
+          </RelOp>
+        </QueryPlan>
+      </StmtSimple>
+    </Statements></Batch></BatchSequence></ShowPlanXML>"#;
+
+    #[test]
+    fn test_parse_spill_details_and_memory_grant() {
+        let plan = parse_plan(SPILLING_PLAN).unwrap();
+        let stmt = &plan.statements[0];
+        let grant = stmt.memory_grant.as_ref().unwrap();
+        assert_eq!(grant.requested_kb, Some(2048.0));
+        assert_eq!(grant.max_used_kb, Some(2048.0));
+
+        let sort = stmt.root.as_ref().unwrap();
+        let spill = sort.spill.as_ref().unwrap();
+        assert_eq!(spill.kind, SpillKind::Sort);
+        assert_eq!(spill.spill_level, Some(1));
+        assert_eq!(spill.pages_written, Some(40000.0));
+        assert_eq!(sort.warnings, vec!["SpillToTempDb", "SortSpillDetails"]);
+
+        let hash = sort.children[0].spill.as_ref().unwrap();
+        assert_eq!(hash.kind, SpillKind::Hash);
+        assert_eq!(hash.pages_written, None);
+    }
+
+    #[test]
+    fn test_spill_messages() {
+        let findings = find_spills(&parse_plan(SPILLING_PLAN).unwrap());
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].message,
+            "Sort (node 0) was granted 2 MB but spilled 40,000 pages (312 MB) to tempdb at spill level 1"
+        );
+        assert_eq!(
+            findings[1].message,
+            "Hash Match (node 1) spilled to tempdb at spill level 2; the statement requested 2 MB"
+        );
+    }
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands(999.0), "999");
+        assert_eq!(group_thousands(40000.0), "40,000");
+        assert_eq!(group_thousands(1234567.0), "1,234,567");
+    }
+}
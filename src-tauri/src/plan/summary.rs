@@ -0,0 +1,128 @@
+use super::types::*;
+
+const TOP_OPERATOR_COUNT: usize = 5;
+
+/// Per-operator cost breakdown for every statement in the plan
+pub fn summarize(plan: &ParsedPlan) -> PlanSummary {
+    PlanSummary {
+        product: plan.product.clone(),
+        unavailable_attributes: plan.unavailable_attributes.clone(),
+        statements: plan.statements.iter().map(summarize_statement).collect(),
+    }
+}
+
+fn summarize_statement(stmt: &PlanStatement) -> StatementSummary {
+    // Fall back to the root subtree cost when the statement attribute is missing
+    let total = if stmt.statement_sub_tree_cost > 0.0 {
+        stmt.statement_sub_tree_cost
+    } else {
+        stmt.root
+            .as_ref()
+            .map(|r| r.estimated_total_subtree_cost)
+            .unwrap_or(0.0)
+    };
+
+    let mut operators = Vec::new();
+    if let Some(root) = &stmt.root {
+        collect_operators(root, total, &mut operators);
+    }
+
+    let mut top_operators = operators.clone();
+    top_operators.sort_by(|a, b| b.operator_cost.total_cmp(&a.operator_cost));
+    top_operators.truncate(TOP_OPERATOR_COUNT);
+
+    StatementSummary {
+        statement_id: stmt.statement_id,
+        statement_text: stmt.statement_text.clone(),
+        total_subtree_cost: total,
+        operators,
+        top_operators,
+    }
+}
+
+fn collect_operators(node: &PlanNode, total: f64, out: &mut Vec<OperatorCost>) {
+    let children_cost: f64 = node
+        .children
+        .iter()
+        .map(|c| c.estimated_total_subtree_cost)
+        .sum();
+    // Rounding in the XML can make the difference slightly negative
+    let operator_cost = (node.estimated_total_subtree_cost - children_cost).max(0.0);
+
+    out.push(OperatorCost {
+        node_id: node.node_id,
+        physical_op: node.physical_op.clone(),
+        logical_op: node.logical_op.clone(),
+        object_name: object_display_name(node),
+        operator_cost,
+        cost_percent: if total > 0.0 {
+            operator_cost / total * 100.0
+        } else {
+            0.0
+        },
+        subtree_cost: node.estimated_total_subtree_cost,
+        estimate_rows: node.estimate_rows,
+        estimate_rows_all_executions: node.estimate_rows
+            * (node.estimate_rebinds + node.estimate_rewinds + 1.0),
+        actual_rows: node.runtime.as_ref().map(|r| r.actual_rows),
+        actual_executions: node.runtime.as_ref().map(|r| r.actual_executions),
+    });
+
+    for child in &node.children {
+        collect_operators(child, total, out);
+    }
+}
+
+/// "schema.table.index" for operators that touch an object
+pub fn object_display_name(node: &PlanNode) -> Option<String> {
+    let object = node.object.as_ref()?;
+    let mut parts: Vec<&str> = Vec::new();
+    if let Some(schema) = object.schema.as_deref() {
+        parts.push(schema);
+    }
+    parts.push(object.table.as_deref()?);
+    if let Some(index) = object.index.as_deref() {
+        parts.push(index);
+    }
+    Some(parts.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::parser::parse_plan;
+
+    #[test]
+    fn test_operator_cost_excludes_children() {
+        let xml = r#"<ShowPlanXML Version="1.5"><BatchSequence><Batch><Statements>
+            <StmtSimple StatementId="1" StatementText="q" StatementSubTreeCost="2.0">
+              <QueryPlan>
+                <RelOp NodeId="0" PhysicalOp="Hash Match" LogicalOp="Inner Join" EstimateRows="10" EstimatedTotalSubtreeCost="2.0">
+                  <Hash>
+                    <RelOp NodeId="1" PhysicalOp="Clustered Index Scan" LogicalOp="Clustered Index Scan" EstimateRows="100" EstimatedTotalSubtreeCost="1.2">
+                      <IndexScan><Object Schema="[dbo]" Table="[Orders]" Index="[PK_Orders]" /></IndexScan>
+                    </RelOp>
+                    <RelOp NodeId="2" PhysicalOp="Index Seek" LogicalOp="Index Seek" EstimateRows="1" EstimateRebinds="9" EstimatedTotalSubtreeCost="0.3" />
+                  </Hash>
+                </RelOp>
+              </QueryPlan>
+            </StmtSimple>
+        </Statements></Batch></BatchSequence></ShowPlanXML>"#;
+
+        let summary = summarize(&parse_plan(xml).unwrap());
+        let stmt = &summary.statements[0];
+        assert_eq!(stmt.operators.len(), 3);
+
+        let hash = &stmt.operators[0];
+        assert!((hash.operator_cost - 0.5).abs() < 1e-9);
+        assert!((hash.cost_percent - 25.0).abs() < 1e-9);
+
+        let scan = &stmt.operators[1];
+        assert_eq!(scan.object_name.as_deref(), Some("dbo.Orders.PK_Orders"));
+        assert!((scan.cost_percent - 60.0).abs() < 1e-9);
+
+        assert_eq!(stmt.operators[2].estimate_rows_all_executions, 10.0);
+        assert_eq!(stmt.top_operators[0].node_id, 1);
+        assert_eq!(stmt.top_operators[1].node_id, 0);
+    }
+}
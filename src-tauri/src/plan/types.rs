@@ -0,0 +1,586 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// ShowPlan XML parsed into an operator tree per statement
+/// What open_plan_from_clipboard found on the clipboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ClipboardContent {
+    Plan {
+        #[serde(rename = "planXml")]
+        plan_xml: String,
+        plan: ParsedPlan,
+    },
+    /// For the deadlock viewer, which parses the graph
+    DeadlockGraph { xml: String },
+    Sql { sql: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedPlan {
+    /// ShowPlanXML schema version ("1.2" for SQL Server 2012, ...)
+    pub version: Option<String>,
+    pub build: Option<String>,
+    /// Server that produced the plan, from `build`
+    #[serde(default)]
+    pub product: Option<String>,
+    /// Attributes the analyses read that this plan's server does not write
+    #[serde(default)]
+    pub unavailable_attributes: Vec<String>,
+    pub statements: Vec<PlanStatement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanStatement {
+    pub statement_id: i64,
+    pub statement_text: String,
+    pub statement_type: String,
+    pub statement_sub_tree_cost: f64,
+    pub statement_est_rows: f64,
+    pub query_hash: Option<String>,
+    pub query_plan_hash: Option<String>,
+    pub degree_of_parallelism: Option<i64>,
+    /// Why the optimizer produced a serial plan, e.g. "MaxDOPSetToOne"
+    #[serde(default)]
+    pub non_parallel_plan_reason: Option<String>,
+    /// StatementOptmLevel: TRIVIAL plans are never considered for parallelism
+    #[serde(default)]
+    pub optimization_level: Option<String>,
+    /// QueryPlan/ThreadStat of parallel plans
+    #[serde(default)]
+    pub thread_stat: Option<ThreadStat>,
+    /// QueryPlan/MemoryGrantInfo; actual plans also carry the used maximum
+    #[serde(default)]
+    pub memory_grant: Option<MemoryGrant>,
+    pub root: Option<PlanNode>,
+}
+
+/// Worker threads of a parallel plan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadStat {
+    /// Parallel branches that can run at the same time
+    pub branches: i64,
+    /// Actual plans only
+    pub used_threads: Option<i64>,
+    /// Sum of ThreadReservation/ReservedThreads
+    pub reserved_threads: Option<i64>,
+}
+
+/// Statement memory grant in KB, as sys.dm_exec_query_memory_grants reports it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryGrant {
+    pub serial_required_kb: Option<f64>,
+    pub serial_desired_kb: Option<f64>,
+    pub requested_kb: Option<f64>,
+    pub granted_kb: Option<f64>,
+    /// Actual plans only
+    pub max_used_kb: Option<f64>,
+    pub grant_wait_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanNode {
+    pub node_id: i64,
+    pub physical_op: String,
+    pub logical_op: String,
+    pub estimate_rows: f64,
+    pub estimate_cpu: f64,
+    pub estimate_io: f64,
+    pub estimated_total_subtree_cost: f64,
+    pub estimate_rebinds: f64,
+    pub estimate_rewinds: f64,
+    pub parallel: bool,
+    pub object: Option<PlanObject>,
+    pub predicate: Option<String>,
+    pub seek_predicates: Vec<String>,
+    pub warnings: Vec<String>,
+    /// SpillToTempDb warning merged with its Sort/Hash/Exchange spill details
+    #[serde(default)]
+    pub spill: Option<SpillDetails>,
+    pub runtime: Option<RuntimeCounters>,
+    /// Raw RelOp attributes plus those of the operator element (IndexScan, Hash, ...)
+    pub attributes: HashMap<String, String>,
+    pub children: Vec<PlanNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanObject {
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub index: Option<String>,
+    pub alias: Option<String>,
+}
+
+/// RunTimeCountersPerThread summed over all threads
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeCounters {
+    pub actual_rows: f64,
+    pub actual_executions: f64,
+    pub actual_elapsed_ms: Option<f64>,
+    pub actual_cpu_ms: Option<f64>,
+    pub actual_logical_reads: Option<f64>,
+    pub actual_physical_reads: Option<f64>,
+    pub thread_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpillKind {
+    Sort,
+    Hash,
+    Exchange,
+    Other,
+}
+
+/// Pages are 8 KB tempdb pages; the details elements only appear in actual plans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpillDetails {
+    pub kind: SpillKind,
+    pub spill_level: Option<i64>,
+    pub spilled_thread_count: Option<i64>,
+    /// Memory the operator was granted and used before spilling
+    pub granted_memory_kb: Option<f64>,
+    pub used_memory_kb: Option<f64>,
+    pub pages_written: Option<f64>,
+    pub pages_read: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanSummary {
+    /// Server that produced the plan, when its Build is known
+    pub product: Option<String>,
+    /// Attributes missing because the server predates them
+    pub unavailable_attributes: Vec<String>,
+    pub statements: Vec<StatementSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementSummary {
+    pub statement_id: i64,
+    pub statement_text: String,
+    pub total_subtree_cost: f64,
+    /// Every operator in tree order (parent before children)
+    pub operators: Vec<OperatorCost>,
+    /// The five operators with the highest own cost
+    pub top_operators: Vec<OperatorCost>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorCost {
+    pub node_id: i64,
+    pub physical_op: String,
+    pub logical_op: String,
+    pub object_name: Option<String>,
+    /// Subtree cost minus the children's subtree costs (SSMS "Estimated Operator Cost")
+    pub operator_cost: f64,
+    /// operator_cost as a percentage of the statement cost
+    pub cost_percent: f64,
+    pub subtree_cost: f64,
+    pub estimate_rows: f64,
+    /// EstimateRows × (rebinds + rewinds + 1), comparable with actual rows
+    pub estimate_rows_all_executions: f64,
+    pub actual_rows: Option<f64>,
+    pub actual_executions: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanImageFormat {
+    Svg,
+    Png,
+}
+
+/// Colors and font of exported plan pictures; missing fields fall back to
+/// the light theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanTheme {
+    /// None leaves the background transparent
+    pub background: Option<String>,
+    pub font_family: String,
+    pub heading: String,
+    pub muted_text: String,
+    pub edge: String,
+    pub node_fill: String,
+    /// Operators with at least 20% of the statement cost
+    pub node_fill_warm: String,
+    /// Operators with at least 50% of the statement cost
+    pub node_fill_hot: String,
+    pub node_border: String,
+    pub warning_border: String,
+    pub node_title: String,
+    pub node_text: String,
+}
+
+impl Default for PlanTheme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl PlanTheme {
+    pub fn light() -> Self {
+        Self {
+            background: Some("#ffffff".to_string()),
+            font_family: "Segoe UI, Helvetica, Arial, sans-serif".to_string(),
+            heading: "#1f2937".to_string(),
+            muted_text: "#6b7280".to_string(),
+            edge: "#9ca3af".to_string(),
+            node_fill: "#f3f4f6".to_string(),
+            node_fill_warm: "#ffedd5".to_string(),
+            node_fill_hot: "#fee2e2".to_string(),
+            node_border: "#6b7280".to_string(),
+            warning_border: "#d97706".to_string(),
+            node_title: "#111827".to_string(),
+            node_text: "#374151".to_string(),
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            background: Some("#0f172a".to_string()),
+            font_family: "Segoe UI, Helvetica, Arial, sans-serif".to_string(),
+            heading: "#e2e8f0".to_string(),
+            muted_text: "#94a3b8".to_string(),
+            edge: "#64748b".to_string(),
+            node_fill: "#1e293b".to_string(),
+            node_fill_warm: "#7c2d12".to_string(),
+            node_fill_hot: "#7f1d1d".to_string(),
+            node_border: "#475569".to_string(),
+            warning_border: "#f59e0b".to_string(),
+            node_title: "#f8fafc".to_string(),
+            node_text: "#cbd5e1".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanImage {
+    pub format: PlanImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// SVG markup, or base64-encoded PNG bytes
+    pub data: String,
+}
+
+/// Plan shape preview for a history entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanThumbnail {
+    pub plan_id: String,
+    #[serde(flatten)]
+    pub image: PlanImage,
+    pub operator_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanComparison {
+    pub statements: Vec<StatementComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementComparison {
+    pub statement_id: i64,
+    pub statement_text: String,
+    pub cost_before: f64,
+    pub cost_after: f64,
+    /// None when the original cost is zero
+    pub cost_change_percent: Option<f64>,
+    /// Operators (with their object) only present in the second plan
+    pub operators_added: Vec<String>,
+    pub operators_removed: Vec<String>,
+}
+
+/// Why a newly saved plan looks worse than the previous plan of the same query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanRegression {
+    /// Plan history entry the new plan was compared against
+    pub previous_plan_id: String,
+    pub cost_before: f64,
+    pub cost_after: f64,
+    pub cost_change_percent: Option<f64>,
+    pub plan_hash_changed: bool,
+    pub reasons: Vec<String>,
+}
+
+/// One operator that spilled to tempdb, explained against its memory grant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpillFinding {
+    pub statement_id: i64,
+    pub node_id: i64,
+    pub physical_op: String,
+    pub details: SpillDetails,
+    /// The statement grant, for operators that do not report their own
+    pub statement_grant: Option<MemoryGrant>,
+    pub message: String,
+}
+
+/// Two runs of one query with different parameter values, operator by operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterPlanComparison {
+    /// The runs compiled to different plan shapes
+    pub plan_changed: bool,
+    pub comparison: PlanComparison,
+    pub operators: Vec<CardinalityComparison>,
+    /// Plain-language hints about likely parameter sniffing problems
+    pub findings: Vec<String>,
+}
+
+/// An operator matched across both plans by label and position in tree order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardinalityComparison {
+    pub statement_id: i64,
+    /// "Index Seek (dbo.Orders.IX_Date)"
+    pub label: String,
+    pub first: Option<OperatorRows>,
+    pub second: Option<OperatorRows>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorRows {
+    pub node_id: i64,
+    pub predicate: Option<String>,
+    pub seek_predicates: Vec<String>,
+    /// EstimateRows × (rebinds + rewinds + 1)
+    pub estimated_rows: f64,
+    pub actual_rows: Option<f64>,
+}
+
+/// Criteria for search_plan; every criterion given must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanSearchQuery {
+    pub statement_id: Option<i64>,
+    /// Physical or logical operator name, case-insensitive ("Index Scan")
+    pub operator: Option<String>,
+    /// Table or index name, with or without schema ("dbo.Orders", "IX_Date")
+    pub object: Option<String>,
+    /// Actual vs estimated rows off by at least this factor either way
+    pub min_estimate_ratio: Option<f64>,
+    pub has_warnings: Option<bool>,
+    /// Substring of the predicate or a seek predicate
+    pub predicate_contains: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanSearchMatch {
+    pub statement_id: i64,
+    pub node_id: i64,
+    pub physical_op: String,
+    pub object_name: Option<String>,
+    /// Actual / estimated rows over all executions, for actual plans
+    pub estimate_ratio: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkewSeverity {
+    /// Within 2x
+    Accurate,
+    /// 2x to 10x
+    Minor,
+    /// 10x to 100x
+    Moderate,
+    /// 100x or worse
+    Severe,
+}
+
+/// How far the optimizer's row estimate was from what actually ran, per node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeEstimateSkew {
+    pub statement_id: i64,
+    pub node_id: i64,
+    pub physical_op: String,
+    /// EstimateRows × (rebinds + rewinds + 1)
+    pub estimated_rows: f64,
+    pub actual_rows: f64,
+    /// actual / estimated
+    pub ratio: f64,
+    /// The ratio or its inverse, whichever is at least 1
+    pub skew_factor: f64,
+    /// More rows came back than estimated
+    pub underestimated: bool,
+    pub severity: SkewSeverity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanExplanation {
+    pub statements: Vec<StatementExplanation>,
+}
+
+/// Plain-English reading of one statement's plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementExplanation {
+    pub statement_id: i64,
+    pub statement_text: String,
+    /// The steps as one paragraph
+    pub narrative: String,
+    /// In the order rows flow: inputs before the operators that consume them
+    pub steps: Vec<ExplanationStep>,
+    /// Things worth a closer look: lookups, warnings, spills, bad estimates
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperatorCategory {
+    DataAccess,
+    Join,
+    Ordering,
+    Aggregation,
+    Filtering,
+    Computation,
+    Parallelism,
+    Spool,
+    SetOperation,
+    Modification,
+}
+
+/// Bundled description of a plan operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorDoc {
+    /// Physical operator name as it appears in plans
+    pub name: String,
+    /// Other operators sharing this description, e.g. Table Insert
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub category: OperatorCategory,
+    /// One sentence for tooltips
+    pub summary: String,
+    pub details: String,
+    pub common_causes: Vec<String>,
+    pub fixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplanationStep {
+    pub node_id: i64,
+    /// For looking up the operator's documentation
+    #[serde(default)]
+    pub physical_op: String,
+    pub text: String,
+}
+
+/// Server and database settings that decide whether a plan may go parallel
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelismSettings {
+    pub cost_threshold: i64,
+    /// Server MAXDOP; 0 lets a query use every scheduler
+    pub server_max_dop: i64,
+    /// Database scoped MAXDOP, which overrides the server's when not 0
+    pub database_max_dop: Option<i64>,
+    pub cpu_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelismReport {
+    pub settings: ParallelismSettings,
+    /// MAXDOP queries get without a hint; None when it depends on CPUs not known
+    pub effective_max_dop: Option<i64>,
+    pub statements: Vec<StatementParallelism>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementParallelism {
+    pub statement_id: i64,
+    pub went_parallel: bool,
+    pub degree_of_parallelism: Option<i64>,
+    pub thread_stat: Option<ThreadStat>,
+    pub estimated_cost: f64,
+    /// Why the plan did or did not go parallel
+    pub reason: String,
+    /// How the settings played into it
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Something an analysis rule found in a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanIssue {
+    pub rule_id: String,
+    pub severity: IssueSeverity,
+    pub title: String,
+    pub description: String,
+    pub node_id: Option<i64>,
+    /// 0-100, for ordering issues by how much fixing them would help
+    pub impact: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementAnalysis {
+    pub statement_id: i64,
+    /// Highest impact first
+    pub issues: Vec<PlanIssue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanAnalysis {
+    pub statements: Vec<StatementAnalysis>,
+}
+
+/// An analysis rule as listed in settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisRuleInfo {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub severity: IssueSeverity,
+    pub enabled: bool,
+    /// Defined by the user in settings rather than built in
+    pub custom: bool,
+}
+
+/// A user-defined analysis rule, kept in settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomRuleDefinition {
+    pub id: String,
+    pub title: String,
+    /// Condition over node properties, e.g.
+    /// `PhysicalOp == 'Clustered Index Scan' && EstimateRows > 100000`
+    pub expression: String,
+    pub severity: IssueSeverity,
+    /// Issue text; by default the operator and the condition it matched
+    #[serde(default)]
+    pub message: Option<String>,
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::Ordering;
+
+use super::profile::{Capability, Profile, ProfileChanges};
+use super::AppSettings;
+use crate::db::connection::AppState;
+use crate::db::types::AppError;
+use crate::logging::LoggingState;
+use crate::share::ShareState;
+
+#[tauri::command]
+pub async fn get_settings(app: tauri::AppHandle) -> Result<AppSettings, String> {
+    super::load(&app)
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    mut settings: AppSettings,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    logging: tauri::State<'_, LoggingState>,
+    share: tauri::State<'_, ShareState>,
+) -> Result<AppSettings, String> {
+    settings = settings.normalized();
+    // The profile only changes through change_profile
+    settings.profile = super::load(&app)?.profile;
+    super::save(&app, &settings)?;
+    logging.set_level(settings.log_level)?;
+    if let Some(conn) = state.connection.lock().await.as_ref() {
+        conn.set_keep_alive(settings.keep_alive_interval());
+        conn.cast_unsupported_types
+            .store(settings.cast_unsupported_types, Ordering::Relaxed);
+        conn.metadata_cache.set_ttl(settings.metadata_cache_ttl());
+    }
+    if !settings.share_server.enabled {
+        share.stop().await;
+    }
+    Ok(settings)
+}
+
+/// Ask to switch the profile. Returns the one-time token `change_profile`
+/// needs, once the user has confirmed the switch.
+#[tauri::command]
+pub async fn request_profile_change(
+    profile: Profile,
+    changes: tauri::State<'_, ProfileChanges>,
+) -> Result<String, AppError> {
+    tracing::info!(to = ?profile, "Profile change requested");
+    Ok(changes.request(profile))
+}
+
+/// Switch the profile. `token` must be the one `request_profile_change`
+/// issued for this profile, so a stray call cannot widen what the app may do.
+#[tauri::command]
+pub async fn change_profile(
+    profile: Profile,
+    token: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    changes: tauri::State<'_, ProfileChanges>,
+) -> Result<AppSettings, AppError> {
+    changes.confirm(profile, &token)?;
+    let mut settings = super::load(&app)?;
+    let previous = settings.profile;
+    settings.profile = profile;
+    super::save(&app, &settings)?;
+    if let Some(conn) = state.connection.lock().await.as_ref() {
+        conn.profile_read_only
+            .store(!profile.allows(Capability::ModifyData), Ordering::Relaxed);
+    }
+    tracing::warn!(from = ?previous, to = ?profile, "Profile changed");
+    Ok(settings)
+}
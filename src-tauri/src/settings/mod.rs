@@ -0,0 +1,174 @@
+pub mod commands;
+pub mod profile;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::db::types::FormatOptions;
+use crate::logging::LogLevel;
+use crate::plan::types::{CustomRuleDefinition, PlanTheme};
+use profile::Profile;
+
+const SETTINGS_STORE: &str = "settings.json";
+
+/// Queries a connection runs at once. A session runs one batch at a time,
+/// so a larger saved value is lowered to this.
+pub const MAX_CONCURRENT_QUERIES: usize = 1;
+
+/// Application-wide preferences; missing fields fall back to the defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// Show a desktop notification when a query runs longer than the threshold
+    pub notify_long_queries: bool,
+    pub long_query_threshold_ms: u64,
+    /// Ping idle connections this often; 0 disables the keep-alive
+    pub keep_alive_minutes: u64,
+    /// Allow sending redacted plan summaries to the configured AI endpoint
+    pub ai_explanations_enabled: bool,
+    /// Style for format_sql, shared by the editor and the history view
+    pub format_options: FormatOptions,
+    pub log_level: LogLevel,
+    /// Retry queries that fail on column types the client cannot read with
+    /// those columns cast
+    pub cast_unsupported_types: bool,
+    pub plan_retention: PlanRetention,
+    /// Queries started at once on the active connection; the rest wait in
+    /// the execution queue. At most `MAX_CONCURRENT_QUERIES`.
+    pub max_concurrent_queries: usize,
+    /// Ids of plan analysis rules that are turned off
+    pub disabled_analysis_rules: Vec<String>,
+    /// User-defined plan analysis rules
+    pub custom_analysis_rules: Vec<CustomRuleDefinition>,
+    /// Colors of exported plan images unless a render call passes its own
+    pub plan_export_theme: PlanTheme,
+    /// How long metadata read from sys.* views is reused before it is
+    /// checked again
+    pub metadata_cache_minutes: u64,
+    /// Gates dangerous commands; only change_profile changes it
+    pub profile: Profile,
+    pub share_server: ShareServerSettings,
+}
+
+/// HTTP listener serving shared plans to browsers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShareServerSettings {
+    pub enabled: bool,
+    /// Listen on every interface instead of localhost only
+    pub allow_lan: bool,
+    /// 0 picks a free port
+    pub port: u16,
+}
+
+/// How much plan history is kept; the oldest plans are evicted first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PlanRetention {
+    /// Size of the stored (compressed) plans across all connections
+    pub max_size_mb: u64,
+    pub max_plans_per_connection: usize,
+    /// Overrides of `max_plans_per_connection` by saved connection id
+    pub connection_limits: HashMap<String, usize>,
+}
+
+impl Default for PlanRetention {
+    fn default() -> Self {
+        Self {
+            max_size_mb: 200,
+            max_plans_per_connection: 50,
+            connection_limits: HashMap::new(),
+        }
+    }
+}
+
+impl PlanRetention {
+    pub fn max_bytes(&self) -> usize {
+        (self.max_size_mb as usize).saturating_mul(1024 * 1024)
+    }
+
+    pub fn limit_for(&self, connection_id: &str) -> usize {
+        self.connection_limits
+            .get(connection_id)
+            .copied()
+            .unwrap_or(self.max_plans_per_connection)
+    }
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            notify_long_queries: true,
+            long_query_threshold_ms: 10_000,
+            keep_alive_minutes: 4,
+            ai_explanations_enabled: false,
+            format_options: FormatOptions::default(),
+            log_level: LogLevel::Info,
+            cast_unsupported_types: false,
+            plan_retention: PlanRetention::default(),
+            max_concurrent_queries: MAX_CONCURRENT_QUERIES,
+            disabled_analysis_rules: Vec::new(),
+            custom_analysis_rules: Vec::new(),
+            plan_export_theme: PlanTheme::default(),
+            metadata_cache_minutes: 10,
+            profile: Profile::default(),
+            share_server: ShareServerSettings::default(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        (self.keep_alive_minutes > 0).then(|| Duration::from_secs(self.keep_alive_minutes * 60))
+    }
+
+    pub fn metadata_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.metadata_cache_minutes * 60)
+    }
+
+    /// Bring saved values within what the app supports
+    pub fn normalized(mut self) -> Self {
+        self.max_concurrent_queries = self.max_concurrent_queries.clamp(1, MAX_CONCURRENT_QUERIES);
+        self
+    }
+}
+
+pub fn load(app: &AppHandle) -> Result<AppSettings, String> {
+    let store = app.store(SETTINGS_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get("settings")
+        .and_then(|v| serde_json::from_value::<AppSettings>(v).ok())
+        .unwrap_or_default()
+        .normalized())
+}
+
+pub fn save(app: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    let store = app.store(SETTINGS_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "settings",
+        serde_json::to_value(settings).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_concurrency_is_kept_within_one_query() {
+        let saved: AppSettings =
+            serde_json::from_value(serde_json::json!({ "maxConcurrentQueries": 4 })).unwrap();
+        assert_eq!(saved.normalized().max_concurrent_queries, 1);
+
+        let saved: AppSettings =
+            serde_json::from_value(serde_json::json!({ "maxConcurrentQueries": 0 })).unwrap();
+        assert_eq!(saved.normalized().max_concurrent_queries, 1);
+        assert_eq!(AppSettings::default().max_concurrent_queries, 1);
+    }
+}
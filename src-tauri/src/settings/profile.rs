@@ -0,0 +1,162 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use uuid::Uuid;
+
+use crate::db::types::AppError;
+
+/// What the person at the keyboard is trusted to do. Checked by the
+/// commands themselves, so a UI that forgets to hide a button cannot get
+/// around it.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Profile {
+    #[default]
+    Developer,
+    Dba,
+    ReadOnly,
+}
+
+/// Actions a profile may be denied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Batches with DML or DDL, on any connection
+    ModifyData,
+    /// KILL sessions other than the app's own
+    KillSessions,
+    /// Force or unforce Query Store plans
+    ForcePlans,
+    /// ALTER DATABASE, e.g. to switch the compatibility level
+    AlterDatabase,
+}
+
+impl Capability {
+    fn description(self) -> &'static str {
+        match self {
+            Capability::ModifyData => "statements that modify data or schema",
+            Capability::KillSessions => "killing sessions",
+            Capability::ForcePlans => "forcing Query Store plans",
+            Capability::AlterDatabase => "ALTER DATABASE",
+        }
+    }
+}
+
+impl Profile {
+    /// Name the user sees when confirming a switch to the profile
+    pub fn label(self) -> &'static str {
+        match self {
+            Profile::Developer => "Developer",
+            Profile::Dba => "DBA",
+            Profile::ReadOnly => "ReadOnly",
+        }
+    }
+
+    pub fn allows(self, capability: Capability) -> bool {
+        match self {
+            Profile::Dba => true,
+            Profile::Developer => capability == Capability::ModifyData,
+            Profile::ReadOnly => false,
+        }
+    }
+
+    pub fn check(self, capability: Capability) -> Result<(), AppError> {
+        if self.allows(capability) {
+            Ok(())
+        } else {
+            Err(AppError::permission(format!(
+                "The {} profile does not allow {}",
+                self.label(),
+                capability.description()
+            )))
+        }
+    }
+}
+
+/// How long a confirmation token may be used
+const CHANGE_TOKEN_TTL: Duration = Duration::from_secs(120);
+
+/// The profile change the backend was asked for and the one-time token that
+/// confirms it. Only the token the backend issued changes the profile; a
+/// caller cannot make one up, and each token works once, for one profile.
+#[derive(Default)]
+pub struct ProfileChanges {
+    pending: Mutex<Option<PendingChange>>,
+}
+
+struct PendingChange {
+    profile: Profile,
+    token: String,
+    issued_at: Instant,
+}
+
+impl ProfileChanges {
+    /// Start a change to `profile`, replacing any earlier request
+    pub fn request(&self, profile: Profile) -> String {
+        let token = Uuid::new_v4().to_string();
+        *self.pending.lock().unwrap() = Some(PendingChange {
+            profile,
+            token: token.clone(),
+            issued_at: Instant::now(),
+        });
+        token
+    }
+
+    /// Use up the pending request; fails unless `token` is the one issued
+    /// for `profile` and has not expired
+    pub fn confirm(&self, profile: Profile, token: &str) -> Result<(), AppError> {
+        let pending = self.pending.lock().unwrap().take();
+        match pending {
+            Some(p)
+                if p.profile == profile
+                    && p.token == token
+                    && p.issued_at.elapsed() < CHANGE_TOKEN_TTL =>
+            {
+                Ok(())
+            }
+            _ => Err(AppError::permission(format!(
+                "Switching to the {} profile was not confirmed",
+                profile.label()
+            ))),
+        }
+    }
+}
+
+/// Fail unless the saved profile allows `capability`
+pub fn require(app: &AppHandle, capability: Capability) -> Result<(), AppError> {
+    super::load(app)?.profile.check(capability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_gate_capabilities() {
+        assert!(Profile::Dba.allows(Capability::KillSessions));
+        assert!(Profile::Developer.allows(Capability::ModifyData));
+        assert!(!Profile::Developer.allows(Capability::ForcePlans));
+        assert_eq!(
+            Profile::ReadOnly.check(Capability::ModifyData),
+            Err(AppError::permission(
+                "The ReadOnly profile does not allow statements that modify data or schema"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_profile_change_needs_the_issued_token_once() {
+        let changes = ProfileChanges::default();
+        assert!(changes.confirm(Profile::Dba, "made-up").is_err());
+
+        let token = changes.request(Profile::Dba);
+        assert!(changes.confirm(Profile::Developer, &token).is_err());
+        // A failed attempt uses the request up
+        assert!(changes.confirm(Profile::Dba, &token).is_err());
+
+        let token = changes.request(Profile::Dba);
+        assert!(changes.confirm(Profile::Dba, &token).is_ok());
+        assert!(changes.confirm(Profile::Dba, &token).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+use super::{render_page, ShareLink, ShareState};
+use crate::db::plan_bundle;
+use crate::db::types::{ExportPlanBundleRequest, PlanBundle};
+use crate::plan::{parser, render};
+use crate::settings;
+
+async fn share_bundle(
+    bundle: PlanBundle,
+    app: &tauri::AppHandle,
+    share: &ShareState,
+) -> Result<ShareLink, String> {
+    let app_settings = settings::load(app)?;
+    let plan = parser::parse_plan_async(bundle.plan_xml.clone()).await?;
+    let (svg, _, _) = render::render_svg(&plan, &app_settings.plan_export_theme);
+    let html = render_page(&bundle, &svg);
+    share.share(&app_settings.share_server, &bundle, html).await
+}
+
+/// Serve a plan read-only at a tokenized URL for a day
+#[tauri::command]
+pub async fn share_plan(
+    request: ExportPlanBundleRequest,
+    app: tauri::AppHandle,
+    share: tauri::State<'_, ShareState>,
+) -> Result<ShareLink, String> {
+    let bundle = plan_bundle::collect(&app, request)?;
+    share_bundle(bundle, &app, &share).await
+}
+
+/// Serve a bundle file (base64-encoded), e.g. one received from a colleague
+#[tauri::command]
+pub async fn share_plan_bundle(
+    data: String,
+    app: tauri::AppHandle,
+    share: tauri::State<'_, ShareState>,
+) -> Result<ShareLink, String> {
+    let bytes = BASE64
+        .decode(data.trim())
+        .map_err(|_| "Not a SqlPlanForDummies plan bundle")?;
+    share_bundle(plan_bundle::read(&bytes)?, &app, &share).await
+}
+
+#[tauri::command]
+pub async fn list_shared_plans(
+    share: tauri::State<'_, ShareState>,
+) -> Result<Vec<ShareLink>, String> {
+    Ok(share.links())
+}
+
+/// Stop serving one link, or all of them and the listener
+#[tauri::command]
+pub async fn stop_sharing(
+    token: Option<String>,
+    share: tauri::State<'_, ShareState>,
+) -> Result<(), String> {
+    match token {
+        Some(token) => share.unshare(&token).await,
+        None => share.stop().await,
+    }
+    Ok(())
+}
@@ -0,0 +1,361 @@
+pub mod commands;
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::db::types::PlanBundle;
+use crate::plan::types::IssueSeverity;
+use crate::settings::ShareServerSettings;
+
+// Plan review links: a small HTTP listener that serves each shared bundle as
+// one static HTML page at /plan/<token>. Pages are rendered when the plan is
+// shared, nothing else is reachable, and tokens are random and expire, so
+// the listener exposes only what the user picked. It binds to localhost
+// unless the settings allow the LAN.
+
+/// How long a link works
+const SHARE_LIFETIME_HOURS: i64 = 24;
+/// Longest request head read before the connection is dropped
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    pub token: String,
+    pub url: String,
+    pub plan_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+struct SharedPlan {
+    link: ShareLink,
+    html: Arc<str>,
+}
+
+type Shares = Arc<StdMutex<HashMap<String, SharedPlan>>>;
+
+struct RunningServer {
+    base_url: String,
+    allow_lan: bool,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+pub struct ShareState {
+    server: Mutex<Option<RunningServer>>,
+    shares: Shares,
+}
+
+impl ShareState {
+    /// Serve `html` under a new token, starting the listener if needed
+    pub async fn share(
+        &self,
+        settings: &ShareServerSettings,
+        bundle: &PlanBundle,
+        html: String,
+    ) -> Result<ShareLink, String> {
+        if !settings.enabled {
+            return Err("Plan sharing is turned off in settings".into());
+        }
+        let base_url = self.ensure_server(settings).await?;
+        let token: String = crate::db::encryption::random_bytes::<16>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let created_at = Utc::now();
+        let link = ShareLink {
+            url: format!("{}/plan/{}", base_url, token),
+            token: token.clone(),
+            plan_hash: bundle.plan_hash.clone(),
+            created_at,
+            expires_at: created_at + chrono::Duration::hours(SHARE_LIFETIME_HOURS),
+        };
+        self.shares.lock().unwrap().insert(
+            token,
+            SharedPlan {
+                link: link.clone(),
+                html: html.into(),
+            },
+        );
+        Ok(link)
+    }
+
+    /// Links still being served, newest first
+    pub fn links(&self) -> Vec<ShareLink> {
+        let mut shares = self.shares.lock().unwrap();
+        let now = Utc::now();
+        shares.retain(|_, shared| shared.link.expires_at > now);
+        let mut links: Vec<ShareLink> = shares.values().map(|s| s.link.clone()).collect();
+        links.sort_by_key(|link| std::cmp::Reverse(link.created_at));
+        links
+    }
+
+    /// Stop serving one link; the listener closes with the last one
+    pub async fn unshare(&self, token: &str) {
+        let empty = {
+            let mut shares = self.shares.lock().unwrap();
+            shares.remove(token);
+            shares.is_empty()
+        };
+        if empty {
+            self.stop().await;
+        }
+    }
+
+    /// Close the listener and forget every link
+    pub async fn stop(&self) {
+        self.shares.lock().unwrap().clear();
+        if let Some(server) = self.server.lock().await.take() {
+            server.task.abort();
+            tracing::info!("Plan share server stopped");
+        }
+    }
+
+    async fn ensure_server(&self, settings: &ShareServerSettings) -> Result<String, String> {
+        let mut server = self.server.lock().await;
+        if let Some(running) = server.as_ref() {
+            if running.allow_lan == settings.allow_lan {
+                return Ok(running.base_url.clone());
+            }
+            running.task.abort();
+        }
+
+        let ip = if settings.allow_lan {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let listener = TcpListener::bind(SocketAddr::from((ip, settings.port)))
+            .await
+            .map_err(|e| format!("Failed to start the share server: {}", e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let host = if settings.allow_lan {
+            hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_else(|| "localhost".to_string())
+        } else {
+            "127.0.0.1".to_string()
+        };
+        let base_url = format!("http://{}:{}", host, port);
+
+        let shares = self.shares.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let shares = shares.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(stream, &shares).await {
+                                tracing::debug!(%peer, error = %e, "Share request failed");
+                            }
+                        });
+                    }
+                    Err(e) => tracing::warn!(error = %e, "Share server accept failed"),
+                }
+            }
+        });
+        tracing::info!(%base_url, lan = settings.allow_lan, "Plan share server started");
+        *server = Some(RunningServer {
+            base_url: base_url.clone(),
+            allow_lan: settings.allow_lan,
+            task,
+        });
+        Ok(base_url)
+    }
+}
+
+async fn serve(mut stream: TcpStream, shares: &Shares) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buf))
+            .await
+            .map_err(|_| std::io::ErrorKind::TimedOut)??;
+        if read == 0 {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let (status, body) = match request_target(&String::from_utf8_lossy(&head)) {
+        Some(("GET", path)) => match path.strip_prefix("/plan/").and_then(|t| page(shares, t)) {
+            Some(html) => ("200 OK", html),
+            None => ("404 Not Found", Arc::from(NOT_FOUND_PAGE)),
+        },
+        Some(_) => ("405 Method Not Allowed", Arc::from("")),
+        None => ("400 Bad Request", Arc::from("")),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         X-Content-Type-Options: nosniff\r\n\
+         Referrer-Policy: no-referrer\r\n\
+         Content-Security-Policy: default-src 'none'; style-src 'unsafe-inline'\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Page of an unexpired share
+fn page(shares: &Shares, token: &str) -> Option<Arc<str>> {
+    let mut shares = shares.lock().unwrap();
+    if shares
+        .get(token)
+        .is_some_and(|shared| shared.link.expires_at <= Utc::now())
+    {
+        shares.remove(token);
+    }
+    shares.get(token).map(|shared| shared.html.clone())
+}
+
+const NOT_FOUND_PAGE: &str = "<!DOCTYPE html><title>Link expired</title>\
+    <p>This plan link does not exist or has expired.</p>";
+
+/// Method and path of an HTTP request head, without the query string
+fn request_target(head: &str) -> Option<(&str, &str)> {
+    let mut parts = head.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts.next()?.starts_with("HTTP/").then_some(())?;
+    Some((method, target.split('?').next().unwrap_or(target)))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The review page: query, rendered plan, findings, environment and notes.
+/// `plan_svg` is the app's own rendering and is embedded as is.
+pub fn render_page(bundle: &PlanBundle, plan_svg: &str) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Execution plan</title>\
+         <style>body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+         pre{background:#f5f5f5;padding:1rem;overflow:auto}\
+         .plan{overflow:auto;border:1px solid #ddd;margin:1rem 0}\
+         .critical{color:#b00020}.warning{color:#a15c00}.info{color:#555}</style></head><body>",
+    );
+    html.push_str(&format!(
+        "<h1>Execution plan</h1><p>Shared {} from SqlPlanForDummies {}</p>",
+        bundle.created_at.format("%Y-%m-%d %H:%M UTC"),
+        escape_html(&bundle.app_version)
+    ));
+    if let Some(sql) = &bundle.sql {
+        html.push_str(&format!("<h2>Query</h2><pre>{}</pre>", escape_html(sql)));
+    }
+    html.push_str(&format!("<div class=\"plan\">{}</div>", plan_svg));
+
+    if let Some(analysis) = &bundle.analysis {
+        let issues: Vec<_> = analysis
+            .statements
+            .iter()
+            .flat_map(|statement| &statement.issues)
+            .collect();
+        if !issues.is_empty() {
+            html.push_str("<h2>Findings</h2><ul>");
+            for issue in issues {
+                let class = match issue.severity {
+                    IssueSeverity::Critical => "critical",
+                    IssueSeverity::Warning => "warning",
+                    IssueSeverity::Info => "info",
+                };
+                html.push_str(&format!(
+                    "<li class=\"{}\"><strong>{}</strong>: {}</li>",
+                    class,
+                    escape_html(&issue.title),
+                    escape_html(&issue.description)
+                ));
+            }
+            html.push_str("</ul>");
+        }
+    }
+
+    if let Some(context) = &bundle.context {
+        html.push_str(&format!(
+            "<h2>Environment</h2><p>SQL Server {} {}, database {}, compatibility level {}</p>",
+            escape_html(&context.server_version),
+            escape_html(context.edition.as_deref().unwrap_or("")),
+            escape_html(&context.database_name),
+            context
+                .compatibility_level
+                .map_or("unknown".to_string(), |level| level.to_string())
+        ));
+    }
+
+    if !bundle.annotations.is_empty() {
+        html.push_str("<h2>Notes</h2><ul>");
+        for annotation in &bundle.annotations {
+            let node = annotation
+                .node_id
+                .map_or(String::new(), |id| format!("Node {}: ", id));
+            html.push_str(&format!(
+                "<li>{}{}</li>",
+                node,
+                escape_html(&annotation.text)
+            ));
+        }
+        html.push_str("</ul>");
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_target_and_escaping() {
+        assert_eq!(
+            request_target("GET /plan/abc?x=1 HTTP/1.1\r\nHost: pc\r\n\r\n"),
+            Some(("GET", "/plan/abc"))
+        );
+        assert_eq!(request_target("garbage\r\n\r\n"), None);
+
+        let bundle = PlanBundle {
+            created_at: Utc::now(),
+            app_version: "2.4.0".to_string(),
+            plan_hash: "1f2e".to_string(),
+            source_plan_id: None,
+            plan_xml: String::new(),
+            sql: Some("SELECT '<script>' AS x".to_string()),
+            analysis: None,
+            context: None,
+            annotations: Vec::new(),
+        };
+        let html = render_page(&bundle, "<svg></svg>");
+        assert!(html.contains("SELECT &#39;&lt;script&gt;&#39; AS x"));
+        assert!(html.contains("<svg></svg>"));
+    }
+}
@@ -0,0 +1,108 @@
+use super::{UploadResult, UploadTarget, UploadTargetInfo};
+use crate::db::redact;
+use crate::db::types::RedactOptions;
+
+#[tauri::command]
+pub async fn list_upload_targets(app: tauri::AppHandle) -> Result<Vec<UploadTargetInfo>, String> {
+    Ok(super::load(&app)?.iter().map(UploadTarget::info).collect())
+}
+
+/// Create a target, or update the one with `id`. `plan_field` and
+/// `url_pointer` fall back to the PasteThePlan-style defaults; `api_key`:
+/// None keeps the stored key, an empty string removes it.
+#[tauri::command]
+pub async fn save_upload_target(
+    id: Option<String>,
+    name: String,
+    endpoint: String,
+    plan_field: Option<String>,
+    url_pointer: Option<String>,
+    api_key: Option<String>,
+    app: tauri::AppHandle,
+) -> Result<UploadTargetInfo, String> {
+    let endpoint = endpoint.trim();
+    if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+        return Err("The endpoint must be an http(s) URL".to_string());
+    }
+    if name.trim().is_empty() {
+        return Err("Target name is required".to_string());
+    }
+
+    let mut targets = super::load(&app)?;
+    let index = match id {
+        Some(id) => targets
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or("Upload target not found")?,
+        None => {
+            targets.push(UploadTarget {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: String::new(),
+                endpoint: String::new(),
+                plan_field: String::new(),
+                url_pointer: String::new(),
+                encrypted_api_key: None,
+            });
+            targets.len() - 1
+        }
+    };
+    let target = &mut targets[index];
+    target.name = name.trim().to_string();
+    target.endpoint = endpoint.to_string();
+    target.plan_field = plan_field
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .unwrap_or_else(|| super::DEFAULT_PLAN_FIELD.to_string());
+    target.url_pointer = url_pointer
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| super::DEFAULT_URL_POINTER.to_string());
+    target.set_api_key(api_key.as_deref())?;
+    let info = target.info();
+
+    super::save(&app, &targets)?;
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn delete_upload_target(id: String, app: tauri::AppHandle) -> Result<(), String> {
+    let mut targets = super::load(&app)?;
+    targets.retain(|t| t.id != id);
+    super::save(&app, &targets)
+}
+
+/// Post a plan to a sharing site and return its link. Literal values are
+/// replaced first unless `redact` is false; `redact_object_names` replaces
+/// database, table, index and column names as well.
+#[tauri::command]
+pub async fn upload_plan(
+    target_id: String,
+    plan_xml: String,
+    redact: Option<bool>,
+    redact_object_names: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<UploadResult, String> {
+    let target = super::load(&app)?
+        .into_iter()
+        .find(|t| t.id == target_id)
+        .ok_or("Upload target not found")?;
+    let redacted = redact.unwrap_or(true);
+    let plan_xml = if redacted {
+        redact::redact_plan_xml(
+            &plan_xml,
+            RedactOptions {
+                object_names: redact_object_names.unwrap_or(false),
+            },
+        )
+    } else {
+        plan_xml
+    };
+
+    let url = super::post_plan(&target, &plan_xml).await?;
+    tracing::info!(target = %target.name, redacted, "Plan uploaded");
+    Ok(UploadResult {
+        url,
+        target_name: target.name,
+        redacted,
+    })
+}
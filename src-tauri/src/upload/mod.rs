@@ -0,0 +1,167 @@
+pub mod commands;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+
+use crate::db::encryption;
+
+const UPLOAD_STORE: &str = "settings.json";
+
+pub const DEFAULT_PLAN_FIELD: &str = "queryPlanXml";
+pub const DEFAULT_URL_POINTER: &str = "/url";
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A plan-sharing site that accepts a plan as JSON and answers with the
+/// link. Sites differ in field names, so both are configurable; the API key
+/// is kept encrypted with the machine key, like saved passwords.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadTarget {
+    pub id: String,
+    pub name: String,
+    /// URL the plan is POSTed to
+    pub endpoint: String,
+    /// JSON field of the request that carries the plan XML
+    pub plan_field: String,
+    /// JSON pointer to the share URL in the response, e.g. `/url`
+    pub url_pointer: String,
+    pub encrypted_api_key: Option<String>,
+}
+
+/// What the frontend sees: never the key itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadTargetInfo {
+    pub id: String,
+    pub name: String,
+    pub endpoint: String,
+    pub plan_field: String,
+    pub url_pointer: String,
+    pub has_api_key: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResult {
+    pub url: String,
+    pub target_name: String,
+    /// Literal values were replaced before the upload
+    pub redacted: bool,
+}
+
+impl UploadTarget {
+    pub fn info(&self) -> UploadTargetInfo {
+        UploadTargetInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            endpoint: self.endpoint.clone(),
+            plan_field: self.plan_field.clone(),
+            url_pointer: self.url_pointer.clone(),
+            has_api_key: self.encrypted_api_key.is_some(),
+        }
+    }
+
+    /// None keeps the current key, an empty string removes it
+    pub fn set_api_key(&mut self, api_key: Option<&str>) -> Result<(), String> {
+        match api_key.map(str::trim) {
+            None => {}
+            Some("") => self.encrypted_api_key = None,
+            Some(key) => self.encrypted_api_key = Some(encryption::encrypt_password(key)?),
+        }
+        Ok(())
+    }
+
+    fn api_key(&self) -> Result<Option<String>, String> {
+        self.encrypted_api_key
+            .as_deref()
+            .map(encryption::decrypt_password)
+            .transpose()
+    }
+}
+
+pub fn load(app: &AppHandle) -> Result<Vec<UploadTarget>, String> {
+    let store = app.store(UPLOAD_STORE).map_err(|e| e.to_string())?;
+    Ok(store
+        .get("uploadTargets")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default())
+}
+
+pub fn save(app: &AppHandle, targets: &[UploadTarget]) -> Result<(), String> {
+    let store = app.store(UPLOAD_STORE).map_err(|e| e.to_string())?;
+    store.set(
+        "uploadTargets",
+        serde_json::to_value(targets).map_err(|e| e.to_string())?,
+    );
+    store.save().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Share URL in a response, either at the configured pointer or, for sites
+/// that answer with the bare link, the whole body
+fn share_url(body: &str, url_pointer: &str) -> Option<String> {
+    let url = match serde_json::from_str::<Value>(body) {
+        Ok(value) => value.pointer(url_pointer)?.as_str()?.to_string(),
+        Err(_) => body.trim().to_string(),
+    };
+    (url.starts_with("https://") || url.starts_with("http://")).then_some(url)
+}
+
+/// POST the plan and return the share URL from the response
+pub async fn post_plan(target: &UploadTarget, plan_xml: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(UPLOAD_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let mut request = client
+        .post(&target.endpoint)
+        .json(&json!({ target.plan_field.as_str(): plan_xml }));
+    if let Some(key) = target.api_key()? {
+        request = request.bearer_auth(key);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", target.name, e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Invalid response from {}: {}", target.name, e))?;
+    if !status.is_success() {
+        let detail: String = body.chars().take(200).collect();
+        return Err(format!("{} returned {}: {}", target.name, status, detail));
+    }
+    share_url(&body, &target.url_pointer)
+        .ok_or_else(|| format!("{} did not return a share URL", target.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_url_from_response() {
+        assert_eq!(
+            share_url(r#"{"id":"abc","url":"https://plans.example/abc"}"#, "/url"),
+            Some("https://plans.example/abc".to_string())
+        );
+        assert_eq!(
+            share_url(
+                r#"{"data":{"link":"https://plans.example/x"}}"#,
+                "/data/link"
+            ),
+            Some("https://plans.example/x".to_string())
+        );
+        assert_eq!(
+            share_url("https://plans.example/raw\n", "/url"),
+            Some("https://plans.example/raw".to_string())
+        );
+        assert_eq!(share_url(r#"{"error":"too large"}"#, "/url"), None);
+    }
+}
@@ -0,0 +1,232 @@
+use chrono::Utc;
+use tauri::Emitter;
+
+use super::{
+    create_session_sql, drop_session_sql, poll, run, session_name, set_state_sql, ActiveSession,
+    XeEvent, XeScope, XeSessionInfo, XeSessionStatus, XeState, POLL_INTERVAL,
+};
+use crate::db::connection::AppState;
+use crate::db::rows::get_i64;
+
+/// EngineEdition of Azure SQL Database, where event sessions are database-scoped
+const AZURE_SQL_DATABASE: i64 = 5;
+
+fn permission_hint(action: &str, error: String) -> String {
+    format!(
+        "Failed to {} the event session: {}\n\
+         Extended Events need the ALTER ANY EVENT SESSION permission \
+         (ALTER ANY DATABASE EVENT SESSION on Azure SQL Database).",
+        action, error
+    )
+}
+
+/// Create the app's event session on the active connection, replacing a
+/// stopped one. The session captures nothing until it is started.
+#[tauri::command]
+pub async fn xe_create_session(
+    scope: XeScope,
+    state: tauri::State<'_, AppState>,
+    xe: tauri::State<'_, XeState>,
+) -> Result<XeSessionInfo, String> {
+    let mut session = xe.session.lock().await;
+    if let Some(active) = session.as_ref() {
+        if active.info.status == XeSessionStatus::Running {
+            return Err("An event session is already running; stop it first".to_string());
+        }
+    }
+    if let Some(previous) = session.take() {
+        let mut client = previous.client.lock().await;
+        let sql = drop_session_sql(&previous.info.name, previous.info.database_scoped);
+        if let Err(e) = run(&mut client, &sql).await {
+            tracing::warn!(error = %e, session = %previous.info.name, "Failed to drop previous event session");
+        }
+    }
+
+    let lock = state.connection.lock().await;
+    let conn = lock.as_ref().ok_or("Not connected to database")?;
+    let client = conn.client.clone();
+    drop(lock);
+
+    let (name, database_scoped) = {
+        let mut client = client.lock().await;
+        let row = client
+            .simple_query("SELECT @@SPID, CAST(SERVERPROPERTY('EngineEdition') AS int)")
+            .await
+            .map_err(|e| e.to_string())?
+            .into_row()
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Failed to read the session id")?;
+        let spid = get_i64(&row, 0).ok_or("Failed to read the session id")?;
+        let database_scoped = get_i64(&row, 1) == Some(AZURE_SQL_DATABASE);
+        let name = session_name(spid);
+
+        // A session left behind by a crashed run has the same name
+        run(&mut client, &drop_session_sql(&name, database_scoped))
+            .await
+            .map_err(|e| permission_hint("create", e))?;
+        run(
+            &mut client,
+            &create_session_sql(&name, &scope, spid, database_scoped),
+        )
+        .await
+        .map_err(|e| permission_hint("create", e))?;
+        (name, database_scoped)
+    };
+
+    xe.capture.clear();
+    let info = XeSessionInfo {
+        name,
+        scope,
+        status: XeSessionStatus::Created,
+        database_scoped,
+        created_at: Utc::now(),
+        events_captured: 0,
+    };
+    tracing::info!(session = %info.name, "Event session created");
+    *session = Some(ActiveSession {
+        info: info.clone(),
+        client,
+        poller: None,
+    });
+    Ok(info)
+}
+
+/// Start capturing; new events are emitted as "xevents-captured"
+#[tauri::command]
+pub async fn xe_start_session(
+    xe: tauri::State<'_, XeState>,
+    app: tauri::AppHandle,
+) -> Result<XeSessionInfo, String> {
+    let mut session = xe.session.lock().await;
+    let active = session
+        .as_mut()
+        .ok_or("No event session; create one first")?;
+    if active.info.status == XeSessionStatus::Running {
+        return Ok(active.info.clone());
+    }
+
+    let sql = set_state_sql(&active.info.name, active.info.database_scoped, "START");
+    run(&mut *active.client.lock().await, &sql)
+        .await
+        .map_err(|e| permission_hint("start", e))?;
+    // Sequence numbers start over with the session
+    *xe.capture.last_sequence.lock().unwrap() = 0;
+
+    let client = active.client.clone();
+    let name = active.info.name.clone();
+    let database_scoped = active.info.database_scoped;
+    let capture = xe.capture.clone();
+    active.poller = Some(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            match poll(&client, &name, database_scoped, &capture).await {
+                Ok(events) if !events.is_empty() => {
+                    let _ = app.emit("xevents-captured", &events);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(error = %e, session = %name, "Failed to read event session")
+                }
+            }
+        }
+    }));
+    active.info.status = XeSessionStatus::Running;
+    Ok(active.info.clone())
+}
+
+/// Stop capturing after reading what the session still holds. The session
+/// stays on the server until it is dropped, so it can be started again.
+#[tauri::command]
+pub async fn xe_stop_session(
+    xe: tauri::State<'_, XeState>,
+    app: tauri::AppHandle,
+) -> Result<XeSessionInfo, String> {
+    let mut session = xe.session.lock().await;
+    let active = session.as_mut().ok_or("No event session")?;
+    if active.info.status != XeSessionStatus::Running {
+        return Ok(active.info.clone());
+    }
+
+    if let Some(task) = active.poller.take() {
+        task.abort();
+    }
+    match poll(
+        &active.client,
+        &active.info.name,
+        active.info.database_scoped,
+        &xe.capture,
+    )
+    .await
+    {
+        Ok(events) if !events.is_empty() => {
+            let _ = app.emit("xevents-captured", &events);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::warn!(error = %e, session = %active.info.name, "Failed to read event session")
+        }
+    }
+
+    let sql = set_state_sql(&active.info.name, active.info.database_scoped, "STOP");
+    run(&mut *active.client.lock().await, &sql)
+        .await
+        .map_err(|e| permission_hint("stop", e))?;
+    active.info.status = XeSessionStatus::Stopped;
+    active.info.events_captured = xe.events_captured();
+    Ok(active.info.clone())
+}
+
+/// Remove the session from the server; captured events are kept
+#[tauri::command]
+pub async fn xe_drop_session(xe: tauri::State<'_, XeState>) -> Result<(), String> {
+    let mut session = xe.session.lock().await;
+    let Some(active) = session.as_mut() else {
+        return Ok(());
+    };
+    if let Some(task) = active.poller.take() {
+        task.abort();
+    }
+    let sql = drop_session_sql(&active.info.name, active.info.database_scoped);
+    run(&mut *active.client.lock().await, &sql)
+        .await
+        .map_err(|e| permission_hint("drop", e))?;
+    tracing::info!(session = %active.info.name, "Event session dropped");
+    *session = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn xe_get_session(
+    xe: tauri::State<'_, XeState>,
+) -> Result<Option<XeSessionInfo>, String> {
+    let session = xe.session.lock().await;
+    Ok(session.as_ref().map(|active| XeSessionInfo {
+        events_captured: xe.events_captured(),
+        ..active.info.clone()
+    }))
+}
+
+/// Captured events, oldest first; `after_sequence` returns only newer ones
+#[tauri::command]
+pub async fn xe_get_events(
+    after_sequence: Option<u64>,
+    xe: tauri::State<'_, XeState>,
+) -> Result<Vec<XeEvent>, String> {
+    let after = after_sequence.unwrap_or(0);
+    Ok(xe
+        .capture
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.sequence > after)
+        .cloned()
+        .collect())
+}
+
+#[tauri::command]
+pub async fn xe_clear_events(xe: tauri::State<'_, XeState>) -> Result<(), String> {
+    xe.capture.events.lock().unwrap().clear();
+    Ok(())
+}
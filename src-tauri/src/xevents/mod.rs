@@ -0,0 +1,550 @@
+pub mod commands;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::db::connection::TiberiusClient;
+use crate::db::identifiers::{quote_identifier, quote_literal};
+use crate::db::rows::get_string;
+use crate::db::{query_hash, splitter};
+
+// Live Extended Events capture, a lightweight stand-in for Profiler. One
+// session per app: completed RPCs and batches plus errors, filtered to our
+// own session or to one database, into a ring_buffer target that is polled
+// in the background. The app's own polling and control batches carry a
+// marker comment and are dropped from the capture.
+
+const SESSION_PREFIX: &str = "SqlPlanForDummies";
+const APP_MARKER: &str = "/* sqlplan:xevents */";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Events kept for xe_get_events
+const CAPTURED_EVENT_CAPACITY: usize = 10_000;
+/// Executed queries remembered for correlation
+const RECENT_QUERY_CAPACITY: usize = 200;
+
+const EVENTS: &[&str] = &[
+    "sqlserver.rpc_completed",
+    "sqlserver.sql_batch_completed",
+    "sqlserver.error_reported",
+];
+const ACTIONS: &[&str] = &[
+    "package0.event_sequence",
+    "sqlserver.session_id",
+    "sqlserver.database_name",
+    "sqlserver.client_app_name",
+    "sqlserver.username",
+    "sqlserver.sql_text",
+];
+
+/// Which activity the session captures
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum XeScope {
+    /// Only the app's own connection
+    CurrentSession,
+    /// Every session working in one database
+    Database { name: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum XeSessionStatus {
+    Created,
+    Running,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XeSessionInfo {
+    pub name: String,
+    pub scope: XeScope,
+    pub status: XeSessionStatus,
+    /// Azure SQL Database sessions are database-scoped
+    pub database_scoped: bool,
+    pub created_at: DateTime<Utc>,
+    pub events_captured: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct XeEvent {
+    /// package0.event_sequence, increasing within the session
+    pub sequence: u64,
+    pub name: String,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub session_id: Option<i64>,
+    pub database_name: Option<String>,
+    pub client_app_name: Option<String>,
+    pub username: Option<String>,
+    pub duration_us: Option<i64>,
+    pub cpu_time_us: Option<i64>,
+    pub logical_reads: Option<i64>,
+    pub writes: Option<i64>,
+    pub row_count: Option<i64>,
+    pub result: Option<String>,
+    /// Statement of an RPC, text of a batch, or the batch an error was raised in
+    pub sql_text: Option<String>,
+    pub error_number: Option<i64>,
+    pub severity: Option<i64>,
+    pub message: Option<String>,
+    /// Query run from the app that produced the event
+    pub query_id: Option<String>,
+}
+
+/// A query run from the app, by the hashes of its batch and statements
+#[derive(Debug, Clone)]
+struct ExecutedQuery {
+    id: String,
+    sql_hashes: Vec<String>,
+}
+
+/// Captured events and recent queries, shared with the polling task
+#[derive(Clone, Default)]
+struct Capture {
+    events: Arc<StdMutex<VecDeque<XeEvent>>>,
+    queries: Arc<StdMutex<VecDeque<ExecutedQuery>>>,
+    last_sequence: Arc<StdMutex<u64>>,
+}
+
+impl Capture {
+    /// Keep events newer than the last one seen, tagged with the query that
+    /// produced them; returns what was added
+    fn add(&self, events: Vec<XeEvent>) -> Vec<XeEvent> {
+        let mut last_sequence = self.last_sequence.lock().unwrap();
+        let queries = self.queries.lock().unwrap();
+        let mut added: Vec<XeEvent> = events
+            .into_iter()
+            .filter(|e| e.sequence > *last_sequence && !is_own_batch(e))
+            .map(|mut e| {
+                e.query_id = correlate(&e, &queries);
+                e
+            })
+            .collect();
+        added.sort_by_key(|e| e.sequence);
+        if let Some(last) = added.last() {
+            *last_sequence = last.sequence;
+        }
+
+        let mut captured = self.events.lock().unwrap();
+        for event in &added {
+            if captured.len() == CAPTURED_EVENT_CAPACITY {
+                captured.pop_front();
+            }
+            captured.push_back(event.clone());
+        }
+        added
+    }
+
+    fn clear(&self) {
+        self.events.lock().unwrap().clear();
+        *self.last_sequence.lock().unwrap() = 0;
+    }
+}
+
+struct ActiveSession {
+    info: XeSessionInfo,
+    /// Connection the session was created on, so it is stopped and dropped
+    /// on the same server even after switching connections
+    client: Arc<Mutex<TiberiusClient>>,
+    poller: Option<JoinHandle<()>>,
+}
+
+impl Drop for ActiveSession {
+    fn drop(&mut self) {
+        if let Some(task) = self.poller.take() {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct XeState {
+    session: Mutex<Option<ActiveSession>>,
+    capture: Capture,
+}
+
+impl XeState {
+    /// Remember a query run from the app so its events can be linked to it
+    pub fn record_query(&self, query_id: &str, sql: &str) {
+        let mut sql_hashes = vec![query_hash::sql_hash(sql)];
+        sql_hashes.extend(
+            splitter::split_statements(sql)
+                .iter()
+                .map(|span| query_hash::sql_hash(&sql[span.start..span.end])),
+        );
+        let mut queries = self.capture.queries.lock().unwrap();
+        if queries.len() == RECENT_QUERY_CAPACITY {
+            queries.pop_front();
+        }
+        queries.push_back(ExecutedQuery {
+            id: query_id.to_string(),
+            sql_hashes,
+        });
+    }
+
+    fn events_captured(&self) -> usize {
+        self.capture.events.lock().unwrap().len()
+    }
+}
+
+fn is_own_batch(event: &XeEvent) -> bool {
+    event
+        .sql_text
+        .as_deref()
+        .is_some_and(|t| t.contains(APP_MARKER))
+}
+
+/// Latest app query whose text matches the event's batch or statement
+fn correlate(event: &XeEvent, queries: &VecDeque<ExecutedQuery>) -> Option<String> {
+    let hash = query_hash::sql_hash(event.sql_text.as_deref()?);
+    queries
+        .iter()
+        .rev()
+        .find(|q| q.sql_hashes.contains(&hash))
+        .map(|q| q.id.clone())
+}
+
+fn session_name(spid: i64) -> String {
+    format!("{}_{}", SESSION_PREFIX, spid)
+}
+
+fn on_clause(database_scoped: bool) -> &'static str {
+    if database_scoped {
+        "ON DATABASE"
+    } else {
+        "ON SERVER"
+    }
+}
+
+fn create_session_sql(name: &str, scope: &XeScope, spid: i64, database_scoped: bool) -> String {
+    let filter = match scope {
+        XeScope::CurrentSession => format!("sqlserver.session_id = {}", spid),
+        XeScope::Database { name } => format!("sqlserver.database_name = {}", quote_literal(name)),
+    };
+    let actions = ACTIONS.join(", ");
+    let events: Vec<String> = EVENTS
+        .iter()
+        .map(|event| {
+            // Severity 10 and below are informational messages, not errors
+            let predicate = if *event == "sqlserver.error_reported" {
+                format!("{} AND severity >= 11", filter)
+            } else {
+                filter.clone()
+            };
+            format!(
+                "ADD EVENT {} (ACTION ({}) WHERE ({}))",
+                event, actions, predicate
+            )
+        })
+        .collect();
+    format!(
+        "{} CREATE EVENT SESSION {} {}\n{}\n\
+         ADD TARGET package0.ring_buffer (SET max_events_limit = 5000, max_memory = 4096)\n\
+         WITH (MAX_DISPATCH_LATENCY = 1 SECONDS, EVENT_RETENTION_MODE = ALLOW_SINGLE_EVENT_LOSS)",
+        APP_MARKER,
+        quote_identifier(name),
+        on_clause(database_scoped),
+        events.join(",\n")
+    )
+}
+
+fn drop_session_sql(name: &str, database_scoped: bool) -> String {
+    let catalog = if database_scoped {
+        "sys.database_event_sessions"
+    } else {
+        "sys.server_event_sessions"
+    };
+    format!(
+        "{} IF EXISTS (SELECT 1 FROM {} WHERE name = {}) DROP EVENT SESSION {} {}",
+        APP_MARKER,
+        catalog,
+        quote_literal(name),
+        quote_identifier(name),
+        on_clause(database_scoped)
+    )
+}
+
+fn set_state_sql(name: &str, database_scoped: bool, state: &str) -> String {
+    format!(
+        "{} ALTER EVENT SESSION {} {} STATE = {}",
+        APP_MARKER,
+        quote_identifier(name),
+        on_clause(database_scoped),
+        state
+    )
+}
+
+fn target_data_sql(name: &str, database_scoped: bool) -> String {
+    let (sessions, targets) = if database_scoped {
+        (
+            "sys.dm_xe_database_sessions",
+            "sys.dm_xe_database_session_targets",
+        )
+    } else {
+        ("sys.dm_xe_sessions", "sys.dm_xe_session_targets")
+    };
+    format!(
+        "{} SELECT CAST(t.target_data AS nvarchar(max)) FROM {} s \
+         JOIN {} t ON t.event_session_address = s.address \
+         WHERE s.name = {} AND t.target_name = N'ring_buffer'",
+        APP_MARKER,
+        sessions,
+        targets,
+        quote_literal(name)
+    )
+}
+
+/// Events in the XML of a ring_buffer target
+pub fn parse_ring_buffer(xml: &str) -> Result<Vec<XeEvent>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut events = Vec::new();
+    let mut current: Option<(XeEvent, HashMap<String, String>)> = None;
+    let mut field: Option<String> = None;
+    // Element whose text is being read; <text> wins over <value> for enums
+    let mut reading: Option<String> = None;
+    let mut has_text = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                let name = local_name(e);
+                match name.as_str() {
+                    "event" => {
+                        let event = XeEvent {
+                            name: attr(e, "name").unwrap_or_default(),
+                            timestamp: attr(e, "timestamp")
+                                .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                                .map(|t| t.with_timezone(&Utc)),
+                            ..Default::default()
+                        };
+                        current = Some((event, HashMap::new()));
+                    }
+                    "data" | "action" => {
+                        field = attr(e, "name");
+                        has_text = false;
+                    }
+                    "value" | "text" => reading = Some(name),
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(ref t)) => {
+                if let (Some((_, fields)), Some(field), Some(reading)) =
+                    (current.as_mut(), field.as_ref(), reading.as_deref())
+                {
+                    let text = t.unescape().map_err(|e| e.to_string())?.to_string();
+                    if reading == "text" {
+                        fields.insert(field.clone(), text);
+                        has_text = true;
+                    } else if !has_text {
+                        fields.insert(field.clone(), text);
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                match String::from_utf8_lossy(e.local_name().as_ref()).as_ref() {
+                    "event" => {
+                        if let Some((event, fields)) = current.take() {
+                            events.push(build_event(event, fields));
+                        }
+                    }
+                    "data" | "action" => field = None,
+                    "value" | "text" => reading = None,
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(format!(
+                    "Invalid ring buffer XML at position {}: {}",
+                    reader.buffer_position(),
+                    e
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+fn build_event(mut event: XeEvent, mut fields: HashMap<String, String>) -> XeEvent {
+    let number = |fields: &HashMap<String, String>, name: &str| -> Option<i64> {
+        fields.get(name).and_then(|v| v.parse().ok())
+    };
+    event.sequence = number(&fields, "event_sequence").unwrap_or_default() as u64;
+    event.session_id = number(&fields, "session_id");
+    event.duration_us = number(&fields, "duration");
+    event.cpu_time_us = number(&fields, "cpu_time");
+    event.logical_reads = number(&fields, "logical_reads");
+    event.writes = number(&fields, "writes");
+    event.row_count = number(&fields, "row_count");
+    event.error_number = number(&fields, "error_number");
+    event.severity = number(&fields, "severity");
+    event.database_name = fields.remove("database_name");
+    event.client_app_name = fields.remove("client_app_name");
+    event.username = fields.remove("username");
+    event.result = fields.remove("result");
+    event.message = fields.remove("message");
+    event.sql_text = fields
+        .remove("statement")
+        .or_else(|| fields.remove("batch_text"))
+        .or_else(|| fields.remove("sql_text"))
+        .filter(|t| !t.is_empty());
+    event
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+fn attr(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+}
+
+async fn run(client: &mut TiberiusClient, sql: &str) -> Result<(), String> {
+    client
+        .simple_query(sql)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_results()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Read the ring buffer and keep the events not seen yet
+async fn poll(
+    client: &Mutex<TiberiusClient>,
+    name: &str,
+    database_scoped: bool,
+    capture: &Capture,
+) -> Result<Vec<XeEvent>, String> {
+    let row = client
+        .lock()
+        .await
+        .simple_query(target_data_sql(name, database_scoped))
+        .await
+        .map_err(|e| e.to_string())?
+        .into_row()
+        .await
+        .map_err(|e| e.to_string())?;
+    let xml = row
+        .and_then(|r| get_string(&r, 0))
+        .ok_or_else(|| format!("Event session {} is not running", name))?;
+    Ok(capture.add(parse_ring_buffer(&xml)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RING_BUFFER: &str = r#"<RingBufferTarget truncated="0" eventCount="3">
+<event name="sql_batch_completed" package="sqlserver" timestamp="2024-05-01T10:00:00.123Z">
+  <data name="cpu_time"><type name="uint64" package="package0"/><value>1500</value></data>
+  <data name="duration"><type name="uint64" package="package0"/><value>2048</value></data>
+  <data name="logical_reads"><type name="uint64" package="package0"/><value>12</value></data>
+  <data name="result"><type name="rpc_result" package="sqlserver"/><value>0</value><text>OK</text></data>
+  <data name="batch_text"><type name="unicode_string" package="package0"/><value>SELECT * FROM t WHERE a &lt; 5</value></data>
+  <action name="event_sequence" package="package0"><type name="uint64" package="package0"/><value>7</value></action>
+  <action name="session_id" package="sqlserver"><type name="uint16" package="package0"/><value>55</value></action>
+  <action name="database_name" package="sqlserver"><type name="unicode_string" package="package0"/><value>Sales</value></action>
+</event>
+<event name="error_reported" package="sqlserver" timestamp="2024-05-01T10:00:01Z">
+  <data name="error_number"><value>208</value></data>
+  <data name="severity"><value>16</value></data>
+  <data name="message"><value>Invalid object name 'x'.</value></data>
+  <action name="event_sequence" package="package0"><value>8</value></action>
+  <action name="sql_text" package="sqlserver"><value>SELECT * FROM x</value></action>
+</event>
+<event name="sql_batch_completed" package="sqlserver" timestamp="2024-05-01T10:00:02Z">
+  <data name="batch_text"><value>/* sqlplan:xevents */ SELECT CAST(t.target_data AS nvarchar(max))</value></data>
+  <action name="event_sequence" package="package0"><value>9</value></action>
+</event>
+</RingBufferTarget>"#;
+
+    #[test]
+    fn test_parse_ring_buffer() {
+        let events = parse_ring_buffer(RING_BUFFER).unwrap();
+        assert_eq!(events.len(), 3);
+
+        let batch = &events[0];
+        assert_eq!(batch.name, "sql_batch_completed");
+        assert_eq!(batch.sequence, 7);
+        assert_eq!(batch.session_id, Some(55));
+        assert_eq!(batch.duration_us, Some(2048));
+        assert_eq!(batch.cpu_time_us, Some(1500));
+        assert_eq!(batch.logical_reads, Some(12));
+        assert_eq!(batch.result.as_deref(), Some("OK"));
+        assert_eq!(
+            batch.sql_text.as_deref(),
+            Some("SELECT * FROM t WHERE a < 5")
+        );
+        assert_eq!(batch.database_name.as_deref(), Some("Sales"));
+        assert_eq!(
+            batch.timestamp.unwrap().to_rfc3339(),
+            "2024-05-01T10:00:00.123+00:00"
+        );
+
+        let error = &events[1];
+        assert_eq!(error.error_number, Some(208));
+        assert_eq!(error.severity, Some(16));
+        assert_eq!(error.sql_text.as_deref(), Some("SELECT * FROM x"));
+    }
+
+    #[test]
+    fn test_capture_skips_seen_and_own_events_and_correlates() {
+        let state = XeState::default();
+        state.record_query("q1", "SELECT  *\nFROM t WHERE a < 5;\nSELECT * FROM x");
+
+        let added = state.capture.add(parse_ring_buffer(RING_BUFFER).unwrap());
+        assert_eq!(
+            added.iter().map(|e| e.sequence).collect::<Vec<_>>(),
+            vec![7, 8]
+        );
+        assert!(added.iter().all(|e| e.query_id.as_deref() == Some("q1")));
+
+        // The ring buffer returns every event again on the next poll
+        assert!(state
+            .capture
+            .add(parse_ring_buffer(RING_BUFFER).unwrap())
+            .is_empty());
+        assert_eq!(state.events_captured(), 2);
+    }
+
+    #[test]
+    fn test_create_session_sql() {
+        let sql = create_session_sql(
+            "SqlPlanForDummies_55",
+            &XeScope::Database {
+                name: "O'Brien".to_string(),
+            },
+            55,
+            false,
+        );
+        assert!(sql.starts_with(APP_MARKER));
+        assert!(sql.contains("CREATE EVENT SESSION [SqlPlanForDummies_55] ON SERVER"));
+        assert!(sql.contains(
+            "ADD EVENT sqlserver.error_reported (ACTION (package0.event_sequence, \
+             sqlserver.session_id, sqlserver.database_name, sqlserver.client_app_name, \
+             sqlserver.username, sqlserver.sql_text) \
+             WHERE (sqlserver.database_name = N'O''Brien' AND severity >= 11))"
+        ));
+
+        let sql = create_session_sql("s", &XeScope::CurrentSession, 55, true);
+        assert!(sql.contains("ON DATABASE"));
+        assert!(sql.contains("WHERE (sqlserver.session_id = 55)"));
+    }
+}
@@ -0,0 +1,34 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface AiProviderInfo {
+  endpoint: string;
+  model: string;
+  timeoutSecs: number;
+  hasApiKey: boolean;
+}
+
+export interface AiExplanation {
+  model: string;
+  suggestions: string;
+  /** The redacted plan summary that was sent */
+  sentSummary: string;
+}
+
+export function getAiProvider(): Promise<AiProviderInfo> {
+  return tauriInvoke<AiProviderInfo>('get_ai_provider');
+}
+
+/** apiKey: undefined keeps the stored key, an empty string removes it */
+export function updateAiProvider(
+  endpoint: string,
+  model: string,
+  timeoutSecs?: number,
+  apiKey?: string,
+): Promise<AiProviderInfo> {
+  return tauriInvoke<AiProviderInfo>('update_ai_provider', { endpoint, model, timeoutSecs, apiKey });
+}
+
+/** Fails unless AI explanations are enabled in the settings */
+export function explainPlanAi(planXml: string): Promise<AiExplanation> {
+  return tauriInvoke<AiExplanation>('explain_plan_ai', { planXml });
+}
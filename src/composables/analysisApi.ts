@@ -0,0 +1,71 @@
+import { tauriInvoke } from './tauriApi';
+
+export type IssueSeverity = 'info' | 'warning' | 'critical';
+
+export interface PlanIssue {
+  ruleId: string;
+  severity: IssueSeverity;
+  title: string;
+  description: string;
+  nodeId: number | null;
+  /** 0-100 */
+  impact: number;
+}
+
+export interface StatementAnalysis {
+  statementId: number;
+  /** Highest impact first */
+  issues: PlanIssue[];
+}
+
+export interface PlanAnalysis {
+  statements: StatementAnalysis[];
+}
+
+export interface AnalysisRuleInfo {
+  id: string;
+  title: string;
+  description: string;
+  severity: IssueSeverity;
+  enabled: boolean;
+  /** Defined by the user rather than built in */
+  custom: boolean;
+}
+
+/** Issues the enabled analysis rules find in each statement */
+export function analyzePlan(planXml: string): Promise<PlanAnalysis> {
+  return tauriInvoke<PlanAnalysis>('analyze_plan', { planXml });
+}
+
+export function listAnalysisRules(): Promise<AnalysisRuleInfo[]> {
+  return tauriInvoke<AnalysisRuleInfo[]>('list_analysis_rules');
+}
+
+/** Returns the updated rules list */
+export function setAnalysisRuleEnabled(ruleId: string, enabled: boolean): Promise<AnalysisRuleInfo[]> {
+  return tauriInvoke<AnalysisRuleInfo[]>('set_analysis_rule_enabled', { ruleId, enabled });
+}
+
+/** A user-defined rule; the expression is a condition over node properties */
+export interface CustomRuleDefinition {
+  id: string;
+  title: string;
+  /** e.g. "PhysicalOp == 'Clustered Index Scan' && EstimateRows > 100000" */
+  expression: string;
+  severity: IssueSeverity;
+  message?: string | null;
+}
+
+/** Add a custom rule or replace the one with the same id */
+export function saveCustomAnalysisRule(rule: CustomRuleDefinition): Promise<AnalysisRuleInfo[]> {
+  return tauriInvoke<AnalysisRuleInfo[]>('save_custom_analysis_rule', { rule });
+}
+
+export function deleteCustomAnalysisRule(ruleId: string): Promise<AnalysisRuleInfo[]> {
+  return tauriInvoke<AnalysisRuleInfo[]>('delete_custom_analysis_rule', { ruleId });
+}
+
+/** Rejects with the parse error, which names the column */
+export function validateRuleExpression(expression: string): Promise<void> {
+  return tauriInvoke<void>('validate_rule_expression', { expression });
+}
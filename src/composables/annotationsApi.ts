@@ -0,0 +1,32 @@
+import { tauriInvoke } from './tauriApi';
+
+/** A note on a plan, or on one of its operators when nodeId is set */
+export interface PlanAnnotation {
+  id: string;
+  planHash: string;
+  /** History entry the note was written on */
+  planId: string | null;
+  nodeId: number | null;
+  text: string;
+  createdAt: string;
+}
+
+export interface AddPlanAnnotationRequest {
+  planHash: string;
+  planId?: string | null;
+  nodeId?: number | null;
+  text: string;
+}
+
+export function addPlanAnnotation(request: AddPlanAnnotationRequest): Promise<PlanAnnotation> {
+  return tauriInvoke<PlanAnnotation>('add_plan_annotation', { request });
+}
+
+/** Notes on every capture of the plan shape, oldest first */
+export function getPlanAnnotations(planHash: string, nodeId?: number): Promise<PlanAnnotation[]> {
+  return tauriInvoke<PlanAnnotation[]>('get_plan_annotations', { planHash, nodeId: nodeId ?? null });
+}
+
+export function deletePlanAnnotation(id: string): Promise<void> {
+  return tauriInvoke<void>('delete_plan_annotation', { id });
+}
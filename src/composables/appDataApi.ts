@@ -0,0 +1,18 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface AppDataImportSummary {
+  connections: number;
+  groups: number;
+  queries: number;
+  plans: number;
+  snippets: number;
+}
+
+/** Encrypted archive text of all app data, ready to be saved to a file */
+export function exportAppData(passphrase: string): Promise<string> {
+  return tauriInvoke<string>('export_app_data', { passphrase });
+}
+
+export function importAppData(archive: string, passphrase: string): Promise<AppDataImportSummary> {
+  return tauriInvoke<AppDataImportSummary>('import_app_data', { archive, passphrase });
+}
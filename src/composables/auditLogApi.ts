@@ -0,0 +1,38 @@
+import { tauriInvoke } from './tauriApi';
+
+export type AuditAction = 'connect' | 'disconnect' | 'execute';
+
+/** An entry of the append-only audit log, hash-chained to the previous one */
+export interface AuditEntry {
+  sequence: number;
+  timestamp: string;
+  action: AuditAction;
+  server: string;
+  database: string;
+  login: string;
+  /** Account signed in to this machine */
+  osUser: string | null;
+  statement: string | null;
+  error: string | null;
+  durationMs: number | null;
+  previousHash: string;
+  hash: string;
+}
+
+export interface AuditLogPage {
+  /** Newest first */
+  entries: AuditEntry[];
+  total: number;
+  intact: boolean;
+  /** First entry that was altered, removed or inserted */
+  firstBrokenSequence: number | null;
+}
+
+export function getAuditLog(limit?: number): Promise<AuditLogPage> {
+  return tauriInvoke<AuditLogPage>('get_audit_log', { limit: limit ?? null });
+}
+
+/** The log as JSON lines, for checking the chain outside the app */
+export function exportAuditLog(): Promise<string> {
+  return tauriInvoke<string>('export_audit_log');
+}
@@ -0,0 +1,68 @@
+import { tauriInvoke } from './tauriApi';
+import type { PlanComparison } from './compatLevelsApi';
+import type { PlanRegression } from './useQueryHistory';
+
+export interface BaselineSummary {
+  id: string;
+  name: string;
+  startedAt: string;
+  endedAt: string | null;
+  queryCount: number;
+  distinctQueries: number;
+  /** The recording hit its size limit and later queries were left out */
+  truncated: boolean;
+}
+
+export type BaselineQueryStatus = 'unchanged' | 'improved' | 'regressed' | 'new' | 'missing';
+
+/** One query, matched by fingerprint, in the baseline and the new session */
+export interface QueryBaselineComparison {
+  fingerprint: string;
+  sql: string;
+  status: BaselineQueryStatus;
+  runsBefore: number;
+  runsAfter: number;
+  errorsBefore: number;
+  errorsAfter: number;
+  avgDurationBeforeMs: number | null;
+  avgDurationAfterMs: number | null;
+  durationChangePercent: number | null;
+  planChanged: boolean;
+  planDiff: PlanComparison | null;
+  planRegression: PlanRegression | null;
+}
+
+export interface BaselineComparison {
+  baseline: BaselineSummary;
+  session: BaselineSummary;
+  /** Regressions first */
+  queries: QueryBaselineComparison[];
+  regressed: number;
+  improved: number;
+}
+
+/** Every query run from now on is kept with its plan and timing */
+export function startBaselineRecording(name: string): Promise<BaselineSummary> {
+  return tauriInvoke<BaselineSummary>('start_baseline_recording', { name });
+}
+
+export function getBaselineRecording(): Promise<BaselineSummary | null> {
+  return tauriInvoke<BaselineSummary | null>('get_baseline_recording');
+}
+
+/** Ends the recording and saves it as a baseline */
+export function stopBaselineRecording(): Promise<BaselineSummary> {
+  return tauriInvoke<BaselineSummary>('stop_baseline_recording');
+}
+
+export function listBaselines(): Promise<BaselineSummary[]> {
+  return tauriInvoke<BaselineSummary[]>('list_baselines');
+}
+
+export function deleteBaseline(id: string): Promise<void> {
+  return tauriInvoke<void>('delete_baseline', { id });
+}
+
+export function compareBaselines(baselineId: string, sessionId: string): Promise<BaselineComparison> {
+  return tauriInvoke<BaselineComparison>('compare_baselines', { baselineId, sessionId });
+}
@@ -0,0 +1,39 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface BlockingNode {
+  sessionId: number;
+  /** 0 when not blocked; negative ids are orphaned or deferred transactions */
+  blockingSessionId: number;
+  status: string | null;
+  command: string | null;
+  waitType: string | null;
+  waitTimeMs: number | null;
+  waitResource: string | null;
+  elapsedMs: number | null;
+  databaseName: string | null;
+  loginName: string | null;
+  hostName: string | null;
+  programName: string | null;
+  openTransactionCount: number | null;
+  sqlText: string | null;
+  blocked: BlockingNode[];
+  totalBlocked: number;
+}
+
+export interface BlockingTree {
+  roots: BlockingNode[];
+  blockedSessions: number;
+  cycles: number[][];
+  ownSessionId: number | null;
+  capturedAt: string;
+}
+
+/** Blocked sessions under their lead blockers, the most disruptive first */
+export function getBlockingTree(): Promise<BlockingTree> {
+  return tauriInvoke<BlockingTree>('get_blocking_tree');
+}
+
+/** KILL another session, e.g. a lead blocker; needs the DBA profile */
+export function killSession(sessionId: number): Promise<void> {
+  return tauriInvoke<void>('kill_session', { sessionId });
+}
@@ -0,0 +1,16 @@
+import { tauriInvoke } from './tauriApi';
+
+/** What the clipboard holds. Plans have been parsed by the backend; the viewer opens planXml. */
+export type ClipboardContent =
+  | {
+      kind: 'plan';
+      planXml: string;
+      plan: { version: string | null; product: string | null; unavailableAttributes: string[] };
+    }
+  | { kind: 'deadlockGraph'; xml: string }
+  | { kind: 'sql'; sql: string };
+
+/** Read the system clipboard and detect a plan, a deadlock graph or a query */
+export function openPlanFromClipboard(): Promise<ClipboardContent> {
+  return tauriInvoke<ClipboardContent>('open_plan_from_clipboard');
+}
@@ -0,0 +1,55 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface StatementComparison {
+  statementId: number;
+  statementText: string;
+  costBefore: number;
+  costAfter: number;
+  /** null when the original cost is zero */
+  costChangePercent: number | null;
+  operatorsAdded: string[];
+  operatorsRemoved: string[];
+}
+
+export interface PlanComparison {
+  statements: StatementComparison[];
+}
+
+export interface CompatComparisonRequest {
+  sql: string;
+  /** e.g. [130, 150, 160] */
+  levels: number[];
+  includeLegacyCe?: boolean;
+  /** Switch the whole database with ALTER DATABASE instead of USE HINT */
+  allowAlterDatabase?: boolean;
+}
+
+export type CompatMethod = 'useHint' | 'alterDatabase';
+
+export interface CompatPlanVariant {
+  label: string;
+  compatibilityLevel: number;
+  legacyCe: boolean;
+  method: CompatMethod;
+  planXml: string | null;
+  estimatedCost: number | null;
+  /** Against the plan under the current level */
+  comparison: PlanComparison | null;
+  error: string | null;
+}
+
+export interface CompatComparisonResult {
+  currentLevel: number;
+  serverMaxLevel: number;
+  /** ALTER DATABASE was requested but the login lacks the permission */
+  fellBackToUseHint: boolean;
+  baselinePlanXml: string | null;
+  baselineCost: number | null;
+  variants: CompatPlanVariant[];
+}
+
+export function compareAcrossCompatLevels(
+  request: CompatComparisonRequest,
+): Promise<CompatComparisonResult> {
+  return tauriInvoke<CompatComparisonResult>('compare_across_compat_levels', { request });
+}
@@ -0,0 +1,36 @@
+import { tauriInvoke } from './tauriApi';
+import type { ConnectionInfo } from './useDbConnection';
+
+/** A connection read from a .regsrvr export or Azure Data Studio settings.json */
+export interface ImportedConnection {
+  name: string;
+  host: string;
+  port: number;
+  database: string;
+  username: string;
+  /** Server group path in the source, e.g. "Prod / EU" */
+  group: string | null;
+  /** What could not be carried over (Windows authentication, instance names) */
+  warnings: string[];
+  alreadySaved: boolean;
+}
+
+export interface ImportConnectionEntry {
+  name: string;
+  host: string;
+  port: number;
+  database: string;
+  username: string;
+  password: string;
+  group?: string | null;
+}
+
+/** Read a file for review; nothing is saved yet */
+export function importConnections(path: string): Promise<ImportedConnection[]> {
+  return tauriInvoke<ImportedConnection[]>('import_connections', { path });
+}
+
+/** Save the reviewed entries with the passwords the user entered */
+export function saveImportedConnections(connections: ImportConnectionEntry[]): Promise<ConnectionInfo[]> {
+  return tauriInvoke<ConnectionInfo[]>('save_imported_connections', { connections });
+}
@@ -0,0 +1,37 @@
+import { tauriInvoke } from './tauriApi';
+
+export type DependencyKind = 'expression' | 'foreignKey';
+
+export interface DependencyNode {
+  /** Lower-cased qualified name, referenced by edges */
+  id: string;
+  /** null for objects in other databases and unresolved references */
+  objectId: number | null;
+  database: string | null;
+  schema: string | null;
+  name: string;
+  /** e.g. USER_TABLE, VIEW, SQL_STORED_PROCEDURE */
+  typeDesc: string | null;
+  depth: number;
+}
+
+/** `from` depends on `to` */
+export interface DependencyEdge {
+  from: string;
+  to: string;
+  kind: DependencyKind;
+  /** Foreign key name */
+  label: string | null;
+}
+
+export interface DependencyGraph {
+  root: string;
+  nodes: DependencyNode[];
+  edges: DependencyEdge[];
+  truncated: boolean;
+}
+
+/** What references the object and what it references, up to 3 levels out */
+export function getObjectDependencies(object: string, depth = 1): Promise<DependencyGraph> {
+  return tauriInvoke<DependencyGraph>('get_object_dependencies', { object, depth });
+}
@@ -0,0 +1,56 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface ErrorExplanation {
+  number: number;
+  title: string;
+  explanation: string;
+}
+
+/** What SQL Server reported with an error */
+export interface EngineError {
+  number: number;
+  /** 11-16 are errors in the statement, 17 and above resource or server faults */
+  severity: number;
+  state: number;
+  /** 1-based line in the batch, or in the procedure when one is named */
+  line: number;
+  procedure: string | null;
+  server: string | null;
+  /** Plain-English explanation, for common error numbers */
+  explanation: ErrorExplanation | null;
+}
+
+/** Error returned by the database commands; branch on `kind` */
+export type AppError =
+  | { kind: 'connectionError'; message: string; engine: EngineError | null }
+  | {
+      kind: 'queryError';
+      /** SQL Server error number; null for driver errors */
+      code: number | null;
+      /** 1-based line of the batch the error was raised on */
+      line: number | null;
+      message: string;
+      engine: EngineError | null;
+    }
+  | { kind: 'permissionError'; message: string; engine: EngineError | null }
+  | { kind: 'cancelled'; message: string }
+  | { kind: 'timeout'; message: string; engine: EngineError | null }
+  | { kind: 'other'; message: string };
+
+export type AppErrorKind = AppError['kind'];
+
+export interface RecentError {
+  occurredAt: string;
+  /** Start of the batch that failed; null for login failures */
+  statementPreview: string | null;
+  error: AppError;
+}
+
+/** Errors raised by recent queries and logins, newest first */
+export function getRecentErrors(): Promise<RecentError[]> {
+  return tauriInvoke<RecentError[]>('get_recent_errors');
+}
+
+export function clearRecentErrors(): Promise<void> {
+  return tauriInvoke<void>('clear_recent_errors');
+}
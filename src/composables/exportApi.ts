@@ -0,0 +1,31 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { tauriInvoke } from './tauriApi';
+
+/** arrowIpc is the Arrow IPC file format (Feather v2) */
+export type ExportFormat = 'parquet' | 'arrowIpc';
+
+/** A query's first result set written to a file */
+export interface ResultExport {
+  path: string;
+  format: ExportFormat;
+  rows: number;
+  columns: number;
+  /** Result sets after the first, which are not exported */
+  skippedResultSets: number;
+  fileSizeBytes: number;
+  durationMs: number;
+}
+
+export interface ExportProgress {
+  path: string;
+  rowsWritten: number;
+}
+
+/** Run the query and stream its first result set to a Parquet or Arrow file */
+export function exportResults(sql: string, path: string, format: ExportFormat): Promise<ResultExport> {
+  return tauriInvoke<ResultExport>('export_results', { sql, path, format });
+}
+
+export function onExportProgress(handler: (progress: ExportProgress) => void): Promise<UnlistenFn> {
+  return listen<ExportProgress>('export-progress', (e) => handler(e.payload));
+}
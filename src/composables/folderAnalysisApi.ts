@@ -0,0 +1,59 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { tauriInvoke } from './tauriApi';
+import type { IssueSeverity, PlanIssue } from './analysisApi';
+
+/** One GO batch, compiled for its estimated plan */
+export interface FolderBatchResult {
+  /** 1-based line where the batch starts */
+  line: number;
+  preview: string;
+  estimatedCost: number | null;
+  issues: PlanIssue[];
+  error: string | null;
+}
+
+export interface FolderFileResult {
+  /** Relative to the analyzed folder */
+  path: string;
+  batches: FolderBatchResult[];
+  /** The file could not be read */
+  error: string | null;
+}
+
+export interface FolderIssueCount {
+  ruleId: string;
+  title: string;
+  severity: IssueSeverity;
+  count: number;
+  /** Files with at least one occurrence */
+  files: number;
+}
+
+export interface FolderAnalysisReport {
+  folder: string;
+  files: FolderFileResult[];
+  batchCount: number;
+  failedBatches: number;
+  criticalIssues: number;
+  warningIssues: number;
+  infoIssues: number;
+  totalEstimatedCost: number;
+  /** Most frequent first */
+  issueCounts: FolderIssueCount[];
+  /** More .sql files than the limit were found; the rest were skipped */
+  truncated: boolean;
+}
+
+export interface FolderAnalysisProgress {
+  path: string;
+  filesDone: number;
+  filesTotal: number;
+}
+
+export function analyzeFolder(path: string): Promise<FolderAnalysisReport> {
+  return tauriInvoke<FolderAnalysisReport>('analyze_folder', { path });
+}
+
+export function onFolderAnalysisProgress(handler: (progress: FolderAnalysisProgress) => void): Promise<UnlistenFn> {
+  return listen<FolderAnalysisProgress>('folder-analysis-progress', (e) => handler(e.payload));
+}
@@ -0,0 +1,12 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface FormatOptions {
+  keywordCase?: 'upper' | 'lower' | 'preserve';
+  commaPlacement?: 'trailing' | 'leading';
+  indentWidth?: number;
+}
+
+/** Without options, the style saved in settings is used */
+export function formatSql(sql: string, options?: FormatOptions): Promise<string> {
+  return tauriInvoke<string>('format_sql', { sql, options });
+}
@@ -0,0 +1,48 @@
+import { tauriInvoke } from './tauriApi';
+
+/** limited: fast, no page fullness; sampled: 1% of leaf pages; detailed: every page */
+export type FragmentationScanMode = 'limited' | 'sampled' | 'detailed';
+
+export type MaintenanceAction = 'none' | 'reorganize' | 'rebuild';
+
+export interface FragmentationRequest {
+  /** Every user table when omitted */
+  table?: string;
+  scanMode?: FragmentationScanMode;
+  /** Defaults to 5 */
+  reorganizePercent?: number;
+  /** Defaults to 30 */
+  rebuildPercent?: number;
+  /** Defaults to 1000 */
+  minPageCount?: number;
+}
+
+export interface IndexFragmentation {
+  schemaName: string;
+  tableName: string;
+  /** null for a heap */
+  indexName: string | null;
+  indexId: number;
+  indexType: string;
+  partitionNumber: number;
+  partitioned: boolean;
+  avgFragmentationPercent: number;
+  pageCount: number;
+  fragmentCount: number | null;
+  /** Not measured in limited mode */
+  avgPageSpaceUsedPercent: number | null;
+  action: MaintenanceAction;
+  script: string | null;
+}
+
+export interface FragmentationReport {
+  scanMode: FragmentationScanMode;
+  /** Most fragmented first */
+  indexes: IndexFragmentation[];
+  /** Every recommended statement, one per line */
+  script: string;
+}
+
+export function getIndexFragmentation(request: FragmentationRequest): Promise<FragmentationReport> {
+  return tauriInvoke<FragmentationReport>('get_index_fragmentation', { request });
+}
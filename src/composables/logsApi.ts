@@ -0,0 +1,16 @@
+import { tauriInvoke } from './tauriApi';
+
+export type LogLevel = 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+export interface LogEntry {
+  timestamp: string;
+  level: LogLevel;
+  target: string;
+  message: string;
+  fields: Record<string, string>;
+}
+
+/** Newest first; minLevel keeps that level and anything more severe */
+export function getRecentLogs(limit?: number, minLevel?: LogLevel): Promise<LogEntry[]> {
+  return tauriInvoke<LogEntry[]>('get_recent_logs', { limit, minLevel });
+}
@@ -0,0 +1,50 @@
+import { tauriInvoke } from './tauriApi';
+
+export type CompletionObjectKind = 'table' | 'view' | 'procedure' | 'function';
+
+export interface CompletionObject {
+  objectId: number;
+  /** Index into CompletionMetadata.schemas */
+  schema: number;
+  name: string;
+  kind: CompletionObjectKind;
+  /** [column name, type name] in column order */
+  columns: [string, string][];
+}
+
+export interface CompletionMetadata {
+  database: string;
+  schemas: string[];
+  objects: CompletionObject[];
+  /** Prefix index: positions in `objects` sorted by lowercased name */
+  nameIndex: number[];
+  loadedAt: string;
+}
+
+/** Objects whose name starts with `prefix`, ignoring case, by binary search on the name index */
+export function objectsWithPrefix(metadata: CompletionMetadata, prefix: string): CompletionObject[] {
+  const key = prefix.toLowerCase();
+  const nameAt = (i: number) => metadata.objects[metadata.nameIndex[i]].name.toLowerCase();
+  let low = 0;
+  let high = metadata.nameIndex.length;
+  while (low < high) {
+    const mid = (low + high) >> 1;
+    if (nameAt(mid) < key) low = mid + 1;
+    else high = mid;
+  }
+  const found: CompletionObject[] = [];
+  for (let i = low; i < metadata.nameIndex.length && nameAt(i).startsWith(key); i++) {
+    found.push(metadata.objects[metadata.nameIndex[i]]);
+  }
+  return found;
+}
+
+/** Served from the connection's metadata cache while it is fresh */
+export function getCompletionMetadata(refresh = false): Promise<CompletionMetadata> {
+  return tauriInvoke<CompletionMetadata>('get_completion_metadata', { refresh });
+}
+
+/** Drop cached metadata and load the current database again */
+export function refreshMetadata(allDatabases = false): Promise<CompletionMetadata> {
+  return tauriInvoke<CompletionMetadata>('refresh_metadata', { allDatabases });
+}
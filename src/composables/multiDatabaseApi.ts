@@ -0,0 +1,90 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { tauriInvoke } from './tauriApi';
+import type { PlanType, QueryResult } from './useQueryExecution';
+
+export interface MultiDatabaseRequest {
+  sql: string;
+  databases: string[];
+  /** Databases run at once, each on a session of its own; one at a time when omitted */
+  concurrency?: number | null;
+  planType: PlanType;
+}
+
+/** Outcome in one database; exactly one of result and error is set */
+export interface MultiDatabaseResult {
+  database: string;
+  result: QueryResult | null;
+  error: string | null;
+  durationMs: number;
+}
+
+export interface MultiDatabaseReport {
+  /** In the order the databases were requested */
+  results: MultiDatabaseResult[];
+  succeeded: number;
+  failed: number;
+  durationMs: number;
+}
+
+export interface MultiDatabaseProgress {
+  database: string;
+  error: string | null;
+  completed: number;
+  total: number;
+}
+
+/** Run one statement in each selected database of the current server */
+export function executeMulti(request: MultiDatabaseRequest): Promise<MultiDatabaseReport> {
+  return tauriInvoke<MultiDatabaseReport>('execute_multi', { request });
+}
+
+export function onMultiDatabaseProgress(handler: (progress: MultiDatabaseProgress) => void): Promise<UnlistenFn> {
+  return listen<MultiDatabaseProgress>('multi-database-progress', (e) => handler(e.payload));
+}
+
+/** Saved connections to run on: a connection group's members and any listed on their own */
+export interface ServerGroupRequest {
+  sql: string;
+  groupId?: string | null;
+  connectionIds?: string[];
+  /** Servers run at once; one at a time when omitted */
+  concurrency?: number | null;
+  planType: PlanType;
+}
+
+/** Outcome on one saved connection; exactly one of result and error is set */
+export interface ServerGroupResult {
+  connectionId: string;
+  connectionName: string;
+  /** host:port or pipe path */
+  server: string;
+  database: string;
+  result: QueryResult | null;
+  error: string | null;
+  durationMs: number;
+}
+
+export interface ServerGroupReport {
+  /** In saved order: group members first, then listed connections */
+  results: ServerGroupResult[];
+  succeeded: number;
+  failed: number;
+  durationMs: number;
+}
+
+export interface ServerGroupProgress {
+  connectionId: string;
+  connectionName: string;
+  error: string | null;
+  completed: number;
+  total: number;
+}
+
+/** Run one statement on each saved connection of a server group */
+export function executeOnServerGroup(request: ServerGroupRequest): Promise<ServerGroupReport> {
+  return tauriInvoke<ServerGroupReport>('execute_on_server_group', { request });
+}
+
+export function onServerGroupProgress(handler: (progress: ServerGroupProgress) => void): Promise<UnlistenFn> {
+  return listen<ServerGroupProgress>('server-group-progress', (e) => handler(e.payload));
+}
@@ -0,0 +1,34 @@
+import { tauriInvoke } from './tauriApi';
+
+export type OperatorCategory =
+  | 'dataAccess'
+  | 'join'
+  | 'ordering'
+  | 'aggregation'
+  | 'filtering'
+  | 'computation'
+  | 'parallelism'
+  | 'spool'
+  | 'setOperation'
+  | 'modification';
+
+/** Bundled description of a plan operator, available offline */
+export interface OperatorDoc {
+  name: string;
+  aliases: string[];
+  category: OperatorCategory;
+  /** One sentence for tooltips */
+  summary: string;
+  details: string;
+  commonCauses: string[];
+  fixes: string[];
+}
+
+/** Fails for operators without documentation */
+export function getOperatorDocs(opName: string): Promise<OperatorDoc> {
+  return tauriInvoke<OperatorDoc>('get_operator_docs', { opName });
+}
+
+export function listOperatorDocs(): Promise<OperatorDoc[]> {
+  return tauriInvoke<OperatorDoc[]>('list_operator_docs');
+}
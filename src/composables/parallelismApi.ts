@@ -0,0 +1,37 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface ParallelismSettings {
+  costThreshold: number;
+  /** 0 lets a query use every scheduler */
+  serverMaxDop: number;
+  /** Overrides the server MAXDOP when not 0 */
+  databaseMaxDop: number | null;
+  cpuCount: number | null;
+}
+
+export interface ThreadStat {
+  branches: number;
+  usedThreads: number | null;
+  reservedThreads: number | null;
+}
+
+export interface StatementParallelism {
+  statementId: number;
+  wentParallel: boolean;
+  degreeOfParallelism: number | null;
+  threadStat: ThreadStat | null;
+  estimatedCost: number;
+  reason: string;
+  notes: string[];
+}
+
+export interface ParallelismReport {
+  settings: ParallelismSettings;
+  effectiveMaxDop: number | null;
+  statements: StatementParallelism[];
+}
+
+/** Needs a connection: the plan is explained against the server's settings */
+export function analyzeParallelism(planXml: string): Promise<ParallelismReport> {
+  return tauriInvoke<ParallelismReport>('analyze_parallelism', { planXml });
+}
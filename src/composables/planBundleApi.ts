@@ -0,0 +1,38 @@
+import { tauriInvoke } from './tauriApi';
+import type { ExecutionContext } from './useQueryHistory';
+import type { PlanAnnotation } from './annotationsApi';
+import type { PlanAnalysis } from './analysisApi';
+import type { RedactOptions } from './redactApi';
+
+/** Plan to export: a history entry, or a plan that is only on screen */
+export interface ExportPlanBundleRequest {
+  planId?: string | null;
+  planXml?: string | null;
+  sql?: string | null;
+  /** Replace literals (and names) in the plan, query and findings */
+  redact?: RedactOptions | null;
+}
+
+/** Everything needed to review a plan elsewhere */
+export interface PlanBundle {
+  createdAt: string;
+  appVersion: string;
+  planHash: string;
+  sourcePlanId: string | null;
+  planXml: string;
+  sql: string | null;
+  /** Findings of the rules enabled on the exporting machine */
+  analysis: PlanAnalysis | null;
+  context: ExecutionContext | null;
+  annotations: PlanAnnotation[];
+}
+
+/** The bundle zip, base64-encoded */
+export function exportPlanBundle(request: ExportPlanBundleRequest): Promise<string> {
+  return tauriInvoke<string>('export_plan_bundle', { request });
+}
+
+/** Open a bundle (base64 zip); its annotations are added to this machine's */
+export function importPlanBundle(data: string): Promise<PlanBundle> {
+  return tauriInvoke<PlanBundle>('import_plan_bundle', { data });
+}
@@ -0,0 +1,30 @@
+import { tauriInvoke } from './tauriApi';
+import type { PlanComparison } from './compatLevelsApi';
+
+/** A plan-affecting SET option where the session and SSMS disagree */
+export interface SetOptionDifference {
+  name: string;
+  sessionValue: boolean;
+  ssmsValue: boolean;
+}
+
+export type MismatchVerdict = 'plansDiffer' | 'separateCacheEntries' | 'notSetOptions';
+
+export interface PlanMismatchDiagnosis {
+  /** SET options ON in the app's session */
+  sessionOptions: string[];
+  differences: SetOptionDifference[];
+  /** QueryPlanHash of each statement */
+  sessionPlanHashes: string[];
+  ssmsPlanHashes: string[];
+  sessionPlanXml: string | null;
+  ssmsPlanXml: string | null;
+  comparison: PlanComparison | null;
+  verdict: MismatchVerdict;
+  explanation: string;
+}
+
+/** Compile the query under the session's SET options and under the SSMS defaults */
+export function diagnosePlanMismatch(sql: string): Promise<PlanMismatchDiagnosis> {
+  return tauriInvoke<PlanMismatchDiagnosis>('diagnose_plan_mismatch', { sql });
+}
@@ -0,0 +1,21 @@
+import { tauriInvoke } from './tauriApi';
+
+/** What the app lets the user do; enforced by the backend commands */
+export type Profile = 'developer' | 'dba' | 'readOnly';
+
+/** Name shown when confirming a switch to a profile */
+export const PROFILE_LABELS: Record<Profile, string> = {
+  developer: 'Developer',
+  dba: 'DBA',
+  readOnly: 'ReadOnly',
+};
+
+/** Start a profile change; returns the one-time token that confirms it */
+export function requestProfileChange(profile: Profile): Promise<string> {
+  return tauriInvoke<string>('request_profile_change', { profile });
+}
+
+/** Returns the saved settings; fails unless token came from requestProfileChange for this profile */
+export function changeProfile(profile: Profile, token: string): Promise<Record<string, unknown>> {
+  return tauriInvoke<Record<string, unknown>>('change_profile', { profile, token });
+}
@@ -0,0 +1,6 @@
+import { tauriInvoke } from './tauriApi';
+
+/** Force a Query Store plan for a query, or unforce it; needs the DBA profile */
+export function forcePlan(queryId: number, planId: number, unforce = false): Promise<void> {
+  return tauriInvoke<void>('force_plan', { queryId, planId, unforce });
+}
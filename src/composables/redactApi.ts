@@ -0,0 +1,11 @@
+import { tauriInvoke } from './tauriApi';
+
+/** Literal values are always replaced; object names only on request */
+export interface RedactOptions {
+  objectNames?: boolean;
+}
+
+/** The query with literals (and names) replaced by placeholders like ?1 and Table1 */
+export function redactQuery(sql: string, options?: RedactOptions): Promise<string> {
+  return tauriInvoke<string>('redact_query', { sql, options: options ?? null });
+}
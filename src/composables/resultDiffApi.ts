@@ -0,0 +1,54 @@
+import { tauriInvoke } from './tauriApi';
+
+/** A query run twice, on two connections or the same one, to compare the results */
+export interface ResultDiffRequest {
+  sql: string;
+  /** Query for the right side; sql again when omitted */
+  rightSql?: string | null;
+  /** Saved connection of each side; the active connection when omitted */
+  leftConnectionId?: string | null;
+  rightConnectionId?: string | null;
+  /** Columns that identify a row; whole rows are compared when empty */
+  keyColumns?: string[];
+}
+
+export interface ChangedRow {
+  left: unknown[];
+  right: unknown[];
+  /** Indexes into columns of the values that differ */
+  changedColumns: number[];
+}
+
+/** Listed rows hold the values of columns; the lists are capped, the counts are not */
+export interface ResultDiff {
+  columns: string[];
+  keyColumns: string[];
+  leftOnlyColumns: string[];
+  rightOnlyColumns: string[];
+  leftRowCount: number;
+  rightRowCount: number;
+  unchanged: number;
+  changed: number;
+  removed: number;
+  added: number;
+  duplicateKeys: number;
+  changedRows: ChangedRow[];
+  removedRows: unknown[][];
+  addedRows: unknown[][];
+}
+
+export interface ResultDiffSide {
+  source: string;
+  database: string;
+  durationMs: number;
+}
+
+export interface ResultDiffReport {
+  left: ResultDiffSide;
+  right: ResultDiffSide;
+  diff: ResultDiff;
+}
+
+export function diffResults(request: ResultDiffRequest): Promise<ResultDiffReport> {
+  return tauriInvoke<ResultDiffReport>('diff_results', { request });
+}
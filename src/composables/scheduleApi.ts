@@ -0,0 +1,54 @@
+import { tauriInvoke } from './tauriApi';
+import type { PlanType } from './useQueryExecution';
+import type { PlanRegression } from './useQueryHistory';
+
+/** A query run in the background on a saved connection to watch its plan */
+export interface ScheduledQuery {
+  id: string;
+  name: string;
+  sql: string;
+  connectionId: string;
+  planType: PlanType;
+  intervalMinutes: number;
+  enabled: boolean;
+  createdAt: string;
+  lastRunAt: string | null;
+  lastError: string | null;
+}
+
+/** Omit id to create a schedule; runs are at least 5 minutes apart */
+export interface SaveScheduledQueryRequest {
+  id?: string | null;
+  name: string;
+  sql: string;
+  connectionId: string;
+  planType: PlanType;
+  intervalMinutes: number;
+  enabled: boolean;
+}
+
+/** Payload of the "scheduled-query-run" event */
+export interface ScheduledRunEvent {
+  scheduledQueryId: string;
+  queryId: string;
+  planId: string | null;
+  error: string | null;
+  regression: PlanRegression | null;
+}
+
+export function listScheduledQueries(): Promise<ScheduledQuery[]> {
+  return tauriInvoke<ScheduledQuery[]>('list_scheduled_queries');
+}
+
+export function saveScheduledQuery(request: SaveScheduledQueryRequest): Promise<ScheduledQuery> {
+  return tauriInvoke<ScheduledQuery>('save_scheduled_query', { request });
+}
+
+export function deleteScheduledQuery(id: string): Promise<void> {
+  return tauriInvoke<void>('delete_scheduled_query', { id });
+}
+
+/** Run a schedule now, outside its interval */
+export function runScheduledQuery(id: string): Promise<ScheduledRunEvent> {
+  return tauriInvoke<ScheduledRunEvent>('run_scheduled_query', { id });
+}
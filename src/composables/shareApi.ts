@@ -0,0 +1,30 @@
+import { tauriInvoke } from './tauriApi';
+import type { ExportPlanBundleRequest } from './planBundleApi';
+
+/** A plan served read-only to browsers until it expires */
+export interface ShareLink {
+  token: string;
+  url: string;
+  planHash: string;
+  createdAt: string;
+  expiresAt: string;
+}
+
+/** Needs plan sharing enabled in settings; links work for a day */
+export function sharePlan(request: ExportPlanBundleRequest): Promise<ShareLink> {
+  return tauriInvoke<ShareLink>('share_plan', { request });
+}
+
+/** Share a bundle file (base64 zip), e.g. one received from a colleague */
+export function sharePlanBundle(data: string): Promise<ShareLink> {
+  return tauriInvoke<ShareLink>('share_plan_bundle', { data });
+}
+
+export function listSharedPlans(): Promise<ShareLink[]> {
+  return tauriInvoke<ShareLink[]>('list_shared_plans');
+}
+
+/** Stop one link, or every link and the listener when token is omitted */
+export function stopSharing(token?: string): Promise<void> {
+  return tauriInvoke<void>('stop_sharing', { token: token ?? null });
+}
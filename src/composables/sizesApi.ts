@@ -0,0 +1,39 @@
+import { tauriInvoke } from './tauriApi';
+
+/** Space used by one table, split the way sp_spaceused reports it */
+export interface TableSize {
+  schemaName: string;
+  tableName: string;
+  rows: number;
+  reservedKb: number;
+  usedKb: number;
+  /** Heap or clustered index pages, LOB and row-overflow included */
+  dataKb: number;
+  indexKb: number;
+  unusedKb: number;
+}
+
+export interface IndexSize {
+  schemaName: string;
+  tableName: string;
+  /** null for a heap */
+  indexName: string | null;
+  indexId: number;
+  indexType: string;
+  partitions: number;
+  rows: number;
+  reservedKb: number;
+  usedKb: number;
+  inRowKb: number;
+  lobKb: number;
+  rowOverflowKb: number;
+}
+
+/** Every user table, largest first, or just `table` */
+export function getTableSizes(table?: string): Promise<TableSize[]> {
+  return tauriInvoke<TableSize[]>('get_table_sizes', { table: table ?? null });
+}
+
+export function getIndexSizes(table?: string): Promise<IndexSize[]> {
+  return tauriInvoke<IndexSize[]>('get_index_sizes', { table: table ?? null });
+}
@@ -0,0 +1,27 @@
+import { tauriInvoke } from './tauriApi';
+
+/** numeric keys are numbers, temporal ones ISO-8601 strings */
+export type HistogramKeyKind = 'numeric' | 'temporal' | 'text';
+
+export interface TypedHistogramStep {
+  rangeHiKey: number | string | null;
+  /** Rows strictly between the previous step and this one */
+  rangeRows: number;
+  /** Rows equal to rangeHiKey */
+  equalRows: number;
+  distinctRangeRows: number;
+  averageRangeRows: number;
+}
+
+export interface StatsHistogram {
+  statsName: string;
+  /** Leading key column, the one the histogram covers */
+  column: string;
+  keyType: string;
+  keyKind: HistogramKeyKind;
+  steps: TypedHistogramStep[];
+}
+
+export function getStatsHistogram(table: string, statsName: string): Promise<StatsHistogram> {
+  return tauriInvoke<StatsHistogram>('get_stats_histogram', { table, statsName });
+}
@@ -0,0 +1,38 @@
+import { tauriInvoke } from './tauriApi';
+import type { ResultSet } from './useQueryExecution';
+
+/** Keys of the first and last row of a page, for keyset paging */
+export interface PageCursor {
+  page: number;
+  firstKey: unknown[];
+  lastKey: unknown[];
+}
+
+export type PagingStrategy = 'keyset' | 'offset';
+
+export interface BrowseTableRequest {
+  table: string;
+  pageSize: number;
+  /** Zero-based */
+  page: number;
+  orderColumn?: string | null;
+  descending?: boolean;
+  /** Cursor of the page on screen, so the next or previous page seeks from it */
+  cursor?: PageCursor | null;
+}
+
+export interface TablePage {
+  table: string;
+  page: number;
+  pageSize: number;
+  resultSet: ResultSet;
+  hasPrevious: boolean;
+  hasNext: boolean;
+  strategy: PagingStrategy;
+  keyColumns: string[];
+  cursor: PageCursor | null;
+}
+
+export function browseTable(request: BrowseTableRequest): Promise<TablePage> {
+  return tauriInvoke<TablePage>('browse_table', { request });
+}
@@ -3,6 +3,38 @@
  * Falls back to an error when running outside Tauri (e.g. npm run dev in browser).
  */
 import { invoke } from '@tauri-apps/api/core';
+import type { AppError } from './errorsApi';
+
+/**
+ * A command failed with a typed AppError. `String(error)` is still the
+ * message, so callers that only show it need no changes.
+ */
+export class CommandError extends Error {
+  readonly error: AppError;
+
+  constructor(error: AppError) {
+    super(error.message);
+    this.name = 'CommandError';
+    this.error = error;
+  }
+
+  get kind(): AppError['kind'] {
+    return this.error.kind;
+  }
+
+  override toString(): string {
+    return this.message;
+  }
+}
+
+function isAppError(value: unknown): value is AppError {
+  return (
+    typeof value === 'object' &&
+    value !== null &&
+    typeof (value as AppError).kind === 'string' &&
+    typeof (value as AppError).message === 'string'
+  );
+}
 
 function isTauri(): boolean {
   return typeof window !== 'undefined' && '__TAURI_INTERNALS__' in window;
@@ -12,5 +44,10 @@ export async function tauriInvoke<T>(cmd: string, args?: Record<string, unknown>
   if (!isTauri()) {
     throw new Error('Tauri backend not available. Run with "npm run tauri dev" to use database features.');
   }
-  return invoke<T>(cmd, args);
+  try {
+    return await invoke<T>(cmd, args);
+  } catch (e) {
+    // Database commands reject with an AppError, the others with text
+    throw isAppError(e) ? new CommandError(e) : e;
+  }
 }
@@ -0,0 +1,62 @@
+import { tauriInvoke } from './tauriApi';
+
+/** A plan-sharing site, e.g. a PasteThePlan-compatible API */
+export interface UploadTargetInfo {
+  id: string;
+  name: string;
+  endpoint: string;
+  /** JSON field of the request that carries the plan XML */
+  planField: string;
+  /** JSON pointer to the share URL in the response */
+  urlPointer: string;
+  hasApiKey: boolean;
+}
+
+export interface SaveUploadTargetRequest {
+  /** Omit to create a new target */
+  id?: string;
+  name: string;
+  endpoint: string;
+  planField?: string;
+  urlPointer?: string;
+  /** Omit to keep the stored key, empty string to remove it */
+  apiKey?: string;
+}
+
+export interface UploadResult {
+  url: string;
+  targetName: string;
+  redacted: boolean;
+}
+
+export function listUploadTargets(): Promise<UploadTargetInfo[]> {
+  return tauriInvoke<UploadTargetInfo[]>('list_upload_targets');
+}
+
+export function saveUploadTarget(request: SaveUploadTargetRequest): Promise<UploadTargetInfo> {
+  return tauriInvoke<UploadTargetInfo>('save_upload_target', {
+    id: request.id ?? null,
+    name: request.name,
+    endpoint: request.endpoint,
+    planField: request.planField ?? null,
+    urlPointer: request.urlPointer ?? null,
+    apiKey: request.apiKey ?? null,
+  });
+}
+
+export function deleteUploadTarget(id: string): Promise<void> {
+  return tauriInvoke<void>('delete_upload_target', { id });
+}
+
+/**
+ * Literal values are replaced before upload unless redact is false;
+ * redactObjectNames replaces database, table and column names too
+ */
+export function uploadPlan(
+  targetId: string,
+  planXml: string,
+  redact = true,
+  redactObjectNames = false,
+): Promise<UploadResult> {
+  return tauriInvoke<UploadResult>('upload_plan', { targetId, planXml, redact, redactObjectNames });
+}
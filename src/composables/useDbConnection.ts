@@ -1,7 +1,19 @@
 import { reactive } from 'vue';
 import { tauriInvoke } from './tauriApi';
 
-interface ConnectionInfo {
+/** Named pipes are Windows-only; pipePath looks like \\.\pipe\sql\query */
+export type ConnectionTransport = { kind: 'tcp' } | { kind: 'namedPipe'; pipePath: string };
+
+/**
+ * Kerberos logs in with the ticket from kinit (or Windows' own login) instead
+ * of a SQL login; spn overrides MSSQLSvc/host:port, realm is that SPN's realm.
+ * Linux and macOS builds need the `kerberos` feature.
+ */
+export type ConnectionAuth =
+  | { kind: 'sqlLogin' }
+  | { kind: 'kerberos'; spn?: string | null; realm?: string | null };
+
+export interface ConnectionInfo {
   id: string;
   name: string;
   host: string;
@@ -10,6 +22,48 @@ interface ConnectionInfo {
   username: string;
   lastUsed: string | null;
   createdAt: string;
+  groupId?: string | null;
+  tags?: string[];
+  readOnly?: boolean;
+  transport?: ConnectionTransport;
+  auth?: ConnectionAuth;
+  /** SHA-256 of the server certificate, pinned on first connect */
+  certFingerprint?: string | null;
+}
+
+export type ConnectionStep = 'tcp' | 'tls' | 'login' | 'databaseAccess';
+
+export interface ConnectionTestReport {
+  success: boolean;
+  failedStep: ConnectionStep | null;
+  error: string | null;
+  serverVersion: string | null;
+  edition: string | null;
+  currentDatabase: string | null;
+  loginName: string | null;
+  encrypted: boolean | null;
+  netTransport: string | null;
+  authScheme: string | null;
+  connectMs: number | null;
+  latencyMs: number | null;
+  permissions: PermissionCheck[];
+}
+
+export interface PermissionCheck {
+  permission: string;
+  scope: 'SERVER' | 'DATABASE';
+  granted: boolean;
+  features: string[];
+}
+
+export interface PermissionReport {
+  permissions: PermissionCheck[];
+  unavailableFeatures: string[];
+}
+
+export interface StoreLockStatus {
+  passphraseEnabled: boolean;
+  unlocked: boolean;
 }
 
 interface ConnectionState {
@@ -18,6 +72,9 @@ interface ConnectionState {
   connections: ConnectionInfo[];
   loading: boolean;
   error: string | null;
+  lastTestReport: ConnectionTestReport | null;
+  permissionReport: PermissionReport | null;
+  lockStatus: StoreLockStatus | null;
 }
 
 const state = reactive<ConnectionState>({
@@ -26,6 +83,9 @@ const state = reactive<ConnectionState>({
   connections: [],
   loading: false,
   error: null,
+  lastTestReport: null,
+  permissionReport: null,
+  lockStatus: null,
 });
 
 export const useDbConnection = () => {
@@ -35,13 +95,15 @@ export const useDbConnection = () => {
     database: string,
     username: string,
     password: string,
-    connectionName?: string
+    connectionName?: string,
+    transport?: ConnectionTransport,
+    auth?: ConnectionAuth
   ) => {
     state.loading = true;
     state.error = null;
     try {
       const msg = await tauriInvoke<string>('connect_db', {
-        request: { host, port, database, username, password },
+        request: { host, port, database, username, password, transport, auth },
       });
       state.connected = true;
       state.activeConnection = {
@@ -53,6 +115,8 @@ export const useDbConnection = () => {
         username,
         lastUsed: new Date().toISOString(),
         createdAt: new Date().toISOString(),
+        transport,
+        auth,
       };
       return msg;
     } catch (e) {
@@ -90,6 +154,7 @@ export const useDbConnection = () => {
     } finally {
       state.connected = false;
       state.activeConnection = null;
+      state.permissionReport = null;
     }
   };
 
@@ -98,19 +163,59 @@ export const useDbConnection = () => {
     port: number,
     database: string,
     username: string,
-    password: string
+    password: string,
+    transport?: ConnectionTransport,
+    auth?: ConnectionAuth
   ): Promise<boolean> => {
     try {
-      await tauriInvoke('test_connection', {
-        request: { host, port, database, username, password },
+      const report = await tauriInvoke<ConnectionTestReport>('test_connection', {
+        request: { host, port, database, username, password, transport, auth },
       });
-      return true;
+      state.lastTestReport = report;
+      if (!report.success) {
+        state.error = `${report.failedStep ?? 'connection'} step failed: ${report.error ?? 'unknown error'}`;
+      }
+      return report.success;
     } catch (e) {
+      state.lastTestReport = null;
       state.error = String(e);
       return false;
     }
   };
 
+  const checkPermissions = async (): Promise<PermissionReport | null> => {
+    try {
+      state.permissionReport = await tauriInvoke<PermissionReport>('check_permissions');
+      return state.permissionReport;
+    } catch (e) {
+      state.error = String(e);
+      return null;
+    }
+  };
+
+  const loadLockStatus = async () => {
+    try {
+      state.lockStatus = await tauriInvoke<StoreLockStatus>('get_store_lock_status');
+    } catch (e) {
+      state.error = String(e);
+    }
+  };
+
+  const unlockStore = async (passphrase: string) => {
+    await tauriInvoke('unlock_store', { passphrase });
+    await loadLockStatus();
+  };
+
+  const lockStore = async () => {
+    await tauriInvoke('lock_store');
+    await loadLockStatus();
+  };
+
+  const setMasterPassphrase = async (passphrase: string, enabled: boolean) => {
+    await tauriInvoke(enabled ? 'enable_master_passphrase' : 'disable_master_passphrase', { passphrase });
+    await loadLockStatus();
+  };
+
   const loadConnections = async () => {
     try {
       const connections = await tauriInvoke<ConnectionInfo[]>('get_connections');
@@ -126,11 +231,13 @@ export const useDbConnection = () => {
     port: number,
     database: string,
     username: string,
-    password: string
+    password: string,
+    transport?: ConnectionTransport,
+    auth?: ConnectionAuth
   ) => {
     try {
       const saved = await tauriInvoke<ConnectionInfo>('save_connection', {
-        request: { name, host, port, database, username, password },
+        request: { name, host, port, database, username, password, transport, auth },
       });
       state.connections.push(saved);
       return saved;
@@ -140,6 +247,60 @@ export const useDbConnection = () => {
     }
   };
 
+  const updateConnection = async (
+    id: string,
+    request: {
+      name: string;
+      host: string;
+      port: number;
+      database: string;
+      username: string;
+      // Omit to keep the saved password
+      password?: string;
+      groupId?: string | null;
+      tags?: string[];
+      readOnly?: boolean;
+      transport?: ConnectionTransport;
+      auth?: ConnectionAuth;
+    }
+  ) => {
+    try {
+      const updated = await tauriInvoke<ConnectionInfo>('update_connection', { id, request });
+      const index = state.connections.findIndex((c) => c.id === id);
+      if (index >= 0) state.connections[index] = updated;
+      return updated;
+    } catch (e) {
+      state.error = String(e);
+      throw e;
+    }
+  };
+
+  const duplicateConnection = async (id: string, name?: string, database?: string) => {
+    try {
+      const copy = await tauriInvoke<ConnectionInfo>('duplicate_connection', { id, name, database });
+      state.connections.push(copy);
+      return copy;
+    } catch (e) {
+      state.error = String(e);
+      throw e;
+    }
+  };
+
+  /** Trust the server's current certificate after connect_saved reported it changed */
+  const acceptServerCertificate = async (id: string) => {
+    try {
+      const fingerprint = await tauriInvoke<string>('accept_server_certificate', { connectionId: id });
+      const saved = state.connections.find((c) => c.id === id);
+      if (saved) {
+        saved.certFingerprint = fingerprint;
+      }
+      return fingerprint;
+    } catch (e) {
+      state.error = String(e);
+      throw e;
+    }
+  };
+
   const deleteConnection = async (id: string) => {
     try {
       await tauriInvoke('delete_connection', { id });
@@ -156,8 +317,16 @@ export const useDbConnection = () => {
     connectSaved,
     disconnect,
     testConnection,
+    checkPermissions,
+    loadLockStatus,
+    unlockStore,
+    lockStore,
+    setMasterPassphrase,
     loadConnections,
     saveConnection,
+    updateConnection,
+    duplicateConnection,
+    acceptServerCertificate,
     deleteConnection,
   };
 };
@@ -1,15 +1,213 @@
 import { reactive } from 'vue';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { tauriInvoke } from './tauriApi';
 
 export type PlanType = 'None' | 'Estimated' | 'Actual';
 
-export interface QueryResult {
-  columns: string[];
+/** Length, precision and scale are null when the type does not fix them */
+export interface ColumnInfo {
+  name: string;
+  typeName: string;
+  nullable: boolean | null;
+  maxLength: number | null;
+  precision: number | null;
+  scale: number | null;
+}
+
+/** Opt-in handling of lock contention for one execution */
+export interface RetryPolicy {
+  /** Re-runs after being chosen as a deadlock victim (error 1205) */
+  deadlockRetries?: number;
+  /** Wait before the first retry; doubled for each further one */
+  backoffMs?: number;
+  /** SET LOCK_TIMEOUT for the batch */
+  lockTimeoutMs?: number | null;
+}
+
+export interface ResultSet {
+  columns: ColumnInfo[];
   rows: any[][];
+  rowsAffected: number;
+  /** Set when a copy is kept for sortResults / filterResults */
+  resultId?: string | null;
+}
+
+export interface QueryResult {
+  resultSets: ResultSet[];
   messages: string[];
   planXml: string | null;
   durationMs: number;
+  /** Rows changed by INSERT/UPDATE/DELETE/MERGE */
   rowsAffected: number;
+  timings: StatementTiming[];
+  /** Session waits while an actual plan query ran */
+  waitStats: WaitStatsDelta | null;
+}
+
+export interface WaitStatDelta {
+  waitType: string;
+  waitingTasks: number;
+  waitTimeMs: number;
+  signalWaitTimeMs: number;
+  /** Share of the total wait time */
+  percent: number;
+}
+
+export interface WaitStatsDelta {
+  capturedAt: string;
+  baselineAt: string | null;
+  intervalMs: number | null;
+  waits: WaitStatDelta[];
+}
+
+export interface StatementTiming {
+  statementPreview: string;
+  elapsedMs: number;
+}
+
+export interface RowCountEstimate {
+  table: string;
+  approximateRows: number;
+  source: string;
+  /** The query has a WHERE clause, so the count is an upper bound */
+  filtered: boolean;
+  warning: string | null;
+}
+
+/** Approximate rows a table or single-table SELECT will return, from metadata */
+export function estimateRowcount(sql: string): Promise<RowCountEstimate> {
+  return tauriInvoke<RowCountEstimate>('estimate_rowcount', { sql });
+}
+
+/** A statement of a script; offsets are in UTF-16 code units, as in the editor */
+export interface ScriptStatement {
+  start: number;
+  end: number;
+  sql: string;
+}
+
+export function splitStatements(sql: string): Promise<ScriptStatement[]> {
+  return tauriInvoke<ScriptStatement[]>('split_statements', { sql });
+}
+
+/** A query waiting for its turn on the connection */
+export interface PendingQuery {
+  id: string;
+  sql: string;
+  queuedAt: string;
+  /** 1 runs next */
+  position: number;
+}
+
+export function listPendingQueries(): Promise<PendingQuery[]> {
+  return tauriInvoke<PendingQuery[]>('list_pending_queries');
+}
+
+/** Take a waiting query out of the queue; its execute call fails as cancelled */
+export function cancelPending(queryId: string): Promise<void> {
+  return tauriInvoke<void>('cancel_pending', { queryId });
+}
+
+/**
+ * Stop the running query by killing its session from the monitoring
+ * connection; the session is reopened, losing SET options and temp tables
+ */
+export function killRunningQuery(): Promise<void> {
+  return tauriInvoke<void>('kill_running_query');
+}
+
+/** The queue after each change, next to run first */
+export function onQueryQueueChanged(handler: (pending: PendingQuery[]) => void): Promise<UnlistenFn> {
+  return listen<PendingQuery[]>('query-queue-changed', (e) => handler(e.payload));
+}
+
+export interface OperatorProgress {
+  nodeId: number;
+  physicalOp: string;
+  rowCount: number;
+  estimateRowCount: number;
+  finished: boolean;
+  percent: number;
+}
+
+/** Progress of a running query, from actual vs estimated rows per operator */
+export interface QueryProgress {
+  queryId: string;
+  percent: number;
+  elapsedMs: number;
+  operators: OperatorProgress[];
+}
+
+/** Emitted about once a second for queries running longer than two seconds */
+export function onQueryProgress(handler: (progress: QueryProgress) => void): Promise<UnlistenFn> {
+  return listen<QueryProgress>('query-progress', (e) => handler(e.payload));
+}
+
+export interface SortKey {
+  column: number;
+  descending?: boolean;
+}
+
+export type FilterOperator =
+  | 'contains'
+  | 'startsWith'
+  | 'equals'
+  | 'notEquals'
+  | 'greaterThan'
+  | 'lessThan'
+  | 'isNull'
+  | 'isNotNull';
+
+export interface ResultFilter {
+  column: number;
+  operator: FilterOperator;
+  value?: string | null;
+}
+
+/** A window of a cached result set after its sort and filters */
+export interface ResultView {
+  resultId: string;
+  totalRows: number;
+  matchingRows: number;
+  offset: number;
+  /** Position of each row in the original result set */
+  rowIndexes: number[];
+  rows: any[][];
+}
+
+/** Replaces the sort of a cached result; [] restores the original order */
+export function sortResults(
+  resultId: string,
+  keys: SortKey[],
+  offset = 0,
+  limit?: number,
+): Promise<ResultView> {
+  return tauriInvoke<ResultView>('sort_results', { resultId, keys, offset, limit });
+}
+
+/** Replaces the filters of a cached result, keeping its sort */
+export function filterResults(
+  resultId: string,
+  filters: ResultFilter[],
+  offset = 0,
+  limit?: number,
+): Promise<ResultView> {
+  return tauriInvoke<ResultView>('filter_results', { resultId, filters, offset, limit });
+}
+
+export type CopyFormat = 'markdown' | 'insert' | 'csv' | 'json';
+
+/**
+ * Rows of a cached result set as clipboard text. `rows` are positions in the
+ * original result set (ResultView.rowIndexes); without them the current
+ * sorted and filtered view is copied.
+ */
+export function copyResults(
+  resultId: string,
+  format: CopyFormat,
+  options: { rows?: number[]; columns?: number[]; tableName?: string } = {},
+): Promise<string> {
+  return tauriInvoke<string>('copy_results', { resultId, format, ...options });
 }
 
 export interface QueryResultTab {
@@ -35,7 +233,7 @@ const state = reactive<ExecutionState>({
 });
 
 export const useQueryExecution = () => {
-  const executeQuery = async (sql: string, planType: PlanType) => {
+  const executeQuery = async (sql: string, planType: PlanType, retry?: RetryPolicy) => {
     state.executing = true;
     const timeout = Number(import.meta.env.VITE_QUERY_TIMEOUT) || 30000;
 
@@ -45,6 +243,8 @@ export const useQueryExecution = () => {
           sql,
           timeoutSeconds: Math.floor(timeout / 1000),
           planType,
+          queryId: crypto.randomUUID(),
+          retry,
         },
       });
 
@@ -10,6 +10,12 @@ export interface QueryHistoryEntry {
   durationMs: number;
   success: boolean;
   error: string | null;
+  sqlHash?: string | null;
+  fingerprint?: string | null;
+  pinned?: boolean;
+  label?: string | null;
+  /** Set for runs of a scheduled query */
+  scheduledQueryId?: string | null;
 }
 
 export interface PlanHistoryEntry {
@@ -20,6 +26,55 @@ export interface PlanHistoryEntry {
   executedAt: string;
   connectionId: string;
   sqlPreview: string;
+  sqlHash?: string | null;
+  fingerprint?: string | null;
+  context?: ExecutionContext | null;
+  regression?: PlanRegression | null;
+  /** Shape of the plan, the key its annotations are stored under */
+  planHash?: string | null;
+  /** Set for plans captured by a scheduled query */
+  scheduledQueryId?: string | null;
+}
+
+export interface PlanRegression {
+  previousPlanId: string;
+  costBefore: number;
+  costAfter: number;
+  costChangePercent: number | null;
+  planHashChanged: boolean;
+  reasons: string[];
+}
+
+// Shape preview of a saved plan; data is SVG markup or base64 PNG
+export interface PlanThumbnail {
+  planId: string;
+  format: 'svg' | 'png';
+  width: number;
+  height: number;
+  data: string;
+  operatorCount: number;
+}
+
+export interface ExecutionContext {
+  capturedAt: string;
+  serverVersion: string;
+  edition: string | null;
+  databaseName: string;
+  compatibilityLevel: number | null;
+  setOptions: string[];
+  cardinalityEstimatorVersion: number | null;
+}
+
+// History entries that differ only in literal values
+export interface QueryHistoryGroup {
+  fingerprint: string;
+  fingerprintSql: string;
+  latestSql: string;
+  runCount: number;
+  failureCount: number;
+  avgDurationMs: number;
+  lastExecutedAt: string;
+  planCount: number;
 }
 
 interface HistoryState {
@@ -36,6 +91,12 @@ const state = reactive<HistoryState>({
   loaded: false,
 });
 
+// Pinned entries do not count towards the limit
+const trimQueries = () => {
+  let unpinned = 0;
+  state.queries = state.queries.filter((q) => q.pinned || ++unpinned <= 100);
+};
+
 export const useQueryHistory = () => {
   const loadHistory = async () => {
     if (state.loaded) return;
@@ -54,9 +115,7 @@ export const useQueryHistory = () => {
 
   const addQueryEntry = async (entry: QueryHistoryEntry) => {
     state.queries.unshift(entry);
-    if (state.queries.length > 100) {
-      state.queries = state.queries.slice(0, 100);
-    }
+    trimQueries();
     try {
       await tauriInvoke('save_query_history_entry', { entry });
     } catch (e) {
@@ -76,6 +135,52 @@ export const useQueryHistory = () => {
     }
   };
 
+  const pinQuery = async (id: string, label: string | null = null) => {
+    try {
+      const pinned = await tauriInvoke<QueryHistoryEntry>('pin_query_history_entry', { id, label });
+      const index = state.queries.findIndex((q) => q.id === id);
+      if (index >= 0) state.queries[index] = pinned;
+    } catch (e) {
+      console.error('Failed to pin query:', e);
+    }
+  };
+
+  const unpinQuery = async (id: string) => {
+    const entry = state.queries.find((q) => q.id === id);
+    if (!entry) return;
+    entry.pinned = false;
+    entry.label = null;
+    trimQueries();
+    try {
+      await tauriInvoke('unpin_query_history_entry', { id });
+    } catch (e) {
+      console.error('Failed to unpin query:', e);
+    }
+  };
+
+  const getHistoryGroups = async (): Promise<QueryHistoryGroup[]> => {
+    try {
+      return await tauriInvoke<QueryHistoryGroup[]>('get_query_history_groups');
+    } catch (e) {
+      console.error('Failed to group query history:', e);
+      return [];
+    }
+  };
+
+  const getPlanThumbnail = async (
+    planId: string,
+    format: 'svg' | 'png' = 'svg',
+  ): Promise<PlanThumbnail | null> => {
+    try {
+      return await tauriInvoke<PlanThumbnail>('render_plan_thumbnail', { planId, format });
+    } catch (e) {
+      console.error('Failed to render plan thumbnail:', e);
+      return null;
+    }
+  };
+
+  const favoriteQueries = computed(() => state.queries.filter((q) => q.pinned));
+
   const filteredQueries = computed(() => {
     if (!state.searchTerm) return state.queries;
     const term = state.searchTerm.toLowerCase();
@@ -95,6 +200,11 @@ export const useQueryHistory = () => {
     loadHistory,
     addQueryEntry,
     addPlanEntry,
+    pinQuery,
+    unpinQuery,
+    getHistoryGroups,
+    getPlanThumbnail,
+    favoriteQueries,
     filteredQueries,
     getPlansForQuery,
     recentPlans,
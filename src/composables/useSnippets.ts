@@ -0,0 +1,86 @@
+import { reactive, computed } from 'vue';
+import { tauriInvoke } from './tauriApi';
+
+export interface SnippetPlaceholder {
+  name: string;
+  default: string | null;
+}
+
+export interface Snippet {
+  id: string;
+  name: string;
+  folder: string | null;
+  sql: string;
+  description: string | null;
+  placeholders: SnippetPlaceholder[];
+  createdAt: string;
+  updatedAt: string;
+}
+
+export interface SaveSnippetRequest {
+  id?: string | null;
+  name: string;
+  folder?: string | null;
+  sql: string;
+  description?: string | null;
+}
+
+interface SnippetState {
+  snippets: Snippet[];
+  loaded: boolean;
+}
+
+const state = reactive<SnippetState>({
+  snippets: [],
+  loaded: false,
+});
+
+export const useSnippets = () => {
+  const loadSnippets = async () => {
+    try {
+      state.snippets = await tauriInvoke<Snippet[]>('list_snippets', { folder: null });
+      state.loaded = true;
+    } catch (e) {
+      console.error('Failed to load snippets:', e);
+    }
+  };
+
+  const saveSnippet = async (request: SaveSnippetRequest) => {
+    const saved = await tauriInvoke<Snippet>('save_snippet', { request });
+    const index = state.snippets.findIndex((s) => s.id === saved.id);
+    if (index >= 0) {
+      state.snippets[index] = saved;
+    } else {
+      state.snippets.push(saved);
+    }
+    return saved;
+  };
+
+  const deleteSnippet = async (id: string) => {
+    await tauriInvoke('delete_snippet', { id });
+    state.snippets = state.snippets.filter((s) => s.id !== id);
+  };
+
+  const renameFolder = async (from: string, to: string) => {
+    await tauriInvoke('rename_snippet_folder', { from, to });
+    await loadSnippets();
+  };
+
+  const renderSnippet = (id: string, values: Record<string, string>) =>
+    tauriInvoke<string>('render_snippet', { id, values });
+
+  const folders = computed(() => {
+    const names = new Set(state.snippets.map((s) => s.folder).filter((f): f is string => !!f));
+    return [...names].sort();
+  });
+
+  return {
+    state,
+    loadSnippets,
+    saveSnippet,
+    deleteSnippet,
+    renameFolder,
+    renderSnippet,
+    folders,
+  };
+};
@@ -0,0 +1,20 @@
+import { tauriInvoke } from './tauriApi';
+
+export interface WorkspaceState {
+  connectionId: string;
+  database?: string | null;
+  /** Most recently used first; maintained by the backend */
+  recentDatabases?: string[];
+  queryText?: string | null;
+  planId?: string | null;
+  updatedAt?: string | null;
+}
+
+export function saveWorkspace(workspace: WorkspaceState): Promise<WorkspaceState> {
+  return tauriInvoke<WorkspaceState>('save_workspace', { workspace });
+}
+
+/** Without a connection id, the workspace used last */
+export function restoreWorkspace(connectionId?: string): Promise<WorkspaceState | null> {
+  return tauriInvoke<WorkspaceState | null>('restore_workspace', { connectionId });
+}
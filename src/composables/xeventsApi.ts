@@ -0,0 +1,72 @@
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { tauriInvoke } from './tauriApi';
+
+export type XeScope =
+  | { kind: 'currentSession' }
+  | { kind: 'database'; name: string };
+
+export type XeSessionStatus = 'created' | 'running' | 'stopped';
+
+export interface XeSessionInfo {
+  name: string;
+  scope: XeScope;
+  status: XeSessionStatus;
+  databaseScoped: boolean;
+  createdAt: string;
+  eventsCaptured: number;
+}
+
+export interface XeEvent {
+  sequence: number;
+  name: string;
+  timestamp: string | null;
+  sessionId: number | null;
+  databaseName: string | null;
+  clientAppName: string | null;
+  username: string | null;
+  durationUs: number | null;
+  cpuTimeUs: number | null;
+  logicalReads: number | null;
+  writes: number | null;
+  rowCount: number | null;
+  result: string | null;
+  sqlText: string | null;
+  errorNumber: number | null;
+  severity: number | null;
+  message: string | null;
+  /** Id of the app query that produced the event */
+  queryId: string | null;
+}
+
+export function xeCreateSession(scope: XeScope): Promise<XeSessionInfo> {
+  return tauriInvoke<XeSessionInfo>('xe_create_session', { scope });
+}
+
+export function xeStartSession(): Promise<XeSessionInfo> {
+  return tauriInvoke<XeSessionInfo>('xe_start_session');
+}
+
+export function xeStopSession(): Promise<XeSessionInfo> {
+  return tauriInvoke<XeSessionInfo>('xe_stop_session');
+}
+
+export function xeDropSession(): Promise<void> {
+  return tauriInvoke<void>('xe_drop_session');
+}
+
+export function xeGetSession(): Promise<XeSessionInfo | null> {
+  return tauriInvoke<XeSessionInfo | null>('xe_get_session');
+}
+
+export function xeGetEvents(afterSequence?: number): Promise<XeEvent[]> {
+  return tauriInvoke<XeEvent[]>('xe_get_events', { afterSequence });
+}
+
+export function xeClearEvents(): Promise<void> {
+  return tauriInvoke<void>('xe_clear_events');
+}
+
+/** Events as the running session captures them */
+export function onXeEventsCaptured(handler: (events: XeEvent[]) => void): Promise<UnlistenFn> {
+  return listen<XeEvent[]>('xevents-captured', (e) => handler(e.payload));
+}